@@ -2,7 +2,7 @@
 
 import { cn } from '@/lib/utils';
 
-type PaymentStatus = 'PENDING' | 'PROCESSING' | 'CONFIRMED' | 'FINALIZED' | 'FAILED' | 'CANCELLED';
+type PaymentStatus = 'PENDING' | 'PROCESSING' | 'CONFIRMED' | 'FINALIZED' | 'FAILED' | 'CANCELLED' | 'REVERTED';
 type PayrollStatus = 'DRAFT' | 'PENDING' | 'PROCESSING' | 'COMPLETED' | 'FAILED' | 'CANCELLED';
 
 const statusStyles: Record<string, { bg: string; text: string; dot: string }> = {
@@ -13,6 +13,7 @@ const statusStyles: Record<string, { bg: string; text: string; dot: string }> =
   FINALIZED: { bg: 'bg-emerald-50', text: 'text-emerald-700', dot: 'bg-emerald-500' },
   FAILED: { bg: 'bg-red-50', text: 'text-red-700', dot: 'bg-red-500' },
   CANCELLED: { bg: 'bg-gray-50', text: 'text-gray-700', dot: 'bg-gray-500' },
+  REVERTED: { bg: 'bg-orange-50', text: 'text-orange-700', dot: 'bg-orange-500' },
   // Payroll statuses
   DRAFT: { bg: 'bg-gray-50', text: 'text-gray-700', dot: 'bg-gray-400' },
   COMPLETED: { bg: 'bg-green-50', text: 'text-green-700', dot: 'bg-green-500' },
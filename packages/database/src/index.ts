@@ -3,6 +3,7 @@ import { PrismaClient } from '@prisma/client';
 // Prevent multiple Prisma Client instances in development
 const globalForPrisma = globalThis as unknown as {
   prisma: PrismaClient | undefined;
+  prismaRead: PrismaClient | undefined;
 };
 
 export const prisma =
@@ -14,8 +15,19 @@ export const prisma =
         : ['error'],
   });
 
+// Read replica for heavy read-only queries, when DATABASE_READ_URL is set.
+// Callers fall back to `prisma` when the replica is unavailable.
+export const prismaRead: PrismaClient | null = process.env.DATABASE_READ_URL
+  ? globalForPrisma.prismaRead ??
+    new PrismaClient({
+      datasources: { db: { url: process.env.DATABASE_READ_URL } },
+      log: process.env.NODE_ENV === 'development' ? ['error', 'warn'] : ['error'],
+    })
+  : null;
+
 if (process.env.NODE_ENV !== 'production') {
   globalForPrisma.prisma = prisma;
+  globalForPrisma.prismaRead = prismaRead ?? undefined;
 }
 
 // Re-export Prisma types for convenience
@@ -0,0 +1,216 @@
+//! Machine-readable error codes returned in `error.code`.
+//!
+//! Codes, HTTP statuses and descriptions are part of the public contract:
+//! add new codes freely, but never rename or repurpose an existing one.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ErrorCode {
+    EncryptionError,
+    DecryptionError,
+    MpcError,
+    RpcError,
+    InvalidInput,
+    Unauthorized,
+    Forbidden,
+    NotFound,
+    RateLimited,
+    WalletValidationFailed,
+    CallbackQuarantined,
+    InternalError,
+    ConfigError,
+    PayloadTooLarge,
+    PayrollRowsInvalid,
+    EndpointDisabled,
+    DeprecatedFormat,
+}
+
+impl ErrorCode {
+    /// Every code, in catalog order
+    pub const ALL: &'static [ErrorCode] = &[
+        ErrorCode::EncryptionError,
+        ErrorCode::DecryptionError,
+        ErrorCode::MpcError,
+        ErrorCode::RpcError,
+        ErrorCode::InvalidInput,
+        ErrorCode::Unauthorized,
+        ErrorCode::Forbidden,
+        ErrorCode::NotFound,
+        ErrorCode::RateLimited,
+        ErrorCode::WalletValidationFailed,
+        ErrorCode::CallbackQuarantined,
+        ErrorCode::InternalError,
+        ErrorCode::ConfigError,
+        ErrorCode::PayloadTooLarge,
+        ErrorCode::PayrollRowsInvalid,
+        ErrorCode::EndpointDisabled,
+        ErrorCode::DeprecatedFormat,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ErrorCode::EncryptionError => "ENCRYPTION_ERROR",
+            ErrorCode::DecryptionError => "DECRYPTION_ERROR",
+            ErrorCode::MpcError => "MPC_ERROR",
+            ErrorCode::RpcError => "RPC_ERROR",
+            ErrorCode::InvalidInput => "INVALID_INPUT",
+            ErrorCode::Unauthorized => "UNAUTHORIZED",
+            ErrorCode::Forbidden => "FORBIDDEN",
+            ErrorCode::NotFound => "NOT_FOUND",
+            ErrorCode::RateLimited => "RATE_LIMITED",
+            ErrorCode::WalletValidationFailed => "WALLET_VALIDATION_FAILED",
+            ErrorCode::CallbackQuarantined => "CALLBACK_QUARANTINED",
+            ErrorCode::InternalError => "INTERNAL_ERROR",
+            ErrorCode::ConfigError => "CONFIG_ERROR",
+            ErrorCode::PayloadTooLarge => "PAYLOAD_TOO_LARGE",
+            ErrorCode::PayrollRowsInvalid => "PAYROLL_ROWS_INVALID",
+            ErrorCode::EndpointDisabled => "ENDPOINT_DISABLED",
+            ErrorCode::DeprecatedFormat => "DEPRECATED_FORMAT",
+        }
+    }
+
+    pub fn http_status(self) -> u16 {
+        match self {
+            ErrorCode::EncryptionError => 500,
+            ErrorCode::DecryptionError => 400,
+            ErrorCode::MpcError => 503,
+            ErrorCode::RpcError => 502,
+            ErrorCode::InvalidInput => 400,
+            ErrorCode::Unauthorized => 401,
+            ErrorCode::Forbidden => 403,
+            ErrorCode::NotFound => 404,
+            ErrorCode::RateLimited => 429,
+            ErrorCode::WalletValidationFailed => 422,
+            ErrorCode::CallbackQuarantined => 422,
+            ErrorCode::InternalError => 500,
+            ErrorCode::ConfigError => 500,
+            ErrorCode::PayloadTooLarge => 413,
+            ErrorCode::PayrollRowsInvalid => 422,
+            ErrorCode::EndpointDisabled => 503,
+            ErrorCode::DeprecatedFormat => 400,
+        }
+    }
+
+    /// Whether repeating the same request later may succeed
+    pub fn retryable(self) -> bool {
+        matches!(
+            self,
+            ErrorCode::MpcError
+                | ErrorCode::RpcError
+                | ErrorCode::RateLimited
+                | ErrorCode::CallbackQuarantined
+                | ErrorCode::InternalError
+                | ErrorCode::EndpointDisabled
+        )
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            ErrorCode::EncryptionError => "The amount could not be encrypted.",
+            ErrorCode::DecryptionError => {
+                "The ciphertext could not be decrypted with the given nonce and public key."
+            }
+            ErrorCode::MpcError => "The MPC cluster is unavailable or rejected the computation.",
+            ErrorCode::RpcError => "A Solana RPC request failed.",
+            ErrorCode::InvalidInput => "The request body or parameters failed validation.",
+            ErrorCode::Unauthorized => "Credentials are missing or invalid.",
+            ErrorCode::Forbidden => "The credentials do not permit this operation.",
+            ErrorCode::NotFound => "The requested resource does not exist.",
+            ErrorCode::RateLimited => "Too many requests; retry after the Retry-After header.",
+            ErrorCode::WalletValidationFailed => {
+                "One or more payroll wallets failed on-chain validation; see wallet_report."
+            }
+            ErrorCode::CallbackQuarantined => {
+                "A signed cluster callback was malformed and has been quarantined for replay."
+            }
+            ErrorCode::InternalError => "An unexpected server error occurred.",
+            ErrorCode::ConfigError => "The service is misconfigured.",
+            ErrorCode::PayloadTooLarge => "The upload exceeds the row or byte limit.",
+            ErrorCode::PayrollRowsInvalid => {
+                "One or more payroll rows failed validation; see errors. Nothing was queued."
+            }
+            ErrorCode::EndpointDisabled => {
+                "An operator has temporarily disabled this endpoint; other endpoints are unaffected."
+            }
+            ErrorCode::DeprecatedFormat => {
+                "The API key is in strict mode and the request uses a deprecated format; the message names the field and its replacement."
+            }
+        }
+    }
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// One row of the published error catalog
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct ErrorCatalogEntry {
+    pub code: ErrorCode,
+    pub http_status: u16,
+    pub retryable: bool,
+    pub description: String,
+}
+
+/// The full catalog, in `ErrorCode::ALL` order
+pub fn catalog() -> Vec<ErrorCatalogEntry> {
+    ErrorCode::ALL
+        .iter()
+        .map(|&code| ErrorCatalogEntry {
+            code,
+            http_status: code.http_status(),
+            retryable: code.retryable(),
+            description: code.description().to_string(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_serialized_code_matches_as_str() {
+        for &code in ErrorCode::ALL {
+            let json = serde_json::to_value(code).unwrap();
+            assert_eq!(json, code.as_str());
+        }
+    }
+
+    #[test]
+    fn test_all_lists_each_code_once() {
+        let mut seen = std::collections::HashSet::new();
+        for &code in ErrorCode::ALL {
+            assert!(seen.insert(code), "{} listed twice", code);
+        }
+        // Adding a variant without listing it here fails to compile
+        for &code in ErrorCode::ALL {
+            match code {
+                ErrorCode::EncryptionError
+                | ErrorCode::DecryptionError
+                | ErrorCode::MpcError
+                | ErrorCode::RpcError
+                | ErrorCode::InvalidInput
+                | ErrorCode::Unauthorized
+                | ErrorCode::Forbidden
+                | ErrorCode::NotFound
+                | ErrorCode::RateLimited
+                | ErrorCode::WalletValidationFailed
+                | ErrorCode::CallbackQuarantined
+                | ErrorCode::InternalError
+                | ErrorCode::ConfigError
+                | ErrorCode::PayloadTooLarge
+                | ErrorCode::PayrollRowsInvalid
+                | ErrorCode::EndpointDisabled
+                | ErrorCode::DeprecatedFormat => {}
+            }
+        }
+        assert_eq!(seen.len(), 17);
+    }
+}
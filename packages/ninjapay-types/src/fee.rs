@@ -0,0 +1,89 @@
+//! Protocol fee math.
+//!
+//! Mirrors `process_payment` in programs/ninjapay-vault: the fee is
+//! `amount * fee_basis_points / 10_000` in base units, rounded down unless
+//! the vault has `round_fee_up` set, and the merchant receives the rest. Every off-chain fee computation goes through
+//! here so a rounding difference from the program can only live in one place.
+
+use std::ops::RangeInclusive;
+
+pub const BASIS_POINTS_DENOMINATOR: u64 = 10_000;
+
+/// The program rejects `update_fee` above this (10%)
+pub const MAX_FEE_BASIS_POINTS: u16 = 1_000;
+
+/// Fee charged on `amount` base units
+pub fn protocol_fee(amount: u64, fee_basis_points: u16) -> u64 {
+    (amount as u128 * fee_basis_points as u128 / BASIS_POINTS_DENOMINATOR as u128) as u64
+}
+
+/// What the merchant receives after the fee
+pub fn net_amount(amount: u64, fee_basis_points: u16) -> u64 {
+    amount - protocol_fee(amount, fee_basis_points)
+}
+
+/// Every fee setting that charges exactly `fee` on `amount`, or `None` if no
+/// valid setting does. Used to tell a fee change apart from a rounding bug.
+pub fn implied_basis_points(amount: u64, fee: u64) -> Option<RangeInclusive<u16>> {
+    let max = MAX_FEE_BASIS_POINTS as u128;
+    if amount == 0 {
+        return (fee == 0).then_some(0..=MAX_FEE_BASIS_POINTS);
+    }
+
+    // floor(amount * bps / D) == fee  <=>  fee * D <= amount * bps < (fee + 1) * D
+    let (amount, fee, d) = (amount as u128, fee as u128, BASIS_POINTS_DENOMINATOR as u128);
+    let low = (fee * d).div_ceil(amount);
+    let high = ((fee + 1) * d - 1) / amount;
+    if low > high.min(max) {
+        return None;
+    }
+    Some(low as u16..=high.min(max) as u16)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Deserialize)]
+    struct Vector {
+        amount: String,
+        fee_basis_points: u16,
+        fee: String,
+    }
+
+    fn vectors() -> Vec<Vector> {
+        let path = format!("{}/tests/fixtures/fee_vectors.json", env!("CARGO_MANIFEST_DIR"));
+        let contents = std::fs::read_to_string(path).expect("missing fixture");
+        let fixture: serde_json::Value = serde_json::from_str(&contents).unwrap();
+        serde_json::from_value(fixture["vectors"].clone()).unwrap()
+    }
+
+    #[test]
+    fn test_fee_vectors() {
+        for v in vectors() {
+            let amount: u64 = v.amount.parse().unwrap();
+            let fee: u64 = v.fee.parse().unwrap();
+            assert_eq!(protocol_fee(amount, v.fee_basis_points), fee, "{} @ {}", amount, v.fee_basis_points);
+            assert_eq!(net_amount(amount, v.fee_basis_points), amount - fee);
+            assert!(implied_basis_points(amount, fee).unwrap().contains(&v.fee_basis_points));
+        }
+    }
+
+    #[test]
+    fn test_implied_basis_points() {
+        // 1.999999 USDC: 50 bps gives 9999.995, rounded down
+        assert_eq!(protocol_fee(1_999_999, 50), 9_999);
+        assert_eq!(implied_basis_points(1_999_999, 9_999), Some(50..=50));
+        // A fee one unit higher needs a fee setting between two integers
+        assert_eq!(implied_basis_points(1_999_999, 10_000), None);
+
+        // Small amounts round every low setting down to zero
+        assert_eq!(implied_basis_points(150, 0), Some(0..=66));
+        assert_eq!(implied_basis_points(0, 0), Some(0..=MAX_FEE_BASIS_POINTS));
+        assert_eq!(implied_basis_points(0, 1), None);
+
+        // Above the 10% cap
+        assert_eq!(implied_basis_points(10_000, 1_001), None);
+    }
+}
@@ -0,0 +1,349 @@
+//! Locale-aware display and entry of token amounts.
+//!
+//! Amounts are integers in base units; `decimals` places the decimal point
+//! (6 for USDC). Formatting never rounds: trailing fraction zeros are
+//! trimmed down to two places, so `parse_amount(format_amount(x))` gives
+//! back `x` in every locale.
+//!
+//! ```
+//! use ninjapay_types::format::{format_amount, parse_amount, Locale};
+//!
+//! assert_eq!(format_amount(1_234_560_000, 6, Locale::DeDe), "1.234,56");
+//! assert_eq!(parse_amount("1,234.56", 6, Locale::EnUs), Ok(1_234_560_000));
+//! ```
+//!
+//! Parsing is strict about separators. A group separator must split the
+//! integer part into groups of three, so "1.5" in de-DE, where `.` groups
+//! thousands, is rejected as ambiguous rather than read as 15.
+
+use std::fmt;
+use std::str::FromStr;
+
+/// Fraction digits always shown, when the currency has that many
+const MIN_FRACTION_DIGITS: usize = 2;
+
+/// Locales with their own number formatting
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Locale {
+    EnUs,
+    EnGb,
+    DeDe,
+    DeCh,
+    FrFr,
+    EsEs,
+    ItIt,
+    PtBr,
+    JaJp,
+}
+
+impl Locale {
+    pub const ALL: &'static [Locale] = &[
+        Locale::EnUs,
+        Locale::EnGb,
+        Locale::DeDe,
+        Locale::DeCh,
+        Locale::FrFr,
+        Locale::EsEs,
+        Locale::ItIt,
+        Locale::PtBr,
+        Locale::JaJp,
+    ];
+
+    /// BCP 47 tag, e.g. `de-DE`
+    pub fn tag(self) -> &'static str {
+        match self {
+            Locale::EnUs => "en-US",
+            Locale::EnGb => "en-GB",
+            Locale::DeDe => "de-DE",
+            Locale::DeCh => "de-CH",
+            Locale::FrFr => "fr-FR",
+            Locale::EsEs => "es-ES",
+            Locale::ItIt => "it-IT",
+            Locale::PtBr => "pt-BR",
+            Locale::JaJp => "ja-JP",
+        }
+    }
+
+    pub fn decimal_separator(self) -> char {
+        match self {
+            Locale::EnUs | Locale::EnGb | Locale::DeCh | Locale::JaJp => '.',
+            Locale::DeDe | Locale::FrFr | Locale::EsEs | Locale::ItIt | Locale::PtBr => ',',
+        }
+    }
+
+    /// Thousands separator used when formatting
+    pub fn group_separator(self) -> char {
+        match self {
+            Locale::EnUs | Locale::EnGb | Locale::JaJp => ',',
+            Locale::DeDe | Locale::EsEs | Locale::ItIt | Locale::PtBr => '.',
+            Locale::DeCh => '\u{2019}',
+            Locale::FrFr => '\u{202f}',
+        }
+    }
+
+    /// Also accepted as a thousands separator, since keyboards can't easily
+    /// type the typographic ones
+    fn is_group_separator(self, c: char) -> bool {
+        c == self.group_separator()
+            || match self {
+                Locale::DeCh => c == '\'',
+                Locale::FrFr => c == ' ' || c == '\u{a0}',
+                _ => false,
+            }
+    }
+}
+
+impl fmt::Display for Locale {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.tag())
+    }
+}
+
+/// Unsupported locale tag
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownLocale(pub String);
+
+impl fmt::Display for UnknownLocale {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unsupported locale: {}", self.0)
+    }
+}
+
+impl std::error::Error for UnknownLocale {}
+
+impl FromStr for Locale {
+    type Err = UnknownLocale;
+
+    /// Case-insensitive, with `-` or `_`: `pt-BR`, `pt_br`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let tag = s.trim().replace('_', "-");
+        Locale::ALL
+            .iter()
+            .copied()
+            .find(|locale| locale.tag().eq_ignore_ascii_case(&tag))
+            .ok_or_else(|| UnknownLocale(s.to_string()))
+    }
+}
+
+/// Decimals of the currencies we settle in
+pub fn currency_decimals(currency: &str) -> Option<u8> {
+    match currency.to_ascii_uppercase().as_str() {
+        "USDC" => Some(6),
+        "SOL" => Some(9),
+        _ => None,
+    }
+}
+
+/// `base_units` with `decimals` fraction digits, grouped and punctuated for
+/// `locale`
+pub fn format_amount(base_units: u64, decimals: u8, locale: Locale) -> String {
+    let digits = format!("{:0>width$}", base_units, width = decimals as usize + 1);
+    let (whole, fraction) = digits.split_at(digits.len() - decimals as usize);
+
+    let mut out = String::with_capacity(digits.len() + digits.len() / 3 + 1);
+    for (i, c) in whole.chars().enumerate() {
+        if i > 0 && (whole.len() - i) % 3 == 0 {
+            out.push(locale.group_separator());
+        }
+        out.push(c);
+    }
+
+    let keep = fraction.trim_end_matches('0').len().max(MIN_FRACTION_DIGITS.min(fraction.len()));
+    if keep > 0 {
+        out.push(locale.decimal_separator());
+        out.push_str(&fraction[..keep]);
+    }
+    out
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseAmountError {
+    Empty,
+    /// Neither a digit nor one of the locale's separators
+    InvalidCharacter(char),
+    /// Separators in an order or spacing the locale can't produce, e.g.
+    /// "1.5" in de-DE or "1,234.5" in fr-FR
+    Ambiguous,
+    /// More fraction digits than the currency has
+    TooPrecise { decimals: u8 },
+    Overflow,
+}
+
+impl fmt::Display for ParseAmountError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseAmountError::Empty => f.write_str("amount is empty"),
+            ParseAmountError::InvalidCharacter(c) => write!(f, "unexpected character {:?} in amount", c),
+            ParseAmountError::Ambiguous => f.write_str("amount's separators don't match the locale"),
+            ParseAmountError::TooPrecise { decimals } => {
+                write!(f, "amount has more than {} decimal places", decimals)
+            }
+            ParseAmountError::Overflow => f.write_str("amount is too large"),
+        }
+    }
+}
+
+impl std::error::Error for ParseAmountError {}
+
+/// Base units of a user-entered amount in `locale`. Grouping is optional,
+/// but where used it must be in threes.
+pub fn parse_amount(input: &str, decimals: u8, locale: Locale) -> Result<u64, ParseAmountError> {
+    let input = input.trim();
+    if input.is_empty() {
+        return Err(ParseAmountError::Empty);
+    }
+
+    let (whole, fraction) = match input.split_once(locale.decimal_separator()) {
+        Some((whole, fraction)) => (whole, Some(fraction)),
+        None => (input, None),
+    };
+
+    let mut groups = Vec::new();
+    let mut group = String::new();
+    for c in whole.chars() {
+        if c.is_ascii_digit() {
+            group.push(c);
+        } else if locale.is_group_separator(c) {
+            groups.push(std::mem::take(&mut group));
+        } else {
+            return Err(unexpected(c, locale));
+        }
+    }
+    groups.push(group);
+    let grouped = groups.len() > 1;
+    if grouped && (groups[0].is_empty() || groups[0].len() > 3 || groups[1..].iter().any(|g| g.len() != 3)) {
+        return Err(ParseAmountError::Ambiguous);
+    }
+
+    let fraction = fraction.unwrap_or("");
+    if let Some(c) = fraction.chars().find(|c| !c.is_ascii_digit()) {
+        return Err(unexpected(c, locale));
+    }
+    let whole = groups.concat();
+    if whole.is_empty() && fraction.is_empty() {
+        return Err(ParseAmountError::Empty);
+    }
+    if fraction.len() > decimals as usize {
+        return Err(ParseAmountError::TooPrecise { decimals });
+    }
+
+    let digits = format!("{}{:0<width$}", whole, fraction, width = decimals as usize);
+    let digits = digits.trim_start_matches('0');
+    if digits.is_empty() {
+        return Ok(0);
+    }
+    digits.parse().map_err(|_| ParseAmountError::Overflow)
+}
+
+/// A second decimal separator, or a separator from another locale, reads as
+/// ambiguous; anything else is just invalid
+fn unexpected(c: char, locale: Locale) -> ParseAmountError {
+    let is_separator = c == locale.decimal_separator()
+        || Locale::ALL
+            .iter()
+            .any(|other| other.is_group_separator(c) || other.decimal_separator() == c);
+    if is_separator {
+        ParseAmountError::Ambiguous
+    } else {
+        ParseAmountError::InvalidCharacter(c)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    #[test]
+    fn test_format_amount() {
+        let cases = [
+            (Locale::EnUs, "1,234.56"),
+            (Locale::EnGb, "1,234.56"),
+            (Locale::DeDe, "1.234,56"),
+            (Locale::DeCh, "1\u{2019}234.56"),
+            (Locale::FrFr, "1\u{202f}234,56"),
+            (Locale::PtBr, "1.234,56"),
+            (Locale::JaJp, "1,234.56"),
+        ];
+        for (locale, expected) in cases {
+            assert_eq!(format_amount(1_234_560_000, 6, locale), expected, "{}", locale);
+        }
+
+        assert_eq!(format_amount(0, 6, Locale::EnUs), "0.00");
+        assert_eq!(format_amount(1, 6, Locale::EnUs), "0.000001");
+        assert_eq!(format_amount(1_500_000, 6, Locale::DeDe), "1,50");
+        assert_eq!(format_amount(999_999_999, 0, Locale::EnUs), "999,999,999");
+        assert_eq!(format_amount(12_345, 1, Locale::EnUs), "1,234.5");
+        assert_eq!(format_amount(u64::MAX, 9, Locale::EnUs), "18,446,744,073.709551615");
+    }
+
+    #[test]
+    fn test_parse_amount() {
+        assert_eq!(parse_amount("1.234,56", 6, Locale::DeDe), Ok(1_234_560_000));
+        assert_eq!(parse_amount("1234,56", 6, Locale::DeDe), Ok(1_234_560_000));
+        assert_eq!(parse_amount(" 1,234 ", 6, Locale::EnUs), Ok(1_234_000_000));
+        assert_eq!(parse_amount("1,234", 6, Locale::DeDe), Ok(1_234_000));
+        assert_eq!(parse_amount("1 234,5", 6, Locale::FrFr), Ok(1_234_500_000));
+        assert_eq!(parse_amount("1'234.5", 6, Locale::DeCh), Ok(1_234_500_000));
+        assert_eq!(parse_amount(".5", 6, Locale::EnUs), Ok(500_000));
+        assert_eq!(parse_amount("007", 0, Locale::EnUs), Ok(7));
+        assert_eq!(parse_amount("18446744073709551615", 0, Locale::EnUs), Ok(u64::MAX));
+    }
+
+    #[test]
+    fn test_parse_amount_rejects_ambiguous_and_invalid_input() {
+        use ParseAmountError::*;
+
+        // Separators only make sense one way round
+        assert_eq!(parse_amount("1.5", 6, Locale::DeDe), Err(Ambiguous));
+        assert_eq!(parse_amount("1,5", 6, Locale::EnUs), Err(Ambiguous));
+        assert_eq!(parse_amount("1.234,56", 6, Locale::EnUs), Err(Ambiguous));
+        assert_eq!(parse_amount("1,234.56", 6, Locale::FrFr), Err(Ambiguous));
+        assert_eq!(parse_amount("1.2.3", 6, Locale::EnUs), Err(Ambiguous));
+        assert_eq!(parse_amount("1234,567", 6, Locale::EnUs), Err(Ambiguous));
+        assert_eq!(parse_amount(",234", 6, Locale::EnUs), Err(Ambiguous));
+
+        assert_eq!(parse_amount("", 6, Locale::EnUs), Err(Empty));
+        assert_eq!(parse_amount(".", 6, Locale::EnUs), Err(Empty));
+        assert_eq!(parse_amount("-1", 6, Locale::EnUs), Err(InvalidCharacter('-')));
+        assert_eq!(parse_amount("1e6", 6, Locale::EnUs), Err(InvalidCharacter('e')));
+        assert_eq!(parse_amount("0.0000001", 6, Locale::EnUs), Err(TooPrecise { decimals: 6 }));
+        assert_eq!(parse_amount("18446744073709551616", 0, Locale::EnUs), Err(Overflow));
+    }
+
+    #[test]
+    fn test_locale_tags() {
+        assert_eq!("de-DE".parse(), Ok(Locale::DeDe));
+        assert_eq!("pt_br".parse(), Ok(Locale::PtBr));
+        assert!("en".parse::<Locale>().is_err());
+        for locale in Locale::ALL {
+            assert_eq!(locale.tag().parse(), Ok(*locale));
+            assert_ne!(locale.decimal_separator(), locale.group_separator());
+        }
+        assert_eq!(currency_decimals("usdc"), Some(6));
+        assert_eq!(currency_decimals("EUR"), None);
+    }
+
+    proptest! {
+        #[test]
+        fn prop_parse_inverts_format(
+            amount in any::<u64>(),
+            decimals in 0u8..=9,
+            locale in prop::sample::select(Locale::ALL),
+        ) {
+            let formatted = format_amount(amount, decimals, locale);
+            prop_assert_eq!(parse_amount(&formatted, decimals, locale), Ok(amount), "{}", formatted);
+        }
+
+        #[test]
+        fn prop_ungrouped_input_parses_everywhere(
+            amount in any::<u64>(),
+            decimals in 0u8..=9,
+            locale in prop::sample::select(Locale::ALL),
+        ) {
+            let formatted = format_amount(amount, decimals, locale);
+            let plain: String = formatted.chars().filter(|c| !locale.is_group_separator(*c)).collect();
+            prop_assert_eq!(parse_amount(&plain, decimals, locale), Ok(amount));
+        }
+    }
+}
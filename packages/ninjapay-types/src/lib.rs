@@ -0,0 +1,8 @@
+//! Shared wire types and serialization helpers for NinjaPay services.
+
+pub mod error_code;
+pub mod fee;
+pub mod format;
+pub mod string_u64;
+
+pub use error_code::ErrorCode;
@@ -0,0 +1,100 @@
+//! Serialize `u64` values as decimal strings.
+//!
+//! JavaScript clients lose precision above 2^53, so v2 responses encode
+//! money fields as strings. Deserialization accepts both strings and JSON
+//! numbers so legacy v1 payloads keep working.
+//!
+//! ```
+//! use serde::{Deserialize, Serialize};
+//!
+//! #[derive(Serialize, Deserialize)]
+//! struct Amount {
+//!     #[serde(with = "ninjapay_types::string_u64")]
+//!     amount: u64,
+//! }
+//!
+//! let json = serde_json::to_string(&Amount { amount: 1_000_000 }).unwrap();
+//! assert_eq!(json, r#"{"amount":"1000000"}"#);
+//! ```
+
+use serde::de::{self, Visitor};
+use serde::{Deserializer, Serializer};
+use std::fmt;
+
+pub fn serialize<S>(value: &u64, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.collect_str(value)
+}
+
+pub fn deserialize<'de, D>(deserializer: D) -> Result<u64, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    deserializer.deserialize_any(U64Visitor)
+}
+
+struct U64Visitor;
+
+impl<'de> Visitor<'de> for U64Visitor {
+    type Value = u64;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("an unsigned 64-bit integer or a decimal string")
+    }
+
+    fn visit_u64<E: de::Error>(self, value: u64) -> Result<u64, E> {
+        Ok(value)
+    }
+
+    fn visit_i64<E: de::Error>(self, value: i64) -> Result<u64, E> {
+        u64::try_from(value).map_err(|_| E::invalid_value(de::Unexpected::Signed(value), &self))
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<u64, E> {
+        if value.is_empty() || !value.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(E::invalid_value(de::Unexpected::Str(value), &self));
+        }
+        value
+            .parse()
+            .map_err(|_| E::invalid_value(de::Unexpected::Str(value), &self))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Amount {
+        #[serde(with = "crate::string_u64")]
+        amount: u64,
+    }
+
+    #[test]
+    fn test_serializes_as_string() {
+        let json = serde_json::to_string(&Amount { amount: u64::MAX }).unwrap();
+        assert_eq!(json, r#"{"amount":"18446744073709551615"}"#);
+    }
+
+    #[test]
+    fn test_deserializes_string_and_number() {
+        let from_str: Amount = serde_json::from_str(r#"{"amount":"1000000"}"#).unwrap();
+        let from_num: Amount = serde_json::from_str(r#"{"amount":1000000}"#).unwrap();
+        assert_eq!(from_str, from_num);
+    }
+
+    #[test]
+    fn test_rejects_invalid_values() {
+        for json in [
+            r#"{"amount":"-1"}"#,
+            r#"{"amount":"1.5"}"#,
+            r#"{"amount":""}"#,
+            r#"{"amount":"18446744073709551616"}"#,
+            r#"{"amount":-1}"#,
+        ] {
+            assert!(serde_json::from_str::<Amount>(json).is_err(), "{}", json);
+        }
+    }
+}
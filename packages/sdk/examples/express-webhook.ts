@@ -0,0 +1,65 @@
+/**
+ * Express webhook consumer with signature verification and deduplication.
+ *
+ *   NINJAPAY_WEBHOOK_SECRET=... REDIS_URL=redis://localhost:6379 tsx examples/express-webhook.ts
+ */
+
+import express from 'express';
+import Redis from 'ioredis';
+import {
+  IdempotencyGuard,
+  IdempotencyStore,
+  WebhookVerificationError,
+  WebhookVerifier,
+} from '@ninjapay/sdk';
+
+// Shared across instances, so a redelivery to another replica is still deduplicated
+class RedisIdempotencyStore implements IdempotencyStore {
+  constructor(private redis: Redis) {}
+
+  async claim(eventId: string, ttlMs: number): Promise<boolean> {
+    return (await this.redis.set(`ninjapay:webhook:${eventId}`, '1', 'PX', ttlMs, 'NX')) === 'OK';
+  }
+
+  async release(eventId: string): Promise<void> {
+    await this.redis.del(`ninjapay:webhook:${eventId}`);
+  }
+}
+
+const verifier = new WebhookVerifier(process.env.NINJAPAY_WEBHOOK_SECRET!);
+const guard = new IdempotencyGuard(new RedisIdempotencyStore(new Redis(process.env.REDIS_URL!)));
+
+const app = express();
+
+// The signature covers the raw body, so don't let express.json() parse it first
+app.post('/webhooks/ninjapay', express.raw({ type: 'application/json' }), async (req, res) => {
+  let event;
+  try {
+    event = verifier.verify(req.body, req.headers);
+  } catch (error) {
+    if (error instanceof WebhookVerificationError) {
+      return res.status(400).send(error.code);
+    }
+    throw error;
+  }
+
+  try {
+    await guard.handle(event, async () => {
+      switch (event.type) {
+        case 'payment_link.payment_completed':
+          console.log('Paid', event.data.payment_intent_id, event.data.tx_signature);
+          break;
+        case 'payment_intent.failed':
+          console.log('Failed', event.data.payment_intent_id);
+          break;
+      }
+    });
+    // Duplicates are acknowledged too, so they stop being retried
+    res.sendStatus(200);
+  } catch {
+    // The claim was released; a non-2xx response makes NinjaPay retry
+    res.sendStatus(500);
+  }
+});
+
+app.listen(3000);
@@ -0,0 +1,119 @@
+/**
+ * Merchant sealed envelopes (Node.js only)
+ *
+ * When a payment amount is encrypted with `merchant_x25519_pubkey`, the
+ * response carries a `merchant_envelope` that only the holder of the matching
+ * X25519 secret key can open, entirely offline:
+ *
+ *   shared     = X25519(merchant_secret, ephemeral_public)
+ *   key        = HKDF-SHA256(ikm = shared, salt = ephemeral_public || merchant_public,
+ *                            info = "ninjapay-merchant-envelope-v1", length = 32)
+ *   amount     = u64 little-endian of ChaCha20-Poly1305-open(key, nonce, ciphertext)
+ *
+ * Test vectors for other languages are published in the arcium-service
+ * repository at tests/fixtures/merchant_envelope_vectors.json.
+ */
+
+import {
+  createDecipheriv,
+  createPrivateKey,
+  createPublicKey,
+  diffieHellman,
+  generateKeyPairSync,
+  hkdfSync,
+  KeyObject,
+} from 'node:crypto';
+
+export interface MerchantEnvelope {
+  version: number;
+  /** Hex */
+  ephemeral_public_key: string;
+  /** Hex */
+  nonce: string;
+  /** Base64, including the 16-byte Poly1305 tag */
+  ciphertext: string;
+}
+
+export interface MerchantKeyPair {
+  /** Hex; pass as `merchant_x25519_pubkey` */
+  publicKey: string;
+  /** Hex; keep this on the merchant backend only */
+  secretKey: string;
+}
+
+const HKDF_INFO = 'ninjapay-merchant-envelope-v1';
+const TAG_LENGTH = 16;
+
+// DER prefixes wrapping a raw 32-byte X25519 key
+const PKCS8_PREFIX = Buffer.from('302e020100300506032b656e04220420', 'hex');
+const SPKI_PREFIX = Buffer.from('302a300506032b656e032100', 'hex');
+
+function rawKey(hex: string, name: string): Buffer {
+  const key = Buffer.from(hex, 'hex');
+  if (key.length !== 32 || key.toString('hex') !== hex.toLowerCase()) {
+    throw new Error(`${name} must be a 32-byte hex string`);
+  }
+  return key;
+}
+
+function privateKeyObject(secret: Buffer): KeyObject {
+  return createPrivateKey({ key: Buffer.concat([PKCS8_PREFIX, secret]), format: 'der', type: 'pkcs8' });
+}
+
+function publicKeyObject(pub: Buffer): KeyObject {
+  return createPublicKey({ key: Buffer.concat([SPKI_PREFIX, pub]), format: 'der', type: 'spki' });
+}
+
+function rawPublicKey(key: KeyObject): Buffer {
+  return (key.export({ format: 'der', type: 'spki' }) as Buffer).subarray(SPKI_PREFIX.length);
+}
+
+/**
+ * Generate an X25519 key pair for receiving sealed envelopes
+ */
+export function generateMerchantKeyPair(): MerchantKeyPair {
+  const { publicKey, privateKey } = generateKeyPairSync('x25519');
+  const secret = (privateKey.export({ format: 'der', type: 'pkcs8' }) as Buffer).subarray(
+    PKCS8_PREFIX.length
+  );
+  return {
+    publicKey: rawPublicKey(publicKey).toString('hex'),
+    secretKey: secret.toString('hex'),
+  };
+}
+
+/**
+ * Open a merchant envelope with the merchant's X25519 secret key (hex).
+ * Returns the amount in base units; throws if the envelope was not sealed
+ * to this key or was tampered with.
+ */
+export function openMerchantEnvelope(envelope: MerchantEnvelope, merchantSecretKey: string): bigint {
+  if (envelope.version !== 1) {
+    throw new Error(`Unsupported envelope version: ${envelope.version}`);
+  }
+
+  const merchantSecret = privateKeyObject(rawKey(merchantSecretKey, 'merchantSecretKey'));
+  const merchantPublic = rawPublicKey(createPublicKey(merchantSecret));
+  const ephemeralPublic = rawKey(envelope.ephemeral_public_key, 'ephemeral_public_key');
+
+  const shared = diffieHellman({
+    privateKey: merchantSecret,
+    publicKey: publicKeyObject(ephemeralPublic),
+  });
+  const key = Buffer.from(
+    hkdfSync('sha256', shared, Buffer.concat([ephemeralPublic, merchantPublic]), HKDF_INFO, 32)
+  );
+
+  const sealed = Buffer.from(envelope.ciphertext, 'base64');
+  if (sealed.length !== 8 + TAG_LENGTH) {
+    throw new Error('Malformed envelope ciphertext');
+  }
+
+  const decipher = createDecipheriv('chacha20-poly1305', key, Buffer.from(envelope.nonce, 'hex'), {
+    authTagLength: TAG_LENGTH,
+  });
+  decipher.setAuthTag(sealed.subarray(8));
+  const plaintext = Buffer.concat([decipher.update(sealed.subarray(0, 8)), decipher.final()]);
+
+  return plaintext.readBigUInt64LE(0);
+}
@@ -18,6 +18,7 @@ export interface PaymentIntent {
   currency: string;
   status: string;
   description?: string;
+  memo?: string | null;
   tx_signature?: string;
   computation_id?: string;
   computation_status?: string;
@@ -203,6 +204,8 @@ class PaymentIntentsResource {
     recipient: string;
     currency?: string;
     description?: string;
+    /** SPL Memo for the payment transaction; UTF-8, max 256 bytes, no control characters */
+    memo?: string;
     metadata?: Record<string, any>;
   }): Promise<SingleResponse<PaymentIntent>> {
     return this.client.request({
@@ -527,5 +530,27 @@ class ApiKeysResource {
 // Export error class
 export { NinjaPayError };
 
+// Sealed envelope helpers (Node.js only)
+export { openMerchantEnvelope, generateMerchantKeyPair } from './envelope.js';
+export type { MerchantEnvelope, MerchantKeyPair } from './envelope.js';
+
 // Default export
 export default NinjaPay;
+
+// Webhook verification and deduplication (Node.js only)
+export {
+  WebhookVerifier,
+  WebhookVerificationError,
+  IdempotencyGuard,
+  InMemoryIdempotencyStore,
+  WEBHOOK_ID_HEADER,
+  WEBHOOK_TIMESTAMP_HEADER,
+  WEBHOOK_SIGNATURE_HEADER,
+} from './webhooks.js';
+export type {
+  WebhookEvent,
+  WebhookEventType,
+  IdempotencyStore,
+  PaymentIntentEventData,
+  PaymentLinkPaymentCompletedData,
+} from './webhooks.js';
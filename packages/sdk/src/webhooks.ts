@@ -0,0 +1,227 @@
+/**
+ * Webhook verification and idempotent handling (Node.js only)
+ *
+ * Every delivery is signed:
+ *
+ *   X-Webhook-ID:        evt_...
+ *   X-Webhook-Timestamp: <unix ms>
+ *   X-Webhook-Signature: sha256=<hex HMAC-SHA256(secret, "<timestamp>.<raw body>")>
+ *
+ * After a secret rotation, deliveries are signed with both the new and the
+ * previous secret until the previous one expires, and the header carries
+ * both signatures comma separated. A delivery verifies if any of them
+ * matches, so either secret works during the overlap.
+ *
+ * Verify against the raw request body, before any JSON parsing. Test vectors
+ * shared with the API's signer are in test-vectors/webhook-signatures.json.
+ */
+
+import { createHmac, timingSafeEqual } from 'node:crypto';
+
+export const WEBHOOK_ID_HEADER = 'x-webhook-id';
+export const WEBHOOK_TIMESTAMP_HEADER = 'x-webhook-timestamp';
+export const WEBHOOK_SIGNATURE_HEADER = 'x-webhook-signature';
+
+const DEFAULT_TOLERANCE_MS = 5 * 60 * 1000;
+
+// ============ Events ============
+
+export interface PaymentIntentEventData {
+  payment_intent_id: string;
+  status: string;
+  tx_signature?: string | null;
+  timestamp?: number;
+}
+
+export interface PaymentLinkPaymentCompletedData {
+  payment_intent_id: string;
+  payment_link_id: string;
+  tx_signature: string;
+  memo?: string | null;
+  amount?: number;
+  currency: string;
+  payer_wallet?: string;
+}
+
+interface EventEnvelope<Type extends string, Data> {
+  id: string;
+  type: Type;
+  /** Unix ms */
+  created: number;
+  livemode: boolean;
+  data: Data;
+}
+
+export type WebhookEvent =
+  | EventEnvelope<'payment_intent.created', Record<string, any>>
+  | EventEnvelope<'payment_intent.confirmed', PaymentIntentEventData>
+  | EventEnvelope<'payment_intent.failed', PaymentIntentEventData>
+  | EventEnvelope<'payment_intent.cancelled', Record<string, any>>
+  | EventEnvelope<'payment_link.payment_completed', PaymentLinkPaymentCompletedData>;
+
+export type WebhookEventType = WebhookEvent['type'];
+
+export class WebhookVerificationError extends Error {
+  code: 'MISSING_HEADER' | 'TIMESTAMP_OUT_OF_TOLERANCE' | 'INVALID_SIGNATURE' | 'INVALID_PAYLOAD';
+
+  constructor(message: string, code: WebhookVerificationError['code']) {
+    super(message);
+    this.name = 'WebhookVerificationError';
+    this.code = code;
+  }
+}
+
+// ============ Verifier ============
+
+type Headers = Record<string, string | string[] | undefined>;
+
+function header(headers: Headers, name: string): string | undefined {
+  const key = Object.keys(headers).find((k) => k.toLowerCase() === name);
+  const value = key === undefined ? undefined : headers[key];
+  return Array.isArray(value) ? value[0] : value;
+}
+
+/**
+ * Verifies webhook signatures and parses the event envelope.
+ *
+ * Only the native `ninjapay` body format is parsed; subscriptions using
+ * `stripe_compatible` or `jq:` formats can still call `verifySignature`.
+ */
+export class WebhookVerifier {
+  private toleranceMs: number;
+
+  constructor(
+    private secret: string,
+    options: { toleranceMs?: number } = {}
+  ) {
+    this.toleranceMs = options.toleranceMs ?? DEFAULT_TOLERANCE_MS;
+  }
+
+  /**
+   * Verify a delivery and return the typed event.
+   * Throws WebhookVerificationError if it was not signed with this secret,
+   * is outside the timestamp tolerance, or is not a ninjapay event.
+   */
+  verify(rawBody: string | Buffer, headers: Headers, now: number = Date.now()): WebhookEvent {
+    const body = typeof rawBody === 'string' ? rawBody : rawBody.toString('utf8');
+    const timestamp = header(headers, WEBHOOK_TIMESTAMP_HEADER);
+    const signature = header(headers, WEBHOOK_SIGNATURE_HEADER);
+    if (!timestamp || !signature) {
+      throw new WebhookVerificationError('Missing webhook signature headers', 'MISSING_HEADER');
+    }
+
+    this.verifySignature(body, timestamp, signature, now);
+
+    let event: any;
+    try {
+      event = JSON.parse(body);
+    } catch {
+      throw new WebhookVerificationError('Webhook body is not JSON', 'INVALID_PAYLOAD');
+    }
+    if (
+      typeof event?.id !== 'string' ||
+      typeof event.type !== 'string' ||
+      typeof event.created !== 'number' ||
+      typeof event.livemode !== 'boolean' ||
+      typeof event.data !== 'object' ||
+      event.data === null
+    ) {
+      throw new WebhookVerificationError('Webhook body is not a ninjapay event', 'INVALID_PAYLOAD');
+    }
+    return event as WebhookEvent;
+  }
+
+  /**
+   * Check the signature and timestamp tolerance of a raw body
+   */
+  verifySignature(body: string, timestamp: string, signature: string, now: number = Date.now()): void {
+    const sentAt = Number(timestamp);
+    if (!/^\d+$/.test(timestamp) || Math.abs(now - sentAt) > this.toleranceMs) {
+      throw new WebhookVerificationError(
+        'Webhook timestamp is outside the tolerance',
+        'TIMESTAMP_OUT_OF_TOLERANCE'
+      );
+    }
+
+    const expected = Buffer.from(
+      'sha256=' + createHmac('sha256', this.secret).update(`${timestamp}.${body}`).digest('hex')
+    );
+    const matches = signature.split(',').some((value) => {
+      const received = Buffer.from(value.trim());
+      return received.length === expected.length && timingSafeEqual(received, expected);
+    });
+    if (!matches) {
+      throw new WebhookVerificationError('Webhook signature does not match', 'INVALID_SIGNATURE');
+    }
+  }
+}
+
+// ============ Idempotency ============
+
+/**
+ * Shared record of processed event ids. Back it with Redis or your database
+ * when several instances consume webhooks, so a failover or a redelivery to
+ * another instance is still deduplicated.
+ */
+export interface IdempotencyStore {
+  /**
+   * Atomically claim an event id; resolves false if it is already claimed.
+   * Claims should expire after `ttlMs` (e.g. Redis `SET key 1 NX PX ttl`).
+   */
+  claim(eventId: string, ttlMs: number): Promise<boolean>;
+  /** Give up a claim so a redelivery can process the event */
+  release(eventId: string): Promise<void>;
+}
+
+/**
+ * Single-process store, suitable for tests and one-instance consumers
+ */
+export class InMemoryIdempotencyStore implements IdempotencyStore {
+  private claims = new Map<string, number>();
+
+  async claim(eventId: string, ttlMs: number): Promise<boolean> {
+    const now = Date.now();
+    const expiresAt = this.claims.get(eventId);
+    if (expiresAt !== undefined && expiresAt > now) {
+      return false;
+    }
+    this.claims.set(eventId, now + ttlMs);
+    return true;
+  }
+
+  async release(eventId: string): Promise<void> {
+    this.claims.delete(eventId);
+  }
+}
+
+/**
+ * Runs each event's handler at most once per event id. If the handler
+ * throws, the claim is released so our retry can process it again.
+ */
+export class IdempotencyGuard {
+  private ttlMs: number;
+
+  constructor(
+    private store: IdempotencyStore = new InMemoryIdempotencyStore(),
+    options: { ttlMs?: number } = {}
+  ) {
+    // Far outlives the delivery retry schedule (about 13 minutes)
+    this.ttlMs = options.ttlMs ?? 24 * 60 * 60 * 1000;
+  }
+
+  /**
+   * Resolves true if the handler ran, false if the event was a duplicate
+   */
+  async handle(event: { id: string }, handler: () => Promise<void> | void): Promise<boolean> {
+    if (!(await this.store.claim(event.id, this.ttlMs))) {
+      return false;
+    }
+    try {
+      await handler();
+      return true;
+    } catch (error) {
+      await this.store.release(event.id);
+      throw error;
+    }
+  }
+}
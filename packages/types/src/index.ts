@@ -64,7 +64,8 @@ export type PaymentStatus =
   | 'CONFIRMED'
   | 'FINALIZED'
   | 'FAILED'
-  | 'CANCELLED';
+  | 'CANCELLED'
+  | 'REVERTED'; // rolled back by a chain reorg before finalizing
 
 export type ComputationStatus =
   | 'QUEUED'
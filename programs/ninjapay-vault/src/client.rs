@@ -0,0 +1,181 @@
+//! Building vault instructions off-chain. Depend on the crate with the
+//! `no-entrypoint` feature so it links alongside other programs:
+//!
+//! ```toml
+//! ninjapay-vault = { path = "programs/ninjapay-vault", features = ["no-entrypoint"] }
+//! ```
+//!
+//! Anchor generates `crate::instruction` for the instruction data alone;
+//! the builders here add the accounts, in the order the program expects.
+//! Account state (`VaultConfig`, `PaymentRecord`, ...) deserializes with
+//! `AccountDeserialize::try_deserialize`.
+//!
+//! ```
+//! use anchor_lang::prelude::Pubkey;
+//! use ninjapay_vault::client::{self, PaymentAccounts};
+//! use ninjapay_vault::FeeMode;
+//!
+//! let payer = Pubkey::new_unique();
+//! let mint = Pubkey::new_unique();
+//! let accounts = PaymentAccounts {
+//!     payer,
+//!     payer_token_account: Pubkey::new_unique(),
+//!     mint,
+//!     accepted_mint: None,
+//!     vault_mint_config: None,
+//!     fee_schedule: None,
+//!     merchant: Pubkey::new_unique(),
+//!     registered_merchant: None,
+//!     merchant_token_account: Pubkey::new_unique(),
+//!     fee_token_account: Pubkey::new_unique(),
+//!     fee_token_account_1: None,
+//!     fee_token_account_2: None,
+//!     day: 20_000,
+//! };
+//! let payment_id = [7; 32];
+//! let fee_mode = FeeMode::Deducted;
+//! let instruction = client::process_payment(&accounts, 1_500_000, payment_id, [0; 32], 0, &[], fee_mode);
+//!
+//! assert_eq!(instruction.program_id, ninjapay_vault::ID);
+//! assert_eq!(instruction.accounts[0].pubkey, client::vault_config_address());
+//! assert_eq!(instruction.accounts[1].pubkey, client::payment_record_address(&payer, &payment_id));
+//! assert_eq!(instruction.accounts[3].pubkey, client::daily_volume_address(20_000));
+//! assert!(instruction.accounts[4].is_signer);
+//! // An AcceptedMint or FeeSchedule left out is passed as the program id
+//! assert_eq!(instruction.accounts[8].pubkey, ninjapay_vault::ID);
+//! ```
+
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::instruction::Instruction;
+use anchor_lang::{system_program, InstructionData};
+use anchor_spl::token::spl_token;
+
+use crate::FeeMode;
+
+pub fn vault_config_address() -> Pubkey {
+    Pubkey::find_program_address(&[b"vault_config"], &crate::ID).0
+}
+
+/// Per PAYMENT_RECORD_SEED_VERSION
+pub fn payment_record_address(payer: &Pubkey, payment_id: &[u8; 32]) -> Pubkey {
+    Pubkey::find_program_address(&[b"payment", payer.as_ref(), payment_id], &crate::ID).0
+}
+
+pub fn payer_rate_limit_address(payer: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"rate_limit", payer.as_ref()], &crate::ID).0
+}
+
+pub fn accepted_mint_address(mint: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"accepted_mint", mint.as_ref()], &crate::ID).0
+}
+
+pub fn vault_mint_config_address(mint: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"vault_mint", mint.as_ref()], &crate::ID).0
+}
+
+pub fn fee_schedule_address() -> Pubkey {
+    Pubkey::find_program_address(&[b"fee_schedule"], &crate::ID).0
+}
+
+/// The DailyVolumeRecord of `day`, in days since the Unix epoch
+pub fn daily_volume_address(day: i64) -> Pubkey {
+    Pubkey::find_program_address(&[b"daily_vol", &day.to_le_bytes()], &crate::ID).0
+}
+
+pub fn merchant_config_address(merchant: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"merchant_cfg", merchant.as_ref()], &crate::ID).0
+}
+
+/// Passed with every `process_payment`, whether or not the payer is
+/// blacklisted
+pub fn blacklisted_payer_address(payer: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"blacklist", payer.as_ref()], &crate::ID).0
+}
+
+pub fn registered_merchant_address(merchant: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"merchant", merchant.as_ref()], &crate::ID).0
+}
+
+pub fn merchant_stats_address(merchant: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"merchant_stats", merchant.as_ref()], &crate::ID).0
+}
+
+/// The caller-chosen accounts of a `process_payment`; the PDAs and
+/// programs are derived
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PaymentAccounts {
+    pub payer: Pubkey,
+    pub payer_token_account: Pubkey,
+    pub mint: Pubkey,
+    /// `accepted_mint_address(&mint)`, needed while the vault requires a
+    /// whitelisted mint
+    pub accepted_mint: Option<Pubkey>,
+    /// `vault_mint_config_address(&mint)`, to charge the mint's fee override
+    pub vault_mint_config: Option<Pubkey>,
+    /// `fee_schedule_address()`, needed while the vault has a fee schedule
+    pub fee_schedule: Option<Pubkey>,
+    pub merchant: Pubkey,
+    /// `registered_merchant_address(&merchant)`, needed while the vault
+    /// requires registered merchants
+    pub registered_merchant: Option<Pubkey>,
+    pub merchant_token_account: Pubkey,
+    pub fee_token_account: Pubkey,
+    /// The other `VaultConfig::fee_collectors` entries' token accounts,
+    /// needed while they have a share of the fee
+    pub fee_token_account_1: Option<Pubkey>,
+    pub fee_token_account_2: Option<Pubkey>,
+    /// UTC day the payment lands on, `unix_timestamp / 86400`, for its
+    /// DailyVolumeRecord
+    pub day: i64,
+}
+
+/// `process_payment` of `amount` base units; an `expires_at` of 0 never
+/// expires. `blinding_factor` opens `commitment`, and may be empty unless
+/// the vault verifies commitments. `fee_mode` decides whether the fee comes
+/// out of `amount` or on top of it. Push the merchant's
+/// `merchant_config_address` onto the accounts to charge its fee override.
+pub fn process_payment(
+    accounts: &PaymentAccounts,
+    amount: u64,
+    payment_id: [u8; 32],
+    commitment: [u8; 32],
+    expires_at: i64,
+    blinding_factor: &[u8],
+    fee_mode: FeeMode,
+) -> Instruction {
+    Instruction {
+        program_id: crate::ID,
+        accounts: crate::accounts::ProcessPayment {
+            vault_config: vault_config_address(),
+            payment_record: payment_record_address(&accounts.payer, &payment_id),
+            payer_rate_limit: payer_rate_limit_address(&accounts.payer),
+            daily_volume: daily_volume_address(accounts.day),
+            payer: accounts.payer,
+            blacklist_check: blacklisted_payer_address(&accounts.payer),
+            payer_token_account: accounts.payer_token_account,
+            mint: accounts.mint,
+            accepted_mint: accounts.accepted_mint,
+            vault_mint_config: accounts.vault_mint_config,
+            fee_schedule: accounts.fee_schedule,
+            merchant: accounts.merchant,
+            registered_merchant: accounts.registered_merchant,
+            merchant_stats: merchant_stats_address(&accounts.merchant),
+            merchant_token_account: accounts.merchant_token_account,
+            fee_token_account: accounts.fee_token_account,
+            fee_token_account_1: accounts.fee_token_account_1,
+            fee_token_account_2: accounts.fee_token_account_2,
+            token_program: spl_token::ID,
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None),
+        data: crate::instruction::ProcessPayment {
+            amount,
+            payment_id,
+            commitment,
+            expires_at,
+            blinding_factor: blinding_factor.to_vec(),
+            fee_mode,
+        }
+        .data(),
+    }
+}
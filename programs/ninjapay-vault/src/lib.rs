@@ -1,21 +1,107 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token::{self, Token, TokenAccount, Transfer};
+use anchor_lang::solana_program::{bpf_loader_upgradeable, hash};
+use anchor_lang::{system_program, Discriminator};
+use anchor_spl::token::{self, spl_token, CloseAccount, Mint, SyncNative, Token, TokenAccount, TransferChecked};
 
 declare_id!("NJPvau1tPBHrRxUqrLvhLq3zDpNZRGpNPdTpP1Dvq6C");
 
+pub mod client;
+
+/// Most recipients a split payment can be divided among
+pub const MAX_SPLITS: usize = 8;
+
+/// Most tiers a `FeeSchedule` holds
+pub const MAX_FEE_TIERS: usize = 5;
+
+/// Most collectors payment fees are divided among
+pub const MAX_FEE_COLLECTORS: usize = 3;
+
+/// Most payments one `process_payment_batch` settles
+pub const MAX_BATCH_PAYMENTS: usize = 5;
+
+/// Compute unit limit a full `process_payment_batch` fits in; clients set
+/// it with the compute budget program, as the default 200k may not cover
+/// five record creations and ten transfers
+pub const PAYMENT_BATCH_COMPUTE_UNITS: u32 = 400_000;
+
+/// How long a proposed authority has to accept, in seconds
+pub const AUTHORITY_TRANSFER_WINDOW: i64 = 48 * 60 * 60;
+
+/// Derivation of PaymentRecord addresses. Version 1 seeded them with
+/// `[b"payment", payment_id]` alone, so anyone who saw a payment_id could
+/// create the record first and block the real payer; version 2 adds the
+/// payer: `[b"payment", payer, payment_id]`.
+pub const PAYMENT_RECORD_SEED_VERSION: u8 = 2;
+
+/// Layout version written to PaymentRecords and BatchRecords. Version 1
+/// adds `record_version` and a `reserved` tail of RECORD_RESERVED_LEN
+/// bytes; later fields are carved out of the tail, so records no longer
+/// have to grow when one is added. Version 2 appends the fee's division
+/// among collectors to PaymentRecords, which the tail had no room for;
+/// BatchRecords are unchanged.
+pub const RECORD_VERSION: u8 = 2;
+
+pub const RECORD_RESERVED_LEN: usize = 64;
+
+/// How long a payroll batch's record is kept before its company can close
+/// it, in seconds
+pub const BATCH_RECORD_RETENTION: i64 = 30 * 24 * 60 * 60;
+
+/// Shortest `VaultConfig::retention_days` a PaymentRecord is kept before its
+/// payer can close it
+pub const MIN_RETENTION_DAYS: u16 = 30;
+
+/// How long a proposed emergency recovery waits before it can be executed,
+/// in seconds; the authority can cancel it in the meantime
+pub const EMERGENCY_RECOVERY_DELAY: i64 = 24 * 60 * 60;
+
 #[program]
 pub mod ninjapay_vault {
     use super::*;
 
-    /// Initialize the vault configuration
-    pub fn initialize(ctx: Context<Initialize>, fee_basis_points: u16) -> Result<()> {
+    /// Initialize the vault configuration. Payments must be at least
+    /// `min_payment` and at most `max_payment` base units; a `max_payment`
+    /// of 0 leaves them unbounded above. `recovery_authority`, if any, can
+    /// take the vault back through `emergency_recover` should the authority
+    /// key be lost.
+    pub fn initialize(
+        ctx: Context<Initialize>,
+        fee_basis_points: u16,
+        min_payment: u64,
+        max_payment: u64,
+        recovery_authority: Option<Pubkey>,
+    ) -> Result<()> {
+        require!(valid_payment_bounds(min_payment, max_payment), VaultError::InvalidAmount);
+
         let vault_config = &mut ctx.accounts.vault_config;
         vault_config.authority = ctx.accounts.authority.key();
         vault_config.fee_collector = ctx.accounts.fee_collector.key();
+        vault_config.fee_collectors = Default::default();
+        vault_config.fee_collectors[0] = FeeCollectorShare {
+            collector: vault_config.fee_collector,
+            basis_points: 10_000,
+        };
         vault_config.fee_basis_points = fee_basis_points;
         vault_config.total_volume = 0;
         vault_config.total_payments = 0;
         vault_config.bump = ctx.bumps.vault_config;
+        vault_config.paused = false;
+        vault_config.min_payment = min_payment;
+        vault_config.max_payment = max_payment;
+        vault_config.total_fees_collected = 0;
+        vault_config.max_payments_per_hour = 0;
+        vault_config.max_volume_per_hour = 0;
+        vault_config.require_mint_whitelist = false;
+        vault_config.has_fee_schedule = false;
+        vault_config.pending_authority = None;
+        vault_config.authority_transfer_expires_at = 0;
+        vault_config.retention_days = MIN_RETENTION_DAYS;
+        vault_config.require_registered_merchants = false;
+        vault_config.recovery_authority = recovery_authority;
+        vault_config.pending_recovery_authority = None;
+        vault_config.recovery_proposed_at = 0;
+        vault_config.merchant_volume_tiers = false;
+        vault_config.round_fee_up = false;
 
         emit!(VaultInitialized {
             authority: vault_config.authority,
@@ -26,239 +112,5232 @@ pub mod ninjapay_vault {
         Ok(())
     }
 
-    /// Process a payment from payer to merchant
-    pub fn process_payment(
-        ctx: Context<ProcessPayment>,
+    /// Process a payment from payer to merchant. The merchant's
+    /// `MerchantConfig` may be passed as the first remaining account to
+    /// charge its fee override instead of the vault-wide fee; the mint's
+    /// active `VaultMintConfig`, if passed, does the same for every merchant
+    /// paid in it. A payment request with a positive `expires_at` (unix seconds) can't be paid
+    /// after it; 0 never expires. While the vault verifies commitments,
+    /// `commitment` must be `sha256(amount_le || blinding_factor)`;
+    /// otherwise `blinding_factor` is ignored and may be empty. Under
+    /// `FeeMode::ExactNet` the merchant receives exactly `amount` and the
+    /// payer is debited the fee on top of it.
+    pub fn process_payment<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ProcessPayment<'info>>,
         amount: u64,
         payment_id: [u8; 32],
         commitment: [u8; 32],
+        expires_at: i64,
+        blinding_factor: Vec<u8>,
+        fee_mode: FeeMode,
+    ) -> Result<()> {
+        let accounts = &mut *ctx.accounts;
+        let settlement = SettlementAccounts {
+            vault_config: &mut accounts.vault_config,
+            payment_record: &mut accounts.payment_record,
+            payment_record_bump: ctx.bumps.payment_record,
+            payer_rate_limit: &mut accounts.payer_rate_limit,
+            payer_rate_limit_bump: ctx.bumps.payer_rate_limit,
+            daily_volume: &mut accounts.daily_volume,
+            daily_volume_bump: ctx.bumps.daily_volume,
+            payer: &accounts.payer,
+            blacklist_check: &accounts.blacklist_check,
+            payer_token_account: &accounts.payer_token_account,
+            mint: &accounts.mint,
+            accepted_mint: accounts.accepted_mint.as_deref(),
+            vault_mint_config: accounts.vault_mint_config.as_deref(),
+            fee_schedule: accounts.fee_schedule.as_deref(),
+            merchant: &accounts.merchant,
+            registered_merchant: accounts.registered_merchant.as_deref(),
+            merchant_stats: &mut accounts.merchant_stats,
+            merchant_stats_bump: ctx.bumps.merchant_stats,
+            merchant_token_account: &accounts.merchant_token_account,
+            fee_token_accounts: [
+                Some(&accounts.fee_token_account),
+                accounts.fee_token_account_1.as_ref(),
+                accounts.fee_token_account_2.as_ref(),
+            ],
+            merchant_config: ctx.remaining_accounts.first(),
+            token_program: &accounts.token_program,
+        };
+        settle_payment(settlement, amount, payment_id, commitment, expires_at, &blinding_factor, fee_mode)
+    }
+
+    /// `process_payment` for a payer that signs through a calling program,
+    /// typically a PDA passing its seeds to `invoke_signed`. Such a payer
+    /// can't fund accounts, so `rent_payer` pays for the payment record
+    /// and rate limit instead; fees, the record and events are otherwise
+    /// the same.
+    pub fn process_payment_cpi<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ProcessPaymentCpi<'info>>,
+        amount: u64,
+        payment_id: [u8; 32],
+        commitment: [u8; 32],
+        expires_at: i64,
+        blinding_factor: Vec<u8>,
+        fee_mode: FeeMode,
+    ) -> Result<()> {
+        let accounts = &mut *ctx.accounts;
+        let settlement = SettlementAccounts {
+            vault_config: &mut accounts.vault_config,
+            payment_record: &mut accounts.payment_record,
+            payment_record_bump: ctx.bumps.payment_record,
+            payer_rate_limit: &mut accounts.payer_rate_limit,
+            payer_rate_limit_bump: ctx.bumps.payer_rate_limit,
+            daily_volume: &mut accounts.daily_volume,
+            daily_volume_bump: ctx.bumps.daily_volume,
+            payer: &accounts.payer,
+            blacklist_check: &accounts.blacklist_check,
+            payer_token_account: &accounts.payer_token_account,
+            mint: &accounts.mint,
+            accepted_mint: accounts.accepted_mint.as_deref(),
+            vault_mint_config: accounts.vault_mint_config.as_deref(),
+            fee_schedule: accounts.fee_schedule.as_deref(),
+            merchant: &accounts.merchant,
+            registered_merchant: accounts.registered_merchant.as_deref(),
+            merchant_stats: &mut accounts.merchant_stats,
+            merchant_stats_bump: ctx.bumps.merchant_stats,
+            merchant_token_account: &accounts.merchant_token_account,
+            fee_token_accounts: [
+                Some(&accounts.fee_token_account),
+                accounts.fee_token_account_1.as_ref(),
+                accounts.fee_token_account_2.as_ref(),
+            ],
+            merchant_config: ctx.remaining_accounts.first(),
+            token_program: &accounts.token_program,
+        };
+        settle_payment(settlement, amount, payment_id, commitment, expires_at, &blinding_factor, fee_mode)
+    }
+
+    /// Divide a payment among up to MAX_SPLITS recipients in one transaction.
+    /// The fee goes to the collectors and each split's share of `amount` to
+    /// its token account, passed as remaining accounts in the same order;
+    /// the split basis points must add up to 10000 less the fee's. The fee
+    /// is the one `process_payment` would charge `merchant`, who owns the
+    /// first recipient's token account and also takes the rounding dust.
+    /// While the vault requires registered merchants, each token account is
+    /// followed by its owner's RegisteredMerchant. `commitment` and
+    /// `blinding_factor` are verified as for `process_payment`.
+    pub fn process_split_payment<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ProcessSplitPayment<'info>>,
+        amount: u64,
+        payment_id: [u8; 32],
+        commitment: [u8; 32],
+        splits: Vec<SplitEntry>,
+        blinding_factor: Vec<u8>,
     ) -> Result<()> {
         let vault_config = &ctx.accounts.vault_config;
+        require!(!vault_config.paused, VaultError::VaultPaused);
+        check_not_blacklisted(&ctx.accounts.blacklist_check)?;
+        vault_config.check_mint_whitelist(ctx.accounts.accepted_mint.as_deref())?;
+        require!(vault_config.accepts_amount(amount), VaultError::InvalidAmount);
+        vault_config.check_commitment(amount, &commitment, &blinding_factor)?;
+        let stride = if vault_config.require_registered_merchants { 2 } else { 1 };
+        require!(
+            ctx.remaining_accounts.len() == stride * splits.len(),
+            VaultError::InvalidSplit
+        );
+        let now = Clock::get()?.unix_timestamp;
+        ctx.accounts.payer_rate_limit.record_payment(
+            vault_config,
+            ctx.accounts.payer.key(),
+            ctx.bumps.payer_rate_limit,
+            amount,
+            now,
+        )?;
+        ctx.accounts
+            .daily_volume
+            .record_payment(vault_config, ctx.bumps.daily_volume, amount, now)?;
+
+        let merchant_fee = ctx.accounts.merchant_config.as_ref().and_then(|config| config.fee_override());
+        let (fee_basis_points, _) = vault_config.payment_fee(
+            ctx.accounts.fee_schedule.as_deref(),
+            vault_config.total_volume,
+            ctx.accounts.vault_mint_config.as_deref(),
+            merchant_fee,
+        )?;
+        let basis_points: Vec<u16> = splits.iter().map(|split| split.basis_points).collect();
+        let (fee, shares) = split_shares_rounded(amount, fee_basis_points, &basis_points, vault_config.round_fee_up)?;
+        let decimals = ctx.accounts.mint.decimals;
 
-        // Calculate fee
-        let fee = (amount as u128)
-            .checked_mul(vault_config.fee_basis_points as u128)
-            .unwrap()
-            .checked_div(10000)
-            .unwrap() as u64;
+        let mut transfers = Vec::with_capacity(splits.len());
+        for ((split, accounts), share) in splits.iter().zip(ctx.remaining_accounts.chunks(stride)).zip(shares) {
+            let recipient = &accounts[0];
+            require_keys_eq!(recipient.key(), split.recipient_token_account, VaultError::InvalidSplit);
+            let recipient_token_account = Account::<'info, TokenAccount>::try_from(recipient)?;
+            require_keys_eq!(recipient_token_account.mint, ctx.accounts.mint.key(), VaultError::MintMismatch);
+            if transfers.is_empty() {
+                require_keys_eq!(recipient_token_account.owner, ctx.accounts.merchant.key(), VaultError::InvalidSplit);
+            }
+            let registered_merchant = accounts
+                .get(1)
+                .map(|info| remaining_registration(info, &recipient_token_account.owner))
+                .transpose()?
+                .flatten();
+            vault_config.check_merchant_registration(registered_merchant.as_ref())?;
 
-        let net_amount = amount.checked_sub(fee).unwrap();
+            let cpi_accounts = TransferChecked {
+                from: ctx.accounts.payer_token_account.to_account_info(),
+                mint: ctx.accounts.mint.to_account_info(),
+                to: recipient.clone(),
+                authority: ctx.accounts.payer.to_account_info(),
+            };
+            let cpi_program = ctx.accounts.token_program.to_account_info();
+            let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
+            token::transfer_checked(cpi_ctx, share, decimals)?;
+
+            transfers.push(SplitTransfer {
+                recipient_token_account: split.recipient_token_account,
+                basis_points: split.basis_points,
+                amount: share,
+            });
+        }
 
-        // Transfer net amount to merchant
-        let cpi_accounts = Transfer {
+        // Transfer fee to collectors by their shares (if any)
+        let fee_transfer = FeeTransfer {
             from: ctx.accounts.payer_token_account.to_account_info(),
-            to: ctx.accounts.merchant_token_account.to_account_info(),
             authority: ctx.accounts.payer.to_account_info(),
+            mint: &ctx.accounts.mint,
+            fee_token_accounts: [
+                Some(&ctx.accounts.fee_token_account),
+                ctx.accounts.fee_token_account_1.as_ref(),
+                ctx.accounts.fee_token_account_2.as_ref(),
+            ],
+            token_program: &ctx.accounts.token_program,
         };
-        let cpi_program = ctx.accounts.token_program.to_account_info();
-        let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
-        token::transfer(cpi_ctx, net_amount)?;
+        pay_fee_collectors(&ctx.accounts.vault_config, &fee_transfer, fee, &[])?;
 
-        // Transfer fee to collector (if any)
-        if fee > 0 {
-            let cpi_accounts = Transfer {
-                from: ctx.accounts.payer_token_account.to_account_info(),
-                to: ctx.accounts.fee_token_account.to_account_info(),
-                authority: ctx.accounts.payer.to_account_info(),
+        // Update vault stats
+        let vault_config = &mut ctx.accounts.vault_config;
+        vault_config.total_volume = vault_config
+            .total_volume
+            .checked_add(amount)
+            .ok_or(VaultError::MathOverflow)?;
+        vault_config.total_payments = vault_config
+            .total_payments
+            .checked_add(1)
+            .ok_or(VaultError::MathOverflow)?;
+        vault_config.total_fees_collected = vault_config
+            .total_fees_collected
+            .checked_add(fee)
+            .ok_or(VaultError::MathOverflow)?;
+
+        emit!(SplitPaymentProcessed {
+            payment_id,
+            payer: ctx.accounts.payer.key(),
+            amount,
+            fee,
+            commitment,
+            mint: ctx.accounts.mint.key(),
+            splits: transfers,
+            timestamp: Clock::get()?.unix_timestamp,
+            total_fees_collected: vault_config.total_fees_collected,
+        });
+
+        Ok(())
+    }
+
+    /// Settle up to MAX_BATCH_PAYMENTS unrelated payments from one token
+    /// account, signed by its owner or delegate: `amounts[i]` under
+    /// `payment_ids[i]` and `commitments[i]`, verified against
+    /// `blinding_factors[i]` as for `process_payment`. Remaining accounts
+    /// come in groups of three, one per payment in order: the merchant's
+    /// token account, the PaymentRecord to create at
+    /// `[b"payment", payer, payment_ids[i]]`, then the merchant's
+    /// MerchantConfig, or the program ID for none; while the vault requires
+    /// registered merchants, each group ends with the merchant's
+    /// RegisteredMerchant.
+    /// Each payment is checked, charged the fee `process_payment` would
+    /// charge its merchant, divided among the collectors as for
+    /// `process_payment`, counted towards the payer's rate limit, recorded
+    /// with the token account's owner as merchant and emitted as a
+    /// `PaymentProcessed` of its own; if any fails, none are made.
+    pub fn process_payment_batch<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ProcessPaymentBatch<'info>>,
+        amounts: Vec<u64>,
+        payment_ids: Vec<[u8; 32]>,
+        commitments: Vec<[u8; 32]>,
+        blinding_factors: Vec<Vec<u8>>,
+    ) -> Result<()> {
+        let count = amounts.len();
+        let stride = if ctx.accounts.vault_config.require_registered_merchants { 4 } else { 3 };
+        require!(
+            (1..=MAX_BATCH_PAYMENTS).contains(&count)
+                && payment_ids.len() == count
+                && commitments.len() == count
+                && blinding_factors.len() == count
+                && ctx.remaining_accounts.len() == stride * count,
+            VaultError::InvalidPaymentBatch
+        );
+
+        let now = Clock::get()?.unix_timestamp;
+        let accounts = &mut *ctx.accounts;
+        require!(!accounts.vault_config.paused, VaultError::VaultPaused);
+        check_not_blacklisted(&accounts.blacklist_check)?;
+        accounts.vault_config.check_mint_whitelist(accounts.accepted_mint.as_deref())?;
+        let payer = accounts.payer.key();
+        let mint = accounts.mint.key();
+        let decimals = accounts.mint.decimals;
+
+        for (i, group) in ctx.remaining_accounts.chunks(stride).enumerate() {
+            let (amount, payment_id, commitment) = (amounts[i], payment_ids[i], commitments[i]);
+            let (merchant_info, record_info, merchant_config) = (&group[0], &group[1], &group[2]);
+
+            let vault_config = &accounts.vault_config;
+            require!(vault_config.accepts_amount(amount), VaultError::InvalidAmount);
+            vault_config.check_commitment(amount, &commitment, &blinding_factors[i])?;
+            accounts.payer_rate_limit.record_payment(
+                vault_config,
+                payer,
+                ctx.bumps.payer_rate_limit,
+                amount,
+                now,
+            )?;
+            accounts
+                .daily_volume
+                .record_payment(vault_config, ctx.bumps.daily_volume, amount, now)?;
+
+            let merchant_token_account = Account::<TokenAccount>::try_from(merchant_info)?;
+            require_keys_eq!(merchant_token_account.mint, mint, VaultError::MintMismatch);
+            let merchant = merchant_token_account.owner;
+            let registered_merchant = group
+                .get(3)
+                .map(|info| remaining_registration(info, &merchant))
+                .transpose()?
+                .flatten();
+            vault_config.check_merchant_registration(registered_merchant.as_ref())?;
+
+            let merchant_fee = match merchant_config.key() == crate::ID {
+                true => None,
+                false => merchant_fee_override(merchant_config, &merchant)?,
             };
-            let cpi_program = ctx.accounts.token_program.to_account_info();
+            let (fee_basis_points, fee_tier) = vault_config.payment_fee(
+                accounts.fee_schedule.as_deref(),
+                vault_config.total_volume,
+                accounts.vault_mint_config.as_deref(),
+                merchant_fee,
+            )?;
+            let (fee, net_amount) = split_payment_rounded(amount, fee_basis_points, vault_config.round_fee_up)?;
+
+            let (expected_record, bump) =
+                Pubkey::find_program_address(&[b"payment", payer.as_ref(), &payment_id], ctx.program_id);
+            require_keys_eq!(record_info.key(), expected_record, ErrorCode::ConstraintSeeds);
+            create_pda_account(
+                record_info,
+                8 + PaymentRecord::INIT_SPACE,
+                &[b"payment", payer.as_ref(), &payment_id, &[bump]],
+                &accounts.payer,
+                &accounts.system_program,
+            )?;
+
+            // Transfer net amount to merchant
+            let cpi_accounts = TransferChecked {
+                from: accounts.payer_token_account.to_account_info(),
+                mint: accounts.mint.to_account_info(),
+                to: merchant_info.clone(),
+                authority: accounts.payer.to_account_info(),
+            };
+            let cpi_program = accounts.token_program.to_account_info();
             let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
-            token::transfer(cpi_ctx, fee)?;
+            token::transfer_checked(cpi_ctx, net_amount, decimals)?;
+
+            // Transfer fee to collectors by their shares (if any)
+            let fee_transfer = FeeTransfer {
+                from: accounts.payer_token_account.to_account_info(),
+                authority: accounts.payer.to_account_info(),
+                mint: &accounts.mint,
+                fee_token_accounts: [
+                    Some(&accounts.fee_token_account),
+                    accounts.fee_token_account_1.as_ref(),
+                    accounts.fee_token_account_2.as_ref(),
+                ],
+                token_program: &accounts.token_program,
+            };
+            let fee_shares = pay_fee_collectors(&accounts.vault_config, &fee_transfer, fee, &[])?;
+
+            // Record payment
+            let payment_record = PaymentRecord {
+                payment_id,
+                payer,
+                merchant,
+                amount,
+                fee,
+                commitment,
+                timestamp: now,
+                bump,
+                status: PaymentStatus::Settled,
+                mint,
+                expires_at: 0,
+                record_version: RECORD_VERSION,
+                refunded_amount: 0,
+                net_amount,
+                fee_mode: FeeMode::Deducted,
+                reserved: [0; RECORD_RESERVED_LEN - 17],
+                fee_collectors: accounts.vault_config.fee_collector_keys(),
+                fee_shares,
+            };
+            payment_record.try_serialize(&mut &mut record_info.try_borrow_mut_data()?[..])?;
+
+            // Update vault stats
+            let vault_config = &mut accounts.vault_config;
+            vault_config.total_volume = vault_config
+                .total_volume
+                .checked_add(amount)
+                .ok_or(VaultError::MathOverflow)?;
+            vault_config.total_payments = vault_config
+                .total_payments
+                .checked_add(1)
+                .ok_or(VaultError::MathOverflow)?;
+            vault_config.total_fees_collected = vault_config
+                .total_fees_collected
+                .checked_add(fee)
+                .ok_or(VaultError::MathOverflow)?;
+
+            emit!(PaymentProcessed {
+                record: record_info.key(),
+                payment_id,
+                payer,
+                merchant,
+                amount,
+                fee,
+                net_amount,
+                fee_mode: FeeMode::Deducted,
+                commitment,
+                timestamp: now,
+                mint,
+                expires_at: 0,
+                total_fees_collected: vault_config.total_fees_collected,
+                fee_tier,
+                fee_basis_points,
+                fee_rounded_up: vault_config.round_fee_up,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Pay a merchant in native SOL. The payer's `lamports` are wrapped into
+    /// a WSOL escrow and the fee is charged as for `process_payment`: the
+    /// volume tiers apply to the vault's total volume, the native mint's
+    /// `VaultMintConfig` override applies, and a merchant's fee override is
+    /// passed as the first remaining account. The escrow is then unwrapped
+    /// with `close_account` into the payment record, which pays the
+    /// merchant and fee collectors their lamports directly, so none needs a
+    /// WSOL account, and returns the escrow's rent to the payer. All must
+    /// be funded system accounts. `commitment` and `blinding_factor` are
+    /// verified as for `process_payment`.
+    pub fn process_sol_payment<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ProcessSolPayment<'info>>,
+        lamports: u64,
+        payment_id: [u8; 32],
+        commitment: [u8; 32],
+        blinding_factor: Vec<u8>,
+    ) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let vault_config = &ctx.accounts.vault_config;
+        require!(!vault_config.paused, VaultError::VaultPaused);
+        check_not_blacklisted(&ctx.accounts.blacklist_check)?;
+        vault_config.check_mint_whitelist(ctx.accounts.accepted_mint.as_deref())?;
+        vault_config.check_merchant_registration(ctx.accounts.registered_merchant.as_deref())?;
+        require!(vault_config.accepts_amount(lamports), VaultError::InvalidAmount);
+        vault_config.check_commitment(lamports, &commitment, &blinding_factor)?;
+        ctx.accounts.payer_rate_limit.record_payment(
+            vault_config,
+            ctx.accounts.payer.key(),
+            ctx.bumps.payer_rate_limit,
+            lamports,
+            now,
+        )?;
+        ctx.accounts
+            .daily_volume
+            .record_payment(vault_config, ctx.bumps.daily_volume, lamports, now)?;
+
+        let merchant_fee = match ctx.remaining_accounts.first() {
+            Some(merchant_config) => merchant_fee_override(merchant_config, &ctx.accounts.merchant.key())?,
+            None => None,
+        };
+        let (fee_basis_points, _) = vault_config.payment_fee(
+            ctx.accounts.fee_schedule.as_deref(),
+            vault_config.total_volume,
+            ctx.accounts.vault_mint_config.as_deref(),
+            merchant_fee,
+        )?;
+        let (fee, net_amount) = split_payment_rounded(lamports, fee_basis_points, vault_config.round_fee_up)?;
+
+        // Wrap
+        let cpi_accounts = system_program::Transfer {
+            from: ctx.accounts.payer.to_account_info(),
+            to: ctx.accounts.sol_escrow.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new(ctx.accounts.system_program.to_account_info(), cpi_accounts);
+        system_program::transfer(cpi_ctx, lamports)?;
+        let cpi_accounts = SyncNative {
+            account: ctx.accounts.sol_escrow.to_account_info(),
+        };
+        token::sync_native(CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts))?;
+
+        // Unwrap: the escrow's lamports, its rent included, go to the record
+        let record_info = ctx.accounts.payment_record.to_account_info();
+        let record_lamports = record_info.lamports();
+        let bump = [vault_config.bump];
+        let vault_seeds: &[&[u8]] = &[b"vault_config", &bump];
+        let cpi_accounts = CloseAccount {
+            account: ctx.accounts.sol_escrow.to_account_info(),
+            destination: record_info.clone(),
+            authority: ctx.accounts.vault_config.to_account_info(),
+        };
+        let signer = &[vault_seeds];
+        let cpi_ctx = CpiContext::new_with_signer(ctx.accounts.token_program.to_account_info(), cpi_accounts, signer);
+        token::close_account(cpi_ctx)?;
+
+        let released = record_info
+            .lamports()
+            .checked_sub(record_lamports)
+            .ok_or(VaultError::MathOverflow)?;
+        let escrow_rent = released.checked_sub(lamports).ok_or(VaultError::MathOverflow)?;
+        let mut fee_shares = [0; MAX_FEE_COLLECTORS];
+        fee_shares.copy_from_slice(&split_fee(fee, &vault_config.fee_collector_shares())?);
+        let mut payouts = vec![
+            (ctx.accounts.merchant.to_account_info(), net_amount),
+            (ctx.accounts.payer.to_account_info(), escrow_rent),
+        ];
+        let fee_collectors = [
+            Some(&ctx.accounts.fee_collector),
+            ctx.accounts.fee_collector_1.as_ref(),
+            ctx.accounts.fee_collector_2.as_ref(),
+        ];
+        for (fee_collector, &share) in fee_collectors.iter().zip(&fee_shares) {
+            if share > 0 {
+                let fee_collector = fee_collector.ok_or(VaultError::MissingFeeAccount)?;
+                payouts.push((fee_collector.to_account_info(), share));
+            }
+        }
+        for (to, amount) in payouts {
+            let remaining = record_info.lamports().checked_sub(amount).ok_or(VaultError::MathOverflow)?;
+            **record_info.try_borrow_mut_lamports()? = remaining;
+            let to_lamports = to.lamports().checked_add(amount).ok_or(VaultError::MathOverflow)?;
+            **to.try_borrow_mut_lamports()? = to_lamports;
         }
 
-        // Record payment
         let payment_record = &mut ctx.accounts.payment_record;
         payment_record.payment_id = payment_id;
         payment_record.payer = ctx.accounts.payer.key();
         payment_record.merchant = ctx.accounts.merchant.key();
-        payment_record.amount = amount;
+        payment_record.amount = lamports;
         payment_record.fee = fee;
         payment_record.commitment = commitment;
-        payment_record.timestamp = Clock::get()?.unix_timestamp;
+        payment_record.timestamp = now;
         payment_record.bump = ctx.bumps.payment_record;
+        payment_record.status = PaymentStatus::Settled;
+        payment_record.mint = spl_token::native_mint::ID;
+        payment_record.expires_at = 0;
+        payment_record.record_version = RECORD_VERSION;
+        payment_record.net_amount = net_amount;
+        payment_record.fee_mode = FeeMode::Deducted;
+        payment_record.fee_collectors = ctx.accounts.vault_config.fee_collector_keys();
+        payment_record.fee_shares = fee_shares;
 
-        // Update vault stats
         let vault_config = &mut ctx.accounts.vault_config;
-        vault_config.total_volume = vault_config.total_volume.checked_add(amount).unwrap();
-        vault_config.total_payments = vault_config.total_payments.checked_add(1).unwrap();
+        vault_config.total_volume = vault_config
+            .total_volume
+            .checked_add(lamports)
+            .ok_or(VaultError::MathOverflow)?;
+        vault_config.total_payments = vault_config
+            .total_payments
+            .checked_add(1)
+            .ok_or(VaultError::MathOverflow)?;
+        vault_config.total_fees_collected = vault_config
+            .total_fees_collected
+            .checked_add(fee)
+            .ok_or(VaultError::MathOverflow)?;
 
-        emit!(PaymentProcessed {
+        emit!(SolPaymentProcessed {
+            record: payment_record.key(),
             payment_id,
             payer: ctx.accounts.payer.key(),
             merchant: ctx.accounts.merchant.key(),
-            amount,
-            fee,
+            lamports,
+            fee_lamports: fee,
+            net_lamports: net_amount,
             commitment,
-            timestamp: payment_record.timestamp,
+            timestamp: now,
+            total_fees_collected: vault_config.total_fees_collected,
         });
 
         Ok(())
     }
 
-    /// Process a batch of payroll payments
-    pub fn process_payroll_batch(
-        ctx: Context<ProcessPayrollBatch>,
-        batch_id: [u8; 32],
-        total_amount: u64,
-        payment_count: u16,
-    ) -> Result<()> {
-        // Record batch on-chain
-        let batch_record = &mut ctx.accounts.batch_record;
-        batch_record.batch_id = batch_id;
-        batch_record.company = ctx.accounts.company.key();
-        batch_record.total_amount = total_amount;
-        batch_record.payment_count = payment_count;
-        batch_record.timestamp = Clock::get()?.unix_timestamp;
-        batch_record.bump = ctx.bumps.batch_record;
-
-        emit!(PayrollBatchProcessed {
-            batch_id,
-            company: ctx.accounts.company.key(),
-            total_amount,
-            payment_count,
-            timestamp: batch_record.timestamp,
-        });
+    /// Refund `amount` of a payment's net amount, in one go or over
+    /// several calls: the merchant returns it to the payer and the record
+    /// keeps the running total. The refund that completes the net amount
+    /// also has each collector the fee was divided among return its share,
+    /// and closes the record to the payer; until then it is
+    /// PartiallyRefunded. Only that refund needs the collectors to sign, so
+    /// partial refunds are the merchant's alone, whoever collects fees now.
+    pub fn refund_payment(ctx: Context<RefundPayment>, payment_id: [u8; 32], amount: u64) -> Result<()> {
+        require!(!ctx.accounts.vault_config.paused, VaultError::VaultPaused);
+        require!(amount > 0, VaultError::InvalidAmount);
 
-        Ok(())
-    }
+        let payment_record = &mut ctx.accounts.payment_record;
+        require!(
+            payment_record.status != PaymentStatus::Refunded,
+            VaultError::AlreadyRefunded
+        );
+        require!(
+            payment_record.status != PaymentStatus::Disputed,
+            VaultError::PaymentUnderDispute
+        );
 
-    /// Update vault fee configuration
-    pub fn update_fee(ctx: Context<UpdateFee>, new_fee_basis_points: u16) -> Result<()> {
-        require!(new_fee_basis_points <= 1000, VaultError::FeeTooHigh); // Max 10%
+        let fee = payment_record.fee;
+        let net_amount = payment_record.amount.checked_sub(fee).ok_or(VaultError::MathOverflow)?;
+        let refunded_amount = payment_record
+            .refunded_amount
+            .checked_add(amount)
+            .ok_or(VaultError::MathOverflow)?;
+        require!(refunded_amount <= net_amount, VaultError::RefundExceedsPayment);
 
-        let vault_config = &mut ctx.accounts.vault_config;
-        let old_fee = vault_config.fee_basis_points;
-        vault_config.fee_basis_points = new_fee_basis_points;
+        // Return amount from merchant
+        let cpi_accounts = TransferChecked {
+            from: ctx.accounts.merchant_token_account.to_account_info(),
+            mint: ctx.accounts.mint.to_account_info(),
+            to: ctx.accounts.payer_token_account.to_account_info(),
+            authority: ctx.accounts.merchant.to_account_info(),
+        };
+        let cpi_program = ctx.accounts.token_program.to_account_info();
+        let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
+        token::transfer_checked(cpi_ctx, amount, ctx.accounts.mint.decimals)?;
 
-        emit!(FeeUpdated {
-            old_fee,
-            new_fee: new_fee_basis_points,
+        let fully_refunded = refunded_amount == net_amount;
+        // Return each collector's share of the fee (if any) with the last of
+        // the net amount, from whoever it went to
+        if fully_refunded && fee > 0 {
+            let fee_collectors = [
+                (ctx.accounts.fee_collector.as_ref(), ctx.accounts.fee_token_account.as_ref()),
+                (ctx.accounts.fee_collector_1.as_ref(), ctx.accounts.fee_token_account_1.as_ref()),
+                (ctx.accounts.fee_collector_2.as_ref(), ctx.accounts.fee_token_account_2.as_ref()),
+            ];
+            let fee_split = payment_record.fee_split(ctx.accounts.vault_config.fee_collector);
+            for ((collector, share), (signer, fee_token_account)) in fee_split.into_iter().zip(fee_collectors) {
+                if share == 0 {
+                    continue;
+                }
+                let signer = signer
+                    .filter(|signer| signer.key() == collector)
+                    .ok_or(VaultError::Unauthorized)?;
+                let fee_token_account = fee_token_account.ok_or(VaultError::MissingFeeAccount)?;
+                require!(fee_token_account.owner == collector, VaultError::InvalidFeeAccount);
+                let cpi_accounts = TransferChecked {
+                    from: fee_token_account.to_account_info(),
+                    mint: ctx.accounts.mint.to_account_info(),
+                    to: ctx.accounts.payer_token_account.to_account_info(),
+                    authority: signer.to_account_info(),
+                };
+                let cpi_program = ctx.accounts.token_program.to_account_info();
+                let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
+                token::transfer_checked(cpi_ctx, share, ctx.accounts.mint.decimals)?;
+            }
+        }
+
+        payment_record.refunded_amount = refunded_amount;
+        payment_record.status = if fully_refunded {
+            PaymentStatus::Refunded
+        } else {
+            PaymentStatus::PartiallyRefunded
+        };
+
+        emit!(PaymentRefunded {
+            payment_id,
+            payer: payment_record.payer,
+            merchant: payment_record.merchant,
+            amount,
+            refunded_amount,
+            timestamp: Clock::get()?.unix_timestamp,
         });
 
+        if fully_refunded {
+            payment_record.close(ctx.accounts.payer.to_account_info())?;
+        }
+
         Ok(())
     }
 
-    /// Transfer vault authority
-    pub fn transfer_authority(ctx: Context<TransferAuthority>) -> Result<()> {
-        let vault_config = &mut ctx.accounts.vault_config;
-        let old_authority = vault_config.authority;
-        vault_config.authority = ctx.accounts.new_authority.key();
+    /// Dispute a payment, signed by its payer. A disputed payment can't be
+    /// refunded or closed until the vault authority resolves the dispute.
+    pub fn open_dispute(ctx: Context<OpenDispute>, payment_id: [u8; 32]) -> Result<()> {
+        let payment_record = &mut ctx.accounts.payment_record;
+        require!(
+            payment_record.status != PaymentStatus::Disputed,
+            VaultError::PaymentUnderDispute
+        );
+        payment_record.status = PaymentStatus::Disputed;
 
-        emit!(AuthorityTransferred {
-            old_authority,
-            new_authority: vault_config.authority,
+        emit!(PaymentDisputed {
+            payment_id,
+            payer: payment_record.payer,
+            merchant: payment_record.merchant,
+            timestamp: Clock::get()?.unix_timestamp,
         });
 
         Ok(())
     }
-}
-
-// ============ Accounts ============
 
-#[derive(Accounts)]
-pub struct Initialize<'info> {
-    #[account(
-        init,
-        payer = authority,
-        space = 8 + VaultConfig::INIT_SPACE,
-        seeds = [b"vault_config"],
-        bump
-    )]
-    pub vault_config: Account<'info, VaultConfig>,
+    /// Close a payment's dispute, signed by the vault authority. The payment
+    /// returns to Settled, or PartiallyRefunded if some of it had been
+    /// refunded; a refund the dispute settled on is then made with
+    /// `refund_payment`.
+    pub fn resolve_dispute(ctx: Context<ResolveDispute>, payment_id: [u8; 32]) -> Result<()> {
+        let payment_record = &mut ctx.accounts.payment_record;
+        require!(
+            payment_record.status == PaymentStatus::Disputed,
+            VaultError::NotDisputed
+        );
+        payment_record.status = if payment_record.refunded_amount > 0 {
+            PaymentStatus::PartiallyRefunded
+        } else {
+            PaymentStatus::Settled
+        };
 
-    #[account(mut)]
-    pub authority: Signer<'info>,
+        emit!(DisputeResolved {
+            payment_id,
+            payer: payment_record.payer,
+            merchant: payment_record.merchant,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
 
-    /// CHECK: Fee collector can be any account
-    pub fee_collector: UncheckedAccount<'info>,
+        Ok(())
+    }
 
-    pub system_program: Program<'info, System>,
-}
+    /// Hold a payment to `merchant` until the merchant captures it: `amount`
+    /// moves from the payer into an escrow token account at
+    /// `[b"escrow_tokens", payer, payment_id]`, held by the payment's
+    /// EscrowRecord. The fee is fixed now, as `process_payment` would
+    /// charge `merchant`, and the payment counts towards the payer's rate
+    /// limit and the daily volume now, even if later cancelled.
+    /// `expires_at` must be in the future; from then on the merchant can no
+    /// longer capture and the payer can cancel. Escrowed payments count
+    /// towards the vault's totals once captured. `commitment` and
+    /// `blinding_factor` are verified as for `process_payment`.
+    pub fn create_escrow_payment(
+        ctx: Context<CreateEscrowPayment>,
+        amount: u64,
+        payment_id: [u8; 32],
+        commitment: [u8; 32],
+        expires_at: i64,
+        blinding_factor: Vec<u8>,
+    ) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let vault_config = &ctx.accounts.vault_config;
+        require!(!vault_config.paused, VaultError::VaultPaused);
+        check_not_blacklisted(&ctx.accounts.blacklist_check)?;
+        require!(vault_config.accepts_amount(amount), VaultError::InvalidAmount);
+        require!(expires_at > now, VaultError::PaymentExpired);
+        vault_config.check_commitment(amount, &commitment, &blinding_factor)?;
+        vault_config.check_mint_whitelist(ctx.accounts.accepted_mint.as_deref())?;
+        vault_config.check_merchant_registration(ctx.accounts.registered_merchant.as_deref())?;
+        ctx.accounts.payer_rate_limit.record_payment(
+            vault_config,
+            ctx.accounts.payer.key(),
+            ctx.bumps.payer_rate_limit,
+            amount,
+            now,
+        )?;
+        ctx.accounts
+            .daily_volume
+            .record_payment(vault_config, ctx.bumps.daily_volume, amount, now)?;
 
-#[derive(Accounts)]
-#[instruction(amount: u64, payment_id: [u8; 32])]
-pub struct ProcessPayment<'info> {
-    #[account(
-        mut,
-        seeds = [b"vault_config"],
-        bump = vault_config.bump
-    )]
-    pub vault_config: Account<'info, VaultConfig>,
+        let merchant_fee = ctx.accounts.merchant_config.as_ref().and_then(|config| config.fee_override());
+        let (fee_basis_points, _) = vault_config.payment_fee(
+            ctx.accounts.fee_schedule.as_deref(),
+            vault_config.total_volume,
+            ctx.accounts.vault_mint_config.as_deref(),
+            merchant_fee,
+        )?;
+        let (fee, _) = split_payment_rounded(amount, fee_basis_points, vault_config.round_fee_up)?;
 
-    #[account(
-        init,
-        payer = payer,
-        space = 8 + PaymentRecord::INIT_SPACE,
-        seeds = [b"payment", &payment_id],
-        bump
-    )]
-    pub payment_record: Account<'info, PaymentRecord>,
+        let cpi_accounts = TransferChecked {
+            from: ctx.accounts.payer_token_account.to_account_info(),
+            mint: ctx.accounts.mint.to_account_info(),
+            to: ctx.accounts.escrow_tokens.to_account_info(),
+            authority: ctx.accounts.payer.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts);
+        token::transfer_checked(cpi_ctx, amount, ctx.accounts.mint.decimals)?;
 
-    #[account(mut)]
-    pub payer: Signer<'info>,
+        let escrow_record = &mut ctx.accounts.escrow_record;
+        escrow_record.payment_id = payment_id;
+        escrow_record.payer = ctx.accounts.payer.key();
+        escrow_record.merchant = ctx.accounts.merchant.key();
+        escrow_record.mint = ctx.accounts.mint.key();
+        escrow_record.amount = amount;
+        escrow_record.fee = fee;
+        escrow_record.commitment = commitment;
+        escrow_record.created_at = now;
+        escrow_record.expires_at = expires_at;
+        escrow_record.status = EscrowStatus::Held;
+        escrow_record.bump = ctx.bumps.escrow_record;
 
-    #[account(mut)]
-    pub payer_token_account: Account<'info, TokenAccount>,
+        emit!(EscrowPaymentCreated {
+            payment_id,
+            payer: escrow_record.payer,
+            merchant: escrow_record.merchant,
+            amount,
+            fee,
+            mint: escrow_record.mint,
+            expires_at,
+        });
 
-    /// CHECK: Merchant wallet
-    pub merchant: UncheckedAccount<'info>,
+        Ok(())
+    }
 
-    #[account(mut)]
-    pub merchant_token_account: Account<'info, TokenAccount>,
+    /// Capture a held payment before it expires, signed by its merchant:
+    /// the net amount goes to the merchant and the fee to the collectors by
+    /// their shares, and the escrow account's rent returns to the payer
+    pub fn capture_payment(ctx: Context<CapturePayment>, payment_id: [u8; 32]) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        require!(!ctx.accounts.vault_config.paused, VaultError::VaultPaused);
+        let escrow_record = &ctx.accounts.escrow_record;
+        require!(escrow_record.status == EscrowStatus::Held, VaultError::EscrowNotHeld);
+        require!(now < escrow_record.expires_at, VaultError::PaymentExpired);
+        let (amount, fee) = (escrow_record.amount, escrow_record.fee);
+        let net_amount = amount.checked_sub(fee).ok_or(VaultError::MathOverflow)?;
 
-    #[account(mut)]
-    pub fee_token_account: Account<'info, TokenAccount>,
+        let bump = [escrow_record.bump];
+        let escrow_seeds: &[&[u8]] = &[b"escrow_payment", escrow_record.payer.as_ref(), payment_id.as_ref(), &bump];
+        let signer = &[escrow_seeds];
+        let cpi_program = ctx.accounts.token_program.to_account_info();
+        let cpi_accounts = TransferChecked {
+            from: ctx.accounts.escrow_tokens.to_account_info(),
+            mint: ctx.accounts.mint.to_account_info(),
+            to: ctx.accounts.merchant_token_account.to_account_info(),
+            authority: ctx.accounts.escrow_record.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new_with_signer(cpi_program.clone(), cpi_accounts, signer);
+        token::transfer_checked(cpi_ctx, net_amount, ctx.accounts.mint.decimals)?;
+        let fee_transfer = FeeTransfer {
+            from: ctx.accounts.escrow_tokens.to_account_info(),
+            authority: ctx.accounts.escrow_record.to_account_info(),
+            mint: &ctx.accounts.mint,
+            fee_token_accounts: [
+                Some(&ctx.accounts.fee_token_account),
+                ctx.accounts.fee_token_account_1.as_ref(),
+                ctx.accounts.fee_token_account_2.as_ref(),
+            ],
+            token_program: &ctx.accounts.token_program,
+        };
+        pay_fee_collectors(&ctx.accounts.vault_config, &fee_transfer, fee, signer)?;
+        let cpi_accounts = CloseAccount {
+            account: ctx.accounts.escrow_tokens.to_account_info(),
+            destination: ctx.accounts.payer.to_account_info(),
+            authority: ctx.accounts.escrow_record.to_account_info(),
+        };
+        token::close_account(CpiContext::new_with_signer(cpi_program, cpi_accounts, signer))?;
 
-    pub token_program: Program<'info, Token>,
-    pub system_program: Program<'info, System>,
-}
+        ctx.accounts.escrow_record.status = EscrowStatus::Captured;
+        let vault_config = &mut ctx.accounts.vault_config;
+        vault_config.total_volume = vault_config
+            .total_volume
+            .checked_add(amount)
+            .ok_or(VaultError::MathOverflow)?;
+        vault_config.total_payments = vault_config
+            .total_payments
+            .checked_add(1)
+            .ok_or(VaultError::MathOverflow)?;
+        vault_config.total_fees_collected = vault_config
+            .total_fees_collected
+            .checked_add(fee)
+            .ok_or(VaultError::MathOverflow)?;
 
-#[derive(Accounts)]
-#[instruction(batch_id: [u8; 32])]
-pub struct ProcessPayrollBatch<'info> {
-    #[account(
-        init,
-        payer = company,
-        space = 8 + BatchRecord::INIT_SPACE,
-        seeds = [b"batch", &batch_id],
-        bump
-    )]
-    pub batch_record: Account<'info, BatchRecord>,
+        emit!(PaymentCaptured {
+            payment_id,
+            payer: ctx.accounts.payer.key(),
+            merchant: ctx.accounts.merchant.key(),
+            amount,
+            fee,
+            timestamp: now,
+        });
 
-    #[account(mut)]
-    pub company: Signer<'info>,
+        Ok(())
+    }
 
-    pub system_program: Program<'info, System>,
-}
+    /// Return an uncaptured payment to its payer once it has expired,
+    /// signed by the payer, closing the escrow account to them
+    pub fn cancel_escrow(ctx: Context<CancelEscrow>, payment_id: [u8; 32]) -> Result<()> {
+        require!(!ctx.accounts.vault_config.paused, VaultError::VaultPaused);
+        let now = Clock::get()?.unix_timestamp;
+        let escrow_record = &ctx.accounts.escrow_record;
+        require!(escrow_record.status == EscrowStatus::Held, VaultError::EscrowNotHeld);
+        require!(now >= escrow_record.expires_at, VaultError::EscrowNotExpired);
+        let amount = escrow_record.amount;
 
-#[derive(Accounts)]
-pub struct UpdateFee<'info> {
-    #[account(
-        mut,
-        seeds = [b"vault_config"],
-        bump = vault_config.bump,
-        has_one = authority
-    )]
-    pub vault_config: Account<'info, VaultConfig>,
+        refund_escrow(
+            escrow_record,
+            &ctx.accounts.escrow_tokens,
+            &ctx.accounts.payer_token_account,
+            &ctx.accounts.mint,
+            ctx.accounts.payer.to_account_info(),
+            &ctx.accounts.token_program,
+            &payment_id,
+        )?;
+        ctx.accounts.escrow_record.status = EscrowStatus::Cancelled;
 
-    pub authority: Signer<'info>,
-}
+        emit!(EscrowCancelled {
+            payment_id,
+            payer: ctx.accounts.payer.key(),
+            amount,
+            timestamp: now,
+        });
 
-#[derive(Accounts)]
-pub struct TransferAuthority<'info> {
-    #[account(
-        mut,
-        seeds = [b"vault_config"],
-        bump = vault_config.bump,
-        has_one = authority
-    )]
-    pub vault_config: Account<'info, VaultConfig>,
+        Ok(())
+    }
 
-    pub authority: Signer<'info>,
+    /// Return an expired escrow the payer never cancelled. Anyone can call
+    /// this; the tokens and the escrow token account's rent all go back to
+    /// the payer, who funded both.
+    pub fn crank_expired_escrow(ctx: Context<CrankExpiredEscrow>, payment_id: [u8; 32]) -> Result<()> {
+        require!(!ctx.accounts.vault_config.paused, VaultError::VaultPaused);
+        let now = Clock::get()?.unix_timestamp;
+        let escrow_record = &ctx.accounts.escrow_record;
+        require!(escrow_record.status == EscrowStatus::Held, VaultError::EscrowNotHeld);
+        require!(now >= escrow_record.expires_at, VaultError::EscrowNotExpired);
+        let amount = escrow_record.amount;
 
-    /// CHECK: New authority can be any account
-    pub new_authority: UncheckedAccount<'info>,
-}
+        refund_escrow(
+            escrow_record,
+            &ctx.accounts.escrow_tokens,
+            &ctx.accounts.payer_token_account,
+            &ctx.accounts.mint,
+            ctx.accounts.payer.to_account_info(),
+            &ctx.accounts.token_program,
+            &payment_id,
+        )?;
+        ctx.accounts.escrow_record.status = EscrowStatus::Cancelled;
+
+        emit!(EscrowExpiredRefunded {
+            payment_id,
+            payer: ctx.accounts.payer.key(),
+            amount,
+            cranker: ctx.accounts.cranker.key(),
+            timestamp: now,
+        });
+
+        Ok(())
+    }
+
+    /// Set `amount` aside for `beneficiary` until `unlock_at`. The fee is
+    /// charged now, as for `process_payment`, and paid straight to the
+    /// collectors; the rest moves from the funder into a token account at
+    /// `[b"timelock_tokens", funder, payment_id]`, held by the
+    /// TimelockPayment. `amount` is subject to the same checks and limits
+    /// as any other payment and counts towards the vault's totals now, as
+    /// it can't be taken back. `unlock_at` must be in the future.
+    pub fn create_timelock_payment(
+        ctx: Context<CreateTimelockPayment>,
+        amount: u64,
+        payment_id: [u8; 32],
+        unlock_at: i64,
+    ) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let vault_config = &ctx.accounts.vault_config;
+        require!(!vault_config.paused, VaultError::VaultPaused);
+        check_not_blacklisted(&ctx.accounts.blacklist_check)?;
+        vault_config.check_mint_whitelist(ctx.accounts.accepted_mint.as_deref())?;
+        require!(vault_config.accepts_amount(amount), VaultError::InvalidAmount);
+        require!(unlock_at > now, VaultError::InvalidUnlockTime);
+        ctx.accounts.payer_rate_limit.record_payment(
+            vault_config,
+            ctx.accounts.funder.key(),
+            ctx.bumps.payer_rate_limit,
+            amount,
+            now,
+        )?;
+        ctx.accounts
+            .daily_volume
+            .record_payment(vault_config, ctx.bumps.daily_volume, amount, now)?;
+        let (fee_basis_points, _) = vault_config.payment_fee(
+            ctx.accounts.fee_schedule.as_deref(),
+            vault_config.total_volume,
+            ctx.accounts.vault_mint_config.as_deref(),
+            None,
+        )?;
+        let (fee, net_amount) = split_payment_rounded(amount, fee_basis_points, vault_config.round_fee_up)?;
+
+        let cpi_accounts = TransferChecked {
+            from: ctx.accounts.funder_token_account.to_account_info(),
+            mint: ctx.accounts.mint.to_account_info(),
+            to: ctx.accounts.timelock_tokens.to_account_info(),
+            authority: ctx.accounts.funder.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts);
+        token::transfer_checked(cpi_ctx, net_amount, ctx.accounts.mint.decimals)?;
+        let fee_transfer = FeeTransfer {
+            from: ctx.accounts.funder_token_account.to_account_info(),
+            authority: ctx.accounts.funder.to_account_info(),
+            mint: &ctx.accounts.mint,
+            fee_token_accounts: [
+                Some(&ctx.accounts.fee_token_account),
+                ctx.accounts.fee_token_account_1.as_ref(),
+                ctx.accounts.fee_token_account_2.as_ref(),
+            ],
+            token_program: &ctx.accounts.token_program,
+        };
+        pay_fee_collectors(vault_config, &fee_transfer, fee, &[])?;
+
+        let vault_config = &mut ctx.accounts.vault_config;
+        vault_config.total_volume = vault_config
+            .total_volume
+            .checked_add(amount)
+            .ok_or(VaultError::MathOverflow)?;
+        vault_config.total_payments = vault_config
+            .total_payments
+            .checked_add(1)
+            .ok_or(VaultError::MathOverflow)?;
+        vault_config.total_fees_collected = vault_config
+            .total_fees_collected
+            .checked_add(fee)
+            .ok_or(VaultError::MathOverflow)?;
+
+        let timelock_payment = &mut ctx.accounts.timelock_payment;
+        timelock_payment.payment_id = payment_id;
+        timelock_payment.funder = ctx.accounts.funder.key();
+        timelock_payment.beneficiary = ctx.accounts.beneficiary.key();
+        timelock_payment.mint = ctx.accounts.mint.key();
+        timelock_payment.amount = net_amount;
+        timelock_payment.created_at = now;
+        timelock_payment.unlock_at = unlock_at;
+        timelock_payment.claimed = false;
+        timelock_payment.bump = ctx.bumps.timelock_payment;
+        timelock_payment.fee = fee;
+
+        emit!(TimelockCreated {
+            payment_id,
+            funder: timelock_payment.funder,
+            beneficiary: timelock_payment.beneficiary,
+            mint: timelock_payment.mint,
+            amount: net_amount,
+            unlock_at,
+            fee,
+        });
+
+        Ok(())
+    }
+
+    /// Pay out a timelocked payment once it has unlocked, signed by its
+    /// beneficiary; the token account's rent returns to the funder
+    pub fn claim_timelock_payment(ctx: Context<ClaimTimelockPayment>, payment_id: [u8; 32]) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        require!(!ctx.accounts.vault_config.paused, VaultError::VaultPaused);
+        let timelock_payment = &ctx.accounts.timelock_payment;
+        require!(!timelock_payment.claimed, VaultError::TimelockAlreadyClaimed);
+        require!(now >= timelock_payment.unlock_at, VaultError::TimelockNotUnlocked);
+        let amount = timelock_payment.amount;
+
+        let bump = [timelock_payment.bump];
+        let timelock_seeds: &[&[u8]] = &[b"timelock", timelock_payment.funder.as_ref(), payment_id.as_ref(), &bump];
+        let signer = &[timelock_seeds];
+        let cpi_program = ctx.accounts.token_program.to_account_info();
+        let cpi_accounts = TransferChecked {
+            from: ctx.accounts.timelock_tokens.to_account_info(),
+            mint: ctx.accounts.mint.to_account_info(),
+            to: ctx.accounts.beneficiary_token_account.to_account_info(),
+            authority: ctx.accounts.timelock_payment.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new_with_signer(cpi_program.clone(), cpi_accounts, signer);
+        token::transfer_checked(cpi_ctx, amount, ctx.accounts.mint.decimals)?;
+        let cpi_accounts = CloseAccount {
+            account: ctx.accounts.timelock_tokens.to_account_info(),
+            destination: ctx.accounts.funder.to_account_info(),
+            authority: ctx.accounts.timelock_payment.to_account_info(),
+        };
+        token::close_account(CpiContext::new_with_signer(cpi_program, cpi_accounts, signer))?;
+
+        ctx.accounts.timelock_payment.claimed = true;
+
+        emit!(TimelockClaimed {
+            payment_id,
+            beneficiary: ctx.accounts.beneficiary.key(),
+            amount,
+            timestamp: now,
+        });
+
+        Ok(())
+    }
+
+    /// Stream `rate_per_second` base units to `recipient` from `start_time`
+    /// to `end_time`. The whole deposit moves from the sender into a token
+    /// account at `[b"stream_tokens", sender, stream_id]`, held by the
+    /// StreamingPayment, and the recipient withdraws what has streamed as it
+    /// goes. The deposit is a payment like any other: the fee is charged on
+    /// top of it now, and it is held to the vault's checks and limits.
+    pub fn open_stream(
+        ctx: Context<OpenStream>,
+        stream_id: [u8; 32],
+        rate_per_second: u64,
+        start_time: i64,
+        end_time: i64,
+    ) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        require!(
+            rate_per_second > 0 && start_time >= now && end_time > start_time,
+            VaultError::InvalidStream
+        );
+        let deposit = stream_amount(rate_per_second, start_time, end_time)?;
+
+        let accounts = &mut *ctx.accounts;
+        let funding = StreamFunding {
+            vault_config: &mut accounts.vault_config,
+            payer_rate_limit: &mut accounts.payer_rate_limit,
+            payer_rate_limit_bump: ctx.bumps.payer_rate_limit,
+            daily_volume: &mut accounts.daily_volume,
+            daily_volume_bump: ctx.bumps.daily_volume,
+            sender: &accounts.sender,
+            blacklist_check: &accounts.blacklist_check,
+            sender_token_account: &accounts.sender_token_account,
+            stream_tokens: &accounts.stream_tokens,
+            mint: &accounts.mint,
+            accepted_mint: accounts.accepted_mint.as_deref(),
+            vault_mint_config: accounts.vault_mint_config.as_deref(),
+            fee_schedule: accounts.fee_schedule.as_deref(),
+            fee_token_accounts: [
+                Some(&accounts.fee_token_account),
+                accounts.fee_token_account_1.as_ref(),
+                accounts.fee_token_account_2.as_ref(),
+            ],
+            token_program: &accounts.token_program,
+        };
+        let fee = fund_stream(funding, deposit, now)?;
+        let vault_config = &mut accounts.vault_config;
+        vault_config.total_payments = vault_config
+            .total_payments
+            .checked_add(1)
+            .ok_or(VaultError::MathOverflow)?;
+
+        let stream = &mut ctx.accounts.streaming_payment;
+        stream.stream_id = stream_id;
+        stream.sender = ctx.accounts.sender.key();
+        stream.recipient = ctx.accounts.recipient.key();
+        stream.mint = ctx.accounts.mint.key();
+        stream.rate_per_second = rate_per_second;
+        stream.start_time = start_time;
+        stream.end_time = end_time;
+        stream.withdrawn_until = start_time;
+        stream.deposit = deposit;
+        stream.bump = ctx.bumps.streaming_payment;
+
+        emit!(StreamOpened {
+            stream_id,
+            sender: stream.sender,
+            recipient: stream.recipient,
+            mint: stream.mint,
+            rate_per_second,
+            start_time,
+            end_time,
+            deposit,
+            fee,
+        });
+
+        Ok(())
+    }
+
+    /// Pay the recipient what has streamed since its last withdrawal,
+    /// signed by the recipient
+    pub fn withdraw_from_stream(ctx: Context<WithdrawFromStream>, stream_id: [u8; 32]) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        require!(!ctx.accounts.vault_config.paused, VaultError::VaultPaused);
+        let stream = &ctx.accounts.streaming_payment;
+        let streamed_until = stream.streamed_until(now);
+        let amount = stream_amount(stream.rate_per_second, stream.withdrawn_until, streamed_until)?;
+        require!(amount > 0, VaultError::NothingStreamed);
+
+        pay_from_stream(
+            stream,
+            &ctx.accounts.stream_tokens,
+            &ctx.accounts.recipient_token_account,
+            &ctx.accounts.mint,
+            &ctx.accounts.token_program,
+            &stream_id,
+            amount,
+        )?;
+        ctx.accounts.streaming_payment.withdrawn_until = streamed_until;
+
+        emit!(StreamWithdrawn {
+            stream_id,
+            recipient: ctx.accounts.recipient.key(),
+            amount,
+            withdrawn_until: streamed_until,
+        });
+
+        Ok(())
+    }
+
+    /// Lengthen a running stream by `amount / rate_per_second` seconds,
+    /// signed by the sender; `amount` must be a whole number of seconds'
+    /// worth. It is charged and checked as the deposit of `open_stream` is.
+    pub fn top_up_stream(ctx: Context<TopUpStream>, stream_id: [u8; 32], amount: u64) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let stream = &ctx.accounts.streaming_payment;
+        require!(now < stream.end_time, VaultError::StreamEnded);
+        require!(
+            amount > 0 && amount.checked_rem(stream.rate_per_second) == Some(0),
+            VaultError::InvalidAmount
+        );
+        let extension = i64::try_from(amount / stream.rate_per_second).map_err(|_| VaultError::MathOverflow)?;
+        let end_time = stream.end_time.checked_add(extension).ok_or(VaultError::MathOverflow)?;
+        let deposit = stream.deposit.checked_add(amount).ok_or(VaultError::MathOverflow)?;
+
+        let accounts = &mut *ctx.accounts;
+        let funding = StreamFunding {
+            vault_config: &mut accounts.vault_config,
+            payer_rate_limit: &mut accounts.payer_rate_limit,
+            payer_rate_limit_bump: ctx.bumps.payer_rate_limit,
+            daily_volume: &mut accounts.daily_volume,
+            daily_volume_bump: ctx.bumps.daily_volume,
+            sender: &accounts.sender,
+            blacklist_check: &accounts.blacklist_check,
+            sender_token_account: &accounts.sender_token_account,
+            stream_tokens: &accounts.stream_tokens,
+            mint: &accounts.mint,
+            accepted_mint: accounts.accepted_mint.as_deref(),
+            vault_mint_config: accounts.vault_mint_config.as_deref(),
+            fee_schedule: accounts.fee_schedule.as_deref(),
+            fee_token_accounts: [
+                Some(&accounts.fee_token_account),
+                accounts.fee_token_account_1.as_ref(),
+                accounts.fee_token_account_2.as_ref(),
+            ],
+            token_program: &accounts.token_program,
+        };
+        let fee = fund_stream(funding, amount, now)?;
+
+        let stream = &mut ctx.accounts.streaming_payment;
+        stream.end_time = end_time;
+        stream.deposit = deposit;
+
+        emit!(StreamToppedUp {
+            stream_id,
+            sender: stream.sender,
+            amount,
+            end_time,
+            fee,
+        });
+
+        Ok(())
+    }
+
+    /// End a stream early, signed by the sender: the recipient is paid what
+    /// has streamed so far and the rest of the deposit returns to the
+    /// sender, along with both accounts' rent
+    pub fn cancel_stream(ctx: Context<CancelStream>, stream_id: [u8; 32]) -> Result<()> {
+        require!(!ctx.accounts.vault_config.paused, VaultError::VaultPaused);
+        let now = Clock::get()?.unix_timestamp;
+        let stream = &ctx.accounts.streaming_payment;
+        let streamed = stream_amount(stream.rate_per_second, stream.withdrawn_until, stream.streamed_until(now))?;
+        let refunded = ctx
+            .accounts
+            .stream_tokens
+            .amount
+            .checked_sub(streamed)
+            .ok_or(VaultError::MathOverflow)?;
+
+        if streamed > 0 {
+            pay_from_stream(
+                stream,
+                &ctx.accounts.stream_tokens,
+                &ctx.accounts.recipient_token_account,
+                &ctx.accounts.mint,
+                &ctx.accounts.token_program,
+                &stream_id,
+                streamed,
+            )?;
+        }
+        if refunded > 0 {
+            pay_from_stream(
+                stream,
+                &ctx.accounts.stream_tokens,
+                &ctx.accounts.sender_token_account,
+                &ctx.accounts.mint,
+                &ctx.accounts.token_program,
+                &stream_id,
+                refunded,
+            )?;
+        }
+        let bump = [stream.bump];
+        let stream_seeds: &[&[u8]] = &[b"stream", stream.sender.as_ref(), stream_id.as_ref(), &bump];
+        let cpi_accounts = CloseAccount {
+            account: ctx.accounts.stream_tokens.to_account_info(),
+            destination: ctx.accounts.sender.to_account_info(),
+            authority: stream.to_account_info(),
+        };
+        let cpi_program = ctx.accounts.token_program.to_account_info();
+        token::close_account(CpiContext::new_with_signer(cpi_program, cpi_accounts, &[stream_seeds]))?;
+
+        emit!(StreamCancelled {
+            stream_id,
+            sender: ctx.accounts.sender.key(),
+            recipient: ctx.accounts.recipient.key(),
+            streamed,
+            refunded,
+            timestamp: now,
+        });
+
+        Ok(())
+    }
+
+    /// Pay a batch of employees: `total_amount` moves from the company's
+    /// token account into the batch's escrow, held by the batch record, and
+    /// from there to each employee. `amounts[i]` goes to the employee token
+    /// account at remaining account `2 * i`, followed by its
+    /// `DisbursementRecord` at `[b"disbursement", batch_id, i]`. The amounts
+    /// must add up to `total_amount`, one per payment. A batch runs once and
+    /// pays everyone or no one. Once the company has named an approver with
+    /// `set_payroll_approver`, the approver signs alongside it; until then
+    /// the company runs payroll alone.
+    pub fn process_payroll_batch<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ProcessPayrollBatch<'info>>,
+        batch_id: [u8; 32],
+        total_amount: u64,
+        payment_count: u16,
+        amounts: Vec<u64>,
+    ) -> Result<()> {
+        run_payroll_batch(
+            ctx.accounts,
+            &ctx.bumps,
+            ctx.remaining_accounts,
+            batch_id,
+            total_amount,
+            payment_count,
+            amounts,
+        )
+    }
+
+    /// Name the second key that must sign the company's payroll batches,
+    /// replacing any approver it had. The approver can't be the company
+    /// itself. Replacing one takes the current approver's signature too, so
+    /// the company can't drop the check on its own.
+    pub fn set_payroll_approver(ctx: Context<SetPayrollApprover>, approver: Pubkey) -> Result<()> {
+        let company = ctx.accounts.company.key();
+        require_keys_neq!(approver, company, VaultError::InvalidPayrollApprover);
+
+        let payroll_approver_config = &mut ctx.accounts.payroll_approver_config;
+        // Default for a company naming its first approver
+        let previous_approver = payroll_approver_config.approver;
+        if previous_approver != Pubkey::default() {
+            let current_approver = ctx.accounts.current_approver.as_ref().ok_or(VaultError::Unauthorized)?;
+            require_keys_eq!(current_approver.key(), previous_approver, VaultError::Unauthorized);
+        }
+        payroll_approver_config.company = company;
+        payroll_approver_config.approver = approver;
+        payroll_approver_config.bump = ctx.bumps.payroll_approver_config;
+
+        emit!(PayrollApproverChanged {
+            company,
+            previous_approver,
+            approver,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Schedule a company's payroll in `mint`: due first at `next_due_at`,
+    /// then every `frequency_seconds`, up to `authorized_amount` a run
+    pub fn create_payroll_schedule(
+        ctx: Context<CreatePayrollSchedule>,
+        schedule_id: [u8; 32],
+        frequency_seconds: u64,
+        next_due_at: i64,
+        authorized_amount: u64,
+    ) -> Result<()> {
+        require!(
+            valid_payroll_schedule(frequency_seconds, authorized_amount),
+            VaultError::InvalidSchedule
+        );
+
+        let schedule = &mut ctx.accounts.payroll_schedule;
+        schedule.company = ctx.accounts.company.key();
+        schedule.schedule_id = schedule_id;
+        schedule.frequency_seconds = frequency_seconds;
+        schedule.next_due_at = next_due_at;
+        schedule.authorized_amount = authorized_amount;
+        schedule.currency_mint = ctx.accounts.mint.key();
+        schedule.status = ScheduleStatus::Active;
+        schedule.bump = ctx.bumps.payroll_schedule;
+
+        emit!(PayrollScheduleSet {
+            schedule_id,
+            company: schedule.company,
+            frequency_seconds,
+            next_due_at,
+            authorized_amount,
+            status: schedule.status,
+        });
+
+        Ok(())
+    }
+
+    /// Change a payroll schedule's timing, its limit per run, or pause and
+    /// resume it, signed by its company
+    pub fn update_payroll_schedule(
+        ctx: Context<UpdatePayrollSchedule>,
+        schedule_id: [u8; 32],
+        frequency_seconds: u64,
+        next_due_at: i64,
+        authorized_amount: u64,
+        status: ScheduleStatus,
+    ) -> Result<()> {
+        require!(
+            valid_payroll_schedule(frequency_seconds, authorized_amount),
+            VaultError::InvalidSchedule
+        );
+
+        let schedule = &mut ctx.accounts.payroll_schedule;
+        schedule.frequency_seconds = frequency_seconds;
+        schedule.next_due_at = next_due_at;
+        schedule.authorized_amount = authorized_amount;
+        schedule.status = status;
+
+        emit!(PayrollScheduleSet {
+            schedule_id,
+            company: schedule.company,
+            frequency_seconds,
+            next_due_at,
+            authorized_amount,
+            status,
+        });
+
+        Ok(())
+    }
+
+    /// Run a scheduled payroll as `process_payroll_batch` does, once it is
+    /// due, moving its due date on by one period. The batch is paid in the
+    /// schedule's mint and can't exceed its authorized amount. A run that
+    /// is more than a period late leaves the schedule due again, so missed
+    /// runs can catch up.
+    pub fn execute_scheduled_payroll<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ExecuteScheduledPayroll<'info>>,
+        batch_id: [u8; 32],
+        schedule_id: [u8; 32],
+        total_amount: u64,
+        payment_count: u16,
+        amounts: Vec<u64>,
+    ) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let schedule = &mut ctx.accounts.payroll_schedule;
+        require!(schedule.status == ScheduleStatus::Active, VaultError::ScheduleNotActive);
+        require!(now >= schedule.next_due_at, VaultError::ScheduleNotDue);
+        require!(
+            total_amount <= schedule.authorized_amount,
+            VaultError::ScheduleAmountExceeded
+        );
+        require_keys_eq!(ctx.accounts.batch.mint.key(), schedule.currency_mint, VaultError::MintMismatch);
+        let frequency = i64::try_from(schedule.frequency_seconds).map_err(|_| VaultError::MathOverflow)?;
+        schedule.next_due_at = schedule
+            .next_due_at
+            .checked_add(frequency)
+            .ok_or(VaultError::MathOverflow)?;
+
+        emit!(ScheduledPayrollExecuted {
+            schedule_id,
+            company: schedule.company,
+            batch_id,
+            next_due_at: schedule.next_due_at,
+        });
+
+        run_payroll_batch(
+            &mut ctx.accounts.batch,
+            &ctx.bumps.batch,
+            ctx.remaining_accounts,
+            batch_id,
+            total_amount,
+            payment_count,
+            amounts,
+        )
+    }
+
+    /// Close a payroll batch's record and its escrow, returning their rent
+    /// to the company that ran it. Only once the batch is final: its escrow
+    /// is empty and BATCH_RECORD_RETENTION has passed since it ran, so
+    /// recent payrolls stay on-chain. The batch's DisbursementRecords are
+    /// kept.
+    pub fn close_batch_record(ctx: Context<CloseBatchRecord>) -> Result<()> {
+        require!(!ctx.accounts.vault_config.paused, VaultError::VaultPaused);
+        let batch_record = &ctx.accounts.batch_record;
+        let now = Clock::get()?.unix_timestamp;
+        let retained_until = batch_record
+            .timestamp
+            .checked_add(BATCH_RECORD_RETENTION)
+            .ok_or(VaultError::MathOverflow)?;
+        require!(
+            ctx.accounts.escrow.amount == 0 && now >= retained_until,
+            VaultError::BatchNotFinal
+        );
+
+        let bump = [batch_record.bump];
+        let batch_seeds: &[&[u8]] = &[b"batch", batch_record.batch_id.as_ref(), &bump];
+        let cpi_accounts = CloseAccount {
+            account: ctx.accounts.escrow.to_account_info(),
+            destination: ctx.accounts.company.to_account_info(),
+            authority: ctx.accounts.batch_record.to_account_info(),
+        };
+        let signer = &[batch_seeds];
+        let cpi_ctx = CpiContext::new_with_signer(ctx.accounts.token_program.to_account_info(), cpi_accounts, signer);
+        token::close_account(cpi_ctx)?;
+
+        emit!(BatchRecordClosed {
+            batch_id: batch_record.batch_id,
+            company: batch_record.company,
+            timestamp: now,
+        });
+
+        Ok(())
+    }
+
+    /// Close a payment's record once it is older than the vault's
+    /// retention period, returning its rent to the payer, who signs. A
+    /// disputed payment's record is kept until the dispute is resolved.
+    pub fn close_payment_record(ctx: Context<ClosePaymentRecord>, payment_id: [u8; 32]) -> Result<()> {
+        require!(!ctx.accounts.vault_config.paused, VaultError::VaultPaused);
+        let payment_record = &ctx.accounts.payment_record;
+        require!(
+            payment_record.status != PaymentStatus::Disputed,
+            VaultError::PaymentUnderDispute
+        );
+        let now = Clock::get()?.unix_timestamp;
+        let retention = ctx.accounts.vault_config.retention_days() as i64 * DailyVolumeRecord::DAY_SECS;
+        require!(
+            payment_record.timestamp < now.saturating_sub(retention),
+            VaultError::RetentionPeriodNotElapsed
+        );
+
+        emit!(PaymentRecordClosed {
+            payment_id,
+            payer: payment_record.payer,
+            rent_reclaimed: payment_record.to_account_info().lamports(),
+            commitment: payment_record.commitment,
+            closed_by: payment_record.payer,
+        });
+
+        Ok(())
+    }
+
+    /// Close a payment's record for compliance cleanup, at any age. The
+    /// authority signs, but the rent still goes back to the payer, who paid
+    /// it. A disputed payment's record is kept until the dispute is
+    /// resolved.
+    pub fn compliance_close_payment_record(
+        ctx: Context<ComplianceClosePaymentRecord>,
+        payment_id: [u8; 32],
+    ) -> Result<()> {
+        require!(!ctx.accounts.vault_config.paused, VaultError::VaultPaused);
+        let payment_record = &ctx.accounts.payment_record;
+        require!(
+            payment_record.status != PaymentStatus::Disputed,
+            VaultError::PaymentUnderDispute
+        );
+
+        emit!(PaymentRecordClosed {
+            payment_id,
+            payer: payment_record.payer,
+            rent_reclaimed: payment_record.to_account_info().lamports(),
+            commitment: payment_record.commitment,
+            closed_by: ctx.accounts.authority.key(),
+        });
+
+        Ok(())
+    }
+
+    /// Check that a payment's stored commitment opens to `plaintext_amount`
+    /// with `blinding_factor`, failing with CommitmentMismatch otherwise.
+    /// The commitment is kept as raw bytes, so they are compared directly
+    /// rather than as the hex the service shows. Nothing is written, so
+    /// simulating it proves an amount without sending a transaction.
+    pub fn verify_payment_commitment(
+        ctx: Context<VerifyPaymentCommitment>,
+        plaintext_amount: u64,
+        blinding_factor: [u8; 32],
+    ) -> Result<()> {
+        require!(
+            payment_commitment(plaintext_amount, &blinding_factor) == ctx.accounts.payment_record.commitment,
+            VaultError::CommitmentMismatch
+        );
+
+        Ok(())
+    }
+
+    /// Update vault fee configuration
+    pub fn update_fee(ctx: Context<UpdateFee>, new_fee_basis_points: u16) -> Result<()> {
+        require!(new_fee_basis_points <= 1000, VaultError::FeeTooHigh); // Max 10%
+
+        let vault_config = &mut ctx.accounts.vault_config;
+        let old_fee = vault_config.fee_basis_points;
+        vault_config.fee_basis_points = new_fee_basis_points;
+
+        emit!(FeeUpdated {
+            old_fee,
+            new_fee: new_fee_basis_points,
+        });
+
+        Ok(())
+    }
+
+    /// Change the range of accepted payment amounts; a `max_payment` of 0
+    /// removes the upper bound
+    pub fn set_payment_bounds(ctx: Context<SetPaymentBounds>, min_payment: u64, max_payment: u64) -> Result<()> {
+        require!(valid_payment_bounds(min_payment, max_payment), VaultError::InvalidAmount);
+
+        let vault_config = &mut ctx.accounts.vault_config;
+        vault_config.min_payment = min_payment;
+        vault_config.max_payment = max_payment;
+
+        emit!(PaymentBoundsUpdated {
+            min_payment,
+            max_payment,
+        });
+
+        Ok(())
+    }
+
+    /// Cap each payer's payments per rolling hour, by count and by volume in
+    /// base units; 0 lifts either cap
+    pub fn set_rate_limit_config(
+        ctx: Context<SetRateLimitConfig>,
+        max_payments_per_hour: u16,
+        max_volume_per_hour: u64,
+    ) -> Result<()> {
+        let vault_config = &mut ctx.accounts.vault_config;
+        vault_config.max_payments_per_hour = max_payments_per_hour;
+        vault_config.max_volume_per_hour = max_volume_per_hour;
+
+        emit!(RateLimitConfigUpdated {
+            max_payments_per_hour,
+            max_volume_per_hour,
+        });
+
+        Ok(())
+    }
+
+    /// Cap the volume all payers together may pay per UTC day, in base
+    /// units summed across mints like `total_volume`; 0 lifts the cap
+    pub fn set_daily_volume_cap(ctx: Context<SetDailyVolumeCap>, daily_volume_cap: u64) -> Result<()> {
+        ctx.accounts.vault_config.daily_volume_cap = daily_volume_cap;
+
+        emit!(DailyVolumeCapUpdated { daily_volume_cap });
+
+        Ok(())
+    }
+
+    /// Keep PaymentRecords for `retention_days` before their payers can
+    /// close them; at least MIN_RETENTION_DAYS
+    pub fn set_retention_days(ctx: Context<SetRetentionDays>, retention_days: u16) -> Result<()> {
+        require!(retention_days >= MIN_RETENTION_DAYS, VaultError::RetentionTooShort);
+        ctx.accounts.vault_config.retention_days = retention_days;
+
+        emit!(RetentionDaysUpdated { retention_days });
+
+        Ok(())
+    }
+
+    /// Grow a VaultConfig written before its newer fields existed to the
+    /// current layout, the authority paying the extra rent. Those fields
+    /// start at zero: no payment bounds, and fees counted from the migration
+    /// on. An up-to-date account is left as it is.
+    pub fn migrate_vault_config(ctx: Context<MigrateVaultConfig>) -> Result<()> {
+        let info = ctx.accounts.vault_config.to_account_info();
+        let old_len = info.data_len();
+        {
+            let data = info.try_borrow_data()?;
+            require!(
+                old_len >= 8 + 32 && data[..8] == VaultConfig::DISCRIMINATOR,
+                ErrorCode::AccountDiscriminatorMismatch
+            );
+            let authority = Pubkey::try_from(&data[8..40]).map_err(|_| ErrorCode::AccountDidNotDeserialize)?;
+            require_keys_eq!(authority, ctx.accounts.authority.key(), VaultError::Unauthorized);
+        }
+
+        let new_len = 8 + VaultConfig::INIT_SPACE;
+        if old_len >= new_len {
+            return Ok(());
+        }
+        grow_account(&info, new_len, &ctx.accounts.authority, &ctx.accounts.system_program)?;
+
+        emit!(VaultConfigMigrated {
+            old_len: old_len as u32,
+            new_len: new_len as u32,
+        });
+
+        Ok(())
+    }
+
+    /// Grow a PaymentRecord written in an older layout to the current one,
+    /// the authority paying the extra rent, so it deserializes again (and
+    /// can be refunded). Fields the record predates read as zero; an
+    /// up-to-date record is left as it is.
+    pub fn resize_payment_record(ctx: Context<ResizePaymentRecord>) -> Result<()> {
+        let info = ctx.accounts.payment_record.to_account_info();
+        let old_len = info.data_len();
+        require!(
+            old_len >= 8 && info.try_borrow_data()?[..8] == PaymentRecord::DISCRIMINATOR,
+            ErrorCode::AccountDiscriminatorMismatch
+        );
+
+        let new_len = 8 + PaymentRecord::INIT_SPACE;
+        if old_len >= new_len {
+            return Ok(());
+        }
+        grow_account(&info, new_len, &ctx.accounts.authority, &ctx.accounts.system_program)?;
+        info.try_borrow_mut_data()?[PaymentRecord::RECORD_VERSION_OFFSET] = RECORD_VERSION;
+
+        emit!(PaymentRecordResized {
+            record: info.key(),
+            old_len: old_len as u32,
+            new_len: new_len as u32,
+        });
+
+        Ok(())
+    }
+
+    /// Send future fees to a new collector
+    pub fn update_fee_collector(ctx: Context<UpdateFeeCollector>) -> Result<()> {
+        let vault_config = &mut ctx.accounts.vault_config;
+        let old_fee_collector = vault_config.fee_collector;
+        vault_config.fee_collector = ctx.accounts.new_fee_collector.key();
+        vault_config.fee_collectors[0].collector = vault_config.fee_collector;
+
+        emit!(FeeCollectorUpdated {
+            old_fee_collector,
+            new_fee_collector: vault_config.fee_collector,
+        });
+
+        Ok(())
+    }
+
+    /// Divide payment fees among 1 to MAX_FEE_COLLECTORS collectors by
+    /// basis points adding up to 10000; the first becomes `fee_collector`
+    /// and takes the rounding dust. Each PaymentRecord keeps who it paid,
+    /// so a refund returns the shares from those collectors whatever the
+    /// division is by then.
+    pub fn set_fee_collectors(ctx: Context<SetFeeCollectors>, fee_collectors: Vec<FeeCollectorShare>) -> Result<()> {
+        require!(valid_fee_collectors(&fee_collectors), VaultError::InvalidFeeCollectors);
+
+        let vault_config = &mut ctx.accounts.vault_config;
+        vault_config.fee_collectors = Default::default();
+        vault_config.fee_collectors[..fee_collectors.len()].copy_from_slice(&fee_collectors);
+        vault_config.fee_collector = fee_collectors[0].collector;
+
+        emit!(FeeCollectorsUpdated { fee_collectors });
+
+        Ok(())
+    }
+
+    /// Charge a merchant a negotiated fee instead of the vault-wide one
+    pub fn set_merchant_fee_override(
+        ctx: Context<SetMerchantFeeOverride>,
+        fee_override_basis_points: u16,
+    ) -> Result<()> {
+        require!(fee_override_basis_points <= 1000, VaultError::FeeTooHigh); // Max 10%
+
+        let merchant_config = &mut ctx.accounts.merchant_config;
+        merchant_config.merchant = ctx.accounts.merchant.key();
+        merchant_config.fee_override_basis_points = fee_override_basis_points;
+        merchant_config.is_active = true;
+        merchant_config.bump = ctx.bumps.merchant_config;
+
+        emit!(MerchantFeeOverrideSet {
+            merchant: merchant_config.merchant,
+            fee_override_basis_points,
+        });
+
+        Ok(())
+    }
+
+    /// Return a merchant to the vault-wide fee; the account is kept so the
+    /// override can be set again
+    pub fn remove_merchant_fee_override(ctx: Context<RemoveMerchantFeeOverride>) -> Result<()> {
+        let merchant_config = &mut ctx.accounts.merchant_config;
+        merchant_config.is_active = false;
+
+        emit!(MerchantFeeOverrideRemoved {
+            merchant: merchant_config.merchant,
+        });
+
+        Ok(())
+    }
+
+    /// Set or clear a merchant's fee in one instruction: `Some` charges it
+    /// as `set_merchant_fee_override` does, `None` returns the merchant to
+    /// the vault-wide fee as `remove_merchant_fee_override` does
+    pub fn set_merchant_fee(ctx: Context<SetMerchantFeeOverride>, fee_override_bps: Option<u16>) -> Result<()> {
+        if let Some(fee_override_bps) = fee_override_bps {
+            require!(fee_override_bps <= 1000, VaultError::FeeTooHigh); // Max 10%
+        }
+
+        let merchant_config = &mut ctx.accounts.merchant_config;
+        merchant_config.merchant = ctx.accounts.merchant.key();
+        if let Some(fee_override_bps) = fee_override_bps {
+            merchant_config.fee_override_basis_points = fee_override_bps;
+        }
+        merchant_config.is_active = fee_override_bps.is_some();
+        merchant_config.bump = ctx.bumps.merchant_config;
+
+        emit!(MerchantFeeUpdated {
+            merchant: merchant_config.merchant,
+            fee_override_bps,
+        });
+
+        Ok(())
+    }
+
+    /// Waive all fees on a merchant's payments, or charge them again. The
+    /// exemption outlasts the fee override, which it takes precedence over.
+    pub fn set_merchant_fee_exempt(ctx: Context<SetMerchantFeeExempt>, fee_exempt: bool) -> Result<()> {
+        let merchant_config = &mut ctx.accounts.merchant_config;
+        merchant_config.merchant = ctx.accounts.merchant.key();
+        merchant_config.fee_exempt = fee_exempt;
+        merchant_config.bump = ctx.bumps.merchant_config;
+
+        emit!(MerchantFeeExemptionUpdated {
+            merchant: merchant_config.merchant,
+            fee_exempt,
+        });
+
+        Ok(())
+    }
+
+    /// Accept payments in `mint` while the vault requires a whitelisted mint;
+    /// also re-activates a removed one
+    pub fn add_accepted_mint(ctx: Context<AddAcceptedMint>) -> Result<()> {
+        let accepted_mint = &mut ctx.accounts.accepted_mint;
+        accepted_mint.mint = ctx.accounts.mint.key();
+        accepted_mint.is_active = true;
+        accepted_mint.bump = ctx.bumps.accepted_mint;
+
+        emit!(AcceptedMintAdded {
+            mint: accepted_mint.mint,
+        });
+
+        Ok(())
+    }
+
+    /// Stop accepting a mint; the account is kept so it can be added again
+    pub fn remove_accepted_mint(ctx: Context<RemoveAcceptedMint>) -> Result<()> {
+        let accepted_mint = &mut ctx.accounts.accepted_mint;
+        accepted_mint.is_active = false;
+
+        emit!(AcceptedMintRemoved {
+            mint: accepted_mint.mint,
+        });
+
+        Ok(())
+    }
+
+    /// Charge payments in `mint` their own fee instead of the vault-wide one
+    pub fn set_vault_mint_config(ctx: Context<SetVaultMintConfig>, fee_basis_points_override: u16) -> Result<()> {
+        require!(fee_basis_points_override <= 1000, VaultError::FeeTooHigh); // Max 10%
+
+        let vault_mint_config = &mut ctx.accounts.vault_mint_config;
+        vault_mint_config.mint = ctx.accounts.mint.key();
+        vault_mint_config.fee_basis_points_override = fee_basis_points_override;
+        vault_mint_config.is_active = true;
+        vault_mint_config.bump = ctx.bumps.vault_mint_config;
+
+        emit!(VaultMintConfigSet {
+            mint: vault_mint_config.mint,
+            fee_basis_points_override,
+        });
+
+        Ok(())
+    }
+
+    /// Return a mint to the vault-wide fee; the account is kept so the
+    /// override can be set again
+    pub fn remove_vault_mint_config(ctx: Context<RemoveVaultMintConfig>) -> Result<()> {
+        let vault_mint_config = &mut ctx.accounts.vault_mint_config;
+        vault_mint_config.is_active = false;
+
+        emit!(VaultMintConfigRemoved {
+            mint: vault_mint_config.mint,
+        });
+
+        Ok(())
+    }
+
+    /// Turn the mint whitelist on or off. While on, payments must pass the
+    /// mint's active `AcceptedMint`; while off, any mint is accepted.
+    pub fn set_mint_whitelist_required(ctx: Context<SetMintWhitelistRequired>, required: bool) -> Result<()> {
+        ctx.accounts.vault_config.require_mint_whitelist = required;
+
+        emit!(MintWhitelistRequirementUpdated { required });
+
+        Ok(())
+    }
+
+    /// Let `merchant` receive payments while the vault requires registered
+    /// merchants; also re-activates a deregistered one
+    pub fn register_merchant(ctx: Context<RegisterMerchant>) -> Result<()> {
+        let registered_merchant = &mut ctx.accounts.registered_merchant;
+        registered_merchant.merchant = ctx.accounts.merchant.key();
+        registered_merchant.is_active = true;
+        registered_merchant.bump = ctx.bumps.registered_merchant;
+
+        emit!(MerchantRegistered {
+            merchant: registered_merchant.merchant,
+        });
+
+        Ok(())
+    }
+
+    /// Deactivate a merchant's registration; the account is kept so it can
+    /// be registered again
+    pub fn deregister_merchant(ctx: Context<DeregisterMerchant>) -> Result<()> {
+        let registered_merchant = &mut ctx.accounts.registered_merchant;
+        registered_merchant.is_active = false;
+
+        emit!(MerchantDeregistered {
+            merchant: registered_merchant.merchant,
+        });
+
+        Ok(())
+    }
+
+    /// Turn merchant registration on or off. While on, `process_payment`
+    /// and `process_payment_cpi` must pass the merchant's active
+    /// `RegisteredMerchant`; while off, any merchant is paid.
+    pub fn set_merchant_registration_required(
+        ctx: Context<SetMerchantRegistrationRequired>,
+        required: bool,
+    ) -> Result<()> {
+        ctx.accounts.vault_config.require_registered_merchants = required;
+
+        emit!(MerchantRegistrationRequirementUpdated { required });
+
+        Ok(())
+    }
+
+    /// Refuse every payment from `payer`, whether settled, split, batched,
+    /// in SOL, escrowed, timelocked or streamed, until it is removed from
+    /// the blacklist. `reason_hash` is a hash of the off-chain reason, such
+    /// as a fraud case id.
+    pub fn blacklist_payer(ctx: Context<BlacklistPayer>, reason_hash: [u8; 32]) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let blacklisted_payer = &mut ctx.accounts.blacklisted_payer;
+        blacklisted_payer.payer = ctx.accounts.payer.key();
+        blacklisted_payer.reason_hash = reason_hash;
+        blacklisted_payer.blacklisted_at = now;
+        blacklisted_payer.bump = ctx.bumps.blacklisted_payer;
+
+        emit!(BlacklistedPayerAdded {
+            payer: blacklisted_payer.payer,
+            reason_hash,
+            timestamp: now,
+        });
+
+        Ok(())
+    }
+
+    /// Let a blacklisted payer pay again, closing its `BlacklistedPayer`
+    pub fn remove_from_blacklist(ctx: Context<RemoveFromBlacklist>) -> Result<()> {
+        emit!(BlacklistedPayerRemoved {
+            payer: ctx.accounts.blacklisted_payer.payer,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Turn on-chain commitment checks on or off. While on, every payment
+    /// instruction rejects a commitment that isn't
+    /// `sha256(amount_le || blinding_factor)`, at the cost of a hash per
+    /// payment. Records written while it was off were stored unchecked;
+    /// timelocked and streamed payments carry no commitment.
+    pub fn set_commitment_verification(ctx: Context<SetCommitmentVerification>, enabled: bool) -> Result<()> {
+        ctx.accounts.vault_config.verify_commitments = enabled;
+
+        emit!(CommitmentVerificationUpdated { enabled });
+
+        Ok(())
+    }
+
+    /// Replace the volume tiers of `process_payment` fees. `tiers` must be
+    /// sorted by strictly ascending `volume_threshold`; from then on payments
+    /// must pass the `FeeSchedule` account.
+    pub fn set_fee_schedule(ctx: Context<SetFeeSchedule>, tiers: Vec<FeeThreshold>) -> Result<()> {
+        require!(
+            (1..=MAX_FEE_TIERS).contains(&tiers.len())
+                && tiers.windows(2).all(|pair| pair[0].volume_threshold < pair[1].volume_threshold),
+            VaultError::InvalidFeeSchedule
+        );
+        require!(tiers.iter().all(|tier| tier.fee_basis_points <= 1000), VaultError::FeeTooHigh); // Max 10%
+
+        let fee_schedule = &mut ctx.accounts.fee_schedule;
+        fee_schedule.tiers = tiers.clone();
+        fee_schedule.bump = ctx.bumps.fee_schedule;
+        ctx.accounts.vault_config.has_fee_schedule = true;
+
+        emit!(FeeScheduleSet { tiers });
+
+        Ok(())
+    }
+
+    /// Return to charging `fee_basis_points` at any volume, refunding the
+    /// schedule's rent to the authority
+    pub fn clear_fee_schedule(ctx: Context<ClearFeeSchedule>) -> Result<()> {
+        ctx.accounts.vault_config.has_fee_schedule = false;
+
+        emit!(FeeScheduleCleared {
+            authority: ctx.accounts.authority.key(),
+        });
+
+        Ok(())
+    }
+
+    /// Tier `process_payment` fees by the merchant's own volume, from its
+    /// `MerchantStats`, rather than the vault's; or go back to the vault's.
+    /// The tiers are still the `FeeSchedule` from `set_fee_schedule`: there
+    /// is no separate merchant tier table in `VaultConfig`, so both volumes
+    /// are measured against one schedule. Every other payment instruction
+    /// stays tiered by the vault's volume.
+    pub fn set_merchant_volume_tiers(ctx: Context<SetMerchantVolumeTiers>, enabled: bool) -> Result<()> {
+        ctx.accounts.vault_config.merchant_volume_tiers = enabled;
+
+        emit!(MerchantVolumeTiersUpdated { enabled });
+
+        Ok(())
+    }
+
+    /// Round fees up to the next base unit rather than down, so the
+    /// smallest payments are still charged. This covers every fee the vault
+    /// charges, split payments included: their first share gives up what
+    /// the fee gains.
+    pub fn set_fee_rounding(ctx: Context<SetFeeRounding>, round_fee_up: bool) -> Result<()> {
+        ctx.accounts.vault_config.round_fee_up = round_fee_up;
+
+        emit!(FeeRoundingUpdated { round_fee_up });
+
+        Ok(())
+    }
+
+    /// Halt every instruction that moves tokens or lamports until unpaused:
+    /// payments, payroll batches, refunds, escrow and stream settlement and
+    /// record closes. Authority recovery stays available while paused.
+    pub fn pause_vault(ctx: Context<PauseVault>) -> Result<()> {
+        ctx.accounts.vault_config.paused = true;
+
+        emit!(VaultPaused {
+            authority: ctx.accounts.authority.key(),
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Resume processing after a pause
+    pub fn unpause_vault(ctx: Context<UnpauseVault>) -> Result<()> {
+        ctx.accounts.vault_config.paused = false;
+
+        emit!(VaultUnpaused {
+            authority: ctx.accounts.authority.key(),
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Offer vault authority to `new_authority` for
+    /// AUTHORITY_TRANSFER_WINDOW, replacing any earlier offer. Nothing
+    /// changes until it signs `accept_authority`, so a mistyped key can't
+    /// take the vault out of reach.
+    pub fn propose_authority(ctx: Context<ProposeAuthority>) -> Result<()> {
+        let expires_at = Clock::get()?
+            .unix_timestamp
+            .checked_add(AUTHORITY_TRANSFER_WINDOW)
+            .ok_or(VaultError::MathOverflow)?;
+        let vault_config = &mut ctx.accounts.vault_config;
+        vault_config.pending_authority = Some(ctx.accounts.new_authority.key());
+        vault_config.authority_transfer_expires_at = expires_at;
+
+        emit!(AuthorityTransferProposed {
+            authority: vault_config.authority,
+            pending_authority: ctx.accounts.new_authority.key(),
+            expires_at,
+        });
+
+        Ok(())
+    }
+
+    /// Take over vault authority as the proposed key, before the offer
+    /// expires
+    pub fn accept_authority(ctx: Context<AcceptAuthority>) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let vault_config = &mut ctx.accounts.vault_config;
+        require!(now < vault_config.authority_transfer_expires_at, VaultError::AuthorityTransferExpired);
+
+        let old_authority = vault_config.authority;
+        vault_config.authority = ctx.accounts.pending_authority.key();
+        vault_config.pending_authority = None;
+        vault_config.authority_transfer_expires_at = 0;
+
+        emit!(AuthorityTransferred {
+            old_authority,
+            new_authority: vault_config.authority,
+        });
+
+        Ok(())
+    }
+
+    /// Withdraw an authority offer before it is accepted
+    pub fn cancel_authority_transfer(ctx: Context<CancelAuthorityTransfer>) -> Result<()> {
+        let vault_config = &mut ctx.accounts.vault_config;
+        let pending_authority = vault_config
+            .pending_authority
+            .take()
+            .ok_or(VaultError::NoPendingAuthority)?;
+        vault_config.authority_transfer_expires_at = 0;
+
+        emit!(AuthorityTransferCancelled {
+            authority: vault_config.authority,
+            pending_authority,
+        });
+
+        Ok(())
+    }
+
+    /// Name the key that can recover the vault if the authority key is lost,
+    /// or none. Any recovery already proposed is dropped.
+    pub fn set_recovery_authority(
+        ctx: Context<SetRecoveryAuthority>,
+        recovery_authority: Option<Pubkey>,
+    ) -> Result<()> {
+        let vault_config = &mut ctx.accounts.vault_config;
+        vault_config.recovery_authority = recovery_authority;
+        vault_config.pending_recovery_authority = None;
+        vault_config.recovery_proposed_at = 0;
+
+        emit!(RecoveryAuthorityUpdated { recovery_authority });
+
+        Ok(())
+    }
+
+    /// Start handing the vault to `new_authority` as the recovery authority.
+    /// It can be executed after EMERGENCY_RECOVERY_DELAY, leaving a live
+    /// authority a day to cancel it; proposing again restarts the delay.
+    pub fn propose_emergency_recovery(ctx: Context<ProposeEmergencyRecovery>) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let vault_config = &mut ctx.accounts.vault_config;
+        vault_config.pending_recovery_authority = Some(ctx.accounts.new_authority.key());
+        vault_config.recovery_proposed_at = now;
+
+        emit!(EmergencyRecoveryProposed {
+            recovery_authority: ctx.accounts.recovery_authority.key(),
+            new_authority: ctx.accounts.new_authority.key(),
+            executable_at: now.checked_add(EMERGENCY_RECOVERY_DELAY).ok_or(VaultError::MathOverflow)?,
+        });
+
+        Ok(())
+    }
+
+    /// Replace the vault authority with the proposed key once the delay has
+    /// passed. Any authority transfer the old authority had offered is
+    /// withdrawn.
+    pub fn emergency_recover(ctx: Context<EmergencyRecover>) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let vault_config = &mut ctx.accounts.vault_config;
+        let new_authority = vault_config
+            .pending_recovery_authority
+            .ok_or(VaultError::NoPendingRecovery)?;
+        require_keys_eq!(ctx.accounts.new_authority.key(), new_authority, VaultError::Unauthorized);
+        let executable_at = vault_config
+            .recovery_proposed_at
+            .checked_add(EMERGENCY_RECOVERY_DELAY)
+            .ok_or(VaultError::MathOverflow)?;
+        require!(now >= executable_at, VaultError::RecoveryTimelockActive);
+
+        let old_authority = vault_config.authority;
+        vault_config.authority = new_authority;
+        vault_config.pending_authority = None;
+        vault_config.authority_transfer_expires_at = 0;
+        vault_config.pending_recovery_authority = None;
+        vault_config.recovery_proposed_at = 0;
+
+        emit!(EmergencyRecoveryExecuted {
+            old_authority,
+            new_authority,
+            recovery_authority: ctx.accounts.recovery_authority.key(),
+        });
+
+        Ok(())
+    }
+
+    /// Stop a proposed emergency recovery, as the authority it would replace
+    pub fn cancel_emergency_recovery(ctx: Context<CancelEmergencyRecovery>) -> Result<()> {
+        let vault_config = &mut ctx.accounts.vault_config;
+        let new_authority = vault_config
+            .pending_recovery_authority
+            .take()
+            .ok_or(VaultError::NoPendingRecovery)?;
+        vault_config.recovery_proposed_at = 0;
+
+        emit!(EmergencyRecoveryCancelled {
+            authority: vault_config.authority,
+            new_authority,
+        });
+
+        Ok(())
+    }
+}
+
+/// The fee on `amount`, rounded down, and what the merchant receives
+pub fn split_payment(amount: u64, fee_basis_points: u16) -> Result<(u64, u64)> {
+    split_payment_rounded(amount, fee_basis_points, false)
+}
+
+/// `split_payment` with the fee rounded up instead while `round_up` is set.
+/// Rounded either way, the fee never exceeds `amount`: a rate above 10000
+/// basis points fails as AmountTooSmall.
+pub fn split_payment_rounded(amount: u64, fee_basis_points: u16, round_up: bool) -> Result<(u64, u64)> {
+    let rounding = if round_up { 9_999 } else { 0 };
+    let fee = (amount as u128)
+        .checked_mul(fee_basis_points as u128)
+        .and_then(|fee| fee.checked_add(rounding))
+        .and_then(|fee| fee.checked_div(10_000))
+        .and_then(|fee| u64::try_from(fee).ok())
+        .ok_or(VaultError::MathOverflow)?;
+    let net_amount = amount.checked_sub(fee).ok_or(VaultError::AmountTooSmall)?;
+    Ok((fee, net_amount))
+}
+
+/// What the payer is debited, the fee and what the merchant receives for a
+/// payment of `amount`. The fee is charged on `amount` either way;
+/// `FeeMode::ExactNet` adds it on top rather than deducting it.
+pub fn payment_amounts(
+    amount: u64,
+    fee_basis_points: u16,
+    fee_mode: FeeMode,
+    round_fee_up: bool,
+) -> Result<(u64, u64, u64)> {
+    let (fee, net_amount) = split_payment_rounded(amount, fee_basis_points, round_fee_up)?;
+    match fee_mode {
+        FeeMode::Deducted => Ok((amount, fee, net_amount)),
+        FeeMode::ExactNet => {
+            let gross_amount = amount.checked_add(fee).ok_or(VaultError::MathOverflow)?;
+            Ok((gross_amount, fee, amount))
+        }
+    }
+}
+
+/// The fee on `amount` and each split's share of it. Shares are rounded
+/// down, with what that leaves of the net amount added to the first, so
+/// they always add up to `amount` less the fee.
+pub fn split_shares(amount: u64, fee_basis_points: u16, basis_points: &[u16]) -> Result<(u64, Vec<u64>)> {
+    split_shares_rounded(amount, fee_basis_points, basis_points, false)
+}
+
+/// `split_shares` with the fee rounded up instead while `round_up` is set;
+/// the first share then gives up what the fee gains
+pub fn split_shares_rounded(
+    amount: u64,
+    fee_basis_points: u16,
+    basis_points: &[u16],
+    round_up: bool,
+) -> Result<(u64, Vec<u64>)> {
+    require!(
+        !basis_points.is_empty() && basis_points.len() <= MAX_SPLITS,
+        VaultError::InvalidSplit
+    );
+    require!(basis_points.iter().all(|&bps| bps > 0), VaultError::InvalidSplit);
+    let total: u32 = basis_points.iter().map(|&bps| bps as u32).sum();
+    require!(
+        total + fee_basis_points as u32 == 10_000,
+        VaultError::InvalidSplit
+    );
+
+    let (fee, net_amount) = split_payment_rounded(amount, fee_basis_points, round_up)?;
+    let mut shares = basis_points
+        .iter()
+        .map(|&bps| (amount as u128 * bps as u128 / 10_000) as u64)
+        .collect::<Vec<_>>();
+    let dust = net_amount
+        .checked_sub(shares.iter().sum())
+        .ok_or(VaultError::MathOverflow)?;
+    shares[0] += dust;
+    Ok((fee, shares))
+}
+
+/// Divide `fee` by basis points adding up to 10000, rounding each share
+/// down; the first takes the dust
+pub fn split_fee(fee: u64, basis_points: &[u16]) -> Result<Vec<u64>> {
+    let mut shares = basis_points
+        .iter()
+        .map(|&bps| (fee as u128 * bps as u128 / 10_000) as u64)
+        .collect::<Vec<_>>();
+    let dust = fee.checked_sub(shares.iter().sum()).ok_or(VaultError::MathOverflow)?;
+    if let Some(first) = shares.first_mut() {
+        *first += dust;
+    }
+    Ok(shares)
+}
+
+/// The body of `process_payroll_batch`, shared with
+/// `execute_scheduled_payroll`
+fn run_payroll_batch<'info>(
+    accounts: &mut ProcessPayrollBatch<'info>,
+    bumps: &ProcessPayrollBatchBumps,
+    remaining_accounts: &'info [AccountInfo<'info>],
+    batch_id: [u8; 32],
+    total_amount: u64,
+    payment_count: u16,
+    amounts: Vec<u64>,
+) -> Result<()> {
+    require!(!accounts.vault_config.paused, VaultError::VaultPaused);
+    check_payroll_approver(&accounts.payroll_approver_config, accounts.approver.as_ref())?;
+    require!(total_amount > 0, VaultError::InvalidAmount);
+    require!(
+        amounts.len() == payment_count as usize
+            && remaining_accounts.len() == 2 * amounts.len(),
+        VaultError::InvalidDisbursement
+    );
+    require!(amounts.iter().all(|&amount| amount > 0), VaultError::InvalidAmount);
+    let disbursed = amounts
+        .iter()
+        .try_fold(0u64, |sum, &amount| sum.checked_add(amount))
+        .ok_or(VaultError::MathOverflow)?;
+    require!(disbursed == total_amount, VaultError::InvalidDisbursement);
+    require!(
+        accounts.company_token_account.amount >= total_amount,
+        VaultError::InsufficientFunds
+    );
+
+    let mint = accounts.mint.key();
+    let decimals = accounts.mint.decimals;
+    let cpi_accounts = TransferChecked {
+        from: accounts.company_token_account.to_account_info(),
+        mint: accounts.mint.to_account_info(),
+        to: accounts.escrow.to_account_info(),
+        authority: accounts.company.to_account_info(),
+    };
+    let cpi_program = accounts.token_program.to_account_info();
+    let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
+    token::transfer_checked(cpi_ctx, total_amount, decimals)?;
+
+    let timestamp = Clock::get()?.unix_timestamp;
+    let bump = [bumps.batch_record];
+    let batch_seeds: &[&[u8]] = &[b"batch", batch_id.as_ref(), &bump];
+    for (index, (pair, &amount)) in remaining_accounts.chunks(2).zip(&amounts).enumerate() {
+        let (employee, disbursement) = (&pair[0], &pair[1]);
+        let employee_token_account = Account::<'info, TokenAccount>::try_from(employee)?;
+        require_keys_eq!(employee_token_account.mint, mint, VaultError::MintMismatch);
+
+        let cpi_accounts = TransferChecked {
+            from: accounts.escrow.to_account_info(),
+            mint: accounts.mint.to_account_info(),
+            to: employee.clone(),
+            authority: accounts.batch_record.to_account_info(),
+        };
+        let cpi_program = accounts.token_program.to_account_info();
+        let signer = &[batch_seeds];
+        let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
+        token::transfer_checked(cpi_ctx, amount, decimals)?;
+
+        create_disbursement_record(
+            disbursement,
+            &accounts.company,
+            &accounts.system_program,
+            DisbursementRecord {
+                batch_id,
+                index: index as u16,
+                employee_token_account: employee.key(),
+                amount,
+                timestamp,
+                bump: 0,
+            },
+        )?;
+    }
+
+    // Record batch on-chain
+    let batch_record = &mut accounts.batch_record;
+    batch_record.batch_id = batch_id;
+    batch_record.company = accounts.company.key();
+    batch_record.total_amount = total_amount;
+    batch_record.payment_count = payment_count;
+    batch_record.timestamp = timestamp;
+    batch_record.bump = bumps.batch_record;
+    batch_record.escrow = accounts.escrow.key();
+    batch_record.mint = mint;
+    batch_record.record_version = RECORD_VERSION;
+
+    emit!(PayrollBatchProcessed {
+        batch_id,
+        company: accounts.company.key(),
+        total_amount,
+        payment_count,
+        timestamp,
+        escrow: batch_record.escrow,
+        mint,
+    });
+
+    Ok(())
+}
+
+/// Schedules pay something and come round again
+fn valid_payroll_schedule(frequency_seconds: u64, authorized_amount: u64) -> bool {
+    frequency_seconds > 0 && authorized_amount > 0
+}
+
+/// What `rate_per_second` streams from `from` to `until`
+fn stream_amount(rate_per_second: u64, from: i64, until: i64) -> Result<u64> {
+    let seconds = u64::try_from(until.saturating_sub(from)).unwrap_or(0);
+    Ok(rate_per_second.checked_mul(seconds).ok_or(VaultError::MathOverflow)?)
+}
+
+/// Transfer `amount` out of a stream's token account, signed by the stream
+fn pay_from_stream<'info>(
+    stream: &Account<'info, StreamingPayment>,
+    stream_tokens: &Account<'info, TokenAccount>,
+    to: &Account<'info, TokenAccount>,
+    mint: &Account<'info, Mint>,
+    token_program: &Program<'info, Token>,
+    stream_id: &[u8; 32],
+    amount: u64,
+) -> Result<()> {
+    let bump = [stream.bump];
+    let stream_seeds: &[&[u8]] = &[b"stream", stream.sender.as_ref(), stream_id.as_ref(), &bump];
+    let cpi_accounts = TransferChecked {
+        from: stream_tokens.to_account_info(),
+        mint: mint.to_account_info(),
+        to: to.to_account_info(),
+        authority: stream.to_account_info(),
+    };
+    token::transfer_checked(
+        CpiContext::new_with_signer(token_program.to_account_info(), cpi_accounts, &[stream_seeds]),
+        amount,
+        mint.decimals,
+    )
+}
+
+/// Send an escrow's tokens back to the payer and close its token account,
+/// the rent going to `rent_destination`
+fn refund_escrow<'info>(
+    escrow_record: &Account<'info, EscrowRecord>,
+    escrow_tokens: &Account<'info, TokenAccount>,
+    payer_token_account: &Account<'info, TokenAccount>,
+    mint: &Account<'info, Mint>,
+    rent_destination: AccountInfo<'info>,
+    token_program: &Program<'info, Token>,
+    payment_id: &[u8; 32],
+) -> Result<()> {
+    let bump = [escrow_record.bump];
+    let escrow_seeds: &[&[u8]] = &[b"escrow_payment", escrow_record.payer.as_ref(), payment_id.as_ref(), &bump];
+    let signer = &[escrow_seeds];
+    let cpi_program = token_program.to_account_info();
+    let cpi_accounts = TransferChecked {
+        from: escrow_tokens.to_account_info(),
+        mint: mint.to_account_info(),
+        to: payer_token_account.to_account_info(),
+        authority: escrow_record.to_account_info(),
+    };
+    let cpi_ctx = CpiContext::new_with_signer(cpi_program.clone(), cpi_accounts, signer);
+    token::transfer_checked(cpi_ctx, escrow_record.amount, mint.decimals)?;
+    let cpi_accounts = CloseAccount {
+        account: escrow_tokens.to_account_info(),
+        destination: rent_destination,
+        authority: escrow_record.to_account_info(),
+    };
+    token::close_account(CpiContext::new_with_signer(cpi_program, cpi_accounts, signer))
+}
+
+/// Create the `DisbursementRecord` for `record.index`. There is one per
+/// employee, so these come in as remaining accounts rather than `init`
+/// accounts; lamports sent to the address ahead of time don't block it.
+fn create_disbursement_record<'info>(
+    info: &AccountInfo<'info>,
+    company: &Signer<'info>,
+    system_program: &Program<'info, System>,
+    mut record: DisbursementRecord,
+) -> Result<()> {
+    let index = record.index.to_le_bytes();
+    let (address, bump) =
+        Pubkey::find_program_address(&[b"disbursement", record.batch_id.as_ref(), &index], &crate::ID);
+    require_keys_eq!(info.key(), address, VaultError::InvalidDisbursement);
+    record.bump = bump;
+
+    let bump = [bump];
+    let seeds: &[&[u8]] = &[b"disbursement", record.batch_id.as_ref(), &index, &bump];
+    let signer = &[seeds];
+    let space = 8 + DisbursementRecord::INIT_SPACE;
+    let rent = Rent::get()?.minimum_balance(space);
+    let system_program = system_program.to_account_info();
+    if info.lamports() == 0 {
+        let cpi_accounts = system_program::CreateAccount {
+            from: company.to_account_info(),
+            to: info.clone(),
+        };
+        let cpi_ctx = CpiContext::new_with_signer(system_program, cpi_accounts, signer);
+        system_program::create_account(cpi_ctx, rent, space as u64, &crate::ID)?;
+    } else {
+        // Anything owned elsewhere or already holding data fails here
+        let top_up = rent.saturating_sub(info.lamports());
+        if top_up > 0 {
+            let cpi_accounts = system_program::Transfer {
+                from: company.to_account_info(),
+                to: info.clone(),
+            };
+            let cpi_ctx = CpiContext::new(system_program.clone(), cpi_accounts);
+            system_program::transfer(cpi_ctx, top_up)?;
+        }
+        let cpi_accounts = system_program::Allocate { account_to_allocate: info.clone() };
+        let cpi_ctx = CpiContext::new_with_signer(system_program.clone(), cpi_accounts, signer);
+        system_program::allocate(cpi_ctx, space as u64)?;
+        let cpi_accounts = system_program::Assign { account_to_assign: info.clone() };
+        let cpi_ctx = CpiContext::new_with_signer(system_program, cpi_accounts, signer);
+        system_program::assign(cpi_ctx, &crate::ID)?;
+    }
+
+    let mut data = info.try_borrow_mut_data()?;
+    record.try_serialize(&mut &mut data[..])
+}
+
+/// `max_payment` of 0 is unbounded; otherwise it can't be below `min_payment`
+fn valid_payment_bounds(min_payment: u64, max_payment: u64) -> bool {
+    max_payment == 0 || min_payment <= max_payment
+}
+
+/// Each collector has a share, they add up to 10000 and none is repeated
+fn valid_fee_collectors(fee_collectors: &[FeeCollectorShare]) -> bool {
+    let total: u32 = fee_collectors.iter().map(|entry| entry.basis_points as u32).sum();
+    (1..=MAX_FEE_COLLECTORS).contains(&fee_collectors.len())
+        && fee_collectors.iter().all(|entry| entry.basis_points > 0)
+        && total == 10_000
+        && fee_collectors
+            .iter()
+            .enumerate()
+            .all(|(i, entry)| fee_collectors[..i].iter().all(|earlier| earlier.collector != entry.collector))
+}
+
+/// Create a rent-exempt account of this program at a PDA, as Anchor's
+/// `init` does: lamports already sent to the address are kept, and an
+/// account already in use fails
+fn create_pda_account<'info>(
+    info: &AccountInfo<'info>,
+    space: usize,
+    seeds: &[&[u8]],
+    payer: &Signer<'info>,
+    system_program: &Program<'info, System>,
+) -> Result<()> {
+    let lamports = Rent::get()?.minimum_balance(space);
+    let signer = &[seeds];
+    if info.lamports() == 0 {
+        let cpi_accounts = system_program::CreateAccount {
+            from: payer.to_account_info(),
+            to: info.clone(),
+        };
+        let cpi_ctx = CpiContext::new_with_signer(system_program.to_account_info(), cpi_accounts, signer);
+        return system_program::create_account(cpi_ctx, lamports, space as u64, &crate::ID);
+    }
+
+    let top_up = lamports.saturating_sub(info.lamports());
+    if top_up > 0 {
+        let cpi_accounts = system_program::Transfer {
+            from: payer.to_account_info(),
+            to: info.clone(),
+        };
+        let cpi_ctx = CpiContext::new(system_program.to_account_info(), cpi_accounts);
+        system_program::transfer(cpi_ctx, top_up)?;
+    }
+    let cpi_accounts = system_program::Allocate {
+        account_to_allocate: info.clone(),
+    };
+    let cpi_ctx = CpiContext::new_with_signer(system_program.to_account_info(), cpi_accounts, signer);
+    system_program::allocate(cpi_ctx, space as u64)?;
+    let cpi_accounts = system_program::Assign {
+        account_to_assign: info.clone(),
+    };
+    let cpi_ctx = CpiContext::new_with_signer(system_program.to_account_info(), cpi_accounts, signer);
+    system_program::assign(cpi_ctx, &crate::ID)
+}
+
+/// Top `info` up to rent exemption at `new_len` from `payer`, then grow it,
+/// zero-filling the new bytes
+fn grow_account<'info>(
+    info: &AccountInfo<'info>,
+    new_len: usize,
+    payer: &Signer<'info>,
+    system_program: &Program<'info, System>,
+) -> Result<()> {
+    let top_up = Rent::get()?.minimum_balance(new_len).saturating_sub(info.lamports());
+    if top_up > 0 {
+        let cpi_accounts = system_program::Transfer {
+            from: payer.to_account_info(),
+            to: info.clone(),
+        };
+        let cpi_ctx = CpiContext::new(system_program.to_account_info(), cpi_accounts);
+        system_program::transfer(cpi_ctx, top_up)?;
+    }
+    info.realloc(new_len, true)?;
+    Ok(())
+}
+
+/// The accounts `process_payment` and `process_payment_cpi` share
+struct SettlementAccounts<'a, 'info> {
+    vault_config: &'a mut Account<'info, VaultConfig>,
+    payment_record: &'a mut Account<'info, PaymentRecord>,
+    payment_record_bump: u8,
+    payer_rate_limit: &'a mut Account<'info, PayerRateLimit>,
+    payer_rate_limit_bump: u8,
+    daily_volume: &'a mut Account<'info, DailyVolumeRecord>,
+    daily_volume_bump: u8,
+    payer: &'a Signer<'info>,
+    blacklist_check: &'a UncheckedAccount<'info>,
+    payer_token_account: &'a Account<'info, TokenAccount>,
+    mint: &'a Account<'info, Mint>,
+    accepted_mint: Option<&'a AcceptedMint>,
+    vault_mint_config: Option<&'a VaultMintConfig>,
+    fee_schedule: Option<&'a FeeSchedule>,
+    merchant: &'a UncheckedAccount<'info>,
+    registered_merchant: Option<&'a RegisteredMerchant>,
+    merchant_stats: &'a mut Account<'info, MerchantStats>,
+    merchant_stats_bump: u8,
+    merchant_token_account: &'a Account<'info, TokenAccount>,
+    /// By `VaultConfig::fee_collectors` index; only the first is required
+    fee_token_accounts: [Option<&'a Account<'info, TokenAccount>>; MAX_FEE_COLLECTORS],
+    merchant_config: Option<&'info AccountInfo<'info>>,
+    token_program: &'a Program<'info, Token>,
+}
+
+fn settle_payment<'info>(
+    mut accounts: SettlementAccounts<'_, 'info>,
+    amount: u64,
+    payment_id: [u8; 32],
+    commitment: [u8; 32],
+    expires_at: i64,
+    blinding_factor: &[u8],
+    fee_mode: FeeMode,
+) -> Result<()> {
+    let now = Clock::get()?.unix_timestamp;
+    require!(expires_at <= 0 || now <= expires_at, VaultError::PaymentExpired);
+
+    let vault_config = &accounts.vault_config;
+    require!(!vault_config.paused, VaultError::VaultPaused);
+    check_not_blacklisted(accounts.blacklist_check)?;
+    vault_config.check_mint_whitelist(accounts.accepted_mint)?;
+    vault_config.check_merchant_registration(accounts.registered_merchant)?;
+    require!(vault_config.accepts_amount(amount), VaultError::InvalidAmount);
+    vault_config.check_commitment(amount, &commitment, blinding_factor)?;
+    let tier_volume = match vault_config.merchant_volume_tiers {
+        true => accounts.merchant_stats.total_volume,
+        false => vault_config.total_volume,
+    };
+    let merchant_fee = match accounts.merchant_config {
+        Some(merchant_config) => merchant_fee_override(merchant_config, &accounts.merchant.key())?,
+        None => None,
+    };
+    let (fee_basis_points, fee_tier) =
+        vault_config.payment_fee(accounts.fee_schedule, tier_volume, accounts.vault_mint_config, merchant_fee)?;
+
+    // Limits and volume count what leaves the payer, fee included
+    let (gross_amount, fee, net_amount) =
+        payment_amounts(amount, fee_basis_points, fee_mode, vault_config.round_fee_up)?;
+    accounts.payer_rate_limit.record_payment(
+        vault_config,
+        accounts.payer.key(),
+        accounts.payer_rate_limit_bump,
+        gross_amount,
+        now,
+    )?;
+    accounts
+        .daily_volume
+        .record_payment(vault_config, accounts.daily_volume_bump, gross_amount, now)?;
+    // From the mint account itself, so the caller can't misstate it
+    let decimals = accounts.mint.decimals;
+
+    // Transfer net amount to merchant
+    let cpi_accounts = TransferChecked {
+        from: accounts.payer_token_account.to_account_info(),
+        mint: accounts.mint.to_account_info(),
+        to: accounts.merchant_token_account.to_account_info(),
+        authority: accounts.payer.to_account_info(),
+    };
+    let cpi_program = accounts.token_program.to_account_info();
+    let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
+    token::transfer_checked(cpi_ctx, net_amount, decimals)?;
+
+    // Transfer fee to collectors by their shares (if any)
+    let fee_transfer = FeeTransfer {
+        from: accounts.payer_token_account.to_account_info(),
+        authority: accounts.payer.to_account_info(),
+        mint: accounts.mint,
+        fee_token_accounts: accounts.fee_token_accounts,
+        token_program: accounts.token_program,
+    };
+    let fee_shares = pay_fee_collectors(accounts.vault_config, &fee_transfer, fee, &[])?;
+
+    // Record payment
+    let payment_record = &mut accounts.payment_record;
+    payment_record.payment_id = payment_id;
+    payment_record.payer = accounts.payer.key();
+    payment_record.merchant = accounts.merchant.key();
+    payment_record.amount = gross_amount;
+    payment_record.fee = fee;
+    payment_record.commitment = commitment;
+    payment_record.timestamp = now;
+    payment_record.bump = accounts.payment_record_bump;
+    payment_record.status = PaymentStatus::Settled;
+    payment_record.mint = accounts.mint.key();
+    payment_record.expires_at = expires_at;
+    payment_record.record_version = RECORD_VERSION;
+    payment_record.net_amount = net_amount;
+    payment_record.fee_mode = fee_mode;
+    payment_record.fee_collectors = accounts.vault_config.fee_collector_keys();
+    payment_record.fee_shares = fee_shares;
+
+    // Update merchant and vault stats
+    let merchant_stats = &mut accounts.merchant_stats;
+    merchant_stats.merchant = accounts.merchant.key();
+    merchant_stats.total_volume = merchant_stats
+        .total_volume
+        .checked_add(gross_amount)
+        .ok_or(VaultError::MathOverflow)?;
+    merchant_stats.payment_count = merchant_stats
+        .payment_count
+        .checked_add(1)
+        .ok_or(VaultError::MathOverflow)?;
+    merchant_stats.bump = accounts.merchant_stats_bump;
+
+    let vault_config = &mut accounts.vault_config;
+    vault_config.total_volume = vault_config
+        .total_volume
+        .checked_add(gross_amount)
+        .ok_or(VaultError::MathOverflow)?;
+    vault_config.total_payments = vault_config
+        .total_payments
+        .checked_add(1)
+        .ok_or(VaultError::MathOverflow)?;
+    vault_config.total_fees_collected = vault_config
+        .total_fees_collected
+        .checked_add(fee)
+        .ok_or(VaultError::MathOverflow)?;
+
+    emit!(PaymentProcessed {
+        record: payment_record.key(),
+        payment_id,
+        payer: accounts.payer.key(),
+        merchant: accounts.merchant.key(),
+        amount: gross_amount,
+        fee,
+        net_amount,
+        fee_mode,
+        commitment,
+        timestamp: payment_record.timestamp,
+        mint: payment_record.mint,
+        expires_at,
+        total_fees_collected: vault_config.total_fees_collected,
+        fee_tier,
+        fee_basis_points,
+        fee_rounded_up: vault_config.round_fee_up,
+    });
+
+    Ok(())
+}
+
+/// The accounts `open_stream` and `top_up_stream` share
+struct StreamFunding<'a, 'info> {
+    vault_config: &'a mut Account<'info, VaultConfig>,
+    payer_rate_limit: &'a mut Account<'info, PayerRateLimit>,
+    payer_rate_limit_bump: u8,
+    daily_volume: &'a mut Account<'info, DailyVolumeRecord>,
+    daily_volume_bump: u8,
+    sender: &'a Signer<'info>,
+    blacklist_check: &'a UncheckedAccount<'info>,
+    sender_token_account: &'a Account<'info, TokenAccount>,
+    stream_tokens: &'a Account<'info, TokenAccount>,
+    mint: &'a Account<'info, Mint>,
+    accepted_mint: Option<&'a AcceptedMint>,
+    vault_mint_config: Option<&'a VaultMintConfig>,
+    fee_schedule: Option<&'a FeeSchedule>,
+    /// By `VaultConfig::fee_collectors` index; only the first is required
+    fee_token_accounts: [Option<&'a Account<'info, TokenAccount>>; MAX_FEE_COLLECTORS],
+    token_program: &'a Program<'info, Token>,
+}
+
+/// Move `deposit` from the sender into the stream's token account, under
+/// the checks and limits `settle_payment` applies to a payer, and charge
+/// the fee on top of it, paid straight to the collectors; returns the
+/// fee. Deposits count towards the vault's volume and fees as they are
+/// made, so a cancelled stream's fee is not refunded.
+fn fund_stream(accounts: StreamFunding, deposit: u64, now: i64) -> Result<u64> {
+    let vault_config = &accounts.vault_config;
+    require!(!vault_config.paused, VaultError::VaultPaused);
+    check_not_blacklisted(accounts.blacklist_check)?;
+    vault_config.check_mint_whitelist(accounts.accepted_mint)?;
+    require!(vault_config.accepts_amount(deposit), VaultError::InvalidAmount);
+    let (fee_basis_points, _) =
+        vault_config.payment_fee(accounts.fee_schedule, vault_config.total_volume, accounts.vault_mint_config, None)?;
+    let (gross_amount, fee, _) =
+        payment_amounts(deposit, fee_basis_points, FeeMode::ExactNet, vault_config.round_fee_up)?;
+    accounts.payer_rate_limit.record_payment(
+        vault_config,
+        accounts.sender.key(),
+        accounts.payer_rate_limit_bump,
+        gross_amount,
+        now,
+    )?;
+    accounts
+        .daily_volume
+        .record_payment(vault_config, accounts.daily_volume_bump, gross_amount, now)?;
+
+    let cpi_accounts = TransferChecked {
+        from: accounts.sender_token_account.to_account_info(),
+        mint: accounts.mint.to_account_info(),
+        to: accounts.stream_tokens.to_account_info(),
+        authority: accounts.sender.to_account_info(),
+    };
+    let cpi_ctx = CpiContext::new(accounts.token_program.to_account_info(), cpi_accounts);
+    token::transfer_checked(cpi_ctx, deposit, accounts.mint.decimals)?;
+    let fee_transfer = FeeTransfer {
+        from: accounts.sender_token_account.to_account_info(),
+        authority: accounts.sender.to_account_info(),
+        mint: accounts.mint,
+        fee_token_accounts: accounts.fee_token_accounts,
+        token_program: accounts.token_program,
+    };
+    pay_fee_collectors(vault_config, &fee_transfer, fee, &[])?;
+
+    let vault_config = accounts.vault_config;
+    vault_config.total_volume = vault_config
+        .total_volume
+        .checked_add(gross_amount)
+        .ok_or(VaultError::MathOverflow)?;
+    vault_config.total_fees_collected = vault_config
+        .total_fees_collected
+        .checked_add(fee)
+        .ok_or(VaultError::MathOverflow)?;
+    Ok(fee)
+}
+
+/// Require the approver the company named with set_payroll_approver, if it
+/// has, to have signed; `payroll_approver_config` is the account at its
+/// address
+fn check_payroll_approver(payroll_approver_config: &AccountInfo, approver: Option<&Signer>) -> Result<()> {
+    // Only set_payroll_approver creates an account at this address
+    if payroll_approver_config.data_is_empty() {
+        return Ok(());
+    }
+    let data = payroll_approver_config.try_borrow_data()?;
+    let payroll_approver_config = PayrollApproverConfig::try_deserialize(&mut &data[..])?;
+    let approver = approver.ok_or(VaultError::Unauthorized)?;
+    require_keys_eq!(approver.key(), payroll_approver_config.approver, VaultError::Unauthorized);
+    Ok(())
+}
+
+/// Refuse a payer blacklist_payer has created a `BlacklistedPayer` for;
+/// `blacklist_check` is the account at its address
+fn check_not_blacklisted(blacklist_check: &AccountInfo) -> Result<()> {
+    // Only blacklist_payer creates an account at this address
+    require!(blacklist_check.data_is_empty(), VaultError::PayerBlacklisted);
+    Ok(())
+}
+
+/// Where a payment's fee is paid from and to
+struct FeeTransfer<'a, 'info> {
+    from: AccountInfo<'info>,
+    /// Owner or delegate of `from`
+    authority: AccountInfo<'info>,
+    mint: &'a Account<'info, Mint>,
+    /// By `VaultConfig::fee_collectors` index; only those with a share are
+    /// required
+    fee_token_accounts: [Option<&'a Account<'info, TokenAccount>>; MAX_FEE_COLLECTORS],
+    token_program: &'a Program<'info, Token>,
+}
+
+/// Divide `fee` among the vault's collectors by their shares and pay each
+/// its own, signing with `signer_seeds` when the authority is a PDA;
+/// returns the shares, for the payment's record
+fn pay_fee_collectors(
+    vault_config: &VaultConfig,
+    transfer: &FeeTransfer,
+    fee: u64,
+    signer_seeds: &[&[&[u8]]],
+) -> Result<[u64; MAX_FEE_COLLECTORS]> {
+    let mut fee_shares = [0; MAX_FEE_COLLECTORS];
+    fee_shares.copy_from_slice(&split_fee(fee, &vault_config.fee_collector_shares())?);
+    for (fee_token_account, &share) in transfer.fee_token_accounts.iter().zip(&fee_shares) {
+        if share == 0 {
+            continue;
+        }
+        let fee_token_account = fee_token_account.ok_or(VaultError::MissingFeeAccount)?;
+        let cpi_accounts = TransferChecked {
+            from: transfer.from.clone(),
+            mint: transfer.mint.to_account_info(),
+            to: fee_token_account.to_account_info(),
+            authority: transfer.authority.clone(),
+        };
+        let cpi_program = transfer.token_program.to_account_info();
+        let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer_seeds);
+        token::transfer_checked(cpi_ctx, share, transfer.mint.decimals)?;
+    }
+    Ok(fee_shares)
+}
+
+/// A `RegisteredMerchant` passed for `merchant` among the remaining
+/// accounts, where no seeds constraint checks it; none if the account is
+/// empty. Only register_merchant creates one, so the program's own account
+/// naming `merchant` is it; any other account in its place fails the
+/// payment.
+fn remaining_registration<'info>(
+    info: &'info AccountInfo<'info>,
+    merchant: &Pubkey,
+) -> Result<Option<RegisteredMerchant>> {
+    if info.data_is_empty() {
+        return Ok(None);
+    }
+    let registered_merchant = Account::<'info, RegisteredMerchant>::try_from(info)?;
+    require_keys_eq!(registered_merchant.merchant, *merchant, VaultError::MerchantNotRegistered);
+    Ok(Some(registered_merchant.into_inner()))
+}
+
+/// The fee a `MerchantConfig` passed as a remaining account sets, as
+/// `MerchantConfig::fee_override`: zero for an exempt merchant, else its
+/// override while active. Any other account in its place fails the
+/// payment.
+fn merchant_fee_override<'info>(info: &'info AccountInfo<'info>, merchant: &Pubkey) -> Result<Option<u16>> {
+    let merchant_config = Account::<'info, MerchantConfig>::try_from(info)?;
+    let expected = Pubkey::create_program_address(
+        &[b"merchant_cfg", merchant.as_ref(), &[merchant_config.bump]],
+        &crate::ID,
+    )
+    .map_err(|_| error!(VaultError::InvalidMerchantConfig))?;
+    require_keys_eq!(info.key(), expected, VaultError::InvalidMerchantConfig);
+    Ok(merchant_config.fee_override())
+}
+
+// ============ Accounts ============
+
+#[derive(Accounts)]
+pub struct Initialize<'info> {
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + VaultConfig::INIT_SPACE,
+        seeds = [b"vault_config"],
+        bump
+    )]
+    pub vault_config: Account<'info, VaultConfig>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    /// The vault's own ProgramData: only its upgrade authority may claim the
+    /// vault, so a fresh deployment can't be initialized by whoever is first
+    #[account(
+        seeds = [crate::ID.as_ref()],
+        bump,
+        seeds::program = bpf_loader_upgradeable::ID,
+        constraint = program_data.upgrade_authority_address == Some(authority.key()) @ VaultError::Unauthorized
+    )]
+    pub program_data: Account<'info, ProgramData>,
+
+    /// CHECK: Fee collector can be any account
+    pub fee_collector: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(amount: u64, payment_id: [u8; 32])]
+pub struct ProcessPayment<'info> {
+    #[account(
+        mut,
+        seeds = [b"vault_config"],
+        bump = vault_config.bump
+    )]
+    pub vault_config: Account<'info, VaultConfig>,
+
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + PaymentRecord::INIT_SPACE,
+        seeds = [b"payment", payer.key().as_ref(), payment_id.as_ref()],
+        bump
+    )]
+    pub payment_record: Account<'info, PaymentRecord>,
+
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = 8 + PayerRateLimit::INIT_SPACE,
+        seeds = [b"rate_limit", payer.key().as_ref()],
+        bump
+    )]
+    pub payer_rate_limit: Account<'info, PayerRateLimit>,
+
+    /// Today's DailyVolumeRecord; a payment landing after midnight UTC
+    /// needs the next day's
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = 8 + DailyVolumeRecord::INIT_SPACE,
+        seeds = [b"daily_vol", current_day()?.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub daily_volume: Account<'info, DailyVolumeRecord>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    /// CHECK: Where the payer's `BlacklistedPayer` would be; passed
+    /// whether or not it exists, so a blacklisted payer can't leave it out
+    #[account(seeds = [b"blacklist", payer.key().as_ref()], bump)]
+    pub blacklist_check: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        constraint = payer_token_account.mint == mint.key() @ VaultError::MintMismatch
+    )]
+    pub payer_token_account: Account<'info, TokenAccount>,
+
+    /// Token paid in; transfers are checked against its decimals
+    pub mint: Account<'info, Mint>,
+
+    /// The mint's whitelist entry; required while the vault requires one
+    #[account(
+        seeds = [b"accepted_mint", mint.key().as_ref()],
+        bump = accepted_mint.bump
+    )]
+    pub accepted_mint: Option<Account<'info, AcceptedMint>>,
+
+    /// The mint's fee override; charged instead of the vault-wide fee
+    /// while active
+    #[account(
+        seeds = [b"vault_mint", mint.key().as_ref()],
+        bump = vault_mint_config.bump
+    )]
+    pub vault_mint_config: Option<Account<'info, VaultMintConfig>>,
+
+    /// The volume tiers; required while the vault has a schedule
+    #[account(
+        seeds = [b"fee_schedule"],
+        bump = fee_schedule.bump
+    )]
+    pub fee_schedule: Option<Account<'info, FeeSchedule>>,
+
+    /// CHECK: Merchant wallet; recorded on the payment and must own merchant_token_account
+    pub merchant: UncheckedAccount<'info>,
+
+    /// The merchant's registration; required while the vault requires one
+    #[account(
+        seeds = [b"merchant", merchant.key().as_ref()],
+        bump = registered_merchant.bump
+    )]
+    pub registered_merchant: Option<Account<'info, RegisteredMerchant>>,
+
+    /// The merchant's running volume, which tiers its fees while the vault
+    /// has `merchant_volume_tiers` set
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = 8 + MerchantStats::INIT_SPACE,
+        seeds = [b"merchant_stats", merchant.key().as_ref()],
+        bump
+    )]
+    pub merchant_stats: Account<'info, MerchantStats>,
+
+    #[account(
+        mut,
+        constraint = merchant_token_account.owner == merchant.key() @ VaultError::MerchantAccountMismatch,
+        constraint = merchant_token_account.mint == payer_token_account.mint @ VaultError::MintMismatch
+    )]
+    pub merchant_token_account: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        constraint = fee_token_account.owner == vault_config.fee_collector @ VaultError::InvalidFeeAccount,
+        constraint = fee_token_account.mint == payer_token_account.mint @ VaultError::InvalidFeeAccount
+    )]
+    pub fee_token_account: Account<'info, TokenAccount>,
+
+    /// The second fee collector's account; required while it has a share
+    #[account(
+        mut,
+        constraint = fee_token_account_1.owner == vault_config.fee_collectors[1].collector
+            @ VaultError::InvalidFeeAccount,
+        constraint = fee_token_account_1.mint == payer_token_account.mint @ VaultError::InvalidFeeAccount
+    )]
+    pub fee_token_account_1: Option<Account<'info, TokenAccount>>,
+
+    /// The third fee collector's account; required while it has a share
+    #[account(
+        mut,
+        constraint = fee_token_account_2.owner == vault_config.fee_collectors[2].collector
+            @ VaultError::InvalidFeeAccount,
+        constraint = fee_token_account_2.mint == payer_token_account.mint @ VaultError::InvalidFeeAccount
+    )]
+    pub fee_token_account_2: Option<Account<'info, TokenAccount>>,
+
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+/// As ProcessPayment, with the records' rent paid by `rent_payer`
+#[derive(Accounts)]
+#[instruction(amount: u64, payment_id: [u8; 32])]
+pub struct ProcessPaymentCpi<'info> {
+    #[account(
+        mut,
+        seeds = [b"vault_config"],
+        bump = vault_config.bump
+    )]
+    pub vault_config: Account<'info, VaultConfig>,
+
+    #[account(
+        init,
+        payer = rent_payer,
+        space = 8 + PaymentRecord::INIT_SPACE,
+        seeds = [b"payment", payer.key().as_ref(), payment_id.as_ref()],
+        bump
+    )]
+    pub payment_record: Account<'info, PaymentRecord>,
+
+    #[account(
+        init_if_needed,
+        payer = rent_payer,
+        space = 8 + PayerRateLimit::INIT_SPACE,
+        seeds = [b"rate_limit", payer.key().as_ref()],
+        bump
+    )]
+    pub payer_rate_limit: Account<'info, PayerRateLimit>,
+
+    /// Today's DailyVolumeRecord; a payment landing after midnight UTC
+    /// needs the next day's
+    #[account(
+        init_if_needed,
+        payer = rent_payer,
+        space = 8 + DailyVolumeRecord::INIT_SPACE,
+        seeds = [b"daily_vol", current_day()?.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub daily_volume: Account<'info, DailyVolumeRecord>,
+
+    /// Authority over payer_token_account; may be a PDA of the calling program
+    pub payer: Signer<'info>,
+
+    /// CHECK: Where the payer's `BlacklistedPayer` would be; passed
+    /// whether or not it exists, so a blacklisted payer can't leave it out
+    #[account(seeds = [b"blacklist", payer.key().as_ref()], bump)]
+    pub blacklist_check: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    pub rent_payer: Signer<'info>,
+
+    #[account(
+        mut,
+        constraint = payer_token_account.mint == mint.key() @ VaultError::MintMismatch
+    )]
+    pub payer_token_account: Account<'info, TokenAccount>,
+
+    /// Token paid in; transfers are checked against its decimals
+    pub mint: Account<'info, Mint>,
+
+    /// The mint's whitelist entry; required while the vault requires one
+    #[account(
+        seeds = [b"accepted_mint", mint.key().as_ref()],
+        bump = accepted_mint.bump
+    )]
+    pub accepted_mint: Option<Account<'info, AcceptedMint>>,
+
+    /// The mint's fee override; charged instead of the vault-wide fee
+    /// while active
+    #[account(
+        seeds = [b"vault_mint", mint.key().as_ref()],
+        bump = vault_mint_config.bump
+    )]
+    pub vault_mint_config: Option<Account<'info, VaultMintConfig>>,
+
+    /// The volume tiers; required while the vault has a schedule
+    #[account(
+        seeds = [b"fee_schedule"],
+        bump = fee_schedule.bump
+    )]
+    pub fee_schedule: Option<Account<'info, FeeSchedule>>,
+
+    /// CHECK: Merchant wallet; recorded on the payment and must own merchant_token_account
+    pub merchant: UncheckedAccount<'info>,
+
+    /// The merchant's registration; required while the vault requires one
+    #[account(
+        seeds = [b"merchant", merchant.key().as_ref()],
+        bump = registered_merchant.bump
+    )]
+    pub registered_merchant: Option<Account<'info, RegisteredMerchant>>,
+
+    /// The merchant's running volume, which tiers its fees while the vault
+    /// has `merchant_volume_tiers` set
+    #[account(
+        init_if_needed,
+        payer = rent_payer,
+        space = 8 + MerchantStats::INIT_SPACE,
+        seeds = [b"merchant_stats", merchant.key().as_ref()],
+        bump
+    )]
+    pub merchant_stats: Account<'info, MerchantStats>,
+
+    #[account(
+        mut,
+        constraint = merchant_token_account.owner == merchant.key() @ VaultError::MerchantAccountMismatch,
+        constraint = merchant_token_account.mint == payer_token_account.mint @ VaultError::MintMismatch
+    )]
+    pub merchant_token_account: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        constraint = fee_token_account.owner == vault_config.fee_collector @ VaultError::InvalidFeeAccount,
+        constraint = fee_token_account.mint == payer_token_account.mint @ VaultError::InvalidFeeAccount
+    )]
+    pub fee_token_account: Account<'info, TokenAccount>,
+
+    /// The second fee collector's account; required while it has a share
+    #[account(
+        mut,
+        constraint = fee_token_account_1.owner == vault_config.fee_collectors[1].collector
+            @ VaultError::InvalidFeeAccount,
+        constraint = fee_token_account_1.mint == payer_token_account.mint @ VaultError::InvalidFeeAccount
+    )]
+    pub fee_token_account_1: Option<Account<'info, TokenAccount>>,
+
+    /// The third fee collector's account; required while it has a share
+    #[account(
+        mut,
+        constraint = fee_token_account_2.owner == vault_config.fee_collectors[2].collector
+            @ VaultError::InvalidFeeAccount,
+        constraint = fee_token_account_2.mint == payer_token_account.mint @ VaultError::InvalidFeeAccount
+    )]
+    pub fee_token_account_2: Option<Account<'info, TokenAccount>>,
+
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+/// Merchant token accounts and payment records follow as remaining
+/// accounts, a pair per payment
+#[derive(Accounts)]
+pub struct ProcessPaymentBatch<'info> {
+    #[account(
+        mut,
+        seeds = [b"vault_config"],
+        bump = vault_config.bump
+    )]
+    pub vault_config: Account<'info, VaultConfig>,
+
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = 8 + PayerRateLimit::INIT_SPACE,
+        seeds = [b"rate_limit", payer.key().as_ref()],
+        bump
+    )]
+    pub payer_rate_limit: Account<'info, PayerRateLimit>,
+
+    /// Today's DailyVolumeRecord; a payment landing after midnight UTC
+    /// needs the next day's
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = 8 + DailyVolumeRecord::INIT_SPACE,
+        seeds = [b"daily_vol", current_day()?.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub daily_volume: Account<'info, DailyVolumeRecord>,
+
+    /// Owner or delegate of payer_token_account; pays the records' rent
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    /// CHECK: Where the payer's `BlacklistedPayer` would be; passed
+    /// whether or not it exists, so a blacklisted payer can't leave it out
+    #[account(seeds = [b"blacklist", payer.key().as_ref()], bump)]
+    pub blacklist_check: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        constraint = payer_token_account.mint == mint.key() @ VaultError::MintMismatch
+    )]
+    pub payer_token_account: Account<'info, TokenAccount>,
+
+    /// Token paid in; transfers are checked against its decimals
+    pub mint: Account<'info, Mint>,
+
+    /// The mint's whitelist entry; required while the vault requires one
+    #[account(
+        seeds = [b"accepted_mint", mint.key().as_ref()],
+        bump = accepted_mint.bump
+    )]
+    pub accepted_mint: Option<Account<'info, AcceptedMint>>,
+
+    /// The mint's fee override; charged instead of the vault-wide fee
+    /// while active
+    #[account(
+        seeds = [b"vault_mint", mint.key().as_ref()],
+        bump = vault_mint_config.bump
+    )]
+    pub vault_mint_config: Option<Account<'info, VaultMintConfig>>,
+
+    /// The volume tiers; required while the vault has a schedule
+    #[account(
+        seeds = [b"fee_schedule"],
+        bump = fee_schedule.bump
+    )]
+    pub fee_schedule: Option<Account<'info, FeeSchedule>>,
+
+    #[account(
+        mut,
+        constraint = fee_token_account.owner == vault_config.fee_collector @ VaultError::InvalidFeeAccount,
+        constraint = fee_token_account.mint == mint.key() @ VaultError::InvalidFeeAccount
+    )]
+    pub fee_token_account: Account<'info, TokenAccount>,
+
+    /// The second fee collector's account; required while it has a share
+    #[account(
+        mut,
+        constraint = fee_token_account_1.owner == vault_config.fee_collectors[1].collector
+            @ VaultError::InvalidFeeAccount,
+        constraint = fee_token_account_1.mint == mint.key() @ VaultError::InvalidFeeAccount
+    )]
+    pub fee_token_account_1: Option<Account<'info, TokenAccount>>,
+
+    /// The third fee collector's account; required while it has a share
+    #[account(
+        mut,
+        constraint = fee_token_account_2.owner == vault_config.fee_collectors[2].collector
+            @ VaultError::InvalidFeeAccount,
+        constraint = fee_token_account_2.mint == mint.key() @ VaultError::InvalidFeeAccount
+    )]
+    pub fee_token_account_2: Option<Account<'info, TokenAccount>>,
+
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+/// Recipient token accounts follow as remaining accounts, one per split
+#[derive(Accounts)]
+pub struct ProcessSplitPayment<'info> {
+    #[account(
+        mut,
+        seeds = [b"vault_config"],
+        bump = vault_config.bump
+    )]
+    pub vault_config: Account<'info, VaultConfig>,
+
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = 8 + PayerRateLimit::INIT_SPACE,
+        seeds = [b"rate_limit", payer.key().as_ref()],
+        bump
+    )]
+    pub payer_rate_limit: Account<'info, PayerRateLimit>,
+
+    /// Today's DailyVolumeRecord; a payment landing after midnight UTC
+    /// needs the next day's
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = 8 + DailyVolumeRecord::INIT_SPACE,
+        seeds = [b"daily_vol", current_day()?.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub daily_volume: Account<'info, DailyVolumeRecord>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    /// CHECK: Where the payer's `BlacklistedPayer` would be; passed
+    /// whether or not it exists, so a blacklisted payer can't leave it out
+    #[account(seeds = [b"blacklist", payer.key().as_ref()], bump)]
+    pub blacklist_check: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        constraint = payer_token_account.mint == mint.key() @ VaultError::MintMismatch
+    )]
+    pub payer_token_account: Account<'info, TokenAccount>,
+
+    /// Token paid in; transfers are checked against its decimals
+    pub mint: Account<'info, Mint>,
+
+    /// The mint's whitelist entry; required while the vault requires one
+    #[account(
+        seeds = [b"accepted_mint", mint.key().as_ref()],
+        bump = accepted_mint.bump
+    )]
+    pub accepted_mint: Option<Account<'info, AcceptedMint>>,
+
+    /// The mint's fee override; charged instead of the vault-wide fee
+    /// while active
+    #[account(
+        seeds = [b"vault_mint", mint.key().as_ref()],
+        bump = vault_mint_config.bump
+    )]
+    pub vault_mint_config: Option<Account<'info, VaultMintConfig>>,
+
+    /// The volume tiers; required while the vault has a schedule
+    #[account(
+        seeds = [b"fee_schedule"],
+        bump = fee_schedule.bump
+    )]
+    pub fee_schedule: Option<Account<'info, FeeSchedule>>,
+
+    /// CHECK: Merchant wallet; must own the first recipient's token account
+    pub merchant: UncheckedAccount<'info>,
+
+    /// The merchant's fee exemption or override, if it has one
+    #[account(
+        seeds = [b"merchant_cfg", merchant.key().as_ref()],
+        bump = merchant_config.bump
+    )]
+    pub merchant_config: Option<Account<'info, MerchantConfig>>,
+
+    #[account(
+        mut,
+        constraint = fee_token_account.owner == vault_config.fee_collector @ VaultError::InvalidFeeAccount,
+        constraint = fee_token_account.mint == mint.key() @ VaultError::InvalidFeeAccount
+    )]
+    pub fee_token_account: Account<'info, TokenAccount>,
+
+    /// The second fee collector's account; required while it has a share
+    #[account(
+        mut,
+        constraint = fee_token_account_1.owner == vault_config.fee_collectors[1].collector
+            @ VaultError::InvalidFeeAccount,
+        constraint = fee_token_account_1.mint == mint.key() @ VaultError::InvalidFeeAccount
+    )]
+    pub fee_token_account_1: Option<Account<'info, TokenAccount>>,
+
+    /// The third fee collector's account; required while it has a share
+    #[account(
+        mut,
+        constraint = fee_token_account_2.owner == vault_config.fee_collectors[2].collector
+            @ VaultError::InvalidFeeAccount,
+        constraint = fee_token_account_2.mint == mint.key() @ VaultError::InvalidFeeAccount
+    )]
+    pub fee_token_account_2: Option<Account<'info, TokenAccount>>,
+
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(lamports: u64, payment_id: [u8; 32])]
+pub struct ProcessSolPayment<'info> {
+    #[account(
+        mut,
+        seeds = [b"vault_config"],
+        bump = vault_config.bump
+    )]
+    pub vault_config: Account<'info, VaultConfig>,
+
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + PaymentRecord::INIT_SPACE,
+        seeds = [b"payment", payer.key().as_ref(), payment_id.as_ref()],
+        bump
+    )]
+    pub payment_record: Account<'info, PaymentRecord>,
+
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = 8 + PayerRateLimit::INIT_SPACE,
+        seeds = [b"rate_limit", payer.key().as_ref()],
+        bump
+    )]
+    pub payer_rate_limit: Account<'info, PayerRateLimit>,
+
+    /// Today's DailyVolumeRecord; a payment landing after midnight UTC
+    /// needs the next day's
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = 8 + DailyVolumeRecord::INIT_SPACE,
+        seeds = [b"daily_vol", current_day()?.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub daily_volume: Account<'info, DailyVolumeRecord>,
+
+    /// Holds the wrapped payment for the length of the instruction
+    #[account(
+        init,
+        payer = payer,
+        seeds = [b"sol_escrow", payer.key().as_ref(), payment_id.as_ref()],
+        bump,
+        token::mint = native_mint,
+        token::authority = vault_config
+    )]
+    pub sol_escrow: Account<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    /// CHECK: Where the payer's `BlacklistedPayer` would be; passed
+    /// whether or not it exists, so a blacklisted payer can't leave it out
+    #[account(seeds = [b"blacklist", payer.key().as_ref()], bump)]
+    pub blacklist_check: UncheckedAccount<'info>,
+
+    #[account(address = spl_token::native_mint::ID)]
+    pub native_mint: Account<'info, Mint>,
+
+    /// The native mint's whitelist entry; required while the vault requires one
+    #[account(
+        seeds = [b"accepted_mint", native_mint.key().as_ref()],
+        bump = accepted_mint.bump
+    )]
+    pub accepted_mint: Option<Account<'info, AcceptedMint>>,
+
+    /// The native mint's fee override; charged instead of the vault-wide fee
+    /// while active
+    #[account(
+        seeds = [b"vault_mint", native_mint.key().as_ref()],
+        bump = vault_mint_config.bump
+    )]
+    pub vault_mint_config: Option<Account<'info, VaultMintConfig>>,
+
+    /// The volume tiers; required while the vault has a schedule
+    #[account(
+        seeds = [b"fee_schedule"],
+        bump = fee_schedule.bump
+    )]
+    pub fee_schedule: Option<Account<'info, FeeSchedule>>,
+
+    /// CHECK: Merchant wallet; recorded on the payment and paid in lamports
+    #[account(mut)]
+    pub merchant: UncheckedAccount<'info>,
+
+    /// The merchant's registration; required while the vault requires one
+    #[account(
+        seeds = [b"merchant", merchant.key().as_ref()],
+        bump = registered_merchant.bump
+    )]
+    pub registered_merchant: Option<Account<'info, RegisteredMerchant>>,
+
+    /// CHECK: Paid the fee in lamports; must be the vault's collector
+    #[account(mut, address = vault_config.fee_collector @ VaultError::InvalidFeeAccount)]
+    pub fee_collector: UncheckedAccount<'info>,
+
+    /// CHECK: The second fee collector; required while it has a share
+    #[account(mut, address = vault_config.fee_collectors[1].collector @ VaultError::InvalidFeeAccount)]
+    pub fee_collector_1: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: The third fee collector; required while it has a share
+    #[account(mut, address = vault_config.fee_collectors[2].collector @ VaultError::InvalidFeeAccount)]
+    pub fee_collector_2: Option<UncheckedAccount<'info>>,
+
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+/// The fee collectors and their token accounts are only needed for the
+/// refund that returns the fee, by the index of their share on the record
+#[derive(Accounts)]
+#[instruction(payment_id: [u8; 32])]
+pub struct RefundPayment<'info> {
+    #[account(
+        seeds = [b"vault_config"],
+        bump = vault_config.bump
+    )]
+    pub vault_config: Account<'info, VaultConfig>,
+
+    #[account(
+        mut,
+        seeds = [b"payment", payer.key().as_ref(), payment_id.as_ref()],
+        bump = payment_record.bump,
+        has_one = merchant @ VaultError::Unauthorized,
+        has_one = payer
+    )]
+    pub payment_record: Account<'info, PaymentRecord>,
+
+    pub merchant: Signer<'info>,
+
+    #[account(
+        mut,
+        constraint = merchant_token_account.owner == merchant.key() @ VaultError::Unauthorized,
+        constraint = merchant_token_account.mint == payment_record.mint @ VaultError::MintMismatch
+    )]
+    pub merchant_token_account: Account<'info, TokenAccount>,
+
+    pub fee_collector: Option<Signer<'info>>,
+
+    #[account(
+        mut,
+        constraint = fee_token_account.mint == payment_record.mint @ VaultError::MintMismatch
+    )]
+    pub fee_token_account: Option<Account<'info, TokenAccount>>,
+
+    pub fee_collector_1: Option<Signer<'info>>,
+
+    #[account(
+        mut,
+        constraint = fee_token_account_1.mint == payment_record.mint @ VaultError::MintMismatch
+    )]
+    pub fee_token_account_1: Option<Account<'info, TokenAccount>>,
+
+    pub fee_collector_2: Option<Signer<'info>>,
+
+    #[account(
+        mut,
+        constraint = fee_token_account_2.mint == payment_record.mint @ VaultError::MintMismatch
+    )]
+    pub fee_token_account_2: Option<Account<'info, TokenAccount>>,
+
+    /// CHECK: Payer of the original payment; receives the record's rent
+    /// once the payment is fully refunded
+    #[account(mut)]
+    pub payer: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        constraint = payer_token_account.owner == payer.key() @ VaultError::Unauthorized,
+        constraint = payer_token_account.mint == payment_record.mint @ VaultError::MintMismatch
+    )]
+    pub payer_token_account: Account<'info, TokenAccount>,
+
+    /// The payment's token; transfers are checked against its decimals
+    #[account(address = payment_record.mint @ VaultError::MintMismatch)]
+    pub mint: Account<'info, Mint>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+#[instruction(payment_id: [u8; 32])]
+pub struct OpenDispute<'info> {
+    #[account(
+        mut,
+        seeds = [b"payment", payer.key().as_ref(), payment_id.as_ref()],
+        bump = payment_record.bump,
+        has_one = payer
+    )]
+    pub payment_record: Account<'info, PaymentRecord>,
+
+    pub payer: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(payment_id: [u8; 32])]
+pub struct ResolveDispute<'info> {
+    #[account(
+        seeds = [b"vault_config"],
+        bump = vault_config.bump,
+        has_one = authority
+    )]
+    pub vault_config: Account<'info, VaultConfig>,
+
+    #[account(
+        mut,
+        seeds = [b"payment", payer.key().as_ref(), payment_id.as_ref()],
+        bump = payment_record.bump,
+        has_one = payer
+    )]
+    pub payment_record: Account<'info, PaymentRecord>,
+
+    /// CHECK: Payer of the disputed payment, for the record's address
+    pub payer: UncheckedAccount<'info>,
+
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(amount: u64, payment_id: [u8; 32])]
+pub struct CreateEscrowPayment<'info> {
+    #[account(
+        seeds = [b"vault_config"],
+        bump = vault_config.bump
+    )]
+    pub vault_config: Account<'info, VaultConfig>,
+
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + EscrowRecord::INIT_SPACE,
+        seeds = [b"escrow_payment", payer.key().as_ref(), payment_id.as_ref()],
+        bump
+    )]
+    pub escrow_record: Account<'info, EscrowRecord>,
+
+    /// Holds the payment until it is captured or cancelled; the escrow
+    /// record signs transfers out of it
+    #[account(
+        init,
+        payer = payer,
+        seeds = [b"escrow_tokens", payer.key().as_ref(), payment_id.as_ref()],
+        bump,
+        token::mint = mint,
+        token::authority = escrow_record
+    )]
+    pub escrow_tokens: Account<'info, TokenAccount>,
+
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = 8 + PayerRateLimit::INIT_SPACE,
+        seeds = [b"rate_limit", payer.key().as_ref()],
+        bump
+    )]
+    pub payer_rate_limit: Account<'info, PayerRateLimit>,
+
+    /// Today's DailyVolumeRecord; a payment landing after midnight UTC
+    /// needs the next day's
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = 8 + DailyVolumeRecord::INIT_SPACE,
+        seeds = [b"daily_vol", current_day()?.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub daily_volume: Account<'info, DailyVolumeRecord>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    /// CHECK: Where the payer's `BlacklistedPayer` would be; passed
+    /// whether or not it exists, so a blacklisted payer can't leave it out
+    #[account(seeds = [b"blacklist", payer.key().as_ref()], bump)]
+    pub blacklist_check: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        constraint = payer_token_account.mint == mint.key() @ VaultError::MintMismatch
+    )]
+    pub payer_token_account: Account<'info, TokenAccount>,
+
+    pub mint: Account<'info, Mint>,
+
+    /// The mint's whitelist entry; required while the vault requires one
+    #[account(
+        seeds = [b"accepted_mint", mint.key().as_ref()],
+        bump = accepted_mint.bump
+    )]
+    pub accepted_mint: Option<Account<'info, AcceptedMint>>,
+
+    /// The mint's fee override; charged instead of the vault-wide fee
+    /// while active
+    #[account(
+        seeds = [b"vault_mint", mint.key().as_ref()],
+        bump = vault_mint_config.bump
+    )]
+    pub vault_mint_config: Option<Account<'info, VaultMintConfig>>,
+
+    /// The volume tiers; required while the vault has a schedule
+    #[account(
+        seeds = [b"fee_schedule"],
+        bump = fee_schedule.bump
+    )]
+    pub fee_schedule: Option<Account<'info, FeeSchedule>>,
+
+    /// CHECK: Merchant wallet; recorded on the escrow and signs its capture
+    pub merchant: UncheckedAccount<'info>,
+
+    /// The merchant's registration; required while the vault requires one
+    #[account(
+        seeds = [b"merchant", merchant.key().as_ref()],
+        bump = registered_merchant.bump
+    )]
+    pub registered_merchant: Option<Account<'info, RegisteredMerchant>>,
+
+    /// The merchant's fee exemption or override, if it has one
+    #[account(
+        seeds = [b"merchant_cfg", merchant.key().as_ref()],
+        bump = merchant_config.bump
+    )]
+    pub merchant_config: Option<Account<'info, MerchantConfig>>,
+
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(payment_id: [u8; 32])]
+pub struct CapturePayment<'info> {
+    #[account(
+        mut,
+        seeds = [b"vault_config"],
+        bump = vault_config.bump
+    )]
+    pub vault_config: Account<'info, VaultConfig>,
+
+    #[account(
+        mut,
+        seeds = [b"escrow_payment", payer.key().as_ref(), payment_id.as_ref()],
+        bump = escrow_record.bump,
+        has_one = merchant @ VaultError::Unauthorized,
+        has_one = payer
+    )]
+    pub escrow_record: Account<'info, EscrowRecord>,
+
+    #[account(
+        mut,
+        seeds = [b"escrow_tokens", payer.key().as_ref(), payment_id.as_ref()],
+        bump
+    )]
+    pub escrow_tokens: Account<'info, TokenAccount>,
+
+    pub merchant: Signer<'info>,
+
+    #[account(
+        mut,
+        constraint = merchant_token_account.owner == merchant.key() @ VaultError::MerchantAccountMismatch,
+        constraint = merchant_token_account.mint == escrow_record.mint @ VaultError::MintMismatch
+    )]
+    pub merchant_token_account: Account<'info, TokenAccount>,
+
+    /// The escrowed payment's token; transfers are checked against its
+    /// decimals
+    #[account(address = escrow_record.mint @ VaultError::MintMismatch)]
+    pub mint: Account<'info, Mint>,
+
+    #[account(
+        mut,
+        constraint = fee_token_account.owner == vault_config.fee_collector @ VaultError::InvalidFeeAccount,
+        constraint = fee_token_account.mint == escrow_record.mint @ VaultError::InvalidFeeAccount
+    )]
+    pub fee_token_account: Account<'info, TokenAccount>,
+
+    /// The second fee collector's account; required while it has a share
+    #[account(
+        mut,
+        constraint = fee_token_account_1.owner == vault_config.fee_collectors[1].collector
+            @ VaultError::InvalidFeeAccount,
+        constraint = fee_token_account_1.mint == escrow_record.mint @ VaultError::InvalidFeeAccount
+    )]
+    pub fee_token_account_1: Option<Account<'info, TokenAccount>>,
+
+    /// The third fee collector's account; required while it has a share
+    #[account(
+        mut,
+        constraint = fee_token_account_2.owner == vault_config.fee_collectors[2].collector
+            @ VaultError::InvalidFeeAccount,
+        constraint = fee_token_account_2.mint == escrow_record.mint @ VaultError::InvalidFeeAccount
+    )]
+    pub fee_token_account_2: Option<Account<'info, TokenAccount>>,
+
+    /// CHECK: Payer of the escrowed payment; receives the escrow account's rent
+    #[account(mut)]
+    pub payer: UncheckedAccount<'info>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+#[instruction(payment_id: [u8; 32])]
+pub struct CancelEscrow<'info> {
+    #[account(
+        seeds = [b"vault_config"],
+        bump = vault_config.bump
+    )]
+    pub vault_config: Account<'info, VaultConfig>,
+
+    #[account(
+        mut,
+        seeds = [b"escrow_payment", payer.key().as_ref(), payment_id.as_ref()],
+        bump = escrow_record.bump,
+        has_one = payer
+    )]
+    pub escrow_record: Account<'info, EscrowRecord>,
+
+    #[account(
+        mut,
+        seeds = [b"escrow_tokens", payer.key().as_ref(), payment_id.as_ref()],
+        bump
+    )]
+    pub escrow_tokens: Account<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(
+        mut,
+        constraint = payer_token_account.owner == payer.key() @ VaultError::Unauthorized,
+        constraint = payer_token_account.mint == escrow_record.mint @ VaultError::MintMismatch
+    )]
+    pub payer_token_account: Account<'info, TokenAccount>,
+
+    /// The escrowed payment's token; transfers are checked against its
+    /// decimals
+    #[account(address = escrow_record.mint @ VaultError::MintMismatch)]
+    pub mint: Account<'info, Mint>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+#[instruction(payment_id: [u8; 32])]
+pub struct CrankExpiredEscrow<'info> {
+    #[account(
+        seeds = [b"vault_config"],
+        bump = vault_config.bump
+    )]
+    pub vault_config: Account<'info, VaultConfig>,
+
+    #[account(
+        mut,
+        seeds = [b"escrow_payment", payer.key().as_ref(), payment_id.as_ref()],
+        bump = escrow_record.bump,
+        has_one = payer
+    )]
+    pub escrow_record: Account<'info, EscrowRecord>,
+
+    #[account(
+        mut,
+        seeds = [b"escrow_tokens", payer.key().as_ref(), payment_id.as_ref()],
+        bump
+    )]
+    pub escrow_tokens: Account<'info, TokenAccount>,
+
+    /// CHECK: Payer of the escrowed payment; receives the tokens and the rent
+    #[account(mut)]
+    pub payer: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        constraint = payer_token_account.owner == payer.key() @ VaultError::Unauthorized,
+        constraint = payer_token_account.mint == escrow_record.mint @ VaultError::MintMismatch
+    )]
+    pub payer_token_account: Account<'info, TokenAccount>,
+
+    /// The escrowed payment's token; transfers are checked against its
+    /// decimals
+    #[account(address = escrow_record.mint @ VaultError::MintMismatch)]
+    pub mint: Account<'info, Mint>,
+
+    pub cranker: Signer<'info>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+#[instruction(stream_id: [u8; 32])]
+pub struct OpenStream<'info> {
+    #[account(
+        mut,
+        seeds = [b"vault_config"],
+        bump = vault_config.bump
+    )]
+    pub vault_config: Account<'info, VaultConfig>,
+
+    #[account(
+        init,
+        payer = sender,
+        space = 8 + StreamingPayment::INIT_SPACE,
+        seeds = [b"stream", sender.key().as_ref(), stream_id.as_ref()],
+        bump
+    )]
+    pub streaming_payment: Account<'info, StreamingPayment>,
+
+    /// Holds the deposit until it streams out or the stream is cancelled;
+    /// the streaming payment signs transfers out of it
+    #[account(
+        init,
+        payer = sender,
+        seeds = [b"stream_tokens", sender.key().as_ref(), stream_id.as_ref()],
+        bump,
+        token::mint = mint,
+        token::authority = streaming_payment
+    )]
+    pub stream_tokens: Account<'info, TokenAccount>,
+
+    #[account(
+        init_if_needed,
+        payer = sender,
+        space = 8 + PayerRateLimit::INIT_SPACE,
+        seeds = [b"rate_limit", sender.key().as_ref()],
+        bump
+    )]
+    pub payer_rate_limit: Account<'info, PayerRateLimit>,
+
+    /// Today's DailyVolumeRecord; a payment landing after midnight UTC
+    /// needs the next day's
+    #[account(
+        init_if_needed,
+        payer = sender,
+        space = 8 + DailyVolumeRecord::INIT_SPACE,
+        seeds = [b"daily_vol", current_day()?.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub daily_volume: Account<'info, DailyVolumeRecord>,
+
+    #[account(mut)]
+    pub sender: Signer<'info>,
+
+    /// CHECK: Where the sender's `BlacklistedPayer` would be; passed
+    /// whether or not it exists, so a blacklisted sender can't leave it out
+    #[account(seeds = [b"blacklist", sender.key().as_ref()], bump)]
+    pub blacklist_check: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        constraint = sender_token_account.mint == mint.key() @ VaultError::MintMismatch
+    )]
+    pub sender_token_account: Account<'info, TokenAccount>,
+
+    pub mint: Account<'info, Mint>,
+
+    /// The mint's whitelist entry; required while the vault requires one
+    #[account(
+        seeds = [b"accepted_mint", mint.key().as_ref()],
+        bump = accepted_mint.bump
+    )]
+    pub accepted_mint: Option<Account<'info, AcceptedMint>>,
+
+    /// The mint's fee override; charged instead of the vault-wide fee
+    /// while active
+    #[account(
+        seeds = [b"vault_mint", mint.key().as_ref()],
+        bump = vault_mint_config.bump
+    )]
+    pub vault_mint_config: Option<Account<'info, VaultMintConfig>>,
+
+    /// The volume tiers; required while the vault has a schedule
+    #[account(
+        seeds = [b"fee_schedule"],
+        bump = fee_schedule.bump
+    )]
+    pub fee_schedule: Option<Account<'info, FeeSchedule>>,
+
+    /// CHECK: Recipient wallet; recorded on the stream and signs withdrawals
+    pub recipient: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        constraint = fee_token_account.owner == vault_config.fee_collector @ VaultError::InvalidFeeAccount,
+        constraint = fee_token_account.mint == mint.key() @ VaultError::InvalidFeeAccount
+    )]
+    pub fee_token_account: Account<'info, TokenAccount>,
+
+    /// The second fee collector's account; required while it has a share
+    #[account(
+        mut,
+        constraint = fee_token_account_1.owner == vault_config.fee_collectors[1].collector
+            @ VaultError::InvalidFeeAccount,
+        constraint = fee_token_account_1.mint == mint.key() @ VaultError::InvalidFeeAccount
+    )]
+    pub fee_token_account_1: Option<Account<'info, TokenAccount>>,
+
+    /// The third fee collector's account; required while it has a share
+    #[account(
+        mut,
+        constraint = fee_token_account_2.owner == vault_config.fee_collectors[2].collector
+            @ VaultError::InvalidFeeAccount,
+        constraint = fee_token_account_2.mint == mint.key() @ VaultError::InvalidFeeAccount
+    )]
+    pub fee_token_account_2: Option<Account<'info, TokenAccount>>,
+
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(stream_id: [u8; 32])]
+pub struct WithdrawFromStream<'info> {
+    #[account(
+        seeds = [b"vault_config"],
+        bump = vault_config.bump
+    )]
+    pub vault_config: Account<'info, VaultConfig>,
+
+    #[account(
+        mut,
+        seeds = [b"stream", streaming_payment.sender.as_ref(), stream_id.as_ref()],
+        bump = streaming_payment.bump,
+        has_one = recipient @ VaultError::Unauthorized
+    )]
+    pub streaming_payment: Account<'info, StreamingPayment>,
+
+    #[account(
+        mut,
+        seeds = [b"stream_tokens", streaming_payment.sender.as_ref(), stream_id.as_ref()],
+        bump
+    )]
+    pub stream_tokens: Account<'info, TokenAccount>,
+
+    pub recipient: Signer<'info>,
+
+    #[account(
+        mut,
+        constraint = recipient_token_account.owner == recipient.key() @ VaultError::Unauthorized,
+        constraint = recipient_token_account.mint == streaming_payment.mint @ VaultError::MintMismatch
+    )]
+    pub recipient_token_account: Account<'info, TokenAccount>,
+
+    /// The stream's token; transfers are checked against its decimals
+    #[account(address = streaming_payment.mint @ VaultError::MintMismatch)]
+    pub mint: Account<'info, Mint>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+#[instruction(stream_id: [u8; 32])]
+pub struct TopUpStream<'info> {
+    #[account(
+        mut,
+        seeds = [b"vault_config"],
+        bump = vault_config.bump
+    )]
+    pub vault_config: Account<'info, VaultConfig>,
+
+    #[account(
+        mut,
+        seeds = [b"stream", sender.key().as_ref(), stream_id.as_ref()],
+        bump = streaming_payment.bump,
+        has_one = sender,
+        has_one = mint
+    )]
+    pub streaming_payment: Account<'info, StreamingPayment>,
+
+    #[account(
+        mut,
+        seeds = [b"stream_tokens", sender.key().as_ref(), stream_id.as_ref()],
+        bump
+    )]
+    pub stream_tokens: Account<'info, TokenAccount>,
+
+    #[account(
+        init_if_needed,
+        payer = sender,
+        space = 8 + PayerRateLimit::INIT_SPACE,
+        seeds = [b"rate_limit", sender.key().as_ref()],
+        bump
+    )]
+    pub payer_rate_limit: Account<'info, PayerRateLimit>,
+
+    /// Today's DailyVolumeRecord; a payment landing after midnight UTC
+    /// needs the next day's
+    #[account(
+        init_if_needed,
+        payer = sender,
+        space = 8 + DailyVolumeRecord::INIT_SPACE,
+        seeds = [b"daily_vol", current_day()?.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub daily_volume: Account<'info, DailyVolumeRecord>,
+
+    #[account(mut)]
+    pub sender: Signer<'info>,
+
+    /// CHECK: Where the sender's `BlacklistedPayer` would be; passed
+    /// whether or not it exists, so a blacklisted sender can't leave it out
+    #[account(seeds = [b"blacklist", sender.key().as_ref()], bump)]
+    pub blacklist_check: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        constraint = sender_token_account.mint == mint.key() @ VaultError::MintMismatch
+    )]
+    pub sender_token_account: Account<'info, TokenAccount>,
+
+    pub mint: Account<'info, Mint>,
+
+    /// The mint's whitelist entry; required while the vault requires one
+    #[account(
+        seeds = [b"accepted_mint", mint.key().as_ref()],
+        bump = accepted_mint.bump
+    )]
+    pub accepted_mint: Option<Account<'info, AcceptedMint>>,
+
+    /// The mint's fee override; charged instead of the vault-wide fee
+    /// while active
+    #[account(
+        seeds = [b"vault_mint", mint.key().as_ref()],
+        bump = vault_mint_config.bump
+    )]
+    pub vault_mint_config: Option<Account<'info, VaultMintConfig>>,
+
+    /// The volume tiers; required while the vault has a schedule
+    #[account(
+        seeds = [b"fee_schedule"],
+        bump = fee_schedule.bump
+    )]
+    pub fee_schedule: Option<Account<'info, FeeSchedule>>,
+
+    #[account(
+        mut,
+        constraint = fee_token_account.owner == vault_config.fee_collector @ VaultError::InvalidFeeAccount,
+        constraint = fee_token_account.mint == mint.key() @ VaultError::InvalidFeeAccount
+    )]
+    pub fee_token_account: Account<'info, TokenAccount>,
+
+    /// The second fee collector's account; required while it has a share
+    #[account(
+        mut,
+        constraint = fee_token_account_1.owner == vault_config.fee_collectors[1].collector
+            @ VaultError::InvalidFeeAccount,
+        constraint = fee_token_account_1.mint == mint.key() @ VaultError::InvalidFeeAccount
+    )]
+    pub fee_token_account_1: Option<Account<'info, TokenAccount>>,
+
+    /// The third fee collector's account; required while it has a share
+    #[account(
+        mut,
+        constraint = fee_token_account_2.owner == vault_config.fee_collectors[2].collector
+            @ VaultError::InvalidFeeAccount,
+        constraint = fee_token_account_2.mint == mint.key() @ VaultError::InvalidFeeAccount
+    )]
+    pub fee_token_account_2: Option<Account<'info, TokenAccount>>,
+
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(stream_id: [u8; 32])]
+pub struct CancelStream<'info> {
+    #[account(
+        seeds = [b"vault_config"],
+        bump = vault_config.bump
+    )]
+    pub vault_config: Account<'info, VaultConfig>,
+
+    #[account(
+        mut,
+        seeds = [b"stream", sender.key().as_ref(), stream_id.as_ref()],
+        bump = streaming_payment.bump,
+        has_one = sender,
+        has_one = recipient,
+        close = sender
+    )]
+    pub streaming_payment: Account<'info, StreamingPayment>,
+
+    #[account(
+        mut,
+        seeds = [b"stream_tokens", sender.key().as_ref(), stream_id.as_ref()],
+        bump
+    )]
+    pub stream_tokens: Account<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub sender: Signer<'info>,
+
+    #[account(
+        mut,
+        constraint = sender_token_account.owner == sender.key() @ VaultError::Unauthorized,
+        constraint = sender_token_account.mint == streaming_payment.mint @ VaultError::MintMismatch
+    )]
+    pub sender_token_account: Account<'info, TokenAccount>,
+
+    /// CHECK: The stream's recipient; paid what has streamed
+    pub recipient: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        constraint = recipient_token_account.owner == recipient.key() @ VaultError::MerchantAccountMismatch,
+        constraint = recipient_token_account.mint == streaming_payment.mint @ VaultError::MintMismatch
+    )]
+    pub recipient_token_account: Account<'info, TokenAccount>,
+
+    /// The stream's token; transfers are checked against its decimals
+    #[account(address = streaming_payment.mint @ VaultError::MintMismatch)]
+    pub mint: Account<'info, Mint>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+#[instruction(amount: u64, payment_id: [u8; 32])]
+pub struct CreateTimelockPayment<'info> {
+    #[account(
+        mut,
+        seeds = [b"vault_config"],
+        bump = vault_config.bump
+    )]
+    pub vault_config: Account<'info, VaultConfig>,
+
+    #[account(
+        init,
+        payer = funder,
+        space = 8 + TimelockPayment::INIT_SPACE,
+        seeds = [b"timelock", funder.key().as_ref(), payment_id.as_ref()],
+        bump
+    )]
+    pub timelock_payment: Account<'info, TimelockPayment>,
+
+    /// Holds the payment until it is claimed; the timelock payment signs the
+    /// transfer out of it
+    #[account(
+        init,
+        payer = funder,
+        seeds = [b"timelock_tokens", funder.key().as_ref(), payment_id.as_ref()],
+        bump,
+        token::mint = mint,
+        token::authority = timelock_payment
+    )]
+    pub timelock_tokens: Account<'info, TokenAccount>,
+
+    #[account(
+        init_if_needed,
+        payer = funder,
+        space = 8 + PayerRateLimit::INIT_SPACE,
+        seeds = [b"rate_limit", funder.key().as_ref()],
+        bump
+    )]
+    pub payer_rate_limit: Account<'info, PayerRateLimit>,
+
+    /// Today's DailyVolumeRecord; a payment landing after midnight UTC
+    /// needs the next day's
+    #[account(
+        init_if_needed,
+        payer = funder,
+        space = 8 + DailyVolumeRecord::INIT_SPACE,
+        seeds = [b"daily_vol", current_day()?.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub daily_volume: Account<'info, DailyVolumeRecord>,
+
+    #[account(mut)]
+    pub funder: Signer<'info>,
+
+    /// CHECK: Where the funder's `BlacklistedPayer` would be; passed
+    /// whether or not it exists, so a blacklisted funder can't leave it out
+    #[account(seeds = [b"blacklist", funder.key().as_ref()], bump)]
+    pub blacklist_check: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        constraint = funder_token_account.mint == mint.key() @ VaultError::MintMismatch
+    )]
+    pub funder_token_account: Account<'info, TokenAccount>,
+
+    pub mint: Account<'info, Mint>,
+
+    /// The mint's whitelist entry; required while the vault requires one
+    #[account(
+        seeds = [b"accepted_mint", mint.key().as_ref()],
+        bump = accepted_mint.bump
+    )]
+    pub accepted_mint: Option<Account<'info, AcceptedMint>>,
+
+    /// The mint's fee override; charged instead of the vault-wide fee
+    /// while active
+    #[account(
+        seeds = [b"vault_mint", mint.key().as_ref()],
+        bump = vault_mint_config.bump
+    )]
+    pub vault_mint_config: Option<Account<'info, VaultMintConfig>>,
+
+    /// The volume tiers; required while the vault has a schedule
+    #[account(
+        seeds = [b"fee_schedule"],
+        bump = fee_schedule.bump
+    )]
+    pub fee_schedule: Option<Account<'info, FeeSchedule>>,
+
+    /// CHECK: Beneficiary wallet; recorded on the payment and signs its claim
+    pub beneficiary: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        constraint = fee_token_account.owner == vault_config.fee_collector @ VaultError::InvalidFeeAccount,
+        constraint = fee_token_account.mint == mint.key() @ VaultError::InvalidFeeAccount
+    )]
+    pub fee_token_account: Account<'info, TokenAccount>,
+
+    /// The second fee collector's account; required while it has a share
+    #[account(
+        mut,
+        constraint = fee_token_account_1.owner == vault_config.fee_collectors[1].collector
+            @ VaultError::InvalidFeeAccount,
+        constraint = fee_token_account_1.mint == mint.key() @ VaultError::InvalidFeeAccount
+    )]
+    pub fee_token_account_1: Option<Account<'info, TokenAccount>>,
+
+    /// The third fee collector's account; required while it has a share
+    #[account(
+        mut,
+        constraint = fee_token_account_2.owner == vault_config.fee_collectors[2].collector
+            @ VaultError::InvalidFeeAccount,
+        constraint = fee_token_account_2.mint == mint.key() @ VaultError::InvalidFeeAccount
+    )]
+    pub fee_token_account_2: Option<Account<'info, TokenAccount>>,
+
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(payment_id: [u8; 32])]
+pub struct ClaimTimelockPayment<'info> {
+    #[account(
+        seeds = [b"vault_config"],
+        bump = vault_config.bump
+    )]
+    pub vault_config: Account<'info, VaultConfig>,
+
+    #[account(
+        mut,
+        seeds = [b"timelock", funder.key().as_ref(), payment_id.as_ref()],
+        bump = timelock_payment.bump,
+        has_one = beneficiary @ VaultError::Unauthorized,
+        has_one = funder
+    )]
+    pub timelock_payment: Account<'info, TimelockPayment>,
+
+    #[account(
+        mut,
+        seeds = [b"timelock_tokens", funder.key().as_ref(), payment_id.as_ref()],
+        bump
+    )]
+    pub timelock_tokens: Account<'info, TokenAccount>,
+
+    pub beneficiary: Signer<'info>,
+
+    #[account(
+        mut,
+        constraint = beneficiary_token_account.owner == beneficiary.key() @ VaultError::Unauthorized,
+        constraint = beneficiary_token_account.mint == timelock_payment.mint @ VaultError::MintMismatch
+    )]
+    pub beneficiary_token_account: Account<'info, TokenAccount>,
+
+    /// The timelocked payment's token; transfers are checked against its
+    /// decimals
+    #[account(address = timelock_payment.mint @ VaultError::MintMismatch)]
+    pub mint: Account<'info, Mint>,
+
+    /// CHECK: Funder of the payment; receives the token account's rent
+    #[account(mut)]
+    pub funder: UncheckedAccount<'info>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+#[instruction(batch_id: [u8; 32])]
+pub struct ProcessPayrollBatch<'info> {
+    #[account(
+        seeds = [b"vault_config"],
+        bump = vault_config.bump
+    )]
+    pub vault_config: Account<'info, VaultConfig>,
+
+    #[account(
+        init,
+        payer = company,
+        space = 8 + BatchRecord::INIT_SPACE,
+        seeds = [b"batch", batch_id.as_ref()],
+        bump
+    )]
+    pub batch_record: Account<'info, BatchRecord>,
+
+    /// Holds the batch's funds until employees are paid; the batch record
+    /// signs transfers out of it
+    #[account(
+        init,
+        payer = company,
+        seeds = [b"batch_escrow", batch_id.as_ref()],
+        bump,
+        token::mint = mint,
+        token::authority = batch_record
+    )]
+    pub escrow: Account<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub company: Signer<'info>,
+
+    /// CHECK: Where the company's `PayrollApproverConfig` would be; passed
+    /// whether or not it exists, so a company with an approver can't leave
+    /// it out
+    #[account(seeds = [b"payroll_approver", company.key().as_ref()], bump)]
+    pub payroll_approver_config: UncheckedAccount<'info>,
+
+    /// The company's payroll approver, co-signing the batch; required once
+    /// the company has named one
+    pub approver: Option<Signer<'info>>,
+
+    #[account(
+        mut,
+        constraint = company_token_account.mint == mint.key() @ VaultError::MintMismatch
+    )]
+    pub company_token_account: Account<'info, TokenAccount>,
+
+    /// Token the batch is paid in
+    pub mint: Account<'info, Mint>,
+
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SetPayrollApprover<'info> {
+    #[account(
+        init_if_needed,
+        payer = company,
+        space = 8 + PayrollApproverConfig::INIT_SPACE,
+        seeds = [b"payroll_approver", company.key().as_ref()],
+        bump
+    )]
+    pub payroll_approver_config: Account<'info, PayrollApproverConfig>,
+
+    #[account(mut)]
+    pub company: Signer<'info>,
+
+    /// The approver being replaced; required once the company has one
+    pub current_approver: Option<Signer<'info>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(schedule_id: [u8; 32])]
+pub struct CreatePayrollSchedule<'info> {
+    #[account(
+        init,
+        payer = company,
+        space = 8 + PayrollSchedule::INIT_SPACE,
+        seeds = [b"payroll_sched", company.key().as_ref(), schedule_id.as_ref()],
+        bump
+    )]
+    pub payroll_schedule: Account<'info, PayrollSchedule>,
+
+    #[account(mut)]
+    pub company: Signer<'info>,
+
+    /// Token the schedule's payrolls are paid in
+    pub mint: Account<'info, Mint>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(schedule_id: [u8; 32])]
+pub struct UpdatePayrollSchedule<'info> {
+    #[account(
+        mut,
+        seeds = [b"payroll_sched", company.key().as_ref(), schedule_id.as_ref()],
+        bump = payroll_schedule.bump,
+        has_one = company @ VaultError::Unauthorized
+    )]
+    pub payroll_schedule: Account<'info, PayrollSchedule>,
+
+    pub company: Signer<'info>,
+}
+
+/// The accounts of `process_payroll_batch`, under `batch`, with the
+/// schedule the payroll runs on
+#[derive(Accounts)]
+#[instruction(batch_id: [u8; 32], schedule_id: [u8; 32])]
+pub struct ExecuteScheduledPayroll<'info> {
+    #[account(
+        mut,
+        seeds = [b"payroll_sched", batch.company.key().as_ref(), schedule_id.as_ref()],
+        bump = payroll_schedule.bump
+    )]
+    pub payroll_schedule: Account<'info, PayrollSchedule>,
+
+    pub batch: ProcessPayrollBatch<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CloseBatchRecord<'info> {
+    #[account(
+        seeds = [b"vault_config"],
+        bump = vault_config.bump
+    )]
+    pub vault_config: Account<'info, VaultConfig>,
+
+    #[account(
+        mut,
+        seeds = [b"batch", batch_record.batch_id.as_ref()],
+        bump = batch_record.bump,
+        has_one = company @ VaultError::Unauthorized,
+        has_one = escrow,
+        close = company
+    )]
+    pub batch_record: Account<'info, BatchRecord>,
+
+    #[account(mut)]
+    pub escrow: Account<'info, TokenAccount>,
+
+    /// The company that ran the batch; receives both accounts' rent
+    #[account(mut)]
+    pub company: Signer<'info>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+#[instruction(payment_id: [u8; 32])]
+pub struct ClosePaymentRecord<'info> {
+    #[account(
+        seeds = [b"vault_config"],
+        bump = vault_config.bump
+    )]
+    pub vault_config: Account<'info, VaultConfig>,
+
+    #[account(
+        mut,
+        seeds = [b"payment", payer.key().as_ref(), payment_id.as_ref()],
+        bump = payment_record.bump,
+        has_one = payer,
+        close = payer
+    )]
+    pub payment_record: Account<'info, PaymentRecord>,
+
+    /// Payer of the payment; receives the record's rent
+    #[account(mut)]
+    pub payer: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(payment_id: [u8; 32])]
+pub struct ComplianceClosePaymentRecord<'info> {
+    #[account(
+        seeds = [b"vault_config"],
+        bump = vault_config.bump,
+        has_one = authority
+    )]
+    pub vault_config: Account<'info, VaultConfig>,
+
+    #[account(
+        mut,
+        seeds = [b"payment", payer.key().as_ref(), payment_id.as_ref()],
+        bump = payment_record.bump,
+        has_one = payer,
+        close = payer
+    )]
+    pub payment_record: Account<'info, PaymentRecord>,
+
+    /// CHECK: Payer of the payment, per the record; receives its rent
+    #[account(mut)]
+    pub payer: UncheckedAccount<'info>,
+
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct VerifyPaymentCommitment<'info> {
+    pub payment_record: Account<'info, PaymentRecord>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateFee<'info> {
+    #[account(
+        mut,
+        seeds = [b"vault_config"],
+        bump = vault_config.bump,
+        has_one = authority
+    )]
+    pub vault_config: Account<'info, VaultConfig>,
+
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetPaymentBounds<'info> {
+    #[account(
+        mut,
+        seeds = [b"vault_config"],
+        bump = vault_config.bump,
+        has_one = authority
+    )]
+    pub vault_config: Account<'info, VaultConfig>,
+
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetRateLimitConfig<'info> {
+    #[account(
+        mut,
+        seeds = [b"vault_config"],
+        bump = vault_config.bump,
+        has_one = authority
+    )]
+    pub vault_config: Account<'info, VaultConfig>,
+
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetDailyVolumeCap<'info> {
+    #[account(
+        mut,
+        seeds = [b"vault_config"],
+        bump = vault_config.bump,
+        has_one = authority
+    )]
+    pub vault_config: Account<'info, VaultConfig>,
+
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetRetentionDays<'info> {
+    #[account(
+        mut,
+        seeds = [b"vault_config"],
+        bump = vault_config.bump,
+        has_one = authority
+    )]
+    pub vault_config: Account<'info, VaultConfig>,
+
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct MigrateVaultConfig<'info> {
+    /// CHECK: an older, shorter layout doesn't deserialize as VaultConfig,
+    /// so the discriminator and authority are checked in the instruction
+    #[account(
+        mut,
+        seeds = [b"vault_config"],
+        bump,
+        owner = crate::ID
+    )]
+    pub vault_config: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ResizePaymentRecord<'info> {
+    #[account(
+        seeds = [b"vault_config"],
+        bump = vault_config.bump,
+        has_one = authority
+    )]
+    pub vault_config: Account<'info, VaultConfig>,
+
+    /// CHECK: an older, shorter layout doesn't deserialize as PaymentRecord,
+    /// so the discriminator is checked in the instruction. Any seed version.
+    #[account(mut, owner = crate::ID)]
+    pub payment_record: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SetFeeCollectors<'info> {
+    #[account(
+        mut,
+        seeds = [b"vault_config"],
+        bump = vault_config.bump,
+        has_one = authority
+    )]
+    pub vault_config: Account<'info, VaultConfig>,
+
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateFeeCollector<'info> {
+    #[account(
+        mut,
+        seeds = [b"vault_config"],
+        bump = vault_config.bump,
+        has_one = authority
+    )]
+    pub vault_config: Account<'info, VaultConfig>,
+
+    pub authority: Signer<'info>,
+
+    /// CHECK: New fee collector can be any account
+    pub new_fee_collector: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetMerchantFeeOverride<'info> {
+    #[account(
+        seeds = [b"vault_config"],
+        bump = vault_config.bump,
+        has_one = authority
+    )]
+    pub vault_config: Account<'info, VaultConfig>,
+
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = 8 + MerchantConfig::INIT_SPACE,
+        seeds = [b"merchant_cfg", merchant.key().as_ref()],
+        bump
+    )]
+    pub merchant_config: Account<'info, MerchantConfig>,
+
+    /// CHECK: Merchant wallet the override applies to
+    pub merchant: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SetMerchantFeeExempt<'info> {
+    #[account(
+        seeds = [b"vault_config"],
+        bump = vault_config.bump,
+        has_one = authority
+    )]
+    pub vault_config: Account<'info, VaultConfig>,
+
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = 8 + MerchantConfig::INIT_SPACE,
+        seeds = [b"merchant_cfg", merchant.key().as_ref()],
+        bump
+    )]
+    pub merchant_config: Account<'info, MerchantConfig>,
+
+    /// CHECK: Merchant wallet the exemption applies to
+    pub merchant: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RemoveMerchantFeeOverride<'info> {
+    #[account(
+        seeds = [b"vault_config"],
+        bump = vault_config.bump,
+        has_one = authority
+    )]
+    pub vault_config: Account<'info, VaultConfig>,
+
+    #[account(
+        mut,
+        seeds = [b"merchant_cfg", merchant_config.merchant.as_ref()],
+        bump = merchant_config.bump
+    )]
+    pub merchant_config: Account<'info, MerchantConfig>,
+
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct AddAcceptedMint<'info> {
+    #[account(
+        seeds = [b"vault_config"],
+        bump = vault_config.bump,
+        has_one = authority
+    )]
+    pub vault_config: Account<'info, VaultConfig>,
+
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = 8 + AcceptedMint::INIT_SPACE,
+        seeds = [b"accepted_mint", mint.key().as_ref()],
+        bump
+    )]
+    pub accepted_mint: Account<'info, AcceptedMint>,
+
+    pub mint: Account<'info, Mint>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RemoveAcceptedMint<'info> {
+    #[account(
+        seeds = [b"vault_config"],
+        bump = vault_config.bump,
+        has_one = authority
+    )]
+    pub vault_config: Account<'info, VaultConfig>,
+
+    #[account(
+        mut,
+        seeds = [b"accepted_mint", accepted_mint.mint.as_ref()],
+        bump = accepted_mint.bump
+    )]
+    pub accepted_mint: Account<'info, AcceptedMint>,
+
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetVaultMintConfig<'info> {
+    #[account(
+        seeds = [b"vault_config"],
+        bump = vault_config.bump,
+        has_one = authority
+    )]
+    pub vault_config: Account<'info, VaultConfig>,
+
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = 8 + VaultMintConfig::INIT_SPACE,
+        seeds = [b"vault_mint", mint.key().as_ref()],
+        bump
+    )]
+    pub vault_mint_config: Account<'info, VaultMintConfig>,
+
+    pub mint: Account<'info, Mint>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RemoveVaultMintConfig<'info> {
+    #[account(
+        seeds = [b"vault_config"],
+        bump = vault_config.bump,
+        has_one = authority
+    )]
+    pub vault_config: Account<'info, VaultConfig>,
+
+    #[account(
+        mut,
+        seeds = [b"vault_mint", vault_mint_config.mint.as_ref()],
+        bump = vault_mint_config.bump
+    )]
+    pub vault_mint_config: Account<'info, VaultMintConfig>,
+
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetCommitmentVerification<'info> {
+    #[account(
+        mut,
+        seeds = [b"vault_config"],
+        bump = vault_config.bump,
+        has_one = authority
+    )]
+    pub vault_config: Account<'info, VaultConfig>,
+
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetMintWhitelistRequired<'info> {
+    #[account(
+        mut,
+        seeds = [b"vault_config"],
+        bump = vault_config.bump,
+        has_one = authority
+    )]
+    pub vault_config: Account<'info, VaultConfig>,
+
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct RegisterMerchant<'info> {
+    #[account(
+        seeds = [b"vault_config"],
+        bump = vault_config.bump,
+        has_one = authority
+    )]
+    pub vault_config: Account<'info, VaultConfig>,
+
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = 8 + RegisteredMerchant::INIT_SPACE,
+        seeds = [b"merchant", merchant.key().as_ref()],
+        bump
+    )]
+    pub registered_merchant: Account<'info, RegisteredMerchant>,
+
+    /// CHECK: Merchant wallet being registered
+    pub merchant: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct DeregisterMerchant<'info> {
+    #[account(
+        seeds = [b"vault_config"],
+        bump = vault_config.bump,
+        has_one = authority
+    )]
+    pub vault_config: Account<'info, VaultConfig>,
+
+    #[account(
+        mut,
+        seeds = [b"merchant", registered_merchant.merchant.as_ref()],
+        bump = registered_merchant.bump
+    )]
+    pub registered_merchant: Account<'info, RegisteredMerchant>,
+
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetMerchantRegistrationRequired<'info> {
+    #[account(
+        mut,
+        seeds = [b"vault_config"],
+        bump = vault_config.bump,
+        has_one = authority
+    )]
+    pub vault_config: Account<'info, VaultConfig>,
+
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct BlacklistPayer<'info> {
+    #[account(
+        seeds = [b"vault_config"],
+        bump = vault_config.bump,
+        has_one = authority
+    )]
+    pub vault_config: Account<'info, VaultConfig>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + BlacklistedPayer::INIT_SPACE,
+        seeds = [b"blacklist", payer.key().as_ref()],
+        bump
+    )]
+    pub blacklisted_payer: Account<'info, BlacklistedPayer>,
+
+    /// CHECK: Payer wallet being blacklisted
+    pub payer: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RemoveFromBlacklist<'info> {
+    #[account(
+        seeds = [b"vault_config"],
+        bump = vault_config.bump,
+        has_one = authority
+    )]
+    pub vault_config: Account<'info, VaultConfig>,
+
+    #[account(
+        mut,
+        seeds = [b"blacklist", blacklisted_payer.payer.as_ref()],
+        bump = blacklisted_payer.bump,
+        close = authority
+    )]
+    pub blacklisted_payer: Account<'info, BlacklistedPayer>,
+
+    /// Receives the account's rent
+    #[account(mut)]
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetFeeSchedule<'info> {
+    #[account(
+        mut,
+        seeds = [b"vault_config"],
+        bump = vault_config.bump,
+        has_one = authority
+    )]
+    pub vault_config: Account<'info, VaultConfig>,
+
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = 8 + FeeSchedule::INIT_SPACE,
+        seeds = [b"fee_schedule"],
+        bump
+    )]
+    pub fee_schedule: Account<'info, FeeSchedule>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SetFeeRounding<'info> {
+    #[account(
+        mut,
+        seeds = [b"vault_config"],
+        bump = vault_config.bump,
+        has_one = authority
+    )]
+    pub vault_config: Account<'info, VaultConfig>,
+
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetMerchantVolumeTiers<'info> {
+    #[account(
+        mut,
+        seeds = [b"vault_config"],
+        bump = vault_config.bump,
+        has_one = authority
+    )]
+    pub vault_config: Account<'info, VaultConfig>,
+
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ClearFeeSchedule<'info> {
+    #[account(
+        mut,
+        seeds = [b"vault_config"],
+        bump = vault_config.bump,
+        has_one = authority
+    )]
+    pub vault_config: Account<'info, VaultConfig>,
+
+    #[account(
+        mut,
+        seeds = [b"fee_schedule"],
+        bump = fee_schedule.bump,
+        close = authority
+    )]
+    pub fee_schedule: Account<'info, FeeSchedule>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct PauseVault<'info> {
+    #[account(
+        mut,
+        seeds = [b"vault_config"],
+        bump = vault_config.bump,
+        has_one = authority
+    )]
+    pub vault_config: Account<'info, VaultConfig>,
+
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct UnpauseVault<'info> {
+    #[account(
+        mut,
+        seeds = [b"vault_config"],
+        bump = vault_config.bump,
+        has_one = authority
+    )]
+    pub vault_config: Account<'info, VaultConfig>,
+
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ProposeAuthority<'info> {
+    #[account(
+        mut,
+        seeds = [b"vault_config"],
+        bump = vault_config.bump,
+        has_one = authority
+    )]
+    pub vault_config: Account<'info, VaultConfig>,
+
+    pub authority: Signer<'info>,
+
+    /// CHECK: Proposed authority can be any account; it must sign to accept
+    pub new_authority: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct AcceptAuthority<'info> {
+    #[account(
+        mut,
+        seeds = [b"vault_config"],
+        bump = vault_config.bump,
+        constraint = vault_config.pending_authority == Some(pending_authority.key()) @ VaultError::Unauthorized
+    )]
+    pub vault_config: Account<'info, VaultConfig>,
+
+    pub pending_authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CancelAuthorityTransfer<'info> {
+    #[account(
+        mut,
+        seeds = [b"vault_config"],
+        bump = vault_config.bump,
+        has_one = authority
+    )]
+    pub vault_config: Account<'info, VaultConfig>,
+
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetRecoveryAuthority<'info> {
+    #[account(
+        mut,
+        seeds = [b"vault_config"],
+        bump = vault_config.bump,
+        has_one = authority
+    )]
+    pub vault_config: Account<'info, VaultConfig>,
+
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ProposeEmergencyRecovery<'info> {
+    #[account(
+        mut,
+        seeds = [b"vault_config"],
+        bump = vault_config.bump,
+        constraint = vault_config.recovery_authority == Some(recovery_authority.key()) @ VaultError::Unauthorized
+    )]
+    pub vault_config: Account<'info, VaultConfig>,
+
+    pub recovery_authority: Signer<'info>,
+
+    /// CHECK: Proposed authority can be any account; it need not sign
+    pub new_authority: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct EmergencyRecover<'info> {
+    #[account(
+        mut,
+        seeds = [b"vault_config"],
+        bump = vault_config.bump,
+        constraint = vault_config.recovery_authority == Some(recovery_authority.key()) @ VaultError::Unauthorized
+    )]
+    pub vault_config: Account<'info, VaultConfig>,
+
+    pub recovery_authority: Signer<'info>,
+
+    /// CHECK: Must be the proposed authority
+    pub new_authority: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CancelEmergencyRecovery<'info> {
+    #[account(
+        mut,
+        seeds = [b"vault_config"],
+        bump = vault_config.bump,
+        has_one = authority
+    )]
+    pub vault_config: Account<'info, VaultConfig>,
+
+    pub authority: Signer<'info>,
+}
 
 // ============ State ============
 
@@ -268,52 +5347,781 @@ pub struct VaultConfig {
     pub authority: Pubkey,
     pub fee_collector: Pubkey,
     pub fee_basis_points: u16,
-    pub total_volume: u64,
-    pub total_payments: u64,
-    pub bump: u8,
+    pub total_volume: u64,
+    pub total_payments: u64,
+    pub bump: u8,
+    pub paused: bool,
+    /// Smallest accepted payment, in base units
+    pub min_payment: u64,
+    /// Largest accepted payment, in base units; 0 for no limit
+    pub max_payment: u64,
+    /// Fees charged on payments since initialization (or migration), summed
+    /// across mints like `total_volume`; refunds don't reduce it
+    pub total_fees_collected: u64,
+    /// Payments one payer may make per rolling hour; 0 for no limit
+    pub max_payments_per_hour: u16,
+    /// Base units one payer may pay per rolling hour; 0 for no limit
+    pub max_volume_per_hour: u64,
+    /// Only accept payments in mints with an active `AcceptedMint`
+    pub require_mint_whitelist: bool,
+    /// Proposed by `propose_authority`, waiting to sign `accept_authority`
+    pub pending_authority: Option<Pubkey>,
+    /// A `FeeSchedule` exists and payments must pass it
+    pub has_fee_schedule: bool,
+    /// How payment fees are divided; the first is `fee_collector`,
+    /// unused entries are zeroed
+    pub fee_collectors: [FeeCollectorShare; MAX_FEE_COLLECTORS],
+    /// Unix time `pending_authority` must accept before; 0 with none pending
+    pub authority_transfer_expires_at: i64,
+    /// Base units all payers together may pay per UTC day; 0 for no limit
+    pub daily_volume_cap: u64,
+    /// Every payment instruction recomputes its commitment from the opening
+    pub verify_commitments: bool,
+    /// Days a PaymentRecord is kept before its payer can close it; read
+    /// through `retention_days()`, as a migrated config holds 0 here
+    pub retention_days: u16,
+    /// Only pay merchants with an active `RegisteredMerchant`
+    pub require_registered_merchants: bool,
+    /// Can propose and execute `emergency_recover`
+    pub recovery_authority: Option<Pubkey>,
+    /// Authority an emergency recovery would install, once proposed
+    pub pending_recovery_authority: Option<Pubkey>,
+    /// Unix time the pending recovery was proposed; 0 with none pending
+    pub recovery_proposed_at: i64,
+    /// `process_payment` fee tiers apply to the merchant's `MerchantStats`
+    /// volume rather than `total_volume`
+    pub merchant_volume_tiers: bool,
+    /// Fees are rounded up to the next base unit rather than down
+    pub round_fee_up: bool,
+}
+
+impl VaultConfig {
+    /// `retention_days`, raised to MIN_RETENTION_DAYS
+    pub fn retention_days(&self) -> u16 {
+        self.retention_days.max(MIN_RETENTION_DAYS)
+    }
+
+    /// Whether `amount` is non-zero and within the payment bounds
+    pub fn accepts_amount(&self, amount: u64) -> bool {
+        amount > 0
+            && amount >= self.min_payment && (self.max_payment == 0 || amount <= self.max_payment)
+    }
+
+    /// Fail unless verification is off or `commitment` opens to `amount`
+    /// with `blinding_factor`, hashed as the arcium service does
+    pub fn check_commitment(&self, amount: u64, commitment: &[u8; 32], blinding_factor: &[u8]) -> Result<()> {
+        if self.verify_commitments {
+            require!(
+                payment_commitment(amount, blinding_factor) == *commitment,
+                VaultError::CommitmentMismatch
+            );
+        }
+        Ok(())
+    }
+
+    /// Fail unless the whitelist is off or the payment's mint is on it
+    pub fn check_mint_whitelist(&self, accepted_mint: Option<&AcceptedMint>) -> Result<()> {
+        require!(
+            !self.require_mint_whitelist || accepted_mint.is_some_and(|accepted| accepted.is_active),
+            VaultError::MintNotWhitelisted
+        );
+        Ok(())
+    }
+
+    /// Fail unless registration is off or the payment's merchant is registered
+    pub fn check_merchant_registration(&self, registered_merchant: Option<&RegisteredMerchant>) -> Result<()> {
+        require!(
+            !self.require_registered_merchants || registered_merchant.is_some_and(|registered| registered.is_active),
+            VaultError::MerchantNotRegistered
+        );
+        Ok(())
+    }
+
+    /// Basis points of each payment fee per `fee_collectors`
+    /// entry. A config migrated from before the division existed has none
+    /// set, and pays `fee_collector` the whole fee.
+    pub fn fee_collector_shares(&self) -> [u16; MAX_FEE_COLLECTORS] {
+        let mut shares = self.fee_collectors.map(|entry| entry.basis_points);
+        if shares == [0; MAX_FEE_COLLECTORS] {
+            shares[0] = 10_000;
+        }
+        shares
+    }
+
+    /// The wallet `fee_collector_shares` pays each share to
+    pub fn fee_collector_keys(&self) -> [Pubkey; MAX_FEE_COLLECTORS] {
+        let mut keys = self.fee_collectors.map(|entry| entry.collector);
+        keys[0] = self.fee_collector;
+        keys
+    }
+
+    /// The fee of a payment without a merchant override: the rate of the
+    /// highest tier `total_volume` has reached, else `fee_basis_points`;
+    /// with the tier's index
+    pub fn scheduled_fee(&self, fee_schedule: Option<&FeeSchedule>) -> Result<(u16, Option<u8>)> {
+        self.scheduled_fee_at(fee_schedule, self.total_volume)
+    }
+
+    /// `scheduled_fee` with the tiers applied to `volume` instead
+    pub fn scheduled_fee_at(&self, fee_schedule: Option<&FeeSchedule>, volume: u64) -> Result<(u16, Option<u8>)> {
+        if !self.has_fee_schedule {
+            return Ok((self.fee_basis_points, None));
+        }
+        let fee_schedule = fee_schedule.ok_or(VaultError::FeeScheduleRequired)?;
+        Ok(match fee_schedule.tier_for(volume) {
+            Some((index, tier)) => (tier.fee_basis_points, Some(index)),
+            None => (self.fee_basis_points, None),
+        })
+    }
+
+    /// The fee of a payment, with the tier it came from if any. A
+    /// merchant's exemption or override (`merchant_fee`) takes precedence
+    /// over the mint's override, and either over the volume tiers, which
+    /// are applied to `tier_volume`.
+    pub fn payment_fee(
+        &self,
+        fee_schedule: Option<&FeeSchedule>,
+        tier_volume: u64,
+        vault_mint_config: Option<&VaultMintConfig>,
+        merchant_fee: Option<u16>,
+    ) -> Result<(u16, Option<u8>)> {
+        let (scheduled_fee, scheduled_tier) = self.scheduled_fee_at(fee_schedule, tier_volume)?;
+        let mint_fee = vault_mint_config
+            .filter(|vault_mint_config| vault_mint_config.is_active)
+            .map(|vault_mint_config| vault_mint_config.fee_basis_points_override);
+        Ok(match merchant_fee.or(mint_fee) {
+            Some(fee_basis_points) => (fee_basis_points, None),
+            None => (scheduled_fee, scheduled_tier),
+        })
+    }
+}
+
+/// Fields are only ever appended, by carving them out of `reserved` where
+/// they fit. Records written before `status`, `mint`, `expires_at`,
+/// `record_version` or `fee_collectors` existed are shorter than
+/// `8 + INIT_SPACE` and don't deserialize until `resize_payment_record`
+/// grows them; indexers read them by offset.
+#[account]
+#[derive(InitSpace)]
+pub struct PaymentRecord {
+    pub payment_id: [u8; 32],
+    pub payer: Pubkey,
+    pub merchant: Pubkey,
+    /// Gross: what the payer was debited, fee included
+    pub amount: u64,
+    pub fee: u64,
+    pub commitment: [u8; 32],
+    pub timestamp: i64,
+    pub bump: u8,
+    pub status: PaymentStatus,
+    pub mint: Pubkey,
+    /// Deadline the payment request was paid under; 0 for none
+    pub expires_at: i64,
+    /// RECORD_VERSION of the layout the record was written or resized to
+    pub record_version: u8,
+    /// Net amount returned by `refund_payment` so far; carved out of the
+    /// reserved tail, so zero in records written before it
+    pub refunded_amount: u64,
+    /// What the merchant received, `amount` less `fee`; zero in records
+    /// written before it was carved out
+    pub net_amount: u64,
+    /// Whether the fee came out of the merchant's amount or on top of it;
+    /// Deducted in records written before it
+    pub fee_mode: FeeMode,
+    pub reserved: [u8; RECORD_RESERVED_LEN - 17],
+    /// Who was paid each share of `fee`, by `VaultConfig::fee_collectors`
+    /// index when the payment was made; version 2
+    pub fee_collectors: [Pubkey; MAX_FEE_COLLECTORS],
+    /// Each collector's share of `fee`; all zero in records written before
+    /// version 2
+    pub fee_shares: [u64; MAX_FEE_COLLECTORS],
+}
+
+impl PaymentRecord {
+    /// Where `record_version` sits in the account data, in every layout
+    /// that has it
+    pub const RECORD_VERSION_OFFSET: usize = 8 + 32 + 32 + 32 + 8 + 8 + 32 + 8 + 1 + 1 + 32 + 8;
+
+    /// Each collector paid a share of `fee` and its share, by
+    /// `VaultConfig::fee_collectors` index. A record written before the
+    /// division was recorded reads as `fee_collector` having taken all of
+    /// it, as it did then.
+    pub fn fee_split(&self, fee_collector: Pubkey) -> [(Pubkey, u64); MAX_FEE_COLLECTORS] {
+        if self.fee_shares == [0; MAX_FEE_COLLECTORS] {
+            let mut split = [(Pubkey::default(), 0); MAX_FEE_COLLECTORS];
+            split[0] = (fee_collector, self.fee);
+            return split;
+        }
+        std::array::from_fn(|i| (self.fee_collectors[i], self.fee_shares[i]))
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace)]
+pub enum PaymentStatus {
+    /// Paid to the merchant and not refunded
+    Settled,
+    Refunded,
+    /// Some of the net amount has been refunded; fully refunded records
+    /// are closed
+    PartiallyRefunded,
+    /// Opened by the payer with `open_dispute`; neither refunded nor closed
+    /// until `resolve_dispute`
+    Disputed,
+}
+
+/// Who bears a payment's fee
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace)]
+pub enum FeeMode {
+    /// The fee comes out of `amount` and the merchant receives the rest
+    Deducted,
+    /// The merchant receives exactly `amount` and the payer pays the fee on
+    /// top of it
+    ExactNet,
+}
+
+/// One recipient of a split payment and its share, in basis points of the
+/// payment amount
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SplitEntry {
+    pub basis_points: u16,
+    pub recipient_token_account: Pubkey,
+}
+
+/// What a split payment sent one recipient
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SplitTransfer {
+    pub recipient_token_account: Pubkey,
+    pub basis_points: u16,
+    pub amount: u64,
+}
+
+/// A payer's payments in the current hour, at `[b"rate_limit", payer]`.
+/// Created on the payer's first payment, at the payer's expense.
+#[account]
+#[derive(InitSpace)]
+pub struct PayerRateLimit {
+    pub payer: Pubkey,
+    pub window_start: i64,
+    pub payments_this_window: u16,
+    pub volume_this_window: u64,
+    pub bump: u8,
+}
+
+impl PayerRateLimit {
+    pub const WINDOW_SECS: i64 = 3600;
+
+    /// Count a payment of `amount` at `now`, starting a new window once the
+    /// current one is over an hour old, and fail if it goes over either of
+    /// the vault's caps
+    pub fn record_payment(
+        &mut self,
+        vault_config: &VaultConfig,
+        payer: Pubkey,
+        bump: u8,
+        amount: u64,
+        now: i64,
+    ) -> Result<()> {
+        self.payer = payer;
+        self.bump = bump;
+        if now.saturating_sub(self.window_start) > Self::WINDOW_SECS {
+            self.window_start = now;
+            self.payments_this_window = 0;
+            self.volume_this_window = 0;
+        }
+
+        let max_payments = vault_config.max_payments_per_hour;
+        require!(
+            max_payments == 0 || self.payments_this_window < max_payments,
+            VaultError::RateLimitExceeded
+        );
+        // Uncapped counters saturate rather than fail the payment
+        let volume = self.volume_this_window.saturating_add(amount);
+        let max_volume = vault_config.max_volume_per_hour;
+        require!(max_volume == 0 || volume <= max_volume, VaultError::RateLimitExceeded);
+
+        self.payments_this_window = self.payments_this_window.saturating_add(1);
+        self.volume_this_window = volume;
+        Ok(())
+    }
+}
+
+/// A mint the vault accepts while it requires a whitelist, at
+/// `[b"accepted_mint", mint]`
+#[account]
+#[derive(InitSpace)]
+pub struct AcceptedMint {
+    pub mint: Pubkey,
+    pub is_active: bool,
+    pub bump: u8,
+}
+
+/// Per-mint settings, at `[b"vault_mint", mint]`
+#[account]
+#[derive(InitSpace)]
+pub struct VaultMintConfig {
+    pub mint: Pubkey,
+    /// Charged instead of the vault-wide fee while active
+    pub fee_basis_points_override: u16,
+    pub is_active: bool,
+    pub bump: u8,
+}
+
+/// A payer the vault refuses payments from, at `[b"blacklist", payer]`. Removing
+/// it from the blacklist closes the account.
+#[account]
+#[derive(InitSpace)]
+pub struct BlacklistedPayer {
+    pub payer: Pubkey,
+    /// Hash of the off-chain reason for the blacklisting
+    pub reason_hash: [u8; 32],
+    pub blacklisted_at: i64,
+    pub bump: u8,
+}
+
+/// A merchant the vault pays while it requires registration, at
+/// `[b"merchant", merchant]`
+#[account]
+#[derive(InitSpace)]
+pub struct RegisteredMerchant {
+    pub merchant: Pubkey,
+    pub is_active: bool,
+    pub bump: u8,
+}
+
+/// Volume paid to a merchant through `process_payment`, at
+/// `[b"merchant_stats", merchant]`, summed across mints
+#[account]
+#[derive(InitSpace)]
+pub struct MerchantStats {
+    pub merchant: Pubkey,
+    pub total_volume: u64,
+    pub payment_count: u64,
+    pub bump: u8,
+}
+
+/// Volume-based fees, at `[b"fee_schedule"]`. Tiers apply to
+/// `VaultConfig::total_volume` before the payment, summed across mints, or
+/// with `merchant_volume_tiers` set to the merchant's `MerchantStats`; it
+/// is the vault's only tier table.
+#[account]
+#[derive(InitSpace)]
+pub struct FeeSchedule {
+    /// Sorted by strictly ascending `volume_threshold`
+    #[max_len(MAX_FEE_TIERS)]
+    pub tiers: Vec<FeeThreshold>,
+    pub bump: u8,
+}
+
+impl FeeSchedule {
+    /// The highest tier whose threshold `total_volume` has reached
+    pub fn tier_for(&self, total_volume: u64) -> Option<(u8, &FeeThreshold)> {
+        self.tiers
+            .iter()
+            .enumerate()
+            .rev()
+            .find(|(_, tier)| total_volume >= tier.volume_threshold)
+            .map(|(index, tier)| (index as u8, tier))
+    }
+}
+
+/// One collector's part of payment fees
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, Default, PartialEq, Eq, InitSpace)]
+pub struct FeeCollectorShare {
+    pub collector: Pubkey,
+    /// Of each fee; the shares add up to 10000
+    pub basis_points: u16,
+}
+
+/// Charge `fee_basis_points` once the vault's volume reaches `volume_threshold`
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq, InitSpace)]
+pub struct FeeThreshold {
+    pub volume_threshold: u64,
+    pub fee_basis_points: u16,
+}
+
+/// Per-merchant settings, at `[b"merchant_cfg", merchant]`
+#[account]
+#[derive(InitSpace)]
+pub struct MerchantConfig {
+    pub merchant: Pubkey,
+    /// Charged instead of `VaultConfig::fee_basis_points` while active
+    pub fee_override_basis_points: u16,
+    pub is_active: bool,
+    pub bump: u8,
+    /// Charged no fee at all, whatever the override
+    pub fee_exempt: bool,
+}
+
+impl MerchantConfig {
+    /// The fee it sets: zero for an exempt merchant, else its active
+    /// override
+    pub fn fee_override(&self) -> Option<u16> {
+        if self.fee_exempt {
+            return Some(0);
+        }
+        self.is_active.then_some(self.fee_override_basis_points)
+    }
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct BatchRecord {
+    pub batch_id: [u8; 32],
+    pub company: Pubkey,
+    pub total_amount: u64,
+    pub payment_count: u16,
+    pub timestamp: i64,
+    pub bump: u8,
+    /// Token account at `[b"batch_escrow", batch_id]` holding the funds
+    pub escrow: Pubkey,
+    pub mint: Pubkey,
+    /// RECORD_VERSION of the layout the record was written in
+    pub record_version: u8,
+    pub reserved: [u8; RECORD_RESERVED_LEN],
+}
+
+/// The second signer of a company's payroll batches, at
+/// `[b"payroll_approver", company]`
+#[account]
+#[derive(InitSpace)]
+pub struct PayrollApproverConfig {
+    pub company: Pubkey,
+    pub approver: Pubkey,
+    pub bump: u8,
+}
+
+/// A company's recurring payroll, at
+/// `[b"payroll_sched", company, schedule_id]`
+#[account]
+#[derive(InitSpace)]
+pub struct PayrollSchedule {
+    pub company: Pubkey,
+    pub schedule_id: [u8; 32],
+    pub frequency_seconds: u64,
+    /// Unix time the next run can execute from
+    pub next_due_at: i64,
+    /// Most one run may pay out, in base units
+    pub authorized_amount: u64,
+    pub currency_mint: Pubkey,
+    pub status: ScheduleStatus,
+    pub bump: u8,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace)]
+pub enum ScheduleStatus {
+    Active,
+    /// Runs are refused until the company sets it Active again
+    Paused,
+}
+
+/// One employee's payment out of a batch, at
+/// `[b"disbursement", batch_id, index.to_le_bytes()]`
+#[account]
+#[derive(InitSpace)]
+pub struct DisbursementRecord {
+    pub batch_id: [u8; 32],
+    pub index: u16,
+    pub employee_token_account: Pubkey,
+    pub amount: u64,
+    pub timestamp: i64,
+    pub bump: u8,
+}
+
+/// A payment held by `create_escrow_payment`, at
+/// `[b"escrow_payment", payer, payment_id]`. Kept once captured or
+/// cancelled, so neither can happen twice.
+#[account]
+#[derive(InitSpace)]
+pub struct EscrowRecord {
+    pub payment_id: [u8; 32],
+    pub payer: Pubkey,
+    pub merchant: Pubkey,
+    pub mint: Pubkey,
+    pub amount: u64,
+    /// Charged on capture, out of `amount`
+    pub fee: u64,
+    pub commitment: [u8; 32],
+    pub created_at: i64,
+    /// The merchant can capture until then, and the payer cancel from then
+    /// on
+    pub expires_at: i64,
+    pub status: EscrowStatus,
+    pub bump: u8,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace)]
+pub enum EscrowStatus {
+    Held,
+    Captured,
+    /// Returned to the payer, by `cancel_escrow` or the expiry crank
+    Cancelled,
+}
+
+/// A stream opened by `open_stream`, at `[b"stream", sender, stream_id]`;
+/// closed when cancelled
+#[account]
+#[derive(InitSpace)]
+pub struct StreamingPayment {
+    pub stream_id: [u8; 32],
+    pub sender: Pubkey,
+    pub recipient: Pubkey,
+    pub mint: Pubkey,
+    pub rate_per_second: u64,
+    pub start_time: i64,
+    /// Moved later by `top_up_stream`
+    pub end_time: i64,
+    /// The recipient has been paid for the stream up to then
+    pub withdrawn_until: i64,
+    /// Paid in so far, including top-ups
+    pub deposit: u64,
+    pub bump: u8,
+}
+
+impl StreamingPayment {
+    /// How far the stream has run by `now`, and no further than its end
+    pub fn streamed_until(&self, now: i64) -> i64 {
+        now.min(self.end_time).max(self.withdrawn_until)
+    }
+}
+
+/// A payout held by `create_timelock_payment` until `unlock_at`, at
+/// `[b"timelock", funder, payment_id]`. Kept once claimed, so it can't be
+/// claimed twice.
+#[account]
+#[derive(InitSpace)]
+pub struct TimelockPayment {
+    pub payment_id: [u8; 32],
+    pub funder: Pubkey,
+    pub beneficiary: Pubkey,
+    pub mint: Pubkey,
+    /// What the beneficiary claims, the fee already paid
+    pub amount: u64,
+    pub created_at: i64,
+    /// The beneficiary can claim from then on
+    pub unlock_at: i64,
+    pub claimed: bool,
+    pub bump: u8,
+    /// Paid to the fee collectors on creation
+    pub fee: u64,
+}
+
+/// Volume paid through the vault on one UTC day, at
+/// `[b"daily_vol", date.to_le_bytes()]`
+#[account]
+#[derive(InitSpace)]
+pub struct DailyVolumeRecord {
+    /// Days since the Unix epoch
+    pub date: i64,
+    pub volume: u64,
+    pub bump: u8,
+}
+
+impl DailyVolumeRecord {
+    pub const DAY_SECS: i64 = 86_400;
+
+    /// Count a payment of `amount` at `now` towards its day, failing if it
+    /// goes over the vault's daily cap
+    pub fn record_payment(&mut self, vault_config: &VaultConfig, bump: u8, amount: u64, now: i64) -> Result<()> {
+        self.date = now / Self::DAY_SECS;
+        self.bump = bump;
+        // As for PayerRateLimit, an uncapped total saturates
+        let volume = self.volume.saturating_add(amount);
+        let cap = vault_config.daily_volume_cap;
+        require!(cap == 0 || volume <= cap, VaultError::DailyCapExceeded);
+        self.volume = volume;
+        Ok(())
+    }
+}
+
+/// The UTC day of the current slot, keying today's DailyVolumeRecord
+pub fn current_day() -> Result<i64> {
+    Ok(Clock::get()?.unix_timestamp / DailyVolumeRecord::DAY_SECS)
+}
+
+/// `sha256(amount_le || blinding_factor)`, the commitment the arcium
+/// service makes to a payment amount
+pub fn payment_commitment(amount: u64, blinding_factor: &[u8]) -> [u8; 32] {
+    hash::hashv(&[&amount.to_le_bytes(), blinding_factor]).to_bytes()
+}
+
+// ============ Events ============
+
+#[event]
+pub struct VaultInitialized {
+    pub authority: Pubkey,
+    pub fee_collector: Pubkey,
+    pub fee_basis_points: u16,
+}
+
+#[event]
+pub struct PaymentProcessed {
+    /// PaymentRecord address, derived per PAYMENT_RECORD_SEED_VERSION
+    pub record: Pubkey,
+    pub payment_id: [u8; 32],
+    pub payer: Pubkey,
+    pub merchant: Pubkey,
+    /// Debited from the payer, fee included
+    pub amount: u64,
+    /// Exactly what was charged, after rounding
+    pub fee: u64,
+    /// Received by the merchant
+    pub net_amount: u64,
+    pub fee_mode: FeeMode,
+    pub commitment: [u8; 32],
+    pub timestamp: i64,
+    pub mint: Pubkey,
+    pub expires_at: i64,
+    /// VaultConfig's running total, including this payment's fee
+    pub total_fees_collected: u64,
+    /// Index of the `FeeSchedule` tier charged; None for the vault-wide
+    /// fee or a merchant override
+    pub fee_tier: Option<u8>,
+    /// Rate `fee` was charged at
+    pub fee_basis_points: u16,
+    /// `fee` was rounded up to the next base unit rather than down
+    pub fee_rounded_up: bool,
 }
 
-#[account]
-#[derive(InitSpace)]
-pub struct PaymentRecord {
+#[event]
+pub struct SplitPaymentProcessed {
     pub payment_id: [u8; 32],
     pub payer: Pubkey,
-    pub merchant: Pubkey,
     pub amount: u64,
     pub fee: u64,
     pub commitment: [u8; 32],
+    pub mint: Pubkey,
+    pub splits: Vec<SplitTransfer>,
     pub timestamp: i64,
-    pub bump: u8,
+    /// VaultConfig's running total, including this payment's fee
+    pub total_fees_collected: u64,
 }
 
-#[account]
-#[derive(InitSpace)]
-pub struct BatchRecord {
-    pub batch_id: [u8; 32],
-    pub company: Pubkey,
-    pub total_amount: u64,
-    pub payment_count: u16,
+#[event]
+pub struct PaymentRefunded {
+    pub payment_id: [u8; 32],
+    pub payer: Pubkey,
+    pub merchant: Pubkey,
+    /// Returned by this refund, out of the net amount
+    pub amount: u64,
+    /// Returned by all the payment's refunds so far
+    pub refunded_amount: u64,
     pub timestamp: i64,
-    pub bump: u8,
 }
 
-// ============ Events ============
+#[event]
+pub struct PaymentDisputed {
+    pub payment_id: [u8; 32],
+    pub payer: Pubkey,
+    pub merchant: Pubkey,
+    pub timestamp: i64,
+}
 
 #[event]
-pub struct VaultInitialized {
-    pub authority: Pubkey,
-    pub fee_collector: Pubkey,
-    pub fee_basis_points: u16,
+pub struct DisputeResolved {
+    pub payment_id: [u8; 32],
+    pub payer: Pubkey,
+    pub merchant: Pubkey,
+    pub timestamp: i64,
 }
 
 #[event]
-pub struct PaymentProcessed {
+pub struct EscrowPaymentCreated {
     pub payment_id: [u8; 32],
     pub payer: Pubkey,
     pub merchant: Pubkey,
     pub amount: u64,
     pub fee: u64,
-    pub commitment: [u8; 32],
+    pub mint: Pubkey,
+    pub expires_at: i64,
+}
+
+#[event]
+pub struct PaymentCaptured {
+    pub payment_id: [u8; 32],
+    pub payer: Pubkey,
+    pub merchant: Pubkey,
+    pub amount: u64,
+    pub fee: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct EscrowCancelled {
+    pub payment_id: [u8; 32],
+    pub payer: Pubkey,
+    pub amount: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct EscrowExpiredRefunded {
+    pub payment_id: [u8; 32],
+    pub payer: Pubkey,
+    pub amount: u64,
+    pub cranker: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct StreamOpened {
+    pub stream_id: [u8; 32],
+    pub sender: Pubkey,
+    pub recipient: Pubkey,
+    pub mint: Pubkey,
+    pub rate_per_second: u64,
+    pub start_time: i64,
+    pub end_time: i64,
+    pub deposit: u64,
+    /// Charged on top of the deposit
+    pub fee: u64,
+}
+
+#[event]
+pub struct StreamWithdrawn {
+    pub stream_id: [u8; 32],
+    pub recipient: Pubkey,
+    pub amount: u64,
+    pub withdrawn_until: i64,
+}
+
+#[event]
+pub struct StreamToppedUp {
+    pub stream_id: [u8; 32],
+    pub sender: Pubkey,
+    pub amount: u64,
+    pub end_time: i64,
+    /// Charged on top of `amount`
+    pub fee: u64,
+}
+
+#[event]
+pub struct StreamCancelled {
+    pub stream_id: [u8; 32],
+    pub sender: Pubkey,
+    pub recipient: Pubkey,
+    /// Paid to the recipient on cancelling
+    pub streamed: u64,
+    /// Returned to the sender
+    pub refunded: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct TimelockCreated {
+    pub payment_id: [u8; 32],
+    pub funder: Pubkey,
+    pub beneficiary: Pubkey,
+    pub mint: Pubkey,
+    pub amount: u64,
+    pub unlock_at: i64,
+    pub fee: u64,
+}
+
+#[event]
+pub struct TimelockClaimed {
+    pub payment_id: [u8; 32],
+    pub beneficiary: Pubkey,
+    pub amount: u64,
     pub timestamp: i64,
 }
 
@@ -324,6 +6132,55 @@ pub struct PayrollBatchProcessed {
     pub total_amount: u64,
     pub payment_count: u16,
     pub timestamp: i64,
+    pub escrow: Pubkey,
+    pub mint: Pubkey,
+}
+
+#[event]
+pub struct PayrollApproverChanged {
+    pub company: Pubkey,
+    /// Default when the company had no approver
+    pub previous_approver: Pubkey,
+    pub approver: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct PayrollScheduleSet {
+    pub schedule_id: [u8; 32],
+    pub company: Pubkey,
+    pub frequency_seconds: u64,
+    pub next_due_at: i64,
+    pub authorized_amount: u64,
+    pub status: ScheduleStatus,
+}
+
+#[event]
+pub struct ScheduledPayrollExecuted {
+    pub schedule_id: [u8; 32],
+    pub company: Pubkey,
+    pub batch_id: [u8; 32],
+    /// When the schedule is due again
+    pub next_due_at: i64,
+}
+
+#[event]
+pub struct BatchRecordClosed {
+    pub batch_id: [u8; 32],
+    pub company: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct PaymentRecordClosed {
+    pub payment_id: [u8; 32],
+    pub payer: Pubkey,
+    /// Lamports returned to the payer
+    pub rent_reclaimed: u64,
+    /// The record's commitment, kept for indexers' audit trail
+    pub commitment: [u8; 32],
+    /// The payer, or the authority for a compliance close
+    pub closed_by: Pubkey,
 }
 
 #[event]
@@ -332,12 +6189,227 @@ pub struct FeeUpdated {
     pub new_fee: u16,
 }
 
+#[event]
+pub struct RateLimitConfigUpdated {
+    pub max_payments_per_hour: u16,
+    pub max_volume_per_hour: u64,
+}
+
+#[event]
+pub struct DailyVolumeCapUpdated {
+    pub daily_volume_cap: u64,
+}
+
+#[event]
+pub struct RetentionDaysUpdated {
+    pub retention_days: u16,
+}
+
+#[event]
+pub struct SolPaymentProcessed {
+    /// PaymentRecord address, derived per PAYMENT_RECORD_SEED_VERSION
+    pub record: Pubkey,
+    pub payment_id: [u8; 32],
+    pub payer: Pubkey,
+    pub merchant: Pubkey,
+    pub lamports: u64,
+    pub fee_lamports: u64,
+    pub net_lamports: u64,
+    pub commitment: [u8; 32],
+    pub timestamp: i64,
+    /// VaultConfig's running total, including this payment's fee
+    pub total_fees_collected: u64,
+}
+
+#[event]
+pub struct AcceptedMintAdded {
+    pub mint: Pubkey,
+}
+
+#[event]
+pub struct AcceptedMintRemoved {
+    pub mint: Pubkey,
+}
+
+#[event]
+pub struct VaultMintConfigSet {
+    pub mint: Pubkey,
+    pub fee_basis_points_override: u16,
+}
+
+#[event]
+pub struct VaultMintConfigRemoved {
+    pub mint: Pubkey,
+}
+
+#[event]
+pub struct MintWhitelistRequirementUpdated {
+    pub required: bool,
+}
+
+#[event]
+pub struct MerchantRegistered {
+    pub merchant: Pubkey,
+}
+
+#[event]
+pub struct MerchantDeregistered {
+    pub merchant: Pubkey,
+}
+
+#[event]
+pub struct MerchantRegistrationRequirementUpdated {
+    pub required: bool,
+}
+
+#[event]
+pub struct BlacklistedPayerAdded {
+    pub payer: Pubkey,
+    pub reason_hash: [u8; 32],
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct BlacklistedPayerRemoved {
+    pub payer: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct CommitmentVerificationUpdated {
+    pub enabled: bool,
+}
+
+#[event]
+pub struct FeeScheduleSet {
+    pub tiers: Vec<FeeThreshold>,
+}
+
+#[event]
+pub struct FeeScheduleCleared {
+    pub authority: Pubkey,
+}
+
+#[event]
+pub struct MerchantVolumeTiersUpdated {
+    pub enabled: bool,
+}
+
+#[event]
+pub struct FeeRoundingUpdated {
+    pub round_fee_up: bool,
+}
+
+#[event]
+pub struct VaultConfigMigrated {
+    pub old_len: u32,
+    pub new_len: u32,
+}
+
+#[event]
+pub struct PaymentBoundsUpdated {
+    pub min_payment: u64,
+    pub max_payment: u64,
+}
+
+#[event]
+pub struct PaymentRecordResized {
+    pub record: Pubkey,
+    pub old_len: u32,
+    pub new_len: u32,
+}
+
+#[event]
+pub struct FeeCollectorUpdated {
+    pub old_fee_collector: Pubkey,
+    pub new_fee_collector: Pubkey,
+}
+
+#[event]
+pub struct FeeCollectorsUpdated {
+    pub fee_collectors: Vec<FeeCollectorShare>,
+}
+
+#[event]
+pub struct MerchantFeeOverrideSet {
+    pub merchant: Pubkey,
+    pub fee_override_basis_points: u16,
+}
+
+#[event]
+pub struct MerchantFeeOverrideRemoved {
+    pub merchant: Pubkey,
+}
+
+#[event]
+pub struct MerchantFeeUpdated {
+    pub merchant: Pubkey,
+    /// None when the merchant is back on the vault-wide fee
+    pub fee_override_bps: Option<u16>,
+}
+
+#[event]
+pub struct MerchantFeeExemptionUpdated {
+    pub merchant: Pubkey,
+    pub fee_exempt: bool,
+}
+
+#[event]
+pub struct VaultPaused {
+    pub authority: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct VaultUnpaused {
+    pub authority: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct AuthorityTransferProposed {
+    pub authority: Pubkey,
+    pub pending_authority: Pubkey,
+    pub expires_at: i64,
+}
+
+#[event]
+pub struct AuthorityTransferCancelled {
+    pub authority: Pubkey,
+    pub pending_authority: Pubkey,
+}
+
 #[event]
 pub struct AuthorityTransferred {
     pub old_authority: Pubkey,
     pub new_authority: Pubkey,
 }
 
+#[event]
+pub struct RecoveryAuthorityUpdated {
+    pub recovery_authority: Option<Pubkey>,
+}
+
+#[event]
+pub struct EmergencyRecoveryProposed {
+    pub recovery_authority: Pubkey,
+    pub new_authority: Pubkey,
+    pub executable_at: i64,
+}
+
+#[event]
+pub struct EmergencyRecoveryExecuted {
+    pub old_authority: Pubkey,
+    pub new_authority: Pubkey,
+    pub recovery_authority: Pubkey,
+}
+
+#[event]
+pub struct EmergencyRecoveryCancelled {
+    pub authority: Pubkey,
+    pub new_authority: Pubkey,
+}
+
 // ============ Errors ============
 
 #[error_code]
@@ -348,4 +6420,96 @@ pub enum VaultError {
     InvalidAmount,
     #[msg("Unauthorized access")]
     Unauthorized,
+    #[msg("Payment has already been refunded")]
+    AlreadyRefunded,
+    #[msg("Fee token account is not the fee collector's account for this mint")]
+    InvalidFeeAccount,
+    #[msg("Vault is paused")]
+    VaultPaused,
+    #[msg("Merchant token account is not owned by the merchant")]
+    MerchantAccountMismatch,
+    #[msg("Token accounts are for different mints")]
+    MintMismatch,
+    #[msg("Merchant config is not this merchant's fee override account")]
+    InvalidMerchantConfig,
+    #[msg("Arithmetic overflow")]
+    MathOverflow,
+    #[msg("Payment amount is smaller than its fee")]
+    AmountTooSmall,
+    #[msg("Payment request has expired")]
+    PaymentExpired,
+    #[msg("Split recipients or basis points are invalid")]
+    InvalidSplit,
+    #[msg("Token balance is too low to fund the batch")]
+    InsufficientFunds,
+    #[msg("Disbursements don't match the batch total, count or accounts")]
+    InvalidDisbursement,
+    #[msg("Payer has reached the vault's hourly payment limit")]
+    RateLimitExceeded,
+    #[msg("Mint is not on the vault's whitelist")]
+    MintNotWhitelisted,
+    #[msg("No authority transfer is pending")]
+    NoPendingAuthority,
+    #[msg("Fee tiers must number 1 to 5 with ascending volume thresholds")]
+    InvalidFeeSchedule,
+    #[msg("Fee schedule account is required while the vault has one")]
+    FeeScheduleRequired,
+    #[msg("Fee collectors must be 1 to 3 distinct accounts with shares adding up to 10000")]
+    InvalidFeeCollectors,
+    #[msg("A fee collector's token account is missing")]
+    MissingFeeAccount,
+    #[msg("Payment record is still within the vault's retention period")]
+    RetentionPeriodNotElapsed,
+    #[msg("Authority transfer offer has expired")]
+    AuthorityTransferExpired,
+    #[msg("Payment batch must have 1 to 5 payments, each with its amount, id, commitment and two accounts")]
+    InvalidPaymentBatch,
+    #[msg("Batch is not final yet")]
+    BatchNotFinal,
+    #[msg("Payment exceeds the vault's daily volume cap")]
+    DailyCapExceeded,
+    #[msg("Refund exceeds the payment's unrefunded net amount")]
+    RefundExceedsPayment,
+    #[msg("Commitment does not match the amount and blinding factor")]
+    CommitmentMismatch,
+    #[msg("Retention period is shorter than the minimum of 30 days")]
+    RetentionTooShort,
+    #[msg("Payment is under dispute")]
+    PaymentUnderDispute,
+    #[msg("Payment is not under dispute")]
+    NotDisputed,
+    #[msg("Escrowed payment was already captured or cancelled")]
+    EscrowNotHeld,
+    #[msg("Escrowed payment can't be cancelled before it expires")]
+    EscrowNotExpired,
+    #[msg("Payroll schedule is not due yet")]
+    ScheduleNotDue,
+    #[msg("Payroll schedule is paused")]
+    ScheduleNotActive,
+    #[msg("Payroll schedule needs a positive frequency and authorized amount")]
+    InvalidSchedule,
+    #[msg("Payroll exceeds the schedule's authorized amount")]
+    ScheduleAmountExceeded,
+    #[msg("Merchant is not registered with the vault")]
+    MerchantNotRegistered,
+    #[msg("Payroll approver must be a key other than the company")]
+    InvalidPayrollApprover,
+    #[msg("Payer is blacklisted")]
+    PayerBlacklisted,
+    #[msg("No emergency recovery has been proposed")]
+    NoPendingRecovery,
+    #[msg("Emergency recovery can't be executed until 24 hours after its proposal")]
+    RecoveryTimelockActive,
+    #[msg("Unlock time must be in the future")]
+    InvalidUnlockTime,
+    #[msg("Timelocked payment hasn't unlocked yet")]
+    TimelockNotUnlocked,
+    #[msg("Timelocked payment already claimed")]
+    TimelockAlreadyClaimed,
+    #[msg("Stream must start now or later, end after it starts and have a positive rate")]
+    InvalidStream,
+    #[msg("Nothing has streamed since the last withdrawal")]
+    NothingStreamed,
+    #[msg("Stream has ended")]
+    StreamEnded,
 }
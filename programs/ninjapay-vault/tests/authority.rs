@@ -0,0 +1,178 @@
+mod common;
+
+use anchor_lang::{AccountDeserialize, InstructionData, ToAccountMetas};
+use common::{assert_program_error, assert_vault_error, vault_config, Vault, FEE_BASIS_POINTS};
+use ninjapay_vault::{VaultConfig, VaultError, AUTHORITY_TRANSFER_WINDOW};
+use solana_sdk::{
+    instruction::Instruction,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+};
+
+fn update_fee(authority: Pubkey, new_fee_basis_points: u16) -> Instruction {
+    Instruction {
+        program_id: ninjapay_vault::ID,
+        accounts: ninjapay_vault::accounts::UpdateFee {
+            vault_config: vault_config(),
+            authority,
+        }
+        .to_account_metas(None),
+        data: ninjapay_vault::instruction::UpdateFee { new_fee_basis_points }.data(),
+    }
+}
+
+fn propose_authority(authority: Pubkey, new_authority: Pubkey) -> Instruction {
+    Instruction {
+        program_id: ninjapay_vault::ID,
+        accounts: ninjapay_vault::accounts::ProposeAuthority {
+            vault_config: vault_config(),
+            authority,
+            new_authority,
+        }
+        .to_account_metas(None),
+        data: ninjapay_vault::instruction::ProposeAuthority {}.data(),
+    }
+}
+
+fn accept_authority(pending_authority: Pubkey) -> Instruction {
+    Instruction {
+        program_id: ninjapay_vault::ID,
+        accounts: ninjapay_vault::accounts::AcceptAuthority {
+            vault_config: vault_config(),
+            pending_authority,
+        }
+        .to_account_metas(None),
+        data: ninjapay_vault::instruction::AcceptAuthority {}.data(),
+    }
+}
+
+fn cancel_authority_transfer(authority: Pubkey) -> Instruction {
+    Instruction {
+        program_id: ninjapay_vault::ID,
+        accounts: ninjapay_vault::accounts::CancelAuthorityTransfer {
+            vault_config: vault_config(),
+            authority,
+        }
+        .to_account_metas(None),
+        data: ninjapay_vault::instruction::CancelAuthorityTransfer {}.data(),
+    }
+}
+
+async fn config(vault: &mut Vault) -> VaultConfig {
+    let account = vault.context.banks_client.get_account(vault_config()).await.unwrap().unwrap();
+    VaultConfig::try_deserialize(&mut account.data.as_slice()).unwrap()
+}
+
+#[tokio::test]
+async fn test_update_fee_is_capped_at_10_percent() {
+    let mut vault = Vault::start().await;
+    let authority = vault.payer();
+
+    vault.send(&[update_fee(authority, 1_000)], &[]).await.unwrap();
+    assert_eq!(config(&mut vault).await.fee_basis_points, 1_000);
+
+    let result = vault.send(&[update_fee(authority, 1_001)], &[]).await;
+    assert_vault_error(result, VaultError::FeeTooHigh);
+
+    vault.send(&[update_fee(authority, 0)], &[]).await.unwrap();
+    assert_eq!(config(&mut vault).await.fee_basis_points, 0);
+}
+
+#[tokio::test]
+async fn test_only_authority_can_update_fee() {
+    let mut vault = Vault::start().await;
+    let intruder = Keypair::new();
+
+    let result = vault.send(&[update_fee(intruder.pubkey(), 0)], &[&intruder]).await;
+
+    assert_program_error(result, anchor_lang::error::ErrorCode::ConstraintHasOne.into());
+    assert_eq!(config(&mut vault).await.fee_basis_points, FEE_BASIS_POINTS);
+}
+
+#[tokio::test]
+async fn test_accepted_authority_replaces_the_old_one() {
+    let mut vault = Vault::start().await;
+    let old_authority = vault.payer();
+    let new_authority = Keypair::new();
+
+    let intruder = Keypair::new();
+    let result = vault.send(&[propose_authority(intruder.pubkey(), intruder.pubkey())], &[&intruder]).await;
+    assert_program_error(result, anchor_lang::error::ErrorCode::ConstraintHasOne.into());
+
+    // Proposing alone hands nothing over
+    vault.send(&[propose_authority(old_authority, new_authority.pubkey())], &[]).await.unwrap();
+    let proposed = config(&mut vault).await;
+    assert_eq!((proposed.authority, proposed.pending_authority), (old_authority, Some(new_authority.pubkey())));
+    assert!(proposed.authority_transfer_expires_at > 0);
+
+    vault.send(&[accept_authority(new_authority.pubkey())], &[&new_authority]).await.unwrap();
+    let accepted = config(&mut vault).await;
+    assert_eq!((accepted.authority, accepted.pending_authority), (new_authority.pubkey(), None));
+    assert_eq!(accepted.authority_transfer_expires_at, 0);
+
+    let result = vault.send(&[update_fee(old_authority, 100)], &[]).await;
+    assert_program_error(result, anchor_lang::error::ErrorCode::ConstraintHasOne.into());
+    vault.send(&[update_fee(new_authority.pubkey(), 100)], &[&new_authority]).await.unwrap();
+    assert_eq!(config(&mut vault).await.fee_basis_points, 100);
+}
+
+#[tokio::test]
+async fn test_only_the_proposed_key_can_accept() {
+    let mut vault = Vault::start().await;
+    let authority = vault.payer();
+    let new_authority = Keypair::new();
+    let intruder = Keypair::new();
+
+    // Nothing proposed yet
+    let result = vault.send(&[accept_authority(new_authority.pubkey())], &[&new_authority]).await;
+    assert_vault_error(result, VaultError::Unauthorized);
+
+    vault.send(&[propose_authority(authority, new_authority.pubkey())], &[]).await.unwrap();
+    let result = vault.send(&[accept_authority(intruder.pubkey())], &[&intruder]).await;
+    assert_vault_error(result, VaultError::Unauthorized);
+    assert_eq!(config(&mut vault).await.authority, authority);
+}
+
+#[tokio::test]
+async fn test_cancelled_proposal_cannot_be_accepted() {
+    let mut vault = Vault::start().await;
+    let authority = vault.payer();
+    let new_authority = Keypair::new();
+
+    vault.send(&[propose_authority(authority, new_authority.pubkey())], &[]).await.unwrap();
+    vault.send(&[cancel_authority_transfer(authority)], &[]).await.unwrap();
+    assert_eq!(config(&mut vault).await.pending_authority, None);
+
+    let result = vault.send(&[accept_authority(new_authority.pubkey())], &[&new_authority]).await;
+    assert_vault_error(result, VaultError::Unauthorized);
+    assert_eq!(config(&mut vault).await.authority, authority);
+
+    let result = vault.send(&[cancel_authority_transfer(authority)], &[]).await;
+    assert_vault_error(result, VaultError::NoPendingAuthority);
+}
+
+#[tokio::test]
+async fn test_proposal_can_only_be_accepted_within_48_hours() {
+    for (accepted_after, expired) in [(AUTHORITY_TRANSFER_WINDOW - 1, false), (AUTHORITY_TRANSFER_WINDOW, true)] {
+        let mut vault = Vault::start().await;
+        let authority = vault.payer();
+        let new_authority = Keypair::new();
+        vault.set_time(1_760_000_000).await;
+
+        vault.send(&[propose_authority(authority, new_authority.pubkey())], &[]).await.unwrap();
+        let expires_at = config(&mut vault).await.authority_transfer_expires_at;
+        assert_eq!(expires_at, 1_760_000_000 + AUTHORITY_TRANSFER_WINDOW);
+
+        vault.set_time(1_760_000_000 + accepted_after).await;
+        let result = vault.send(&[accept_authority(new_authority.pubkey())], &[&new_authority]).await;
+        if expired {
+            assert_vault_error(result, VaultError::AuthorityTransferExpired);
+            // Still pending, for the authority to cancel or propose again
+            let config = config(&mut vault).await;
+            assert_eq!((config.authority, config.pending_authority), (authority, Some(new_authority.pubkey())));
+        } else {
+            result.unwrap();
+            assert_eq!(config(&mut vault).await.authority, new_authority.pubkey());
+        }
+    }
+}
@@ -0,0 +1,107 @@
+mod common;
+
+use anchor_lang::AccountDeserialize;
+use common::{
+    assert_program_error, assert_vault_error, blacklist_payer_instruction, blacklisted_payer,
+    remove_from_blacklist_instruction, sol_payment_instruction, Vault, START_TIME,
+};
+use ninjapay_vault::{BlacklistedPayer, VaultError};
+use solana_sdk::{
+    signature::{Keypair, Signer},
+    system_instruction,
+};
+
+#[tokio::test]
+async fn test_blacklisted_payer_is_refused_until_removed() {
+    let mut vault = Vault::start().await;
+    let payer = Keypair::new();
+    let payment = vault.payment_by(&payer).await;
+    let authority = vault.payer();
+
+    vault.send(&[blacklist_payer_instruction(authority, payer.pubkey(), [3; 32])], &[]).await.unwrap();
+    let account = vault.context.banks_client.get_account(blacklisted_payer(&payer.pubkey())).await.unwrap().unwrap();
+    let blacklisted = BlacklistedPayer::try_deserialize(&mut account.data.as_slice()).unwrap();
+    assert_eq!((blacklisted.payer, blacklisted.reason_hash), (payer.pubkey(), [3; 32]));
+    assert_eq!(blacklisted.blacklisted_at, START_TIME);
+
+    let result = vault.send(&[payment.instruction(1_000_000, [1; 32])], &[&payer]).await;
+    assert_vault_error(result, VaultError::PayerBlacklisted);
+    assert_eq!(vault.balance(&payment.payer_token_account).await, 10_000_000);
+
+    vault.send(&[remove_from_blacklist_instruction(authority, payer.pubkey())], &[]).await.unwrap();
+    assert!(vault.context.banks_client.get_account(blacklisted_payer(&payer.pubkey())).await.unwrap().is_none());
+    vault.send(&[payment.instruction(1_000_000, [2; 32])], &[&payer]).await.unwrap();
+    assert_eq!(vault.balance(&payment.merchant_token_account).await, 995_000);
+}
+
+#[tokio::test]
+async fn test_blacklisted_payer_is_refused_on_every_payment_path() {
+    let mut vault = Vault::start().await;
+    vault.ensure_native_mint().await;
+    let payer = Keypair::new();
+    let payment = vault.payment_by(&payer).await;
+    let authority = vault.payer();
+    vault.send(&[blacklist_payer_instruction(authority, payer.pubkey(), [3; 32])], &[]).await.unwrap();
+
+    let merchant = (payment.merchant.pubkey(), payment.merchant_token_account);
+    let fee_collector = vault.fee_collector.pubkey();
+    for instruction in [
+        payment.split_instruction(1_000_000, [1; 32], &[(9_950, merchant.1)]),
+        payment.batch_instruction(&[(merchant.1, 1_000_000, [2; 32])]),
+        sol_payment_instruction(payer.pubkey(), merchant.0, fee_collector, 1_000_000, [3; 32]),
+        payment.escrow_instruction(1_000_000, [4; 32], START_TIME + 3_600),
+        payment.timelock_instruction(1_000_000, [5; 32], START_TIME + 3_600),
+        payment.open_stream_instruction([6; 32], 1_000, START_TIME, START_TIME + 1_000),
+    ] {
+        let result = vault.send(&[instruction], &[&payer]).await;
+        assert_vault_error(result, VaultError::PayerBlacklisted);
+    }
+    assert_eq!(vault.balance(&payment.payer_token_account).await, 10_000_000);
+}
+
+#[tokio::test]
+async fn test_blacklisting_one_payer_leaves_others_paying() {
+    let mut vault = Vault::start().await;
+    let payment = vault.payment().await;
+    let authority = vault.payer();
+
+    let other = Keypair::new().pubkey();
+    vault.send(&[blacklist_payer_instruction(authority, other, [3; 32])], &[]).await.unwrap();
+    // Lamports sent to the payer's address don't blacklist it
+    let squat = system_instruction::transfer(&authority, &blacklisted_payer(&payment.payer), 1_000_000);
+    vault.send(&[squat], &[]).await.unwrap();
+
+    vault.send(&[payment.instruction(1_000_000, [1; 32])], &[]).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_the_payers_own_blacklist_address_is_checked() {
+    let mut vault = Vault::start().await;
+    let payer = Keypair::new();
+    let payment = vault.payment_by(&payer).await;
+    let authority = vault.payer();
+    vault.send(&[blacklist_payer_instruction(authority, payer.pubkey(), [3; 32])], &[]).await.unwrap();
+
+    // Passing someone else's address instead
+    let mut instruction = payment.instruction(1_000_000, [1; 32]);
+    instruction.accounts[5].pubkey = blacklisted_payer(&Keypair::new().pubkey());
+    let result = vault.send(&[instruction], &[&payer]).await;
+
+    assert_program_error(result, anchor_lang::error::ErrorCode::ConstraintSeeds.into());
+}
+
+#[tokio::test]
+async fn test_only_the_authority_manages_the_blacklist() {
+    let mut vault = Vault::start().await;
+    let stranger = Keypair::new();
+    let (authority, payer) = (vault.payer(), Keypair::new().pubkey());
+    vault.send(&[blacklist_payer_instruction(authority, payer, [3; 32])], &[]).await.unwrap();
+
+    for instruction in [
+        blacklist_payer_instruction(stranger.pubkey(), Keypair::new().pubkey(), [3; 32]),
+        remove_from_blacklist_instruction(stranger.pubkey(), payer),
+    ] {
+        let result = vault.send(&[instruction], &[&stranger]).await;
+        assert_program_error(result, anchor_lang::error::ErrorCode::ConstraintHasOne.into());
+    }
+}
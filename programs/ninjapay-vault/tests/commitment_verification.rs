@@ -0,0 +1,242 @@
+mod common;
+
+use anchor_lang::{AccountDeserialize, InstructionData};
+use common::{
+    assert_program_error, assert_vault_error, payment_record, set_commitment_verification_instruction,
+    sol_payment_instruction, verify_payment_commitment_instruction, Vault, START_TIME,
+};
+use ninjapay_vault::{PaymentRecord, SplitEntry, VaultError, PAYMENT_BATCH_COMPUTE_UNITS};
+use solana_sdk::{
+    compute_budget::ComputeBudgetInstruction,
+    hash::hashv,
+    signature::{Keypair, Signer},
+    system_instruction,
+    transaction::Transaction,
+};
+
+/// Per-instruction compute budget when none is requested
+const DEFAULT_COMPUTE_UNITS: u64 = 200_000;
+
+/// A nonce-sized blinding factor, as the arcium service uses
+const BLINDING_FACTOR: [u8; 12] = [7; 12];
+
+fn commitment(amount: u64, blinding_factor: &[u8]) -> [u8; 32] {
+    hashv(&[&amount.to_le_bytes(), blinding_factor]).to_bytes()
+}
+
+#[tokio::test]
+async fn test_verified_commitment_must_open_to_the_amount() {
+    let mut vault = Vault::start().await;
+    let payment = vault.payment().await;
+    let authority = vault.payer();
+    vault.send(&[set_commitment_verification_instruction(authority, true)], &[]).await.unwrap();
+
+    // Committed to a different amount, then with another blinding factor
+    let mismatched = [([1; 32], commitment(999_999, &BLINDING_FACTOR)), ([2; 32], commitment(1_000_000, &[8; 12]))];
+    for (payment_id, committed) in mismatched {
+        let instruction = payment.instruction_with(1_000_000, payment_id, committed, 0, &BLINDING_FACTOR);
+        let result = vault.send(&[instruction], &[]).await;
+        assert_vault_error(result, VaultError::CommitmentMismatch);
+    }
+    assert_eq!(vault.balance(&payment.payer_token_account).await, 10_000_000);
+
+    let committed = commitment(1_000_000, &BLINDING_FACTOR);
+    let instruction = payment.instruction_with(1_000_000, [3; 32], committed, 0, &BLINDING_FACTOR);
+    vault.send(&[instruction], &[]).await.unwrap();
+
+    let address = payment_record(&payment.payer, &[3; 32]);
+    let account = vault.context.banks_client.get_account(address).await.unwrap().unwrap();
+    let record = PaymentRecord::try_deserialize(&mut account.data.as_slice()).unwrap();
+    assert_eq!((record.amount, record.commitment), (1_000_000, committed));
+}
+
+#[tokio::test]
+async fn test_unverified_commitments_are_stored_as_sent() {
+    let mut vault = Vault::start().await;
+    let payment = vault.payment().await;
+    let authority = vault.payer();
+    vault.send(&[set_commitment_verification_instruction(authority, true)], &[]).await.unwrap();
+    vault.send(&[set_commitment_verification_instruction(authority, false)], &[]).await.unwrap();
+
+    vault.send(&[payment.instruction_with(1_000_000, [1; 32], [9; 32], 0, &[])], &[]).await.unwrap();
+
+    let stranger = Keypair::new();
+    let result = vault.send(&[set_commitment_verification_instruction(stranger.pubkey(), true)], &[&stranger]).await;
+    assert_program_error(result, anchor_lang::error::ErrorCode::ConstraintHasOne.into());
+}
+
+#[tokio::test]
+async fn test_verification_fits_the_default_compute_budget() {
+    let mut vault = Vault::start().await;
+    let payment = vault.payment().await;
+    let authority = vault.payer();
+    let committed = commitment(1_000_000, &BLINDING_FACTOR);
+    let instruction = payment.instruction_with(1_000_000, [1; 32], committed, 0, &BLINDING_FACTOR);
+
+    // Simulated, so each run is a first payment that also creates the rate
+    // limit and daily volume records, the most expensive kind
+    let mut units = Vec::new();
+    for verify in [false, true] {
+        vault.send(&[set_commitment_verification_instruction(authority, verify)], &[]).await.unwrap();
+        let blockhash = vault.context.banks_client.get_latest_blockhash().await.unwrap();
+        let transaction = Transaction::new_signed_with_payer(
+            &[instruction.clone()],
+            Some(&authority),
+            &[&vault.context.payer],
+            blockhash,
+        );
+        let simulation = vault.context.banks_client.simulate_transaction(transaction).await.unwrap();
+        assert!(simulation.result.unwrap().is_ok());
+        units.push(simulation.simulation_details.unwrap().units_consumed);
+    }
+
+    println!("process_payment: {} compute units, {} verifying the commitment", units[0], units[1]);
+    assert!(units[1] <= DEFAULT_COMPUTE_UNITS);
+}
+
+#[tokio::test]
+async fn test_stored_commitment_is_verified_against_an_opening() {
+    let mut vault = Vault::start().await;
+    let payment = vault.payment().await;
+    let blinding_factor = [5; 32];
+    let committed = commitment(1_000_000, &blinding_factor);
+    // Stored unchecked, as verification is off
+    vault.send(&[payment.instruction_with(1_000_000, [1; 32], committed, 0, &[])], &[]).await.unwrap();
+    let record = payment_record(&payment.payer, &[1; 32]);
+
+    // Read-only, so a simulation is proof enough
+    let blockhash = vault.context.banks_client.get_latest_blockhash().await.unwrap();
+    let transaction = Transaction::new_signed_with_payer(
+        &[verify_payment_commitment_instruction(record, 1_000_000, blinding_factor)],
+        Some(&vault.payer()),
+        &[&vault.context.payer],
+        blockhash,
+    );
+    let simulation = vault.context.banks_client.simulate_transaction(transaction).await.unwrap();
+    assert!(simulation.result.unwrap().is_ok());
+
+    for (amount, blinding_factor) in [(999_999, blinding_factor), (1_000_000, [6; 32])] {
+        let result = vault.send(&[verify_payment_commitment_instruction(record, amount, blinding_factor)], &[]).await;
+        assert_vault_error(result, VaultError::CommitmentMismatch);
+    }
+}
+
+#[tokio::test]
+async fn test_split_payment_commitment_must_open_to_the_amount() {
+    let mut vault = Vault::start().await;
+    let payment = vault.payment().await;
+    let authority = vault.payer();
+    let mint = vault.mint;
+    let recipient = vault.create_token_account(&mint, &Keypair::new().pubkey()).await;
+    vault.send(&[set_commitment_verification_instruction(authority, true)], &[]).await.unwrap();
+
+    let splits = [(4_950, payment.merchant_token_account), (5_000, recipient)];
+    let split = |blinding_factor: &[u8]| {
+        let mut instruction = payment.split_instruction(1_000_000, [1; 32], &splits);
+        instruction.data = ninjapay_vault::instruction::ProcessSplitPayment {
+            amount: 1_000_000,
+            payment_id: [1; 32],
+            commitment: commitment(1_000_000, &BLINDING_FACTOR),
+            splits: splits
+                .iter()
+                .map(|&(basis_points, recipient_token_account)| SplitEntry {
+                    basis_points,
+                    recipient_token_account,
+                })
+                .collect(),
+            blinding_factor: blinding_factor.to_vec(),
+        }
+        .data();
+        instruction
+    };
+
+    let result = vault.send(&[split(&[8; 12])], &[]).await;
+    assert_vault_error(result, VaultError::CommitmentMismatch);
+    assert_eq!(vault.balance(&payment.payer_token_account).await, 10_000_000);
+
+    vault.send(&[split(&BLINDING_FACTOR)], &[]).await.unwrap();
+    assert_eq!(vault.balance(&recipient).await, 500_000);
+}
+
+#[tokio::test]
+async fn test_batch_commitments_must_each_open_to_their_amount() {
+    let mut vault = Vault::start().await;
+    let payment = vault.payment().await;
+    let authority = vault.payer();
+    vault.send(&[set_commitment_verification_instruction(authority, true)], &[]).await.unwrap();
+
+    let payments = [
+        (payment.merchant_token_account, 1_000_000, [1; 32]),
+        (payment.merchant_token_account, 2_000_000, [2; 32]),
+    ];
+    let batch = |blinding_factors: Vec<Vec<u8>>| {
+        let mut instruction = payment.batch_instruction(&payments);
+        instruction.data = ninjapay_vault::instruction::ProcessPaymentBatch {
+            amounts: vec![1_000_000, 2_000_000],
+            payment_ids: vec![[1; 32], [2; 32]],
+            commitments: vec![commitment(1_000_000, &BLINDING_FACTOR), commitment(2_000_000, &BLINDING_FACTOR)],
+            blinding_factors,
+        }
+        .data();
+        instruction
+    };
+
+    let budget = ComputeBudgetInstruction::set_compute_unit_limit(PAYMENT_BATCH_COMPUTE_UNITS);
+
+    // The second payment's opening is wrong, so neither is made
+    let result = vault.send(&[budget.clone(), batch(vec![BLINDING_FACTOR.to_vec(), vec![8; 12]])], &[]).await;
+    assert_vault_error(result, VaultError::CommitmentMismatch);
+    assert_eq!(vault.balance(&payment.payer_token_account).await, 10_000_000);
+
+    vault.send(&[budget, batch(vec![BLINDING_FACTOR.to_vec(); 2])], &[]).await.unwrap();
+    assert_eq!(vault.balance(&payment.payer_token_account).await, 7_000_000);
+}
+
+#[tokio::test]
+async fn test_escrow_and_sol_commitments_must_open_to_their_amount() {
+    let mut vault = Vault::start().await;
+    let payment = vault.payment().await;
+    let authority = vault.payer();
+    vault.send(&[set_commitment_verification_instruction(authority, true)], &[]).await.unwrap();
+
+    let escrow = |blinding_factor: &[u8]| {
+        let mut instruction = payment.escrow_instruction(1_000_000, [1; 32], START_TIME + 3_600);
+        instruction.data = ninjapay_vault::instruction::CreateEscrowPayment {
+            amount: 1_000_000,
+            payment_id: [1; 32],
+            commitment: commitment(1_000_000, &BLINDING_FACTOR),
+            expires_at: START_TIME + 3_600,
+            blinding_factor: blinding_factor.to_vec(),
+        }
+        .data();
+        instruction
+    };
+    let result = vault.send(&[escrow(&[8; 12])], &[]).await;
+    assert_vault_error(result, VaultError::CommitmentMismatch);
+    vault.send(&[escrow(&BLINDING_FACTOR)], &[]).await.unwrap();
+    assert_eq!(vault.balance(&payment.payer_token_account).await, 9_000_000);
+
+    // Both wallets funded, so they can take lamports of any size
+    vault.ensure_native_mint().await;
+    let (merchant, fee_collector) = (payment.merchant.pubkey(), vault.fee_collector.pubkey());
+    let fund = [
+        system_instruction::transfer(&authority, &merchant, 1_000_000_000),
+        system_instruction::transfer(&authority, &fee_collector, 1_000_000_000),
+    ];
+    vault.send(&fund, &[]).await.unwrap();
+    let sol = |blinding_factor: &[u8]| {
+        let mut instruction = sol_payment_instruction(authority, merchant, fee_collector, 1_000_000, [2; 32]);
+        instruction.data = ninjapay_vault::instruction::ProcessSolPayment {
+            lamports: 1_000_000,
+            payment_id: [2; 32],
+            commitment: commitment(1_000_000, &BLINDING_FACTOR),
+            blinding_factor: blinding_factor.to_vec(),
+        }
+        .data();
+        instruction
+    };
+    let result = vault.send(&[sol(&[8; 12])], &[]).await;
+    assert_vault_error(result, VaultError::CommitmentMismatch);
+    vault.send(&[sol(&BLINDING_FACTOR)], &[]).await.unwrap();
+    assert_eq!(vault.lamports(&merchant).await, 1_000_000_000 + 995_000);
+}
@@ -0,0 +1,1565 @@
+//! Program-test harness for the vault: a fresh bank with the vault
+//! initialized, a mint and token accounts to pay with.
+
+#![allow(dead_code)]
+
+use anchor_lang::{AccountDeserialize, AccountSerialize, InstructionData, ToAccountMetas};
+use anchor_spl::token::spl_token;
+use ninjapay_vault::{
+    DailyVolumeRecord, FeeCollectorShare, FeeMode, FeeThreshold, ScheduleStatus, SplitEntry, VaultConfig, VaultError,
+};
+use solana_program_test::{processor, BanksClientError, ProgramTest, ProgramTestContext};
+use solana_sdk::{
+    account::Account,
+    account_info::AccountInfo,
+    bpf_loader_upgradeable::{self, UpgradeableLoaderState},
+    clock::Clock,
+    entrypoint::ProgramResult,
+    instruction::{AccountMeta, Instruction, InstructionError},
+    program_pack::Pack,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    signer::keypair::keypair_from_seed,
+    system_instruction, system_program,
+    transaction::{Transaction, TransactionError},
+};
+
+pub const FEE_BASIS_POINTS: u16 = 50;
+
+/// Where the bank's clock starts: noon UTC, so tests moving it by hours stay
+/// on the same day
+pub const START_TIME: i64 = 1_750_075_200;
+
+/// The UTC day of START_TIME, as DailyVolumeRecords are keyed
+pub const TODAY: i64 = START_TIME / DailyVolumeRecord::DAY_SECS;
+
+// Anchor 0.29's entry ties the accounts slice to the accounts' lifetime
+fn process_instruction(program_id: &Pubkey, accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
+    let accounts = Box::leak(Box::new(accounts.to_vec()));
+    ninjapay_vault::entry(program_id, accounts, data)
+}
+
+pub struct Vault {
+    pub context: ProgramTestContext,
+    pub mint: Pubkey,
+    pub fee_collector: Keypair,
+    pub fee_token_account: Pubkey,
+}
+
+pub fn vault_config() -> Pubkey {
+    Pubkey::find_program_address(&[b"vault_config"], &ninjapay_vault::ID).0
+}
+
+pub fn payment_record(payer: &Pubkey, payment_id: &[u8; 32]) -> Pubkey {
+    Pubkey::find_program_address(&[b"payment", payer.as_ref(), payment_id], &ninjapay_vault::ID).0
+}
+
+pub fn escrow_record(payer: &Pubkey, payment_id: &[u8; 32]) -> Pubkey {
+    Pubkey::find_program_address(&[b"escrow_payment", payer.as_ref(), payment_id], &ninjapay_vault::ID).0
+}
+
+pub fn escrow_tokens(payer: &Pubkey, payment_id: &[u8; 32]) -> Pubkey {
+    Pubkey::find_program_address(&[b"escrow_tokens", payer.as_ref(), payment_id], &ninjapay_vault::ID).0
+}
+
+pub fn streaming_payment(sender: &Pubkey, stream_id: &[u8; 32]) -> Pubkey {
+    Pubkey::find_program_address(&[b"stream", sender.as_ref(), stream_id], &ninjapay_vault::ID).0
+}
+
+pub fn stream_tokens(sender: &Pubkey, stream_id: &[u8; 32]) -> Pubkey {
+    Pubkey::find_program_address(&[b"stream_tokens", sender.as_ref(), stream_id], &ninjapay_vault::ID).0
+}
+
+pub fn timelock_payment(funder: &Pubkey, payment_id: &[u8; 32]) -> Pubkey {
+    Pubkey::find_program_address(&[b"timelock", funder.as_ref(), payment_id], &ninjapay_vault::ID).0
+}
+
+pub fn timelock_tokens(funder: &Pubkey, payment_id: &[u8; 32]) -> Pubkey {
+    Pubkey::find_program_address(&[b"timelock_tokens", funder.as_ref(), payment_id], &ninjapay_vault::ID).0
+}
+
+pub fn batch_record(batch_id: &[u8; 32]) -> Pubkey {
+    Pubkey::find_program_address(&[b"batch", batch_id], &ninjapay_vault::ID).0
+}
+
+pub fn batch_escrow(batch_id: &[u8; 32]) -> Pubkey {
+    Pubkey::find_program_address(&[b"batch_escrow", batch_id], &ninjapay_vault::ID).0
+}
+
+pub fn disbursement_record(batch_id: &[u8; 32], index: u16) -> Pubkey {
+    Pubkey::find_program_address(&[b"disbursement", batch_id, &index.to_le_bytes()], &ninjapay_vault::ID).0
+}
+
+pub fn payer_rate_limit(payer: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"rate_limit", payer.as_ref()], &ninjapay_vault::ID).0
+}
+
+pub fn daily_volume(day: i64) -> Pubkey {
+    Pubkey::find_program_address(&[b"daily_vol", &day.to_le_bytes()], &ninjapay_vault::ID).0
+}
+
+pub fn accepted_mint(mint: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"accepted_mint", mint.as_ref()], &ninjapay_vault::ID).0
+}
+
+pub fn vault_mint_config(mint: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"vault_mint", mint.as_ref()], &ninjapay_vault::ID).0
+}
+
+pub fn blacklisted_payer(payer: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"blacklist", payer.as_ref()], &ninjapay_vault::ID).0
+}
+
+pub fn merchant_stats(merchant: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"merchant_stats", merchant.as_ref()], &ninjapay_vault::ID).0
+}
+
+pub fn registered_merchant(merchant: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"merchant", merchant.as_ref()], &ninjapay_vault::ID).0
+}
+
+pub fn fee_schedule() -> Pubkey {
+    Pubkey::find_program_address(&[b"fee_schedule"], &ninjapay_vault::ID).0
+}
+
+pub fn merchant_config(merchant: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"merchant_cfg", merchant.as_ref()], &ninjapay_vault::ID).0
+}
+
+pub fn program_data() -> Pubkey {
+    bpf_loader_upgradeable::get_program_data_address(&ninjapay_vault::ID)
+}
+
+/// The vault's program, to add other programs to before starting
+pub fn program_test() -> ProgramTest {
+    ProgramTest::new("ninjapay_vault", ninjapay_vault::ID, processor!(process_instruction))
+}
+
+/// Start a bank with the vault deployed but not initialized; the context
+/// payer is the program's upgrade authority
+pub async fn start_uninitialized() -> ProgramTestContext {
+    start_uninitialized_with(program_test()).await
+}
+
+/// As `start_uninitialized`, from `program_test()` with more programs added
+pub async fn start_uninitialized_with(program_test: ProgramTest) -> ProgramTestContext {
+    let mut context = program_test.start_with_context().await;
+
+    // The builtin processor has no ProgramData of its own, so stand one in
+    let state = UpgradeableLoaderState::ProgramData {
+        slot: 0,
+        upgrade_authority_address: Some(context.payer.pubkey()),
+    };
+    let rent = context.banks_client.get_rent().await.unwrap();
+    let lamports = rent.minimum_balance(UpgradeableLoaderState::size_of_programdata_metadata());
+    let account = Account::new_data(lamports, &state, &bpf_loader_upgradeable::ID).unwrap();
+    context.set_account(&program_data(), &account.into());
+
+    let mut clock: Clock = context.banks_client.get_sysvar().await.unwrap();
+    clock.unix_timestamp = START_TIME;
+    context.set_sysvar(&clock);
+    context
+}
+
+/// initialize at FEE_BASIS_POINTS with no payment bounds or recovery authority,
+/// signed by `authority`
+pub fn initialize_instruction(authority: Pubkey, fee_collector: Pubkey) -> Instruction {
+    Instruction {
+        program_id: ninjapay_vault::ID,
+        accounts: ninjapay_vault::accounts::Initialize {
+            vault_config: vault_config(),
+            authority,
+            program_data: program_data(),
+            fee_collector,
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None),
+        data: ninjapay_vault::instruction::Initialize {
+            fee_basis_points: FEE_BASIS_POINTS,
+            min_payment: 0,
+            max_payment: 0,
+            recovery_authority: None,
+        }
+        .data(),
+    }
+}
+
+impl Vault {
+    /// Start a bank and initialize the vault at FEE_BASIS_POINTS with no
+    /// payment bounds; the context payer is the vault authority
+    pub async fn start() -> Self {
+        Self::start_with(program_test()).await
+    }
+
+    /// As `start`, from `program_test()` with more programs added
+    pub async fn start_with(program_test: ProgramTest) -> Self {
+        let mut context = start_uninitialized_with(program_test).await;
+
+        let fee_collector = Keypair::new();
+        let initialize = initialize_instruction(context.payer.pubkey(), fee_collector.pubkey());
+        send(&mut context, &[initialize], &[]).await.unwrap();
+
+        let mut vault = Self {
+            context,
+            mint: Pubkey::default(),
+            fee_collector,
+            fee_token_account: Pubkey::default(),
+        };
+        vault.mint = vault.create_mint().await;
+        let collector = vault.fee_collector.pubkey();
+        vault.fee_token_account = vault.create_token_account(&vault.mint.clone(), &collector).await;
+        vault
+    }
+
+    pub fn payer(&self) -> Pubkey {
+        self.context.payer.pubkey()
+    }
+
+    pub async fn send(&mut self, instructions: &[Instruction], signers: &[&Keypair]) -> Result<(), BanksClientError> {
+        send(&mut self.context, instructions, signers).await
+    }
+
+    pub async fn create_mint(&mut self) -> Pubkey {
+        self.create_mint_with_decimals(6).await
+    }
+
+    pub async fn create_mint_with_decimals(&mut self, decimals: u8) -> Pubkey {
+        let mint = Keypair::new();
+        let rent = self.context.banks_client.get_rent().await.unwrap();
+        let payer = self.payer();
+        let instructions = [
+            system_instruction::create_account(
+                &payer,
+                &mint.pubkey(),
+                rent.minimum_balance(spl_token::state::Mint::LEN),
+                spl_token::state::Mint::LEN as u64,
+                &spl_token::ID,
+            ),
+            spl_token::instruction::initialize_mint(&spl_token::ID, &mint.pubkey(), &payer, None, decimals).unwrap(),
+        ];
+        self.send(&instructions, &[&mint]).await.unwrap();
+        mint.pubkey()
+    }
+
+    pub async fn create_token_account(&mut self, mint: &Pubkey, owner: &Pubkey) -> Pubkey {
+        let account = Keypair::new();
+        let rent = self.context.banks_client.get_rent().await.unwrap();
+        let payer = self.payer();
+        let instructions = [
+            system_instruction::create_account(
+                &payer,
+                &account.pubkey(),
+                rent.minimum_balance(spl_token::state::Account::LEN),
+                spl_token::state::Account::LEN as u64,
+                &spl_token::ID,
+            ),
+            spl_token::instruction::initialize_account3(&spl_token::ID, &account.pubkey(), mint, owner).unwrap(),
+        ];
+        self.send(&instructions, &[&account]).await.unwrap();
+        account.pubkey()
+    }
+
+    pub async fn mint_to(&mut self, mint: &Pubkey, account: &Pubkey, amount: u64) {
+        let payer = self.payer();
+        let mint_to = spl_token::instruction::mint_to(&spl_token::ID, mint, account, &payer, &[], amount).unwrap();
+        self.send(&[mint_to], &[]).await.unwrap();
+    }
+
+    pub async fn balance(&mut self, account: &Pubkey) -> u64 {
+        let account = self.context.banks_client.get_account(*account).await.unwrap().unwrap();
+        spl_token::state::Account::unpack(&account.data).unwrap().amount
+    }
+
+    /// Lamports held by `account`, 0 if it doesn't exist
+    pub async fn lamports(&mut self, account: &Pubkey) -> u64 {
+        let account = self.context.banks_client.get_account(*account).await.unwrap();
+        account.map_or(0, |account| account.lamports)
+    }
+
+    /// Create the native mint unless the bank already has it
+    pub async fn ensure_native_mint(&mut self) {
+        let native_mint = spl_token::native_mint::ID;
+        if self
+            .context
+            .banks_client
+            .get_account(native_mint)
+            .await
+            .unwrap()
+            .is_some()
+        {
+            return;
+        }
+        let mint = spl_token::state::Mint {
+            decimals: spl_token::native_mint::DECIMALS,
+            is_initialized: true,
+            ..Default::default()
+        };
+        let mut data = vec![0; spl_token::state::Mint::LEN];
+        spl_token::state::Mint::pack(mint, &mut data).unwrap();
+        let rent = self.context.banks_client.get_rent().await.unwrap();
+        let account = Account {
+            lamports: rent.minimum_balance(data.len()),
+            data,
+            owner: spl_token::ID,
+            executable: false,
+            rent_epoch: 0,
+        };
+        self.context.set_account(&native_mint, &account.into());
+    }
+
+    /// Move the bank's clock to `unix_timestamp`
+    pub async fn set_time(&mut self, unix_timestamp: i64) {
+        let mut clock: Clock = self.context.banks_client.get_sysvar().await.unwrap();
+        clock.unix_timestamp = unix_timestamp;
+        self.context.set_sysvar(&clock);
+    }
+
+    /// Rewrite VaultConfig in place, to reach states no instruction can
+    pub async fn edit_vault_config(&mut self, edit: impl FnOnce(&mut VaultConfig)) {
+        let mut account = self
+            .context
+            .banks_client
+            .get_account(vault_config())
+            .await
+            .unwrap()
+            .unwrap();
+        let mut config = VaultConfig::try_deserialize(&mut account.data.as_slice()).unwrap();
+        edit(&mut config);
+        let mut data = Vec::with_capacity(account.data.len());
+        config.try_serialize(&mut data).unwrap();
+        account.data[..data.len()].copy_from_slice(&data);
+        self.context.set_account(&vault_config(), &account.into());
+    }
+
+    /// A funded payer token account and a merchant with a token account,
+    /// all for the vault's mint
+    pub async fn payment(&mut self) -> Payment {
+        let (mint, fee_token_account) = (self.mint, self.fee_token_account);
+        self.payment_in(mint, fee_token_account).await
+    }
+
+    /// As `payment`, in another mint with the collector's account for it
+    pub async fn payment_in(&mut self, mint: Pubkey, fee_token_account: Pubkey) -> Payment {
+        let payer = self.payer();
+        self.payment_for(payer, mint, fee_token_account).await
+    }
+
+    /// As `payment`, paid by `payer` rather than the context payer; it is
+    /// given SOL for the record's rent and must sign the payment
+    pub async fn payment_by(&mut self, payer: &Keypair) -> Payment {
+        let transfer = system_instruction::transfer(&self.payer(), &payer.pubkey(), 1_000_000_000);
+        self.send(&[transfer], &[]).await.unwrap();
+        let (mint, fee_token_account) = (self.mint, self.fee_token_account);
+        self.payment_for(payer.pubkey(), mint, fee_token_account).await
+    }
+
+    async fn payment_for(&mut self, payer: Pubkey, mint: Pubkey, fee_token_account: Pubkey) -> Payment {
+        let payer_token_account = self.create_token_account(&mint, &payer).await;
+        self.mint_to(&mint, &payer_token_account, 10_000_000).await;
+
+        let merchant = Keypair::new();
+        let merchant_token_account = self.create_token_account(&mint, &merchant.pubkey()).await;
+        Payment {
+            payer,
+            payer_token_account,
+            mint,
+            merchant,
+            merchant_token_account,
+            fee_token_account,
+            accepted_mint: None,
+            vault_mint_config: None,
+            fee_schedule: None,
+            registered_merchant: None,
+            merchant_config: None,
+            fee_token_account_1: None,
+            fee_token_account_2: None,
+            day: TODAY,
+            fee_mode: FeeMode::Deducted,
+        }
+    }
+}
+
+/// Accounts of a process_payment call; tests swap them to probe constraints
+pub struct Payment {
+    pub payer: Pubkey,
+    pub payer_token_account: Pubkey,
+    pub mint: Pubkey,
+    pub merchant: Keypair,
+    pub merchant_token_account: Pubkey,
+    pub fee_token_account: Pubkey,
+    /// The mint's whitelist entry, if the payment passes one
+    pub accepted_mint: Option<Pubkey>,
+    /// The mint's fee override, if the payment passes it
+    pub vault_mint_config: Option<Pubkey>,
+    /// The vault's fee schedule, if the payment passes it
+    pub fee_schedule: Option<Pubkey>,
+    /// The merchant's registration, if the payment passes one
+    pub registered_merchant: Option<Pubkey>,
+    /// The merchant's fee settings, if a payment other than process_payment,
+    /// which takes them as a remaining account, passes them
+    pub merchant_config: Option<Pubkey>,
+    /// The other fee collectors' token accounts, if the payment passes them
+    pub fee_token_account_1: Option<Pubkey>,
+    pub fee_token_account_2: Option<Pubkey>,
+    /// UTC day the payment lands on, for its DailyVolumeRecord; TODAY
+    /// unless the test moves the clock to another day
+    pub day: i64,
+    /// Who bears the fee; Deducted unless the test sets it
+    pub fee_mode: FeeMode,
+}
+
+impl Payment {
+    pub fn instruction(&self, amount: u64, payment_id: [u8; 32]) -> Instruction {
+        self.instruction_expiring(amount, payment_id, 0)
+    }
+
+    pub fn instruction_expiring(&self, amount: u64, payment_id: [u8; 32], expires_at: i64) -> Instruction {
+        self.instruction_with(amount, payment_id, [9; 32], expires_at, &[])
+    }
+
+    /// process_payment with every argument given
+    pub fn instruction_with(
+        &self,
+        amount: u64,
+        payment_id: [u8; 32],
+        commitment: [u8; 32],
+        expires_at: i64,
+        blinding_factor: &[u8],
+    ) -> Instruction {
+        Instruction {
+            program_id: ninjapay_vault::ID,
+            accounts: ninjapay_vault::accounts::ProcessPayment {
+                vault_config: vault_config(),
+                payment_record: payment_record(&self.payer, &payment_id),
+                payer_rate_limit: payer_rate_limit(&self.payer),
+                daily_volume: daily_volume(self.day),
+                payer: self.payer,
+                blacklist_check: blacklisted_payer(&self.payer),
+                payer_token_account: self.payer_token_account,
+                mint: self.mint,
+                accepted_mint: self.accepted_mint,
+                vault_mint_config: self.vault_mint_config,
+                fee_schedule: self.fee_schedule,
+                merchant: self.merchant.pubkey(),
+                registered_merchant: self.registered_merchant,
+                merchant_stats: merchant_stats(&self.merchant.pubkey()),
+                merchant_token_account: self.merchant_token_account,
+                fee_token_account: self.fee_token_account,
+                fee_token_account_1: self.fee_token_account_1,
+                fee_token_account_2: self.fee_token_account_2,
+                token_program: spl_token::ID,
+                system_program: system_program::ID,
+            }
+            .to_account_metas(None),
+            data: ninjapay_vault::instruction::ProcessPayment {
+                amount,
+                payment_id,
+                commitment,
+                expires_at,
+                blinding_factor: blinding_factor.to_vec(),
+                fee_mode: self.fee_mode,
+            }
+            .data(),
+        }
+    }
+
+    /// process_split_payment to `(basis_points, recipient_token_account)`
+    /// splits, passing the same accounts as remaining accounts
+    pub fn split_instruction(&self, amount: u64, payment_id: [u8; 32], splits: &[(u16, Pubkey)]) -> Instruction {
+        self.split_instruction_registered(amount, payment_id, splits, &[])
+    }
+
+    /// As `split_instruction`, following each recipient's token account with
+    /// the RegisteredMerchant of `owners[i]`, its owner
+    pub fn split_instruction_registered(
+        &self,
+        amount: u64,
+        payment_id: [u8; 32],
+        splits: &[(u16, Pubkey)],
+        owners: &[Pubkey],
+    ) -> Instruction {
+        let mut accounts = ninjapay_vault::accounts::ProcessSplitPayment {
+            vault_config: vault_config(),
+            payer_rate_limit: payer_rate_limit(&self.payer),
+            daily_volume: daily_volume(self.day),
+            payer: self.payer,
+            blacklist_check: blacklisted_payer(&self.payer),
+            payer_token_account: self.payer_token_account,
+            mint: self.mint,
+            accepted_mint: self.accepted_mint,
+            vault_mint_config: self.vault_mint_config,
+            fee_schedule: self.fee_schedule,
+            merchant: self.merchant.pubkey(),
+            merchant_config: self.merchant_config,
+            fee_token_account: self.fee_token_account,
+            fee_token_account_1: self.fee_token_account_1,
+            fee_token_account_2: self.fee_token_account_2,
+            token_program: spl_token::ID,
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None);
+        for (i, &(_, recipient)) in splits.iter().enumerate() {
+            accounts.push(AccountMeta::new(recipient, false));
+            if let Some(owner) = owners.get(i) {
+                accounts.push(AccountMeta::new_readonly(registered_merchant(owner), false));
+            }
+        }
+        Instruction {
+            program_id: ninjapay_vault::ID,
+            accounts,
+            data: ninjapay_vault::instruction::ProcessSplitPayment {
+                amount,
+                payment_id,
+                commitment: [9; 32],
+                splits: splits
+                    .iter()
+                    .map(|&(basis_points, recipient_token_account)| SplitEntry {
+                        basis_points,
+                        recipient_token_account,
+                    })
+                    .collect(),
+                blinding_factor: vec![],
+            }
+            .data(),
+        }
+    }
+
+    /// refund_payment of `amount` of `payment_id`'s net amount, signed by
+    /// the merchant and `fee_collector`
+    pub fn refund_instruction(&self, payment_id: [u8; 32], amount: u64, fee_collector: Pubkey) -> Instruction {
+        self.refund_instruction_from(payment_id, amount, &[(fee_collector, self.fee_token_account)])
+    }
+
+    /// refund_payment passing `(fee_collector, fee_token_account)` pairs
+    /// by the index of their share, each collector signing; none for a
+    /// partial refund
+    pub fn refund_instruction_from(
+        &self,
+        payment_id: [u8; 32],
+        amount: u64,
+        fee_collectors: &[(Pubkey, Pubkey)],
+    ) -> Instruction {
+        let collector = |index: usize| fee_collectors.get(index).map(|&(collector, _)| collector);
+        let account = |index: usize| fee_collectors.get(index).map(|&(_, account)| account);
+        Instruction {
+            program_id: ninjapay_vault::ID,
+            accounts: ninjapay_vault::accounts::RefundPayment {
+                vault_config: vault_config(),
+                payment_record: payment_record(&self.payer, &payment_id),
+                merchant: self.merchant.pubkey(),
+                merchant_token_account: self.merchant_token_account,
+                fee_collector: collector(0),
+                fee_token_account: account(0),
+                fee_collector_1: collector(1),
+                fee_token_account_1: account(1),
+                fee_collector_2: collector(2),
+                fee_token_account_2: account(2),
+                payer: self.payer,
+                payer_token_account: self.payer_token_account,
+                mint: self.mint,
+                token_program: spl_token::ID,
+            }
+            .to_account_metas(None),
+            data: ninjapay_vault::instruction::RefundPayment { payment_id, amount }.data(),
+        }
+    }
+
+    /// create_escrow_payment of `amount` to the merchant, held until
+    /// `expires_at`
+    pub fn escrow_instruction(&self, amount: u64, payment_id: [u8; 32], expires_at: i64) -> Instruction {
+        Instruction {
+            program_id: ninjapay_vault::ID,
+            accounts: ninjapay_vault::accounts::CreateEscrowPayment {
+                vault_config: vault_config(),
+                escrow_record: escrow_record(&self.payer, &payment_id),
+                escrow_tokens: escrow_tokens(&self.payer, &payment_id),
+                payer_rate_limit: payer_rate_limit(&self.payer),
+                daily_volume: daily_volume(self.day),
+                payer: self.payer,
+                blacklist_check: blacklisted_payer(&self.payer),
+                payer_token_account: self.payer_token_account,
+                mint: self.mint,
+                accepted_mint: self.accepted_mint,
+                vault_mint_config: self.vault_mint_config,
+                fee_schedule: self.fee_schedule,
+                merchant: self.merchant.pubkey(),
+                registered_merchant: self.registered_merchant,
+                merchant_config: self.merchant_config,
+                token_program: spl_token::ID,
+                system_program: system_program::ID,
+            }
+            .to_account_metas(None),
+            data: ninjapay_vault::instruction::CreateEscrowPayment {
+                amount,
+                payment_id,
+                commitment: [0; 32],
+                expires_at,
+                blinding_factor: vec![],
+            }
+            .data(),
+        }
+    }
+
+    /// capture_payment of `payment_id`, signed by the merchant
+    pub fn capture_instruction(&self, payment_id: [u8; 32]) -> Instruction {
+        Instruction {
+            program_id: ninjapay_vault::ID,
+            accounts: ninjapay_vault::accounts::CapturePayment {
+                vault_config: vault_config(),
+                escrow_record: escrow_record(&self.payer, &payment_id),
+                escrow_tokens: escrow_tokens(&self.payer, &payment_id),
+                merchant: self.merchant.pubkey(),
+                merchant_token_account: self.merchant_token_account,
+                mint: self.mint,
+                fee_token_account: self.fee_token_account,
+                fee_token_account_1: self.fee_token_account_1,
+                fee_token_account_2: self.fee_token_account_2,
+                payer: self.payer,
+                token_program: spl_token::ID,
+            }
+            .to_account_metas(None),
+            data: ninjapay_vault::instruction::CapturePayment { payment_id }.data(),
+        }
+    }
+
+    /// cancel_escrow of `payment_id`, signed by the payer
+    pub fn cancel_escrow_instruction(&self, payment_id: [u8; 32]) -> Instruction {
+        Instruction {
+            program_id: ninjapay_vault::ID,
+            accounts: ninjapay_vault::accounts::CancelEscrow {
+                vault_config: vault_config(),
+                escrow_record: escrow_record(&self.payer, &payment_id),
+                escrow_tokens: escrow_tokens(&self.payer, &payment_id),
+                payer: self.payer,
+                payer_token_account: self.payer_token_account,
+                mint: self.mint,
+                token_program: spl_token::ID,
+            }
+            .to_account_metas(None),
+            data: ninjapay_vault::instruction::CancelEscrow { payment_id }.data(),
+        }
+    }
+
+    /// crank_expired_escrow of this payer's escrow, cranked by `cranker`
+    pub fn crank_escrow_instruction(&self, payment_id: [u8; 32], cranker: Pubkey) -> Instruction {
+        Instruction {
+            program_id: ninjapay_vault::ID,
+            accounts: ninjapay_vault::accounts::CrankExpiredEscrow {
+                vault_config: vault_config(),
+                escrow_record: escrow_record(&self.payer, &payment_id),
+                escrow_tokens: escrow_tokens(&self.payer, &payment_id),
+                payer: self.payer,
+                payer_token_account: self.payer_token_account,
+                mint: self.mint,
+                cranker,
+                token_program: spl_token::ID,
+            }
+            .to_account_metas(None),
+            data: ninjapay_vault::instruction::CrankExpiredEscrow { payment_id }.data(),
+        }
+    }
+
+    /// create_timelock_payment of `amount` from the payer to the merchant
+    pub fn timelock_instruction(&self, amount: u64, payment_id: [u8; 32], unlock_at: i64) -> Instruction {
+        Instruction {
+            program_id: ninjapay_vault::ID,
+            accounts: ninjapay_vault::accounts::CreateTimelockPayment {
+                vault_config: vault_config(),
+                timelock_payment: timelock_payment(&self.payer, &payment_id),
+                timelock_tokens: timelock_tokens(&self.payer, &payment_id),
+                payer_rate_limit: payer_rate_limit(&self.payer),
+                daily_volume: daily_volume(self.day),
+                funder: self.payer,
+                blacklist_check: blacklisted_payer(&self.payer),
+                funder_token_account: self.payer_token_account,
+                mint: self.mint,
+                accepted_mint: self.accepted_mint,
+                vault_mint_config: self.vault_mint_config,
+                fee_schedule: self.fee_schedule,
+                beneficiary: self.merchant.pubkey(),
+                fee_token_account: self.fee_token_account,
+                fee_token_account_1: self.fee_token_account_1,
+                fee_token_account_2: self.fee_token_account_2,
+                token_program: spl_token::ID,
+                system_program: system_program::ID,
+            }
+            .to_account_metas(None),
+            data: ninjapay_vault::instruction::CreateTimelockPayment {
+                amount,
+                payment_id,
+                unlock_at,
+            }
+            .data(),
+        }
+    }
+
+    /// claim_timelock_payment of `payment_id`, signed by the merchant
+    pub fn claim_timelock_instruction(&self, payment_id: [u8; 32]) -> Instruction {
+        Instruction {
+            program_id: ninjapay_vault::ID,
+            accounts: ninjapay_vault::accounts::ClaimTimelockPayment {
+                vault_config: vault_config(),
+                timelock_payment: timelock_payment(&self.payer, &payment_id),
+                timelock_tokens: timelock_tokens(&self.payer, &payment_id),
+                beneficiary: self.merchant.pubkey(),
+                beneficiary_token_account: self.merchant_token_account,
+                mint: self.mint,
+                funder: self.payer,
+                token_program: spl_token::ID,
+            }
+            .to_account_metas(None),
+            data: ninjapay_vault::instruction::ClaimTimelockPayment { payment_id }.data(),
+        }
+    }
+
+    /// open_stream of `rate_per_second` from the payer to the merchant
+    pub fn open_stream_instruction(
+        &self,
+        stream_id: [u8; 32],
+        rate_per_second: u64,
+        start_time: i64,
+        end_time: i64,
+    ) -> Instruction {
+        Instruction {
+            program_id: ninjapay_vault::ID,
+            accounts: ninjapay_vault::accounts::OpenStream {
+                vault_config: vault_config(),
+                streaming_payment: streaming_payment(&self.payer, &stream_id),
+                stream_tokens: stream_tokens(&self.payer, &stream_id),
+                payer_rate_limit: payer_rate_limit(&self.payer),
+                daily_volume: daily_volume(self.day),
+                sender: self.payer,
+                blacklist_check: blacklisted_payer(&self.payer),
+                sender_token_account: self.payer_token_account,
+                mint: self.mint,
+                accepted_mint: self.accepted_mint,
+                vault_mint_config: self.vault_mint_config,
+                fee_schedule: self.fee_schedule,
+                recipient: self.merchant.pubkey(),
+                fee_token_account: self.fee_token_account,
+                fee_token_account_1: self.fee_token_account_1,
+                fee_token_account_2: self.fee_token_account_2,
+                token_program: spl_token::ID,
+                system_program: system_program::ID,
+            }
+            .to_account_metas(None),
+            data: ninjapay_vault::instruction::OpenStream {
+                stream_id,
+                rate_per_second,
+                start_time,
+                end_time,
+            }
+            .data(),
+        }
+    }
+
+    /// withdraw_from_stream of the payer's stream, signed by the merchant
+    pub fn withdraw_stream_instruction(&self, stream_id: [u8; 32]) -> Instruction {
+        Instruction {
+            program_id: ninjapay_vault::ID,
+            accounts: ninjapay_vault::accounts::WithdrawFromStream {
+                vault_config: vault_config(),
+                streaming_payment: streaming_payment(&self.payer, &stream_id),
+                stream_tokens: stream_tokens(&self.payer, &stream_id),
+                recipient: self.merchant.pubkey(),
+                recipient_token_account: self.merchant_token_account,
+                mint: self.mint,
+                token_program: spl_token::ID,
+            }
+            .to_account_metas(None),
+            data: ninjapay_vault::instruction::WithdrawFromStream { stream_id }.data(),
+        }
+    }
+
+    /// top_up_stream of the payer's stream by `amount`
+    pub fn top_up_stream_instruction(&self, stream_id: [u8; 32], amount: u64) -> Instruction {
+        Instruction {
+            program_id: ninjapay_vault::ID,
+            accounts: ninjapay_vault::accounts::TopUpStream {
+                vault_config: vault_config(),
+                streaming_payment: streaming_payment(&self.payer, &stream_id),
+                stream_tokens: stream_tokens(&self.payer, &stream_id),
+                payer_rate_limit: payer_rate_limit(&self.payer),
+                daily_volume: daily_volume(self.day),
+                sender: self.payer,
+                blacklist_check: blacklisted_payer(&self.payer),
+                sender_token_account: self.payer_token_account,
+                mint: self.mint,
+                accepted_mint: self.accepted_mint,
+                vault_mint_config: self.vault_mint_config,
+                fee_schedule: self.fee_schedule,
+                fee_token_account: self.fee_token_account,
+                fee_token_account_1: self.fee_token_account_1,
+                fee_token_account_2: self.fee_token_account_2,
+                token_program: spl_token::ID,
+                system_program: system_program::ID,
+            }
+            .to_account_metas(None),
+            data: ninjapay_vault::instruction::TopUpStream { stream_id, amount }.data(),
+        }
+    }
+
+    /// cancel_stream of the payer's stream
+    pub fn cancel_stream_instruction(&self, stream_id: [u8; 32]) -> Instruction {
+        Instruction {
+            program_id: ninjapay_vault::ID,
+            accounts: ninjapay_vault::accounts::CancelStream {
+                vault_config: vault_config(),
+                streaming_payment: streaming_payment(&self.payer, &stream_id),
+                stream_tokens: stream_tokens(&self.payer, &stream_id),
+                sender: self.payer,
+                sender_token_account: self.payer_token_account,
+                recipient: self.merchant.pubkey(),
+                recipient_token_account: self.merchant_token_account,
+                mint: self.mint,
+                token_program: spl_token::ID,
+            }
+            .to_account_metas(None),
+            data: ninjapay_vault::instruction::CancelStream { stream_id }.data(),
+        }
+    }
+
+    /// process_payment_batch of `(merchant_token_account, amount, payment_id)`
+    /// payments, passing each token account, its record and no
+    /// MerchantConfig as remaining accounts
+    pub fn batch_instruction(&self, payments: &[(Pubkey, u64, [u8; 32])]) -> Instruction {
+        self.batch_instruction_registered(payments, &[])
+    }
+
+    /// As `batch_instruction`, following each payment's accounts with the
+    /// RegisteredMerchant of `merchants[i]`, its token account's owner
+    pub fn batch_instruction_registered(
+        &self,
+        payments: &[(Pubkey, u64, [u8; 32])],
+        merchants: &[Pubkey],
+    ) -> Instruction {
+        self.batch_instruction_with(payments, &[], merchants)
+    }
+
+    /// As `batch_instruction_registered`, passing `merchant_configs[i]` as
+    /// each payment's MerchantConfig where there is one
+    pub fn batch_instruction_with(
+        &self,
+        payments: &[(Pubkey, u64, [u8; 32])],
+        merchant_configs: &[Option<Pubkey>],
+        merchants: &[Pubkey],
+    ) -> Instruction {
+        let mut accounts = ninjapay_vault::accounts::ProcessPaymentBatch {
+            vault_config: vault_config(),
+            payer_rate_limit: payer_rate_limit(&self.payer),
+            daily_volume: daily_volume(self.day),
+            payer: self.payer,
+            blacklist_check: blacklisted_payer(&self.payer),
+            payer_token_account: self.payer_token_account,
+            mint: self.mint,
+            accepted_mint: self.accepted_mint,
+            vault_mint_config: self.vault_mint_config,
+            fee_schedule: self.fee_schedule,
+            fee_token_account: self.fee_token_account,
+            fee_token_account_1: self.fee_token_account_1,
+            fee_token_account_2: self.fee_token_account_2,
+            token_program: spl_token::ID,
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None);
+        for (i, (merchant_token_account, _, payment_id)) in payments.iter().enumerate() {
+            accounts.push(AccountMeta::new(*merchant_token_account, false));
+            accounts.push(AccountMeta::new(payment_record(&self.payer, payment_id), false));
+            let merchant_config = merchant_configs.get(i).copied().flatten();
+            accounts.push(AccountMeta::new_readonly(merchant_config.unwrap_or(ninjapay_vault::ID), false));
+            if let Some(merchant) = merchants.get(i) {
+                accounts.push(AccountMeta::new_readonly(registered_merchant(merchant), false));
+            }
+        }
+        Instruction {
+            program_id: ninjapay_vault::ID,
+            accounts,
+            data: ninjapay_vault::instruction::ProcessPaymentBatch {
+                amounts: payments.iter().map(|&(_, amount, _)| amount).collect(),
+                payment_ids: payments.iter().map(|&(_, _, payment_id)| payment_id).collect(),
+                commitments: vec![[9; 32]; payments.len()],
+                blinding_factors: vec![vec![]; payments.len()],
+            }
+            .data(),
+        }
+    }
+}
+
+pub async fn send(
+    context: &mut ProgramTestContext,
+    instructions: &[Instruction],
+    signers: &[&Keypair],
+) -> Result<(), BanksClientError> {
+    let blockhash = context.banks_client.get_latest_blockhash().await.unwrap();
+    let mut all_signers = vec![&context.payer];
+    all_signers.extend_from_slice(signers);
+    let transaction =
+        Transaction::new_signed_with_payer(instructions, Some(&context.payer.pubkey()), &all_signers, blockhash);
+    context.banks_client.process_transaction(transaction).await
+}
+
+/// The approver the payroll builders pass; tests name it for a company with
+/// set_payroll_approver_instruction and sign with it
+pub fn payroll_approver() -> Keypair {
+    keypair_from_seed(&[7; 32]).unwrap()
+}
+
+pub fn payroll_approver_config(company: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"payroll_approver", company.as_ref()], &ninjapay_vault::ID).0
+}
+
+/// set_payroll_approver naming `approver`, signed by `company`
+pub fn set_payroll_approver_instruction(company: Pubkey, approver: Pubkey) -> Instruction {
+    replace_payroll_approver_instruction(company, None, approver)
+}
+
+/// set_payroll_approver, co-signed by `current_approver` if given
+pub fn replace_payroll_approver_instruction(
+    company: Pubkey,
+    current_approver: Option<Pubkey>,
+    approver: Pubkey,
+) -> Instruction {
+    Instruction {
+        program_id: ninjapay_vault::ID,
+        accounts: ninjapay_vault::accounts::SetPayrollApprover {
+            payroll_approver_config: payroll_approver_config(&company),
+            company,
+            current_approver,
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None),
+        data: ninjapay_vault::instruction::SetPayrollApprover { approver }.data(),
+    }
+}
+
+/// process_payroll_batch funding `batch_id` from `company`'s
+/// `company_token_account` and paying each `(employee_token_account, amount)`,
+/// co-signed by `payroll_approver()`
+pub fn payroll_batch_instruction(
+    company: Pubkey,
+    company_token_account: Pubkey,
+    mint: Pubkey,
+    batch_id: [u8; 32],
+    total_amount: u64,
+    payment_count: u16,
+    employees: &[(Pubkey, u64)],
+) -> Instruction {
+    let mut accounts = payroll_batch_accounts(company, company_token_account, mint, batch_id).to_account_metas(None);
+    push_disbursements(&mut accounts, batch_id, employees);
+    Instruction {
+        program_id: ninjapay_vault::ID,
+        accounts,
+        data: ninjapay_vault::instruction::ProcessPayrollBatch {
+            batch_id,
+            total_amount,
+            payment_count,
+            amounts: employees.iter().map(|&(_, amount)| amount).collect(),
+        }
+        .data(),
+    }
+}
+
+fn payroll_batch_accounts(
+    company: Pubkey,
+    company_token_account: Pubkey,
+    mint: Pubkey,
+    batch_id: [u8; 32],
+) -> ninjapay_vault::accounts::ProcessPayrollBatch {
+    ninjapay_vault::accounts::ProcessPayrollBatch {
+        vault_config: vault_config(),
+        batch_record: batch_record(&batch_id),
+        escrow: batch_escrow(&batch_id),
+        company,
+        payroll_approver_config: payroll_approver_config(&company),
+        approver: Some(payroll_approver().pubkey()),
+        company_token_account,
+        mint,
+        token_program: spl_token::ID,
+        system_program: system_program::ID,
+    }
+}
+
+/// Each employee token account followed by its DisbursementRecord
+fn push_disbursements(accounts: &mut Vec<AccountMeta>, batch_id: [u8; 32], employees: &[(Pubkey, u64)]) {
+    for (index, &(employee_token_account, _)) in employees.iter().enumerate() {
+        accounts.push(AccountMeta::new(employee_token_account, false));
+        accounts.push(AccountMeta::new(disbursement_record(&batch_id, index as u16), false));
+    }
+}
+
+pub fn payroll_schedule(company: &Pubkey, schedule_id: &[u8; 32]) -> Pubkey {
+    Pubkey::find_program_address(&[b"payroll_sched", company.as_ref(), schedule_id], &ninjapay_vault::ID).0
+}
+
+/// create_payroll_schedule of `schedule_id` in `mint`, signed by `company`
+pub fn create_payroll_schedule_instruction(
+    company: Pubkey,
+    mint: Pubkey,
+    schedule_id: [u8; 32],
+    frequency_seconds: u64,
+    next_due_at: i64,
+    authorized_amount: u64,
+) -> Instruction {
+    Instruction {
+        program_id: ninjapay_vault::ID,
+        accounts: ninjapay_vault::accounts::CreatePayrollSchedule {
+            payroll_schedule: payroll_schedule(&company, &schedule_id),
+            company,
+            mint,
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None),
+        data: ninjapay_vault::instruction::CreatePayrollSchedule {
+            schedule_id,
+            frequency_seconds,
+            next_due_at,
+            authorized_amount,
+        }
+        .data(),
+    }
+}
+
+/// update_payroll_schedule of `schedule_id`, signed by `company`
+pub fn update_payroll_schedule_instruction(
+    company: Pubkey,
+    schedule_id: [u8; 32],
+    frequency_seconds: u64,
+    next_due_at: i64,
+    authorized_amount: u64,
+    status: ScheduleStatus,
+) -> Instruction {
+    Instruction {
+        program_id: ninjapay_vault::ID,
+        accounts: ninjapay_vault::accounts::UpdatePayrollSchedule {
+            payroll_schedule: payroll_schedule(&company, &schedule_id),
+            company,
+        }
+        .to_account_metas(None),
+        data: ninjapay_vault::instruction::UpdatePayrollSchedule {
+            schedule_id,
+            frequency_seconds,
+            next_due_at,
+            authorized_amount,
+            status,
+        }
+        .data(),
+    }
+}
+
+/// execute_scheduled_payroll of `company`'s `schedule_id` as `batch_id`,
+/// paying each `(employee_token_account, amount)`, co-signed by
+/// `payroll_approver()`
+pub fn scheduled_payroll_instruction(
+    company: Pubkey,
+    company_token_account: Pubkey,
+    mint: Pubkey,
+    schedule_id: [u8; 32],
+    batch_id: [u8; 32],
+    employees: &[(Pubkey, u64)],
+) -> Instruction {
+    let mut accounts = ninjapay_vault::accounts::ExecuteScheduledPayroll {
+        payroll_schedule: payroll_schedule(&company, &schedule_id),
+        batch: payroll_batch_accounts(company, company_token_account, mint, batch_id),
+    }
+    .to_account_metas(None);
+    push_disbursements(&mut accounts, batch_id, employees);
+    let amounts: Vec<u64> = employees.iter().map(|&(_, amount)| amount).collect();
+    Instruction {
+        program_id: ninjapay_vault::ID,
+        accounts,
+        data: ninjapay_vault::instruction::ExecuteScheduledPayroll {
+            batch_id,
+            schedule_id,
+            total_amount: amounts.iter().sum(),
+            payment_count: amounts.len() as u16,
+            amounts,
+        }
+        .data(),
+    }
+}
+
+/// close_batch_record of `batch_id`, signed by `company`
+pub fn close_batch_record_instruction(company: Pubkey, batch_id: [u8; 32]) -> Instruction {
+    Instruction {
+        program_id: ninjapay_vault::ID,
+        accounts: ninjapay_vault::accounts::CloseBatchRecord {
+            vault_config: vault_config(),
+            batch_record: batch_record(&batch_id),
+            escrow: batch_escrow(&batch_id),
+            company,
+            token_program: spl_token::ID,
+        }
+        .to_account_metas(None),
+        data: ninjapay_vault::instruction::CloseBatchRecord {}.data(),
+    }
+}
+
+/// close_payment_record of `payment_id`, signed by `payer`
+pub fn close_payment_record_instruction(payer: Pubkey, payment_id: [u8; 32]) -> Instruction {
+    Instruction {
+        program_id: ninjapay_vault::ID,
+        accounts: ninjapay_vault::accounts::ClosePaymentRecord {
+            vault_config: vault_config(),
+            payment_record: payment_record(&payer, &payment_id),
+            payer,
+        }
+        .to_account_metas(None),
+        data: ninjapay_vault::instruction::ClosePaymentRecord { payment_id }.data(),
+    }
+}
+
+/// compliance_close_payment_record of `payer`'s `payment_id`, signed by
+/// `authority`
+pub fn compliance_close_instruction(authority: Pubkey, payer: Pubkey, payment_id: [u8; 32]) -> Instruction {
+    Instruction {
+        program_id: ninjapay_vault::ID,
+        accounts: ninjapay_vault::accounts::ComplianceClosePaymentRecord {
+            vault_config: vault_config(),
+            payment_record: payment_record(&payer, &payment_id),
+            payer,
+            authority,
+        }
+        .to_account_metas(None),
+        data: ninjapay_vault::instruction::ComplianceClosePaymentRecord { payment_id }.data(),
+    }
+}
+
+/// open_dispute of `payment_id`, signed by `payer`
+pub fn open_dispute_instruction(payer: Pubkey, payment_id: [u8; 32]) -> Instruction {
+    Instruction {
+        program_id: ninjapay_vault::ID,
+        accounts: ninjapay_vault::accounts::OpenDispute {
+            payment_record: payment_record(&payer, &payment_id),
+            payer,
+        }
+        .to_account_metas(None),
+        data: ninjapay_vault::instruction::OpenDispute { payment_id }.data(),
+    }
+}
+
+/// resolve_dispute of `payer`'s `payment_id`, signed by `authority`
+pub fn resolve_dispute_instruction(authority: Pubkey, payer: Pubkey, payment_id: [u8; 32]) -> Instruction {
+    Instruction {
+        program_id: ninjapay_vault::ID,
+        accounts: ninjapay_vault::accounts::ResolveDispute {
+            vault_config: vault_config(),
+            payment_record: payment_record(&payer, &payment_id),
+            payer,
+            authority,
+        }
+        .to_account_metas(None),
+        data: ninjapay_vault::instruction::ResolveDispute { payment_id }.data(),
+    }
+}
+
+/// set_rate_limit_config signed by `authority`
+pub fn set_rate_limit_config_instruction(
+    authority: Pubkey,
+    max_payments_per_hour: u16,
+    max_volume_per_hour: u64,
+) -> Instruction {
+    Instruction {
+        program_id: ninjapay_vault::ID,
+        accounts: ninjapay_vault::accounts::SetRateLimitConfig {
+            vault_config: vault_config(),
+            authority,
+        }
+        .to_account_metas(None),
+        data: ninjapay_vault::instruction::SetRateLimitConfig {
+            max_payments_per_hour,
+            max_volume_per_hour,
+        }
+        .data(),
+    }
+}
+
+/// set_daily_volume_cap signed by `authority`
+pub fn set_daily_volume_cap_instruction(authority: Pubkey, daily_volume_cap: u64) -> Instruction {
+    Instruction {
+        program_id: ninjapay_vault::ID,
+        accounts: ninjapay_vault::accounts::SetDailyVolumeCap {
+            vault_config: vault_config(),
+            authority,
+        }
+        .to_account_metas(None),
+        data: ninjapay_vault::instruction::SetDailyVolumeCap { daily_volume_cap }.data(),
+    }
+}
+
+pub fn set_retention_days_instruction(authority: Pubkey, retention_days: u16) -> Instruction {
+    Instruction {
+        program_id: ninjapay_vault::ID,
+        accounts: ninjapay_vault::accounts::SetRetentionDays {
+            vault_config: vault_config(),
+            authority,
+        }
+        .to_account_metas(None),
+        data: ninjapay_vault::instruction::SetRetentionDays { retention_days }.data(),
+    }
+}
+
+/// set_fee_collectors to `(collector, basis_points)` shares
+pub fn set_fee_collectors_instruction(authority: Pubkey, fee_collectors: &[(Pubkey, u16)]) -> Instruction {
+    let fee_collectors = fee_collectors
+        .iter()
+        .map(|&(collector, basis_points)| FeeCollectorShare { collector, basis_points })
+        .collect();
+    Instruction {
+        program_id: ninjapay_vault::ID,
+        accounts: ninjapay_vault::accounts::SetFeeCollectors {
+            vault_config: vault_config(),
+            authority,
+        }
+        .to_account_metas(None),
+        data: ninjapay_vault::instruction::SetFeeCollectors { fee_collectors }.data(),
+    }
+}
+
+/// migrate_vault_config signed by `authority`
+pub fn migrate_vault_config_instruction(authority: Pubkey) -> Instruction {
+    Instruction {
+        program_id: ninjapay_vault::ID,
+        accounts: ninjapay_vault::accounts::MigrateVaultConfig {
+            vault_config: vault_config(),
+            authority,
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None),
+        data: ninjapay_vault::instruction::MigrateVaultConfig {}.data(),
+    }
+}
+
+pub fn sol_escrow(payer: &Pubkey, payment_id: &[u8; 32]) -> Pubkey {
+    Pubkey::find_program_address(&[b"sol_escrow", payer.as_ref(), payment_id], &ninjapay_vault::ID).0
+}
+
+/// process_sol_payment of `lamports` from `payer` to `merchant`'s wallet
+pub fn sol_payment_instruction(
+    payer: Pubkey,
+    merchant: Pubkey,
+    fee_collector: Pubkey,
+    lamports: u64,
+    payment_id: [u8; 32],
+) -> Instruction {
+    sol_payment_instruction_to(payer, merchant, None, &[fee_collector], lamports, payment_id)
+}
+
+/// As `sol_payment_instruction`, passing the merchant's RegisteredMerchant
+/// and `fee_collectors` by the index of their share
+pub fn sol_payment_instruction_to(
+    payer: Pubkey,
+    merchant: Pubkey,
+    registered_merchant: Option<Pubkey>,
+    fee_collectors: &[Pubkey],
+    lamports: u64,
+    payment_id: [u8; 32],
+) -> Instruction {
+    let accounts = ninjapay_vault::accounts::ProcessSolPayment {
+        registered_merchant,
+        ..sol_payment_accounts(payer, merchant, fee_collectors, payment_id)
+    };
+    sol_payment_instruction_from(accounts, lamports, payment_id)
+}
+
+/// process_sol_payment's accounts, with every optional one left out
+pub fn sol_payment_accounts(
+    payer: Pubkey,
+    merchant: Pubkey,
+    fee_collectors: &[Pubkey],
+    payment_id: [u8; 32],
+) -> ninjapay_vault::accounts::ProcessSolPayment {
+    ninjapay_vault::accounts::ProcessSolPayment {
+        vault_config: vault_config(),
+        payment_record: payment_record(&payer, &payment_id),
+        payer_rate_limit: payer_rate_limit(&payer),
+        daily_volume: daily_volume(TODAY),
+        sol_escrow: sol_escrow(&payer, &payment_id),
+        payer,
+        blacklist_check: blacklisted_payer(&payer),
+        native_mint: spl_token::native_mint::ID,
+        accepted_mint: None,
+        vault_mint_config: None,
+        fee_schedule: None,
+        merchant,
+        registered_merchant: None,
+        fee_collector: fee_collectors[0],
+        fee_collector_1: fee_collectors.get(1).copied(),
+        fee_collector_2: fee_collectors.get(2).copied(),
+        token_program: spl_token::ID,
+        system_program: system_program::ID,
+    }
+}
+
+/// process_sol_payment of `lamports` with the given accounts
+pub fn sol_payment_instruction_from(
+    accounts: ninjapay_vault::accounts::ProcessSolPayment,
+    lamports: u64,
+    payment_id: [u8; 32],
+) -> Instruction {
+    Instruction {
+        program_id: ninjapay_vault::ID,
+        accounts: accounts.to_account_metas(None),
+        data: ninjapay_vault::instruction::ProcessSolPayment {
+            lamports,
+            payment_id,
+            commitment: [9; 32],
+            blinding_factor: vec![],
+        }
+        .data(),
+    }
+}
+
+/// add_accepted_mint or remove_accepted_mint for `mint`, signed by `authority`
+pub fn set_accepted_mint_instruction(accepted: bool, authority: Pubkey, mint: Pubkey) -> Instruction {
+    let (accounts, data) = if accepted {
+        (
+            ninjapay_vault::accounts::AddAcceptedMint {
+                vault_config: vault_config(),
+                accepted_mint: accepted_mint(&mint),
+                mint,
+                authority,
+                system_program: system_program::ID,
+            }
+            .to_account_metas(None),
+            ninjapay_vault::instruction::AddAcceptedMint {}.data(),
+        )
+    } else {
+        (
+            ninjapay_vault::accounts::RemoveAcceptedMint {
+                vault_config: vault_config(),
+                accepted_mint: accepted_mint(&mint),
+                authority,
+            }
+            .to_account_metas(None),
+            ninjapay_vault::instruction::RemoveAcceptedMint {}.data(),
+        )
+    };
+    Instruction {
+        program_id: ninjapay_vault::ID,
+        accounts,
+        data,
+    }
+}
+
+/// set_mint_whitelist_required signed by `authority`
+/// set_fee_schedule to `(volume_threshold, fee_basis_points)` tiers
+pub fn set_fee_schedule_instruction(authority: Pubkey, tiers: &[(u64, u16)]) -> Instruction {
+    let tiers = tiers
+        .iter()
+        .map(|&(volume_threshold, fee_basis_points)| FeeThreshold {
+            volume_threshold,
+            fee_basis_points,
+        })
+        .collect();
+    Instruction {
+        program_id: ninjapay_vault::ID,
+        accounts: ninjapay_vault::accounts::SetFeeSchedule {
+            vault_config: vault_config(),
+            fee_schedule: fee_schedule(),
+            authority,
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None),
+        data: ninjapay_vault::instruction::SetFeeSchedule { tiers }.data(),
+    }
+}
+
+pub fn clear_fee_schedule_instruction(authority: Pubkey) -> Instruction {
+    Instruction {
+        program_id: ninjapay_vault::ID,
+        accounts: ninjapay_vault::accounts::ClearFeeSchedule {
+            vault_config: vault_config(),
+            fee_schedule: fee_schedule(),
+            authority,
+        }
+        .to_account_metas(None),
+        data: ninjapay_vault::instruction::ClearFeeSchedule {}.data(),
+    }
+}
+
+pub fn set_merchant_volume_tiers_instruction(authority: Pubkey, enabled: bool) -> Instruction {
+    Instruction {
+        program_id: ninjapay_vault::ID,
+        accounts: ninjapay_vault::accounts::SetMerchantVolumeTiers {
+            vault_config: vault_config(),
+            authority,
+        }
+        .to_account_metas(None),
+        data: ninjapay_vault::instruction::SetMerchantVolumeTiers { enabled }.data(),
+    }
+}
+
+pub fn set_commitment_verification_instruction(authority: Pubkey, enabled: bool) -> Instruction {
+    Instruction {
+        program_id: ninjapay_vault::ID,
+        accounts: ninjapay_vault::accounts::SetCommitmentVerification {
+            vault_config: vault_config(),
+            authority,
+        }
+        .to_account_metas(None),
+        data: ninjapay_vault::instruction::SetCommitmentVerification { enabled }.data(),
+    }
+}
+
+/// verify_payment_commitment of the PaymentRecord at `record`
+pub fn verify_payment_commitment_instruction(
+    record: Pubkey,
+    plaintext_amount: u64,
+    blinding_factor: [u8; 32],
+) -> Instruction {
+    Instruction {
+        program_id: ninjapay_vault::ID,
+        accounts: ninjapay_vault::accounts::VerifyPaymentCommitment { payment_record: record }.to_account_metas(None),
+        data: ninjapay_vault::instruction::VerifyPaymentCommitment {
+            plaintext_amount,
+            blinding_factor,
+        }
+        .data(),
+    }
+}
+
+pub fn set_mint_whitelist_required_instruction(authority: Pubkey, required: bool) -> Instruction {
+    Instruction {
+        program_id: ninjapay_vault::ID,
+        accounts: ninjapay_vault::accounts::SetMintWhitelistRequired {
+            vault_config: vault_config(),
+            authority,
+        }
+        .to_account_metas(None),
+        data: ninjapay_vault::instruction::SetMintWhitelistRequired { required }.data(),
+    }
+}
+
+/// register_merchant or deregister_merchant for `merchant`, signed by
+/// `authority`
+pub fn set_registered_merchant_instruction(registered: bool, authority: Pubkey, merchant: Pubkey) -> Instruction {
+    let (accounts, data) = if registered {
+        (
+            ninjapay_vault::accounts::RegisterMerchant {
+                vault_config: vault_config(),
+                registered_merchant: registered_merchant(&merchant),
+                merchant,
+                authority,
+                system_program: system_program::ID,
+            }
+            .to_account_metas(None),
+            ninjapay_vault::instruction::RegisterMerchant {}.data(),
+        )
+    } else {
+        (
+            ninjapay_vault::accounts::DeregisterMerchant {
+                vault_config: vault_config(),
+                registered_merchant: registered_merchant(&merchant),
+                authority,
+            }
+            .to_account_metas(None),
+            ninjapay_vault::instruction::DeregisterMerchant {}.data(),
+        )
+    };
+    Instruction {
+        program_id: ninjapay_vault::ID,
+        accounts,
+        data,
+    }
+}
+
+/// blacklist_payer of `payer` for `reason_hash`, signed by `authority`
+pub fn blacklist_payer_instruction(authority: Pubkey, payer: Pubkey, reason_hash: [u8; 32]) -> Instruction {
+    Instruction {
+        program_id: ninjapay_vault::ID,
+        accounts: ninjapay_vault::accounts::BlacklistPayer {
+            vault_config: vault_config(),
+            blacklisted_payer: blacklisted_payer(&payer),
+            payer,
+            authority,
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None),
+        data: ninjapay_vault::instruction::BlacklistPayer { reason_hash }.data(),
+    }
+}
+
+/// remove_from_blacklist of `payer`, signed by `authority`
+pub fn remove_from_blacklist_instruction(authority: Pubkey, payer: Pubkey) -> Instruction {
+    Instruction {
+        program_id: ninjapay_vault::ID,
+        accounts: ninjapay_vault::accounts::RemoveFromBlacklist {
+            vault_config: vault_config(),
+            blacklisted_payer: blacklisted_payer(&payer),
+            authority,
+        }
+        .to_account_metas(None),
+        data: ninjapay_vault::instruction::RemoveFromBlacklist {}.data(),
+    }
+}
+
+pub fn set_merchant_registration_required_instruction(authority: Pubkey, required: bool) -> Instruction {
+    Instruction {
+        program_id: ninjapay_vault::ID,
+        accounts: ninjapay_vault::accounts::SetMerchantRegistrationRequired {
+            vault_config: vault_config(),
+            authority,
+        }
+        .to_account_metas(None),
+        data: ninjapay_vault::instruction::SetMerchantRegistrationRequired { required }.data(),
+    }
+}
+
+/// set_payment_bounds signed by `authority`
+pub fn set_payment_bounds_instruction(authority: Pubkey, min_payment: u64, max_payment: u64) -> Instruction {
+    Instruction {
+        program_id: ninjapay_vault::ID,
+        accounts: ninjapay_vault::accounts::SetPaymentBounds {
+            vault_config: vault_config(),
+            authority,
+        }
+        .to_account_metas(None),
+        data: ninjapay_vault::instruction::SetPaymentBounds {
+            min_payment,
+            max_payment,
+        }
+        .data(),
+    }
+}
+
+/// pause_vault or unpause_vault signed by `authority`
+pub fn set_paused_instruction(paused: bool, authority: Pubkey) -> Instruction {
+    let (accounts, data) = if paused {
+        (
+            ninjapay_vault::accounts::PauseVault {
+                vault_config: vault_config(),
+                authority,
+            }
+            .to_account_metas(None),
+            ninjapay_vault::instruction::PauseVault {}.data(),
+        )
+    } else {
+        (
+            ninjapay_vault::accounts::UnpauseVault {
+                vault_config: vault_config(),
+                authority,
+            }
+            .to_account_metas(None),
+            ninjapay_vault::instruction::UnpauseVault {}.data(),
+        )
+    };
+    Instruction {
+        program_id: ninjapay_vault::ID,
+        accounts,
+        data,
+    }
+}
+
+pub fn assert_vault_error(result: Result<(), BanksClientError>, expected: VaultError) {
+    assert_program_error(result, u32::from(expected));
+}
+
+pub fn assert_program_error(result: Result<(), BanksClientError>, expected: u32) {
+    match result {
+        Err(BanksClientError::TransactionError(TransactionError::InstructionError(
+            _,
+            InstructionError::Custom(code),
+        ))) => assert_eq!(code, expected),
+        other => panic!("expected custom error {}, got {:?}", expected, other),
+    }
+}
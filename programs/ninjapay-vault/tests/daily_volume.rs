@@ -0,0 +1,85 @@
+mod common;
+
+use anchor_lang::AccountDeserialize;
+use common::{
+    assert_program_error, assert_vault_error, daily_volume, set_daily_volume_cap_instruction, Vault,
+    FEE_BASIS_POINTS, START_TIME, TODAY,
+};
+use ninjapay_vault::{DailyVolumeRecord, VaultError};
+use solana_sdk::signature::{Keypair, Signer};
+
+async fn record(vault: &mut Vault, day: i64) -> DailyVolumeRecord {
+    let account = vault.context.banks_client.get_account(daily_volume(day)).await.unwrap().unwrap();
+    DailyVolumeRecord::try_deserialize(&mut account.data.as_slice()).unwrap()
+}
+
+#[tokio::test]
+async fn test_payments_over_the_daily_cap_are_rejected() {
+    let mut vault = Vault::start().await;
+    let payment = vault.payment().await;
+    let authority = vault.payer();
+    vault.send(&[set_daily_volume_cap_instruction(authority, 2_000_000)], &[]).await.unwrap();
+
+    vault.send(&[payment.instruction(1_500_000, [1; 32])], &[]).await.unwrap();
+    let result = vault.send(&[payment.instruction(500_001, [2; 32])], &[]).await;
+    assert_vault_error(result, VaultError::DailyCapExceeded);
+
+    // Exactly reaching the cap is fine
+    vault.send(&[payment.instruction(500_000, [3; 32])], &[]).await.unwrap();
+    let today = record(&mut vault, TODAY).await;
+    assert_eq!((today.date, today.volume), (TODAY, 2_000_000));
+    assert_eq!(vault.balance(&payment.payer_token_account).await, 8_000_000);
+}
+
+#[tokio::test]
+async fn test_volume_is_shared_by_all_payers_and_payment_kinds() {
+    let mut vault = Vault::start().await;
+    let payment = vault.payment().await;
+    let other_payer = Keypair::new();
+    let other = vault.payment_by(&other_payer).await;
+    let authority = vault.payer();
+    vault.send(&[set_daily_volume_cap_instruction(authority, 3_000_000)], &[]).await.unwrap();
+
+    vault.send(&[payment.instruction(1_000_000, [1; 32])], &[]).await.unwrap();
+    vault.send(&[other.instruction(1_000_000, [1; 32])], &[&other_payer]).await.unwrap();
+    let recipient = payment.merchant_token_account;
+    let split = payment.split_instruction(1_000_000, [2; 32], &[(10_000 - FEE_BASIS_POINTS, recipient)]);
+    vault.send(&[split], &[]).await.unwrap();
+    assert_eq!(record(&mut vault, TODAY).await.volume, 3_000_000);
+
+    let result = vault.send(&[other.instruction(1, [2; 32])], &[&other_payer]).await;
+    assert_vault_error(result, VaultError::DailyCapExceeded);
+}
+
+#[tokio::test]
+async fn test_each_day_starts_from_zero() {
+    let mut vault = Vault::start().await;
+    let mut payment = vault.payment().await;
+    let authority = vault.payer();
+    vault.send(&[set_daily_volume_cap_instruction(authority, 1_000_000)], &[]).await.unwrap();
+    vault.send(&[payment.instruction(1_000_000, [1; 32])], &[]).await.unwrap();
+
+    vault.set_time(START_TIME + DailyVolumeRecord::DAY_SECS).await;
+    // Still naming yesterday's record
+    let result = vault.send(&[payment.instruction(1_000_000, [2; 32])], &[]).await;
+    assert_program_error(result, anchor_lang::error::ErrorCode::ConstraintSeeds.into());
+
+    payment.day = TODAY + 1;
+    vault.send(&[payment.instruction(1_000_000, [3; 32])], &[]).await.unwrap();
+    assert_eq!(record(&mut vault, TODAY).await.volume, 1_000_000);
+    let tomorrow = record(&mut vault, TODAY + 1).await;
+    assert_eq!((tomorrow.date, tomorrow.volume), (TODAY + 1, 1_000_000));
+}
+
+#[tokio::test]
+async fn test_zero_cap_is_uncapped_and_only_the_authority_sets_it() {
+    let mut vault = Vault::start().await;
+    let payment = vault.payment().await;
+    let stranger = Keypair::new();
+
+    let result = vault.send(&[set_daily_volume_cap_instruction(stranger.pubkey(), 1)], &[&stranger]).await;
+    assert_program_error(result, anchor_lang::error::ErrorCode::ConstraintHasOne.into());
+
+    vault.send(&[payment.instruction(10_000_000, [1; 32])], &[]).await.unwrap();
+    assert_eq!(record(&mut vault, TODAY).await.volume, 10_000_000);
+}
@@ -0,0 +1,115 @@
+mod common;
+
+use anchor_lang::AccountDeserialize;
+use common::{
+    assert_program_error, assert_vault_error, close_payment_record_instruction, open_dispute_instruction,
+    payment_record, resolve_dispute_instruction, Vault, START_TIME,
+};
+use ninjapay_vault::{DailyVolumeRecord, PaymentRecord, PaymentStatus, VaultError, MIN_RETENTION_DAYS};
+use solana_sdk::{
+    compute_budget::ComputeBudgetInstruction,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+};
+
+async fn status(vault: &mut Vault, payer: &Pubkey, payment_id: [u8; 32]) -> PaymentStatus {
+    let account = vault.context.banks_client.get_account(payment_record(payer, &payment_id)).await.unwrap().unwrap();
+    PaymentRecord::try_deserialize(&mut account.data.as_slice()).unwrap().status
+}
+
+#[tokio::test]
+async fn test_disputed_payment_is_refunded_only_after_resolution() {
+    let mut vault = Vault::start().await;
+    let payment = vault.payment().await;
+    let fee_collector = vault.fee_collector.insecure_clone();
+    let authority = vault.payer();
+    vault.send(&[payment.instruction(1_000_000, [1; 32])], &[]).await.unwrap();
+
+    vault.send(&[open_dispute_instruction(payment.payer, [1; 32])], &[]).await.unwrap();
+    assert!(status(&mut vault, &payment.payer, [1; 32]).await == PaymentStatus::Disputed);
+
+    let refund = payment.refund_instruction([1; 32], 400_000, fee_collector.pubkey());
+    let result = vault.send(&[refund], &[&payment.merchant, &fee_collector]).await;
+    assert_vault_error(result, VaultError::PaymentUnderDispute);
+
+    vault.send(&[resolve_dispute_instruction(authority, payment.payer, [1; 32])], &[]).await.unwrap();
+    assert!(status(&mut vault, &payment.payer, [1; 32]).await == PaymentStatus::Settled);
+    let refund = payment.refund_instruction([1; 32], 995_000, fee_collector.pubkey());
+    vault.send(&[refund], &[&payment.merchant, &fee_collector]).await.unwrap();
+    assert_eq!(vault.balance(&payment.payer_token_account).await, 10_000_000);
+}
+
+#[tokio::test]
+async fn test_resolution_restores_a_partial_refund() {
+    let mut vault = Vault::start().await;
+    let payment = vault.payment().await;
+    let fee_collector = vault.fee_collector.insecure_clone();
+    let authority = vault.payer();
+    vault.send(&[payment.instruction(1_000_000, [1; 32])], &[]).await.unwrap();
+    let refund = payment.refund_instruction([1; 32], 400_000, fee_collector.pubkey());
+    vault.send(&[refund], &[&payment.merchant, &fee_collector]).await.unwrap();
+
+    vault.send(&[open_dispute_instruction(payment.payer, [1; 32])], &[]).await.unwrap();
+    vault.send(&[resolve_dispute_instruction(authority, payment.payer, [1; 32])], &[]).await.unwrap();
+
+    assert!(status(&mut vault, &payment.payer, [1; 32]).await == PaymentStatus::PartiallyRefunded);
+}
+
+#[tokio::test]
+async fn test_illegal_dispute_transitions_are_rejected() {
+    let mut vault = Vault::start().await;
+    let payment = vault.payment().await;
+    let authority = vault.payer();
+    vault.send(&[payment.instruction(1_000_000, [1; 32])], &[]).await.unwrap();
+
+    // Resolving a payment that isn't disputed. Compute budgets keep repeated
+    // instructions from repeating a transaction already processed.
+    let budget = ComputeBudgetInstruction::set_compute_unit_limit(200_001);
+    let result = vault.send(&[budget, resolve_dispute_instruction(authority, payment.payer, [1; 32])], &[]).await;
+    assert_vault_error(result, VaultError::NotDisputed);
+
+    vault.send(&[open_dispute_instruction(payment.payer, [1; 32])], &[]).await.unwrap();
+    // Disputing twice
+    let budget = ComputeBudgetInstruction::set_compute_unit_limit(200_001);
+    let result = vault.send(&[budget, open_dispute_instruction(payment.payer, [1; 32])], &[]).await;
+    assert_vault_error(result, VaultError::PaymentUnderDispute);
+
+    // Closing the record, however old
+    vault.set_time(START_TIME + (MIN_RETENTION_DAYS as i64 + 1) * DailyVolumeRecord::DAY_SECS).await;
+    let result = vault.send(&[close_payment_record_instruction(payment.payer, [1; 32])], &[]).await;
+    assert_vault_error(result, VaultError::PaymentUnderDispute);
+
+    vault.send(&[resolve_dispute_instruction(authority, payment.payer, [1; 32])], &[]).await.unwrap();
+    // Resolving twice
+    let budget = ComputeBudgetInstruction::set_compute_unit_limit(200_002);
+    let result = vault.send(&[budget, resolve_dispute_instruction(authority, payment.payer, [1; 32])], &[]).await;
+    assert_vault_error(result, VaultError::NotDisputed);
+    let budget = ComputeBudgetInstruction::set_compute_unit_limit(200_001);
+    vault.send(&[budget, close_payment_record_instruction(payment.payer, [1; 32])], &[]).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_only_the_payer_disputes_and_only_the_authority_resolves() {
+    let mut vault = Vault::start().await;
+    let payment = vault.payment().await;
+    vault.send(&[payment.instruction(1_000_000, [1; 32])], &[]).await.unwrap();
+    let stranger = Keypair::new();
+
+    let mut instruction = open_dispute_instruction(stranger.pubkey(), [1; 32]);
+    instruction.accounts[0].pubkey = payment_record(&payment.payer, &[1; 32]);
+    let result = vault.send(&[instruction], &[&stranger]).await;
+    assert_program_error(result, anchor_lang::error::ErrorCode::ConstraintSeeds.into());
+
+    // The merchant can't dispute on the payer's behalf either
+    let merchant = payment.merchant.pubkey();
+    let mut instruction = open_dispute_instruction(merchant, [1; 32]);
+    instruction.accounts[0].pubkey = payment_record(&payment.payer, &[1; 32]);
+    let result = vault.send(&[instruction], &[&payment.merchant]).await;
+    assert_program_error(result, anchor_lang::error::ErrorCode::ConstraintSeeds.into());
+
+    vault.send(&[open_dispute_instruction(payment.payer, [1; 32])], &[]).await.unwrap();
+    let result =
+        vault.send(&[resolve_dispute_instruction(stranger.pubkey(), payment.payer, [1; 32])], &[&stranger]).await;
+    assert_program_error(result, anchor_lang::error::ErrorCode::ConstraintHasOne.into());
+    assert!(status(&mut vault, &payment.payer, [1; 32]).await == PaymentStatus::Disputed);
+}
@@ -0,0 +1,148 @@
+mod common;
+
+use anchor_lang::{AccountDeserialize, InstructionData, ToAccountMetas};
+use common::{assert_program_error, assert_vault_error, vault_config, Vault, START_TIME};
+use ninjapay_vault::{VaultConfig, VaultError, EMERGENCY_RECOVERY_DELAY};
+use solana_sdk::{
+    compute_budget::ComputeBudgetInstruction,
+    instruction::Instruction,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+};
+
+fn set_recovery_authority(authority: Pubkey, recovery_authority: Option<Pubkey>) -> Instruction {
+    Instruction {
+        program_id: ninjapay_vault::ID,
+        accounts: ninjapay_vault::accounts::SetRecoveryAuthority {
+            vault_config: vault_config(),
+            authority,
+        }
+        .to_account_metas(None),
+        data: ninjapay_vault::instruction::SetRecoveryAuthority { recovery_authority }.data(),
+    }
+}
+
+fn propose_recovery(recovery_authority: Pubkey, new_authority: Pubkey) -> Instruction {
+    Instruction {
+        program_id: ninjapay_vault::ID,
+        accounts: ninjapay_vault::accounts::ProposeEmergencyRecovery {
+            vault_config: vault_config(),
+            recovery_authority,
+            new_authority,
+        }
+        .to_account_metas(None),
+        data: ninjapay_vault::instruction::ProposeEmergencyRecovery {}.data(),
+    }
+}
+
+fn recover(recovery_authority: Pubkey, new_authority: Pubkey) -> Instruction {
+    Instruction {
+        program_id: ninjapay_vault::ID,
+        accounts: ninjapay_vault::accounts::EmergencyRecover {
+            vault_config: vault_config(),
+            recovery_authority,
+            new_authority,
+        }
+        .to_account_metas(None),
+        data: ninjapay_vault::instruction::EmergencyRecover {}.data(),
+    }
+}
+
+fn cancel_recovery(authority: Pubkey) -> Instruction {
+    Instruction {
+        program_id: ninjapay_vault::ID,
+        accounts: ninjapay_vault::accounts::CancelEmergencyRecovery {
+            vault_config: vault_config(),
+            authority,
+        }
+        .to_account_metas(None),
+        data: ninjapay_vault::instruction::CancelEmergencyRecovery {}.data(),
+    }
+}
+
+async fn config(vault: &mut Vault) -> VaultConfig {
+    let account = vault.context.banks_client.get_account(vault_config()).await.unwrap().unwrap();
+    VaultConfig::try_deserialize(&mut account.data.as_slice()).unwrap()
+}
+
+#[tokio::test]
+async fn test_recovery_authority_takes_over_after_the_delay() {
+    let mut vault = Vault::start().await;
+    let authority = vault.payer();
+    let recovery = Keypair::new();
+    let new_authority = Keypair::new();
+    assert_eq!(config(&mut vault).await.recovery_authority, None);
+    vault.send(&[set_recovery_authority(authority, Some(recovery.pubkey()))], &[]).await.unwrap();
+
+    let result = vault.send(&[recover(recovery.pubkey(), new_authority.pubkey())], &[&recovery]).await;
+    assert_vault_error(result, VaultError::NoPendingRecovery);
+
+    vault.send(&[propose_recovery(recovery.pubkey(), new_authority.pubkey())], &[&recovery]).await.unwrap();
+    vault.set_time(START_TIME + EMERGENCY_RECOVERY_DELAY - 1).await;
+    let budget = ComputeBudgetInstruction::set_compute_unit_limit(200_001);
+    let result = vault.send(&[budget, recover(recovery.pubkey(), new_authority.pubkey())], &[&recovery]).await;
+    assert_vault_error(result, VaultError::RecoveryTimelockActive);
+
+    // Only to the proposed key
+    vault.set_time(START_TIME + EMERGENCY_RECOVERY_DELAY).await;
+    let result = vault.send(&[recover(recovery.pubkey(), Keypair::new().pubkey())], &[&recovery]).await;
+    assert_vault_error(result, VaultError::Unauthorized);
+
+    let budget = ComputeBudgetInstruction::set_compute_unit_limit(200_002);
+    vault.send(&[budget, recover(recovery.pubkey(), new_authority.pubkey())], &[&recovery]).await.unwrap();
+    let recovered = config(&mut vault).await;
+    assert_eq!(recovered.authority, new_authority.pubkey());
+    assert_eq!((recovered.pending_recovery_authority, recovered.recovery_proposed_at), (None, 0));
+
+    // The old authority is locked out; the new one is in charge
+    let result = vault.send(&[set_recovery_authority(authority, None)], &[]).await;
+    assert_program_error(result, anchor_lang::error::ErrorCode::ConstraintHasOne.into());
+    vault.send(&[set_recovery_authority(new_authority.pubkey(), None)], &[&new_authority]).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_authority_cancels_a_recovery_it_did_not_want() {
+    let mut vault = Vault::start().await;
+    let authority = vault.payer();
+    let recovery = Keypair::new();
+    let attacker = Keypair::new().pubkey();
+    vault.send(&[set_recovery_authority(authority, Some(recovery.pubkey()))], &[]).await.unwrap();
+    vault.send(&[propose_recovery(recovery.pubkey(), attacker)], &[&recovery]).await.unwrap();
+
+    vault.send(&[cancel_recovery(authority)], &[]).await.unwrap();
+    vault.set_time(START_TIME + EMERGENCY_RECOVERY_DELAY).await;
+    let result = vault.send(&[recover(recovery.pubkey(), attacker)], &[&recovery]).await;
+    assert_vault_error(result, VaultError::NoPendingRecovery);
+    assert_eq!(config(&mut vault).await.authority, authority);
+
+    let budget = ComputeBudgetInstruction::set_compute_unit_limit(200_001);
+    let result = vault.send(&[budget, cancel_recovery(authority)], &[]).await;
+    assert_vault_error(result, VaultError::NoPendingRecovery);
+}
+
+#[tokio::test]
+async fn test_only_the_recovery_authority_proposes_and_recovers() {
+    let mut vault = Vault::start().await;
+    let authority = vault.payer();
+    let stranger = Keypair::new();
+
+    // No recovery authority configured
+    let result = vault.send(&[propose_recovery(stranger.pubkey(), stranger.pubkey())], &[&stranger]).await;
+    assert_vault_error(result, VaultError::Unauthorized);
+
+    let recovery = Keypair::new();
+    vault.send(&[set_recovery_authority(authority, Some(recovery.pubkey()))], &[]).await.unwrap();
+    vault.send(&[propose_recovery(recovery.pubkey(), stranger.pubkey())], &[&recovery]).await.unwrap();
+    vault.set_time(START_TIME + EMERGENCY_RECOVERY_DELAY).await;
+    let result = vault.send(&[recover(stranger.pubkey(), stranger.pubkey())], &[&stranger]).await;
+    assert_vault_error(result, VaultError::Unauthorized);
+
+    // Replacing the recovery authority drops its proposal
+    let result = vault.send(&[set_recovery_authority(stranger.pubkey(), None)], &[&stranger]).await;
+    assert_program_error(result, anchor_lang::error::ErrorCode::ConstraintHasOne.into());
+    vault.send(&[set_recovery_authority(authority, Some(stranger.pubkey()))], &[]).await.unwrap();
+    let budget = ComputeBudgetInstruction::set_compute_unit_limit(200_001);
+    let result = vault.send(&[budget, recover(stranger.pubkey(), stranger.pubkey())], &[&stranger]).await;
+    assert_vault_error(result, VaultError::NoPendingRecovery);
+    assert_eq!(config(&mut vault).await.authority, authority);
+}
@@ -0,0 +1,184 @@
+mod common;
+
+use anchor_lang::AccountDeserialize;
+use common::{
+    assert_program_error, assert_vault_error, escrow_record, escrow_tokens, set_daily_volume_cap_instruction,
+    set_rate_limit_config_instruction, vault_config, Vault, START_TIME,
+};
+use ninjapay_vault::{EscrowRecord, EscrowStatus, VaultConfig, VaultError};
+use solana_sdk::{
+    compute_budget::ComputeBudgetInstruction,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+};
+
+const EXPIRES_AT: i64 = START_TIME + 3_600;
+
+async fn record(vault: &mut Vault, payer: &Pubkey, payment_id: [u8; 32]) -> EscrowRecord {
+    let account = vault.context.banks_client.get_account(escrow_record(payer, &payment_id)).await.unwrap().unwrap();
+    EscrowRecord::try_deserialize(&mut account.data.as_slice()).unwrap()
+}
+
+#[tokio::test]
+async fn test_merchant_captures_before_expiry() {
+    let mut vault = Vault::start().await;
+    let payment = vault.payment().await;
+    vault.send(&[payment.escrow_instruction(1_000_000, [1; 32], EXPIRES_AT)], &[]).await.unwrap();
+    let escrow = escrow_tokens(&payment.payer, &[1; 32]);
+    assert_eq!(vault.balance(&payment.payer_token_account).await, 9_000_000);
+    assert_eq!(vault.balance(&escrow).await, 1_000_000);
+
+    // Held until it expires
+    let result = vault.send(&[payment.cancel_escrow_instruction([1; 32])], &[]).await;
+    assert_vault_error(result, VaultError::EscrowNotExpired);
+
+    vault.set_time(EXPIRES_AT - 1).await;
+    vault.send(&[payment.capture_instruction([1; 32])], &[&payment.merchant]).await.unwrap();
+
+    // 50 bps fee
+    assert_eq!(vault.balance(&payment.merchant_token_account).await, 995_000);
+    assert_eq!(vault.balance(&payment.fee_token_account).await, 5_000);
+    assert!(vault.context.banks_client.get_account(escrow).await.unwrap().is_none());
+    let captured = record(&mut vault, &payment.payer, [1; 32]).await;
+    assert!(captured.status == EscrowStatus::Captured);
+    assert_eq!((captured.amount, captured.fee, captured.expires_at), (1_000_000, 5_000, EXPIRES_AT));
+    let account = vault.context.banks_client.get_account(vault_config()).await.unwrap().unwrap();
+    let config = VaultConfig::try_deserialize(&mut account.data.as_slice()).unwrap();
+    assert_eq!((config.total_volume, config.total_payments, config.total_fees_collected), (1_000_000, 1, 5_000));
+
+    // The escrow's token account closed with the capture, so a retry can't
+    // load it. The budget keeps it from repeating the capture's transaction.
+    let budget = ComputeBudgetInstruction::set_compute_unit_limit(200_001);
+    let result = vault.send(&[budget, payment.capture_instruction([1; 32])], &[&payment.merchant]).await;
+    assert_program_error(result, anchor_lang::error::ErrorCode::AccountNotInitialized.into());
+}
+
+#[tokio::test]
+async fn test_escrow_counts_towards_the_rate_limit_and_daily_cap() {
+    let mut vault = Vault::start().await;
+    let payment = vault.payment().await;
+    let authority = vault.payer();
+    vault.send(&[set_rate_limit_config_instruction(authority, 1, 0)], &[]).await.unwrap();
+
+    vault.send(&[payment.escrow_instruction(1_000_000, [1; 32], EXPIRES_AT)], &[]).await.unwrap();
+    let result = vault.send(&[payment.escrow_instruction(1_000_000, [2; 32], EXPIRES_AT)], &[]).await;
+    assert_vault_error(result, VaultError::RateLimitExceeded);
+
+    // Held, not yet captured, and still counted for the day
+    vault.send(&[set_rate_limit_config_instruction(authority, 0, 0)], &[]).await.unwrap();
+    vault.send(&[set_daily_volume_cap_instruction(authority, 1_500_000)], &[]).await.unwrap();
+    let result = vault.send(&[payment.escrow_instruction(1_000_000, [2; 32], EXPIRES_AT)], &[]).await;
+    assert_vault_error(result, VaultError::DailyCapExceeded);
+    assert_eq!(vault.balance(&payment.payer_token_account).await, 9_000_000);
+}
+
+#[tokio::test]
+async fn test_payer_cancels_after_expiry_and_capture_is_then_refused() {
+    let mut vault = Vault::start().await;
+    let payment = vault.payment().await;
+    vault.send(&[payment.escrow_instruction(1_000_000, [1; 32], EXPIRES_AT)], &[]).await.unwrap();
+
+    vault.set_time(EXPIRES_AT).await;
+    let result = vault.send(&[payment.capture_instruction([1; 32])], &[&payment.merchant]).await;
+    assert_vault_error(result, VaultError::PaymentExpired);
+
+    vault.send(&[payment.cancel_escrow_instruction([1; 32])], &[]).await.unwrap();
+    assert_eq!(vault.balance(&payment.payer_token_account).await, 10_000_000);
+    assert!(vault.context.banks_client.get_account(escrow_tokens(&payment.payer, &[1; 32])).await.unwrap().is_none());
+    assert!(record(&mut vault, &payment.payer, [1; 32]).await.status == EscrowStatus::Cancelled);
+
+    // Neither can load the closed escrow token account
+    let budget = ComputeBudgetInstruction::set_compute_unit_limit(200_001);
+    let result = vault.send(&[budget, payment.capture_instruction([1; 32])], &[&payment.merchant]).await;
+    assert_program_error(result, anchor_lang::error::ErrorCode::AccountNotInitialized.into());
+    let budget = ComputeBudgetInstruction::set_compute_unit_limit(200_001);
+    let result = vault.send(&[budget, payment.cancel_escrow_instruction([1; 32])], &[]).await;
+    assert_program_error(result, anchor_lang::error::ErrorCode::AccountNotInitialized.into());
+    assert_eq!(vault.balance(&payment.merchant_token_account).await, 0);
+}
+
+#[tokio::test]
+async fn test_only_the_recorded_merchant_captures() {
+    let mut vault = Vault::start().await;
+    let mut payment = vault.payment().await;
+    vault.send(&[payment.escrow_instruction(1_000_000, [1; 32], EXPIRES_AT)], &[]).await.unwrap();
+
+    payment.merchant = Keypair::new();
+    let result = vault.send(&[payment.capture_instruction([1; 32])], &[&payment.merchant]).await;
+
+    assert_vault_error(result, VaultError::Unauthorized);
+    assert_eq!(vault.balance(&escrow_tokens(&payment.payer, &[1; 32])).await, 1_000_000);
+}
+
+#[tokio::test]
+async fn test_escrow_must_expire_in_the_future() {
+    let mut vault = Vault::start().await;
+    let payment = vault.payment().await;
+
+    let result = vault.send(&[payment.escrow_instruction(1_000_000, [1; 32], START_TIME)], &[]).await;
+
+    assert_vault_error(result, VaultError::PaymentExpired);
+    assert_eq!(vault.balance(&payment.payer_token_account).await, 10_000_000);
+}
+
+#[tokio::test]
+async fn test_anyone_cranks_an_expired_escrow_back_to_the_payer() {
+    let mut vault = Vault::start().await;
+    // Paid by a payer of its own, so no transaction fee muddies its rent
+    let payer = Keypair::new();
+    let payment = vault.payment_by(&payer).await;
+    vault.send(&[payment.escrow_instruction(1_000_000, [1; 32], EXPIRES_AT)], &[&payer]).await.unwrap();
+    let cranker = Keypair::new();
+    let escrow = escrow_tokens(&payment.payer, &[1; 32]);
+
+    let result = vault.send(&[payment.crank_escrow_instruction([1; 32], cranker.pubkey())], &[&cranker]).await;
+    assert_vault_error(result, VaultError::EscrowNotExpired);
+
+    vault.set_time(EXPIRES_AT).await;
+    let rent = vault.lamports(&escrow).await;
+    let before = vault.lamports(&payment.payer).await;
+    let budget = ComputeBudgetInstruction::set_compute_unit_limit(200_001);
+    let crank = payment.crank_escrow_instruction([1; 32], cranker.pubkey());
+    vault.send(&[budget, crank], &[&cranker]).await.unwrap();
+
+    // The tokens and the token account's rent go back in full, and the
+    // cranker is paid nothing
+    assert_eq!(vault.balance(&payment.payer_token_account).await, 10_000_000);
+    assert_eq!(vault.lamports(&payment.payer).await, before + rent);
+    assert_eq!(vault.lamports(&cranker.pubkey()).await, 0);
+    assert!(vault.context.banks_client.get_account(escrow).await.unwrap().is_none());
+    assert!(record(&mut vault, &payment.payer, [1; 32]).await.status == EscrowStatus::Cancelled);
+
+    let budget = ComputeBudgetInstruction::set_compute_unit_limit(200_002);
+    let crank = payment.crank_escrow_instruction([1; 32], cranker.pubkey());
+    let result = vault.send(&[budget, crank], &[&cranker]).await;
+    assert_program_error(result, anchor_lang::error::ErrorCode::AccountNotInitialized.into());
+    let result = vault.send(&[payment.cancel_escrow_instruction([1; 32])], &[&payer]).await;
+    assert_program_error(result, anchor_lang::error::ErrorCode::AccountNotInitialized.into());
+}
+
+#[tokio::test]
+async fn test_crank_and_capture_settle_an_escrow_only_once() {
+    let mut vault = Vault::start().await;
+    let payment = vault.payment().await;
+    let escrow = payment.escrow_instruction(1_000_000, [1; 32], EXPIRES_AT);
+    vault.send(&[escrow, payment.escrow_instruction(2_000_000, [2; 32], EXPIRES_AT)], &[]).await.unwrap();
+    let cranker = Keypair::new();
+
+    // The merchant captures the first just in time; the crank finds its token
+    // account closed
+    vault.set_time(EXPIRES_AT - 1).await;
+    vault.send(&[payment.capture_instruction([1; 32])], &[&payment.merchant]).await.unwrap();
+    vault.set_time(EXPIRES_AT).await;
+    let result = vault.send(&[payment.crank_escrow_instruction([1; 32], cranker.pubkey())], &[&cranker]).await;
+    assert_program_error(result, anchor_lang::error::ErrorCode::AccountNotInitialized.into());
+    assert_eq!(vault.balance(&payment.merchant_token_account).await, 995_000);
+
+    // The crank reaches the second first; the capture finds its token account
+    // closed
+    vault.send(&[payment.crank_escrow_instruction([2; 32], cranker.pubkey())], &[&cranker]).await.unwrap();
+    let result = vault.send(&[payment.capture_instruction([2; 32])], &[&payment.merchant]).await;
+    assert_program_error(result, anchor_lang::error::ErrorCode::AccountNotInitialized.into());
+    assert_eq!(vault.balance(&payment.payer_token_account).await, 9_000_000);
+    assert_eq!(vault.balance(&payment.merchant_token_account).await, 995_000);
+}
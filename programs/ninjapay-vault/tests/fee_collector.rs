@@ -0,0 +1,256 @@
+mod common;
+
+use anchor_lang::{AccountDeserialize, InstructionData, ToAccountMetas};
+use common::{
+    assert_program_error, assert_vault_error, payment_record, set_fee_collectors_instruction, sol_payment_instruction,
+    sol_payment_instruction_to, vault_config, Payment, Vault, START_TIME,
+};
+use ninjapay_vault::{FeeCollectorShare, PaymentRecord, VaultConfig, VaultError};
+use solana_sdk::{
+    instruction::Instruction,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    system_instruction,
+};
+
+fn update_fee_collector(authority: Pubkey, new_fee_collector: Pubkey) -> Instruction {
+    Instruction {
+        program_id: ninjapay_vault::ID,
+        accounts: ninjapay_vault::accounts::UpdateFeeCollector {
+            vault_config: vault_config(),
+            authority,
+            new_fee_collector,
+        }
+        .to_account_metas(None),
+        data: ninjapay_vault::instruction::UpdateFeeCollector {}.data(),
+    }
+}
+
+#[tokio::test]
+async fn test_fees_follow_the_new_collector() {
+    let mut vault = Vault::start().await;
+    let mut payment = vault.payment().await;
+    let authority = vault.payer();
+    let new_collector = Keypair::new();
+
+    vault
+        .send(&[update_fee_collector(authority, new_collector.pubkey())], &[])
+        .await
+        .unwrap();
+
+    // The old collector's account no longer qualifies
+    let result = vault.send(&[payment.instruction(1_000_000, [1; 32])], &[]).await;
+    assert_vault_error(result, VaultError::InvalidFeeAccount);
+
+    let mint = vault.mint;
+    payment.fee_token_account = vault.create_token_account(&mint, &new_collector.pubkey()).await;
+    vault.send(&[payment.instruction(1_000_000, [1; 32])], &[]).await.unwrap();
+    assert_eq!(vault.balance(&payment.fee_token_account).await, 5_000);
+}
+
+#[tokio::test]
+async fn test_sol_payment_fees_follow_the_new_collector() {
+    let mut vault = Vault::start().await;
+    vault.ensure_native_mint().await;
+    let authority = vault.payer();
+    let (old_collector, new_collector, merchant) = (vault.fee_collector.pubkey(), Keypair::new(), Keypair::new());
+    let fund: Vec<_> = [old_collector, new_collector.pubkey(), merchant.pubkey()]
+        .iter()
+        .map(|wallet| system_instruction::transfer(&authority, wallet, 1_000_000_000))
+        .collect();
+    vault.send(&fund, &[]).await.unwrap();
+    vault
+        .send(&[update_fee_collector(authority, new_collector.pubkey())], &[])
+        .await
+        .unwrap();
+
+    let pay_old = sol_payment_instruction(authority, merchant.pubkey(), old_collector, 1_000_000_000, [1; 32]);
+    let result = vault.send(&[pay_old], &[]).await;
+    assert_vault_error(result, VaultError::InvalidFeeAccount);
+
+    let pay_new = sol_payment_instruction(authority, merchant.pubkey(), new_collector.pubkey(), 1_000_000_000, [1; 32]);
+    vault.send(&[pay_new], &[]).await.unwrap();
+    assert_eq!(vault.lamports(&new_collector.pubkey()).await, 1_005_000_000);
+}
+
+#[tokio::test]
+async fn test_only_authority_can_update_fee_collector() {
+    let mut vault = Vault::start().await;
+    let intruder = Keypair::new();
+
+    let result = vault
+        .send(&[update_fee_collector(intruder.pubkey(), intruder.pubkey())], &[&intruder])
+        .await;
+
+    assert_program_error(result, anchor_lang::error::ErrorCode::ConstraintHasOne.into());
+}
+
+#[tokio::test]
+async fn test_fees_are_divided_among_collectors() {
+    let mut vault = Vault::start().await;
+    let mut payment = vault.payment().await;
+    let (authority, mint, operator) = (vault.payer(), vault.mint, vault.fee_collector.pubkey());
+    let treasury = Keypair::new().pubkey();
+    payment.fee_token_account_1 = Some(vault.create_token_account(&mint, &treasury).await);
+    let divide = set_fee_collectors_instruction(authority, &[(operator, 7_000), (treasury, 3_000)]);
+    vault.send(&[divide], &[]).await.unwrap();
+
+    let account = vault.context.banks_client.get_account(vault_config()).await.unwrap().unwrap();
+    let config = VaultConfig::try_deserialize(&mut account.data.as_slice()).unwrap();
+    assert_eq!(config.fee_collector, operator);
+    assert_eq!(config.fee_collectors[1], FeeCollectorShare { collector: treasury, basis_points: 3_000 });
+    assert_eq!(config.fee_collectors[2], FeeCollectorShare::default());
+
+    vault.send(&[payment.instruction(1_000_000, [1; 32])], &[]).await.unwrap();
+    let treasury_account = payment.fee_token_account_1.unwrap();
+    assert_eq!(vault.balance(&payment.fee_token_account).await, 3_500);
+    assert_eq!(vault.balance(&treasury_account).await, 1_500);
+
+    // A fee of 5_001: the first collector takes the rounding dust
+    vault.send(&[payment.instruction(1_000_200, [2; 32])], &[]).await.unwrap();
+    assert_eq!(vault.balance(&payment.fee_token_account).await, 3_500 + 3_501);
+    assert_eq!(vault.balance(&treasury_account).await, 1_500 + 1_500);
+    assert_eq!(vault.balance(&payment.merchant_token_account).await, 995_000 + 995_199);
+}
+
+async fn payment_record_at(vault: &mut Vault, payer: &Pubkey, payment_id: [u8; 32]) -> PaymentRecord {
+    let account = vault.context.banks_client.get_account(payment_record(payer, &payment_id)).await.unwrap().unwrap();
+    PaymentRecord::try_deserialize(&mut account.data.as_slice()).unwrap()
+}
+
+/// A vault whose fees go 70/30 to its collector and a treasury, with a
+/// payment passing the treasury's token account
+async fn divided_fees(vault: &mut Vault, treasury: &Keypair) -> Payment {
+    let mut payment = vault.payment().await;
+    let (authority, mint, operator) = (vault.payer(), vault.mint, vault.fee_collector.pubkey());
+    payment.fee_token_account_1 = Some(vault.create_token_account(&mint, &treasury.pubkey()).await);
+    let divide = set_fee_collectors_instruction(authority, &[(operator, 7_000), (treasury.pubkey(), 3_000)]);
+    vault.send(&[divide], &[]).await.unwrap();
+    payment
+}
+
+#[tokio::test]
+async fn test_split_batch_and_escrow_fees_are_divided_among_collectors() {
+    let mut vault = Vault::start().await;
+    let treasury = Keypair::new();
+    let payment = divided_fees(&mut vault, &treasury).await;
+    let treasury_account = payment.fee_token_account_1.unwrap();
+
+    let splits = [(9_950, payment.merchant_token_account)];
+    vault.send(&[payment.split_instruction(1_000_000, [1; 32], &splits)], &[]).await.unwrap();
+    assert_eq!(vault.balance(&payment.fee_token_account).await, 3_500);
+    assert_eq!(vault.balance(&treasury_account).await, 1_500);
+
+    let batch = payment.batch_instruction(&[(payment.merchant_token_account, 1_000_000, [2; 32])]);
+    vault.send(&[batch], &[]).await.unwrap();
+    assert_eq!(vault.balance(&payment.fee_token_account).await, 2 * 3_500);
+    assert_eq!(vault.balance(&treasury_account).await, 2 * 1_500);
+    let record = payment_record_at(&mut vault, &payment.payer, [2; 32]).await;
+    assert_eq!(record.fee_collectors[..2], [vault.fee_collector.pubkey(), treasury.pubkey()]);
+    assert_eq!(record.fee_shares, [3_500, 1_500, 0]);
+
+    vault.send(&[payment.escrow_instruction(1_000_000, [3; 32], START_TIME + 3_600)], &[]).await.unwrap();
+    vault.send(&[payment.capture_instruction([3; 32])], &[&payment.merchant]).await.unwrap();
+    assert_eq!(vault.balance(&payment.fee_token_account).await, 3 * 3_500);
+    assert_eq!(vault.balance(&treasury_account).await, 3 * 1_500);
+    assert_eq!(vault.balance(&payment.merchant_token_account).await, 3 * 995_000);
+}
+
+#[tokio::test]
+async fn test_sol_payment_fees_are_divided_among_collectors() {
+    let mut vault = Vault::start().await;
+    vault.ensure_native_mint().await;
+    let authority = vault.payer();
+    let (operator, treasury, merchant) = (vault.fee_collector.pubkey(), Keypair::new().pubkey(), Keypair::new());
+    let fund: Vec<_> = [operator, treasury, merchant.pubkey()]
+        .iter()
+        .map(|wallet| system_instruction::transfer(&authority, wallet, 1_000_000_000))
+        .collect();
+    vault.send(&fund, &[]).await.unwrap();
+    let divide = set_fee_collectors_instruction(authority, &[(operator, 7_000), (treasury, 3_000)]);
+    vault.send(&[divide], &[]).await.unwrap();
+
+    // Without the treasury's wallet, its share has nowhere to go
+    let pay = sol_payment_instruction(authority, merchant.pubkey(), operator, 1_000_000_000, [1; 32]);
+    let result = vault.send(&[pay], &[]).await;
+    assert_vault_error(result, VaultError::MissingFeeAccount);
+
+    let collectors = [operator, treasury];
+    let pay = sol_payment_instruction_to(authority, merchant.pubkey(), None, &collectors, 1_000_000_000, [1; 32]);
+    vault.send(&[pay], &[]).await.unwrap();
+    assert_eq!(vault.lamports(&operator).await, 1_003_500_000);
+    assert_eq!(vault.lamports(&treasury).await, 1_001_500_000);
+}
+
+#[tokio::test]
+async fn test_refund_takes_each_share_back_from_the_collector_it_was_paid_to() {
+    let mut vault = Vault::start().await;
+    let treasury = Keypair::new();
+    let payment = divided_fees(&mut vault, &treasury).await;
+    let (authority, fee_collector) = (vault.payer(), vault.fee_collector.insecure_clone());
+    let treasury_account = payment.fee_token_account_1.unwrap();
+    vault.send(&[payment.instruction(1_000_000, [1; 32])], &[]).await.unwrap();
+
+    // The division changing since doesn't move the refund onto the collector
+    let undivide = set_fee_collectors_instruction(authority, &[(fee_collector.pubkey(), 10_000)]);
+    vault.send(&[undivide], &[]).await.unwrap();
+
+    let operator_only = [(fee_collector.pubkey(), payment.fee_token_account)];
+    let refund = payment.refund_instruction_from([1; 32], 995_000, &operator_only);
+    let result = vault.send(&[refund], &[&payment.merchant, &fee_collector]).await;
+    assert_vault_error(result, VaultError::Unauthorized);
+
+    let both = [operator_only[0], (treasury.pubkey(), treasury_account)];
+    let refund = payment.refund_instruction_from([1; 32], 995_000, &both);
+    vault.send(&[refund], &[&payment.merchant, &fee_collector, &treasury]).await.unwrap();
+    assert_eq!(vault.balance(&payment.payer_token_account).await, 10_000_000);
+    assert_eq!(vault.balance(&payment.fee_token_account).await, 0);
+    assert_eq!(vault.balance(&treasury_account).await, 0);
+}
+
+#[tokio::test]
+async fn test_a_collector_with_a_share_must_be_paid() {
+    let mut vault = Vault::start().await;
+    let mut payment = vault.payment().await;
+    let (authority, operator) = (vault.payer(), vault.fee_collector.pubkey());
+    let treasury = Keypair::new().pubkey();
+    let halve = set_fee_collectors_instruction(authority, &[(operator, 5_000), (treasury, 5_000)]);
+    vault.send(&[halve], &[]).await.unwrap();
+
+    let result = vault.send(&[payment.instruction(1_000_000, [1; 32])], &[]).await;
+    assert_vault_error(result, VaultError::MissingFeeAccount);
+
+    // Nor can the first collector's account stand in for the second's
+    payment.fee_token_account_1 = Some(payment.fee_token_account);
+    let result = vault.send(&[payment.instruction(1_000_000, [2; 32])], &[]).await;
+    assert_vault_error(result, VaultError::InvalidFeeAccount);
+
+    // Back to one collector, the account isn't needed
+    vault.send(&[set_fee_collectors_instruction(authority, &[(operator, 10_000)])], &[]).await.unwrap();
+    payment.fee_token_account_1 = None;
+    vault.send(&[payment.instruction(1_000_000, [3; 32])], &[]).await.unwrap();
+    assert_eq!(vault.balance(&payment.fee_token_account).await, 5_000);
+}
+
+#[tokio::test]
+async fn test_invalid_fee_collectors_are_rejected() {
+    let mut vault = Vault::start().await;
+    let authority = vault.payer();
+    let [a, b, c, d] = [(); 4].map(|_| Keypair::new().pubkey());
+
+    for fee_collectors in [
+        &[][..],
+        &[(a, 9_999)],
+        &[(a, 5_000), (b, 5_001)],
+        &[(a, 10_000), (b, 0)],
+        &[(a, 5_000), (a, 5_000)],
+        &[(a, 2_500), (b, 2_500), (c, 2_500), (d, 2_500)],
+    ] {
+        let result = vault.send(&[set_fee_collectors_instruction(authority, fee_collectors)], &[]).await;
+        assert_vault_error(result, VaultError::InvalidFeeCollectors);
+    }
+
+    let intruder = Keypair::new();
+    let result = vault.send(&[set_fee_collectors_instruction(intruder.pubkey(), &[(a, 10_000)])], &[&intruder]).await;
+    assert_program_error(result, anchor_lang::error::ErrorCode::ConstraintHasOne.into());
+}
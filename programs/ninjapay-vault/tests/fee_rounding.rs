@@ -0,0 +1,78 @@
+mod common;
+
+use anchor_lang::{AccountDeserialize, InstructionData, ToAccountMetas};
+use common::{assert_program_error, payment_record, vault_config, Vault};
+use ninjapay_vault::{split_payment_rounded, PaymentRecord, VaultConfig, VaultError};
+use solana_sdk::{
+    instruction::Instruction,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+};
+
+fn set_fee_rounding(authority: Pubkey, round_fee_up: bool) -> Instruction {
+    Instruction {
+        program_id: ninjapay_vault::ID,
+        accounts: ninjapay_vault::accounts::SetFeeRounding {
+            vault_config: vault_config(),
+            authority,
+        }
+        .to_account_metas(None),
+        data: ninjapay_vault::instruction::SetFeeRounding { round_fee_up }.data(),
+    }
+}
+
+#[tokio::test]
+async fn test_rounding_up_charges_the_smallest_payments() {
+    let mut vault = Vault::start().await;
+    let payment = vault.payment().await;
+    let authority = vault.payer();
+    vault.send(&[set_fee_rounding(authority, true)], &[]).await.unwrap();
+    let account = vault.context.banks_client.get_account(vault_config()).await.unwrap().unwrap();
+    assert!(VaultConfig::try_deserialize(&mut account.data.as_slice()).unwrap().round_fee_up);
+
+    // 50 bps: 0.045 and 50.005 round up to 1 and 51
+    for (id, amount) in [(1, 9), (2, 10_001)] {
+        vault.send(&[payment.instruction(amount, [id; 32])], &[]).await.unwrap();
+    }
+    assert_eq!(vault.balance(&payment.fee_token_account).await, 1 + 51);
+    assert_eq!(vault.balance(&payment.merchant_token_account).await, 8 + 9_950);
+    let account = vault.context.banks_client.get_account(payment_record(&payment.payer, &[1; 32])).await.unwrap();
+    assert_eq!(PaymentRecord::try_deserialize(&mut account.unwrap().data.as_slice()).unwrap().fee, 1);
+
+    // Back to rounding down
+    vault.send(&[set_fee_rounding(authority, false)], &[]).await.unwrap();
+    vault.send(&[payment.instruction(9, [3; 32])], &[]).await.unwrap();
+    assert_eq!(vault.balance(&payment.fee_token_account).await, 1 + 51);
+}
+
+#[tokio::test]
+async fn test_only_authority_can_set_fee_rounding() {
+    let mut vault = Vault::start().await;
+    let intruder = Keypair::new();
+
+    let result = vault.send(&[set_fee_rounding(intruder.pubkey(), true)], &[&intruder]).await;
+    assert_program_error(result, anchor_lang::error::ErrorCode::ConstraintHasOne.into());
+}
+
+#[test]
+fn test_fee_rounding_at_the_boundaries() {
+    // (fee_basis_points, [(amount, fee rounded down, fee rounded up)])
+    let cases: [(u16, [(u64, u64, u64); 4]); 4] = [
+        (1, [(1, 0, 1), (9_999, 0, 1), (10_000, 1, 1), (10_001, 1, 2)]),
+        (50, [(1, 0, 1), (9_999, 49, 50), (10_000, 50, 50), (10_001, 50, 51)]),
+        (1_000, [(1, 0, 1), (9_999, 999, 1_000), (10_000, 1_000, 1_000), (10_001, 1_000, 1_001)]),
+        (10_000, [(1, 1, 1), (9_999, 9_999, 9_999), (10_000, 10_000, 10_000), (10_001, 10_001, 10_001)]),
+    ];
+    for (fee_basis_points, amounts) in cases {
+        for (amount, down, up) in amounts {
+            assert_eq!(split_payment_rounded(amount, fee_basis_points, false).unwrap(), (down, amount - down));
+            assert_eq!(split_payment_rounded(amount, fee_basis_points, true).unwrap(), (up, amount - up));
+        }
+    }
+
+    // A zero fee stays zero, and none ever exceeds the amount
+    assert_eq!(split_payment_rounded(u64::MAX, 0, true).unwrap(), (0, u64::MAX));
+    assert_eq!(split_payment_rounded(u64::MAX, 10_000, true).unwrap(), (u64::MAX, 0));
+    let too_high = split_payment_rounded(1, 10_001, true).unwrap_err();
+    assert_eq!(too_high, VaultError::AmountTooSmall.into());
+}
@@ -0,0 +1,129 @@
+mod common;
+
+use anchor_lang::AccountDeserialize;
+use common::{
+    assert_program_error, assert_vault_error, clear_fee_schedule_instruction, fee_schedule, merchant_stats,
+    set_fee_schedule_instruction, set_merchant_volume_tiers_instruction, Vault,
+};
+use ninjapay_vault::{FeeSchedule, FeeThreshold, MerchantStats, VaultError};
+use solana_sdk::signature::{Keypair, Signer};
+
+#[tokio::test]
+async fn test_tiers_apply_once_the_vault_volume_reaches_them() {
+    let mut vault = Vault::start().await;
+    let mut payment = vault.payment().await;
+    let authority = vault.payer();
+    let tiers = [(1_500_000, 30), (2_500_000, 10)];
+    vault.send(&[set_fee_schedule_instruction(authority, &tiers)], &[]).await.unwrap();
+    payment.fee_schedule = Some(fee_schedule());
+
+    let account = vault.context.banks_client.get_account(fee_schedule()).await.unwrap().unwrap();
+    let schedule = FeeSchedule::try_deserialize(&mut account.data.as_slice()).unwrap();
+    assert_eq!(
+        schedule.tiers,
+        [
+            FeeThreshold { volume_threshold: 1_500_000, fee_basis_points: 30 },
+            FeeThreshold { volume_threshold: 2_500_000, fee_basis_points: 10 },
+        ]
+    );
+
+    // Volume before each payment: 0 and 1M pay the vault's 0.5%, 2M the
+    // first tier's 0.3% and 3M the second's 0.1%
+    let mut fees = Vec::new();
+    for id in 1..=4 {
+        let before = vault.balance(&payment.fee_token_account).await;
+        vault.send(&[payment.instruction(1_000_000, [id; 32])], &[]).await.unwrap();
+        fees.push(vault.balance(&payment.fee_token_account).await - before);
+    }
+    assert_eq!(fees, [5_000, 5_000, 3_000, 1_000]);
+    assert_eq!(vault.balance(&payment.merchant_token_account).await, 4_000_000 - 14_000);
+}
+
+#[tokio::test]
+async fn test_merchant_volume_tiers_follow_each_merchants_own_volume() {
+    let mut vault = Vault::start().await;
+    let mut payment = vault.payment().await;
+    let mut newcomer = vault.payment().await;
+    let authority = vault.payer();
+    vault.send(&[set_fee_schedule_instruction(authority, &[(1_500_000, 30)])], &[]).await.unwrap();
+    vault.send(&[set_merchant_volume_tiers_instruction(authority, true)], &[]).await.unwrap();
+    payment.fee_schedule = Some(fee_schedule());
+    newcomer.fee_schedule = Some(fee_schedule());
+
+    // The merchant's volume before each payment: 0 and 1M pay the vault's
+    // 0.5%; at 2M it has crossed the tier and pays 0.3%
+    let mut fees = Vec::new();
+    for id in 1..=3 {
+        let before = vault.balance(&payment.fee_token_account).await;
+        vault.send(&[payment.instruction(1_000_000, [id; 32])], &[]).await.unwrap();
+        fees.push(vault.balance(&payment.fee_token_account).await - before);
+    }
+    assert_eq!(fees, [5_000, 5_000, 3_000]);
+    let account = vault.context.banks_client.get_account(merchant_stats(&payment.merchant.pubkey())).await.unwrap();
+    let stats = MerchantStats::try_deserialize(&mut account.unwrap().data.as_slice()).unwrap();
+    assert_eq!((stats.merchant, stats.total_volume, stats.payment_count), (payment.merchant.pubkey(), 3_000_000, 3));
+
+    // The vault has seen 3M, but this merchant nothing yet
+    vault.send(&[newcomer.instruction(1_000_000, [4; 32])], &[]).await.unwrap();
+    assert_eq!(vault.balance(&newcomer.merchant_token_account).await, 995_000);
+
+    // Back on the vault's volume, the newcomer gets the tier too
+    vault.send(&[set_merchant_volume_tiers_instruction(authority, false)], &[]).await.unwrap();
+    vault.send(&[newcomer.instruction(1_000_000, [5; 32])], &[]).await.unwrap();
+    assert_eq!(vault.balance(&newcomer.merchant_token_account).await, 995_000 + 997_000);
+}
+
+#[tokio::test]
+async fn test_payments_must_pass_the_schedule_until_it_is_cleared() {
+    let mut vault = Vault::start().await;
+    let payment = vault.payment().await;
+    let authority = vault.payer();
+    vault.send(&[set_fee_schedule_instruction(authority, &[(0, 10)])], &[]).await.unwrap();
+
+    // Leaving it out would dodge the tiers
+    let result = vault.send(&[payment.instruction(1_000_000, [1; 32])], &[]).await;
+    assert_vault_error(result, VaultError::FeeScheduleRequired);
+
+    let authority_lamports = vault.lamports(&authority).await;
+    vault.send(&[clear_fee_schedule_instruction(authority)], &[]).await.unwrap();
+    assert!(vault.context.banks_client.get_account(fee_schedule()).await.unwrap().is_none());
+    // The rent came back, less the transaction fee
+    assert!(vault.lamports(&authority).await > authority_lamports);
+
+    vault.send(&[payment.instruction(1_000_000, [2; 32])], &[]).await.unwrap();
+    assert_eq!(vault.balance(&payment.fee_token_account).await, 5_000);
+}
+
+#[tokio::test]
+async fn test_invalid_schedules_are_rejected() {
+    let mut vault = Vault::start().await;
+    let authority = vault.payer();
+
+    let six_tiers: Vec<(u64, u16)> = (0..6).map(|tier| (tier * 1_000, 10)).collect();
+    for tiers in [&[][..], &[(2_000, 20), (1_000, 10)], &[(1_000, 20), (1_000, 10)], &six_tiers] {
+        let result = vault.send(&[set_fee_schedule_instruction(authority, tiers)], &[]).await;
+        assert_vault_error(result, VaultError::InvalidFeeSchedule);
+    }
+
+    let result = vault.send(&[set_fee_schedule_instruction(authority, &[(0, 10), (1_000, 1001)])], &[]).await;
+    assert_vault_error(result, VaultError::FeeTooHigh);
+}
+
+#[tokio::test]
+async fn test_only_the_authority_manages_the_schedule() {
+    let mut vault = Vault::start().await;
+    let intruder = Keypair::new();
+
+    // Created by the authority first, so the intruder's attempt gets as far
+    // as the authority check rather than failing to pay for the account
+    let authority = vault.payer();
+    vault.send(&[set_fee_schedule_instruction(authority, &[(0, 10)])], &[]).await.unwrap();
+    for instruction in [
+        set_fee_schedule_instruction(intruder.pubkey(), &[(0, 0)]),
+        clear_fee_schedule_instruction(intruder.pubkey()),
+        set_merchant_volume_tiers_instruction(intruder.pubkey(), true),
+    ] {
+        let result = vault.send(&[instruction], &[&intruder]).await;
+        assert_program_error(result, anchor_lang::error::ErrorCode::ConstraintHasOne.into());
+    }
+}
@@ -0,0 +1,87 @@
+mod common;
+
+use anchor_lang::{AccountDeserialize, Space};
+use common::{
+    assert_program_error, assert_vault_error, migrate_vault_config_instruction, vault_config, Vault, FEE_BASIS_POINTS,
+};
+use ninjapay_vault::{VaultConfig, VaultError};
+use solana_sdk::signature::{Keypair, Signer};
+
+/// VaultConfig as first deployed, before payment bounds and the fee total
+const ORIGINAL_LEN: usize = 8 + 32 + 32 + 2 + 8 + 8 + 1 + 1;
+
+async fn config(vault: &mut Vault) -> VaultConfig {
+    let account = vault.context.banks_client.get_account(vault_config()).await.unwrap().unwrap();
+    VaultConfig::try_deserialize(&mut account.data.as_slice()).unwrap()
+}
+
+#[tokio::test]
+async fn test_fees_are_totalled_across_payments() {
+    let mut vault = Vault::start().await;
+    let payment = vault.payment().await;
+    assert_eq!(config(&mut vault).await.total_fees_collected, 0);
+
+    vault.send(&[payment.instruction(1_000_000, [1; 32])], &[]).await.unwrap();
+    vault.send(&[payment.instruction(2_000_000, [2; 32])], &[]).await.unwrap();
+    // 50 bps of each
+    assert_eq!(config(&mut vault).await.total_fees_collected, 15_000);
+
+    let recipient = payment.merchant_token_account;
+    let split = payment.split_instruction(1_000_000, [3; 32], &[(10_000 - FEE_BASIS_POINTS, recipient)]);
+    vault.send(&[split], &[]).await.unwrap();
+
+    assert_eq!(config(&mut vault).await.total_fees_collected, 20_000);
+    assert_eq!(vault.balance(&vault.fee_token_account.clone()).await, 20_000);
+}
+
+#[tokio::test]
+async fn test_fee_total_overflow_fails_the_payment() {
+    let mut vault = Vault::start().await;
+    let payment = vault.payment().await;
+    vault.edit_vault_config(|config| config.total_fees_collected = u64::MAX - 4_999).await;
+
+    let result = vault.send(&[payment.instruction(1_000_000, [4; 32])], &[]).await;
+
+    assert_vault_error(result, VaultError::MathOverflow);
+    assert_eq!(vault.balance(&payment.payer_token_account).await, 10_000_000);
+}
+
+#[tokio::test]
+async fn test_original_vault_config_is_migrated_in_place() {
+    let mut vault = Vault::start().await;
+    let payment = vault.payment().await;
+    let authority = vault.payer();
+    let rent = vault.context.banks_client.get_rent().await.unwrap();
+
+    let mut account = vault.context.banks_client.get_account(vault_config()).await.unwrap().unwrap();
+    account.data.truncate(ORIGINAL_LEN);
+    account.lamports = rent.minimum_balance(ORIGINAL_LEN);
+    vault.context.set_account(&vault_config(), &account.into());
+
+    // Nothing that reads the config works until it is migrated
+    let result = vault.send(&[payment.instruction(1_000_000, [5; 32])], &[]).await;
+    assert_program_error(result, anchor_lang::error::ErrorCode::AccountDidNotDeserialize.into());
+
+    let stranger = Keypair::new();
+    let result = vault.send(&[migrate_vault_config_instruction(stranger.pubkey())], &[&stranger]).await;
+    assert_vault_error(result, VaultError::Unauthorized);
+
+    vault.send(&[migrate_vault_config_instruction(authority)], &[]).await.unwrap();
+
+    let new_len = 8 + VaultConfig::INIT_SPACE;
+    let account = vault.context.banks_client.get_account(vault_config()).await.unwrap().unwrap();
+    assert_eq!((account.data.len(), account.lamports), (new_len, rent.minimum_balance(new_len)));
+    let migrated = config(&mut vault).await;
+    assert_eq!((migrated.authority, migrated.fee_basis_points), (authority, FEE_BASIS_POINTS));
+    assert_eq!((migrated.min_payment, migrated.max_payment, migrated.total_fees_collected), (0, 0, 0));
+
+    // Fees count from the migration on
+    vault.send(&[payment.instruction(1_000_000, [5; 32])], &[]).await.unwrap();
+    assert_eq!(config(&mut vault).await.total_fees_collected, 5_000);
+
+    // Migrating an up-to-date account changes nothing
+    vault.send(&[migrate_vault_config_instruction(authority)], &[]).await.unwrap();
+    let account = vault.context.banks_client.get_account(vault_config()).await.unwrap().unwrap();
+    assert_eq!(account.data.len(), new_len);
+    assert_eq!(config(&mut vault).await.total_fees_collected, 5_000);
+}
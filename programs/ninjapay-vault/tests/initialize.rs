@@ -0,0 +1,33 @@
+mod common;
+
+use anchor_lang::AccountDeserialize;
+use common::{assert_vault_error, initialize_instruction, send, start_uninitialized, vault_config, FEE_BASIS_POINTS};
+use ninjapay_vault::{FeeCollectorShare, VaultConfig, VaultError};
+use solana_sdk::{
+    signature::{Keypair, Signer},
+    system_instruction,
+};
+
+#[tokio::test]
+async fn test_only_the_upgrade_authority_can_initialize() {
+    let mut context = start_uninitialized().await;
+    let fee_collector = Keypair::new().pubkey();
+
+    // A funded third party racing the deployer
+    let stranger = Keypair::new();
+    let fund = system_instruction::transfer(&context.payer.pubkey(), &stranger.pubkey(), 1_000_000_000);
+    send(&mut context, &[fund], &[]).await.unwrap();
+    let result = send(&mut context, &[initialize_instruction(stranger.pubkey(), fee_collector)], &[&stranger]).await;
+    assert_vault_error(result, VaultError::Unauthorized);
+    assert!(context.banks_client.get_account(vault_config()).await.unwrap().is_none());
+
+    let authority = context.payer.pubkey();
+    send(&mut context, &[initialize_instruction(authority, fee_collector)], &[]).await.unwrap();
+    let account = context.banks_client.get_account(vault_config()).await.unwrap().unwrap();
+    let config = VaultConfig::try_deserialize(&mut account.data.as_slice()).unwrap();
+    assert_eq!((config.authority, config.fee_collector), (authority, fee_collector));
+    assert_eq!((config.fee_basis_points, config.total_volume, config.total_payments), (FEE_BASIS_POINTS, 0, 0));
+    assert!(!config.paused && !config.require_mint_whitelist);
+    let primary = FeeCollectorShare { collector: fee_collector, basis_points: 10_000 };
+    assert_eq!(config.fee_collectors, [primary, FeeCollectorShare::default(), FeeCollectorShare::default()]);
+}
@@ -0,0 +1,300 @@
+mod common;
+
+use anchor_lang::{AccountDeserialize, InstructionData, ToAccountMetas};
+use common::{
+    assert_program_error, assert_vault_error, fee_schedule, merchant_config, payment_record,
+    set_fee_schedule_instruction, vault_config, Vault, START_TIME,
+};
+use ninjapay_vault::{MerchantConfig, PaymentRecord, VaultError, PAYMENT_BATCH_COMPUTE_UNITS};
+use solana_sdk::{
+    compute_budget::ComputeBudgetInstruction,
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    system_program,
+};
+
+fn set_override(authority: Pubkey, merchant: Pubkey, fee_override_basis_points: u16) -> Instruction {
+    Instruction {
+        program_id: ninjapay_vault::ID,
+        accounts: ninjapay_vault::accounts::SetMerchantFeeOverride {
+            vault_config: vault_config(),
+            merchant_config: merchant_config(&merchant),
+            merchant,
+            authority,
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None),
+        data: ninjapay_vault::instruction::SetMerchantFeeOverride {
+            fee_override_basis_points,
+        }
+        .data(),
+    }
+}
+
+fn set_fee(authority: Pubkey, merchant: Pubkey, fee_override_bps: Option<u16>) -> Instruction {
+    Instruction {
+        program_id: ninjapay_vault::ID,
+        accounts: ninjapay_vault::accounts::SetMerchantFeeOverride {
+            vault_config: vault_config(),
+            merchant_config: merchant_config(&merchant),
+            merchant,
+            authority,
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None),
+        data: ninjapay_vault::instruction::SetMerchantFee { fee_override_bps }.data(),
+    }
+}
+
+fn remove_override(authority: Pubkey, merchant: Pubkey) -> Instruction {
+    Instruction {
+        program_id: ninjapay_vault::ID,
+        accounts: ninjapay_vault::accounts::RemoveMerchantFeeOverride {
+            vault_config: vault_config(),
+            merchant_config: merchant_config(&merchant),
+            authority,
+        }
+        .to_account_metas(None),
+        data: ninjapay_vault::instruction::RemoveMerchantFeeOverride {}.data(),
+    }
+}
+
+fn set_exempt(authority: Pubkey, merchant: Pubkey, fee_exempt: bool) -> Instruction {
+    Instruction {
+        program_id: ninjapay_vault::ID,
+        accounts: ninjapay_vault::accounts::SetMerchantFeeExempt {
+            vault_config: vault_config(),
+            merchant_config: merchant_config(&merchant),
+            merchant,
+            authority,
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None),
+        data: ninjapay_vault::instruction::SetMerchantFeeExempt { fee_exempt }.data(),
+    }
+}
+
+/// process_payment with `config` as the first remaining account
+fn with_merchant_config(mut instruction: Instruction, config: Pubkey) -> Instruction {
+    instruction.accounts.push(AccountMeta::new_readonly(config, false));
+    instruction
+}
+
+#[tokio::test]
+async fn test_active_override_replaces_the_vault_fee() {
+    let mut vault = Vault::start().await;
+    let payment = vault.payment().await;
+    let (authority, merchant) = (vault.payer(), payment.merchant.pubkey());
+    vault.send(&[set_override(authority, merchant, 20)], &[]).await.unwrap();
+
+    let account = vault.context.banks_client.get_account(merchant_config(&merchant)).await.unwrap().unwrap();
+    assert_eq!(account.data.len(), 8 + <MerchantConfig as anchor_lang::Space>::INIT_SPACE);
+    let config = MerchantConfig::try_deserialize(&mut account.data.as_slice()).unwrap();
+    assert_eq!((config.merchant, config.fee_override_basis_points, config.is_active), (merchant, 20, true));
+
+    // 0.2% instead of the vault's 0.5%
+    let pay = with_merchant_config(payment.instruction(1_000_000, [1; 32]), merchant_config(&merchant));
+    vault.send(&[pay], &[]).await.unwrap();
+    assert_eq!(vault.balance(&payment.fee_token_account).await, 2_000);
+    assert_eq!(vault.balance(&payment.merchant_token_account).await, 998_000);
+
+    // Without the account the vault-wide fee applies
+    vault.send(&[payment.instruction(1_000_000, [2; 32])], &[]).await.unwrap();
+    assert_eq!(vault.balance(&payment.fee_token_account).await, 7_000);
+}
+
+#[tokio::test]
+async fn test_active_override_takes_precedence_over_the_fee_schedule() {
+    let mut vault = Vault::start().await;
+    let mut payment = vault.payment().await;
+    let (authority, merchant) = (vault.payer(), payment.merchant.pubkey());
+    vault.send(&[set_override(authority, merchant, 20)], &[]).await.unwrap();
+    vault.send(&[set_fee_schedule_instruction(authority, &[(0, 10)])], &[]).await.unwrap();
+    payment.fee_schedule = Some(fee_schedule());
+
+    let pay = with_merchant_config(payment.instruction(1_000_000, [1; 32]), merchant_config(&merchant));
+    vault.send(&[pay], &[]).await.unwrap();
+    assert_eq!(vault.balance(&payment.fee_token_account).await, 2_000);
+
+    // Without the merchant config the tier applies
+    vault.send(&[payment.instruction(1_000_000, [2; 32])], &[]).await.unwrap();
+    assert_eq!(vault.balance(&payment.fee_token_account).await, 3_000);
+}
+
+#[tokio::test]
+async fn test_removed_override_falls_back_to_the_vault_fee() {
+    let mut vault = Vault::start().await;
+    let payment = vault.payment().await;
+    let (authority, merchant) = (vault.payer(), payment.merchant.pubkey());
+    vault.send(&[set_override(authority, merchant, 0)], &[]).await.unwrap();
+    vault.send(&[remove_override(authority, merchant)], &[]).await.unwrap();
+
+    let pay = with_merchant_config(payment.instruction(1_000_000, [1; 32]), merchant_config(&merchant));
+    vault.send(&[pay], &[]).await.unwrap();
+    assert_eq!(vault.balance(&payment.fee_token_account).await, 5_000);
+
+    // Setting it again reuses the account
+    vault.send(&[set_override(authority, merchant, 0)], &[]).await.unwrap();
+    let pay = with_merchant_config(payment.instruction(1_000_000, [2; 32]), merchant_config(&merchant));
+    vault.send(&[pay], &[]).await.unwrap();
+    assert_eq!(vault.balance(&payment.fee_token_account).await, 5_000);
+}
+
+#[tokio::test]
+async fn test_set_merchant_fee_sets_and_clears_the_override() {
+    let mut vault = Vault::start().await;
+    let payment = vault.payment().await;
+    let (authority, merchant) = (vault.payer(), payment.merchant.pubkey());
+
+    let result = vault.send(&[set_fee(authority, merchant, Some(1001))], &[]).await;
+    assert_vault_error(result, VaultError::FeeTooHigh);
+
+    // 0.3% instead of the vault's 0.5%
+    vault.send(&[set_fee(authority, merchant, Some(30))], &[]).await.unwrap();
+    let stranger = Keypair::new();
+    let result = vault.send(&[set_fee(stranger.pubkey(), merchant, None)], &[&stranger]).await;
+    assert_program_error(result, anchor_lang::error::ErrorCode::ConstraintHasOne.into());
+    let pay = with_merchant_config(payment.instruction(1_000_000, [1; 32]), merchant_config(&merchant));
+    vault.send(&[pay], &[]).await.unwrap();
+    assert_eq!(vault.balance(&payment.fee_token_account).await, 3_000);
+
+    vault.send(&[set_fee(authority, merchant, None)], &[]).await.unwrap();
+    let pay = with_merchant_config(payment.instruction(1_000_000, [2; 32]), merchant_config(&merchant));
+    vault.send(&[pay], &[]).await.unwrap();
+    assert_eq!(vault.balance(&payment.fee_token_account).await, 8_000);
+    let account = vault.context.banks_client.get_account(merchant_config(&merchant)).await.unwrap().unwrap();
+    let config = MerchantConfig::try_deserialize(&mut account.data.as_slice()).unwrap();
+    assert_eq!((config.merchant, config.is_active), (merchant, false));
+}
+
+#[tokio::test]
+async fn test_exempt_merchant_is_paid_in_full() {
+    let mut vault = Vault::start().await;
+    let payment = vault.payment().await;
+    let (authority, merchant) = (vault.payer(), payment.merchant.pubkey());
+    vault.send(&[set_override(authority, merchant, 20)], &[]).await.unwrap();
+    vault.send(&[set_exempt(authority, merchant, true)], &[]).await.unwrap();
+
+    let pay = with_merchant_config(payment.instruction(1_000_000, [1; 32]), merchant_config(&merchant));
+    vault.send(&[pay], &[]).await.unwrap();
+    assert_eq!(vault.balance(&payment.fee_token_account).await, 0);
+    assert_eq!(vault.balance(&payment.merchant_token_account).await, 1_000_000);
+    let account = vault.context.banks_client.get_account(payment_record(&payment.payer, &[1; 32])).await.unwrap();
+    assert_eq!(PaymentRecord::try_deserialize(&mut account.unwrap().data.as_slice()).unwrap().fee, 0);
+
+    // Lifting the exemption brings back the override
+    vault.send(&[set_exempt(authority, merchant, false)], &[]).await.unwrap();
+    let pay = with_merchant_config(payment.instruction(1_000_000, [2; 32]), merchant_config(&merchant));
+    vault.send(&[pay], &[]).await.unwrap();
+    assert_eq!(vault.balance(&payment.fee_token_account).await, 2_000);
+    assert_eq!(vault.balance(&payment.merchant_token_account).await, 1_998_000);
+}
+
+#[tokio::test]
+async fn test_split_payment_charges_the_merchants_fee() {
+    let mut vault = Vault::start().await;
+    let mut payment = vault.payment().await;
+    let (authority, merchant, mint) = (vault.payer(), payment.merchant.pubkey(), vault.mint);
+    let recipient = vault.create_token_account(&mint, &Keypair::new().pubkey()).await;
+    vault.send(&[set_override(authority, merchant, 20)], &[]).await.unwrap();
+    payment.merchant_config = Some(merchant_config(&merchant));
+
+    // The recipients share what the 0.2% override leaves, not the vault's 0.5%
+    let splits = [(4_950, payment.merchant_token_account), (5_000, recipient)];
+    let result = vault.send(&[payment.split_instruction(1_000_000, [1; 32], &splits)], &[]).await;
+    assert_vault_error(result, VaultError::InvalidSplit);
+    let splits = [(4_980, payment.merchant_token_account), (5_000, recipient)];
+    vault.send(&[payment.split_instruction(1_000_000, [2; 32], &splits)], &[]).await.unwrap();
+    assert_eq!(vault.balance(&vault.fee_token_account.clone()).await, 2_000);
+
+    vault.send(&[set_exempt(authority, merchant, true)], &[]).await.unwrap();
+    let splits = [(5_000, payment.merchant_token_account), (5_000, recipient)];
+    vault.send(&[payment.split_instruction(1_000_000, [3; 32], &splits)], &[]).await.unwrap();
+    assert_eq!(vault.balance(&vault.fee_token_account.clone()).await, 2_000);
+    assert_eq!(vault.balance(&recipient).await, 1_000_000);
+}
+
+#[tokio::test]
+async fn test_escrow_fee_is_the_merchants_override() {
+    let mut vault = Vault::start().await;
+    let mut payment = vault.payment().await;
+    let (authority, merchant) = (vault.payer(), payment.merchant.pubkey());
+    vault.send(&[set_override(authority, merchant, 20)], &[]).await.unwrap();
+    payment.merchant_config = Some(merchant_config(&merchant));
+
+    vault.send(&[payment.escrow_instruction(1_000_000, [1; 32], START_TIME + 3_600)], &[]).await.unwrap();
+    vault.send(&[payment.capture_instruction([1; 32])], &[&payment.merchant]).await.unwrap();
+
+    assert_eq!(vault.balance(&payment.merchant_token_account).await, 998_000);
+    assert_eq!(vault.balance(&payment.fee_token_account).await, 2_000);
+}
+
+#[tokio::test]
+async fn test_batch_charges_each_merchants_fee() {
+    let mut vault = Vault::start().await;
+    let payment = vault.payment().await;
+    let (authority, merchant, mint) = (vault.payer(), payment.merchant.pubkey(), vault.mint);
+    let other = vault.create_token_account(&mint, &Keypair::new().pubkey()).await;
+    vault.send(&[set_override(authority, merchant, 20)], &[]).await.unwrap();
+
+    let payments = [(payment.merchant_token_account, 1_000_000, [1; 32]), (other, 1_000_000, [2; 32])];
+    let batch = payment.batch_instruction_with(&payments, &[Some(merchant_config(&merchant)), None], &[]);
+    let budget = ComputeBudgetInstruction::set_compute_unit_limit(PAYMENT_BATCH_COMPUTE_UNITS);
+    vault.send(&[budget, batch], &[]).await.unwrap();
+
+    // 0.2% for the merchant with the override, the vault's 0.5% for the other
+    assert_eq!(vault.balance(&payment.merchant_token_account).await, 998_000);
+    assert_eq!(vault.balance(&other).await, 995_000);
+    assert_eq!(vault.balance(&payment.fee_token_account).await, 7_000);
+}
+
+#[tokio::test]
+async fn test_another_merchants_exemption_is_rejected() {
+    let mut vault = Vault::start().await;
+    let payment = vault.payment().await;
+    let authority = vault.payer();
+    let other = Keypair::new().pubkey();
+    vault.send(&[set_exempt(authority, other, true)], &[]).await.unwrap();
+
+    let pay = with_merchant_config(payment.instruction(1_000_000, [1; 32]), merchant_config(&other));
+    let result = vault.send(&[pay], &[]).await;
+    assert_vault_error(result, VaultError::InvalidMerchantConfig);
+    assert_eq!(vault.balance(&payment.payer_token_account).await, 10_000_000);
+
+    let intruder = Keypair::new();
+    let result = vault.send(&[set_exempt(intruder.pubkey(), other, false)], &[&intruder]).await;
+    assert_program_error(result, anchor_lang::error::ErrorCode::ConstraintHasOne.into());
+}
+
+#[tokio::test]
+async fn test_another_merchants_override_is_rejected() {
+    let mut vault = Vault::start().await;
+    let payment = vault.payment().await;
+    let authority = vault.payer();
+    let other = Keypair::new().pubkey();
+    vault.send(&[set_override(authority, other, 0)], &[]).await.unwrap();
+
+    let pay = with_merchant_config(payment.instruction(1_000_000, [1; 32]), merchant_config(&other));
+    let result = vault.send(&[pay], &[]).await;
+    assert_vault_error(result, VaultError::InvalidMerchantConfig);
+}
+
+#[tokio::test]
+async fn test_only_authority_can_manage_overrides() {
+    let mut vault = Vault::start().await;
+    let intruder = Keypair::new();
+    let merchant = Keypair::new().pubkey();
+
+    // Created by the authority first, so the intruder's attempt gets as far
+    // as the authority check rather than failing to pay for the account
+    let authority = vault.payer();
+    vault.send(&[set_override(authority, merchant, 10)], &[]).await.unwrap();
+    let result = vault.send(&[set_override(intruder.pubkey(), merchant, 0)], &[&intruder]).await;
+    assert_program_error(result, anchor_lang::error::ErrorCode::ConstraintHasOne.into());
+    let result = vault.send(&[remove_override(intruder.pubkey(), merchant)], &[&intruder]).await;
+    assert_program_error(result, anchor_lang::error::ErrorCode::ConstraintHasOne.into());
+
+    let result = vault.send(&[set_override(authority, merchant, 1001)], &[]).await;
+    assert_vault_error(result, VaultError::FeeTooHigh);
+}
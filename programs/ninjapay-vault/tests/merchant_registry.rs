@@ -0,0 +1,141 @@
+mod common;
+
+use common::{
+    assert_program_error, assert_vault_error, registered_merchant, set_merchant_registration_required_instruction,
+    set_registered_merchant_instruction, sol_payment_instruction_to, Payment, Vault, START_TIME,
+};
+use ninjapay_vault::VaultError;
+use solana_sdk::{
+    compute_budget::ComputeBudgetInstruction,
+    signature::{Keypair, Signer},
+    system_instruction,
+};
+
+#[tokio::test]
+async fn test_any_merchant_is_paid_until_registration_is_required() {
+    let mut vault = Vault::start().await;
+    let payment = vault.payment().await;
+
+    // No RegisteredMerchant passed, none needed
+    vault.send(&[payment.instruction(1_000_000, [1; 32])], &[]).await.unwrap();
+    assert_eq!(vault.balance(&payment.merchant_token_account).await, 995_000);
+}
+
+#[tokio::test]
+async fn test_required_registration_pays_only_active_merchants() {
+    let mut vault = Vault::start().await;
+    let mut payment = vault.payment().await;
+    let (authority, merchant) = (vault.payer(), payment.merchant.pubkey());
+    vault.send(&[set_merchant_registration_required_instruction(authority, true)], &[]).await.unwrap();
+
+    let result = vault.send(&[payment.instruction(1_000_000, [1; 32])], &[]).await;
+    assert_vault_error(result, VaultError::MerchantNotRegistered);
+
+    vault.send(&[set_registered_merchant_instruction(true, authority, merchant)], &[]).await.unwrap();
+    payment.registered_merchant = Some(registered_merchant(&merchant));
+    vault.send(&[payment.instruction(1_000_000, [2; 32])], &[]).await.unwrap();
+
+    vault.send(&[set_registered_merchant_instruction(false, authority, merchant)], &[]).await.unwrap();
+    let result = vault.send(&[payment.instruction(1_000_000, [3; 32])], &[]).await;
+    assert_vault_error(result, VaultError::MerchantNotRegistered);
+
+    // Deregistered merchants can be registered again
+    vault.send(&[set_registered_merchant_instruction(true, authority, merchant)], &[]).await.unwrap();
+    vault.send(&[payment.instruction(1_000_000, [4; 32])], &[]).await.unwrap();
+    assert_eq!(vault.balance(&payment.payer_token_account).await, 8_000_000);
+
+    // Turned off again, the registration is no longer needed
+    vault.send(&[set_merchant_registration_required_instruction(authority, false)], &[]).await.unwrap();
+    payment.registered_merchant = None;
+    vault.send(&[payment.instruction(1_000_000, [5; 32])], &[]).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_another_merchants_registration_does_not_cover_the_payment() {
+    let mut vault = Vault::start().await;
+    let mut payment = vault.payment().await;
+    let authority = vault.payer();
+    let other_merchant = Keypair::new().pubkey();
+    vault.send(&[set_merchant_registration_required_instruction(authority, true)], &[]).await.unwrap();
+    vault.send(&[set_registered_merchant_instruction(true, authority, other_merchant)], &[]).await.unwrap();
+
+    payment.registered_merchant = Some(registered_merchant(&other_merchant));
+    let result = vault.send(&[payment.instruction(1_000_000, [1; 32])], &[]).await;
+
+    assert_program_error(result, anchor_lang::error::ErrorCode::ConstraintSeeds.into());
+    assert_eq!(vault.balance(&payment.payer_token_account).await, 10_000_000);
+}
+
+#[tokio::test]
+async fn test_required_registration_covers_batch_split_sol_and_escrow_payments() {
+    let mut vault = Vault::start().await;
+    vault.ensure_native_mint().await;
+    let mut payment = vault.payment().await;
+    let (authority, merchant, fee_collector) = (vault.payer(), payment.merchant.pubkey(), vault.fee_collector.pubkey());
+    let fund: Vec<_> = [merchant, fee_collector]
+        .iter()
+        .map(|wallet| system_instruction::transfer(&authority, wallet, 1_000_000_000))
+        .collect();
+    vault.send(&fund, &[]).await.unwrap();
+    vault.send(&[set_merchant_registration_required_instruction(authority, true)], &[]).await.unwrap();
+
+    let account = payment.merchant_token_account;
+    let payments = |payment: &Payment, registered_merchant| {
+        [
+            payment.batch_instruction_registered(&[(account, 1_000_000, [1; 32])], &[merchant]),
+            payment.split_instruction_registered(1_000_000, [2; 32], &[(9_950, account)], &[merchant]),
+            sol_payment_instruction_to(authority, merchant, registered_merchant, &[fee_collector], 1_000_000, [3; 32]),
+        ]
+    };
+    for instruction in payments(&payment, None) {
+        let result = vault.send(&[instruction], &[]).await;
+        assert_vault_error(result, VaultError::MerchantNotRegistered);
+    }
+    let result = vault.send(&[payment.escrow_instruction(1_000_000, [4; 32], START_TIME + 3_600)], &[]).await;
+    assert_vault_error(result, VaultError::MerchantNotRegistered);
+    // Nor can a batch leave the registrations out
+    let result = vault.send(&[payment.batch_instruction(&[(account, 1_000_000, [1; 32])])], &[]).await;
+    assert_vault_error(result, VaultError::InvalidPaymentBatch);
+
+    vault.send(&[set_registered_merchant_instruction(true, authority, merchant)], &[]).await.unwrap();
+    payment.registered_merchant = Some(registered_merchant(&merchant));
+    // Compute budgets keep the retries from repeating a transaction already
+    // processed
+    for instruction in payments(&payment, payment.registered_merchant) {
+        let budget = ComputeBudgetInstruction::set_compute_unit_limit(200_001);
+        vault.send(&[budget, instruction], &[]).await.unwrap();
+    }
+    vault.send(&[payment.escrow_instruction(1_000_000, [4; 32], START_TIME + 3_600)], &[]).await.unwrap();
+    assert_eq!(vault.balance(&payment.merchant_token_account).await, 2 * 995_000);
+}
+
+#[tokio::test]
+async fn test_a_batch_cannot_pass_another_merchants_registration() {
+    let mut vault = Vault::start().await;
+    let payment = vault.payment().await;
+    let authority = vault.payer();
+    let other_merchant = Keypair::new().pubkey();
+    vault.send(&[set_merchant_registration_required_instruction(authority, true)], &[]).await.unwrap();
+    vault.send(&[set_registered_merchant_instruction(true, authority, other_merchant)], &[]).await.unwrap();
+
+    let payments = [(payment.merchant_token_account, 1_000_000, [1; 32])];
+    let result = vault.send(&[payment.batch_instruction_registered(&payments, &[other_merchant])], &[]).await;
+
+    assert_vault_error(result, VaultError::MerchantNotRegistered);
+    assert_eq!(vault.balance(&payment.payer_token_account).await, 10_000_000);
+}
+
+#[tokio::test]
+async fn test_only_the_authority_manages_the_registry() {
+    let mut vault = Vault::start().await;
+    let stranger = Keypair::new();
+    let merchant = Keypair::new().pubkey();
+
+    for instruction in [
+        set_merchant_registration_required_instruction(stranger.pubkey(), true),
+        set_registered_merchant_instruction(true, stranger.pubkey(), merchant),
+    ] {
+        let result = vault.send(&[instruction], &[&stranger]).await;
+        assert_program_error(result, anchor_lang::error::ErrorCode::ConstraintHasOne.into());
+    }
+}
@@ -0,0 +1,78 @@
+mod common;
+
+use common::{
+    accepted_mint, assert_program_error, assert_vault_error, set_accepted_mint_instruction,
+    set_mint_whitelist_required_instruction, Vault, FEE_BASIS_POINTS,
+};
+use ninjapay_vault::VaultError;
+use solana_sdk::signature::{Keypair, Signer};
+
+#[tokio::test]
+async fn test_whitelist_is_off_until_required() {
+    let mut vault = Vault::start().await;
+    let payment = vault.payment().await;
+
+    // No AcceptedMint passed, none needed
+    vault.send(&[payment.instruction(1_000_000, [1; 32])], &[]).await.unwrap();
+    assert_eq!(vault.balance(&payment.merchant_token_account).await, 995_000);
+}
+
+#[tokio::test]
+async fn test_required_whitelist_accepts_only_active_mints() {
+    let mut vault = Vault::start().await;
+    let mut payment = vault.payment().await;
+    let (authority, mint) = (vault.payer(), vault.mint);
+    vault.send(&[set_mint_whitelist_required_instruction(authority, true)], &[]).await.unwrap();
+
+    let result = vault.send(&[payment.instruction(1_000_000, [1; 32])], &[]).await;
+    assert_vault_error(result, VaultError::MintNotWhitelisted);
+
+    vault.send(&[set_accepted_mint_instruction(true, authority, mint)], &[]).await.unwrap();
+    payment.accepted_mint = Some(accepted_mint(&mint));
+    vault.send(&[payment.instruction(1_000_000, [2; 32])], &[]).await.unwrap();
+    let recipient = payment.merchant_token_account;
+    let split = payment.split_instruction(1_000_000, [3; 32], &[(10_000 - FEE_BASIS_POINTS, recipient)]);
+    vault.send(&[split], &[]).await.unwrap();
+
+    vault.send(&[set_accepted_mint_instruction(false, authority, mint)], &[]).await.unwrap();
+    let result = vault.send(&[payment.instruction(1_000_000, [4; 32])], &[]).await;
+    assert_vault_error(result, VaultError::MintNotWhitelisted);
+
+    // Removed mints can be added back
+    vault.send(&[set_accepted_mint_instruction(true, authority, mint)], &[]).await.unwrap();
+    vault.send(&[payment.instruction(1_000_000, [5; 32])], &[]).await.unwrap();
+    assert_eq!(vault.balance(&payment.payer_token_account).await, 7_000_000);
+}
+
+#[tokio::test]
+async fn test_another_mints_entry_does_not_whitelist_the_payment() {
+    let mut vault = Vault::start().await;
+    let other_mint = vault.create_mint().await;
+    let collector = vault.fee_collector.pubkey();
+    let other_fee_account = vault.create_token_account(&other_mint, &collector).await;
+    let mut payment = vault.payment_in(other_mint, other_fee_account).await;
+    let (authority, mint) = (vault.payer(), vault.mint);
+    vault.send(&[set_mint_whitelist_required_instruction(authority, true)], &[]).await.unwrap();
+    vault.send(&[set_accepted_mint_instruction(true, authority, mint)], &[]).await.unwrap();
+
+    payment.accepted_mint = Some(accepted_mint(&mint));
+    let result = vault.send(&[payment.instruction(1_000_000, [1; 32])], &[]).await;
+
+    assert_program_error(result, anchor_lang::error::ErrorCode::ConstraintSeeds.into());
+    assert_eq!(vault.balance(&payment.payer_token_account).await, 10_000_000);
+}
+
+#[tokio::test]
+async fn test_only_the_authority_manages_the_whitelist() {
+    let mut vault = Vault::start().await;
+    let stranger = Keypair::new();
+    let mint = vault.mint;
+
+    for instruction in [
+        set_mint_whitelist_required_instruction(stranger.pubkey(), true),
+        set_accepted_mint_instruction(true, stranger.pubkey(), mint),
+    ] {
+        let result = vault.send(&[instruction], &[&stranger]).await;
+        assert_program_error(result, anchor_lang::error::ErrorCode::ConstraintHasOne.into());
+    }
+}
@@ -0,0 +1,77 @@
+mod common;
+
+use common::{
+    assert_program_error, assert_vault_error, payroll_approver, payroll_batch_instruction, set_paused_instruction,
+    set_payroll_approver_instruction, Vault, START_TIME,
+};
+use ninjapay_vault::VaultError;
+use solana_sdk::signature::{Keypair, Signer};
+
+#[tokio::test]
+async fn test_paused_vault_rejects_payments_until_unpaused() {
+    let mut vault = Vault::start().await;
+    let payment = vault.payment().await;
+    let authority = vault.payer();
+
+    vault.send(&[set_paused_instruction(true, authority)], &[]).await.unwrap();
+    let result = vault.send(&[payment.instruction(1_000_000, [1; 32])], &[]).await;
+    assert_vault_error(result, VaultError::VaultPaused);
+    assert_eq!(vault.balance(&payment.payer_token_account).await, 10_000_000);
+
+    vault.send(&[set_paused_instruction(false, authority)], &[]).await.unwrap();
+    vault.send(&[payment.instruction(1_000_000, [1; 32])], &[]).await.unwrap();
+    assert_eq!(vault.balance(&payment.merchant_token_account).await, 995_000);
+}
+
+#[tokio::test]
+async fn test_paused_vault_rejects_payroll_batches_until_unpaused() {
+    let mut vault = Vault::start().await;
+    let (mint, company) = (vault.mint, vault.payer());
+    let company_token_account = vault.create_token_account(&mint, &company).await;
+    vault.mint_to(&mint, &company_token_account, 1_000_000).await;
+    let employee = vault.create_token_account(&mint, &Keypair::new().pubkey()).await;
+    let pay = [(employee, 1_000_000)];
+    let approver = payroll_approver();
+    vault.send(&[set_payroll_approver_instruction(company, approver.pubkey())], &[]).await.unwrap();
+
+    vault.send(&[set_paused_instruction(true, company)], &[]).await.unwrap();
+    let batch = payroll_batch_instruction(company, company_token_account, mint, [1; 32], 1_000_000, 1, &pay);
+    let result = vault.send(&[batch], &[&approver]).await;
+    assert_vault_error(result, VaultError::VaultPaused);
+    assert_eq!(vault.balance(&company_token_account).await, 1_000_000);
+
+    vault.send(&[set_paused_instruction(false, company)], &[]).await.unwrap();
+    let batch = payroll_batch_instruction(company, company_token_account, mint, [2; 32], 1_000_000, 1, &pay);
+    vault.send(&[batch], &[&approver]).await.unwrap();
+    assert_eq!(vault.balance(&employee).await, 1_000_000);
+}
+
+#[tokio::test]
+async fn test_paused_vault_holds_expired_escrow_refunds_until_unpaused() {
+    let mut vault = Vault::start().await;
+    let payment = vault.payment().await;
+    let authority = vault.payer();
+    vault.send(&[payment.escrow_instruction(1_000_000, [1; 32], START_TIME + 3_600)], &[]).await.unwrap();
+    vault.set_time(START_TIME + 3_600).await;
+
+    vault.send(&[set_paused_instruction(true, authority)], &[]).await.unwrap();
+    let result = vault.send(&[payment.cancel_escrow_instruction([1; 32])], &[]).await;
+    assert_vault_error(result, VaultError::VaultPaused);
+    let result = vault.send(&[payment.crank_escrow_instruction([1; 32], authority)], &[]).await;
+    assert_vault_error(result, VaultError::VaultPaused);
+    assert_eq!(vault.balance(&payment.payer_token_account).await, 9_000_000);
+
+    vault.send(&[set_paused_instruction(false, authority)], &[]).await.unwrap();
+    vault.send(&[payment.cancel_escrow_instruction([1; 32])], &[]).await.unwrap();
+    assert_eq!(vault.balance(&payment.payer_token_account).await, 10_000_000);
+}
+
+#[tokio::test]
+async fn test_only_authority_can_pause() {
+    let mut vault = Vault::start().await;
+    let intruder = Keypair::new();
+
+    let result = vault.send(&[set_paused_instruction(true, intruder.pubkey())], &[&intruder]).await;
+
+    assert_program_error(result, anchor_lang::error::ErrorCode::ConstraintHasOne.into());
+}
@@ -0,0 +1,113 @@
+mod common;
+
+use anchor_lang::AccountDeserialize;
+use common::{assert_vault_error, payment_record, vault_config, Vault};
+use ninjapay_vault::{
+    PaymentRecord, PaymentStatus, VaultConfig, VaultError, MAX_BATCH_PAYMENTS, PAYMENT_BATCH_COMPUTE_UNITS,
+    RECORD_VERSION,
+};
+use solana_sdk::{
+    compute_budget::ComputeBudgetInstruction,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+};
+
+/// Token accounts in the vault's mint for `count` new merchants
+async fn merchants(vault: &mut Vault, count: usize) -> Vec<(Pubkey, Pubkey)> {
+    let mint = vault.mint;
+    let mut accounts = Vec::with_capacity(count);
+    for _ in 0..count {
+        let merchant = Keypair::new().pubkey();
+        accounts.push((merchant, vault.create_token_account(&mint, &merchant).await));
+    }
+    accounts
+}
+
+#[tokio::test]
+async fn test_batch_pays_each_merchant_and_records_each_payment() {
+    let mut vault = Vault::start().await;
+    let payment = vault.payment().await;
+    let to = merchants(&mut vault, MAX_BATCH_PAYMENTS).await;
+
+    let payments: Vec<_> = to
+        .iter()
+        .enumerate()
+        .map(|(i, &(_, account))| (account, 1_000_000, [i as u8 + 1; 32]))
+        .collect();
+    let budget = ComputeBudgetInstruction::set_compute_unit_limit(PAYMENT_BATCH_COMPUTE_UNITS);
+    vault.send(&[budget, payment.batch_instruction(&payments)], &[]).await.unwrap();
+
+    for (i, &(merchant, account)) in to.iter().enumerate() {
+        // 50 bps of each
+        assert_eq!(vault.balance(&account).await, 995_000);
+        let address = payment_record(&payment.payer, &[i as u8 + 1; 32]);
+        let record = vault.context.banks_client.get_account(address).await.unwrap().unwrap();
+        let record = PaymentRecord::try_deserialize(&mut record.data.as_slice()).unwrap();
+        assert_eq!((record.merchant, record.amount, record.fee), (merchant, 1_000_000, 5_000));
+        assert_eq!(record.record_version, RECORD_VERSION);
+        assert!(record.status == PaymentStatus::Settled);
+    }
+    assert_eq!(vault.balance(&vault.fee_token_account.clone()).await, 25_000);
+    assert_eq!(vault.balance(&payment.payer_token_account).await, 5_000_000);
+
+    let account = vault.context.banks_client.get_account(vault_config()).await.unwrap().unwrap();
+    let config = VaultConfig::try_deserialize(&mut account.data.as_slice()).unwrap();
+    assert_eq!((config.total_volume, config.total_payments, config.total_fees_collected), (5_000_000, 5, 25_000));
+}
+
+#[tokio::test]
+async fn test_batch_size_and_lengths_are_checked() {
+    let mut vault = Vault::start().await;
+    let payment = vault.payment().await;
+    let to = merchants(&mut vault, MAX_BATCH_PAYMENTS + 1).await;
+
+    let payments: Vec<_> =
+        to.iter().enumerate().map(|(i, &(_, account))| (account, 1_000, [i as u8 + 1; 32])).collect();
+    let result = vault.send(&[payment.batch_instruction(&payments)], &[]).await;
+    assert_vault_error(result, VaultError::InvalidPaymentBatch);
+
+    let result = vault.send(&[payment.batch_instruction(&[])], &[]).await;
+    assert_vault_error(result, VaultError::InvalidPaymentBatch);
+
+    // A payment without its record account
+    let mut instruction = payment.batch_instruction(&payments[..2]);
+    instruction.accounts.pop();
+    let result = vault.send(&[instruction], &[]).await;
+    assert_vault_error(result, VaultError::InvalidPaymentBatch);
+    assert_eq!(vault.balance(&payment.payer_token_account).await, 10_000_000);
+}
+
+#[tokio::test]
+async fn test_one_failing_payment_fails_the_whole_batch() {
+    let mut vault = Vault::start().await;
+    let payment = vault.payment().await;
+    let to = merchants(&mut vault, 2).await;
+    let other_mint = vault.create_mint().await;
+    let wrong_mint_account = vault.create_token_account(&other_mint, &Keypair::new().pubkey()).await;
+
+    let payments = [
+        (to[0].1, 1_000_000, [1; 32]),
+        (to[1].1, 1_000_000, [2; 32]),
+        (wrong_mint_account, 1_000_000, [3; 32]),
+    ];
+    let result = vault.send(&[payment.batch_instruction(&payments)], &[]).await;
+
+    assert_vault_error(result, VaultError::MintMismatch);
+    assert_eq!(vault.balance(&to[0].1).await, 0);
+    assert_eq!(vault.balance(&payment.payer_token_account).await, 10_000_000);
+    let record = vault.context.banks_client.get_account(payment_record(&payment.payer, &[1; 32])).await.unwrap();
+    assert!(record.is_none());
+}
+
+#[tokio::test]
+async fn test_batch_payment_ids_cannot_repeat_earlier_payments() {
+    let mut vault = Vault::start().await;
+    let payment = vault.payment().await;
+    vault.send(&[payment.instruction(1_000_000, [1; 32])], &[]).await.unwrap();
+    let to = merchants(&mut vault, 1).await;
+
+    let result = vault.send(&[payment.batch_instruction(&[(to[0].1, 1_000_000, [1; 32])])], &[]).await;
+
+    assert!(result.is_err());
+    assert_eq!(vault.balance(&to[0].1).await, 0);
+}
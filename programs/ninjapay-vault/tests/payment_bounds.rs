@@ -0,0 +1,137 @@
+mod common;
+
+use anchor_lang::AccountDeserialize;
+use common::{assert_program_error, assert_vault_error, set_payment_bounds_instruction, vault_config, Vault};
+use ninjapay_vault::{VaultConfig, VaultError};
+use proptest::prelude::*;
+use solana_sdk::signature::{Keypair, Signer};
+
+fn config(min_payment: u64, max_payment: u64) -> VaultConfig {
+    VaultConfig {
+        authority: Default::default(),
+        fee_collector: Default::default(),
+        fee_basis_points: 50,
+        total_volume: 0,
+        total_payments: 0,
+        bump: 255,
+        paused: false,
+        min_payment,
+        max_payment,
+        total_fees_collected: 0,
+        max_payments_per_hour: 0,
+        max_volume_per_hour: 0,
+        require_mint_whitelist: false,
+        pending_authority: None,
+    }
+}
+
+async fn bounds(vault: &mut Vault) -> (u64, u64) {
+    let account = vault.context.banks_client.get_account(vault_config()).await.unwrap().unwrap();
+    let config = VaultConfig::try_deserialize(&mut account.data.as_slice()).unwrap();
+    (config.min_payment, config.max_payment)
+}
+
+#[tokio::test]
+async fn test_payments_outside_the_bounds_are_rejected() {
+    let mut vault = Vault::start().await;
+    let payment = vault.payment().await;
+    let authority = vault.payer();
+    assert_eq!(bounds(&mut vault).await, (0, 0));
+
+    vault.send(&[set_payment_bounds_instruction(authority, 1_000, 2_000_000)], &[]).await.unwrap();
+    assert_eq!(bounds(&mut vault).await, (1_000, 2_000_000));
+
+    let result = vault.send(&[payment.instruction(999, [1; 32])], &[]).await;
+    assert_vault_error(result, VaultError::InvalidAmount);
+    let result = vault.send(&[payment.instruction(2_000_001, [2; 32])], &[]).await;
+    assert_vault_error(result, VaultError::InvalidAmount);
+    assert_eq!(vault.balance(&payment.payer_token_account).await, 10_000_000);
+
+    // Both bounds are inclusive
+    vault.send(&[payment.instruction(1_000, [3; 32])], &[]).await.unwrap();
+    vault.send(&[payment.instruction(2_000_000, [4; 32])], &[]).await.unwrap();
+
+    // A max of 0 lifts the upper bound
+    vault.send(&[set_payment_bounds_instruction(authority, 1_000, 0)], &[]).await.unwrap();
+    vault.send(&[payment.instruction(5_000_000, [5; 32])], &[]).await.unwrap();
+    assert_eq!(vault.balance(&payment.payer_token_account).await, 10_000_000 - 7_001_000);
+}
+
+#[tokio::test]
+async fn test_min_above_max_is_rejected() {
+    let mut vault = Vault::start().await;
+    let authority = vault.payer();
+
+    let result = vault.send(&[set_payment_bounds_instruction(authority, 2_000, 1_999)], &[]).await;
+
+    assert_vault_error(result, VaultError::InvalidAmount);
+    assert_eq!(bounds(&mut vault).await, (0, 0));
+}
+
+#[tokio::test]
+async fn test_only_authority_can_set_payment_bounds() {
+    let mut vault = Vault::start().await;
+    let intruder = Keypair::new();
+
+    let result = vault
+        .send(&[set_payment_bounds_instruction(intruder.pubkey(), 0, 1)], &[&intruder])
+        .await;
+
+    assert_program_error(result, anchor_lang::error::ErrorCode::ConstraintHasOne.into());
+    assert_eq!(bounds(&mut vault).await, (0, 0));
+}
+
+proptest! {
+    #[test]
+    fn prop_unbounded_max_accepts_every_payment_from_min(min in any::<u64>(), amount in any::<u64>()) {
+        prop_assert_eq!(config(min, 0).accepts_amount(amount), amount > 0 && amount >= min);
+    }
+
+    #[test]
+    fn prop_bounds_are_inclusive(min in any::<u64>(), max in 1u64.., amount in any::<u64>()) {
+        let config = config(min, max);
+        prop_assert_eq!(config.accepts_amount(amount), amount > 0 && min <= amount && amount <= max);
+        if 0 < min && min <= max {
+            prop_assert!(config.accepts_amount(min));
+            prop_assert!(config.accepts_amount(max));
+        }
+    }
+}
+
+proptest! {
+    // Each case starts its own bank
+    #![proptest_config(ProptestConfig::with_cases(16))]
+
+    #[test]
+    fn prop_program_enforces_the_bounds(
+        min in 0u64..3_000_000,
+        span in 0u64..3_000_000,
+        unbounded in any::<bool>(),
+        amount in 0u64..=10_000_000,
+    ) {
+        let max = if unbounded { 0 } else { min + span };
+        let expected = config(min, max).accepts_amount(amount);
+
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+        let (accepted, payer_balance) = runtime.block_on(async {
+            let mut vault = Vault::start().await;
+            let payment = vault.payment().await;
+            let authority = vault.payer();
+            vault.send(&[set_payment_bounds_instruction(authority, min, max)], &[]).await.unwrap();
+
+            let result = vault.send(&[payment.instruction(amount, [7; 32])], &[]).await;
+            let accepted = match result {
+                Ok(()) => true,
+                Err(_) if !expected => {
+                    assert_vault_error(result, VaultError::InvalidAmount);
+                    false
+                }
+                Err(e) => panic!("payment of {} within [{}, {}] failed: {:?}", amount, min, max, e),
+            };
+            (accepted, vault.balance(&payment.payer_token_account).await)
+        });
+
+        prop_assert_eq!(accepted, expected);
+        prop_assert_eq!(payer_balance, if accepted { 10_000_000 - amount } else { 10_000_000 });
+    }
+}
@@ -0,0 +1,52 @@
+mod common;
+
+use anchor_lang::AccountDeserialize;
+use common::{assert_vault_error, payment_record, Vault};
+use ninjapay_vault::{DailyVolumeRecord, PaymentRecord, VaultError};
+
+const NOW: i64 = 1_792_000_000;
+
+async fn record(vault: &mut Vault, payment_id: [u8; 32]) -> PaymentRecord {
+    let address = payment_record(&vault.payer(), &payment_id);
+    let account = vault.context.banks_client.get_account(address).await.unwrap().unwrap();
+    PaymentRecord::try_deserialize(&mut account.data.as_slice()).unwrap()
+}
+
+#[tokio::test]
+async fn test_expired_payment_request_is_rejected() {
+    let mut vault = Vault::start().await;
+    let mut payment = vault.payment().await;
+    vault.set_time(NOW).await;
+    payment.day = NOW / DailyVolumeRecord::DAY_SECS;
+
+    let result = vault.send(&[payment.instruction_expiring(1_000_000, [1; 32], NOW - 1)], &[]).await;
+
+    assert_vault_error(result, VaultError::PaymentExpired);
+    assert_eq!(vault.balance(&payment.payer_token_account).await, 10_000_000);
+}
+
+#[tokio::test]
+async fn test_payment_is_accepted_until_it_expires() {
+    let mut vault = Vault::start().await;
+    let mut payment = vault.payment().await;
+    vault.set_time(NOW).await;
+    payment.day = NOW / DailyVolumeRecord::DAY_SECS;
+
+    // The deadline itself is still in time
+    vault.send(&[payment.instruction_expiring(1_000_000, [2; 32], NOW)], &[]).await.unwrap();
+
+    let record = record(&mut vault, [2; 32]).await;
+    assert_eq!((record.timestamp, record.expires_at), (NOW, NOW));
+}
+
+#[tokio::test]
+async fn test_zero_never_expires() {
+    let mut vault = Vault::start().await;
+    let mut payment = vault.payment().await;
+    vault.set_time(NOW).await;
+    payment.day = NOW / DailyVolumeRecord::DAY_SECS;
+
+    vault.send(&[payment.instruction(1_000_000, [3; 32])], &[]).await.unwrap();
+
+    assert_eq!(record(&mut vault, [3; 32]).await.expires_at, 0);
+}
@@ -0,0 +1,115 @@
+mod common;
+
+use anchor_lang::AccountDeserialize;
+use common::{
+    assert_program_error, assert_vault_error, payroll_approver, payroll_approver_config, payroll_batch_instruction,
+    replace_payroll_approver_instruction, set_payroll_approver_instruction, Vault,
+};
+use ninjapay_vault::{PayrollApproverConfig, VaultError};
+use solana_sdk::{
+    instruction::AccountMeta,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+};
+
+/// The context payer as the company, with a 5_000_000 token balance, and an
+/// employee's token account
+async fn setup(vault: &mut Vault) -> (Pubkey, Pubkey, Pubkey) {
+    let (mint, company) = (vault.mint, vault.payer());
+    let company_token_account = vault.create_token_account(&mint, &company).await;
+    vault.mint_to(&mint, &company_token_account, 5_000_000).await;
+    let employee = vault.create_token_account(&mint, &Keypair::new().pubkey()).await;
+    (company, company_token_account, employee)
+}
+
+#[tokio::test]
+async fn test_batch_needs_the_company_and_its_approver() {
+    let mut vault = Vault::start().await;
+    let (company, company_token_account, employee) = setup(&mut vault).await;
+    let (mint, approver) = (vault.mint, payroll_approver());
+    let pay = [(employee, 1_000_000)];
+
+    vault.send(&[set_payroll_approver_instruction(company, approver.pubkey())], &[]).await.unwrap();
+    // The company alone, unsigned by the approver or leaving it out
+    let mut batch = payroll_batch_instruction(company, company_token_account, mint, [2; 32], 1_000_000, 1, &pay);
+    batch.accounts[5].is_signer = false;
+    let result = vault.send(&[batch], &[]).await;
+    assert_program_error(result, anchor_lang::error::ErrorCode::AccountNotSigner.into());
+    let mut batch = payroll_batch_instruction(company, company_token_account, mint, [2; 32], 1_000_000, 1, &pay);
+    batch.accounts[5] = AccountMeta::new_readonly(ninjapay_vault::ID, false);
+    let result = vault.send(&[batch], &[]).await;
+    assert_vault_error(result, VaultError::Unauthorized);
+    // Someone else co-signing
+    let stranger = Keypair::new();
+    let mut batch = payroll_batch_instruction(company, company_token_account, mint, [3; 32], 1_000_000, 1, &pay);
+    batch.accounts[5].pubkey = stranger.pubkey();
+    let result = vault.send(&[batch], &[&stranger]).await;
+    assert_vault_error(result, VaultError::Unauthorized);
+    assert_eq!(vault.balance(&company_token_account).await, 5_000_000);
+
+    let batch = payroll_batch_instruction(company, company_token_account, mint, [4; 32], 1_000_000, 1, &pay);
+    vault.send(&[batch], &[&approver]).await.unwrap();
+    assert_eq!(vault.balance(&employee).await, 1_000_000);
+}
+
+#[tokio::test]
+async fn test_company_without_an_approver_runs_payroll_alone() {
+    let mut vault = Vault::start().await;
+    let (company, company_token_account, employee) = setup(&mut vault).await;
+    let (mint, pay) = (vault.mint, [(employee, 1_000_000)]);
+
+    let mut batch = payroll_batch_instruction(company, company_token_account, mint, [1; 32], 1_000_000, 1, &pay);
+    batch.accounts[5] = AccountMeta::new_readonly(ninjapay_vault::ID, false);
+    vault.send(&[batch], &[]).await.unwrap();
+
+    assert_eq!(vault.balance(&employee).await, 1_000_000);
+}
+
+#[tokio::test]
+async fn test_company_rotates_its_approver() {
+    let mut vault = Vault::start().await;
+    let (company, company_token_account, employee) = setup(&mut vault).await;
+    let (mint, approver) = (vault.mint, payroll_approver());
+    let pay = [(employee, 1_000_000)];
+    let replacement = Keypair::new();
+    vault.send(&[set_payroll_approver_instruction(company, approver.pubkey())], &[]).await.unwrap();
+
+    // Not without the approver being replaced, nor co-signed by another key
+    let result = vault.send(&[set_payroll_approver_instruction(company, replacement.pubkey())], &[]).await;
+    assert_vault_error(result, VaultError::Unauthorized);
+    let rotate = replace_payroll_approver_instruction(company, Some(replacement.pubkey()), replacement.pubkey());
+    let result = vault.send(&[rotate], &[&replacement]).await;
+    assert_vault_error(result, VaultError::Unauthorized);
+
+    let rotate = replace_payroll_approver_instruction(company, Some(approver.pubkey()), replacement.pubkey());
+    vault.send(&[rotate], &[&approver]).await.unwrap();
+    let account = vault.context.banks_client.get_account(payroll_approver_config(&company)).await.unwrap().unwrap();
+    let config = PayrollApproverConfig::try_deserialize(&mut account.data.as_slice()).unwrap();
+    assert_eq!((config.company, config.approver), (company, replacement.pubkey()));
+
+    // The old approver no longer co-signs
+    let batch = payroll_batch_instruction(company, company_token_account, mint, [1; 32], 1_000_000, 1, &pay);
+    let result = vault.send(&[batch], &[&approver]).await;
+    assert_vault_error(result, VaultError::Unauthorized);
+
+    let mut batch = payroll_batch_instruction(company, company_token_account, mint, [2; 32], 1_000_000, 1, &pay);
+    batch.accounts[5].pubkey = replacement.pubkey();
+    vault.send(&[batch], &[&replacement]).await.unwrap();
+    assert_eq!(vault.balance(&employee).await, 1_000_000);
+}
+
+#[tokio::test]
+async fn test_approver_is_a_second_key_named_by_the_company() {
+    let mut vault = Vault::start().await;
+    let company = vault.payer();
+
+    let result = vault.send(&[set_payroll_approver_instruction(company, company)], &[]).await;
+    assert_vault_error(result, VaultError::InvalidPayrollApprover);
+
+    // A stranger can't name an approver for the company
+    let stranger = Keypair::new();
+    let mut instruction = set_payroll_approver_instruction(stranger.pubkey(), stranger.pubkey());
+    instruction.accounts[0].pubkey = payroll_approver_config(&company);
+    let result = vault.send(&[instruction], &[&stranger]).await;
+    assert_program_error(result, anchor_lang::error::ErrorCode::ConstraintSeeds.into());
+}
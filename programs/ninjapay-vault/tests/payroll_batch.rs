@@ -0,0 +1,242 @@
+mod common;
+
+use anchor_lang::AccountDeserialize;
+use common::{
+    assert_program_error, assert_vault_error, batch_escrow, batch_record, close_batch_record_instruction,
+    disbursement_record, payroll_approver, payroll_batch_instruction, set_payroll_approver_instruction, Vault,
+};
+use ninjapay_vault::{BatchRecord, DisbursementRecord, VaultError, BATCH_RECORD_RETENTION};
+use solana_sdk::{
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    system_instruction,
+};
+
+/// The context payer as the company, with a token account holding `balance`
+/// and `payroll_approver()` as its approver
+async fn company(vault: &mut Vault, balance: u64) -> (Pubkey, Pubkey) {
+    let (mint, company) = (vault.mint, vault.payer());
+    let company_token_account = vault.create_token_account(&mint, &company).await;
+    vault.mint_to(&mint, &company_token_account, balance).await;
+    let approver = payroll_approver().pubkey();
+    vault.send(&[set_payroll_approver_instruction(company, approver)], &[]).await.unwrap();
+    (company, company_token_account)
+}
+
+/// Token accounts in the vault's mint for `count` new employees
+async fn employees(vault: &mut Vault, count: usize) -> Vec<Pubkey> {
+    let mint = vault.mint;
+    let mut accounts = Vec::with_capacity(count);
+    for _ in 0..count {
+        accounts.push(vault.create_token_account(&mint, &Keypair::new().pubkey()).await);
+    }
+    accounts
+}
+
+async fn exists(vault: &mut Vault, address: Pubkey) -> bool {
+    vault.context.banks_client.get_account(address).await.unwrap().is_some()
+}
+
+#[tokio::test]
+async fn test_batch_pays_each_employee_through_escrow() {
+    let mut vault = Vault::start().await;
+    let (company, company_token_account) = company(&mut vault, 3_000_000).await;
+    let approver = payroll_approver();
+    let to = employees(&mut vault, 3).await;
+    let batch_id = [1; 32];
+
+    // The whole balance, to the base unit
+    let pay = [(to[0], 1_500_000), (to[1], 1_000_000), (to[2], 500_000)];
+    let batch = payroll_batch_instruction(company, company_token_account, vault.mint, batch_id, 3_000_000, 3, &pay);
+    vault.send(&[batch], &[&approver]).await.unwrap();
+
+    assert_eq!(vault.balance(&company_token_account).await, 0);
+    assert_eq!(vault.balance(&batch_escrow(&batch_id)).await, 0);
+    for (index, &(employee, amount)) in pay.iter().enumerate() {
+        assert_eq!(vault.balance(&employee).await, amount);
+
+        let address = disbursement_record(&batch_id, index as u16);
+        let account = vault.context.banks_client.get_account(address).await.unwrap().unwrap();
+        let record = DisbursementRecord::try_deserialize(&mut account.data.as_slice()).unwrap();
+        assert_eq!((record.batch_id, record.index), (batch_id, index as u16));
+        assert_eq!((record.employee_token_account, record.amount), (employee, amount));
+    }
+
+    let account = vault.context.banks_client.get_account(batch_record(&batch_id)).await.unwrap().unwrap();
+    let record = BatchRecord::try_deserialize(&mut account.data.as_slice()).unwrap();
+    assert_eq!((record.escrow, record.mint), (batch_escrow(&batch_id), vault.mint));
+    assert_eq!((record.total_amount, record.payment_count), (3_000_000, 3));
+    assert_eq!((record.record_version, record.reserved), (ninjapay_vault::RECORD_VERSION, [0; 64]));
+}
+
+#[tokio::test]
+async fn test_batch_larger_than_the_balance_is_rejected() {
+    let mut vault = Vault::start().await;
+    let (company, company_token_account) = company(&mut vault, 2_999_999).await;
+    let approver = payroll_approver();
+    let to = employees(&mut vault, 1).await;
+    let batch_id = [2; 32];
+
+    let pay = [(to[0], 3_000_000)];
+    let batch = payroll_batch_instruction(company, company_token_account, vault.mint, batch_id, 3_000_000, 1, &pay);
+    let result = vault.send(&[batch], &[&approver]).await;
+
+    assert_vault_error(result, VaultError::InsufficientFunds);
+    assert_eq!(vault.balance(&company_token_account).await, 2_999_999);
+    assert!(!exists(&mut vault, batch_record(&batch_id)).await);
+}
+
+#[tokio::test]
+async fn test_batch_cannot_be_paid_twice() {
+    let mut vault = Vault::start().await;
+    let (company, company_token_account) = company(&mut vault, 5_000_000).await;
+    let approver = payroll_approver();
+    let to = employees(&mut vault, 2).await;
+    let batch_id = [3; 32];
+
+    let pay = [(to[0], 1_000_000), (to[1], 1_000_000)];
+    let batch = payroll_batch_instruction(company, company_token_account, vault.mint, batch_id, 2_000_000, 2, &pay);
+    vault.send(&[batch], &[&approver]).await.unwrap();
+
+    // Same batch, different total: the record and escrow already exist
+    let mint = vault.mint;
+    let again = payroll_batch_instruction(company, company_token_account, mint, batch_id, 1_000_000, 1, &pay[1..]);
+    let result = vault.send(&[again], &[&approver]).await;
+
+    // SystemError::AccountAlreadyInUse
+    assert_program_error(result, 0);
+    assert_eq!(vault.balance(&company_token_account).await, 3_000_000);
+    assert_eq!(vault.balance(&to[1]).await, 1_000_000);
+}
+
+#[tokio::test]
+async fn test_disbursements_must_match_the_batch() {
+    let mut vault = Vault::start().await;
+    let (company, company_token_account) = company(&mut vault, 5_000_000).await;
+    let approver = payroll_approver();
+    let to = employees(&mut vault, 2).await;
+    let mint = vault.mint;
+    let pay = [(to[0], 1_000_000), (to[1], 1_000_000)];
+
+    // Amounts short of the total, and a payment count with no employee behind it
+    let short = payroll_batch_instruction(company, company_token_account, mint, [4; 32], 3_000_000, 2, &pay);
+    let miscounted = payroll_batch_instruction(company, company_token_account, mint, [5; 32], 2_000_000, 3, &pay);
+    // The second employee's disbursement record at the first one's address
+    let mut misplaced = payroll_batch_instruction(company, company_token_account, mint, [6; 32], 2_000_000, 2, &pay);
+    misplaced.accounts[13].pubkey = disbursement_record(&[6; 32], 0);
+
+    for batch in [short, miscounted, misplaced] {
+        let result = vault.send(&[batch], &[&approver]).await;
+        assert_vault_error(result, VaultError::InvalidDisbursement);
+    }
+    assert_eq!(vault.balance(&company_token_account).await, 5_000_000);
+}
+
+#[tokio::test]
+async fn test_one_failed_transfer_fails_the_whole_batch() {
+    let mut vault = Vault::start().await;
+    let (company, company_token_account) = company(&mut vault, 2_000_000).await;
+    let approver = payroll_approver();
+    let to = employees(&mut vault, 1).await;
+    let other_mint = vault.create_mint().await;
+    let elsewhere = vault.create_token_account(&other_mint, &Keypair::new().pubkey()).await;
+    let batch_id = [7; 32];
+
+    let pay = [(to[0], 1_000_000), (elsewhere, 1_000_000)];
+    let batch = payroll_batch_instruction(company, company_token_account, vault.mint, batch_id, 2_000_000, 2, &pay);
+    let result = vault.send(&[batch], &[&approver]).await;
+
+    assert_vault_error(result, VaultError::MintMismatch);
+    assert_eq!(vault.balance(&company_token_account).await, 2_000_000);
+    assert_eq!(vault.balance(&to[0]).await, 0);
+    assert!(!exists(&mut vault, batch_record(&batch_id)).await);
+    assert!(!exists(&mut vault, disbursement_record(&batch_id, 0)).await);
+}
+
+#[tokio::test]
+async fn test_lamports_sent_to_a_disbursement_record_do_not_block_the_batch() {
+    let mut vault = Vault::start().await;
+    let (company, company_token_account) = company(&mut vault, 1_000_000).await;
+    let approver = payroll_approver();
+    let to = employees(&mut vault, 1).await;
+    let batch_id = [8; 32];
+
+    let squat = system_instruction::transfer(&company, &disbursement_record(&batch_id, 0), 1_000_000);
+    vault.send(&[squat], &[]).await.unwrap();
+
+    let pay = [(to[0], 1_000_000)];
+    let batch = payroll_batch_instruction(company, company_token_account, vault.mint, batch_id, 1_000_000, 1, &pay);
+    vault.send(&[batch], &[&approver]).await.unwrap();
+
+    assert_eq!(vault.balance(&to[0]).await, 1_000_000);
+    let account = vault.context.banks_client.get_account(disbursement_record(&batch_id, 0)).await.unwrap().unwrap();
+    assert_eq!(account.owner, ninjapay_vault::ID);
+}
+
+/// Run a one-employee batch of `batch_id` for the context payer; returns
+/// when it ran
+async fn settled_batch(vault: &mut Vault, batch_id: [u8; 32]) -> i64 {
+    let (company, company_token_account) = company(vault, 1_000_000).await;
+    let approver = payroll_approver();
+    let to = employees(vault, 1).await;
+    let pay = [(to[0], 1_000_000)];
+    let batch = payroll_batch_instruction(company, company_token_account, vault.mint, batch_id, 1_000_000, 1, &pay);
+    vault.send(&[batch], &[&approver]).await.unwrap();
+
+    let account = vault.context.banks_client.get_account(batch_record(&batch_id)).await.unwrap().unwrap();
+    BatchRecord::try_deserialize(&mut account.data.as_slice()).unwrap().timestamp
+}
+
+#[tokio::test]
+async fn test_company_closes_a_final_batch_record_and_its_escrow() {
+    let mut vault = Vault::start().await;
+    let batch_id = [9; 32];
+    let ran_at = settled_batch(&mut vault, batch_id).await;
+    let company = vault.payer();
+    let before = vault.lamports(&company).await;
+    let rent = vault.lamports(&batch_record(&batch_id)).await + vault.lamports(&batch_escrow(&batch_id)).await;
+
+    vault.set_time(ran_at + BATCH_RECORD_RETENTION).await;
+    vault.send(&[close_batch_record_instruction(company, batch_id)], &[]).await.unwrap();
+
+    assert!(!exists(&mut vault, batch_record(&batch_id)).await);
+    assert!(!exists(&mut vault, batch_escrow(&batch_id)).await);
+    // Less the transaction fee
+    let gained = vault.lamports(&company).await - before;
+    assert!(gained > rent - 10_000 && gained <= rent);
+    assert!(exists(&mut vault, disbursement_record(&batch_id, 0)).await);
+}
+
+#[tokio::test]
+async fn test_batch_record_is_kept_until_the_batch_is_final() {
+    let mut vault = Vault::start().await;
+    let batch_id = [10; 32];
+    let ran_at = settled_batch(&mut vault, batch_id).await;
+    let company = vault.payer();
+
+    vault.set_time(ran_at + BATCH_RECORD_RETENTION - 1).await;
+    let result = vault.send(&[close_batch_record_instruction(company, batch_id)], &[]).await;
+    assert_vault_error(result, VaultError::BatchNotFinal);
+
+    // Tokens left in the escrow keep it open past the retention period
+    let mint = vault.mint;
+    vault.mint_to(&mint, &batch_escrow(&batch_id), 1).await;
+    vault.set_time(ran_at + BATCH_RECORD_RETENTION).await;
+    let result = vault.send(&[close_batch_record_instruction(company, batch_id)], &[]).await;
+    assert_vault_error(result, VaultError::BatchNotFinal);
+    assert!(exists(&mut vault, batch_record(&batch_id)).await);
+}
+
+#[tokio::test]
+async fn test_only_the_batch_company_closes_its_record() {
+    let mut vault = Vault::start().await;
+    let batch_id = [11; 32];
+    let ran_at = settled_batch(&mut vault, batch_id).await;
+    vault.set_time(ran_at + BATCH_RECORD_RETENTION).await;
+    let stranger = Keypair::new();
+
+    let result = vault.send(&[close_batch_record_instruction(stranger.pubkey(), batch_id)], &[&stranger]).await;
+
+    assert_vault_error(result, VaultError::Unauthorized);
+    assert!(exists(&mut vault, batch_record(&batch_id)).await);
+}
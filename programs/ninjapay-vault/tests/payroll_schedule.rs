@@ -0,0 +1,124 @@
+mod common;
+
+use anchor_lang::AccountDeserialize;
+use common::{
+    assert_program_error, assert_vault_error, create_payroll_schedule_instruction, payroll_approver, payroll_schedule,
+    scheduled_payroll_instruction, set_payroll_approver_instruction, update_payroll_schedule_instruction, Vault,
+    START_TIME,
+};
+use ninjapay_vault::{PayrollSchedule, ScheduleStatus, VaultError};
+use solana_sdk::{
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+};
+
+const WEEK: u64 = 7 * 24 * 60 * 60;
+const FIRST_DUE: i64 = START_TIME + 600;
+const SCHEDULE: [u8; 32] = [9; 32];
+
+/// The context payer as the company, with a 10_000_000 token balance,
+/// `payroll_approver()` as its approver, a weekly schedule of up to
+/// 3_000_000 and two employees
+async fn setup(vault: &mut Vault) -> (Pubkey, Pubkey, Vec<Pubkey>) {
+    let (mint, company) = (vault.mint, vault.payer());
+    let company_token_account = vault.create_token_account(&mint, &company).await;
+    vault.mint_to(&mint, &company_token_account, 10_000_000).await;
+    let approve = set_payroll_approver_instruction(company, payroll_approver().pubkey());
+    let create = create_payroll_schedule_instruction(company, mint, SCHEDULE, WEEK, FIRST_DUE, 3_000_000);
+    vault.send(&[approve, create], &[]).await.unwrap();
+    let mut employees = Vec::new();
+    for _ in 0..2 {
+        employees.push(vault.create_token_account(&mint, &Keypair::new().pubkey()).await);
+    }
+    (company, company_token_account, employees)
+}
+
+async fn schedule(vault: &mut Vault, company: &Pubkey) -> PayrollSchedule {
+    let account = vault.context.banks_client.get_account(payroll_schedule(company, &SCHEDULE)).await.unwrap().unwrap();
+    PayrollSchedule::try_deserialize(&mut account.data.as_slice()).unwrap()
+}
+
+#[tokio::test]
+async fn test_scheduled_payroll_runs_once_due_and_moves_on_a_period() {
+    let mut vault = Vault::start().await;
+    let (company, company_token_account, employees) = setup(&mut vault).await;
+    let approver = payroll_approver();
+    let mint = vault.mint;
+    let pay = [(employees[0], 1_000_000), (employees[1], 2_000_000)];
+
+    let run = scheduled_payroll_instruction(company, company_token_account, mint, SCHEDULE, [1; 32], &pay);
+    let result = vault.send(&[run], &[&approver]).await;
+    assert_vault_error(result, VaultError::ScheduleNotDue);
+
+    vault.set_time(FIRST_DUE).await;
+    let run = scheduled_payroll_instruction(company, company_token_account, mint, SCHEDULE, [2; 32], &pay);
+    vault.send(&[run], &[&approver]).await.unwrap();
+    assert_eq!(vault.balance(&employees[0]).await, 1_000_000);
+    assert_eq!(vault.balance(&employees[1]).await, 2_000_000);
+    assert_eq!(vault.balance(&company_token_account).await, 7_000_000);
+    assert_eq!(schedule(&mut vault, &company).await.next_due_at, FIRST_DUE + WEEK as i64);
+
+    // Not again until next week
+    let run = scheduled_payroll_instruction(company, company_token_account, mint, SCHEDULE, [3; 32], &pay);
+    let result = vault.send(&[run], &[&approver]).await;
+    assert_vault_error(result, VaultError::ScheduleNotDue);
+
+    vault.set_time(FIRST_DUE + WEEK as i64).await;
+    let run = scheduled_payroll_instruction(company, company_token_account, mint, SCHEDULE, [4; 32], &pay);
+    vault.send(&[run], &[&approver]).await.unwrap();
+    assert_eq!(vault.balance(&company_token_account).await, 4_000_000);
+}
+
+#[tokio::test]
+async fn test_runs_are_held_to_the_schedule() {
+    let mut vault = Vault::start().await;
+    let (company, company_token_account, employees) = setup(&mut vault).await;
+    let approver = payroll_approver();
+    let mint = vault.mint;
+    vault.set_time(FIRST_DUE).await;
+
+    let over = [(employees[0], 1_000_000), (employees[1], 2_000_001)];
+    let run = scheduled_payroll_instruction(company, company_token_account, mint, SCHEDULE, [1; 32], &over);
+    let result = vault.send(&[run], &[&approver]).await;
+    assert_vault_error(result, VaultError::ScheduleAmountExceeded);
+
+    // Paid in another mint
+    let other_mint = vault.create_mint().await;
+    let other_account = vault.create_token_account(&other_mint, &company).await;
+    let employee = vault.create_token_account(&other_mint, &Keypair::new().pubkey()).await;
+    vault.mint_to(&other_mint, &other_account, 1_000_000).await;
+    let run = scheduled_payroll_instruction(company, other_account, other_mint, SCHEDULE, [2; 32], &[(employee, 1)]);
+    let result = vault.send(&[run], &[&approver]).await;
+    assert_vault_error(result, VaultError::MintMismatch);
+
+    let paused = ScheduleStatus::Paused;
+    let pause = update_payroll_schedule_instruction(company, SCHEDULE, WEEK, FIRST_DUE, 3_000_000, paused);
+    vault.send(&[pause], &[]).await.unwrap();
+    let pay = [(employees[0], 1_000_000)];
+    let run = scheduled_payroll_instruction(company, company_token_account, mint, SCHEDULE, [3; 32], &pay);
+    let result = vault.send(&[run], &[&approver]).await;
+    assert_vault_error(result, VaultError::ScheduleNotActive);
+    assert_eq!(vault.balance(&company_token_account).await, 10_000_000);
+}
+
+#[tokio::test]
+async fn test_only_the_company_updates_its_schedule() {
+    let mut vault = Vault::start().await;
+    let (company, _, _) = setup(&mut vault).await;
+    let stranger = Keypair::new();
+
+    let mut update =
+        update_payroll_schedule_instruction(stranger.pubkey(), SCHEDULE, 1, 0, u64::MAX, ScheduleStatus::Active);
+    update.accounts[0].pubkey = payroll_schedule(&company, &SCHEDULE);
+    let result = vault.send(&[update], &[&stranger]).await;
+    assert_program_error(result, anchor_lang::error::ErrorCode::ConstraintSeeds.into());
+
+    let active = ScheduleStatus::Active;
+    let update = update_payroll_schedule_instruction(company, SCHEDULE, 0, FIRST_DUE, 3_000_000, active);
+    let result = vault.send(&[update], &[]).await;
+    assert_vault_error(result, VaultError::InvalidSchedule);
+
+    let updated = schedule(&mut vault, &company).await;
+    assert_eq!((updated.frequency_seconds, updated.authorized_amount), (WEEK, 3_000_000));
+    assert!(updated.status == ScheduleStatus::Active);
+}
@@ -0,0 +1,309 @@
+mod common;
+
+use anchor_lang::AccountDeserialize;
+use common::{assert_program_error, assert_vault_error, payment_record, vault_config, Vault};
+use ninjapay_vault::{payment_amounts, split_payment, FeeMode, PaymentRecord, VaultConfig, VaultError};
+use solana_sdk::signature::{Keypair, Signer};
+
+#[tokio::test]
+async fn test_payment_splits_fee_to_collector() {
+    let mut vault = Vault::start().await;
+    let payment = vault.payment().await;
+
+    vault.send(&[payment.instruction(1_000_000, [1; 32])], &[]).await.unwrap();
+
+    // 50 bps of 1_000_000
+    assert_eq!(vault.balance(&payment.merchant_token_account).await, 995_000);
+    assert_eq!(vault.balance(&vault.fee_token_account.clone()).await, 5_000);
+    assert_eq!(vault.balance(&payment.payer_token_account).await, 9_000_000);
+
+    let address = payment_record(&vault.payer(), &[1; 32]);
+    let record = vault.context.banks_client.get_account(address).await.unwrap().unwrap();
+    let record = PaymentRecord::try_deserialize(&mut record.data.as_slice()).unwrap();
+    assert_eq!((record.mint, record.record_version), (vault.mint, ninjapay_vault::RECORD_VERSION));
+}
+
+#[tokio::test]
+async fn test_exact_net_payment_adds_the_fee_on_top() {
+    let mut vault = Vault::start().await;
+    let mut payment = vault.payment().await;
+
+    // The same 1_000_000 at 50 bps, deducted then on top
+    vault.send(&[payment.instruction(1_000_000, [1; 32])], &[]).await.unwrap();
+    assert_eq!(vault.balance(&payment.merchant_token_account).await, 995_000);
+    payment.fee_mode = FeeMode::ExactNet;
+    vault.send(&[payment.instruction(1_000_000, [2; 32])], &[]).await.unwrap();
+    assert_eq!(vault.balance(&payment.merchant_token_account).await, 995_000 + 1_000_000);
+    assert_eq!(vault.balance(&payment.payer_token_account).await, 10_000_000 - 1_000_000 - 1_005_000);
+    assert_eq!(vault.balance(&vault.fee_token_account.clone()).await, 5_000 + 5_000);
+
+    for (payment_id, fee_mode, gross, net) in [
+        ([1; 32], FeeMode::Deducted, 1_000_000, 995_000),
+        ([2; 32], FeeMode::ExactNet, 1_005_000, 1_000_000),
+    ] {
+        let address = payment_record(&payment.payer, &payment_id);
+        let account = vault.context.banks_client.get_account(address).await.unwrap().unwrap();
+        let record = PaymentRecord::try_deserialize(&mut account.data.as_slice()).unwrap();
+        assert_eq!((record.amount, record.net_amount, record.fee), (gross, net, 5_000));
+        assert!(record.fee_mode == fee_mode);
+    }
+
+    // Volume counts what left the payer
+    let account = vault.context.banks_client.get_account(vault_config()).await.unwrap().unwrap();
+    let config = VaultConfig::try_deserialize(&mut account.data.as_slice()).unwrap();
+    assert_eq!(config.total_volume, 2_005_000);
+}
+
+#[tokio::test]
+async fn test_exact_net_payment_beyond_the_payers_balance_fails() {
+    let mut vault = Vault::start().await;
+    let mut payment = vault.payment().await;
+    payment.fee_mode = FeeMode::ExactNet;
+
+    // 10_000_000 is the whole balance, leaving nothing for the fee on top
+    let result = vault.send(&[payment.instruction(10_000_000, [1; 32])], &[]).await;
+    assert!(result.is_err());
+    assert_eq!(vault.balance(&payment.payer_token_account).await, 10_000_000);
+}
+
+#[tokio::test]
+async fn test_zero_fee_vault_pays_the_merchant_in_full() {
+    let mut vault = Vault::start().await;
+    let payment = vault.payment().await;
+    vault.edit_vault_config(|config| config.fee_basis_points = 0).await;
+
+    vault.send(&[payment.instruction(1_000_000, [1; 32])], &[]).await.unwrap();
+
+    assert_eq!(vault.balance(&payment.merchant_token_account).await, 1_000_000);
+    assert_eq!(vault.balance(&vault.fee_token_account.clone()).await, 0);
+}
+
+#[tokio::test]
+async fn test_fee_on_the_smallest_payments_rounds_down() {
+    let mut vault = Vault::start().await;
+    let payment = vault.payment().await;
+
+    // 50 bps: no fee below 200 base units, 1 from 200
+    for (id, amount) in [(1, 1), (2, 199), (3, 200)] {
+        vault.send(&[payment.instruction(amount, [id; 32])], &[]).await.unwrap();
+    }
+
+    assert_eq!(vault.balance(&payment.merchant_token_account).await, 1 + 199 + 199);
+    assert_eq!(vault.balance(&vault.fee_token_account.clone()).await, 1);
+    assert_eq!(vault.balance(&payment.payer_token_account).await, 10_000_000 - 400);
+}
+
+#[tokio::test]
+async fn test_duplicate_payment_id_is_rejected() {
+    let mut vault = Vault::start().await;
+    let payment = vault.payment().await;
+
+    vault.send(&[payment.instruction(1_000_000, [1; 32])], &[]).await.unwrap();
+    let result = vault.send(&[payment.instruction(2_000_000, [1; 32])], &[]).await;
+
+    // SystemError::AccountAlreadyInUse: the payment record exists
+    assert_program_error(result, 0);
+    assert_eq!(vault.balance(&payment.payer_token_account).await, 9_000_000);
+}
+
+#[tokio::test]
+async fn test_payment_rejects_fee_account_of_another_owner() {
+    let mut vault = Vault::start().await;
+    let mut payment = vault.payment().await;
+
+    // The payer routes the fee back to themselves
+    let mint = vault.mint;
+    let payer = vault.payer();
+    payment.fee_token_account = vault.create_token_account(&mint, &payer).await;
+
+    let result = vault.send(&[payment.instruction(1_000_000, [2; 32])], &[]).await;
+
+    assert_vault_error(result, VaultError::InvalidFeeAccount);
+    assert_eq!(vault.balance(&payment.payer_token_account).await, 10_000_000);
+}
+
+#[tokio::test]
+async fn test_payment_rejects_fee_account_of_another_mint() {
+    let mut vault = Vault::start().await;
+    let mut payment = vault.payment().await;
+
+    let other_mint = vault.create_mint().await;
+    let collector = vault.fee_collector.pubkey();
+    payment.fee_token_account = vault.create_token_account(&other_mint, &collector).await;
+
+    let result = vault.send(&[payment.instruction(1_000_000, [3; 32])], &[]).await;
+
+    assert_vault_error(result, VaultError::InvalidFeeAccount);
+    assert_eq!(vault.balance(&payment.payer_token_account).await, 10_000_000);
+}
+
+#[tokio::test]
+async fn test_payment_rejects_token_account_of_another_merchant() {
+    let mut vault = Vault::start().await;
+    let mut payment = vault.payment().await;
+
+    // Records merchant A while paying merchant B
+    let mint = vault.mint;
+    let other_merchant = Keypair::new();
+    payment.merchant_token_account = vault.create_token_account(&mint, &other_merchant.pubkey()).await;
+
+    let result = vault.send(&[payment.instruction(1_000_000, [4; 32])], &[]).await;
+
+    assert_vault_error(result, VaultError::MerchantAccountMismatch);
+    assert_eq!(vault.balance(&payment.merchant_token_account).await, 0);
+}
+
+#[tokio::test]
+async fn test_payment_rejects_merchant_paying_into_payer_account() {
+    let mut vault = Vault::start().await;
+    let mut payment = vault.payment().await;
+
+    // The net amount would land back in the payer's own account
+    payment.merchant_token_account = payment.payer_token_account;
+
+    let result = vault.send(&[payment.instruction(1_000_000, [5; 32])], &[]).await;
+
+    assert_vault_error(result, VaultError::MerchantAccountMismatch);
+}
+
+#[tokio::test]
+async fn test_payment_rejects_merchant_account_of_another_mint() {
+    let mut vault = Vault::start().await;
+    let mut payment = vault.payment().await;
+
+    // Paid in one token, received in another
+    let other_mint = vault.create_mint().await;
+    let merchant = payment.merchant.pubkey();
+    payment.merchant_token_account = vault.create_token_account(&other_mint, &merchant).await;
+
+    let result = vault.send(&[payment.instruction(1_000_000, [6; 32])], &[]).await;
+
+    assert_vault_error(result, VaultError::MintMismatch);
+    assert_eq!(vault.balance(&payment.payer_token_account).await, 10_000_000);
+}
+
+#[tokio::test]
+async fn test_payment_rejects_mint_other_than_the_payers() {
+    let mut vault = Vault::start().await;
+    let mut payment = vault.payment().await;
+
+    // Decimals would be read from a mint the tokens aren't in
+    payment.mint = vault.create_mint_with_decimals(9).await;
+
+    let result = vault.send(&[payment.instruction(1_000_000, [7; 32])], &[]).await;
+
+    assert_vault_error(result, VaultError::MintMismatch);
+    assert_eq!(vault.balance(&payment.payer_token_account).await, 10_000_000);
+}
+
+#[tokio::test]
+async fn test_payment_transfers_are_checked_for_6_and_9_decimal_mints() {
+    let mut vault = Vault::start().await;
+
+    for (decimals, payment_id) in [(6, [8; 32]), (9, [9; 32])] {
+        let mint = vault.create_mint_with_decimals(decimals).await;
+        let collector = vault.fee_collector.pubkey();
+        let fee_token_account = vault.create_token_account(&mint, &collector).await;
+        let payment = vault.payment_in(mint, fee_token_account).await;
+
+        vault.send(&[payment.instruction(1_000_000, payment_id)], &[]).await.unwrap();
+
+        assert_eq!(vault.balance(&payment.merchant_token_account).await, 995_000, "{} decimals", decimals);
+        assert_eq!(vault.balance(&fee_token_account).await, 5_000, "{} decimals", decimals);
+        let address = payment_record(&vault.payer(), &payment_id);
+        let record = vault.context.banks_client.get_account(address).await.unwrap().unwrap();
+        let record = PaymentRecord::try_deserialize(&mut record.data.as_slice()).unwrap();
+        assert_eq!(record.mint, mint);
+    }
+}
+
+#[tokio::test]
+async fn test_payment_rejects_zero_amount() {
+    let mut vault = Vault::start().await;
+    let payment = vault.payment().await;
+
+    let result = vault.send(&[payment.instruction(0, [9; 32])], &[]).await;
+
+    assert_vault_error(result, VaultError::InvalidAmount);
+}
+
+#[tokio::test]
+async fn test_payment_smaller_than_its_fee_is_rejected() {
+    let mut vault = Vault::start().await;
+    let payment = vault.payment().await;
+    // Above 100%: update_fee can't set this, but initialize doesn't check
+    vault.edit_vault_config(|config| config.fee_basis_points = 20_000).await;
+
+    let result = vault.send(&[payment.instruction(1_000_000, [10; 32])], &[]).await;
+
+    assert_vault_error(result, VaultError::AmountTooSmall);
+    assert_eq!(vault.balance(&payment.payer_token_account).await, 10_000_000);
+}
+
+#[tokio::test]
+async fn test_volume_overflow_fails_the_payment() {
+    let mut vault = Vault::start().await;
+    let payment = vault.payment().await;
+    vault.edit_vault_config(|config| config.total_volume = u64::MAX - 999_999).await;
+
+    // Exactly reaching u64::MAX is fine; one more base unit is not
+    vault.send(&[payment.instruction(999_999, [11; 32])], &[]).await.unwrap();
+    let result = vault.send(&[payment.instruction(1, [12; 32])], &[]).await;
+    assert_vault_error(result, VaultError::MathOverflow);
+
+    let account = vault.context.banks_client.get_account(vault_config()).await.unwrap().unwrap();
+    let config = VaultConfig::try_deserialize(&mut account.data.as_slice()).unwrap();
+    assert_eq!((config.total_volume, config.total_payments), (u64::MAX, 1));
+}
+
+#[tokio::test]
+async fn test_observed_payment_id_cannot_be_squatted() {
+    let mut vault = Vault::start().await;
+    let payment = vault.payment().await;
+    let attacker = Keypair::new();
+    let squat = vault.payment_by(&attacker).await;
+    let payment_id = [13; 32];
+
+    // Creating the payer's record from another payer fails the seeds check
+    let mut instruction = squat.instruction(1_000, payment_id);
+    instruction.accounts[1].pubkey = payment_record(&payment.payer, &payment_id);
+    let result = vault.send(&[instruction], &[&attacker]).await;
+    assert_program_error(result, anchor_lang::error::ErrorCode::ConstraintSeeds.into());
+
+    // Paying first with the same payment_id only creates the attacker's own
+    // record, and the real payment still goes through
+    vault.send(&[squat.instruction(1_000, payment_id)], &[&attacker]).await.unwrap();
+    vault.send(&[payment.instruction(1_000_000, payment_id)], &[]).await.unwrap();
+
+    assert_eq!(vault.balance(&payment.merchant_token_account).await, 995_000);
+    let records = [(payment.payer, payment.merchant.pubkey()), (attacker.pubkey(), squat.merchant.pubkey())];
+    for (payer, merchant) in records {
+        let address = payment_record(&payer, &payment_id);
+        let account = vault.context.banks_client.get_account(address).await.unwrap().unwrap();
+        let record = PaymentRecord::try_deserialize(&mut account.data.as_slice()).unwrap();
+        assert_eq!((record.payer, record.merchant, record.payment_id), (payer, merchant, payment_id));
+    }
+}
+
+#[test]
+fn test_split_payment() {
+    assert_eq!(split_payment(1_000_000, 50).unwrap(), (5_000, 995_000));
+    // Rounded down, never up
+    assert_eq!(split_payment(199, 50).unwrap(), (0, 199));
+    assert_eq!(split_payment(u64::MAX, 1_000).unwrap(), (u64::MAX / 10, u64::MAX - u64::MAX / 10));
+    assert_eq!(split_payment(u64::MAX, 10_000).unwrap(), (u64::MAX, 0));
+
+    assert_eq!(split_payment(100, 20_000).unwrap_err(), VaultError::AmountTooSmall.into());
+    assert_eq!(split_payment(u64::MAX, 20_000).unwrap_err(), VaultError::MathOverflow.into());
+}
+
+#[test]
+fn test_payment_amounts() {
+    assert_eq!(payment_amounts(1_000_000, 50, FeeMode::Deducted, false).unwrap(), (1_000_000, 5_000, 995_000));
+    assert_eq!(payment_amounts(1_000_000, 50, FeeMode::ExactNet, false).unwrap(), (1_005_000, 5_000, 1_000_000));
+    assert_eq!(payment_amounts(u64::MAX, 0, FeeMode::ExactNet, false).unwrap(), (u64::MAX, 0, u64::MAX));
+
+    // The fee on top can't overflow the gross amount
+    let overflow = payment_amounts(u64::MAX - 4_999, 50, FeeMode::ExactNet, false).unwrap_err();
+    assert_eq!(overflow, VaultError::MathOverflow.into());
+}
@@ -0,0 +1,90 @@
+mod common;
+
+use anchor_lang::AccountDeserialize;
+use common::{
+    assert_program_error, assert_vault_error, payer_rate_limit, set_rate_limit_config_instruction, Vault,
+    FEE_BASIS_POINTS,
+};
+use ninjapay_vault::{PayerRateLimit, VaultError};
+use solana_sdk::{
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+};
+
+async fn rate_limit(vault: &mut Vault, payer: &Pubkey) -> PayerRateLimit {
+    let account = vault.context.banks_client.get_account(payer_rate_limit(payer)).await.unwrap().unwrap();
+    PayerRateLimit::try_deserialize(&mut account.data.as_slice()).unwrap()
+}
+
+#[tokio::test]
+async fn test_payments_over_the_hourly_count_are_rejected_until_the_window_resets() {
+    let mut vault = Vault::start().await;
+    let payment = vault.payment().await;
+    let authority = vault.payer();
+    vault.send(&[set_rate_limit_config_instruction(authority, 3, 0)], &[]).await.unwrap();
+
+    for id in 1..=3 {
+        vault.send(&[payment.instruction(1_000, [id; 32])], &[]).await.unwrap();
+    }
+    let result = vault.send(&[payment.instruction(1_000, [4; 32])], &[]).await;
+    assert_vault_error(result, VaultError::RateLimitExceeded);
+
+    let limit = rate_limit(&mut vault, &payment.payer).await;
+    assert_eq!((limit.payer, limit.payments_this_window, limit.volume_this_window), (payment.payer, 3, 3_000));
+
+    // The window is open for a full hour, then starts over
+    vault.set_time(limit.window_start + 3_600).await;
+    let result = vault.send(&[payment.instruction(1_000, [5; 32])], &[]).await;
+    assert_vault_error(result, VaultError::RateLimitExceeded);
+
+    vault.set_time(limit.window_start + 3_601).await;
+    vault.send(&[payment.instruction(1_000, [6; 32])], &[]).await.unwrap();
+    let limit = rate_limit(&mut vault, &payment.payer).await;
+    assert_eq!((limit.payments_this_window, limit.volume_this_window), (1, 1_000));
+}
+
+#[tokio::test]
+async fn test_payments_over_the_hourly_volume_are_rejected() {
+    let mut vault = Vault::start().await;
+    let payment = vault.payment().await;
+    let authority = vault.payer();
+    vault.send(&[set_rate_limit_config_instruction(authority, 0, 2_000_000)], &[]).await.unwrap();
+
+    vault.send(&[payment.instruction(1_500_000, [1; 32])], &[]).await.unwrap();
+    let result = vault.send(&[payment.instruction(500_001, [2; 32])], &[]).await;
+    assert_vault_error(result, VaultError::RateLimitExceeded);
+
+    // Exactly reaching the cap is fine
+    vault.send(&[payment.instruction(500_000, [3; 32])], &[]).await.unwrap();
+    assert_eq!(vault.balance(&payment.payer_token_account).await, 8_000_000);
+}
+
+#[tokio::test]
+async fn test_limits_apply_per_payer_and_to_split_payments() {
+    let mut vault = Vault::start().await;
+    let payment = vault.payment().await;
+    let other_payer = Keypair::new();
+    let other = vault.payment_by(&other_payer).await;
+    let authority = vault.payer();
+    vault.send(&[set_rate_limit_config_instruction(authority, 1, 0)], &[]).await.unwrap();
+
+    vault.send(&[payment.instruction(1_000_000, [1; 32])], &[]).await.unwrap();
+    let recipient = payment.merchant_token_account;
+    let split = payment.split_instruction(1_000_000, [2; 32], &[(10_000 - FEE_BASIS_POINTS, recipient)]);
+    let result = vault.send(&[split], &[]).await;
+    assert_vault_error(result, VaultError::RateLimitExceeded);
+
+    // Another payer has a window of its own
+    vault.send(&[other.instruction(1_000_000, [1; 32])], &[&other_payer]).await.unwrap();
+    assert_eq!(rate_limit(&mut vault, &other.payer).await.payments_this_window, 1);
+}
+
+#[tokio::test]
+async fn test_only_the_authority_sets_rate_limits() {
+    let mut vault = Vault::start().await;
+    let stranger = Keypair::new();
+
+    let result = vault.send(&[set_rate_limit_config_instruction(stranger.pubkey(), 1, 1)], &[&stranger]).await;
+
+    assert_program_error(result, anchor_lang::error::ErrorCode::ConstraintHasOne.into());
+}
@@ -0,0 +1,149 @@
+mod common;
+
+use anchor_lang::{AccountDeserialize, InstructionData, Space, ToAccountMetas};
+use common::{assert_program_error, payment_record, vault_config, Vault};
+use ninjapay_vault::{PaymentRecord, PaymentStatus, MAX_FEE_COLLECTORS, RECORD_RESERVED_LEN, RECORD_VERSION};
+use solana_sdk::{
+    instruction::Instruction,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    system_program,
+};
+
+const CURRENT_LEN: usize = 8 + PaymentRecord::INIT_SPACE;
+/// Before `record_version` and the reserved tail
+const UNVERSIONED_LEN: usize = PaymentRecord::RECORD_VERSION_OFFSET;
+/// Version 1: the reserved tail, without the fee's division among collectors
+const V1_LEN: usize = UNVERSIONED_LEN + 1 + RECORD_RESERVED_LEN;
+/// Before refunds added `status`, and everything after it
+const PRE_STATUS_LEN: usize = 8 + 32 + 32 + 32 + 8 + 8 + 32 + 8 + 1;
+
+fn resize_instruction(authority: Pubkey, record: Pubkey) -> Instruction {
+    Instruction {
+        program_id: ninjapay_vault::ID,
+        accounts: ninjapay_vault::accounts::ResizePaymentRecord {
+            vault_config: vault_config(),
+            payment_record: record,
+            authority,
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None),
+        data: ninjapay_vault::instruction::ResizePaymentRecord {}.data(),
+    }
+}
+
+/// Pay, then cut the record back to `len` bytes and the rent of that size,
+/// as if it had been written by an older program
+async fn legacy_record(vault: &mut Vault, payment_id: [u8; 32], len: usize) -> Pubkey {
+    let payment = vault.payment().await;
+    vault.send(&[payment.instruction(1_000_000, payment_id)], &[]).await.unwrap();
+
+    let address = payment_record(&payment.payer, &payment_id);
+    let rent = vault.context.banks_client.get_rent().await.unwrap();
+    let mut account = vault.context.banks_client.get_account(address).await.unwrap().unwrap();
+    account.data.truncate(len);
+    account.lamports = rent.minimum_balance(len);
+    vault.context.set_account(&address, &account.into());
+    address
+}
+
+async fn record(vault: &mut Vault, address: Pubkey) -> (usize, u64, Result<PaymentRecord, anchor_lang::error::Error>) {
+    let account = vault.context.banks_client.get_account(address).await.unwrap().unwrap();
+    let record = PaymentRecord::try_deserialize(&mut account.data.as_slice());
+    (account.data.len(), account.lamports, record)
+}
+
+#[tokio::test]
+async fn test_new_records_are_versioned_and_padded() {
+    let mut vault = Vault::start().await;
+    let payment = vault.payment().await;
+    vault.send(&[payment.instruction(1_000_000, [1; 32])], &[]).await.unwrap();
+
+    let (len, _, record) = record(&mut vault, payment_record(&payment.payer, &[1; 32])).await;
+    let record = record.unwrap();
+    assert_eq!(len, CURRENT_LEN);
+    assert_eq!((record.record_version, record.reserved), (RECORD_VERSION, [0; RECORD_RESERVED_LEN - 17]));
+    assert_eq!(record.refunded_amount, 0);
+}
+
+#[tokio::test]
+async fn test_unversioned_record_is_resized_with_a_rent_top_up() {
+    let mut vault = Vault::start().await;
+    let authority = vault.payer();
+    let address = legacy_record(&mut vault, [2; 32], UNVERSIONED_LEN).await;
+    let rent = vault.context.banks_client.get_rent().await.unwrap();
+
+    let (_, lamports, legacy) = record(&mut vault, address).await;
+    assert!(legacy.is_err());
+
+    let stranger = Keypair::new();
+    let result = vault.send(&[resize_instruction(stranger.pubkey(), address)], &[&stranger]).await;
+    assert_program_error(result, anchor_lang::error::ErrorCode::ConstraintHasOne.into());
+
+    let authority_lamports = vault.lamports(&authority).await;
+    vault.send(&[resize_instruction(authority, address)], &[]).await.unwrap();
+
+    let (len, resized_lamports, resized) = record(&mut vault, address).await;
+    let resized = resized.unwrap();
+    assert_eq!((len, resized_lamports), (CURRENT_LEN, rent.minimum_balance(CURRENT_LEN)));
+    // The authority paid the difference, plus the transaction fee
+    let top_up = resized_lamports - lamports;
+    assert!(authority_lamports - vault.lamports(&authority).await >= top_up);
+    assert_eq!((resized.payment_id, resized.amount, resized.fee), ([2; 32], 1_000_000, 5_000));
+    assert_eq!(resized.mint, vault.mint);
+    assert!(resized.status == PaymentStatus::Settled);
+    assert_eq!((resized.record_version, resized.reserved), (RECORD_VERSION, [0; RECORD_RESERVED_LEN - 17]));
+
+    // Resizing a current record changes nothing
+    vault.send(&[resize_instruction(authority, address)], &[]).await.unwrap();
+    assert_eq!(record(&mut vault, address).await.0, CURRENT_LEN);
+}
+
+#[tokio::test]
+async fn test_fields_a_record_predates_read_as_zero() {
+    let mut vault = Vault::start().await;
+    let authority = vault.payer();
+    let address = legacy_record(&mut vault, [3; 32], PRE_STATUS_LEN).await;
+
+    vault.send(&[resize_instruction(authority, address)], &[]).await.unwrap();
+
+    let (len, _, resized) = record(&mut vault, address).await;
+    let resized = resized.unwrap();
+    assert_eq!(len, CURRENT_LEN);
+    assert_eq!(resized.amount, 1_000_000);
+    // Zero is Settled, which every open pre-refund record was
+    assert!(resized.status == PaymentStatus::Settled);
+    assert_eq!((resized.mint, resized.expires_at), (Pubkey::default(), 0));
+    assert_eq!(resized.record_version, RECORD_VERSION);
+}
+
+#[tokio::test]
+async fn test_version_1_record_is_resized_and_its_fee_reads_as_the_first_collectors() {
+    let mut vault = Vault::start().await;
+    let authority = vault.payer();
+    let address = legacy_record(&mut vault, [4; 32], V1_LEN).await;
+    let mut account = vault.context.banks_client.get_account(address).await.unwrap().unwrap();
+    account.data[UNVERSIONED_LEN] = 1;
+    vault.context.set_account(&address, &account.into());
+
+    vault.send(&[resize_instruction(authority, address)], &[]).await.unwrap();
+
+    let (len, _, resized) = record(&mut vault, address).await;
+    let resized = resized.unwrap();
+    assert_eq!((len, resized.record_version), (CURRENT_LEN, RECORD_VERSION));
+    assert_eq!(resized.fee_shares, [0; MAX_FEE_COLLECTORS]);
+    let collector = vault.fee_collector.pubkey();
+    let split = resized.fee_split(collector);
+    assert_eq!(split[0], (collector, 5_000));
+    assert_eq!(split[1..], [(Pubkey::default(), 0); MAX_FEE_COLLECTORS - 1]);
+}
+
+#[tokio::test]
+async fn test_only_payment_records_are_resized() {
+    let mut vault = Vault::start().await;
+    let authority = vault.payer();
+
+    let result = vault.send(&[resize_instruction(authority, vault_config())], &[]).await;
+
+    assert_program_error(result, anchor_lang::error::ErrorCode::AccountDiscriminatorMismatch.into());
+}
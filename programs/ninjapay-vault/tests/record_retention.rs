@@ -0,0 +1,130 @@
+mod common;
+
+use common::{
+    assert_program_error, assert_vault_error, close_payment_record_instruction, compliance_close_instruction,
+    open_dispute_instruction, payment_record, set_retention_days_instruction, Vault, START_TIME,
+};
+use ninjapay_vault::{DailyVolumeRecord, VaultError, MIN_RETENTION_DAYS};
+use solana_sdk::{
+    compute_budget::ComputeBudgetInstruction,
+    signature::{Keypair, Signer},
+};
+
+const DAY: i64 = DailyVolumeRecord::DAY_SECS;
+
+#[tokio::test]
+async fn test_payer_reclaims_rent_once_the_retention_period_has_passed() {
+    let mut vault = Vault::start().await;
+    let payer = Keypair::new();
+    let payment = vault.payment_by(&payer).await;
+    vault.send(&[payment.instruction(1_000_000, [1; 32])], &[&payer]).await.unwrap();
+    let address = payment_record(&payer.pubkey(), &[1; 32]);
+    let rent = vault.lamports(&address).await;
+    let before = vault.lamports(&payer.pubkey()).await;
+
+    let result = vault.send(&[close_payment_record_instruction(payer.pubkey(), [1; 32])], &[&payer]).await;
+    assert_vault_error(result, VaultError::RetentionPeriodNotElapsed);
+
+    // Exactly MIN_RETENTION_DAYS old is still retained. Compute budgets keep
+    // the retries from repeating a transaction already processed.
+    vault.set_time(START_TIME + MIN_RETENTION_DAYS as i64 * DAY).await;
+    let budget = ComputeBudgetInstruction::set_compute_unit_limit(200_001);
+    let close = close_payment_record_instruction(payer.pubkey(), [1; 32]);
+    let result = vault.send(&[budget, close], &[&payer]).await;
+    assert_vault_error(result, VaultError::RetentionPeriodNotElapsed);
+
+    vault.set_time(START_TIME + MIN_RETENTION_DAYS as i64 * DAY + 1).await;
+    let budget = ComputeBudgetInstruction::set_compute_unit_limit(200_002);
+    let close = close_payment_record_instruction(payer.pubkey(), [1; 32]);
+    vault.send(&[budget, close], &[&payer]).await.unwrap();
+
+    assert!(vault.context.banks_client.get_account(address).await.unwrap().is_none());
+    assert_eq!(vault.lamports(&payer.pubkey()).await, before + rent);
+}
+
+#[tokio::test]
+async fn test_only_the_payer_closes_a_record() {
+    let mut vault = Vault::start().await;
+    let payment = vault.payment().await;
+    vault.send(&[payment.instruction(1_000_000, [1; 32])], &[]).await.unwrap();
+    vault.set_time(START_TIME + 365 * DAY).await;
+    let stranger = Keypair::new();
+
+    let mut instruction = close_payment_record_instruction(stranger.pubkey(), [1; 32]);
+    instruction.accounts[1].pubkey = payment_record(&payment.payer, &[1; 32]);
+    let result = vault.send(&[instruction], &[&stranger]).await;
+
+    assert_program_error(result, anchor_lang::error::ErrorCode::ConstraintSeeds.into());
+}
+
+#[tokio::test]
+async fn test_authority_lengthens_the_retention_period() {
+    let mut vault = Vault::start().await;
+    let payment = vault.payment().await;
+    let authority = vault.payer();
+    vault.send(&[payment.instruction(1_000_000, [1; 32])], &[]).await.unwrap();
+
+    let result = vault.send(&[set_retention_days_instruction(authority, MIN_RETENTION_DAYS - 1)], &[]).await;
+    assert_vault_error(result, VaultError::RetentionTooShort);
+    let stranger = Keypair::new();
+    let result = vault.send(&[set_retention_days_instruction(stranger.pubkey(), 90)], &[&stranger]).await;
+    assert_program_error(result, anchor_lang::error::ErrorCode::ConstraintHasOne.into());
+
+    vault.send(&[set_retention_days_instruction(authority, 90)], &[]).await.unwrap();
+    vault.set_time(START_TIME + 60 * DAY).await;
+    let result = vault.send(&[close_payment_record_instruction(payment.payer, [1; 32])], &[]).await;
+    assert_vault_error(result, VaultError::RetentionPeriodNotElapsed);
+
+    vault.set_time(START_TIME + 91 * DAY).await;
+    let budget = ComputeBudgetInstruction::set_compute_unit_limit(200_001);
+    vault.send(&[budget, close_payment_record_instruction(payment.payer, [1; 32])], &[]).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_a_migrated_zero_retention_reads_as_the_minimum() {
+    let mut vault = Vault::start().await;
+    let payment = vault.payment().await;
+    vault.send(&[payment.instruction(1_000_000, [1; 32])], &[]).await.unwrap();
+    vault.edit_vault_config(|config| config.retention_days = 0).await;
+
+    vault.set_time(START_TIME + DAY).await;
+    let result = vault.send(&[close_payment_record_instruction(payment.payer, [1; 32])], &[]).await;
+
+    assert_vault_error(result, VaultError::RetentionPeriodNotElapsed);
+}
+
+#[tokio::test]
+async fn test_authority_closes_a_record_early_and_the_payer_gets_the_rent() {
+    let mut vault = Vault::start().await;
+    let payer = Keypair::new();
+    let payment = vault.payment_by(&payer).await;
+    vault.send(&[payment.instruction(1_000_000, [1; 32])], &[&payer]).await.unwrap();
+    let address = payment_record(&payer.pubkey(), &[1; 32]);
+    let rent = vault.lamports(&address).await;
+    let before = vault.lamports(&payer.pubkey()).await;
+
+    let stranger = Keypair::new();
+    let close = compliance_close_instruction(stranger.pubkey(), payer.pubkey(), [1; 32]);
+    let result = vault.send(&[close], &[&stranger]).await;
+    assert_program_error(result, anchor_lang::error::ErrorCode::ConstraintHasOne.into());
+
+    // No retention period for the authority; the context payer signs as it
+    // and pays the transaction fee
+    let authority = vault.payer();
+    vault.send(&[compliance_close_instruction(authority, payer.pubkey(), [1; 32])], &[]).await.unwrap();
+    assert!(vault.context.banks_client.get_account(address).await.unwrap().is_none());
+    assert_eq!(vault.lamports(&payer.pubkey()).await, before + rent);
+}
+
+#[tokio::test]
+async fn test_authority_cannot_close_a_disputed_record() {
+    let mut vault = Vault::start().await;
+    let payment = vault.payment().await;
+    let authority = vault.payer();
+    vault.send(&[payment.instruction(1_000_000, [1; 32])], &[]).await.unwrap();
+    vault.send(&[open_dispute_instruction(payment.payer, [1; 32])], &[]).await.unwrap();
+
+    let result = vault.send(&[compliance_close_instruction(authority, payment.payer, [1; 32])], &[]).await;
+    assert_vault_error(result, VaultError::PaymentUnderDispute);
+    assert!(vault.context.banks_client.get_account(payment_record(&payment.payer, &[1; 32])).await.unwrap().is_some());
+}
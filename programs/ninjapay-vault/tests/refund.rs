@@ -0,0 +1,178 @@
+mod common;
+
+use anchor_lang::AccountDeserialize;
+use common::{assert_vault_error, payment_record, Vault};
+use ninjapay_vault::{PaymentRecord, PaymentStatus, VaultError};
+use solana_sdk::{
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+};
+
+async fn record(vault: &mut Vault, payer: &Pubkey, payment_id: [u8; 32]) -> Option<PaymentRecord> {
+    let account = vault.context.banks_client.get_account(payment_record(payer, &payment_id)).await.unwrap();
+    account.map(|account| PaymentRecord::try_deserialize(&mut account.data.as_slice()).unwrap())
+}
+
+#[tokio::test]
+async fn test_partial_refunds_add_up_to_the_net_amount() {
+    let mut vault = Vault::start().await;
+    let payment = vault.payment().await;
+    let fee_collector = vault.fee_collector.insecure_clone();
+    let collector = fee_collector.pubkey();
+    // 995_000 net, 5_000 fee
+    vault.send(&[payment.instruction(1_000_000, [1; 32])], &[]).await.unwrap();
+
+    let refund = payment.refund_instruction([1; 32], 400_000, collector);
+    vault.send(&[refund], &[&payment.merchant, &fee_collector]).await.unwrap();
+    assert_eq!(vault.balance(&payment.payer_token_account).await, 9_400_000);
+    assert_eq!(vault.balance(&payment.merchant_token_account).await, 595_000);
+    let partial = record(&mut vault, &payment.payer, [1; 32]).await.unwrap();
+    assert_eq!(partial.refunded_amount, 400_000);
+    assert!(partial.status == PaymentStatus::PartiallyRefunded);
+
+    // One base unit more than what is left
+    let refund = payment.refund_instruction([1; 32], 595_001, collector);
+    let result = vault.send(&[refund], &[&payment.merchant, &fee_collector]).await;
+    assert_vault_error(result, VaultError::RefundExceedsPayment);
+
+    // The rest returns the fee too and closes the record
+    let refund = payment.refund_instruction([1; 32], 595_000, collector);
+    vault.send(&[refund], &[&payment.merchant, &fee_collector]).await.unwrap();
+    assert_eq!(vault.balance(&payment.payer_token_account).await, 10_000_000);
+    assert_eq!(vault.balance(&payment.merchant_token_account).await, 0);
+    assert_eq!(vault.balance(&payment.fee_token_account).await, 0);
+    assert!(record(&mut vault, &payment.payer, [1; 32]).await.is_none());
+}
+
+#[tokio::test]
+async fn test_full_refund_in_one_call() {
+    let mut vault = Vault::start().await;
+    let payment = vault.payment().await;
+    let fee_collector = vault.fee_collector.insecure_clone();
+    vault.send(&[payment.instruction(1_000_000, [2; 32])], &[]).await.unwrap();
+
+    let refund = payment.refund_instruction([2; 32], 995_000, fee_collector.pubkey());
+    vault.send(&[refund], &[&payment.merchant, &fee_collector]).await.unwrap();
+
+    assert_eq!(vault.balance(&payment.payer_token_account).await, 10_000_000);
+    assert!(record(&mut vault, &payment.payer, [2; 32]).await.is_none());
+}
+
+#[tokio::test]
+async fn test_refund_over_the_net_amount_or_of_nothing_is_rejected() {
+    let mut vault = Vault::start().await;
+    let payment = vault.payment().await;
+    let fee_collector = vault.fee_collector.insecure_clone();
+    let collector = fee_collector.pubkey();
+    vault.send(&[payment.instruction(1_000_000, [3; 32])], &[]).await.unwrap();
+
+    // The fee isn't the merchant's to refund
+    let refund = payment.refund_instruction([3; 32], 1_000_000, collector);
+    let result = vault.send(&[refund], &[&payment.merchant, &fee_collector]).await;
+    assert_vault_error(result, VaultError::RefundExceedsPayment);
+
+    let refund = payment.refund_instruction([3; 32], 0, collector);
+    let result = vault.send(&[refund], &[&payment.merchant, &fee_collector]).await;
+    assert_vault_error(result, VaultError::InvalidAmount);
+    assert_eq!(vault.balance(&payment.payer_token_account).await, 9_000_000);
+}
+
+#[tokio::test]
+async fn test_only_the_payment_merchant_refunds() {
+    let mut vault = Vault::start().await;
+    let mut payment = vault.payment().await;
+    let fee_collector = vault.fee_collector.insecure_clone();
+    vault.send(&[payment.instruction(1_000_000, [4; 32])], &[]).await.unwrap();
+
+    payment.merchant = Keypair::new();
+    let refund = payment.refund_instruction([4; 32], 1_000, fee_collector.pubkey());
+    let result = vault.send(&[refund], &[&payment.merchant, &fee_collector]).await;
+
+    assert_vault_error(result, VaultError::Unauthorized);
+    assert_eq!(record(&mut vault, &payment.payer, [4; 32]).await.unwrap().refunded_amount, 0);
+}
+
+#[tokio::test]
+async fn test_refund_comes_from_the_merchants_own_account_in_the_payment_mint() {
+    let mut vault = Vault::start().await;
+    let mut payment = vault.payment().await;
+    let fee_collector = vault.fee_collector.insecure_clone();
+    vault.send(&[payment.instruction(1_000_000, [5; 32])], &[]).await.unwrap();
+    let merchant_token_account = payment.merchant_token_account;
+
+    // Not from an account the merchant doesn't own
+    let mint = vault.mint;
+    payment.merchant_token_account = vault.create_token_account(&mint, &Keypair::new().pubkey()).await;
+    let refund = payment.refund_instruction([5; 32], 1_000, fee_collector.pubkey());
+    let result = vault.send(&[refund], &[&payment.merchant, &fee_collector]).await;
+    assert_vault_error(result, VaultError::Unauthorized);
+
+    let other_mint = vault.create_mint().await;
+    payment.merchant_token_account = vault.create_token_account(&other_mint, &payment.merchant.pubkey()).await;
+    let refund = payment.refund_instruction([5; 32], 1_000, fee_collector.pubkey());
+    let result = vault.send(&[refund], &[&payment.merchant, &fee_collector]).await;
+    assert_vault_error(result, VaultError::MintMismatch);
+
+    payment.merchant_token_account = merchant_token_account;
+    let refund = payment.refund_instruction([5; 32], 1_000, fee_collector.pubkey());
+    vault.send(&[refund], &[&payment.merchant, &fee_collector]).await.unwrap();
+    assert_eq!(vault.balance(&payment.payer_token_account).await, 9_001_000);
+}
+
+#[tokio::test]
+async fn test_refund_of_a_fee_free_payment_needs_no_collector() {
+    let mut vault = Vault::start().await;
+    vault.edit_vault_config(|config| config.fee_basis_points = 0).await;
+    let payment = vault.payment().await;
+    vault.send(&[payment.instruction(1_000_000, [6; 32])], &[]).await.unwrap();
+
+    let refund = payment.refund_instruction_from([6; 32], 1_000_000, &[]);
+    vault.send(&[refund], &[&payment.merchant]).await.unwrap();
+
+    assert_eq!(vault.balance(&payment.payer_token_account).await, 10_000_000);
+    assert!(record(&mut vault, &payment.payer, [6; 32]).await.is_none());
+}
+
+#[tokio::test]
+async fn test_partial_refunds_need_only_the_merchant() {
+    let mut vault = Vault::start().await;
+    let payment = vault.payment().await;
+    let fee_collector = vault.fee_collector.insecure_clone();
+    vault.send(&[payment.instruction(1_000_000, [7; 32])], &[]).await.unwrap();
+    // Rotating the collector doesn't hold up the merchant's refunds
+    let authority = vault.payer();
+    vault.edit_vault_config(|config| config.fee_collector = authority).await;
+
+    let refund = payment.refund_instruction_from([7; 32], 400_000, &[]);
+    vault.send(&[refund], &[&payment.merchant]).await.unwrap();
+    assert_eq!(vault.balance(&payment.payer_token_account).await, 9_400_000);
+
+    // The fee comes back from the collector it was paid to, who must sign
+    let refund = payment.refund_instruction_from([7; 32], 595_000, &[]);
+    let result = vault.send(&[refund], &[&payment.merchant]).await;
+    assert_vault_error(result, VaultError::Unauthorized);
+    let refund = payment.refund_instruction([7; 32], 595_000, fee_collector.pubkey());
+    vault.send(&[refund], &[&payment.merchant, &fee_collector]).await.unwrap();
+    assert_eq!(vault.balance(&payment.payer_token_account).await, 10_000_000);
+}
+
+#[tokio::test]
+async fn test_the_fee_returns_from_the_collectors_account_in_the_payment_mint() {
+    let mut vault = Vault::start().await;
+    let payment = vault.payment().await;
+    let fee_collector = vault.fee_collector.insecure_clone();
+    vault.send(&[payment.instruction(1_000_000, [8; 32])], &[]).await.unwrap();
+
+    let other_mint = vault.create_mint().await;
+    let other_account = vault.create_token_account(&other_mint, &fee_collector.pubkey()).await;
+    let refund = payment.refund_instruction_from([8; 32], 995_000, &[(fee_collector.pubkey(), other_account)]);
+    let result = vault.send(&[refund], &[&payment.merchant, &fee_collector]).await;
+    assert_vault_error(result, VaultError::MintMismatch);
+
+    // Nor from someone else's account in the right one
+    let mint = vault.mint;
+    let stranger_account = vault.create_token_account(&mint, &Keypair::new().pubkey()).await;
+    let refund = payment.refund_instruction_from([8; 32], 995_000, &[(fee_collector.pubkey(), stranger_account)]);
+    let result = vault.send(&[refund], &[&payment.merchant, &fee_collector]).await;
+    assert_vault_error(result, VaultError::InvalidFeeAccount);
+}
@@ -0,0 +1,122 @@
+mod common;
+
+use anchor_lang::AccountDeserialize;
+use anchor_spl::token::spl_token;
+use common::{
+    assert_vault_error, fee_schedule, payment_record, set_fee_schedule_instruction, sol_escrow, sol_payment_accounts,
+    sol_payment_instruction, sol_payment_instruction_from, Vault,
+};
+use ninjapay_vault::accounts::ProcessSolPayment;
+use ninjapay_vault::{PaymentRecord, VaultError};
+use solana_sdk::{
+    program_pack::Pack,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    system_instruction,
+};
+
+const SOL: u64 = 1_000_000_000;
+
+/// A funded merchant wallet, with the fee collector's wallet funded too so
+/// both can take payments of any size
+async fn wallets(vault: &mut Vault) -> (Pubkey, Pubkey) {
+    vault.ensure_native_mint().await;
+    let merchant = Keypair::new().pubkey();
+    let fee_collector = vault.fee_collector.pubkey();
+    let payer = vault.payer();
+    let fund = [
+        system_instruction::transfer(&payer, &merchant, SOL),
+        system_instruction::transfer(&payer, &fee_collector, SOL),
+    ];
+    vault.send(&fund, &[]).await.unwrap();
+    (merchant, fee_collector)
+}
+
+#[tokio::test]
+async fn test_sol_payment_pays_merchant_and_collector_in_lamports() {
+    let mut vault = Vault::start().await;
+    let (merchant, fee_collector) = wallets(&mut vault).await;
+    let payer = vault.payer();
+    let payment_id = [1; 32];
+
+    vault.send(&[sol_payment_instruction(payer, merchant, fee_collector, 2 * SOL, payment_id)], &[]).await.unwrap();
+
+    // 50 bps of 2 SOL
+    assert_eq!(vault.lamports(&merchant).await, SOL + 1_990_000_000);
+    assert_eq!(vault.lamports(&fee_collector).await, SOL + 10_000_000);
+    assert_eq!(vault.lamports(&sol_escrow(&payer, &payment_id)).await, 0);
+
+    // The record keeps exactly its rent
+    let address = payment_record(&payer, &payment_id);
+    let account = vault.context.banks_client.get_account(address).await.unwrap().unwrap();
+    let rent = vault.context.banks_client.get_rent().await.unwrap();
+    assert_eq!(account.lamports, rent.minimum_balance(account.data.len()));
+    let record = PaymentRecord::try_deserialize(&mut account.data.as_slice()).unwrap();
+    assert_eq!((record.amount, record.fee, record.merchant), (2 * SOL, 10_000_000, merchant));
+    assert_eq!(record.mint, spl_token::native_mint::ID);
+}
+
+#[tokio::test]
+async fn test_sol_payment_costs_the_payer_only_the_payment_and_records() {
+    let mut vault = Vault::start().await;
+    let (merchant, fee_collector) = wallets(&mut vault).await;
+    let payer = vault.payer();
+    // Creates the rate limit account, so the second payment measures alone
+    vault.send(&[sol_payment_instruction(payer, merchant, fee_collector, SOL, [1; 32])], &[]).await.unwrap();
+
+    let before = vault.lamports(&payer).await;
+    vault.send(&[sol_payment_instruction(payer, merchant, fee_collector, SOL, [2; 32])], &[]).await.unwrap();
+    let record_rent = vault.lamports(&payment_record(&payer, &[2; 32])).await;
+
+    // Only the transaction fee on top, well short of the escrow's rent,
+    // which came back
+    let rent = vault.context.banks_client.get_rent().await.unwrap();
+    let escrow_rent = rent.minimum_balance(spl_token::state::Account::LEN);
+    let spent = before - vault.lamports(&payer).await;
+    assert!(spent >= SOL + record_rent && spent < SOL + record_rent + escrow_rent / 2, "spent {}", spent);
+}
+
+#[tokio::test]
+async fn test_sol_payment_fee_must_go_to_the_collector() {
+    let mut vault = Vault::start().await;
+    let (merchant, _) = wallets(&mut vault).await;
+    let payer = vault.payer();
+
+    let result = vault.send(&[sol_payment_instruction(payer, merchant, merchant, SOL, [1; 32])], &[]).await;
+
+    assert_vault_error(result, VaultError::InvalidFeeAccount);
+    assert_eq!(vault.lamports(&merchant).await, SOL);
+}
+
+#[tokio::test]
+async fn test_sol_payment_rejects_zero_lamports() {
+    let mut vault = Vault::start().await;
+    let (merchant, fee_collector) = wallets(&mut vault).await;
+    let payer = vault.payer();
+
+    let result = vault.send(&[sol_payment_instruction(payer, merchant, fee_collector, 0, [1; 32])], &[]).await;
+
+    assert_vault_error(result, VaultError::InvalidAmount);
+}
+
+#[tokio::test]
+async fn test_sol_payment_pays_the_scheduled_fee() {
+    let mut vault = Vault::start().await;
+    let (merchant, fee_collector) = wallets(&mut vault).await;
+    let payer = vault.payer();
+    vault.send(&[set_fee_schedule_instruction(payer, &[(0, 10)])], &[]).await.unwrap();
+
+    // Leaving it out would dodge the tiers
+    let result = vault.send(&[sol_payment_instruction(payer, merchant, fee_collector, 2 * SOL, [1; 32])], &[]).await;
+    assert_vault_error(result, VaultError::FeeScheduleRequired);
+
+    let accounts = ProcessSolPayment {
+        fee_schedule: Some(fee_schedule()),
+        ..sol_payment_accounts(payer, merchant, &[fee_collector], [1; 32])
+    };
+    vault.send(&[sol_payment_instruction_from(accounts, 2 * SOL, [1; 32])], &[]).await.unwrap();
+
+    // 10 bps of 2 SOL
+    assert_eq!(vault.lamports(&merchant).await, SOL + 1_998_000_000);
+    assert_eq!(vault.lamports(&fee_collector).await, SOL + 2_000_000);
+}
@@ -0,0 +1,141 @@
+mod common;
+
+use anchor_lang::AccountDeserialize;
+use common::{assert_vault_error, vault_config, Payment, Vault};
+use ninjapay_vault::{split_shares, split_shares_rounded, VaultConfig, VaultError, MAX_SPLITS};
+use solana_sdk::{
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+};
+
+/// Token accounts in the vault's mint for `count` recipients: the
+/// payment's merchant, whose account a split must start with, then new ones
+async fn recipients(vault: &mut Vault, payment: &Payment, count: usize) -> Vec<Pubkey> {
+    let mint = vault.mint;
+    let mut accounts = vec![payment.merchant_token_account];
+    for _ in 1..count {
+        accounts.push(vault.create_token_account(&mint, &Keypair::new().pubkey()).await);
+    }
+    accounts
+}
+
+#[tokio::test]
+async fn test_split_payment_pays_each_recipient_and_the_fee() {
+    let mut vault = Vault::start().await;
+    let payment = vault.payment().await;
+    let to = recipients(&mut vault, &payment, 3).await;
+
+    let splits = [(5_000, to[0]), (3_000, to[1]), (1_950, to[2])];
+    vault.send(&[payment.split_instruction(1_000_000, [1; 32], &splits)], &[]).await.unwrap();
+
+    assert_eq!(vault.balance(&to[0]).await, 500_000);
+    assert_eq!(vault.balance(&to[1]).await, 300_000);
+    assert_eq!(vault.balance(&to[2]).await, 195_000);
+    // 50 bps of 1_000_000
+    assert_eq!(vault.balance(&vault.fee_token_account.clone()).await, 5_000);
+    assert_eq!(vault.balance(&payment.payer_token_account).await, 9_000_000);
+
+    let account = vault.context.banks_client.get_account(vault_config()).await.unwrap().unwrap();
+    let config = VaultConfig::try_deserialize(&mut account.data.as_slice()).unwrap();
+    assert_eq!((config.total_volume, config.total_payments), (1_000_000, 1));
+}
+
+#[tokio::test]
+async fn test_split_basis_points_must_leave_exactly_the_fee() {
+    let mut vault = Vault::start().await;
+    let payment = vault.payment().await;
+    let to = recipients(&mut vault, &payment, 2).await;
+
+    // 9_949 and 10_000 in total, against a 50 bps fee
+    for (id, splits) in [(2, [(5_000, to[0]), (4_949, to[1])]), (3, [(5_000, to[0]), (5_000, to[1])])] {
+        let result = vault.send(&[payment.split_instruction(1_000_000, [id; 32], &splits)], &[]).await;
+        assert_vault_error(result, VaultError::InvalidSplit);
+    }
+    assert_eq!(vault.balance(&payment.payer_token_account).await, 10_000_000);
+}
+
+#[tokio::test]
+async fn test_split_recipients_must_match_the_remaining_accounts() {
+    let mut vault = Vault::start().await;
+    let payment = vault.payment().await;
+    let to = recipients(&mut vault, &payment, 3).await;
+
+    // The second split names one account and pays another
+    let mut instruction = payment.split_instruction(1_000_000, [4; 32], &[(4_950, to[0]), (5_000, to[1])]);
+    instruction.accounts.last_mut().unwrap().pubkey = to[2];
+    let result = vault.send(&[instruction], &[]).await;
+    assert_vault_error(result, VaultError::InvalidSplit);
+
+    // A recipient dropped from the remaining accounts
+    let mut instruction = payment.split_instruction(1_000_000, [5; 32], &[(4_950, to[0]), (5_000, to[1])]);
+    instruction.accounts.pop();
+    let result = vault.send(&[instruction], &[]).await;
+    assert_vault_error(result, VaultError::InvalidSplit);
+
+    assert_eq!(vault.balance(&to[2]).await, 0);
+}
+
+#[tokio::test]
+async fn test_split_recipient_in_another_mint_is_rejected() {
+    let mut vault = Vault::start().await;
+    let payment = vault.payment().await;
+    let to = recipients(&mut vault, &payment, 1).await;
+    let other_mint = vault.create_mint().await;
+    let elsewhere = vault.create_token_account(&other_mint, &Keypair::new().pubkey()).await;
+
+    let splits = [(4_950, to[0]), (5_000, elsewhere)];
+    let result = vault.send(&[payment.split_instruction(1_000_000, [6; 32], &splits)], &[]).await;
+
+    assert_vault_error(result, VaultError::MintMismatch);
+    assert_eq!(vault.balance(&to[0]).await, 0);
+}
+
+#[tokio::test]
+async fn test_split_fee_rounds_up_like_process_payment() {
+    let mut vault = Vault::start().await;
+    vault.edit_vault_config(|config| config.round_fee_up = true).await;
+    let payment = vault.payment().await;
+    let to = recipients(&mut vault, &payment, 2).await;
+
+    // 50 bps of 999 is 4.995, charged as 5 rather than 4
+    let splits = [(4_950, to[0]), (5_000, to[1])];
+    vault.send(&[payment.split_instruction(999, [7; 32], &splits)], &[]).await.unwrap();
+
+    assert_eq!(vault.balance(&vault.fee_token_account.clone()).await, 5);
+    assert_eq!((vault.balance(&to[0]).await, vault.balance(&to[1]).await), (495, 499));
+}
+
+#[tokio::test]
+async fn test_split_must_start_with_the_merchants_account() {
+    let mut vault = Vault::start().await;
+    let payment = vault.payment().await;
+    let to = recipients(&mut vault, &payment, 2).await;
+
+    // Naming a merchant who owns none of the recipients would let the payer
+    // pick whose fee override applies
+    let splits = [(5_000, to[1]), (4_950, to[0])];
+    let result = vault.send(&[payment.split_instruction(1_000_000, [8; 32], &splits)], &[]).await;
+
+    assert_vault_error(result, VaultError::InvalidSplit);
+    assert_eq!(vault.balance(&payment.payer_token_account).await, 10_000_000);
+}
+
+#[test]
+fn test_split_shares() {
+    assert_eq!(split_shares(1_000_000, 50, &[5_000, 4_950]).unwrap(), (5_000, vec![500_000, 495_000]));
+    assert_eq!(split_shares(1_000, 0, &[10_000]).unwrap(), (0, vec![1_000]));
+
+    // Rounding dust goes to the first recipient
+    assert_eq!(split_shares(999, 50, &[3_317, 3_317, 3_316]).unwrap(), (4, vec![333, 331, 331]));
+    let (fee, shares) = split_shares(u64::MAX, 1_000, &[3_000; 3]).unwrap();
+    assert_eq!(fee + shares.iter().sum::<u64>(), u64::MAX);
+
+    let invalid: anchor_lang::error::Error = VaultError::InvalidSplit.into();
+    assert_eq!(split_shares(1_000, 50, &[]).unwrap_err(), invalid);
+    assert_eq!(split_shares(1_000, 50, &[9_950, 0]).unwrap_err(), invalid);
+    assert_eq!(split_shares(1_000, 0, &[1_250; MAX_SPLITS]).unwrap(), (0, vec![125; MAX_SPLITS]));
+    assert_eq!(split_shares(1_000, 1_000, &[1_000; MAX_SPLITS + 1]).unwrap_err(), invalid);
+
+    // Rounding the fee up takes it from the first recipient's dust
+    assert_eq!(split_shares_rounded(999, 50, &[3_317, 3_317, 3_316], true).unwrap(), (5, vec![332, 331, 331]));
+}
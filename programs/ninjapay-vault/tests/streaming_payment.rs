@@ -0,0 +1,142 @@
+mod common;
+
+use anchor_lang::AccountDeserialize;
+use common::{
+    assert_program_error, assert_vault_error, set_payment_bounds_instruction, set_rate_limit_config_instruction,
+    stream_tokens, streaming_payment, vault_config, Vault, START_TIME,
+};
+use ninjapay_vault::{StreamingPayment, VaultConfig, VaultError};
+use solana_sdk::{
+    compute_budget::ComputeBudgetInstruction,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+};
+
+const START: i64 = START_TIME + 100;
+const END: i64 = START + 1_000;
+const RATE: u64 = 100;
+
+async fn stream(vault: &mut Vault, sender: &Pubkey, stream_id: [u8; 32]) -> StreamingPayment {
+    let account = vault.context.banks_client.get_account(streaming_payment(sender, &stream_id)).await.unwrap().unwrap();
+    StreamingPayment::try_deserialize(&mut account.data.as_slice()).unwrap()
+}
+
+#[tokio::test]
+async fn test_recipient_withdraws_what_has_streamed() {
+    let mut vault = Vault::start().await;
+    let payment = vault.payment().await;
+    vault.send(&[payment.open_stream_instruction([1; 32], RATE, START, END)], &[]).await.unwrap();
+    // The 50 bps fee is charged on top of the deposit
+    assert_eq!(vault.balance(&payment.payer_token_account).await, 10_000_000 - 100_500);
+    assert_eq!(vault.balance(&payment.fee_token_account).await, 500);
+    assert_eq!(vault.balance(&stream_tokens(&payment.payer, &[1; 32])).await, 100_000);
+    assert_eq!(stream(&mut vault, &payment.payer, [1; 32]).await.deposit, 100_000);
+    let account = vault.context.banks_client.get_account(vault_config()).await.unwrap().unwrap();
+    let config = VaultConfig::try_deserialize(&mut account.data.as_slice()).unwrap();
+    assert_eq!((config.total_volume, config.total_payments, config.total_fees_collected), (100_500, 1, 500));
+
+    let result = vault.send(&[payment.withdraw_stream_instruction([1; 32])], &[&payment.merchant]).await;
+    assert_vault_error(result, VaultError::NothingStreamed);
+
+    vault.set_time(START + 250).await;
+    let budget = ComputeBudgetInstruction::set_compute_unit_limit(200_001);
+    vault.send(&[budget, payment.withdraw_stream_instruction([1; 32])], &[&payment.merchant]).await.unwrap();
+    assert_eq!(vault.balance(&payment.merchant_token_account).await, 25_000);
+    assert_eq!(stream(&mut vault, &payment.payer, [1; 32]).await.withdrawn_until, START + 250);
+
+    let budget = ComputeBudgetInstruction::set_compute_unit_limit(200_002);
+    let result = vault.send(&[budget, payment.withdraw_stream_instruction([1; 32])], &[&payment.merchant]).await;
+    assert_vault_error(result, VaultError::NothingStreamed);
+
+    // Nothing streams past the end. Compute budgets keep the withdrawals from
+    // repeating a transaction already processed.
+    vault.set_time(END + 500).await;
+    let budget = ComputeBudgetInstruction::set_compute_unit_limit(200_003);
+    vault.send(&[budget, payment.withdraw_stream_instruction([1; 32])], &[&payment.merchant]).await.unwrap();
+    assert_eq!(vault.balance(&payment.merchant_token_account).await, 100_000);
+    assert_eq!(vault.balance(&stream_tokens(&payment.payer, &[1; 32])).await, 0);
+}
+
+#[tokio::test]
+async fn test_top_up_lengthens_and_cancel_splits_the_deposit() {
+    let mut vault = Vault::start().await;
+    let payment = vault.payment().await;
+    vault.send(&[payment.open_stream_instruction([1; 32], RATE, START, END)], &[]).await.unwrap();
+
+    vault.set_time(START + 400).await;
+    let result = vault.send(&[payment.top_up_stream_instruction([1; 32], 50_001)], &[]).await;
+    assert_vault_error(result, VaultError::InvalidAmount);
+    vault.send(&[payment.top_up_stream_instruction([1; 32], 50_000)], &[]).await.unwrap();
+    let topped_up = stream(&mut vault, &payment.payer, [1; 32]).await;
+    assert_eq!((topped_up.end_time, topped_up.deposit), (END + 500, 150_000));
+
+    // 600 seconds in, 60_000 has streamed and 90_000 hasn't. The fees on the
+    // deposit and the top-up were charged as they were made and stay paid.
+    vault.set_time(START + 600).await;
+    vault.send(&[payment.cancel_stream_instruction([1; 32])], &[]).await.unwrap();
+    assert_eq!(vault.balance(&payment.merchant_token_account).await, 60_000);
+    assert_eq!(vault.balance(&payment.fee_token_account).await, 750);
+    assert_eq!(vault.balance(&payment.payer_token_account).await, 10_000_000 - 60_000 - 750);
+    for address in [streaming_payment(&payment.payer, &[1; 32]), stream_tokens(&payment.payer, &[1; 32])] {
+        assert!(vault.context.banks_client.get_account(address).await.unwrap().is_none());
+    }
+
+    // An ended stream can only be cancelled
+    vault.send(&[payment.open_stream_instruction([2; 32], RATE, START + 700, END)], &[]).await.unwrap();
+    vault.set_time(END).await;
+    let result = vault.send(&[payment.top_up_stream_instruction([2; 32], 100)], &[]).await;
+    assert_vault_error(result, VaultError::StreamEnded);
+}
+
+#[tokio::test]
+async fn test_only_the_recipient_withdraws_and_the_sender_cancels() {
+    let mut vault = Vault::start().await;
+    let mut payment = vault.payment().await;
+    vault.send(&[payment.open_stream_instruction([1; 32], RATE, START, END)], &[]).await.unwrap();
+    vault.set_time(START + 500).await;
+    let stranger = Keypair::new();
+
+    let mut cancel = payment.cancel_stream_instruction([1; 32]);
+    cancel.accounts[2].pubkey = stranger.pubkey();
+    let result = vault.send(&[cancel], &[&stranger]).await;
+    assert_program_error(result, anchor_lang::error::ErrorCode::ConstraintSeeds.into());
+
+    payment.merchant = stranger;
+    payment.merchant_token_account = vault.create_token_account(&payment.mint, &payment.merchant.pubkey()).await;
+    let result = vault.send(&[payment.withdraw_stream_instruction([1; 32])], &[&payment.merchant]).await;
+    assert_vault_error(result, VaultError::Unauthorized);
+    assert_eq!(vault.balance(&stream_tokens(&payment.payer, &[1; 32])).await, 100_000);
+}
+
+#[tokio::test]
+async fn test_invalid_streams_are_rejected() {
+    let mut vault = Vault::start().await;
+    let payment = vault.payment().await;
+
+    for (rate, start, end) in [(0, START, END), (RATE, START_TIME - 1, END), (RATE, START, START)] {
+        let result = vault.send(&[payment.open_stream_instruction([1; 32], rate, start, end)], &[]).await;
+        assert_vault_error(result, VaultError::InvalidStream);
+    }
+    assert_eq!(vault.balance(&payment.payer_token_account).await, 10_000_000);
+}
+
+#[tokio::test]
+async fn test_deposits_and_top_ups_are_held_to_the_payment_limits() {
+    let mut vault = Vault::start().await;
+    let payment = vault.payment().await;
+    let authority = vault.payer();
+    vault.send(&[set_payment_bounds_instruction(authority, 0, 50_000)], &[]).await.unwrap();
+
+    let result = vault.send(&[payment.open_stream_instruction([1; 32], RATE, START, END)], &[]).await;
+    assert_vault_error(result, VaultError::InvalidAmount);
+
+    // A single payment an hour leaves the stream no top-up. The budget keeps
+    // the retry from repeating the refused transaction.
+    vault.send(&[set_payment_bounds_instruction(authority, 0, 0)], &[]).await.unwrap();
+    vault.send(&[set_rate_limit_config_instruction(authority, 1, 0)], &[]).await.unwrap();
+    let budget = ComputeBudgetInstruction::set_compute_unit_limit(200_001);
+    vault.send(&[budget, payment.open_stream_instruction([1; 32], RATE, START, END)], &[]).await.unwrap();
+    let result = vault.send(&[payment.top_up_stream_instruction([1; 32], 10_000)], &[]).await;
+    assert_vault_error(result, VaultError::RateLimitExceeded);
+    assert_eq!(vault.balance(&payment.payer_token_account).await, 10_000_000 - 100_500);
+}
@@ -0,0 +1,118 @@
+mod common;
+
+use anchor_lang::AccountDeserialize;
+use common::{
+    assert_program_error, assert_vault_error, set_daily_volume_cap_instruction, set_payment_bounds_instruction,
+    timelock_payment, timelock_tokens, vault_config, Vault, START_TIME,
+};
+use ninjapay_vault::{TimelockPayment, VaultConfig, VaultError};
+use solana_sdk::{
+    compute_budget::ComputeBudgetInstruction,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+};
+
+const UNLOCK_AT: i64 = START_TIME + 30 * 24 * 60 * 60;
+
+async fn record(vault: &mut Vault, funder: &Pubkey, payment_id: [u8; 32]) -> TimelockPayment {
+    let address = timelock_payment(funder, &payment_id);
+    let account = vault.context.banks_client.get_account(address).await.unwrap().unwrap();
+    TimelockPayment::try_deserialize(&mut account.data.as_slice()).unwrap()
+}
+
+#[tokio::test]
+async fn test_beneficiary_claims_once_unlocked() {
+    let mut vault = Vault::start().await;
+    let payment = vault.payment().await;
+    vault.send(&[payment.timelock_instruction(1_000_000, [1; 32], UNLOCK_AT)], &[]).await.unwrap();
+    let tokens = timelock_tokens(&payment.payer, &[1; 32]);
+    // The 50 bps fee is paid now, leaving the rest to claim
+    assert_eq!(vault.balance(&payment.payer_token_account).await, 9_000_000);
+    assert_eq!(vault.balance(&payment.fee_token_account).await, 5_000);
+    assert_eq!(vault.balance(&tokens).await, 995_000);
+
+    vault.set_time(UNLOCK_AT - 1).await;
+    let result = vault.send(&[payment.claim_timelock_instruction([1; 32])], &[&payment.merchant]).await;
+    assert_vault_error(result, VaultError::TimelockNotUnlocked);
+
+    vault.set_time(UNLOCK_AT).await;
+    let rent = vault.lamports(&tokens).await;
+    let before = vault.lamports(&payment.payer).await;
+    vault.send(&[payment.claim_timelock_instruction([1; 32])], &[&payment.merchant]).await.unwrap();
+
+    // The funder, also paying for the transaction's two signatures, gets the
+    // token account's rent back
+    assert_eq!(vault.balance(&payment.merchant_token_account).await, 995_000);
+    assert!(vault.context.banks_client.get_account(tokens).await.unwrap().is_none());
+    assert_eq!(vault.lamports(&payment.payer).await, before + rent - 10_000);
+    let claimed = record(&mut vault, &payment.payer, [1; 32]).await;
+    assert!(claimed.claimed);
+    assert_eq!((claimed.amount, claimed.fee, claimed.unlock_at), (995_000, 5_000, UNLOCK_AT));
+    let account = vault.context.banks_client.get_account(vault_config()).await.unwrap().unwrap();
+    let config = VaultConfig::try_deserialize(&mut account.data.as_slice()).unwrap();
+    assert_eq!((config.total_volume, config.total_payments, config.total_fees_collected), (1_000_000, 1, 5_000));
+
+    // The token account closed with the claim, so a second one can't load it
+    let budget = ComputeBudgetInstruction::set_compute_unit_limit(200_001);
+    let result = vault.send(&[budget, payment.claim_timelock_instruction([1; 32])], &[&payment.merchant]).await;
+    assert_program_error(result, anchor_lang::error::ErrorCode::AccountNotInitialized.into());
+}
+
+#[tokio::test]
+async fn test_only_the_beneficiary_claims() {
+    let mut vault = Vault::start().await;
+    let mut payment = vault.payment().await;
+    vault.send(&[payment.timelock_instruction(1_000_000, [1; 32], UNLOCK_AT)], &[]).await.unwrap();
+    vault.set_time(UNLOCK_AT).await;
+
+    payment.merchant = Keypair::new();
+    payment.merchant_token_account = vault.create_token_account(&payment.mint, &payment.merchant.pubkey()).await;
+    let result = vault.send(&[payment.claim_timelock_instruction([1; 32])], &[&payment.merchant]).await;
+
+    assert_vault_error(result, VaultError::Unauthorized);
+    assert_eq!(vault.balance(&timelock_tokens(&payment.payer, &[1; 32])).await, 995_000);
+}
+
+#[tokio::test]
+async fn test_unlock_must_be_in_the_future() {
+    let mut vault = Vault::start().await;
+    let payment = vault.payment().await;
+
+    let result = vault.send(&[payment.timelock_instruction(1_000_000, [1; 32], START_TIME)], &[]).await;
+    assert_vault_error(result, VaultError::InvalidUnlockTime);
+    let result = vault.send(&[payment.timelock_instruction(0, [1; 32], UNLOCK_AT)], &[]).await;
+    assert_vault_error(result, VaultError::InvalidAmount);
+    assert_eq!(vault.balance(&payment.payer_token_account).await, 10_000_000);
+}
+
+#[tokio::test]
+async fn test_timelock_is_held_to_the_payment_bounds_and_daily_cap() {
+    let mut vault = Vault::start().await;
+    let payment = vault.payment().await;
+    let authority = vault.payer();
+    vault.send(&[set_payment_bounds_instruction(authority, 1_000, 2_000_000)], &[]).await.unwrap();
+    vault.send(&[set_daily_volume_cap_instruction(authority, 3_000_000)], &[]).await.unwrap();
+
+    for amount in [999, 2_000_001] {
+        let result = vault.send(&[payment.timelock_instruction(amount, [1; 32], UNLOCK_AT)], &[]).await;
+        assert_vault_error(result, VaultError::InvalidAmount);
+    }
+    vault.send(&[payment.timelock_instruction(2_000_000, [1; 32], UNLOCK_AT)], &[]).await.unwrap();
+    let result = vault.send(&[payment.timelock_instruction(1_500_000, [2; 32], UNLOCK_AT)], &[]).await;
+    assert_vault_error(result, VaultError::DailyCapExceeded);
+}
+
+#[tokio::test]
+async fn test_another_funder_cannot_take_a_payment_id() {
+    let mut vault = Vault::start().await;
+    let payment = vault.payment().await;
+    let squatter = Keypair::new();
+    let other = vault.payment_by(&squatter).await;
+
+    // Payment ids are per funder, so the same one is still free to the first
+    vault.send(&[other.timelock_instruction(1_000_000, [1; 32], UNLOCK_AT)], &[&squatter]).await.unwrap();
+    vault.send(&[payment.timelock_instruction(1_000_000, [1; 32], UNLOCK_AT)], &[]).await.unwrap();
+
+    assert_eq!(record(&mut vault, &squatter.pubkey(), [1; 32]).await.funder, squatter.pubkey());
+    assert_eq!(record(&mut vault, &payment.payer, [1; 32]).await.funder, payment.payer);
+}
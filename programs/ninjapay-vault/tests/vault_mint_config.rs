@@ -0,0 +1,195 @@
+mod common;
+
+use anchor_lang::{AccountDeserialize, InstructionData, ToAccountMetas};
+use anchor_spl::token::spl_token;
+use common::{
+    assert_program_error, assert_vault_error, fee_schedule, merchant_config, set_fee_schedule_instruction,
+    sol_payment_accounts, sol_payment_instruction_from, vault_config, vault_mint_config, Vault,
+};
+use ninjapay_vault::{accounts::ProcessSolPayment, VaultError, VaultMintConfig, PAYMENT_BATCH_COMPUTE_UNITS};
+use solana_sdk::{
+    compute_budget::ComputeBudgetInstruction,
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    system_instruction, system_program,
+};
+
+fn set_mint_config(authority: Pubkey, mint: Pubkey, fee_basis_points_override: u16) -> Instruction {
+    Instruction {
+        program_id: ninjapay_vault::ID,
+        accounts: ninjapay_vault::accounts::SetVaultMintConfig {
+            vault_config: vault_config(),
+            vault_mint_config: vault_mint_config(&mint),
+            mint,
+            authority,
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None),
+        data: ninjapay_vault::instruction::SetVaultMintConfig {
+            fee_basis_points_override,
+        }
+        .data(),
+    }
+}
+
+fn remove_mint_config(authority: Pubkey, mint: Pubkey) -> Instruction {
+    Instruction {
+        program_id: ninjapay_vault::ID,
+        accounts: ninjapay_vault::accounts::RemoveVaultMintConfig {
+            vault_config: vault_config(),
+            vault_mint_config: vault_mint_config(&mint),
+            authority,
+        }
+        .to_account_metas(None),
+        data: ninjapay_vault::instruction::RemoveVaultMintConfig {}.data(),
+    }
+}
+
+fn set_merchant_override(authority: Pubkey, merchant: Pubkey, fee_override_basis_points: u16) -> Instruction {
+    Instruction {
+        program_id: ninjapay_vault::ID,
+        accounts: ninjapay_vault::accounts::SetMerchantFeeOverride {
+            vault_config: vault_config(),
+            merchant_config: merchant_config(&merchant),
+            merchant,
+            authority,
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None),
+        data: ninjapay_vault::instruction::SetMerchantFeeOverride {
+            fee_override_basis_points,
+        }
+        .data(),
+    }
+}
+
+#[tokio::test]
+async fn test_active_mint_override_replaces_the_vault_fee() {
+    let mut vault = Vault::start().await;
+    let mut payment = vault.payment().await;
+    let (authority, mint) = (vault.payer(), payment.mint);
+    vault.send(&[set_mint_config(authority, mint, 100)], &[]).await.unwrap();
+
+    let account = vault.context.banks_client.get_account(vault_mint_config(&mint)).await.unwrap().unwrap();
+    let config = VaultMintConfig::try_deserialize(&mut account.data.as_slice()).unwrap();
+    assert_eq!((config.mint, config.fee_basis_points_override, config.is_active), (mint, 100, true));
+
+    // 1% instead of the vault's 0.5%
+    payment.vault_mint_config = Some(vault_mint_config(&mint));
+    vault.send(&[payment.instruction(1_000_000, [1; 32])], &[]).await.unwrap();
+    assert_eq!(vault.balance(&payment.fee_token_account).await, 10_000);
+    assert_eq!(vault.balance(&payment.merchant_token_account).await, 990_000);
+
+    // Without the account the vault-wide fee applies
+    payment.vault_mint_config = None;
+    vault.send(&[payment.instruction(1_000_000, [2; 32])], &[]).await.unwrap();
+    assert_eq!(vault.balance(&payment.fee_token_account).await, 15_000);
+}
+
+#[tokio::test]
+async fn test_removed_mint_override_falls_back_to_the_vault_fee() {
+    let mut vault = Vault::start().await;
+    let mut payment = vault.payment().await;
+    let (authority, mint) = (vault.payer(), payment.mint);
+    vault.send(&[set_mint_config(authority, mint, 0)], &[]).await.unwrap();
+    vault.send(&[remove_mint_config(authority, mint)], &[]).await.unwrap();
+    payment.vault_mint_config = Some(vault_mint_config(&mint));
+
+    vault.send(&[payment.instruction(1_000_000, [1; 32])], &[]).await.unwrap();
+    assert_eq!(vault.balance(&payment.fee_token_account).await, 5_000);
+
+    // Setting it again reuses the account
+    vault.send(&[set_mint_config(authority, mint, 0)], &[]).await.unwrap();
+    vault.send(&[payment.instruction(1_000_000, [2; 32])], &[]).await.unwrap();
+    assert_eq!(vault.balance(&payment.fee_token_account).await, 5_000);
+}
+
+#[tokio::test]
+async fn test_mint_override_sits_between_merchant_override_and_fee_schedule() {
+    let mut vault = Vault::start().await;
+    let mut payment = vault.payment().await;
+    let (authority, mint, merchant) = (vault.payer(), payment.mint, payment.merchant.pubkey());
+    vault.send(&[set_mint_config(authority, mint, 100)], &[]).await.unwrap();
+    vault.send(&[set_fee_schedule_instruction(authority, &[(0, 10)])], &[]).await.unwrap();
+    payment.fee_schedule = Some(fee_schedule());
+    payment.vault_mint_config = Some(vault_mint_config(&mint));
+
+    vault.send(&[payment.instruction(1_000_000, [1; 32])], &[]).await.unwrap();
+    assert_eq!(vault.balance(&payment.fee_token_account).await, 10_000);
+
+    vault.send(&[set_merchant_override(authority, merchant, 20)], &[]).await.unwrap();
+    let mut pay = payment.instruction(1_000_000, [2; 32]);
+    pay.accounts.push(AccountMeta::new_readonly(merchant_config(&merchant), false));
+    vault.send(&[pay], &[]).await.unwrap();
+    assert_eq!(vault.balance(&payment.fee_token_account).await, 12_000);
+}
+
+#[tokio::test]
+async fn test_batch_and_sol_payments_charge_the_mint_override() {
+    let mut vault = Vault::start().await;
+    let mut payment = vault.payment().await;
+    let (authority, mint) = (vault.payer(), payment.mint);
+    vault.send(&[set_mint_config(authority, mint, 100)], &[]).await.unwrap();
+    payment.vault_mint_config = Some(vault_mint_config(&mint));
+
+    // 1% of each
+    let payments = [
+        (payment.merchant_token_account, 1_000_000, [1; 32]),
+        (payment.merchant_token_account, 1_000_000, [2; 32]),
+    ];
+    let budget = ComputeBudgetInstruction::set_compute_unit_limit(PAYMENT_BATCH_COMPUTE_UNITS);
+    vault.send(&[budget, payment.batch_instruction(&payments)], &[]).await.unwrap();
+    assert_eq!(vault.balance(&payment.merchant_token_account).await, 1_980_000);
+    assert_eq!(vault.balance(&payment.fee_token_account).await, 20_000);
+
+    // The native mint's override, paid in lamports to funded wallets
+    vault.ensure_native_mint().await;
+    let native_mint = spl_token::native_mint::ID;
+    vault.send(&[set_mint_config(authority, native_mint, 100)], &[]).await.unwrap();
+    let (merchant, fee_collector) = (payment.merchant.pubkey(), vault.fee_collector.pubkey());
+    let fund = [
+        system_instruction::transfer(&authority, &merchant, 1_000_000_000),
+        system_instruction::transfer(&authority, &fee_collector, 1_000_000_000),
+    ];
+    vault.send(&fund, &[]).await.unwrap();
+    let accounts = ProcessSolPayment {
+        vault_mint_config: Some(vault_mint_config(&native_mint)),
+        ..sol_payment_accounts(authority, merchant, &[fee_collector], [3; 32])
+    };
+    vault.send(&[sol_payment_instruction_from(accounts, 1_000_000, [3; 32])], &[]).await.unwrap();
+    assert_eq!(vault.lamports(&merchant).await, 1_000_000_000 + 990_000);
+    assert_eq!(vault.lamports(&fee_collector).await, 1_000_000_000 + 10_000);
+}
+
+#[tokio::test]
+async fn test_another_mints_config_is_rejected() {
+    let mut vault = Vault::start().await;
+    let mut payment = vault.payment().await;
+    let authority = vault.payer();
+    let other_mint = vault.create_mint().await;
+    vault.send(&[set_mint_config(authority, other_mint, 0)], &[]).await.unwrap();
+
+    payment.vault_mint_config = Some(vault_mint_config(&other_mint));
+    let result = vault.send(&[payment.instruction(1_000_000, [1; 32])], &[]).await;
+    assert_program_error(result, anchor_lang::error::ErrorCode::ConstraintSeeds.into());
+    assert_eq!(vault.balance(&payment.payer_token_account).await, 10_000_000);
+}
+
+#[tokio::test]
+async fn test_only_authority_can_manage_mint_configs() {
+    let mut vault = Vault::start().await;
+    let intruder = Keypair::new();
+    let (authority, mint) = (vault.payer(), vault.mint);
+
+    // Created by the authority first, so the intruder's attempt gets as far
+    // as the authority check rather than failing to pay for the account
+    vault.send(&[set_mint_config(authority, mint, 10)], &[]).await.unwrap();
+    let result = vault.send(&[set_mint_config(intruder.pubkey(), mint, 0)], &[&intruder]).await;
+    assert_program_error(result, anchor_lang::error::ErrorCode::ConstraintHasOne.into());
+    let result = vault.send(&[remove_mint_config(intruder.pubkey(), mint)], &[&intruder]).await;
+    assert_program_error(result, anchor_lang::error::ErrorCode::ConstraintHasOne.into());
+
+    let result = vault.send(&[set_mint_config(authority, mint, 1001)], &[]).await;
+    assert_vault_error(result, VaultError::FeeTooHigh);
+}
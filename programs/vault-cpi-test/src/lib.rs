@@ -0,0 +1,106 @@
+//! Stand-in for a partner program routing payments through the vault: it
+//! pays from a token account owned by its `[b"treasury"]` PDA, signing for
+//! the PDA in the CPI to `process_payment_cpi`.
+
+use anchor_lang::prelude::*;
+use anchor_spl::token::Token;
+use ninjapay_vault::cpi::accounts::ProcessPaymentCpi;
+use ninjapay_vault::program::NinjapayVault;
+use ninjapay_vault::FeeMode;
+
+declare_id!("7E1cPpJVAHz8EZm73HH9v3UsHLZ5BHY2FEULLUowD4QW");
+
+#[program]
+pub mod vault_cpi_test {
+    use super::*;
+
+    /// Pay `amount` from the treasury's token account to a merchant
+    pub fn pay(ctx: Context<Pay>, amount: u64, payment_id: [u8; 32], commitment: [u8; 32]) -> Result<()> {
+        let bump = [ctx.bumps.treasury];
+        let treasury_seeds: &[&[u8]] = &[b"treasury", &bump];
+        let cpi_accounts = ProcessPaymentCpi {
+            vault_config: ctx.accounts.vault_config.to_account_info(),
+            payment_record: ctx.accounts.payment_record.to_account_info(),
+            payer_rate_limit: ctx.accounts.payer_rate_limit.to_account_info(),
+            daily_volume: ctx.accounts.daily_volume.to_account_info(),
+            payer: ctx.accounts.treasury.to_account_info(),
+            blacklist_check: ctx.accounts.blacklist_check.to_account_info(),
+            rent_payer: ctx.accounts.rent_payer.to_account_info(),
+            payer_token_account: ctx.accounts.treasury_token_account.to_account_info(),
+            mint: ctx.accounts.mint.to_account_info(),
+            accepted_mint: None,
+            vault_mint_config: None,
+            fee_schedule: None,
+            merchant: ctx.accounts.merchant.to_account_info(),
+            registered_merchant: None,
+            merchant_stats: ctx.accounts.merchant_stats.to_account_info(),
+            merchant_token_account: ctx.accounts.merchant_token_account.to_account_info(),
+            fee_token_account: ctx.accounts.fee_token_account.to_account_info(),
+            fee_token_account_1: None,
+            fee_token_account_2: None,
+            token_program: ctx.accounts.token_program.to_account_info(),
+            system_program: ctx.accounts.system_program.to_account_info(),
+        };
+        let signer = &[treasury_seeds];
+        let cpi_ctx = CpiContext::new_with_signer(ctx.accounts.vault_program.to_account_info(), cpi_accounts, signer);
+        // expires_at 0: never; no blinding factor, as the test vault verifies no commitments
+        let fee_mode = FeeMode::Deducted;
+        ninjapay_vault::cpi::process_payment_cpi(cpi_ctx, amount, payment_id, commitment, 0, Vec::new(), fee_mode)
+    }
+}
+
+/// The vault checks everything it is handed; only the treasury is derived here
+#[derive(Accounts)]
+pub struct Pay<'info> {
+    /// CHECK: Checked by the vault
+    #[account(mut)]
+    pub vault_config: UncheckedAccount<'info>,
+
+    /// CHECK: Created by the vault
+    #[account(mut)]
+    pub payment_record: UncheckedAccount<'info>,
+
+    /// CHECK: Created or updated by the vault
+    #[account(mut)]
+    pub payer_rate_limit: UncheckedAccount<'info>,
+
+    /// CHECK: Created or updated by the vault
+    #[account(mut)]
+    pub daily_volume: UncheckedAccount<'info>,
+
+    /// CHECK: Holds no data; signs for its token account through this program
+    #[account(seeds = [b"treasury"], bump)]
+    pub treasury: UncheckedAccount<'info>,
+
+    /// CHECK: Checked by the vault
+    pub blacklist_check: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    pub rent_payer: Signer<'info>,
+
+    /// CHECK: Checked by the vault
+    #[account(mut)]
+    pub treasury_token_account: UncheckedAccount<'info>,
+
+    /// CHECK: Checked by the vault
+    pub mint: UncheckedAccount<'info>,
+
+    /// CHECK: Checked by the vault
+    pub merchant: UncheckedAccount<'info>,
+
+    /// CHECK: Created or updated by the vault
+    #[account(mut)]
+    pub merchant_stats: UncheckedAccount<'info>,
+
+    /// CHECK: Checked by the vault
+    #[account(mut)]
+    pub merchant_token_account: UncheckedAccount<'info>,
+
+    /// CHECK: Checked by the vault
+    #[account(mut)]
+    pub fee_token_account: UncheckedAccount<'info>,
+
+    pub vault_program: Program<'info, NinjapayVault>,
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
@@ -0,0 +1,137 @@
+//! Paying through `process_payment_cpi` from another program, with a PDA
+//! as the payer. Reuses the vault's harness.
+
+#[path = "../../ninjapay-vault/tests/common/mod.rs"]
+mod common;
+
+use anchor_lang::{AccountDeserialize, InstructionData, ToAccountMetas};
+use anchor_spl::token::spl_token;
+use common::{
+    assert_program_error, blacklisted_payer, daily_volume, merchant_stats, payer_rate_limit, payment_record,
+    vault_config, Vault, TODAY,
+};
+use ninjapay_vault::PaymentRecord;
+use solana_program_test::processor;
+use solana_sdk::{
+    account_info::AccountInfo, entrypoint::ProgramResult, instruction::Instruction, pubkey::Pubkey,
+    signature::Signer, system_program,
+};
+
+// As the vault's harness, for Anchor 0.29's entry
+fn process_instruction(program_id: &Pubkey, accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
+    let accounts = Box::leak(Box::new(accounts.to_vec()));
+    vault_cpi_test::entry(program_id, accounts, data)
+}
+
+fn treasury() -> Pubkey {
+    Pubkey::find_program_address(&[b"treasury"], &vault_cpi_test::ID).0
+}
+
+async fn start() -> Vault {
+    let mut program_test = common::program_test();
+    program_test.add_program("vault_cpi_test", vault_cpi_test::ID, processor!(process_instruction));
+    Vault::start_with(program_test).await
+}
+
+/// `pay` from the treasury's token account, the context payer covering rent
+fn pay_instruction(vault: &Vault, accounts: &common::Payment, amount: u64, payment_id: [u8; 32]) -> Instruction {
+    Instruction {
+        program_id: vault_cpi_test::ID,
+        accounts: vault_cpi_test::accounts::Pay {
+            vault_config: vault_config(),
+            payment_record: payment_record(&treasury(), &payment_id),
+            payer_rate_limit: payer_rate_limit(&treasury()),
+            daily_volume: daily_volume(TODAY),
+            treasury: treasury(),
+            blacklist_check: blacklisted_payer(&treasury()),
+            rent_payer: vault.payer(),
+            treasury_token_account: accounts.payer_token_account,
+            mint: accounts.mint,
+            merchant: accounts.merchant.pubkey(),
+            merchant_stats: merchant_stats(&accounts.merchant.pubkey()),
+            merchant_token_account: accounts.merchant_token_account,
+            fee_token_account: accounts.fee_token_account,
+            vault_program: ninjapay_vault::ID,
+            token_program: spl_token::ID,
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None),
+        data: vault_cpi_test::instruction::Pay {
+            amount,
+            payment_id,
+            commitment: [9; 32],
+        }
+        .data(),
+    }
+}
+
+#[tokio::test]
+async fn test_a_program_pays_from_its_pda() {
+    let mut vault = start().await;
+    let mut payment = vault.payment().await;
+    let mint = vault.mint;
+    payment.payer = treasury();
+    payment.payer_token_account = vault.create_token_account(&mint, &treasury()).await;
+    vault.mint_to(&mint, &payment.payer_token_account.clone(), 1_000_000).await;
+
+    let pay = pay_instruction(&vault, &payment, 1_000_000, [1; 32]);
+    vault.send(&[pay], &[]).await.unwrap();
+
+    assert_eq!(vault.balance(&payment.merchant_token_account).await, 995_000);
+    assert_eq!(vault.balance(&payment.fee_token_account).await, 5_000);
+    assert_eq!(vault.balance(&payment.payer_token_account).await, 0);
+    let account = vault.context.banks_client.get_account(payment_record(&treasury(), &[1; 32])).await.unwrap().unwrap();
+    let record = PaymentRecord::try_deserialize(&mut account.data.as_slice()).unwrap();
+    assert_eq!((record.payer, record.amount, record.fee), (treasury(), 1_000_000, 5_000));
+}
+
+#[tokio::test]
+async fn test_the_pda_payer_must_sign() {
+    let mut vault = start().await;
+    let treasury_token_account = vault.create_token_account(&vault.mint.clone(), &treasury()).await;
+    let payment = vault.payment().await;
+
+    // Calling the vault directly, nothing can sign for the treasury
+    let mut accounts = ninjapay_vault::accounts::ProcessPaymentCpi {
+        vault_config: vault_config(),
+        payment_record: payment_record(&treasury(), &[2; 32]),
+        payer_rate_limit: payer_rate_limit(&treasury()),
+        daily_volume: daily_volume(TODAY),
+        payer: treasury(),
+        blacklist_check: blacklisted_payer(&treasury()),
+        rent_payer: vault.payer(),
+        payer_token_account: treasury_token_account,
+        mint: payment.mint,
+        accepted_mint: None,
+        vault_mint_config: None,
+        fee_schedule: None,
+        merchant: payment.merchant.pubkey(),
+        registered_merchant: None,
+        merchant_stats: merchant_stats(&payment.merchant.pubkey()),
+        merchant_token_account: payment.merchant_token_account,
+        fee_token_account: payment.fee_token_account,
+        fee_token_account_1: None,
+        fee_token_account_2: None,
+        token_program: spl_token::ID,
+        system_program: system_program::ID,
+    }
+    .to_account_metas(None);
+    accounts[4].is_signer = false;
+    let direct = Instruction {
+        program_id: ninjapay_vault::ID,
+        accounts,
+        data: ninjapay_vault::instruction::ProcessPaymentCpi {
+            amount: 1_000,
+            payment_id: [2; 32],
+            commitment: [9; 32],
+            expires_at: 0,
+            blinding_factor: vec![],
+            fee_mode: ninjapay_vault::FeeMode::Deducted,
+        }
+        .data(),
+    };
+
+    let result = vault.send(&[direct], &[]).await;
+
+    assert_program_error(result, anchor_lang::error::ErrorCode::AccountNotSigner.into());
+}
@@ -29,6 +29,11 @@ import webhookRoutes from './routes/webhooks.js';
 import payrollRoutes from './routes/payroll.js';
 import arciumCallbackRoutes from './routes/arcium-callbacks.js';
 import checkoutRoutes from './routes/checkout.js';
+import adminRoutes from './routes/admin.js';
+import paymentIdRoutes from './routes/payment-ids.js';
+import merchantRoutes from './routes/merchants.js';
+import { startRetentionJob } from './services/retention.js';
+import { readRouter } from './services/read-routing.js';
 
 const logger = createLogger('api-gateway');
 const app = express();
@@ -58,16 +63,22 @@ app.use(requestLogger);
 // Rate limiting
 app.use(rateLimiter);
 
+// Remember recent writers so their reads skip the replica
+app.use(readRouter.trackWrites());
+
 // Routes
 app.use('/health', healthRoutes);
 app.use('/v1/auth', authRoutes);
 app.use('/v1/api_keys', apiKeyRoutes);
+app.use('/v1/merchants', merchantRoutes);
 app.use('/v1/payment_intents', paymentIntentRoutes);
 app.use('/v1/payment_links', paymentLinkRoutes);
+app.use('/v1/payment_ids', paymentIdRoutes);
 app.use('/v1/webhooks', webhookRoutes);
 app.use('/v1/payroll', payrollRoutes);
 app.use('/v1/arcium/callbacks', arciumCallbackRoutes);
 app.use('/v1/checkout', checkoutRoutes);
+app.use('/v1/admin', adminRoutes);
 
 // Error handling (must be last)
 app.use(errorHandler);
@@ -79,6 +90,7 @@ app.listen(PORT, () => {
   logger.info(`MPC Mode: ${env.MPC_MODE}`);
   logger.info(`Solana Network: ${env.SOLANA_NETWORK}`);
   logger.info(`CORS enabled for: ${env.CORS_ORIGIN || '*'}`);
+  startRetentionJob();
 });
 
 // Graceful shutdown
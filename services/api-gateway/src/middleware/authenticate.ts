@@ -1,4 +1,5 @@
 import { Request, Response, NextFunction } from 'express';
+import crypto from 'crypto';
 import jwt from 'jsonwebtoken';
 import bcrypt from 'bcrypt';
 import { prisma } from '@ninjapay/database';
@@ -13,6 +14,8 @@ declare global {
     interface Request {
       merchantId?: string;
       companyId?: string;
+      livemode?: boolean;
+      apiKeyScope?: ApiKeyScope;
       user?: {
         id: string;
         walletAddress: string;
@@ -22,6 +25,26 @@ declare global {
   }
 }
 
+export type ApiKeyMode = 'live' | 'test';
+
+/**
+ * The API key a request authenticated with. A non-empty allowedMerchants
+ * limits where the key can send payments: each entry is a recipient wallet
+ * or the id of the merchant owning it.
+ */
+export interface ApiKeyScope {
+  apiKeyId: string;
+  allowedMerchants: string[];
+}
+
+export const API_KEY_PREFIXES: Record<ApiKeyMode, string> = {
+  live: 'sk_live_',
+  test: 'sk_test_',
+};
+
+// Dashboard (JWT) sessions pick sandbox with this header; they default to live
+const MODE_HEADER = 'x-ninjapay-mode';
+
 interface JWTPayload {
   id: string;
   walletAddress: string;
@@ -43,11 +66,19 @@ export const authenticateMerchant = async (
     const apiKey = req.headers['x-api-key'] as string;
 
     if (apiKey) {
-      const merchant = await validateApiKey(apiKey);
-      if (!merchant) {
+      const mode = parseApiKeyMode(apiKey);
+      if (!mode) {
+        throw new AppError('API keys must start with sk_live_ or sk_test_', 401, 'INVALID_API_KEY');
+      }
+      const validated = await validateApiKey(apiKey, mode);
+      if (!validated) {
         throw new AppError('Invalid API key', 401, 'INVALID_API_KEY');
       }
+      const { merchant, scope } = validated;
+      assertMerchantMode(merchant, mode === 'live');
       req.merchantId = merchant.id;
+      req.livemode = mode === 'live';
+      req.apiKeyScope = scope;
       req.user = {
         id: merchant.id,
         walletAddress: merchant.walletAddress,
@@ -75,7 +106,14 @@ export const authenticateMerchant = async (
       throw new AppError('Merchant not found', 404, 'MERCHANT_NOT_FOUND');
     }
 
+    const sessionMode = req.headers[MODE_HEADER] ?? 'live';
+    if (sessionMode !== 'live' && sessionMode !== 'test') {
+      throw new AppError('X-Ninjapay-Mode must be live or test', 400, 'INVALID_MODE');
+    }
+    assertMerchantMode(merchant, sessionMode === 'live');
+
     req.merchantId = merchant.id;
+    req.livemode = sessionMode === 'live';
     req.user = {
       id: merchant.id,
       walletAddress: merchant.walletAddress,
@@ -160,16 +198,110 @@ export const authenticateCompany = async (
 };
 
 /**
- * Validate merchant API key using prefix lookup + bcrypt verification
+ * Admit only operators bearing ADMIN_API_TOKEN; admin routes are disabled
+ * when it is unset
  */
-async function validateApiKey(rawKey: string): Promise<{ id: string; walletAddress: string } | null> {
-  // Extract prefix (first 8 chars: sk_live_ or sk_test_)
-  const keyPrefix = rawKey.substring(0, 8);
+export const authenticateAdmin = (req: Request, _res: Response, next: NextFunction) => {
+  const expected = process.env.ADMIN_API_TOKEN;
+  if (!expected) {
+    return next(new AppError('Admin API is disabled', 403, 'ADMIN_DISABLED'));
+  }
+
+  const provided = extractBearerToken(req);
+  if (!provided) {
+    return next(new AppError('Missing admin token', 401, 'NO_CREDENTIALS'));
+  }
 
-  // Find API keys with matching prefix
+  // Compare digests so the comparison time doesn't depend on the token
+  const digest = (value: string) => crypto.createHash('sha256').update(value).digest();
+  if (!crypto.timingSafeEqual(digest(expected), digest(provided))) {
+    return next(new AppError('Invalid admin token', 401, 'INVALID_ADMIN_TOKEN'));
+  }
+  next();
+};
+
+/**
+ * Mode of a merchant API key from its prefix, or null if unrecognised
+ */
+export function parseApiKeyMode(rawKey: string): ApiKeyMode | null {
+  if (rawKey.startsWith(API_KEY_PREFIXES.live)) return 'live';
+  if (rawKey.startsWith(API_KEY_PREFIXES.test)) return 'test';
+  return null;
+}
+
+/**
+ * Reject access to a record from the other mode: test keys only see sandbox
+ * data and live keys only live data
+ */
+export function assertLivemode(req: Request, record: { livemode: boolean }, resource: string): void {
+  if (record.livemode !== req.livemode) {
+    const recordMode = record.livemode ? 'live' : 'test';
+    const requestMode = req.livemode ? 'live' : 'test';
+    throw new AppError(
+      `This ${resource} exists in ${recordMode} mode, but the request was made in ${requestMode} mode`,
+      403,
+      'LIVEMODE_MISMATCH'
+    );
+  }
+}
+
+/**
+ * Reject payments to a recipient outside the API key's merchant allowlist.
+ * Dashboard sessions and keys without an allowlist may pay anyone.
+ */
+export async function assertMerchantAllowed(req: Request, recipient: string): Promise<void> {
+  const allowed = req.apiKeyScope?.allowedMerchants ?? [];
+  if (allowed.length === 0 || allowed.includes(recipient)) {
+    return;
+  }
+
+  // Entries that aren't the wallet itself may name the merchant owning it
+  const owners = await prisma.merchant.count({
+    where: { id: { in: allowed }, walletAddress: recipient },
+  });
+  if (owners === 0) {
+    throw new AppError(
+      `This API key may not send payments to ${recipient}`,
+      403,
+      'MERCHANT_NOT_ALLOWED'
+    );
+  }
+}
+
+/**
+ * The calling key's scope, as recorded in audit events
+ */
+export function apiKeyScopeDetails(req: Request) {
+  const scope = req.apiKeyScope;
+  if (!scope) {
+    return { api_key_id: null, key_scope: 'dashboard' };
+  }
+  return scope.allowedMerchants.length > 0
+    ? { api_key_id: scope.apiKeyId, key_scope: 'restricted', allowed_merchants: scope.allowedMerchants }
+    : { api_key_id: scope.apiKeyId, key_scope: 'unrestricted' };
+}
+
+/**
+ * Self-registered merchants stay in test mode until an admin approves them
+ */
+function assertMerchantMode(merchant: { status: string }, livemode: boolean): void {
+  if (livemode && merchant.status === 'PENDING') {
+    throw new AppError('Merchant is pending approval; use test mode until approved', 403, 'MERCHANT_PENDING_APPROVAL');
+  }
+}
+
+/**
+ * Validate merchant API key using prefix lookup + bcrypt verification
+ */
+async function validateApiKey(
+  rawKey: string,
+  mode: ApiKeyMode
+): Promise<{ merchant: { id: string; walletAddress: string; status: string }; scope: ApiKeyScope } | null> {
+  // Find API keys with matching prefix and mode
   const apiKeys = await prisma.apiKey.findMany({
     where: {
-      keyPrefix,
+      keyPrefix: API_KEY_PREFIXES[mode],
+      livemode: mode === 'live',
       active: true,
       OR: [
         { expiresAt: null },
@@ -181,6 +313,7 @@ async function validateApiKey(rawKey: string): Promise<{ id: string; walletAddre
         select: {
           id: true,
           walletAddress: true,
+          status: true,
         },
       },
     },
@@ -197,7 +330,10 @@ async function validateApiKey(rawKey: string): Promise<{ id: string; walletAddre
       }).catch(() => {}); // Best-effort update
 
       logger.debug('API key authenticated', { merchantId: apiKey.merchant.id });
-      return apiKey.merchant;
+      return {
+        merchant: apiKey.merchant,
+        scope: { apiKeyId: apiKey.id, allowedMerchants: apiKey.allowedMerchants ?? [] },
+      };
     }
   }
 
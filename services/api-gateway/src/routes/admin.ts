@@ -0,0 +1,204 @@
+import { Router, Request } from 'express';
+import { z } from 'zod';
+import crypto from 'crypto';
+import { prisma } from '@ninjapay/database';
+import { asyncHandler, AppError } from '../middleware/error-handler.js';
+import { authenticateAdmin } from '../middleware/authenticate.js';
+import { createLogger } from '@ninjapay/logger';
+import {
+  MIN_PASSPHRASE_LENGTH,
+  applyImport,
+  openState,
+  previewImport,
+  readState,
+  sealState,
+  summarize,
+} from '../services/state-transfer.js';
+import { approveMerchant } from '../services/merchant-onboarding.js';
+import { readRouter } from '../services/read-routing.js';
+import {
+  DEFAULT_OVERRIDE_TTL_SECONDS,
+  MAX_OVERRIDE_TTL_SECONDS,
+  issueBudgetOverride,
+  queuedToday,
+  serializeBudget,
+} from '../services/payroll-budget.js';
+import { renderMetrics } from '../services/metrics.js';
+
+const router = Router();
+const logger = createLogger('admin');
+
+const PASSPHRASE_HEADER = 'x-state-passphrase';
+
+const importStateSchema = z.object({
+  archive: z.record(z.any()),
+  passphrase: z.string().min(MIN_PASSPHRASE_LENGTH),
+  on_conflict: z.enum(['skip', 'overwrite']).default('skip'),
+  // Imports only preview unless explicitly applied
+  dry_run: z.boolean().default(true),
+});
+
+// null clears a limit; omitted fields are left as they are
+const budgetLimitsSchema = z.object({
+  max_batch_total: z.number().positive().nullable().optional(),
+  max_daily_total: z.number().positive().nullable().optional(),
+  max_batch_employees: z.number().int().positive().nullable().optional(),
+});
+
+const budgetOverrideSchema = z.object({
+  company_id: z.string().min(1),
+  batch_id: z.string().min(1),
+  reason: z.string().min(1).max(500),
+  ttl_seconds: z.number().int().positive().max(MAX_OVERRIDE_TTL_SECONDS).default(DEFAULT_OVERRIDE_TTL_SECONDS),
+});
+
+function auditActor(req: Request): string {
+  return (req.headers['x-admin-actor'] as string | undefined) || req.ip || 'unknown';
+}
+
+/**
+ * GET /v1/admin/state/export - Encrypted archive of non-secret configuration
+ */
+router.get('/state/export', authenticateAdmin, asyncHandler(async (req, res) => {
+  const passphrase = req.headers[PASSPHRASE_HEADER];
+  if (typeof passphrase !== 'string' || passphrase.length < MIN_PASSPHRASE_LENGTH) {
+    throw new AppError(
+      `X-State-Passphrase must be at least ${MIN_PASSPHRASE_LENGTH} characters`,
+      400,
+      'INVALID_PASSPHRASE'
+    );
+  }
+
+  const snapshot = await readRouter.read(req, readState);
+  const archive = sealState(snapshot, passphrase);
+  const counts = {
+    merchants: snapshot.merchants.length,
+    api_keys: snapshot.api_keys.length,
+    webhooks: snapshot.webhooks.length,
+  };
+
+  await prisma.auditEvent.create({
+    data: { action: 'state.export', actor: auditActor(req), details: { exported_at: snapshot.exported_at, counts } },
+  });
+  logger.info('State exported', { actor: auditActor(req), ...counts });
+
+  const filename = `ninjapay-state-${snapshot.exported_at.replace(/[:.]/g, '-')}.json`;
+  res.setHeader('Content-Disposition', `attachment; filename="${filename}"`);
+  res.json(archive);
+}));
+
+/**
+ * POST /v1/admin/state/import - Preview, or apply, an exported archive
+ */
+router.post('/state/import', authenticateAdmin, asyncHandler(async (req, res) => {
+  const body = importStateSchema.parse(req.body);
+  const snapshot = openState(body.archive, body.passphrase);
+
+  if (body.dry_run) {
+    const plan = await previewImport(snapshot, body.on_conflict);
+    return res.json({
+      success: true,
+      data: { applied: false, exported_at: snapshot.exported_at, on_conflict: body.on_conflict, plan },
+    });
+  }
+
+  const archiveDigest = crypto.createHash('sha256').update(JSON.stringify(body.archive)).digest('hex');
+  const plan = await applyImport(snapshot, body.on_conflict, { actor: auditActor(req), archiveDigest });
+
+  logger.info('State imported', {
+    actor: auditActor(req),
+    archiveDigest,
+    onConflict: body.on_conflict,
+    ...summarize(plan),
+  });
+
+  res.json({
+    success: true,
+    data: { applied: true, exported_at: snapshot.exported_at, on_conflict: body.on_conflict, plan },
+  });
+}));
+
+/**
+ * POST /v1/admin/merchants/:id/approve - Allow a self-registered merchant into live mode
+ */
+router.post('/merchants/:id/approve', authenticateAdmin, asyncHandler(async (req, res) => {
+  const merchant = await approveMerchant(req.params.id, auditActor(req));
+
+  logger.info('Merchant approved', { actor: auditActor(req), merchantId: merchant.id });
+
+  res.json({
+    success: true,
+    data: { id: merchant.id, status: merchant.status.toLowerCase() },
+    timestamp: Date.now(),
+  });
+}));
+
+/**
+ * PUT /v1/admin/companies/:id/budget - Set a company's payroll budget limits
+ */
+router.put('/companies/:id/budget', authenticateAdmin, asyncHandler(async (req, res) => {
+  const body = budgetLimitsSchema.parse(req.body);
+
+  const existing = await prisma.company.findUnique({ where: { id: req.params.id } });
+  if (!existing) {
+    throw new AppError('Company not found', 404, 'COMPANY_NOT_FOUND');
+  }
+
+  const company = await prisma.company.update({
+    where: { id: req.params.id },
+    data: {
+      maxBatchTotal: body.max_batch_total,
+      maxDailyTotal: body.max_daily_total,
+      maxBatchEmployees: body.max_batch_employees,
+    },
+  });
+
+  await prisma.auditEvent.create({
+    data: { action: 'budget.limits_updated', actor: auditActor(req), details: { company_id: company.id, ...body } },
+  });
+  logger.info('Payroll budget updated', { actor: auditActor(req), companyId: company.id, ...body });
+
+  res.json({
+    success: true,
+    data: { company_id: company.id, ...serializeBudget(company, await queuedToday(company.id)) },
+    timestamp: Date.now(),
+  });
+}));
+
+/**
+ * POST /v1/admin/budget-overrides - Let one payroll batch through its company's limits
+ */
+router.post('/budget-overrides', authenticateAdmin, asyncHandler(async (req, res) => {
+  const body = budgetOverrideSchema.parse(req.body);
+  const { override, token } = await issueBudgetOverride({
+    companyId: body.company_id,
+    batchId: body.batch_id,
+    reason: body.reason,
+    ttlSeconds: body.ttl_seconds,
+    actor: auditActor(req),
+  });
+
+  res.status(201).json({
+    success: true,
+    data: {
+      id: override.id,
+      company_id: override.companyId,
+      batch_id: override.batchId,
+      reason: override.reason,
+      // Only returned here; pass it as X-Budget-Override when executing or settling the batch
+      token,
+      expires_at: override.expiresAt,
+      created_at: override.createdAt,
+    },
+    timestamp: Date.now(),
+  });
+}));
+
+/**
+ * GET /v1/admin/metrics - Gateway counters in Prometheus text format
+ */
+router.get('/metrics', authenticateAdmin, (_req, res) => {
+  res.type('text/plain; version=0.0.4').send(renderMetrics());
+});
+
+export default router;
@@ -1,25 +1,31 @@
-import { Router } from 'express';
+import { Request, Router } from 'express';
 import { z } from 'zod';
 import crypto from 'crypto';
 import bcrypt from 'bcrypt';
 import { prisma } from '@ninjapay/database';
 import { asyncHandler, AppError } from '../middleware/error-handler.js';
-import { authenticateMerchant } from '../middleware/authenticate.js';
+import { API_KEY_PREFIXES, assertLivemode, authenticateMerchant } from '../middleware/authenticate.js';
+import { readRouter } from '../services/read-routing.js';
 import { createLogger } from '@ninjapay/logger';
 
 const router = Router();
 const logger = createLogger('api-keys');
 
+// Recipient wallets or merchant ids; an empty list lifts the restriction
+const allowedMerchantsSchema = z.array(z.string().min(1).max(64)).max(100);
+
 const createApiKeySchema = z.object({
   name: z.string().min(1).max(100),
   permissions: z.array(z.string()).default(['read', 'write']),
   expiresAt: z.string().datetime().optional(),
+  allowed_merchants: allowedMerchantsSchema.default([]),
 });
 
 const updateApiKeySchema = z.object({
   name: z.string().min(1).max(100).optional(),
   permissions: z.array(z.string()).optional(),
   active: z.boolean().optional(),
+  allowed_merchants: allowedMerchantsSchema.optional(),
 });
 
 const listApiKeysSchema = z.object({
@@ -28,14 +34,26 @@ const listApiKeysSchema = z.object({
   offset: z.coerce.number().int().nonnegative().default(0),
 });
 
+/**
+ * A key restricted to some merchants can't manage keys, or it could lift
+ * its own restriction
+ */
+function assertUnrestricted(req: Request) {
+  if (req.apiKeyScope && req.apiKeyScope.allowedMerchants.length > 0) {
+    throw new AppError('API keys restricted to merchants cannot manage API keys', 403, 'MERCHANT_NOT_ALLOWED');
+  }
+}
+
 function serializeApiKey(key: any, rawKey?: string) {
   return {
     id: key.id,
     merchant_id: key.merchantId,
     name: key.name,
     key_prefix: key.keyPrefix,
+    livemode: key.livemode,
     key: rawKey, // Only included on creation
     permissions: key.permissions,
+    allowed_merchants: key.allowedMerchants ?? [],
     active: key.active,
     last_used_at: key.lastUsedAt,
     expires_at: key.expiresAt,
@@ -47,20 +65,25 @@ function serializeApiKey(key: any, rawKey?: string) {
  * POST /v1/api_keys - Create new API key
  */
 router.post('/', authenticateMerchant, asyncHandler(async (req, res) => {
+  assertUnrestricted(req);
   const body = createApiKeySchema.parse(req.body);
   const merchantId = req.merchantId!;
 
-  // Check API key limit (max 10 per merchant)
+  // Keys are issued in the caller's mode; use a test key (or a dashboard
+  // session in test mode) to mint sandbox keys
+  const livemode = req.livemode!;
+
+  // Check API key limit (max 10 per merchant per mode)
   const existingCount = await prisma.apiKey.count({
-    where: { merchantId, active: true },
+    where: { merchantId, livemode, active: true },
   });
 
   if (existingCount >= 10) {
     throw new AppError('Maximum API key limit reached (10)', 400, 'API_KEY_LIMIT');
   }
 
-  // Generate API key: sk_live_<random>
-  const keyPrefix = 'sk_live_';
+  // Generate API key: sk_live_<random> or sk_test_<random>
+  const keyPrefix = API_KEY_PREFIXES[livemode ? 'live' : 'test'];
   const keyRandom = crypto.randomBytes(24).toString('hex');
   const rawKey = `${keyPrefix}${keyRandom}`;
 
@@ -72,13 +95,19 @@ router.post('/', authenticateMerchant, asyncHandler(async (req, res) => {
       merchantId,
       keyHash,
       keyPrefix,
+      livemode,
       name: body.name,
       permissions: body.permissions,
+      allowedMerchants: [...new Set(body.allowed_merchants)],
       expiresAt: body.expiresAt ? new Date(body.expiresAt) : null,
     },
   });
 
-  logger.info('API key created', { apiKeyId: apiKey.id, merchantId });
+  logger.info('API key created', {
+    apiKeyId: apiKey.id,
+    merchantId,
+    allowedMerchants: apiKey.allowedMerchants.length,
+  });
 
   // Return raw key only once
   res.status(201).json({
@@ -96,20 +125,22 @@ router.get('/', authenticateMerchant, asyncHandler(async (req, res) => {
   const query = listApiKeysSchema.parse(req.query);
   const merchantId = req.merchantId!;
 
-  const where: any = { merchantId };
+  const where: any = { merchantId, livemode: req.livemode! };
   if (query.active !== undefined) {
     where.active = query.active === 'true';
   }
 
-  const [apiKeys, total] = await Promise.all([
-    prisma.apiKey.findMany({
-      where,
-      orderBy: { createdAt: 'desc' },
-      take: query.limit,
-      skip: query.offset,
-    }),
-    prisma.apiKey.count({ where }),
-  ]);
+  const [apiKeys, total] = await readRouter.read(req, (db) =>
+    Promise.all([
+      db.apiKey.findMany({
+        where,
+        orderBy: { createdAt: 'desc' },
+        take: query.limit,
+        skip: query.offset,
+      }),
+      db.apiKey.count({ where }),
+    ])
+  );
 
   res.json({
     success: true,
@@ -139,6 +170,8 @@ router.get('/:id', authenticateMerchant, asyncHandler(async (req, res) => {
     throw new AppError('API key not found', 404, 'API_KEY_NOT_FOUND');
   }
 
+  assertLivemode(req, apiKey, 'API key');
+
   res.json({
     success: true,
     data: serializeApiKey(apiKey),
@@ -150,6 +183,7 @@ router.get('/:id', authenticateMerchant, asyncHandler(async (req, res) => {
  * PATCH /v1/api_keys/:id - Update API key
  */
 router.patch('/:id', authenticateMerchant, asyncHandler(async (req, res) => {
+  assertUnrestricted(req);
   const body = updateApiKeySchema.parse(req.body);
 
   const apiKey = await prisma.apiKey.findFirst({
@@ -163,12 +197,15 @@ router.patch('/:id', authenticateMerchant, asyncHandler(async (req, res) => {
     throw new AppError('API key not found', 404, 'API_KEY_NOT_FOUND');
   }
 
+  assertLivemode(req, apiKey, 'API key');
+
   const updated = await prisma.apiKey.update({
     where: { id: apiKey.id },
     data: {
       name: body.name,
       permissions: body.permissions,
       active: body.active,
+      allowedMerchants: body.allowed_merchants && [...new Set(body.allowed_merchants)],
     },
   });
 
@@ -185,6 +222,7 @@ router.patch('/:id', authenticateMerchant, asyncHandler(async (req, res) => {
  * DELETE /v1/api_keys/:id - Revoke API key
  */
 router.delete('/:id', authenticateMerchant, asyncHandler(async (req, res) => {
+  assertUnrestricted(req);
   const apiKey = await prisma.apiKey.findFirst({
     where: {
       id: req.params.id,
@@ -196,6 +234,8 @@ router.delete('/:id', authenticateMerchant, asyncHandler(async (req, res) => {
     throw new AppError('API key not found', 404, 'API_KEY_NOT_FOUND');
   }
 
+  assertLivemode(req, apiKey, 'API key');
+
   await prisma.apiKey.update({
     where: { id: apiKey.id },
     data: { active: false },
@@ -214,6 +254,7 @@ router.delete('/:id', authenticateMerchant, asyncHandler(async (req, res) => {
  * POST /v1/api_keys/:id/roll - Roll (regenerate) API key
  */
 router.post('/:id/roll', authenticateMerchant, asyncHandler(async (req, res) => {
+  assertUnrestricted(req);
   const apiKey = await prisma.apiKey.findFirst({
     where: {
       id: req.params.id,
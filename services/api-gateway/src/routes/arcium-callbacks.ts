@@ -3,6 +3,8 @@ import { z } from 'zod';
 import { prisma } from '@ninjapay/database';
 import { asyncHandler, AppError } from '../middleware/error-handler.js';
 import { ArciumClientService } from '../services/arcium-client.js';
+import { getWebhookDeliveryService } from '../services/webhook-delivery.js';
+import { revertPayment, verifyReorgSignature } from '../services/payment-reorg.js';
 import { createLogger } from '@ninjapay/logger';
 
 const router = Router();
@@ -72,21 +74,51 @@ router.post('/', asyncHandler(async (req, res) => {
   });
 }));
 
+const reorgEventSchema = z.object({
+  event: z.literal('payment.reverted'),
+  payment: z.object({
+    record: z.string(),
+    payment_id: z.string().regex(/^[0-9a-f]{64}$/),
+    merchant: z.string(),
+    amount: z.number().int().nonnegative(),
+    fee: z.number().int().nonnegative(),
+    day: z.string(),
+    indexed_slot: z.number().int().nonnegative(),
+    detected_at: z.number().int().nonnegative(),
+  }),
+});
+
+/**
+ * POST /v1/arcium/callbacks/reorgs - A payment was rolled back before finalizing
+ */
+router.post('/reorgs', asyncHandler(async (req, res) => {
+  const signature = req.headers['x-ninjapay-signature'] as string;
+  if (!signature) {
+    throw new AppError('Missing callback signature', 401, 'MISSING_SIGNATURE');
+  }
+
+  const body = (req as typeof req & { rawBody?: string }).rawBody ?? JSON.stringify(req.body);
+  if (!verifyReorgSignature(process.env.ARCIUM_CALLBACK_SECRET || '', signature, body)) {
+    logger.warn('Invalid reorg callback signature received');
+    throw new AppError('Invalid callback signature', 401, 'INVALID_SIGNATURE');
+  }
+
+  const event = reorgEventSchema.parse(req.body);
+  const outcome = await revertPayment(event.payment);
+
+  res.json({
+    success: true,
+    data: outcome,
+    timestamp: Date.now(),
+  });
+}));
+
 /**
  * Handle payment settlement callback
  */
 async function handlePaymentSettlement(callback: z.infer<typeof callbackSchema>) {
   const paymentIntent = await prisma.paymentIntent.findFirst({
     where: { computationId: callback.computation_id },
-    include: {
-      merchant: {
-        include: {
-          webhooks: {
-            where: { enabled: true },
-          },
-        },
-      },
-    },
   });
 
   if (!paymentIntent) {
@@ -124,12 +156,17 @@ async function handlePaymentSettlement(callback: z.infer<typeof callbackSchema>)
     ? 'payment_intent.confirmed'
     : 'payment_intent.failed';
 
-  await sendWebhooks(paymentIntent.merchant.webhooks, eventType, {
-    payment_intent_id: paymentIntent.id,
-    status: newStatus.toLowerCase(),
-    tx_signature: txSignature,
-    timestamp: callback.timestamp,
-  });
+  await getWebhookDeliveryService().broadcastEvent(
+    paymentIntent.merchantId,
+    paymentIntent.livemode,
+    eventType,
+    {
+      payment_intent_id: paymentIntent.id,
+      status: newStatus.toLowerCase(),
+      tx_signature: txSignature,
+      timestamp: callback.timestamp,
+    }
+  );
 }
 
 /**
@@ -194,111 +231,4 @@ async function handlePayrollSettlement(callback: z.infer<typeof callbackSchema>)
   });
 }
 
-/**
- * Send webhook notifications
- */
-async function sendWebhooks(
-  webhooks: any[],
-  eventType: string,
-  payload: Record<string, any>
-) {
-  const crypto = await import('crypto');
-
-  for (const webhook of webhooks) {
-    if (!webhook.events.includes(eventType)) {
-      continue;
-    }
-
-    const webhookPayload = {
-      id: `evt_${crypto.randomBytes(16).toString('hex')}`,
-      type: eventType,
-      created: Date.now(),
-      data: payload,
-    };
-
-    const payloadString = JSON.stringify(webhookPayload);
-    const signature = crypto
-      .createHmac('sha256', webhook.secret)
-      .update(payloadString)
-      .digest('hex');
-
-    // Create delivery record
-    const delivery = await prisma.webhookDelivery.create({
-      data: {
-        webhookId: webhook.id,
-        eventType,
-        payload: webhookPayload,
-        attempts: 0,
-        status: 'PENDING',
-      },
-    });
-
-    // Send webhook (fire and forget with retry logic)
-    deliverWebhook(webhook.url, payloadString, signature, delivery.id).catch(err => {
-      logger.error('Webhook delivery failed', { error: err, deliveryId: delivery.id });
-    });
-  }
-}
-
-/**
- * Deliver webhook with retry logic
- */
-async function deliverWebhook(
-  url: string,
-  payload: string,
-  signature: string,
-  deliveryId: string,
-  attempt = 1
-) {
-  const maxAttempts = 3;
-  const retryDelays = [0, 5000, 30000]; // immediate, 5s, 30s
-
-  try {
-    const response = await fetch(url, {
-      method: 'POST',
-      headers: {
-        'Content-Type': 'application/json',
-        'X-Webhook-Signature': signature,
-        'X-Webhook-Timestamp': Date.now().toString(),
-      },
-      body: payload,
-    });
-
-    await prisma.webhookDelivery.update({
-      where: { id: deliveryId },
-      data: {
-        attempts: attempt,
-        status: response.ok ? 'DELIVERED' : 'FAILED',
-        responseCode: response.status,
-        respondedAt: new Date(),
-      },
-    });
-
-    if (!response.ok && attempt < maxAttempts) {
-      // Schedule retry
-      setTimeout(() => {
-        deliverWebhook(url, payload, signature, deliveryId, attempt + 1);
-      }, retryDelays[attempt]);
-    }
-
-    logger.debug('Webhook delivered', { deliveryId, status: response.status, attempt });
-  } catch (error) {
-    await prisma.webhookDelivery.update({
-      where: { id: deliveryId },
-      data: {
-        attempts: attempt,
-        status: attempt >= maxAttempts ? 'FAILED' : 'PENDING',
-      },
-    });
-
-    if (attempt < maxAttempts) {
-      setTimeout(() => {
-        deliverWebhook(url, payload, signature, deliveryId, attempt + 1);
-      }, retryDelays[attempt]);
-    }
-
-    logger.warn('Webhook delivery error', { deliveryId, error, attempt });
-  }
-}
-
 export default router;
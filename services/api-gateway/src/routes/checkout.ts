@@ -3,6 +3,9 @@ import { z } from 'zod';
 import { prisma } from '@ninjapay/database';
 import { asyncHandler, AppError } from '../middleware/error-handler.js';
 import { ArciumClientService } from '../services/arcium-client.js';
+import { getCostQuoteService } from '../services/cost-quote.js';
+import { getWebhookDeliveryService } from '../services/webhook-delivery.js';
+import { MEMO_PROGRAM_ID, memoSchema } from '../utils/memo.js';
 import { createLogger } from '@ninjapay/logger';
 
 const router = Router();
@@ -19,6 +22,7 @@ function getArciumClient(): ArciumClientService {
 const createIntentSchema = z.object({
   amount: z.number().positive(),
   payer_wallet: z.string().min(32).max(64),
+  memo: memoSchema.optional(),
 });
 
 const confirmPaymentSchema = z.object({
@@ -161,16 +165,28 @@ router.post('/:linkId/intent', asyncHandler(async (req, res) => {
     },
   });
 
+  // Disclose fees and rent before the payer signs
+  const costBreakdown = await getCostQuoteService().tryQuote({
+    payer: body.payer_wallet,
+    recipient: paymentLink.merchant.walletAddress,
+    amount: body.amount,
+    currency: paymentLink.currency,
+    memo: body.memo,
+  });
+
   // Create payment intent
   const paymentIntent = await prisma.paymentIntent.create({
     data: {
       merchantId: paymentLink.merchantId,
+      livemode: paymentLink.livemode, // Checkout inherits the link's mode
       recipient: paymentLink.merchant.walletAddress,
       encryptedAmount: encryptionResult.ciphertext,
       amountCommitment: encryptionResult.commitment,
+      commitmentAlgorithm: encryptionResult.commitmentAlgorithm,
       currency: paymentLink.currency,
       status: 'PENDING',
       description: `Payment via ${paymentLink.name}`,
+      memo: body.memo,
       computationStatus: 'QUEUED',
       metadata: {
         paymentLinkId: paymentLink.id,
@@ -178,6 +194,7 @@ router.post('/:linkId/intent', asyncHandler(async (req, res) => {
         amount: body.amount,
         encrypted: true,
       },
+      costBreakdown: costBreakdown ?? undefined,
     },
   });
 
@@ -195,6 +212,11 @@ router.post('/:linkId/intent', asyncHandler(async (req, res) => {
       amount: body.amount,
       currency: paymentLink.currency,
       commitment: encryptionResult.commitment,
+      // Append as an SPL Memo instruction after process_payment, signed by the payer
+      memo: body.memo
+        ? { text: body.memo, program_id: MEMO_PROGRAM_ID.toBase58() }
+        : null,
+      cost_breakdown: costBreakdown,
     },
     timestamp: Date.now(),
   });
@@ -211,15 +233,6 @@ router.post('/:linkId/confirm', asyncHandler(async (req, res) => {
   // Find payment intent
   const paymentIntent = await prisma.paymentIntent.findUnique({
     where: { id: body.payment_intent_id },
-    include: {
-      merchant: {
-        include: {
-          webhooks: {
-            where: { enabled: true },
-          },
-        },
-      },
-    },
   });
 
   if (!paymentIntent) {
@@ -254,15 +267,21 @@ router.post('/:linkId/confirm', asyncHandler(async (req, res) => {
     },
   });
 
-  // Send webhooks
-  await sendPaymentWebhooks(paymentIntent.merchant, 'payment_link.payment_completed', {
-    payment_intent_id: paymentIntent.id,
-    payment_link_id: metadata.paymentLinkId,
-    tx_signature: body.tx_signature,
-    amount: metadata.amount,
-    currency: paymentIntent.currency,
-    payer_wallet: metadata.payerWallet,
-  });
+  // Send webhooks (signed and retried like every other event)
+  await getWebhookDeliveryService().broadcastEvent(
+    paymentIntent.merchantId,
+    paymentIntent.livemode,
+    'payment_link.payment_completed',
+    {
+      payment_intent_id: paymentIntent.id,
+      payment_link_id: metadata.paymentLinkId,
+      tx_signature: body.tx_signature,
+      memo: paymentIntent.memo,
+      amount: metadata.amount,
+      currency: paymentIntent.currency,
+      payer_wallet: metadata.payerWallet,
+    }
+  );
 
   logger.info('Payment confirmed', {
     paymentIntentId: paymentIntent.id,
@@ -275,81 +294,10 @@ router.post('/:linkId/confirm', asyncHandler(async (req, res) => {
       id: updated.id,
       status: updated.status.toLowerCase(),
       tx_signature: updated.txSignature,
+      memo: updated.memo,
     },
     timestamp: Date.now(),
   });
 }));
 
-/**
- * Send webhook notifications for payment events
- */
-async function sendPaymentWebhooks(
-  merchant: any,
-  eventType: string,
-  payload: Record<string, any>
-) {
-  const crypto = await import('crypto');
-
-  for (const webhook of merchant.webhooks || []) {
-    if (!webhook.events.includes(eventType)) {
-      continue;
-    }
-
-    const webhookPayload = {
-      id: `evt_${crypto.randomBytes(16).toString('hex')}`,
-      type: eventType,
-      created: Date.now(),
-      data: payload,
-    };
-
-    const payloadString = JSON.stringify(webhookPayload);
-    const signature = crypto
-      .createHmac('sha256', webhook.secret)
-      .update(payloadString)
-      .digest('hex');
-
-    // Create delivery record and send
-    try {
-      const delivery = await prisma.webhookDelivery.create({
-        data: {
-          webhookId: webhook.id,
-          eventType,
-          payload: webhookPayload,
-          attempts: 1,
-          status: 'PENDING',
-        },
-      });
-
-      // Fire and forget webhook delivery
-      fetch(webhook.url, {
-        method: 'POST',
-        headers: {
-          'Content-Type': 'application/json',
-          'X-Webhook-Signature': signature,
-          'X-Webhook-Timestamp': Date.now().toString(),
-        },
-        body: payloadString,
-      })
-        .then(async (response) => {
-          await prisma.webhookDelivery.update({
-            where: { id: delivery.id },
-            data: {
-              status: response.ok ? 'DELIVERED' : 'FAILED',
-              responseCode: response.status,
-              respondedAt: new Date(),
-            },
-          });
-        })
-        .catch(async () => {
-          await prisma.webhookDelivery.update({
-            where: { id: delivery.id },
-            data: { status: 'FAILED' },
-          });
-        });
-    } catch (err) {
-      logger.error('Failed to create webhook delivery', { error: err });
-    }
-  }
-}
-
 export default router;
@@ -1,5 +1,6 @@
 import { Router, Request, Response } from 'express';
-import { prisma } from '@ninjapay/database';
+import { prisma, prismaRead } from '@ninjapay/database';
+import { readRouter } from '../services/read-routing.js';
 
 const router = Router();
 
@@ -13,7 +14,10 @@ router.get('/', (_req: Request, res: Response) => {
 });
 
 router.get('/detailed', async (_req: Request, res: Response) => {
-  const checks: Record<string, { status: string; latency?: number; error?: string }> = {};
+  const checks: Record<
+    string,
+    { status: string; latency?: number; error?: string; disabled_endpoints?: string[] }
+  > = {};
 
   // Database check
   const dbStart = Date.now();
@@ -24,6 +28,17 @@ router.get('/detailed', async (_req: Request, res: Response) => {
     checks.database = { status: 'unhealthy', error: (error as Error).message };
   }
 
+  // Read replica check, when configured
+  if (prismaRead) {
+    const replicaStart = Date.now();
+    try {
+      await prismaRead.$queryRaw`SELECT 1`;
+      checks.database_replica = { status: 'healthy', latency: Date.now() - replicaStart };
+    } catch (error) {
+      checks.database_replica = { status: 'unhealthy', error: (error as Error).message };
+    }
+  }
+
   // Arcium service check
   const arciumUrl = process.env.ARCIUM_SERVICE_URL || 'http://localhost:8002';
   const arciumStart = Date.now();
@@ -32,9 +47,13 @@ router.get('/detailed', async (_req: Request, res: Response) => {
     const timeout = setTimeout(() => controller.abort(), 5000);
     const response = await fetch(`${arciumUrl}/api/health`, { signal: controller.signal });
     clearTimeout(timeout);
+    // Endpoints an operator switched off leave the service up but degraded
+    const body = response.ok ? ((await response.json().catch(() => ({}))) as any) : {};
+    const disabled: string[] = (body.disabled_endpoints ?? []).map((d: { endpoint: string }) => d.endpoint);
     checks.arcium = {
-      status: response.ok ? 'healthy' : 'unhealthy',
+      status: !response.ok ? 'unhealthy' : disabled.length > 0 ? 'degraded' : 'healthy',
       latency: Date.now() - arciumStart,
+      ...(disabled.length > 0 && { disabled_endpoints: disabled }),
     };
   } catch (error) {
     checks.arcium = { status: 'unhealthy', error: (error as Error).message };
@@ -49,6 +68,7 @@ router.get('/detailed', async (_req: Request, res: Response) => {
     service: 'ninjapay-api-gateway',
     version: '2.0.0',
     checks,
+    database_pools: await readRouter.stats(),
     timestamp: new Date().toISOString(),
   });
 });
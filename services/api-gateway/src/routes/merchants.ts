@@ -0,0 +1,74 @@
+import { Router } from 'express';
+import { z } from 'zod';
+import { asyncHandler } from '../middleware/error-handler.js';
+import { createLogger } from '@ninjapay/logger';
+import { issueChallenge, selfRegister } from '../services/merchant-onboarding.js';
+
+const router = Router();
+const logger = createLogger('merchants');
+
+const challengeSchema = z.object({
+  wallet: z.string().min(32).max(44),
+});
+
+const selfRegisterSchema = z.object({
+  wallet: z.string().min(32).max(44),
+  challenge: z.string().regex(/^[0-9a-f]{64}$/),
+  // base64 of the 64-byte ed25519 signature over the challenge message
+  signature: z.string().min(1).max(128),
+  business_name: z.string().min(1).max(200),
+  email: z.string().email(),
+});
+
+/**
+ * GET /v1/merchants/challenge?wallet= - Message to sign for self-registration
+ */
+router.get('/challenge', asyncHandler(async (req, res) => {
+  const { wallet } = challengeSchema.parse(req.query);
+  const challenge = await issueChallenge(wallet);
+
+  res.json({
+    success: true,
+    data: challenge,
+    timestamp: Date.now(),
+  });
+}));
+
+/**
+ * POST /v1/merchants/self-register - Create a pending merchant from a signed challenge
+ */
+router.post('/self-register', asyncHandler(async (req, res) => {
+  const body = selfRegisterSchema.parse(req.body);
+
+  const { merchant, apiKey, rawKey } = await selfRegister({
+    wallet: body.wallet,
+    challenge: body.challenge,
+    signature: Buffer.from(body.signature, 'base64'),
+    businessName: body.business_name,
+    email: body.email,
+  });
+
+  logger.info('Merchant self-registered', { merchantId: merchant.id, wallet: body.wallet.substring(0, 8) });
+
+  res.status(201).json({
+    success: true,
+    data: {
+      id: merchant.id,
+      wallet_address: merchant.walletAddress,
+      business_name: merchant.businessName,
+      email: merchant.email,
+      status: merchant.status.toLowerCase(),
+      api_key: {
+        id: apiKey.id,
+        key: rawKey,
+        key_prefix: apiKey.keyPrefix,
+        livemode: apiKey.livemode,
+        permissions: apiKey.permissions,
+      },
+    },
+    message: 'Store this key securely. It will not be shown again. Live mode is available once the merchant is approved.',
+    timestamp: Date.now(),
+  });
+}));
+
+export default router;
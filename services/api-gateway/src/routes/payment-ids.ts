@@ -0,0 +1,77 @@
+import { Router } from 'express';
+import { z } from 'zod';
+import { asyncHandler } from '../middleware/error-handler.js';
+import { authenticateMerchant } from '../middleware/authenticate.js';
+import { createLogger } from '@ninjapay/logger';
+import {
+  DEFAULT_RESERVATION_TTL_SECONDS,
+  MAX_RESERVATION_TTL_SECONDS,
+  consumePaymentId,
+  reservePaymentId,
+  serializeReservation,
+} from '../services/payment-id-reservations.js';
+
+const router = Router();
+const logger = createLogger('payment-ids');
+
+const reserveSchema = z.object({
+  order_ref: z.string().min(1).max(200),
+  ttl_seconds: z.number().int().positive().max(MAX_RESERVATION_TTL_SECONDS).default(DEFAULT_RESERVATION_TTL_SECONDS),
+  payment_intent_id: z.string().optional(),
+});
+
+const consumeSchema = z.object({
+  payment_intent_id: z.string().optional(),
+  tx_signature: z.string().optional(),
+});
+
+const paymentIdSchema = z.string().regex(/^[0-9a-f]{64}$/, 'payment_id must be 32 bytes of lowercase hex');
+
+/**
+ * POST /v1/payment_ids/reserve - Reserve the payment_id for an order
+ */
+router.post('/reserve', authenticateMerchant, asyncHandler(async (req, res) => {
+  const body = reserveSchema.parse(req.body);
+
+  const { reservation, created } = await reservePaymentId({
+    merchantId: req.merchantId!,
+    livemode: req.livemode!,
+    orderRef: body.order_ref,
+    ttlSeconds: body.ttl_seconds,
+    paymentIntentId: body.payment_intent_id,
+  });
+
+  if (created) {
+    logger.info('Payment id reserved', { merchantId: req.merchantId, orderRef: body.order_ref });
+  }
+
+  res.status(created ? 201 : 200).json({
+    success: true,
+    data: serializeReservation(reservation),
+    timestamp: Date.now(),
+  });
+}));
+
+/**
+ * POST /v1/payment_ids/:id/consume - Mark a reserved id used by a landed payment
+ */
+router.post('/:id/consume', authenticateMerchant, asyncHandler(async (req, res) => {
+  const paymentId = paymentIdSchema.parse(req.params.id);
+  const body = consumeSchema.parse(req.body ?? {});
+
+  const reservation = await consumePaymentId({
+    merchantId: req.merchantId!,
+    livemode: req.livemode!,
+    paymentId,
+    paymentIntentId: body.payment_intent_id,
+    txSignature: body.tx_signature,
+  });
+
+  res.json({
+    success: true,
+    data: serializeReservation(reservation),
+    timestamp: Date.now(),
+  });
+}));
+
+export default router;
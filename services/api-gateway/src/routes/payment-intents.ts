@@ -2,8 +2,17 @@ import { Router } from 'express';
 import { z } from 'zod';
 import { prisma } from '@ninjapay/database';
 import { asyncHandler, AppError } from '../middleware/error-handler.js';
-import { authenticateMerchant } from '../middleware/authenticate.js';
+import {
+  apiKeyScopeDetails,
+  assertLivemode,
+  assertMerchantAllowed,
+  authenticateMerchant,
+} from '../middleware/authenticate.js';
 import { ArciumClientService } from '../services/arcium-client.js';
+import { CostBreakdown, getCostQuoteService } from '../services/cost-quote.js';
+import { findReservationForIntent, serializeReservation } from '../services/payment-id-reservations.js';
+import { memoSchema } from '../utils/memo.js';
+import { readRouter } from '../services/read-routing.js';
 import { createLogger } from '@ninjapay/logger';
 
 const router = Router();
@@ -19,21 +28,29 @@ function getArciumClient(): ArciumClientService {
 }
 
 // Validation schemas
+// Merchant metadata: flat string map, max 20 keys, 40-char keys, 500-char values
+const metadataSchema = z
+  .record(z.string().min(1).max(40), z.string().max(500))
+  .refine((metadata) => Object.keys(metadata).length <= 20, {
+    message: 'metadata: at most 20 keys are allowed',
+  });
+
 const createPaymentIntentSchema = z.object({
   amount: z.number().positive(),
   currency: z.string().default('USDC'),
   recipient: z.string().min(32).max(64),
   description: z.string().optional(),
-  metadata: z.record(z.any()).optional(),
+  memo: memoSchema.optional(),
+  metadata: metadataSchema.optional(),
 });
 
 const updatePaymentIntentSchema = z.object({
   description: z.string().optional(),
-  metadata: z.record(z.any()).optional(),
+  metadata: metadataSchema.optional(),
 });
 
 const listPaymentIntentsSchema = z.object({
-  status: z.enum(['PENDING', 'PROCESSING', 'CONFIRMED', 'FINALIZED', 'FAILED', 'CANCELLED']).optional(),
+  status: z.enum(['PENDING', 'PROCESSING', 'CONFIRMED', 'FINALIZED', 'FAILED', 'CANCELLED', 'REVERTED']).optional(),
   limit: z.coerce.number().int().positive().max(100).default(50),
   offset: z.coerce.number().int().nonnegative().default(0),
 });
@@ -48,14 +65,18 @@ function serializePaymentIntent(pi: any) {
     recipient: pi.recipient,
     amount: null, // Never expose plaintext amount
     amount_commitment: pi.amountCommitment,
+    commitment_algorithm: pi.commitmentAlgorithm,
     encrypted_amount: pi.encryptedAmount ? Buffer.from(pi.encryptedAmount).toString('base64') : null,
     currency: pi.currency,
     status: pi.status.toLowerCase(),
     description: pi.description,
+    memo: pi.memo ?? null,
     tx_signature: pi.txSignature,
     computation_id: pi.computationId,
     computation_status: pi.computationStatus?.toLowerCase(),
     metadata: pi.metadata,
+    cost_breakdown: pi.costBreakdown ?? null,
+    livemode: pi.livemode,
     created_at: pi.createdAt,
     updated_at: pi.updatedAt,
   };
@@ -70,6 +91,8 @@ router.post('/', authenticateMerchant, asyncHandler(async (req, res) => {
 
   logger.info('Creating payment intent', { merchantId, recipient: body.recipient });
 
+  await assertMerchantAllowed(req, body.recipient);
+
   // Get merchant wallet for encryption
   const merchant = await prisma.merchant.findUnique({
     where: { id: merchantId },
@@ -90,16 +113,27 @@ router.post('/', authenticateMerchant, asyncHandler(async (req, res) => {
     },
   });
 
+  const costBreakdown = await getCostQuoteService().tryQuote({
+    payer: merchant.walletAddress,
+    recipient: body.recipient,
+    amount: body.amount,
+    currency: body.currency,
+    memo: body.memo,
+  });
+
   // Create payment intent in database
   const paymentIntent = await prisma.paymentIntent.create({
     data: {
       merchantId,
+      livemode: req.livemode!,
       recipient: body.recipient,
       encryptedAmount: encryptionResult.ciphertext,
       amountCommitment: encryptionResult.commitment,
+      commitmentAlgorithm: encryptionResult.commitmentAlgorithm,
       currency: body.currency,
       status: 'PENDING',
       description: body.description,
+      memo: body.memo,
       computationStatus: 'QUEUED',
       metadata: {
         ...(body.metadata || {}),
@@ -107,6 +141,7 @@ router.post('/', authenticateMerchant, asyncHandler(async (req, res) => {
         encryptionKey: merchant.walletAddress,
         amount: body.amount, // Store for later settlement
       },
+      costBreakdown: costBreakdown ?? undefined,
     },
   });
 
@@ -134,9 +169,17 @@ router.get('/:id', authenticateMerchant, asyncHandler(async (req, res) => {
     throw new AppError('Payment intent not found', 404, 'PAYMENT_INTENT_NOT_FOUND');
   }
 
+  assertLivemode(req, paymentIntent, 'payment intent');
+
+  // Shown on lookup so support can see why an order is stuck
+  const reservation = await findReservationForIntent(paymentIntent.id);
+
   res.json({
     success: true,
-    data: serializePaymentIntent(paymentIntent),
+    data: {
+      ...serializePaymentIntent(paymentIntent),
+      payment_id_reservation: reservation ? serializeReservation(reservation) : null,
+    },
     timestamp: Date.now(),
   });
 }));
@@ -148,20 +191,22 @@ router.get('/', authenticateMerchant, asyncHandler(async (req, res) => {
   const query = listPaymentIntentsSchema.parse(req.query);
   const merchantId = req.merchantId!;
 
-  const where: any = { merchantId };
+  const where: any = { merchantId, livemode: req.livemode! };
   if (query.status) {
     where.status = query.status;
   }
 
-  const [paymentIntents, total] = await Promise.all([
-    prisma.paymentIntent.findMany({
-      where,
-      orderBy: { createdAt: 'desc' },
-      take: query.limit,
-      skip: query.offset,
-    }),
-    prisma.paymentIntent.count({ where }),
-  ]);
+  const [paymentIntents, total] = await readRouter.read(req, (db) =>
+    Promise.all([
+      db.paymentIntent.findMany({
+        where,
+        orderBy: { createdAt: 'desc' },
+        take: query.limit,
+        skip: query.offset,
+      }),
+      db.paymentIntent.count({ where }),
+    ])
+  );
 
   res.json({
     success: true,
@@ -193,6 +238,8 @@ router.patch('/:id', authenticateMerchant, asyncHandler(async (req, res) => {
     throw new AppError('Payment intent not found', 404, 'PAYMENT_INTENT_NOT_FOUND');
   }
 
+  assertLivemode(req, paymentIntent, 'payment intent');
+
   if (paymentIntent.status !== 'PENDING') {
     throw new AppError('Can only update pending payment intents', 400, 'INVALID_STATUS');
   }
@@ -212,6 +259,58 @@ router.patch('/:id', authenticateMerchant, asyncHandler(async (req, res) => {
   });
 }));
 
+/**
+ * POST /v1/payment_intents/:id/quote - Re-quote fees and rent
+ */
+router.post('/:id/quote', authenticateMerchant, asyncHandler(async (req, res) => {
+  const paymentIntent = await prisma.paymentIntent.findFirst({
+    where: {
+      id: req.params.id,
+      merchantId: req.merchantId!,
+    },
+  });
+
+  if (!paymentIntent) {
+    throw new AppError('Payment intent not found', 404, 'PAYMENT_INTENT_NOT_FOUND');
+  }
+
+  assertLivemode(req, paymentIntent, 'payment intent');
+
+  if (paymentIntent.status !== 'PENDING') {
+    throw new AppError('Can only quote pending payment intents', 400, 'INVALID_STATUS');
+  }
+
+  const merchant = await prisma.merchant.findUnique({
+    where: { id: req.merchantId! },
+    select: { walletAddress: true },
+  });
+  const metadata = paymentIntent.metadata as Record<string, any> || {};
+
+  let costBreakdown: CostBreakdown;
+  try {
+    costBreakdown = await getCostQuoteService().quote({
+      payer: metadata.payerWallet ?? merchant!.walletAddress,
+      recipient: paymentIntent.recipient,
+      amount: metadata.amount,
+      currency: paymentIntent.currency,
+    });
+  } catch (error: any) {
+    logger.error('Cost quote failed', { paymentIntentId: paymentIntent.id, error: error.message });
+    throw new AppError('Unable to quote network costs, try again shortly', 502, 'QUOTE_UNAVAILABLE');
+  }
+
+  const updated = await prisma.paymentIntent.update({
+    where: { id: paymentIntent.id },
+    data: { costBreakdown },
+  });
+
+  res.json({
+    success: true,
+    data: serializePaymentIntent(updated),
+    timestamp: Date.now(),
+  });
+}));
+
 /**
  * POST /v1/payment_intents/:id/confirm - Confirm payment intent
  */
@@ -227,10 +326,15 @@ router.post('/:id/confirm', authenticateMerchant, asyncHandler(async (req, res)
     throw new AppError('Payment intent not found', 404, 'PAYMENT_INTENT_NOT_FOUND');
   }
 
+  assertLivemode(req, paymentIntent, 'payment intent');
+
   if (paymentIntent.status !== 'PENDING') {
     throw new AppError(`Cannot confirm payment intent in status: ${paymentIntent.status}`, 400, 'INVALID_STATUS');
   }
 
+  // Checked again here: the intent may predate the key or its allowlist
+  await assertMerchantAllowed(req, paymentIntent.recipient);
+
   logger.info('Confirming payment intent', { paymentIntentId: paymentIntent.id });
 
   // Get merchant wallet
@@ -261,6 +365,20 @@ router.post('/:id/confirm', authenticateMerchant, asyncHandler(async (req, res)
     },
   });
 
+  await prisma.auditEvent.create({
+    data: {
+      action: 'payment_intent.settle',
+      actor: req.apiKeyScope ? `api_key:${req.apiKeyScope.apiKeyId}` : `merchant:${req.merchantId}`,
+      details: {
+        payment_intent_id: paymentIntent.id,
+        merchant_id: req.merchantId!,
+        recipient: paymentIntent.recipient,
+        computation_id: computationResult.computationId,
+        ...apiKeyScopeDetails(req),
+      },
+    },
+  });
+
   logger.info('Payment intent confirmed', {
     paymentIntentId: paymentIntent.id,
     computationId: computationResult.computationId
@@ -288,6 +406,8 @@ router.post('/:id/cancel', authenticateMerchant, asyncHandler(async (req, res) =
     throw new AppError('Payment intent not found', 404, 'PAYMENT_INTENT_NOT_FOUND');
   }
 
+  assertLivemode(req, paymentIntent, 'payment intent');
+
   if (!['PENDING', 'PROCESSING'].includes(paymentIntent.status)) {
     throw new AppError(`Cannot cancel payment intent in status: ${paymentIntent.status}`, 400, 'INVALID_STATUS');
   }
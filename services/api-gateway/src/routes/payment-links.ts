@@ -3,7 +3,8 @@ import { z } from 'zod';
 import crypto from 'crypto';
 import { prisma } from '@ninjapay/database';
 import { asyncHandler, AppError } from '../middleware/error-handler.js';
-import { authenticateMerchant } from '../middleware/authenticate.js';
+import { assertLivemode, authenticateMerchant } from '../middleware/authenticate.js';
+import { readRouter } from '../services/read-routing.js';
 import { createLogger } from '@ninjapay/logger';
 
 const router = Router();
@@ -44,6 +45,7 @@ function serializePaymentLink(link: any) {
     usage_count: link.usageCount,
     expires_at: link.expiresAt,
     metadata: link.metadata,
+    livemode: link.livemode,
     created_at: link.createdAt,
     updated_at: link.updatedAt,
   };
@@ -64,6 +66,7 @@ router.post('/', authenticateMerchant, asyncHandler(async (req, res) => {
   const paymentLink = await prisma.paymentLink.create({
     data: {
       merchantId,
+      livemode: req.livemode!,
       url,
       name: body.name,
       amount: body.amount,
@@ -98,6 +101,8 @@ router.get('/:id', authenticateMerchant, asyncHandler(async (req, res) => {
     throw new AppError('Payment link not found', 404, 'PAYMENT_LINK_NOT_FOUND');
   }
 
+  assertLivemode(req, paymentLink, 'payment link');
+
   res.json({
     success: true,
     data: serializePaymentLink(paymentLink),
@@ -112,20 +117,22 @@ router.get('/', authenticateMerchant, asyncHandler(async (req, res) => {
   const query = listPaymentLinksSchema.parse(req.query);
   const merchantId = req.merchantId!;
 
-  const where: any = { merchantId };
+  const where: any = { merchantId, livemode: req.livemode! };
   if (query.active !== undefined) {
     where.active = query.active === 'true';
   }
 
-  const [paymentLinks, total] = await Promise.all([
-    prisma.paymentLink.findMany({
-      where,
-      orderBy: { createdAt: 'desc' },
-      take: query.limit,
-      skip: query.offset,
-    }),
-    prisma.paymentLink.count({ where }),
-  ]);
+  const [paymentLinks, total] = await readRouter.read(req, (db) =>
+    Promise.all([
+      db.paymentLink.findMany({
+        where,
+        orderBy: { createdAt: 'desc' },
+        take: query.limit,
+        skip: query.offset,
+      }),
+      db.paymentLink.count({ where }),
+    ])
+  );
 
   res.json({
     success: true,
@@ -157,6 +164,8 @@ router.patch('/:id', authenticateMerchant, asyncHandler(async (req, res) => {
     throw new AppError('Payment link not found', 404, 'PAYMENT_LINK_NOT_FOUND');
   }
 
+  assertLivemode(req, paymentLink, 'payment link');
+
   const updated = await prisma.paymentLink.update({
     where: { id: paymentLink.id },
     data: {
@@ -191,6 +200,8 @@ router.delete('/:id', authenticateMerchant, asyncHandler(async (req, res) => {
     throw new AppError('Payment link not found', 404, 'PAYMENT_LINK_NOT_FOUND');
   }
 
+  assertLivemode(req, paymentLink, 'payment link');
+
   await prisma.paymentLink.update({
     where: { id: paymentLink.id },
     data: { active: false },
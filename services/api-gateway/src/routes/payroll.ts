@@ -5,6 +5,8 @@ import { asyncHandler, AppError } from '../middleware/error-handler.js';
 import { authenticateCompany } from '../middleware/authenticate.js';
 import { ArciumClientService } from '../services/arcium-client.js';
 import { getSettlementService } from '../services/settlement.js';
+import { readRouter } from '../services/read-routing.js';
+import { OVERRIDE_HEADER, enforcePayrollBudget, queuedToday, serializeBudget } from '../services/payroll-budget.js';
 import { createLogger } from '@ninjapay/logger';
 
 const router = Router();
@@ -82,6 +84,7 @@ function serializeBatch(batch: any, payments?: any[]) {
       id: p.id,
       employee_id: p.employeeId,
       amount_commitment: p.amountCommitment,
+      commitment_algorithm: p.commitmentAlgorithm,
       status: p.status.toLowerCase(),
       tx_signature: p.txSignature,
     })),
@@ -141,15 +144,17 @@ router.get('/employees', authenticateCompany, asyncHandler(async (req, res) => {
     where.active = query.active === 'true';
   }
 
-  const [employees, total] = await Promise.all([
-    prisma.employee.findMany({
-      where,
-      orderBy: { name: 'asc' },
-      take: query.limit,
-      skip: query.offset,
-    }),
-    prisma.employee.count({ where }),
-  ]);
+  const [employees, total] = await readRouter.read(req, (db) =>
+    Promise.all([
+      db.employee.findMany({
+        where,
+        orderBy: { name: 'asc' },
+        take: query.limit,
+        skip: query.offset,
+      }),
+      db.employee.count({ where }),
+    ])
+  );
 
   res.json({
     success: true,
@@ -322,6 +327,7 @@ router.post('/batches', authenticateCompany, asyncHandler(async (req, res) => {
           employeeId: payment.employeeId,
           encryptedAmount: encryptionResult.ciphertext,
           amountCommitment: encryptionResult.commitment,
+          commitmentAlgorithm: encryptionResult.commitmentAlgorithm,
           status: 'PENDING',
         },
       });
@@ -354,15 +360,17 @@ router.get('/batches', authenticateCompany, asyncHandler(async (req, res) => {
     where.status = query.status;
   }
 
-  const [batches, total] = await Promise.all([
-    prisma.payrollBatch.findMany({
-      where,
-      orderBy: { createdAt: 'desc' },
-      take: query.limit,
-      skip: query.offset,
-    }),
-    prisma.payrollBatch.count({ where }),
-  ]);
+  const [batches, total] = await readRouter.read(req, (db) =>
+    Promise.all([
+      db.payrollBatch.findMany({
+        where,
+        orderBy: { createdAt: 'desc' },
+        take: query.limit,
+        skip: query.offset,
+      }),
+      db.payrollBatch.count({ where }),
+    ])
+  );
 
   res.json({
     success: true,
@@ -436,6 +444,8 @@ router.post('/batches/:id/execute', authenticateCompany, asyncHandler(async (req
     throw new AppError(`Cannot execute batch in status: ${batch.status}`, 400, 'INVALID_STATUS');
   }
 
+  await enforcePayrollBudget(batch, req.header(OVERRIDE_HEADER));
+
   // Get company wallet
   const company = await prisma.company.findUnique({
     where: { id: req.companyId! },
@@ -466,6 +476,7 @@ router.post('/batches/:id/execute', authenticateCompany, asyncHandler(async (req
     data: {
       status: 'PROCESSING',
       computationId: computationResult.computationId,
+      executedDate: new Date(),
     },
   });
 
@@ -554,10 +565,12 @@ router.post('/batches/:id/settle', authenticateCompany, asyncHandler(async (req,
     throw new AppError(`Cannot settle batch in status: ${batch.status}`, 400, 'INVALID_STATUS');
   }
 
+  await enforcePayrollBudget(batch, req.header(OVERRIDE_HEADER));
+
   // Update batch to processing
   await prisma.payrollBatch.update({
     where: { id: batch.id },
-    data: { status: 'PROCESSING' },
+    data: { status: 'PROCESSING', executedDate: new Date() },
   });
 
   // Update all payment statuses
@@ -620,6 +633,27 @@ router.post('/batches/:id/settle', authenticateCompany, asyncHandler(async (req,
   }
 }));
 
+/**
+ * GET /v1/payroll/budget - Budget limits and today's queued total
+ */
+router.get('/budget', authenticateCompany, asyncHandler(async (req, res) => {
+  const companyId = req.companyId!;
+  const company = await prisma.company.findUnique({
+    where: { id: companyId },
+    select: { maxBatchTotal: true, maxDailyTotal: true, maxBatchEmployees: true },
+  });
+
+  if (!company) {
+    throw new AppError('Company not found', 404, 'COMPANY_NOT_FOUND');
+  }
+
+  res.json({
+    success: true,
+    data: serializeBudget(company, await queuedToday(companyId)),
+    timestamp: Date.now(),
+  });
+}));
+
 /**
  * GET /v1/payroll/balance - Get settlement wallet balance
  */
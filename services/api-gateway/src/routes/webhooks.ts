@@ -1,10 +1,27 @@
 import { Router } from 'express';
 import { z } from 'zod';
-import crypto from 'crypto';
 import { prisma } from '@ninjapay/database';
 import { asyncHandler, AppError } from '../middleware/error-handler.js';
-import { authenticateMerchant } from '../middleware/authenticate.js';
+import { assertLivemode, authenticateMerchant } from '../middleware/authenticate.js';
+import { readRouter } from '../services/read-routing.js';
 import { createLogger } from '@ninjapay/logger';
+import {
+  defaultOverlapSeconds,
+  generateWebhookSecret,
+  MAX_OVERLAP_SECONDS,
+  rotateWebhookSecret,
+  serializeSecretRotation,
+} from '../services/webhook-secrets.js';
+import {
+  compileWebhookFormat,
+  DEFAULT_WEBHOOK_FORMAT,
+  WebhookTransformError,
+} from '../services/webhook-transform.js';
+import {
+  MAX_REPLAY_EVENT_IDS,
+  serializeReplay,
+  startWebhookReplay,
+} from '../services/webhook-replay.js';
 
 const router = Router();
 const logger = createLogger('webhooks');
@@ -14,21 +31,53 @@ const WEBHOOK_EVENTS = [
   'payment_intent.confirmed',
   'payment_intent.failed',
   'payment_intent.cancelled',
+  'payment.reverted',
   'payment_link.payment_completed',
 ] as const;
 
+// Body format: "ninjapay" | "stripe_compatible" | "jq:<expr>"
+const webhookFormatSchema = z.string().superRefine((format, ctx) => {
+  try {
+    compileWebhookFormat(format);
+  } catch (error) {
+    const message = error instanceof WebhookTransformError ? error.message : 'invalid expression';
+    ctx.addIssue({ code: z.ZodIssueCode.custom, message: `format: ${message}` });
+  }
+});
+
 const createWebhookSchema = z.object({
   url: z.string().url(),
   events: z.array(z.enum(WEBHOOK_EVENTS)).min(1),
   enabled: z.boolean().default(true),
+  format: webhookFormatSchema.default(DEFAULT_WEBHOOK_FORMAT),
 });
 
 const updateWebhookSchema = z.object({
   url: z.string().url().optional(),
   events: z.array(z.enum(WEBHOOK_EVENTS)).min(1).optional(),
   enabled: z.boolean().optional(),
+  format: webhookFormatSchema.optional(),
+});
+
+const rotateSecretSchema = z.object({
+  // How long the current secret keeps signing deliveries alongside the new one
+  overlap_seconds: z.number().int().nonnegative().max(MAX_OVERLAP_SECONDS).optional(),
 });
 
+// Either a time range or a list of event ids
+const replaySchema = z
+  .object({
+    from: z.coerce.date().optional(),
+    to: z.coerce.date().optional(),
+    event_ids: z.array(z.string().min(1)).min(1).max(MAX_REPLAY_EVENT_IDS).optional(),
+  })
+  .refine((body) => (body.event_ids ? !body.from && !body.to : body.from && body.to), {
+    message: 'Provide either event_ids or both from and to',
+  })
+  .refine((body) => !body.from || !body.to || body.from <= body.to, {
+    message: 'from must not be after to',
+  });
+
 const listWebhooksSchema = z.object({
   enabled: z.enum(['true', 'false']).optional(),
   limit: z.coerce.number().int().positive().max(100).default(50),
@@ -42,7 +91,10 @@ function serializeWebhook(webhook: any, includeSecret = false) {
     url: webhook.url,
     events: webhook.events,
     enabled: webhook.enabled,
+    format: webhook.format,
+    livemode: webhook.livemode,
     secret: includeSecret ? webhook.secret : undefined,
+    secret_rotation: serializeSecretRotation(webhook),
     created_at: webhook.createdAt,
     updated_at: webhook.updatedAt,
   };
@@ -56,15 +108,17 @@ router.post('/', authenticateMerchant, asyncHandler(async (req, res) => {
   const merchantId = req.merchantId!;
 
   // Generate webhook secret
-  const secret = `whsec_${crypto.randomBytes(24).toString('hex')}`;
+  const secret = generateWebhookSecret();
 
   const webhook = await prisma.webhook.create({
     data: {
       merchantId,
+      livemode: req.livemode!,
       url: body.url,
       events: body.events,
       secret,
       enabled: body.enabled,
+      format: body.format,
     },
   });
 
@@ -93,6 +147,8 @@ router.get('/:id', authenticateMerchant, asyncHandler(async (req, res) => {
     throw new AppError('Webhook not found', 404, 'WEBHOOK_NOT_FOUND');
   }
 
+  assertLivemode(req, webhook, 'webhook');
+
   res.json({
     success: true,
     data: serializeWebhook(webhook),
@@ -107,20 +163,22 @@ router.get('/', authenticateMerchant, asyncHandler(async (req, res) => {
   const query = listWebhooksSchema.parse(req.query);
   const merchantId = req.merchantId!;
 
-  const where: any = { merchantId };
+  const where: any = { merchantId, livemode: req.livemode! };
   if (query.enabled !== undefined) {
     where.enabled = query.enabled === 'true';
   }
 
-  const [webhooks, total] = await Promise.all([
-    prisma.webhook.findMany({
-      where,
-      orderBy: { createdAt: 'desc' },
-      take: query.limit,
-      skip: query.offset,
-    }),
-    prisma.webhook.count({ where }),
-  ]);
+  const [webhooks, total] = await readRouter.read(req, (db) =>
+    Promise.all([
+      db.webhook.findMany({
+        where,
+        orderBy: { createdAt: 'desc' },
+        take: query.limit,
+        skip: query.offset,
+      }),
+      db.webhook.count({ where }),
+    ])
+  );
 
   res.json({
     success: true,
@@ -152,12 +210,15 @@ router.patch('/:id', authenticateMerchant, asyncHandler(async (req, res) => {
     throw new AppError('Webhook not found', 404, 'WEBHOOK_NOT_FOUND');
   }
 
+  assertLivemode(req, webhook, 'webhook');
+
   const updated = await prisma.webhook.update({
     where: { id: webhook.id },
     data: {
       url: body.url,
       events: body.events,
       enabled: body.enabled,
+      format: body.format,
     },
   });
 
@@ -185,6 +246,8 @@ router.delete('/:id', authenticateMerchant, asyncHandler(async (req, res) => {
     throw new AppError('Webhook not found', 404, 'WEBHOOK_NOT_FOUND');
   }
 
+  assertLivemode(req, webhook, 'webhook');
+
   await prisma.webhook.delete({
     where: { id: webhook.id },
   });
@@ -199,9 +262,13 @@ router.delete('/:id', authenticateMerchant, asyncHandler(async (req, res) => {
 }));
 
 /**
- * POST /v1/webhooks/:id/rotate-secret - Rotate webhook secret
+ * POST /v1/webhooks/:id/rotate-secret - Rotate webhook secret, keeping the
+ * old one valid for the overlap window
  */
 router.post('/:id/rotate-secret', authenticateMerchant, asyncHandler(async (req, res) => {
+  const body = rotateSecretSchema.parse(req.body ?? {});
+  const overlapSeconds = body.overlap_seconds ?? defaultOverlapSeconds();
+
   const webhook = await prisma.webhook.findFirst({
     where: {
       id: req.params.id,
@@ -213,14 +280,11 @@ router.post('/:id/rotate-secret', authenticateMerchant, asyncHandler(async (req,
     throw new AppError('Webhook not found', 404, 'WEBHOOK_NOT_FOUND');
   }
 
-  const newSecret = `whsec_${crypto.randomBytes(24).toString('hex')}`;
+  assertLivemode(req, webhook, 'webhook');
 
-  const updated = await prisma.webhook.update({
-    where: { id: webhook.id },
-    data: { secret: newSecret },
-  });
+  const updated = await rotateWebhookSecret(webhook, overlapSeconds);
 
-  logger.info('Webhook secret rotated', { webhookId: webhook.id });
+  logger.info('Webhook secret rotated', { webhookId: webhook.id, overlapSeconds });
 
   res.json({
     success: true,
@@ -229,4 +293,69 @@ router.post('/:id/rotate-secret', authenticateMerchant, asyncHandler(async (req,
   });
 }));
 
+/**
+ * POST /v1/webhooks/:id/replay - Re-send past events in a time range or
+ * by id, marked `replayed: true`
+ */
+router.post('/:id/replay', authenticateMerchant, asyncHandler(async (req, res) => {
+  const body = replaySchema.parse(req.body ?? {});
+
+  const webhook = await prisma.webhook.findFirst({
+    where: {
+      id: req.params.id,
+      merchantId: req.merchantId!,
+    },
+  });
+
+  if (!webhook) {
+    throw new AppError('Webhook not found', 404, 'WEBHOOK_NOT_FOUND');
+  }
+
+  assertLivemode(req, webhook, 'webhook');
+
+  const replay = await startWebhookReplay(webhook, {
+    from: body.from,
+    to: body.to,
+    eventIds: body.event_ids,
+  });
+
+  res.status(202).json({
+    success: true,
+    data: await serializeReplay(replay),
+    timestamp: Date.now(),
+  });
+}));
+
+/**
+ * GET /v1/webhooks/:id/replays/:replayId - Replay progress
+ */
+router.get('/:id/replays/:replayId', authenticateMerchant, asyncHandler(async (req, res) => {
+  const webhook = await prisma.webhook.findFirst({
+    where: {
+      id: req.params.id,
+      merchantId: req.merchantId!,
+    },
+  });
+
+  if (!webhook) {
+    throw new AppError('Webhook not found', 404, 'WEBHOOK_NOT_FOUND');
+  }
+
+  assertLivemode(req, webhook, 'webhook');
+
+  const replay = await prisma.webhookReplay.findFirst({
+    where: { id: req.params.replayId, webhookId: webhook.id },
+  });
+
+  if (!replay) {
+    throw new AppError('Replay not found', 404, 'REPLAY_NOT_FOUND');
+  }
+
+  res.json({
+    success: true,
+    data: await serializeReplay(replay),
+    timestamp: Date.now(),
+  });
+}));
+
 export default router;
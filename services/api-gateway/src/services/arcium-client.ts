@@ -8,9 +8,13 @@ interface EncryptionOptions {
   metadata?: Record<string, any>;
 }
 
+// Hash behind an amount commitment; arcium-service also accepts blake3
+export type CommitmentAlgorithm = 'sha256' | 'blake3';
+
 interface EncryptionResult {
   ciphertext: Buffer;
   commitment: string;
+  commitmentAlgorithm: CommitmentAlgorithm;
   nonce: string;
 }
 
@@ -115,6 +119,7 @@ export class ArciumClientService {
     return {
       ciphertext,
       commitment,
+      commitmentAlgorithm: 'sha256',
       nonce: nonce.toString('hex'),
     };
   }
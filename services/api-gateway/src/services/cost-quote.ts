@@ -0,0 +1,277 @@
+import {
+  Connection,
+  PublicKey,
+  SystemProgram,
+  TransactionInstruction,
+  TransactionMessage,
+  VersionedTransaction,
+  ComputeBudgetProgram,
+  LAMPORTS_PER_SOL,
+} from '@solana/web3.js';
+import {
+  ACCOUNT_SIZE,
+  createAssociatedTokenAccountIdempotentInstruction,
+  createTransferInstruction,
+  getAssociatedTokenAddressSync,
+} from '@solana/spl-token';
+import { createLogger } from '@ninjapay/logger';
+import { getPriceService, PriceSource } from './price.js';
+import { createMemoInstruction } from '../utils/memo.js';
+import { protocolFee as protocolFeeUnits } from '../utils/fee.js';
+import { decodeSimulationError } from '../utils/program-errors.js';
+
+const logger = createLogger('cost-quote');
+
+// Mirrors `8 + PaymentRecord::INIT_SPACE` in programs/ninjapay-vault: discriminator,
+// payment_id, payer, merchant, amount, fee, commitment, timestamp, bump, status, mint, expires_at,
+// record_version, reserved, fee_collectors, fee_shares
+export const PAYMENT_RECORD_SPACE = 8 + 32 + 32 + 32 + 8 + 8 + 32 + 8 + 1 + 1 + 32 + 8 + 1 + 64 + 3 * 32 + 3 * 8;
+
+// VaultConfig: discriminator, authority, fee_collector, then fee_basis_points (u16 LE)
+const VAULT_CONFIG_FEE_OFFSET = 8 + 32 + 32;
+
+// Solana's default per-instruction compute limit, used when simulation reports none
+const DEFAULT_COMPUTE_UNITS = 200_000;
+
+// Base fee for the single payer signature, used if the RPC cannot price the message
+const LAMPORTS_PER_SIGNATURE = 5000;
+
+const TOKEN_MINTS: Record<string, { mainnet: string; devnet: string; decimals: number }> = {
+  USDC: {
+    mainnet: 'EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v',
+    devnet: '4zMMC9srt5Ri5X14GAgXhaHii3GnPAEERYPJgZJDncDU',
+    decimals: 6,
+  },
+};
+
+export interface CostQuoteRequest {
+  payer: string;
+  recipient: string;
+  amount: number; // in payment currency units
+  currency: string;
+  memo?: string;
+}
+
+// Type aliases (not interfaces) so quotes are assignable to Prisma Json columns
+
+/** A cost in lamports and in the payment currency (null if no price is available) */
+export type CostAmount = {
+  lamports: number | null;
+  amount: number | null;
+};
+
+export type CostBreakdown = {
+  quoted_at: string;
+  currency: string;
+  sol_price: number | null;
+  fee_basis_points: number;
+  protocol_fee: CostAmount;
+  network_fee: CostAmount;
+  payment_record_rent: CostAmount;
+  ata_creation_rent: CostAmount | null;
+  total: CostAmount;
+};
+
+/**
+ * Quotes what a payer is charged on top of the payment itself: the vault's
+ * protocol fee, the estimated network fee, rent for the PaymentRecord
+ * account and, for token payments to a new wallet, rent for the recipient's
+ * associated token account.
+ *
+ * Every quote re-reads the vault fee and rent from the chain, so re-quoting
+ * after a config change reflects the new values.
+ */
+export class CostQuoteService {
+  private vaultConfig: PublicKey;
+  private isMainnet: boolean;
+
+  constructor(
+    private connection: Connection,
+    private prices: PriceSource,
+    options: { vaultProgramId?: string; isMainnet?: boolean } = {}
+  ) {
+    const programId = new PublicKey(
+      options.vaultProgramId || process.env.VAULT_PROGRAM_ID || 'NJPvau1tPBHrRxUqrLvhLq3zDpNZRGpNPdTpP1Dvq6C'
+    );
+    [this.vaultConfig] = PublicKey.findProgramAddressSync([Buffer.from('vault_config')], programId);
+    this.isMainnet = options.isMainnet ?? false;
+  }
+
+  async quote(request: CostQuoteRequest): Promise<CostBreakdown> {
+    const currency = request.currency.toUpperCase();
+    const payer = new PublicKey(request.payer);
+    const recipient = new PublicKey(request.recipient);
+    const token = TOKEN_MINTS[currency];
+    const mint = token ? new PublicKey(this.isMainnet ? token.mainnet : token.devnet) : null;
+    const decimals = token?.decimals ?? 9;
+
+    const recipientAta = mint ? getAssociatedTokenAddressSync(mint, recipient, true) : null;
+
+    const [feeBasisPoints, solPrice, recordRent, ataRent] = await Promise.all([
+      this.feeBasisPoints(),
+      this.prices.solPrice(currency),
+      this.connection.getMinimumBalanceForRentExemption(PAYMENT_RECORD_SPACE),
+      recipientAta ? this.ataCreationRent(recipientAta) : Promise.resolve(null),
+    ]);
+
+    const networkLamports = await this.estimateNetworkFee(
+      [
+        ...this.transferInstructions(payer, recipient, request.amount, mint, recipientAta, decimals, ataRent !== null),
+        ...(request.memo ? [createMemoInstruction(request.memo, payer)] : []),
+      ],
+      payer
+    );
+
+    const toCurrency = (lamports: number): number | null =>
+      solPrice === null ? null : round((lamports / LAMPORTS_PER_SOL) * solPrice, decimals);
+    const fromLamports = (lamports: number): CostAmount => ({ lamports, amount: toCurrency(lamports) });
+
+    // Computed in base units and rounded down, as the program charges it
+    const protocolFeeAmount =
+      Number(protocolFeeUnits(BigInt(Math.round(request.amount * 10 ** decimals)), feeBasisPoints)) /
+      10 ** decimals;
+    const protocolFee: CostAmount = {
+      lamports:
+        solPrice === null ? null : Math.ceil((protocolFeeAmount / solPrice) * LAMPORTS_PER_SOL),
+      amount: protocolFeeAmount,
+    };
+
+    const networkFee = fromLamports(networkLamports);
+    const paymentRecordRent = fromLamports(recordRent);
+    const ataCreationRent = ataRent === null ? null : fromLamports(ataRent);
+    const components = [protocolFee, networkFee, paymentRecordRent, ...(ataCreationRent ? [ataCreationRent] : [])];
+
+    return {
+      quoted_at: new Date().toISOString(),
+      currency,
+      sol_price: solPrice,
+      fee_basis_points: feeBasisPoints,
+      protocol_fee: protocolFee,
+      network_fee: networkFee,
+      payment_record_rent: paymentRecordRent,
+      ata_creation_rent: ataCreationRent,
+      total: {
+        lamports: sum(components.map((c) => c.lamports), 0),
+        amount: sum(components.map((c) => c.amount), decimals),
+      },
+    };
+  }
+
+  /**
+   * Quote for disclosure alongside another operation; failures are logged and
+   * yield null so they never block it
+   */
+  async tryQuote(request: CostQuoteRequest): Promise<CostBreakdown | null> {
+    try {
+      return await this.quote(request);
+    } catch (error: any) {
+      logger.warn('Cost quote failed', { error: error.message });
+      return null;
+    }
+  }
+
+  /**
+   * Current vault fee; falls back to FEE_BASIS_POINTS before the vault is initialized
+   */
+  private async feeBasisPoints(): Promise<number> {
+    const account = await this.connection.getAccountInfo(this.vaultConfig);
+    if (account && account.data.length >= VAULT_CONFIG_FEE_OFFSET + 2) {
+      return account.data.readUInt16LE(VAULT_CONFIG_FEE_OFFSET);
+    }
+    return parseInt(process.env.FEE_BASIS_POINTS || '0');
+  }
+
+  /**
+   * Rent for the recipient's token account, or null if it already exists
+   */
+  private async ataCreationRent(ata: PublicKey): Promise<number | null> {
+    const account = await this.connection.getAccountInfo(ata);
+    return account ? null : this.connection.getMinimumBalanceForRentExemption(ACCOUNT_SIZE);
+  }
+
+  private transferInstructions(
+    payer: PublicKey,
+    recipient: PublicKey,
+    amount: number,
+    mint: PublicKey | null,
+    recipientAta: PublicKey | null,
+    decimals: number,
+    createAta: boolean
+  ): TransactionInstruction[] {
+    const baseUnits = BigInt(Math.round(amount * 10 ** decimals));
+    if (!mint || !recipientAta) {
+      return [SystemProgram.transfer({ fromPubkey: payer, toPubkey: recipient, lamports: baseUnits })];
+    }
+
+    const sourceAta = getAssociatedTokenAddressSync(mint, payer, true);
+    return [
+      ...(createAta
+        ? [createAssociatedTokenAccountIdempotentInstruction(payer, recipientAta, recipient, mint)]
+        : []),
+      createTransferInstruction(sourceAta, recipientAta, payer, baseUnits),
+    ];
+  }
+
+  /**
+   * Base fee for the message plus the priority fee for the compute units the
+   * simulation consumed
+   */
+  private async estimateNetworkFee(instructions: TransactionInstruction[], payer: PublicKey): Promise<number> {
+    const priorityFee = parseInt(process.env.SOLANA_PRIORITY_FEE || '1000'); // microlamports per CU
+    const { blockhash } = await this.connection.getLatestBlockhash();
+    const message = new TransactionMessage({
+      payerKey: payer,
+      recentBlockhash: blockhash,
+      instructions: [ComputeBudgetProgram.setComputeUnitPrice({ microLamports: priorityFee }), ...instructions],
+    }).compileToV0Message();
+
+    const [fee, simulation] = await Promise.all([
+      this.connection.getFeeForMessage(message),
+      this.connection
+        .simulateTransaction(new VersionedTransaction(message), {
+          sigVerify: false,
+          replaceRecentBlockhash: true,
+        })
+        .catch((error: any) => {
+          logger.warn('Fee simulation failed', { error: error.message });
+          return null;
+        }),
+    ]);
+
+    if (simulation?.value.err) {
+      const programError = decodeSimulationError(simulation.value.err);
+      logger.warn('Fee simulation reported a program error', {
+        err: simulation.value.err,
+        programError: programError?.name,
+        remediation: programError?.remediation,
+      });
+    }
+
+    const unitsConsumed = simulation?.value.unitsConsumed || DEFAULT_COMPUTE_UNITS;
+    return (fee.value ?? LAMPORTS_PER_SIGNATURE) + Math.ceil((priorityFee * unitsConsumed) / 1_000_000);
+  }
+}
+
+function round(value: number, decimals: number): number {
+  return Number(value.toFixed(decimals));
+}
+
+function sum(values: (number | null)[], decimals: number): number | null {
+  if (values.some((v) => v === null)) {
+    return null;
+  }
+  return round((values as number[]).reduce((a, b) => a + b, 0), decimals);
+}
+
+// Singleton instance
+let costQuoteService: CostQuoteService | null = null;
+
+export function getCostQuoteService(): CostQuoteService {
+  if (!costQuoteService) {
+    const rpcUrl = process.env.SOLANA_RPC_URL || 'https://api.devnet.solana.com';
+    costQuoteService = new CostQuoteService(new Connection(rpcUrl, 'confirmed'), getPriceService(), {
+      isMainnet: rpcUrl.includes('mainnet'),
+    });
+  }
+  return costQuoteService;
+}
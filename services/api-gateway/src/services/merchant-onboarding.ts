@@ -0,0 +1,192 @@
+import crypto from 'crypto';
+import bcrypt from 'bcrypt';
+import { PublicKey } from '@solana/web3.js';
+import { prisma } from '@ninjapay/database';
+import { AppError } from '../middleware/error-handler.js';
+import { API_KEY_PREFIXES } from '../middleware/authenticate.js';
+
+/**
+ * Self-serve merchant onboarding
+ *
+ * A merchant proves control of their wallet by signing a server-issued
+ * challenge with it. The challenge is single-use and expires after
+ * CHALLENGE_TTL_SECONDS. Self-registered merchants start PENDING with a
+ * test-mode API key only, until an admin approves them.
+ */
+
+export const CHALLENGE_TTL_SECONDS = 5 * 60;
+
+// Order of the ed25519 group; a signature's S must be below it
+const ED25519_ORDER = (1n << 252n) + 27742317777372353535851937790883648493n;
+
+// DER prefix of an ed25519 SubjectPublicKeyInfo, followed by the 32 raw key bytes
+const ED25519_SPKI_PREFIX = Buffer.from('302a300506032b6570032100', 'hex');
+
+export interface SelfRegisterRequest {
+  wallet: string;
+  challenge: string;
+  signature: Buffer;
+  businessName: string;
+  email: string;
+}
+
+function walletKey(wallet: string): PublicKey {
+  try {
+    return new PublicKey(wallet);
+  } catch {
+    throw new AppError('wallet must be a base58 Solana public key', 400, 'INVALID_WALLET');
+  }
+}
+
+/**
+ * Text the wallet signs (with signMessage) to prove control
+ */
+export function challengeMessage(wallet: string, challenge: string, expiresAt: Date): string {
+  return [
+    'Register this wallet as a NinjaPay merchant.',
+    '',
+    `Wallet: ${wallet}`,
+    `Challenge: ${challenge}`,
+    `Expires: ${expiresAt.toISOString()}`,
+  ].join('\n');
+}
+
+/**
+ * Verify an ed25519 signature by the wallet over `message`. Rejects
+ * non-canonical signatures (S >= group order), which would otherwise let a
+ * second valid encoding of the same signature through.
+ */
+export function verifyWalletSignature(wallet: string, message: string, signature: Buffer): boolean {
+  if (signature.length !== 64) return false;
+
+  const s = BigInt(`0x${Buffer.from(signature.subarray(32)).reverse().toString('hex')}`);
+  if (s >= ED25519_ORDER) return false;
+
+  const publicKey = crypto.createPublicKey({
+    key: Buffer.concat([ED25519_SPKI_PREFIX, walletKey(wallet).toBuffer()]),
+    format: 'der',
+    type: 'spki',
+  });
+  return crypto.verify(null, Buffer.from(message, 'utf8'), publicKey, signature);
+}
+
+/**
+ * Issue a challenge for the wallet to sign
+ */
+export async function issueChallenge(wallet: string, now: Date = new Date()) {
+  walletKey(wallet);
+
+  const challenge = crypto.randomBytes(32).toString('hex');
+  const expiresAt = new Date(now.getTime() + CHALLENGE_TTL_SECONDS * 1000);
+  const message = challengeMessage(wallet, challenge, expiresAt);
+
+  await prisma.onboardingChallenge.create({
+    data: { wallet, challenge, message, expiresAt },
+  });
+
+  return { challenge, message, expires_at: expiresAt };
+}
+
+/**
+ * Check the signed challenge and use it up. The signature is checked
+ * before the challenge is consumed, and consuming is a compare-and-swap,
+ * so a challenge registers at most one merchant.
+ */
+async function consumeChallenge(request: SelfRegisterRequest, now: Date): Promise<void> {
+  const invalid = new AppError('Challenge is invalid, expired or already used', 401, 'INVALID_CHALLENGE');
+
+  const record = await prisma.onboardingChallenge.findUnique({ where: { challenge: request.challenge } });
+  if (!record || record.wallet !== request.wallet || record.usedAt || record.expiresAt <= now) {
+    throw invalid;
+  }
+  if (!verifyWalletSignature(request.wallet, record.message, request.signature)) {
+    throw new AppError('Signature does not verify for this wallet and challenge', 401, 'INVALID_SIGNATURE');
+  }
+
+  const { count } = await prisma.onboardingChallenge.updateMany({
+    where: { id: record.id, usedAt: null, expiresAt: { gt: now } },
+    data: { usedAt: now },
+  });
+  if (count !== 1) {
+    throw invalid;
+  }
+}
+
+/**
+ * Register a PENDING merchant for a verified wallet and issue its test key
+ */
+export async function selfRegister(request: SelfRegisterRequest, now: Date = new Date()) {
+  walletKey(request.wallet);
+  await consumeChallenge(request, now);
+
+  const exists = new AppError('A merchant with this wallet or email already exists', 409, 'MERCHANT_EXISTS');
+  const existing = await prisma.merchant.findFirst({
+    where: { OR: [{ walletAddress: request.wallet }, { email: request.email }] },
+  });
+  if (existing) {
+    throw exists;
+  }
+
+  const keyPrefix = API_KEY_PREFIXES.test;
+  const rawKey = `${keyPrefix}${crypto.randomBytes(24).toString('hex')}`;
+  const keyHash = await bcrypt.hash(rawKey, 12);
+
+  return prisma.$transaction(async (tx: any) => {
+    const merchant = await tx.merchant.create({
+      data: {
+        walletAddress: request.wallet,
+        businessName: request.businessName,
+        email: request.email,
+        status: 'PENDING',
+      },
+    });
+    const apiKey = await tx.apiKey.create({
+      data: {
+        merchantId: merchant.id,
+        keyHash,
+        keyPrefix,
+        livemode: false,
+        name: 'Self-registration',
+        permissions: ['read', 'write'],
+      },
+    });
+    return { merchant, apiKey, rawKey };
+  }).catch((error: any) => {
+    // Registered concurrently with another challenge
+    if (error?.code === 'P2002') throw exists;
+    throw error;
+  });
+}
+
+/**
+ * Delete challenges past their expiry. Run by the retention job.
+ */
+export async function deleteExpiredChallenges(now: Date = new Date()): Promise<number> {
+  const { count } = await prisma.onboardingChallenge.deleteMany({
+    where: { expiresAt: { lte: now } },
+  });
+  return count;
+}
+
+/**
+ * Promote a PENDING merchant to ACTIVE, allowing live mode
+ */
+export async function approveMerchant(merchantId: string, actor: string) {
+  const { count } = await prisma.merchant.updateMany({
+    where: { id: merchantId, status: 'PENDING' },
+    data: { status: 'ACTIVE' },
+  });
+
+  const merchant = await prisma.merchant.findUnique({ where: { id: merchantId } });
+  if (!merchant) {
+    throw new AppError('Merchant not found', 404, 'MERCHANT_NOT_FOUND');
+  }
+  if (count !== 1) {
+    throw new AppError('Merchant is not pending approval', 409, 'MERCHANT_NOT_PENDING');
+  }
+
+  await prisma.auditEvent.create({
+    data: { action: 'merchant.approve', actor, details: { merchant_id: merchantId } },
+  });
+  return merchant;
+}
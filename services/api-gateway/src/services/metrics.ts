@@ -0,0 +1,45 @@
+/**
+ * In-process counters, exposed in Prometheus text format at
+ * GET /v1/admin/metrics
+ */
+
+type Labels = Record<string, string>;
+
+interface Counter {
+  help: string;
+  values: Map<string, number>;
+}
+
+const counters = new Map<string, Counter>();
+
+function labelKey(labels: Labels): string {
+  return Object.keys(labels)
+    .sort()
+    .map((name) => `${name}="${labels[name].replace(/\\/g, '\\\\').replace(/"/g, '\\"')}"`)
+    .join(',');
+}
+
+export function incrementCounter(name: string, help: string, labels: Labels = {}): void {
+  let counter = counters.get(name);
+  if (!counter) {
+    counter = { help, values: new Map() };
+    counters.set(name, counter);
+  }
+  const key = labelKey(labels);
+  counter.values.set(key, (counter.values.get(key) ?? 0) + 1);
+}
+
+export function renderMetrics(): string {
+  const lines: string[] = [];
+  for (const [name, counter] of counters) {
+    lines.push(`# HELP ${name} ${counter.help}`, `# TYPE ${name} counter`);
+    for (const [key, value] of counter.values) {
+      lines.push(key ? `${name}{${key}} ${value}` : `${name} ${value}`);
+    }
+  }
+  return lines.join('\n') + '\n';
+}
+
+export function resetMetrics(): void {
+  counters.clear();
+}
@@ -0,0 +1,194 @@
+import crypto from 'crypto';
+import { prisma } from '@ninjapay/database';
+import { AppError } from '../middleware/error-handler.js';
+
+/**
+ * payment_id reservations
+ *
+ * The vault program derives each PaymentRecord from a 32-byte payment_id, so
+ * two services that generate ids for the same order independently end up
+ * with two records. Callers reserve the id for (merchant, order_ref)
+ * instead: every reserve call returns the same id until the reservation
+ * expires or is consumed.
+ *
+ * Every state change is a compare-and-swap on (paymentId, status), so
+ * concurrent reserve and consume calls never both win.
+ */
+
+export const DEFAULT_RESERVATION_TTL_SECONDS = 15 * 60;
+export const MAX_RESERVATION_TTL_SECONDS = 24 * 60 * 60;
+
+// Concurrent reserve calls retry after losing a race
+const MAX_RESERVE_ATTEMPTS = 3;
+
+export interface ReserveRequest {
+  merchantId: string;
+  livemode: boolean;
+  orderRef: string;
+  ttlSeconds: number;
+  paymentIntentId?: string;
+}
+
+export interface ConsumeRequest {
+  merchantId: string;
+  livemode: boolean;
+  paymentId: string;
+  paymentIntentId?: string;
+  txSignature?: string;
+}
+
+function newPaymentId(): string {
+  return crypto.randomBytes(32).toString('hex');
+}
+
+function isUniqueViolation(error: any): boolean {
+  return error?.code === 'P2002';
+}
+
+/**
+ * Reserve the payment_id for an order. Resolves `created: false` when an
+ * active or consumed reservation already exists and is returned as is.
+ */
+export async function reservePaymentId(request: ReserveRequest): Promise<{ reservation: any; created: boolean }> {
+  const key = {
+    merchantId_livemode_orderRef: {
+      merchantId: request.merchantId,
+      livemode: request.livemode,
+      orderRef: request.orderRef,
+    },
+  };
+
+  for (let attempt = 0; attempt < MAX_RESERVE_ATTEMPTS; attempt++) {
+    const now = new Date();
+    const expiresAt = new Date(now.getTime() + request.ttlSeconds * 1000);
+    const existing = await prisma.paymentIdReservation.findUnique({ where: key });
+
+    if (!existing) {
+      try {
+        const reservation = await prisma.paymentIdReservation.create({
+          data: {
+            paymentId: newPaymentId(),
+            merchantId: request.merchantId,
+            livemode: request.livemode,
+            orderRef: request.orderRef,
+            paymentIntentId: request.paymentIntentId,
+            expiresAt,
+          },
+        });
+        return { reservation, created: true };
+      } catch (error) {
+        // Another caller reserved this order first; return theirs
+        if (isUniqueViolation(error)) continue;
+        throw error;
+      }
+    }
+
+    if (existing.status === 'CONSUMED' || (existing.status === 'RESERVED' && existing.expiresAt > now)) {
+      return { reservation: existing, created: false };
+    }
+
+    // Expired or released: issue a new id unless another caller already has
+    const { count } = await prisma.paymentIdReservation.updateMany({
+      where: { id: existing.id, paymentId: existing.paymentId, status: existing.status },
+      data: {
+        paymentId: newPaymentId(),
+        status: 'RESERVED',
+        paymentIntentId: request.paymentIntentId ?? null,
+        txSignature: null,
+        expiresAt,
+        consumedAt: null,
+        releasedAt: null,
+      },
+    });
+    if (count === 1) {
+      const reservation = await prisma.paymentIdReservation.findUnique({ where: { id: existing.id } });
+      return { reservation, created: true };
+    }
+  }
+
+  throw new AppError('Payment id reservation is contended, retry the request', 409, 'RESERVATION_CONFLICT');
+}
+
+/**
+ * Mark a reservation consumed once its payment has landed. A payment that
+ * lands after the TTL is still accepted until the retention job releases
+ * the reservation. Consuming twice with the same payment intent is a no-op.
+ */
+export async function consumePaymentId(request: ConsumeRequest): Promise<any> {
+  const where = {
+    paymentId: request.paymentId,
+    merchantId: request.merchantId,
+    livemode: request.livemode,
+  };
+
+  const { count } = await prisma.paymentIdReservation.updateMany({
+    where: { ...where, status: 'RESERVED' },
+    data: {
+      status: 'CONSUMED',
+      consumedAt: new Date(),
+      ...(request.paymentIntentId ? { paymentIntentId: request.paymentIntentId } : {}),
+      ...(request.txSignature ? { txSignature: request.txSignature } : {}),
+    },
+  });
+
+  const reservation = await prisma.paymentIdReservation.findFirst({ where });
+  if (!reservation) {
+    // Also the case once a re-reserve has replaced this id for the order
+    throw new AppError('Payment id reservation not found or released', 404, 'RESERVATION_NOT_FOUND');
+  }
+  if (count === 1) {
+    return reservation;
+  }
+
+  if (reservation.status === 'RELEASED') {
+    throw new AppError('Payment id reservation was released after expiring', 410, 'RESERVATION_RELEASED');
+  }
+  // Already consumed: idempotent unless a different payment claims it
+  if (request.paymentIntentId && reservation.paymentIntentId && reservation.paymentIntentId !== request.paymentIntentId) {
+    throw new AppError(
+      'Payment id was already consumed by another payment intent',
+      409,
+      'RESERVATION_ALREADY_CONSUMED'
+    );
+  }
+  return reservation;
+}
+
+/**
+ * Release reservations whose TTL has passed without a payment, so the order
+ * gets a fresh id on its next reserve. Run by the retention job.
+ */
+export async function releaseExpiredReservations(now: Date = new Date()): Promise<number> {
+  const { count } = await prisma.paymentIdReservation.updateMany({
+    where: { status: 'RESERVED', expiresAt: { lte: now } },
+    data: { status: 'RELEASED', releasedAt: now },
+  });
+  return count;
+}
+
+/**
+ * Latest reservation linked to a payment intent, for support lookups
+ */
+export async function findReservationForIntent(paymentIntentId: string): Promise<any | null> {
+  return prisma.paymentIdReservation.findFirst({
+    where: { paymentIntentId },
+    orderBy: { updatedAt: 'desc' },
+  });
+}
+
+export function serializeReservation(reservation: any, now: Date = new Date()) {
+  const expired = reservation.status === 'RESERVED' && reservation.expiresAt <= now;
+  return {
+    payment_id: reservation.paymentId,
+    order_ref: reservation.orderRef,
+    // "expired" until the retention job releases it
+    status: expired ? 'expired' : reservation.status.toLowerCase(),
+    payment_intent_id: reservation.paymentIntentId ?? null,
+    tx_signature: reservation.txSignature ?? null,
+    livemode: reservation.livemode,
+    expires_at: reservation.expiresAt,
+    consumed_at: reservation.consumedAt ?? null,
+    released_at: reservation.releasedAt ?? null,
+    created_at: reservation.createdAt,
+  };
+}
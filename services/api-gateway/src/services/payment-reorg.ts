@@ -0,0 +1,131 @@
+import crypto from 'crypto';
+import { prisma } from '@ninjapay/database';
+import { createLogger } from '@ninjapay/logger';
+import { getWebhookDeliveryService } from './webhook-delivery.js';
+
+const logger = createLogger('payment-reorg');
+
+/**
+ * Reverted payments
+ *
+ * arcium-service indexes PaymentRecords before they finalize. When a fork
+ * rolls one back it POSTs a `payment.reverted` event, signed like its other
+ * callbacks:
+ *
+ *   X-Ninjapay-Signature: t=<unix seconds>,v1=<hex HMAC-SHA256(secret, "<t>.<body>")>
+ *
+ * The payment intent behind the record's payment_id moves to REVERTED, a
+ * terminal state, and merchants subscribed to `payment.reverted` are told.
+ * Redelivered events find the intent already reverted and change nothing.
+ */
+
+// Accepted clock difference between arcium-service and the gateway
+export const SIGNATURE_TOLERANCE_SECONDS = 5 * 60;
+
+// Only a payment that reached the chain can be rolled back
+const REVERTIBLE_STATUSES = ['PROCESSING', 'CONFIRMED', 'FINALIZED'];
+
+export interface RevertedPayment {
+  record: string;
+  payment_id: string;
+  merchant: string;
+  amount: number;
+  fee: number;
+  day: string;
+  indexed_slot: number;
+  detected_at: number;
+}
+
+export type RevertOutcome =
+  | { status: 'reverted'; paymentIntentId: string }
+  | { status: 'already_reverted'; paymentIntentId: string }
+  | { status: 'not_revertible'; paymentIntentId: string; intentStatus: string }
+  | { status: 'unknown_payment' };
+
+/**
+ * Check an `X-Ninjapay-Signature` header against the raw body
+ */
+export function verifyReorgSignature(
+  secret: string,
+  header: string,
+  body: string,
+  nowSeconds = Math.floor(Date.now() / 1000)
+): boolean {
+  const parts = Object.fromEntries(
+    header.split(',').map((part) => {
+      const [key, ...value] = part.trim().split('=');
+      return [key, value.join('=')];
+    })
+  );
+  const timestamp = Number(parts.t);
+  if (!secret || !Number.isInteger(timestamp) || !parts.v1) {
+    return false;
+  }
+  if (Math.abs(nowSeconds - timestamp) > SIGNATURE_TOLERANCE_SECONDS) {
+    return false;
+  }
+
+  const expected = crypto.createHmac('sha256', secret).update(`${timestamp}.${body}`).digest('hex');
+  const given = Buffer.from(parts.v1, 'utf8');
+  return given.length === expected.length && crypto.timingSafeEqual(given, Buffer.from(expected, 'utf8'));
+}
+
+/**
+ * Move the payment intent behind a reverted record to REVERTED and notify
+ * the merchant's subscriptions
+ */
+export async function revertPayment(payment: RevertedPayment): Promise<RevertOutcome> {
+  const reservation = await prisma.paymentIdReservation.findUnique({
+    where: { paymentId: payment.payment_id },
+  });
+  if (!reservation?.paymentIntentId) {
+    logger.warn('Reverted payment has no payment intent', { paymentId: payment.payment_id, record: payment.record });
+    return { status: 'unknown_payment' };
+  }
+
+  const intent = await prisma.paymentIntent.findUnique({ where: { id: reservation.paymentIntentId } });
+  if (!intent) {
+    return { status: 'unknown_payment' };
+  }
+  if (intent.status === 'REVERTED') {
+    return { status: 'already_reverted', paymentIntentId: intent.id };
+  }
+  if (!REVERTIBLE_STATUSES.includes(intent.status)) {
+    return { status: 'not_revertible', paymentIntentId: intent.id, intentStatus: intent.status };
+  }
+
+  // Compare-and-swap on the status, so concurrent redeliveries notify once
+  const { count } = await prisma.paymentIntent.updateMany({
+    where: { id: intent.id, status: intent.status },
+    data: {
+      status: 'REVERTED',
+      metadata: {
+        ...(intent.metadata as object || {}),
+        revertedAt: payment.detected_at,
+        revertedRecord: payment.record,
+        revertedIndexedSlot: payment.indexed_slot,
+      },
+    },
+  });
+  if (count === 0) {
+    return { status: 'already_reverted', paymentIntentId: intent.id };
+  }
+
+  logger.warn('Payment intent reverted after a chain reorg', {
+    paymentIntentId: intent.id,
+    previousStatus: intent.status,
+    record: payment.record,
+  });
+
+  await getWebhookDeliveryService().broadcastEvent(intent.merchantId, intent.livemode, 'payment.reverted', {
+    payment_intent_id: intent.id,
+    status: 'reverted',
+    previous_status: intent.status.toLowerCase(),
+    payment_id: payment.payment_id,
+    payment_record: payment.record,
+    tx_signature: intent.txSignature,
+    timestamp: payment.detected_at,
+  });
+
+  return { status: 'reverted', paymentIntentId: intent.id };
+}
@@ -0,0 +1,239 @@
+import crypto from 'crypto';
+import { prisma } from '@ninjapay/database';
+import { createLogger } from '@ninjapay/logger';
+import { AppError } from '../middleware/error-handler.js';
+import { incrementCounter } from './metrics.js';
+
+/**
+ * Payroll budget limits
+ *
+ * A guardrail against a compromised company API key: a batch is refused
+ * when it is queued (execute or settle) if its total or employee count is
+ * over the company's limits, or if it would take the company's queued total
+ * for the UTC day over the daily limit. An admin can let one specific batch
+ * through with a time-limited, single-use override token.
+ *
+ * The daily total is read before the batch is queued, without a lock, so
+ * two batches queued at the same moment can each fit on their own. The
+ * limits are soft; they are not an accounting control.
+ */
+
+export const DEFAULT_OVERRIDE_TTL_SECONDS = 60 * 60;
+export const MAX_OVERRIDE_TTL_SECONDS = 24 * 60 * 60;
+
+// Execute and settle read the override token from this header
+export const OVERRIDE_HEADER = 'x-budget-override';
+
+const logger = createLogger('payroll-budget');
+
+export type BudgetLimit = 'max_batch_total' | 'max_daily_total' | 'max_batch_employees';
+
+export interface BudgetViolation {
+  limit: BudgetLimit;
+  limit_value: number;
+  actual: number;
+}
+
+interface CompanyLimits {
+  maxBatchTotal?: unknown;
+  maxDailyTotal?: unknown;
+  maxBatchEmployees?: number | null;
+}
+
+interface QueuedBatch {
+  id: string;
+  companyId: string;
+  employeeCount: number;
+  totalAmount: unknown;
+}
+
+// Prisma returns Decimal columns as Decimal objects
+function toNumber(value: unknown): number | null {
+  return value === null || value === undefined ? null : Number(value);
+}
+
+export function startOfUtcDay(now: Date): Date {
+  return new Date(Date.UTC(now.getUTCFullYear(), now.getUTCMonth(), now.getUTCDate()));
+}
+
+/**
+ * Limits a batch would break, given what the company has already queued today
+ */
+export function budgetViolations(
+  limits: CompanyLimits,
+  batch: { totalAmount: number; employeeCount: number },
+  queuedToday: number
+): BudgetViolation[] {
+  const violations: BudgetViolation[] = [];
+  const maxBatchTotal = toNumber(limits.maxBatchTotal);
+  const maxDailyTotal = toNumber(limits.maxDailyTotal);
+
+  if (maxBatchTotal !== null && batch.totalAmount > maxBatchTotal) {
+    violations.push({ limit: 'max_batch_total', limit_value: maxBatchTotal, actual: batch.totalAmount });
+  }
+  if (maxDailyTotal !== null && queuedToday + batch.totalAmount > maxDailyTotal) {
+    violations.push({ limit: 'max_daily_total', limit_value: maxDailyTotal, actual: queuedToday + batch.totalAmount });
+  }
+  if (limits.maxBatchEmployees != null && batch.employeeCount > limits.maxBatchEmployees) {
+    violations.push({
+      limit: 'max_batch_employees',
+      limit_value: limits.maxBatchEmployees,
+      actual: batch.employeeCount,
+    });
+  }
+  return violations;
+}
+
+/**
+ * Total of the company's batches queued since midnight UTC
+ */
+export async function queuedToday(companyId: string, now: Date = new Date()): Promise<number> {
+  const batches = await prisma.payrollBatch.findMany({
+    where: {
+      companyId,
+      executedDate: { gte: startOfUtcDay(now) },
+      status: { in: ['PROCESSING', 'COMPLETED'] },
+    },
+    select: { totalAmount: true },
+  });
+  return batches.reduce((sum: number, b: { totalAmount: unknown }) => sum + Number(b.totalAmount), 0);
+}
+
+export function hashOverrideToken(token: string): string {
+  return crypto.createHash('sha256').update(token).digest('hex');
+}
+
+function describeViolations(violations: BudgetViolation[]): string {
+  return violations.map((v) => `${v.limit} is ${v.limit_value}, batch needs ${v.actual}`).join('; ');
+}
+
+/**
+ * Issue a single-use token that lets `batchId` through its company's limits
+ */
+export async function issueBudgetOverride(request: {
+  companyId: string;
+  batchId: string;
+  reason: string;
+  ttlSeconds: number;
+  actor: string;
+}): Promise<{ override: any; token: string }> {
+  const batch = await prisma.payrollBatch.findFirst({
+    where: { id: request.batchId, companyId: request.companyId },
+  });
+  if (!batch) {
+    throw new AppError('Batch not found for this company', 404, 'BATCH_NOT_FOUND');
+  }
+  if (batch.status !== 'PENDING') {
+    throw new AppError(`Cannot override a batch in status: ${batch.status}`, 400, 'INVALID_STATUS');
+  }
+
+  const token = `bo_${crypto.randomBytes(24).toString('hex')}`;
+  const override = await prisma.budgetOverride.create({
+    data: {
+      tokenHash: hashOverrideToken(token),
+      companyId: request.companyId,
+      batchId: request.batchId,
+      reason: request.reason,
+      issuedBy: request.actor,
+      expiresAt: new Date(Date.now() + request.ttlSeconds * 1000),
+    },
+  });
+
+  await prisma.auditEvent.create({
+    data: {
+      action: 'budget.override_issued',
+      actor: request.actor,
+      details: {
+        override_id: override.id,
+        company_id: request.companyId,
+        batch_id: request.batchId,
+        reason: request.reason,
+        expires_at: override.expiresAt,
+      },
+    },
+  });
+  logger.info('Budget override issued', {
+    actor: request.actor,
+    overrideId: override.id,
+    companyId: request.companyId,
+    batchId: request.batchId,
+  });
+  return { override, token };
+}
+
+/**
+ * Refuse a batch over its company's limits with BUDGET_EXCEEDED, unless
+ * `overrideToken` is an unused, unexpired override for this batch. The
+ * token is only consumed when it is needed.
+ */
+export async function enforcePayrollBudget(
+  batch: QueuedBatch,
+  overrideToken: string | undefined,
+  now: Date = new Date()
+): Promise<void> {
+  const company = await prisma.company.findUnique({
+    where: { id: batch.companyId },
+    select: { maxBatchTotal: true, maxDailyTotal: true, maxBatchEmployees: true },
+  });
+  if (!company) {
+    throw new AppError('Company not found', 404, 'COMPANY_NOT_FOUND');
+  }
+
+  const totalAmount = Number(batch.totalAmount);
+  const spent = company.maxDailyTotal == null ? 0 : await queuedToday(batch.companyId, now);
+  const violations = budgetViolations(company, { totalAmount, employeeCount: batch.employeeCount }, spent);
+  if (violations.length === 0) {
+    return;
+  }
+
+  const actor = `company:${batch.companyId}`;
+  const details = { company_id: batch.companyId, batch_id: batch.id, violations };
+
+  if (overrideToken) {
+    const { count } = await prisma.budgetOverride.updateMany({
+      where: {
+        tokenHash: hashOverrideToken(overrideToken),
+        companyId: batch.companyId,
+        batchId: batch.id,
+        usedAt: null,
+        expiresAt: { gt: now },
+      },
+      data: { usedAt: now },
+    });
+    if (count === 1) {
+      incrementCounter('ninjapay_payroll_budget_overrides_total', 'Budget override tokens presented', {
+        outcome: 'used',
+      });
+      await prisma.auditEvent.create({ data: { action: 'budget.override_used', actor, details } });
+      logger.warn('Payroll batch queued over budget with an override', details);
+      return;
+    }
+    incrementCounter('ninjapay_payroll_budget_overrides_total', 'Budget override tokens presented', {
+      outcome: 'rejected',
+    });
+  }
+
+  for (const violation of violations) {
+    incrementCounter('ninjapay_payroll_budget_exceeded_total', 'Payroll batches refused by a budget limit', {
+      limit: violation.limit,
+    });
+  }
+  await prisma.auditEvent.create({
+    data: { action: 'budget.exceeded', actor, details: { ...details, override_rejected: Boolean(overrideToken) } },
+  });
+  logger.warn('Payroll batch over budget', { ...details, overrideRejected: Boolean(overrideToken) });
+
+  const reason = overrideToken
+    ? 'the override token is invalid, expired, already used, or for another batch'
+    : 'an admin can approve it with a budget override';
+  throw new AppError(`Payroll batch exceeds the company budget (${describeViolations(violations)}); ${reason}`, 422, 'BUDGET_EXCEEDED');
+}
+
+export function serializeBudget(company: CompanyLimits, spentToday: number) {
+  return {
+    max_batch_total: toNumber(company.maxBatchTotal),
+    max_daily_total: toNumber(company.maxDailyTotal),
+    max_batch_employees: company.maxBatchEmployees ?? null,
+    queued_today: spentToday,
+  };
+}
@@ -0,0 +1,82 @@
+import { createLogger } from '@ninjapay/logger';
+
+const logger = createLogger('price');
+
+const CACHE_TTL_MS = 60_000;
+const REQUEST_TIMEOUT_MS = 3_000;
+
+/**
+ * Source of SOL prices in a payment currency
+ */
+export interface PriceSource {
+  /** Units of `currency` per 1 SOL, or null if unavailable */
+  solPrice(currency: string): Promise<number | null>;
+}
+
+/**
+ * SOL price lookups for fee disclosure.
+ *
+ * `SOL_PRICE_<CURRENCY>` (e.g. SOL_PRICE_USDC=150) pins a price, which is
+ * useful on devnet; otherwise prices come from PRICE_API_URL (Jupiter's
+ * price API by default) and are cached for a minute. Lookups never throw:
+ * callers get null and disclose lamports only.
+ */
+export class PriceService implements PriceSource {
+  private cache = new Map<string, { price: number; fetchedAt: number }>();
+  private apiUrl: string;
+
+  constructor() {
+    this.apiUrl = process.env.PRICE_API_URL || 'https://price.jup.ag/v6/price';
+  }
+
+  async solPrice(currency: string): Promise<number | null> {
+    const symbol = currency.toUpperCase();
+    if (symbol === 'SOL') {
+      return 1;
+    }
+
+    const pinned = process.env[`SOL_PRICE_${symbol}`];
+    if (pinned) {
+      const price = Number(pinned);
+      return Number.isFinite(price) && price > 0 ? price : null;
+    }
+
+    const cached = this.cache.get(symbol);
+    if (cached && Date.now() - cached.fetchedAt < CACHE_TTL_MS) {
+      return cached.price;
+    }
+
+    const controller = new AbortController();
+    const timeoutId = setTimeout(() => controller.abort(), REQUEST_TIMEOUT_MS);
+    try {
+      const response = await fetch(`${this.apiUrl}?ids=SOL&vsToken=${symbol}`, {
+        signal: controller.signal,
+      });
+      if (!response.ok) {
+        throw new Error(`price API returned ${response.status}`);
+      }
+      const body = (await response.json()) as { data?: { SOL?: { price?: number } } };
+      const price = body.data?.SOL?.price;
+      if (typeof price !== 'number' || !(price > 0)) {
+        throw new Error('price API returned no SOL price');
+      }
+      this.cache.set(symbol, { price, fetchedAt: Date.now() });
+      return price;
+    } catch (error: any) {
+      logger.warn('SOL price unavailable', { currency: symbol, error: error.message });
+      return cached?.price ?? null;
+    } finally {
+      clearTimeout(timeoutId);
+    }
+  }
+}
+
+// Singleton instance
+let priceService: PriceService | null = null;
+
+export function getPriceService(): PriceService {
+  if (!priceService) {
+    priceService = new PriceService();
+  }
+  return priceService;
+}
@@ -0,0 +1,173 @@
+import { NextFunction, Request, Response } from 'express';
+import { prisma, prismaRead, PrismaClient } from '@ninjapay/database';
+import { createLogger } from '@ninjapay/logger';
+
+const logger = createLogger('read-routing');
+
+/**
+ * Read replica routing
+ *
+ * List, export and summary queries are sent to the DATABASE_READ_URL
+ * replica so they don't compete with the write path. A read goes to the
+ * primary instead when no replica is configured, when the replica recently
+ * failed to connect, or when the request needs to see its own writes:
+ * either the caller sent `X-Read-Consistency: strong`, or the same
+ * merchant or company wrote within the last READ_AFTER_WRITE_SECONDS.
+ */
+
+export const CONSISTENCY_HEADER = 'x-read-consistency';
+
+const DEFAULT_READ_AFTER_WRITE_SECONDS = 5;
+const DEFAULT_REPLICA_RETRY_SECONDS = 30;
+const MAX_TRACKED_WRITERS = 10_000;
+
+// Prisma error codes meaning the database could not be reached
+const CONNECTION_ERROR_CODES = new Set(['P1001', 'P1002', 'P1008', 'P1017']);
+
+export type Pool = 'primary' | 'replica';
+
+export interface ReadRoutingOptions {
+  readAfterWriteSeconds?: number;
+  replicaRetrySeconds?: number;
+}
+
+export interface ReadRoutingStats {
+  replica_configured: boolean;
+  replica_available: boolean;
+  queries: Record<Pool, number>;
+  fallbacks: number;
+  replica_lag_seconds: number | null;
+}
+
+function isConnectionError(error: any): boolean {
+  return (
+    error?.name === 'PrismaClientInitializationError' ||
+    CONNECTION_ERROR_CODES.has(error?.code) ||
+    CONNECTION_ERROR_CODES.has(error?.errorCode)
+  );
+}
+
+function writer(req: Request): string | undefined {
+  if (req.merchantId) return `merchant:${req.merchantId}`;
+  if (req.companyId) return `company:${req.companyId}`;
+  return undefined;
+}
+
+export class ReadRouter {
+  private readonly readAfterWriteMs: number;
+  private readonly replicaRetryMs: number;
+  private readonly lastWrites = new Map<string, number>();
+  private replicaDownUntil = 0;
+  private queries: Record<Pool, number> = { primary: 0, replica: 0 };
+  private fallbacks = 0;
+
+  constructor(
+    private readonly primary: PrismaClient,
+    private readonly replica: PrismaClient | null,
+    options: ReadRoutingOptions = {}
+  ) {
+    this.readAfterWriteMs = (options.readAfterWriteSeconds ?? DEFAULT_READ_AFTER_WRITE_SECONDS) * 1000;
+    this.replicaRetryMs = (options.replicaRetrySeconds ?? DEFAULT_REPLICA_RETRY_SECONDS) * 1000;
+  }
+
+  /**
+   * Note that the request's merchant or company just wrote
+   */
+  recordWrite(req: Request, now: number = Date.now()): void {
+    const key = writer(req);
+    if (!key) return;
+
+    if (this.lastWrites.size >= MAX_TRACKED_WRITERS) {
+      for (const [k, at] of this.lastWrites) {
+        if (now - at >= this.readAfterWriteMs) this.lastWrites.delete(k);
+      }
+    }
+    this.lastWrites.set(key, now);
+  }
+
+  /**
+   * Whether the request must read from the primary to see its own writes
+   */
+  needsPrimary(req: Request, now: number = Date.now()): boolean {
+    if (String(req.headers[CONSISTENCY_HEADER] ?? '').toLowerCase() === 'strong') {
+      return true;
+    }
+    const key = writer(req);
+    const lastWrite = key ? this.lastWrites.get(key) : undefined;
+    return lastWrite !== undefined && now - lastWrite < this.readAfterWriteMs;
+  }
+
+  /**
+   * Run a read-only query on the replica where allowed, falling back to
+   * the primary if the replica can't be reached
+   */
+  async read<T>(req: Request, query: (client: PrismaClient) => Promise<T>, now: number = Date.now()): Promise<T> {
+    if (!this.replica || now < this.replicaDownUntil || this.needsPrimary(req, now)) {
+      this.queries.primary++;
+      return query(this.primary);
+    }
+
+    try {
+      this.queries.replica++;
+      return await query(this.replica);
+    } catch (error: any) {
+      if (!isConnectionError(error)) throw error;
+
+      this.replicaDownUntil = now + this.replicaRetryMs;
+      this.fallbacks++;
+      logger.warn('Read replica unreachable, using primary', {
+        error: error.message,
+        retryInSeconds: this.replicaRetryMs / 1000,
+      });
+
+      this.queries.primary++;
+      return query(this.primary);
+    }
+  }
+
+  /**
+   * Seconds the replica is behind the primary, or null if the database
+   * doesn't expose it (not a streaming replica) or the replica is down
+   */
+  async replicaLagSeconds(): Promise<number | null> {
+    if (!this.replica) return null;
+    try {
+      const rows = await this.replica.$queryRaw<{ lag: number | null }[]>`
+        SELECT EXTRACT(EPOCH FROM (now() - pg_last_xact_replay_timestamp()))::float8 AS lag
+      `;
+      return rows[0]?.lag ?? null;
+    } catch {
+      return null;
+    }
+  }
+
+  async stats(now: number = Date.now()): Promise<ReadRoutingStats> {
+    return {
+      replica_configured: this.replica !== null,
+      replica_available: this.replica !== null && now >= this.replicaDownUntil,
+      queries: { ...this.queries },
+      fallbacks: this.fallbacks,
+      replica_lag_seconds: await this.replicaLagSeconds(),
+    };
+  }
+
+  /**
+   * Middleware recording successful writes, for read-after-write routing
+   */
+  trackWrites() {
+    return (req: Request, res: Response, next: NextFunction) => {
+      if (req.method !== 'GET' && req.method !== 'HEAD') {
+        res.on('finish', () => {
+          if (res.statusCode < 400) this.recordWrite(req);
+        });
+      }
+      next();
+    };
+  }
+}
+
+export const readRouter = new ReadRouter(prisma, prismaRead, {
+  readAfterWriteSeconds: process.env.READ_AFTER_WRITE_SECONDS
+    ? parseInt(process.env.READ_AFTER_WRITE_SECONDS)
+    : undefined,
+});
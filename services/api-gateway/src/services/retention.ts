@@ -0,0 +1,46 @@
+import { createLogger } from '@ninjapay/logger';
+import { releaseExpiredReservations } from './payment-id-reservations.js';
+import { deleteExpiredChallenges } from './merchant-onboarding.js';
+import { expirePreviousSecrets } from './webhook-secrets.js';
+import { failStaleReplays } from './webhook-replay.js';
+
+const logger = createLogger('retention');
+
+const DEFAULT_INTERVAL_SECONDS = 60;
+
+/**
+ * One pass of the retention job
+ */
+export async function runRetention(now: Date = new Date()): Promise<void> {
+  const released = await releaseExpiredReservations(now);
+  if (released > 0) {
+    logger.info('Released expired payment id reservations', { released });
+  }
+
+  const deleted = await deleteExpiredChallenges(now);
+  if (deleted > 0) {
+    logger.info('Deleted expired onboarding challenges', { deleted });
+  }
+
+  const expired = await expirePreviousSecrets(now);
+  if (expired > 0) {
+    logger.info('Expired rotated webhook secrets', { webhooks: expired });
+  }
+
+  const interrupted = await failStaleReplays(now);
+  if (interrupted > 0) {
+    logger.warn('Marked interrupted webhook replays failed', { replays: interrupted });
+  }
+}
+
+/**
+ * Run the retention job every RETENTION_INTERVAL_SECONDS
+ */
+export function startRetentionJob(): NodeJS.Timeout {
+  const seconds = parseInt(process.env.RETENTION_INTERVAL_SECONDS || String(DEFAULT_INTERVAL_SECONDS));
+  const timer = setInterval(() => {
+    runRetention().catch((error) => logger.error('Retention job failed', { error: error.message }));
+  }, seconds * 1000);
+  timer.unref();
+  return timer;
+}
@@ -18,6 +18,7 @@ import {
 } from '@solana/spl-token';
 import { prisma } from '@ninjapay/database';
 import { createLogger } from '@ninjapay/logger';
+import { decodeProgramError, describeTransactionError } from '../utils/program-errors.js';
 
 const logger = createLogger('settlement');
 
@@ -136,8 +137,8 @@ export class SettlementService {
       logger.error('SOL payment failed', { error, recipient });
       return {
         success: false,
-        error: error.message,
-        payments: [{ recipient, status: 'failed', error: error.message }],
+        error: describeTransactionError(error),
+        payments: [{ recipient, status: 'failed', error: describeTransactionError(error) }],
       };
     }
   }
@@ -189,8 +190,8 @@ export class SettlementService {
       logger.error('USDC payment failed', { error, recipient });
       return {
         success: false,
-        error: error.message,
-        payments: [{ recipient, status: 'failed', error: error.message }],
+        error: describeTransactionError(error),
+        payments: [{ recipient, status: 'failed', error: describeTransactionError(error) }],
       };
     }
   }
@@ -237,7 +238,7 @@ export class SettlementService {
           results.push({
             recipient: payment.recipient,
             status: 'failed',
-            error: error.message,
+            error: describeTransactionError(error),
           });
         }
       }
@@ -391,10 +392,17 @@ export class SettlementService {
         return signature;
       } catch (error: any) {
         lastError = error;
+        const programError = decodeProgramError(error.message) ?? decodeProgramError(error.logs);
         logger.warn(`Transaction attempt ${attempt + 1} failed`, {
           error: error.message,
+          programError: programError?.name,
         });
 
+        // The program rejected the transaction; resending won't change that
+        if (programError) {
+          break;
+        }
+
         if (attempt < this.config.maxRetries - 1) {
           await this.sleep(this.config.retryDelay * (attempt + 1));
         }
@@ -0,0 +1,520 @@
+import crypto from 'crypto';
+import bcrypt from 'bcrypt';
+import { z } from 'zod';
+import { prisma, PrismaClient } from '@ninjapay/database';
+import { AppError } from '../middleware/error-handler.js';
+
+/**
+ * Export and import of non-secret configuration, for cloning an environment
+ * (e.g. seeding staging from production).
+ *
+ * Only the fields listed in the *_SELECT objects below leave the database.
+ * Webhook secrets, API key hashes and all payment, payroll and delivery
+ * history are never exported; imported keys and webhooks get fresh secrets
+ * in the target environment.
+ */
+
+export const STATE_ARCHIVE_FORMAT = 'ninjapay-state';
+export const STATE_ARCHIVE_VERSION = 1;
+
+export const MIN_PASSPHRASE_LENGTH = 12;
+
+/** Listed in every archive so operators can see what was left behind */
+export const EXCLUDED_FROM_EXPORT = [
+  'merchants.webhookSecret',
+  'api_keys.keyHash',
+  'webhooks.secret',
+  'payment_intents',
+  'payment_links',
+  'webhook_deliveries',
+  'companies',
+  'employees',
+  'payroll_batches',
+  'payroll_payments',
+];
+
+// scrypt parameters are fixed per archive version so an archive can't make
+// the importer burn arbitrary CPU and memory
+const KDF = { name: 'scrypt' as const, N: 2 ** 15, r: 8, p: 1 };
+const CIPHER = 'chacha20-poly1305';
+
+const MERCHANT_SELECT = {
+  id: true,
+  walletAddress: true,
+  businessName: true,
+  email: true,
+  kycStatus: true,
+  webhookUrl: true,
+  settings: true,
+} as const;
+
+const API_KEY_SELECT = {
+  id: true,
+  merchantId: true,
+  keyPrefix: true,
+  livemode: true,
+  name: true,
+  permissions: true,
+  allowedMerchants: true,
+  active: true,
+  expiresAt: true,
+} as const;
+
+const WEBHOOK_SELECT = {
+  id: true,
+  merchantId: true,
+  url: true,
+  events: true,
+  enabled: true,
+  format: true,
+  description: true,
+  livemode: true,
+} as const;
+
+// ============ Snapshot ============
+
+const merchantStateSchema = z.object({
+  id: z.string().min(1),
+  wallet_address: z.string().min(1),
+  business_name: z.string(),
+  email: z.string(),
+  kyc_status: z.enum(['PENDING', 'APPROVED', 'REJECTED']),
+  webhook_url: z.string().nullable(),
+  settings: z.any(),
+});
+
+const apiKeyStateSchema = z.object({
+  id: z.string().min(1),
+  merchant_id: z.string().min(1),
+  key_prefix: z.string(),
+  livemode: z.boolean(),
+  name: z.string(),
+  permissions: z.array(z.string()),
+  // Absent from archives taken before keys could be restricted
+  allowed_merchants: z.array(z.string()).default([]),
+  active: z.boolean(),
+  expires_at: z.string().datetime().nullable(),
+});
+
+const webhookStateSchema = z.object({
+  id: z.string().min(1),
+  merchant_id: z.string().min(1),
+  url: z.string(),
+  events: z.array(z.string()),
+  enabled: z.boolean(),
+  format: z.string(),
+  description: z.string().nullable(),
+  livemode: z.boolean(),
+});
+
+const snapshotSchema = z.object({
+  version: z.literal(STATE_ARCHIVE_VERSION),
+  exported_at: z.string().datetime(),
+  merchants: z.array(merchantStateSchema),
+  api_keys: z.array(apiKeyStateSchema),
+  webhooks: z.array(webhookStateSchema),
+});
+
+export type MerchantState = z.infer<typeof merchantStateSchema>;
+export type ApiKeyState = z.infer<typeof apiKeyStateSchema>;
+export type WebhookState = z.infer<typeof webhookStateSchema>;
+export type StateSnapshot = z.infer<typeof snapshotSchema>;
+
+type Entities = Pick<StateSnapshot, 'merchants' | 'api_keys' | 'webhooks'>;
+
+function toMerchantState(m: any): MerchantState {
+  return {
+    id: m.id,
+    wallet_address: m.walletAddress,
+    business_name: m.businessName,
+    email: m.email,
+    kyc_status: m.kycStatus,
+    webhook_url: m.webhookUrl,
+    settings: m.settings,
+  };
+}
+
+function toApiKeyState(k: any): ApiKeyState {
+  return {
+    id: k.id,
+    merchant_id: k.merchantId,
+    key_prefix: k.keyPrefix,
+    livemode: k.livemode,
+    name: k.name,
+    permissions: k.permissions,
+    allowed_merchants: k.allowedMerchants ?? [],
+    active: k.active,
+    expires_at: k.expiresAt ? new Date(k.expiresAt).toISOString() : null,
+  };
+}
+
+function toWebhookState(w: any): WebhookState {
+  return {
+    id: w.id,
+    merchant_id: w.merchantId,
+    url: w.url,
+    events: w.events,
+    enabled: w.enabled,
+    format: w.format,
+    description: w.description,
+    livemode: w.livemode,
+  };
+}
+
+/**
+ * Read the exportable state. Secrets are excluded by the select, not
+ * stripped afterwards.
+ */
+export async function readState(db: PrismaClient = prisma): Promise<StateSnapshot> {
+  const [merchants, apiKeys, webhooks] = await Promise.all([
+    db.merchant.findMany({ select: MERCHANT_SELECT, orderBy: { createdAt: 'asc' } }),
+    db.apiKey.findMany({ select: API_KEY_SELECT, orderBy: { createdAt: 'asc' } }),
+    db.webhook.findMany({ select: WEBHOOK_SELECT, orderBy: { createdAt: 'asc' } }),
+  ]);
+
+  return {
+    version: STATE_ARCHIVE_VERSION,
+    exported_at: new Date().toISOString(),
+    merchants: merchants.map(toMerchantState),
+    api_keys: apiKeys.map(toApiKeyState),
+    webhooks: webhooks.map(toWebhookState),
+  };
+}
+
+// ============ Archive ============
+
+const archiveSchema = z.object({
+  format: z.literal(STATE_ARCHIVE_FORMAT),
+  version: z.number().int(),
+  excluded: z.array(z.string()),
+  cipher: z.literal(CIPHER),
+  kdf: z.object({
+    name: z.literal(KDF.name),
+    salt: z.string(),
+    N: z.literal(KDF.N),
+    r: z.literal(KDF.r),
+    p: z.literal(KDF.p),
+  }),
+  nonce: z.string(),
+  ciphertext: z.string(),
+  tag: z.string(),
+});
+
+export type StateArchive = z.infer<typeof archiveSchema>;
+
+function deriveKey(passphrase: string, salt: Buffer): Buffer {
+  return crypto.scryptSync(passphrase, salt, 32, {
+    N: KDF.N,
+    r: KDF.r,
+    p: KDF.p,
+    maxmem: 64 * 1024 * 1024,
+  });
+}
+
+// Everything outside the ciphertext is authenticated, so the version and
+// KDF parameters can't be swapped without failing decryption
+function associatedData(archive: Omit<StateArchive, 'nonce' | 'ciphertext' | 'tag'>): Buffer {
+  return Buffer.from(
+    JSON.stringify([archive.format, archive.version, archive.excluded, archive.cipher, archive.kdf])
+  );
+}
+
+/**
+ * Encrypt a snapshot with ChaCha20-Poly1305 under a scrypt-derived key
+ */
+export function sealState(snapshot: StateSnapshot, passphrase: string): StateArchive {
+  const salt = crypto.randomBytes(16);
+  const nonce = crypto.randomBytes(12);
+  const header = {
+    format: STATE_ARCHIVE_FORMAT as typeof STATE_ARCHIVE_FORMAT,
+    version: STATE_ARCHIVE_VERSION,
+    excluded: EXCLUDED_FROM_EXPORT,
+    cipher: CIPHER as typeof CIPHER,
+    kdf: { ...KDF, salt: salt.toString('base64') },
+  };
+
+  const cipher = crypto.createCipheriv(CIPHER, deriveKey(passphrase, salt), nonce, { authTagLength: 16 });
+  cipher.setAAD(associatedData(header));
+  const ciphertext = Buffer.concat([cipher.update(JSON.stringify(snapshot), 'utf8'), cipher.final()]);
+
+  return {
+    ...header,
+    nonce: nonce.toString('base64'),
+    ciphertext: ciphertext.toString('base64'),
+    tag: cipher.getAuthTag().toString('base64'),
+  };
+}
+
+/**
+ * Check the archive version, decrypt it and validate the snapshot inside
+ */
+export function openState(input: unknown, passphrase: string): StateSnapshot {
+  const version = (input as any)?.version;
+  if ((input as any)?.format !== STATE_ARCHIVE_FORMAT) {
+    throw new AppError('Not a ninjapay state archive', 400, 'INVALID_STATE_ARCHIVE');
+  }
+  if (version !== STATE_ARCHIVE_VERSION) {
+    throw new AppError(
+      `State archive version ${version} is not supported (expected ${STATE_ARCHIVE_VERSION})`,
+      422,
+      'UNSUPPORTED_STATE_VERSION'
+    );
+  }
+  const parsed = archiveSchema.safeParse(input);
+  if (!parsed.success) {
+    throw new AppError('Malformed state archive', 400, 'INVALID_STATE_ARCHIVE');
+  }
+  const archive = parsed.data;
+
+  let plaintext: string;
+  try {
+    const key = deriveKey(passphrase, Buffer.from(archive.kdf.salt, 'base64'));
+    const ciphertext = Buffer.from(archive.ciphertext, 'base64');
+    const decipher = crypto.createDecipheriv(CIPHER, key, Buffer.from(archive.nonce, 'base64'), {
+      authTagLength: 16,
+    });
+    decipher.setAAD(associatedData(archive));
+    decipher.setAuthTag(Buffer.from(archive.tag, 'base64'));
+    plaintext = Buffer.concat([decipher.update(ciphertext), decipher.final()]).toString('utf8');
+  } catch {
+    throw new AppError(
+      'State archive could not be decrypted: wrong passphrase or modified archive',
+      400,
+      'INVALID_STATE_ARCHIVE'
+    );
+  }
+
+  const snapshot = snapshotSchema.safeParse(JSON.parse(plaintext));
+  if (!snapshot.success) {
+    throw new AppError('State archive contents are invalid', 400, 'INVALID_STATE_ARCHIVE');
+  }
+  return snapshot.data;
+}
+
+// ============ Import ============
+
+export type ConflictResolution = 'skip' | 'overwrite';
+
+/** What an import does to one entity type, by record id */
+export type EntityPlan = {
+  create: string[];
+  overwrite: string[];
+  skip: string[];
+  unchanged: string[];
+};
+
+export type ImportPlan = {
+  merchants: EntityPlan;
+  api_keys: EntityPlan;
+  webhooks: EntityPlan;
+  /** Problems that block the import; it is all-or-nothing */
+  errors: string[];
+};
+
+function sameRecord(a: object, b: object): boolean {
+  return JSON.stringify(a) === JSON.stringify(b);
+}
+
+function planEntities<T extends { id: string }>(
+  incoming: T[],
+  existing: T[],
+  onConflict: ConflictResolution
+): EntityPlan {
+  const byId = new Map(existing.map((record) => [record.id, record]));
+  const plan: EntityPlan = { create: [], overwrite: [], skip: [], unchanged: [] };
+
+  for (const record of incoming) {
+    const current = byId.get(record.id);
+    if (!current) {
+      plan.create.push(record.id);
+    } else if (sameRecord(current, record)) {
+      plan.unchanged.push(record.id);
+    } else if (onConflict === 'overwrite') {
+      plan.overwrite.push(record.id);
+    } else {
+      plan.skip.push(record.id);
+    }
+  }
+  return plan;
+}
+
+/**
+ * Diff a snapshot against the records it would touch. `existing` must hold
+ * every current record sharing an id, wallet address or email with the
+ * snapshot.
+ */
+export function planImport(
+  snapshot: Entities,
+  existing: Entities,
+  onConflict: ConflictResolution
+): ImportPlan {
+  const merchants = planEntities(snapshot.merchants, existing.merchants, onConflict);
+  const errors: string[] = [];
+
+  // A different merchant already owning the wallet or email would fail the
+  // unique constraints part way through
+  const written = new Set([...merchants.create, ...merchants.overwrite]);
+  for (const merchant of snapshot.merchants.filter((m) => written.has(m.id))) {
+    const taken = existing.merchants.find(
+      (m) => m.id !== merchant.id && (m.wallet_address === merchant.wallet_address || m.email === merchant.email)
+    );
+    if (taken) {
+      errors.push(`merchant ${merchant.id}: wallet address or email already belongs to merchant ${taken.id}`);
+    }
+  }
+
+  const merchantIds = new Set([...snapshot.merchants, ...existing.merchants].map((m) => m.id));
+  for (const child of [...snapshot.api_keys, ...snapshot.webhooks]) {
+    if (!merchantIds.has(child.merchant_id)) {
+      errors.push(`${child.id}: merchant ${child.merchant_id} is neither in the archive nor in this environment`);
+    }
+  }
+
+  return {
+    merchants,
+    api_keys: planEntities(snapshot.api_keys, existing.api_keys, onConflict),
+    webhooks: planEntities(snapshot.webhooks, existing.webhooks, onConflict),
+    errors,
+  };
+}
+
+async function readExisting(db: any, snapshot: Entities): Promise<Entities> {
+  const ids = (records: { id: string }[]) => records.map((r) => r.id);
+  const [merchants, apiKeys, webhooks] = await Promise.all([
+    db.merchant.findMany({
+      select: MERCHANT_SELECT,
+      where: {
+        OR: [
+          { id: { in: ids(snapshot.merchants) } },
+          { walletAddress: { in: snapshot.merchants.map((m) => m.wallet_address) } },
+          { email: { in: snapshot.merchants.map((m) => m.email) } },
+          { id: { in: [...snapshot.api_keys, ...snapshot.webhooks].map((c) => c.merchant_id) } },
+        ],
+      },
+    }),
+    db.apiKey.findMany({ select: API_KEY_SELECT, where: { id: { in: ids(snapshot.api_keys) } } }),
+    db.webhook.findMany({ select: WEBHOOK_SELECT, where: { id: { in: ids(snapshot.webhooks) } } }),
+  ]);
+
+  return {
+    merchants: merchants.map(toMerchantState),
+    api_keys: apiKeys.map(toApiKeyState),
+    webhooks: webhooks.map(toWebhookState),
+  };
+}
+
+/**
+ * Plan an import without writing anything
+ */
+export async function previewImport(snapshot: StateSnapshot, onConflict: ConflictResolution): Promise<ImportPlan> {
+  return planImport(snapshot, await readExisting(prisma, snapshot), onConflict);
+}
+
+/**
+ * Apply an import in one transaction, re-planning inside it so the applied
+ * diff is the one checked. The audit event is written in the same
+ * transaction.
+ */
+export async function applyImport(
+  snapshot: StateSnapshot,
+  onConflict: ConflictResolution,
+  audit: { actor: string; archiveDigest: string }
+): Promise<ImportPlan> {
+  // Imported keys can't authenticate: the raw keys never left the source.
+  // Hash outside the transaction to keep it short.
+  const keyHashes = new Map<string, string>();
+  for (const key of snapshot.api_keys) {
+    keyHashes.set(key.id, await bcrypt.hash(crypto.randomBytes(32).toString('hex'), 4));
+  }
+
+  return prisma.$transaction(
+    async (tx: any) => {
+      const plan = planImport(snapshot, await readExisting(tx, snapshot), onConflict);
+      if (plan.errors.length > 0) {
+        throw new AppError(`State import conflicts: ${plan.errors.join('; ')}`, 409, 'STATE_IMPORT_CONFLICT');
+      }
+
+      const creates = (p: EntityPlan) => new Set(p.create);
+      const overwrites = (p: EntityPlan) => new Set(p.overwrite);
+
+      const [newMerchants, updatedMerchants] = [creates(plan.merchants), overwrites(plan.merchants)];
+      for (const m of snapshot.merchants) {
+        const data = {
+          walletAddress: m.wallet_address,
+          businessName: m.business_name,
+          email: m.email,
+          kycStatus: m.kyc_status,
+          webhookUrl: m.webhook_url,
+          settings: m.settings,
+        };
+        if (newMerchants.has(m.id)) await tx.merchant.create({ data: { id: m.id, ...data } });
+        else if (updatedMerchants.has(m.id)) await tx.merchant.update({ where: { id: m.id }, data });
+      }
+
+      const [newKeys, updatedKeys] = [creates(plan.api_keys), overwrites(plan.api_keys)];
+      for (const k of snapshot.api_keys) {
+        const data = {
+          merchantId: k.merchant_id,
+          keyPrefix: k.key_prefix,
+          livemode: k.livemode,
+          name: k.name,
+          permissions: k.permissions,
+          allowedMerchants: k.allowed_merchants,
+          active: k.active,
+          expiresAt: k.expires_at ? new Date(k.expires_at) : null,
+        };
+        if (newKeys.has(k.id)) {
+          await tx.apiKey.create({ data: { id: k.id, keyHash: keyHashes.get(k.id)!, ...data } });
+        } else if (updatedKeys.has(k.id)) {
+          await tx.apiKey.update({ where: { id: k.id }, data });
+        }
+      }
+
+      const [newWebhooks, updatedWebhooks] = [creates(plan.webhooks), overwrites(plan.webhooks)];
+      for (const w of snapshot.webhooks) {
+        const data = {
+          merchantId: w.merchant_id,
+          url: w.url,
+          events: w.events,
+          enabled: w.enabled,
+          format: w.format,
+          description: w.description,
+          livemode: w.livemode,
+        };
+        if (newWebhooks.has(w.id)) {
+          const secret = `whsec_${crypto.randomBytes(24).toString('hex')}`;
+          await tx.webhook.create({ data: { id: w.id, secret, ...data } });
+        } else if (updatedWebhooks.has(w.id)) {
+          await tx.webhook.update({ where: { id: w.id }, data });
+        }
+      }
+
+      await tx.auditEvent.create({
+        data: {
+          action: 'state.import',
+          actor: audit.actor,
+          details: {
+            archive_sha256: audit.archiveDigest,
+            exported_at: snapshot.exported_at,
+            on_conflict: onConflict,
+            plan: summarize(plan),
+          },
+        },
+      });
+
+      return plan;
+    },
+    { timeout: 60_000 }
+  );
+}
+
+/** Counts per entity, for audit records and logs */
+export function summarize(plan: ImportPlan) {
+  const counts = (p: EntityPlan) => ({
+    create: p.create.length,
+    overwrite: p.overwrite.length,
+    skip: p.skip.length,
+    unchanged: p.unchanged.length,
+  });
+  return { merchants: counts(plan.merchants), api_keys: counts(plan.api_keys), webhooks: counts(plan.webhooks) };
+}
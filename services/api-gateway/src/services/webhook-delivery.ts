@@ -1,14 +1,14 @@
 import crypto from 'crypto';
 import { prisma } from '@ninjapay/database';
 import { createLogger } from '@ninjapay/logger';
+import { transformWebhookBody, WebhookPayload } from './webhook-transform.js';
+import { signWebhookWithSecrets } from './webhook-signature.js';
+import { signingSecrets } from './webhook-secrets.js';
 
 const logger = createLogger('webhook-delivery');
 
-interface WebhookPayload {
-  id: string;
-  type: string;
-  created: number;
-  data: Record<string, any>;
+function sha256(value: string): string {
+  return crypto.createHash('sha256').update(value).digest('hex');
 }
 
 interface DeliveryConfig {
@@ -61,35 +61,77 @@ export class WebhookDeliveryService {
       id: `evt_${crypto.randomBytes(16).toString('hex')}`,
       type: eventType,
       created: Date.now(),
+      livemode: webhook.livemode,
       data: payload,
     };
 
+    await this.enqueue(webhook, webhookPayload);
+  }
+
+  /**
+   * Queue a past event again, keeping its id and creation time so the
+   * merchant's dedup still recognises it. Resolves false if it couldn't be
+   * rendered.
+   */
+  async queueReplay(
+    webhook: { id: string; url: string; format: string; livemode: boolean },
+    event: WebhookPayload,
+    replayId: string
+  ): Promise<boolean> {
+    return this.enqueue(webhook, { ...event, replayed: true }, replayId);
+  }
+
+  /**
+   * Record a delivery of `event` and start delivering it
+   */
+  private async enqueue(
+    webhook: { id: string; url: string; format: string; livemode: boolean },
+    event: WebhookPayload,
+    replayId?: string
+  ): Promise<boolean> {
+    // Render the subscription's format; the signature covers this body
+    let body: string;
+    try {
+      body = transformWebhookBody(webhook.format, event);
+    } catch (error: any) {
+      logger.error('Webhook transformation failed', { webhookId: webhook.id, error: error.message });
+      return false;
+    }
+
     // Create delivery record
     const delivery = await prisma.webhookDelivery.create({
       data: {
-        webhookId,
-        eventType,
-        payload: webhookPayload,
+        webhookId: webhook.id,
+        eventId: event.id,
+        replayId,
+        eventType: event.type,
+        livemode: webhook.livemode,
+        payload: event,
+        payloadHash: sha256(JSON.stringify(event)),
+        transformedPayloadHash: sha256(body),
         attempts: 0,
         status: 'PENDING',
       },
     });
 
     // Start delivery process
-    this.deliverWebhook(delivery.id, webhook.url, webhook.secret, webhookPayload);
+    this.deliverWebhook(delivery.id, webhook.url, webhook.id, event.id, body);
+    return true;
   }
 
   /**
-   * Queue webhooks for all matching subscribers
+   * Queue webhooks for all matching subscribers in the record's mode
    */
   async broadcastEvent(
     merchantId: string,
+    livemode: boolean,
     eventType: string,
     payload: Record<string, any>
   ): Promise<void> {
     const webhooks = await prisma.webhook.findMany({
       where: {
         merchantId,
+        livemode,
         enabled: true,
         events: { has: eventType },
       },
@@ -97,6 +139,7 @@ export class WebhookDeliveryService {
 
     logger.debug('Broadcasting event', {
       merchantId,
+      livemode,
       eventType,
       webhookCount: webhooks.length,
     });
@@ -107,13 +150,15 @@ export class WebhookDeliveryService {
   }
 
   /**
-   * Deliver a webhook with retry logic
+   * Deliver a webhook with retry logic. Each attempt is signed with the
+   * webhook's secrets at that time, so retries follow a rotation.
    */
   private async deliverWebhook(
     deliveryId: string,
     url: string,
-    secret: string,
-    payload: WebhookPayload,
+    webhookId: string,
+    eventId: string,
+    payloadString: string,
     attempt: number = 1
   ): Promise<void> {
     // Prevent duplicate processing
@@ -123,15 +168,14 @@ export class WebhookDeliveryService {
     this.processingQueue.add(deliveryId);
 
     try {
-      const payloadString = JSON.stringify(payload);
-      const timestamp = Date.now().toString();
+      const webhook = await prisma.webhook.findUnique({ where: { id: webhookId } });
+      if (!webhook) {
+        logger.warn('Webhook deleted; abandoning delivery', { deliveryId, webhookId });
+        return;
+      }
 
-      // Generate signature: HMAC-SHA256(timestamp.payload)
-      const signaturePayload = `${timestamp}.${payloadString}`;
-      const signature = crypto
-        .createHmac('sha256', secret)
-        .update(signaturePayload)
-        .digest('hex');
+      const timestamp = Date.now().toString();
+      const signature = signWebhookWithSecrets(signingSecrets(webhook), timestamp, payloadString);
 
       logger.debug('Attempting webhook delivery', {
         deliveryId,
@@ -147,9 +191,9 @@ export class WebhookDeliveryService {
           method: 'POST',
           headers: {
             'Content-Type': 'application/json',
-            'X-Webhook-ID': payload.id,
+            'X-Webhook-ID': eventId,
             'X-Webhook-Timestamp': timestamp,
-            'X-Webhook-Signature': `sha256=${signature}`,
+            'X-Webhook-Signature': signature,
             'User-Agent': 'NinjaPay-Webhook/2.0',
           },
           body: payloadString,
@@ -183,7 +227,7 @@ export class WebhookDeliveryService {
           });
 
           // Schedule retry if not max attempts
-          await this.scheduleRetry(deliveryId, url, secret, payload, attempt);
+          await this.scheduleRetry(deliveryId, url, webhookId, eventId, payloadString, attempt);
         }
       } catch (fetchError: any) {
         clearTimeout(timeoutId);
@@ -207,7 +251,7 @@ export class WebhookDeliveryService {
         });
 
         // Schedule retry
-        await this.scheduleRetry(deliveryId, url, secret, payload, attempt);
+        await this.scheduleRetry(deliveryId, url, webhookId, eventId, payloadString, attempt);
       }
     } finally {
       this.processingQueue.delete(deliveryId);
@@ -220,8 +264,9 @@ export class WebhookDeliveryService {
   private async scheduleRetry(
     deliveryId: string,
     url: string,
-    secret: string,
-    payload: WebhookPayload,
+    webhookId: string,
+    eventId: string,
+    payloadString: string,
     currentAttempt: number
   ): Promise<void> {
     if (currentAttempt >= this.config.maxRetries) {
@@ -242,7 +287,7 @@ export class WebhookDeliveryService {
     });
 
     setTimeout(() => {
-      this.deliverWebhook(deliveryId, url, secret, payload, nextAttempt);
+      this.deliverWebhook(deliveryId, url, webhookId, eventId, payloadString, nextAttempt);
     }, delay);
   }
 
@@ -259,18 +304,29 @@ export class WebhookDeliveryService {
       return false;
     }
 
+    // Re-render with the subscription's current format
+    const payload = delivery.payload as unknown as WebhookPayload;
+    let body: string;
+    try {
+      body = transformWebhookBody(delivery.webhook.format, payload);
+    } catch (error: any) {
+      logger.error('Webhook transformation failed', { deliveryId, error: error.message });
+      return false;
+    }
+
     // Reset status
     await prisma.webhookDelivery.update({
       where: { id: deliveryId },
-      data: { status: 'PENDING' },
+      data: { status: 'PENDING', transformedPayloadHash: sha256(body) },
     });
 
     // Trigger delivery
     this.deliverWebhook(
       deliveryId,
       delivery.webhook.url,
-      delivery.webhook.secret,
-      delivery.payload as WebhookPayload,
+      delivery.webhook.id,
+      payload.id,
+      body,
       1
     );
 
@@ -319,18 +375,19 @@ export class WebhookDeliveryService {
 // Node.js webhook signature verification
 const crypto = require('crypto');
 
-function verifyWebhookSignature(payload, timestamp, signature, secret) {
+function verifyWebhookSignature(payload, timestamp, header, secret) {
   const signaturePayload = timestamp + '.' + payload;
-  const expectedSignature = 'sha256=' + crypto
+  const expected = Buffer.from('sha256=' + crypto
     .createHmac('sha256', secret)
     .update(signaturePayload)
-    .digest('hex');
-
-  // Constant-time comparison to prevent timing attacks
-  return crypto.timingSafeEqual(
-    Buffer.from(signature),
-    Buffer.from(expectedSignature)
-  );
+    .digest('hex'));
+
+  // During a secret rotation the header carries one signature per secret
+  return header.split(',').some((signature) => {
+    const received = Buffer.from(signature.trim());
+    // Constant-time comparison to prevent timing attacks
+    return received.length === expected.length && crypto.timingSafeEqual(received, expected);
+  });
 }
 
 // Express middleware example
@@ -0,0 +1,236 @@
+import { prisma } from '@ninjapay/database';
+import { createLogger } from '@ninjapay/logger';
+import { AppError } from '../middleware/error-handler.js';
+import { getWebhookDeliveryService, WebhookDeliveryService } from './webhook-delivery.js';
+import { WebhookPayload } from './webhook-transform.js';
+
+/**
+ * Webhook event replay
+ *
+ * Re-sends a webhook's past events, picked by time range or event id, from
+ * the payloads kept on its delivery records. Each event goes back through
+ * the normal delivery pipeline with its original id and `replayed: true`,
+ * so a merchant that dedups on the event id skips what it already has.
+ * Deliveries queued by a previous replay are never replayed again.
+ *
+ * Replays run in the background, one per webhook at a time. Every replay
+ * in the process shares one rate cap (WEBHOOK_REPLAY_RATE_PER_SECOND), so
+ * a large replay can't crowd out live deliveries. Progress is stored after
+ * every page. A replay whose runner died (e.g. in a restart) is marked
+ * failed by the retention job once it has made no progress for
+ * STALE_REPLAY_MS.
+ */
+
+export const DEFAULT_REPLAY_RATE_PER_SECOND = 5;
+export const MAX_REPLAY_EVENTS = 10_000;
+export const MAX_REPLAY_EVENT_IDS = 1_000;
+export const STALE_REPLAY_MS = 5 * 60 * 1000;
+
+const PAGE_SIZE = 50;
+
+const logger = createLogger('webhook-replay');
+
+export interface ReplayRequest {
+  from?: Date;
+  to?: Date;
+  eventIds?: string[];
+}
+
+interface ReplayFilter {
+  webhookId: string;
+  fromTime: Date | null;
+  toTime: Date | null;
+  eventIds: string[];
+}
+
+/**
+ * Spaces out enqueues across every replay in the process
+ */
+export class ReplayThrottle {
+  private nextAt = 0;
+
+  constructor(private ratePerSecond: number) {}
+
+  async take(): Promise<void> {
+    const now = Date.now();
+    const at = Math.max(now, this.nextAt);
+    this.nextAt = at + 1000 / this.ratePerSecond;
+    if (at > now) {
+      await new Promise((resolve) => setTimeout(resolve, at - now));
+    }
+  }
+}
+
+let throttle: ReplayThrottle | null = null;
+
+function getReplayThrottle(): ReplayThrottle {
+  if (!throttle) {
+    const rate = parseInt(process.env.WEBHOOK_REPLAY_RATE_PER_SECOND || String(DEFAULT_REPLAY_RATE_PER_SECOND));
+    throttle = new ReplayThrottle(rate > 0 ? rate : DEFAULT_REPLAY_RATE_PER_SECOND);
+  }
+  return throttle;
+}
+
+/**
+ * Original deliveries of this webhook that a replay covers
+ */
+export function replayWhere(filter: ReplayFilter) {
+  const where: any = { webhookId: filter.webhookId, replayId: null };
+  if (filter.eventIds.length > 0) {
+    where.eventId = { in: filter.eventIds };
+  } else {
+    where.createdAt = { gte: filter.fromTime, lte: filter.toTime };
+  }
+  return where;
+}
+
+/**
+ * Start replaying a webhook's past events. Resolves once the replay is
+ * recorded; the events are sent in the background.
+ */
+export async function startWebhookReplay(
+  webhook: { id: string; enabled: boolean },
+  request: ReplayRequest
+): Promise<any> {
+  if (!webhook.enabled) {
+    throw new AppError('Enable the webhook before replaying events to it', 400, 'WEBHOOK_DISABLED');
+  }
+
+  const active = await prisma.webhookReplay.findFirst({
+    where: { webhookId: webhook.id, status: 'RUNNING' },
+  });
+  if (active) {
+    throw new AppError(`Replay ${active.id} is still running for this webhook`, 409, 'REPLAY_IN_PROGRESS');
+  }
+
+  const filter: ReplayFilter = {
+    webhookId: webhook.id,
+    fromTime: request.from ?? null,
+    toTime: request.to ?? null,
+    eventIds: request.eventIds ?? [],
+  };
+  const total = await prisma.webhookDelivery.count({ where: replayWhere(filter) });
+  if (total > MAX_REPLAY_EVENTS) {
+    throw new AppError(
+      `${total} events match; replay at most ${MAX_REPLAY_EVENTS} at a time by narrowing the range`,
+      422,
+      'REPLAY_TOO_LARGE'
+    );
+  }
+
+  const replay = await prisma.webhookReplay.create({
+    data: { ...filter, total, status: total === 0 ? 'COMPLETED' : 'RUNNING', completedAt: total === 0 ? new Date() : null },
+  });
+  logger.info('Webhook replay started', { replayId: replay.id, webhookId: webhook.id, total });
+
+  if (total > 0) {
+    runReplay(replay.id).catch((error) => failReplay(replay.id, error.message));
+  }
+  return replay;
+}
+
+async function failReplay(replayId: string, error: string): Promise<void> {
+  logger.error('Webhook replay failed', { replayId, error });
+  await prisma.webhookReplay
+    .updateMany({ where: { id: replayId, status: 'RUNNING' }, data: { status: 'FAILED', error, completedAt: new Date() } })
+    .catch(() => undefined);
+}
+
+/**
+ * Enqueue a replay's events page by page, oldest first, until none are
+ * left or the replay is no longer running
+ */
+export async function runReplay(
+  replayId: string,
+  delivery: WebhookDeliveryService = getWebhookDeliveryService(),
+  rate: ReplayThrottle = getReplayThrottle()
+): Promise<void> {
+  for (;;) {
+    const replay = await prisma.webhookReplay.findUnique({
+      where: { id: replayId },
+      include: { webhook: true },
+    });
+    if (!replay || replay.status !== 'RUNNING') {
+      return;
+    }
+    if (!replay.webhook.enabled) {
+      await failReplay(replayId, 'webhook was disabled');
+      return;
+    }
+
+    const where = replayWhere(replay);
+    if (replay.cursorCreatedAt) {
+      where.OR = [
+        { createdAt: { gt: replay.cursorCreatedAt } },
+        { createdAt: replay.cursorCreatedAt, id: { gt: replay.cursorId } },
+      ];
+    }
+    const page = await prisma.webhookDelivery.findMany({
+      where,
+      orderBy: [{ createdAt: 'asc' }, { id: 'asc' }],
+      take: PAGE_SIZE,
+    });
+
+    if (page.length === 0) {
+      await prisma.webhookReplay.updateMany({
+        where: { id: replayId, status: 'RUNNING' },
+        data: { status: 'COMPLETED', completedAt: new Date() },
+      });
+      logger.info('Webhook replay completed', { replayId, enqueued: replay.enqueued });
+      return;
+    }
+
+    let enqueued = 0;
+    for (const original of page) {
+      await rate.take();
+      const { replayed: _previous, ...event } = original.payload as unknown as WebhookPayload;
+      if (await delivery.queueReplay(replay.webhook, event, replayId)) {
+        enqueued++;
+      }
+    }
+
+    const last = page[page.length - 1];
+    await prisma.webhookReplay.update({
+      where: { id: replayId },
+      data: { enqueued: { increment: enqueued }, cursorCreatedAt: last.createdAt, cursorId: last.id },
+    });
+  }
+}
+
+/**
+ * Mark replays failed whose runner stopped making progress. Run by the
+ * retention job.
+ */
+export async function failStaleReplays(now: Date = new Date()): Promise<number> {
+  const { count } = await prisma.webhookReplay.updateMany({
+    where: { status: 'RUNNING', updatedAt: { lt: new Date(now.getTime() - STALE_REPLAY_MS) } },
+    data: { status: 'FAILED', error: 'replay was interrupted', completedAt: now },
+  });
+  return count;
+}
+
+/**
+ * Replay progress, with the outcome of the deliveries it queued
+ */
+export async function serializeReplay(replay: any) {
+  const [delivered, failed, pending] = await Promise.all(
+    ['DELIVERED', 'FAILED', 'PENDING'].map((status) =>
+      prisma.webhookDelivery.count({ where: { replayId: replay.id, status } })
+    )
+  );
+
+  return {
+    id: replay.id,
+    webhook_id: replay.webhookId,
+    status: replay.status.toLowerCase(),
+    from: replay.fromTime ?? null,
+    to: replay.toTime ?? null,
+    event_ids: replay.eventIds.length > 0 ? replay.eventIds : null,
+    total: replay.total,
+    enqueued: replay.enqueued,
+    deliveries: { delivered, failed, pending },
+    error: replay.error ?? null,
+    created_at: replay.createdAt,
+    completed_at: replay.completedAt ?? null,
+  };
+}
@@ -0,0 +1,98 @@
+import crypto from 'crypto';
+import { prisma } from '@ninjapay/database';
+import { AppError } from '../middleware/error-handler.js';
+
+/**
+ * Webhook secret rotation
+ *
+ * Rotating a secret keeps the old one as `previousSecret` for an overlap
+ * window. Until it expires, deliveries are signed with both secrets, so
+ * the merchant can switch their endpoint to the new secret at any point
+ * in the window without rejecting deliveries.
+ */
+
+export const DEFAULT_OVERLAP_SECONDS = 24 * 60 * 60;
+export const MAX_OVERLAP_SECONDS = 7 * 24 * 60 * 60;
+
+interface WebhookSecrets {
+  secret: string;
+  previousSecret?: string | null;
+  previousSecretExpiresAt?: Date | null;
+}
+
+export function generateWebhookSecret(): string {
+  return `whsec_${crypto.randomBytes(24).toString('hex')}`;
+}
+
+export function defaultOverlapSeconds(): number {
+  return parseInt(process.env.WEBHOOK_SECRET_OVERLAP_SECONDS || String(DEFAULT_OVERLAP_SECONDS));
+}
+
+function previousSecretActive(webhook: WebhookSecrets, now: Date): boolean {
+  return Boolean(webhook.previousSecret && webhook.previousSecretExpiresAt && webhook.previousSecretExpiresAt > now);
+}
+
+/**
+ * Secrets a delivery is signed with, newest first
+ */
+export function signingSecrets(webhook: WebhookSecrets, now: Date = new Date()): string[] {
+  return previousSecretActive(webhook, now) ? [webhook.secret, webhook.previousSecret!] : [webhook.secret];
+}
+
+/**
+ * Rotation state shown on the webhook; null if it was never rotated
+ */
+export function serializeSecretRotation(
+  webhook: WebhookSecrets & { secretRotatedAt?: Date | null },
+  now: Date = new Date()
+) {
+  if (!webhook.secretRotatedAt) return null;
+
+  const active = previousSecretActive(webhook, now);
+  return {
+    rotated_at: webhook.secretRotatedAt,
+    previous_secret_active: active,
+    previous_secret_expires_at: active ? webhook.previousSecretExpiresAt : null,
+  };
+}
+
+/**
+ * Replace the webhook's secret, keeping the current one valid for
+ * `overlapSeconds`. A rotation during an overlap drops the older secret.
+ */
+export async function rotateWebhookSecret(
+  webhook: { id: string; secret: string },
+  overlapSeconds: number,
+  now: Date = new Date()
+) {
+  const secret = generateWebhookSecret();
+  const overlap = overlapSeconds > 0;
+
+  // Compare-and-swap on the secret, so concurrent rotations can't both
+  // demote the same secret and lose one of the new ones
+  const { count } = await prisma.webhook.updateMany({
+    where: { id: webhook.id, secret: webhook.secret },
+    data: {
+      secret,
+      previousSecret: overlap ? webhook.secret : null,
+      previousSecretExpiresAt: overlap ? new Date(now.getTime() + overlapSeconds * 1000) : null,
+      secretRotatedAt: now,
+    },
+  });
+  if (count !== 1) {
+    throw new AppError('Webhook secret was rotated concurrently; retry', 409, 'ROTATION_CONFLICT');
+  }
+
+  return prisma.webhook.findUnique({ where: { id: webhook.id } });
+}
+
+/**
+ * Forget previous secrets past their overlap window. Run by the retention job.
+ */
+export async function expirePreviousSecrets(now: Date = new Date()): Promise<number> {
+  const { count } = await prisma.webhook.updateMany({
+    where: { previousSecretExpiresAt: { lte: now } },
+    data: { previousSecret: null, previousSecretExpiresAt: null },
+  });
+  return count;
+}
@@ -0,0 +1,27 @@
+import crypto from 'crypto';
+
+/**
+ * Webhook request signing
+ *
+ *   X-Webhook-Timestamp: <unix ms>
+ *   X-Webhook-Signature: sha256=<hex HMAC-SHA256(secret, "<timestamp>.<body>")>
+ *
+ * While a rotated secret is still valid the header carries one signature
+ * per secret, comma separated, newest first:
+ *
+ *   X-Webhook-Signature: sha256=<new secret>,sha256=<previous secret>
+ *
+ * The SDK's WebhookVerifier checks the same construction; both are tested
+ * against packages/sdk/test-vectors/webhook-signatures.json.
+ */
+export function signWebhook(secret: string, timestamp: string, body: string): string {
+  const signature = crypto.createHmac('sha256', secret).update(`${timestamp}.${body}`).digest('hex');
+  return `sha256=${signature}`;
+}
+
+/**
+ * Signature header value for a delivery signed with every secret in `secrets`
+ */
+export function signWebhookWithSecrets(secrets: string[], timestamp: string, body: string): string {
+  return secrets.map((secret) => signWebhook(secret, timestamp, body)).join(',');
+}
@@ -0,0 +1,296 @@
+/**
+ * Webhook payload transformations
+ *
+ * Each subscription picks a body format:
+ * - `ninjapay`           the native event envelope (default)
+ * - `stripe_compatible`  a Stripe-shaped `event` object
+ * - `jq:<expr>`          a restricted jq-style projection of the native envelope
+ *
+ * The jq subset only builds objects, arrays and literals from paths into the
+ * event; there are no functions, pipes or loops, and evaluation is bounded in
+ * expression size, AST size, time and output size.
+ */
+
+export interface WebhookPayload {
+  id: string;
+  type: string;
+  created: number;
+  livemode: boolean;
+  data: Record<string, any>;
+  // Only present on events re-sent by a replay
+  replayed?: true;
+}
+
+export const DEFAULT_WEBHOOK_FORMAT = 'ninjapay';
+
+export const MAX_EXPRESSION_LENGTH = 1024;
+export const MAX_EXPRESSION_NODES = 256;
+export const MAX_TRANSFORMED_BYTES = 64 * 1024;
+export const MAX_TRANSFORM_MS = 50;
+
+export class WebhookTransformError extends Error {
+  constructor(message: string) {
+    super(message);
+    this.name = 'WebhookTransformError';
+  }
+}
+
+type Transform = (payload: WebhookPayload) => unknown;
+
+// ============ Stripe-compatible mapper ============
+
+const STRIPE_EVENT_TYPES: Record<string, string> = {
+  'payment_intent.created': 'payment_intent.created',
+  'payment_intent.confirmed': 'payment_intent.succeeded',
+  'payment_intent.failed': 'payment_intent.payment_failed',
+  'payment_intent.cancelled': 'payment_intent.canceled',
+  'payment_link.payment_completed': 'checkout.session.completed',
+};
+
+function toStripeEvent(payload: WebhookPayload): unknown {
+  return {
+    id: payload.id,
+    object: 'event',
+    created: Math.floor(payload.created / 1000),
+    livemode: payload.livemode,
+    type: STRIPE_EVENT_TYPES[payload.type] ?? payload.type,
+    data: { object: payload.data },
+    ...(payload.replayed ? { replayed: true } : {}),
+  };
+}
+
+// ============ Restricted jq-style expressions ============
+
+type Node =
+  | { kind: 'path'; segments: (string | number)[] }
+  | { kind: 'literal'; value: unknown }
+  | { kind: 'array'; items: Node[] }
+  | { kind: 'object'; entries: [string, Node][] };
+
+type Token = { type: 'punct' | 'ident' | 'string' | 'number'; value: string };
+
+function tokenize(source: string): Token[] {
+  const tokens: Token[] = [];
+  let i = 0;
+  while (i < source.length) {
+    const ch = source[i]!;
+    if (/\s/.test(ch)) {
+      i++;
+    } else if ('{}[],:.'.includes(ch)) {
+      tokens.push({ type: 'punct', value: ch });
+      i++;
+    } else if (ch === '"') {
+      let j = i + 1;
+      while (j < source.length && source[j] !== '"') {
+        j += source[j] === '\\' ? 2 : 1;
+      }
+      if (j >= source.length) {
+        throw new WebhookTransformError('unterminated string');
+      }
+      tokens.push({ type: 'string', value: source.slice(i, j + 1) });
+      i = j + 1;
+    } else if (/[-0-9]/.test(ch)) {
+      const match = /^-?\d+(\.\d+)?/.exec(source.slice(i));
+      if (!match) {
+        throw new WebhookTransformError(`unexpected '${ch}' at ${i}`);
+      }
+      tokens.push({ type: 'number', value: match[0] });
+      i += match[0].length;
+    } else if (/[A-Za-z_]/.test(ch)) {
+      const match = /^[A-Za-z_][A-Za-z0-9_]*/.exec(source.slice(i))!;
+      tokens.push({ type: 'ident', value: match[0] });
+      i += match[0].length;
+    } else {
+      throw new WebhookTransformError(`unexpected '${ch}' at ${i}`);
+    }
+  }
+  return tokens;
+}
+
+class Parser {
+  private pos = 0;
+  nodes = 0;
+
+  constructor(private tokens: Token[]) {}
+
+  parse(): Node {
+    const node = this.expr();
+    if (this.pos < this.tokens.length) {
+      throw new WebhookTransformError(`unexpected '${this.tokens[this.pos]!.value}'`);
+    }
+    return node;
+  }
+
+  private peek(value?: string): Token | undefined {
+    const token = this.tokens[this.pos];
+    return value === undefined || token?.value === value ? token : undefined;
+  }
+
+  private expect(value: string) {
+    if (!this.peek(value)) {
+      throw new WebhookTransformError(`expected '${value}'`);
+    }
+    this.pos++;
+  }
+
+  private expr(): Node {
+    if (++this.nodes > MAX_EXPRESSION_NODES) {
+      throw new WebhookTransformError(`expression exceeds ${MAX_EXPRESSION_NODES} nodes`);
+    }
+
+    const token = this.tokens[this.pos];
+    if (!token) {
+      throw new WebhookTransformError('unexpected end of expression');
+    }
+
+    if (token.value === '{') return this.object();
+    if (token.value === '[') return this.array();
+    if (token.value === '.') return this.path();
+
+    this.pos++;
+    if (token.type === 'string') return { kind: 'literal', value: JSON.parse(token.value) };
+    if (token.type === 'number') return { kind: 'literal', value: Number(token.value) };
+    if (token.type === 'ident' && ['true', 'false', 'null'].includes(token.value)) {
+      return { kind: 'literal', value: JSON.parse(token.value) };
+    }
+    throw new WebhookTransformError(`unsupported token '${token.value}'`);
+  }
+
+  private path(): Node {
+    const segments: (string | number)[] = [];
+    this.expect('.');
+    if (this.peek()?.type === 'ident') {
+      segments.push(this.tokens[this.pos++]!.value);
+    }
+    for (;;) {
+      if (this.peek('.')) {
+        this.pos++;
+        const token = this.tokens[this.pos++];
+        if (token?.type !== 'ident') {
+          throw new WebhookTransformError("expected a field name after '.'");
+        }
+        segments.push(token.value);
+      } else if (this.peek('[')) {
+        this.pos++;
+        const token = this.tokens[this.pos++];
+        if (token?.type !== 'number' || !/^\d+$/.test(token.value)) {
+          throw new WebhookTransformError('array index must be a non-negative integer');
+        }
+        segments.push(Number(token.value));
+        this.expect(']');
+      } else {
+        return { kind: 'path', segments };
+      }
+    }
+  }
+
+  private array(): Node {
+    const items: Node[] = [];
+    this.expect('[');
+    while (!this.peek(']')) {
+      items.push(this.expr());
+      if (!this.peek(']')) this.expect(',');
+    }
+    this.expect(']');
+    return { kind: 'array', items };
+  }
+
+  private object(): Node {
+    const entries: [string, Node][] = [];
+    this.expect('{');
+    while (!this.peek('}')) {
+      const token = this.tokens[this.pos++];
+      let key: string;
+      if (token?.type === 'ident') {
+        key = token.value;
+      } else if (token?.type === 'string') {
+        key = JSON.parse(token.value);
+      } else {
+        throw new WebhookTransformError('object keys must be names or strings');
+      }
+
+      if (this.peek(':')) {
+        this.pos++;
+        entries.push([key, this.expr()]);
+      } else {
+        // `{id}` is shorthand for `{id: .id}`
+        entries.push([key, { kind: 'path', segments: [key] }]);
+      }
+      if (!this.peek('}')) this.expect(',');
+    }
+    this.expect('}');
+    return { kind: 'object', entries };
+  }
+}
+
+function evaluate(node: Node, input: unknown, deadline: number): unknown {
+  if (Date.now() > deadline) {
+    throw new WebhookTransformError(`transformation exceeded ${MAX_TRANSFORM_MS}ms`);
+  }
+
+  switch (node.kind) {
+    case 'literal':
+      return node.value;
+    case 'path': {
+      let value: any = input;
+      for (const segment of node.segments) {
+        if (value === null || typeof value !== 'object' || !Object.hasOwn(value, segment)) {
+          return null;
+        }
+        value = value[segment];
+      }
+      return value ?? null;
+    }
+    case 'array':
+      return node.items.map((item) => evaluate(item, input, deadline));
+    case 'object':
+      return Object.fromEntries(
+        node.entries.map(([key, value]) => [key, evaluate(value, input, deadline)])
+      );
+  }
+}
+
+function compileExpression(source: string): Transform {
+  if (source.length > MAX_EXPRESSION_LENGTH) {
+    throw new WebhookTransformError(`expression exceeds ${MAX_EXPRESSION_LENGTH} characters`);
+  }
+  const ast = new Parser(tokenize(source)).parse();
+  return (payload) => evaluate(ast, payload, Date.now() + MAX_TRANSFORM_MS);
+}
+
+// ============ Public API ============
+
+/**
+ * Compile a subscription format, throwing WebhookTransformError if invalid
+ */
+export function compileWebhookFormat(format: string): Transform {
+  if (format === 'ninjapay') return (payload) => payload;
+  if (format === 'stripe_compatible') return toStripeEvent;
+  if (format.startsWith('jq:')) return compileExpression(format.slice(3));
+  throw new WebhookTransformError(
+    `unknown format '${format}' (expected ninjapay, stripe_compatible or jq:<expr>)`
+  );
+}
+
+/**
+ * Whether a format string is valid, for request validation
+ */
+export function isValidWebhookFormat(format: string): boolean {
+  try {
+    compileWebhookFormat(format);
+    return true;
+  } catch {
+    return false;
+  }
+}
+
+/**
+ * Render the body actually sent (and signed) for a subscription
+ */
+export function transformWebhookBody(format: string, payload: WebhookPayload): string {
+  const body = JSON.stringify(compileWebhookFormat(format)(payload));
+  if (Buffer.byteLength(body) > MAX_TRANSFORMED_BYTES) {
+    throw new WebhookTransformError(`transformed body exceeds ${MAX_TRANSFORMED_BYTES} bytes`);
+  }
+  return body;
+}
@@ -15,12 +15,15 @@ const envSchema = z.object({
 
   // Database
   DATABASE_URL: z.string().min(1, 'DATABASE_URL is required'),
+  DATABASE_READ_URL: z.string().optional(),
+  READ_AFTER_WRITE_SECONDS: z.string().regex(/^\d+$/).transform(Number).optional(),
 
   // Redis (optional in development)
   REDIS_URL: z.string().optional(),
 
   // Authentication
   JWT_SECRET: z.string().min(32, 'JWT_SECRET must be at least 32 characters'),
+  ADMIN_API_TOKEN: z.string().min(32, 'ADMIN_API_TOKEN must be at least 32 characters').optional(),
 
   // Encryption
   ENCRYPTION_MASTER_KEY: z.string().length(64, 'ENCRYPTION_MASTER_KEY must be 64 hex characters'),
@@ -48,11 +51,15 @@ const envSchema = z.object({
 
   // Webhooks
   WEBHOOK_SIGNING_SECRET: z.string().min(32).optional(),
+  WEBHOOK_SECRET_OVERLAP_SECONDS: z.string().regex(/^\d+$/).transform(Number).optional(),
 
   // Rate Limiting
   RATE_LIMIT_WINDOW: z.string().regex(/^\d+$/).transform(Number).optional(),
   RATE_LIMIT_MAX_REQUESTS: z.string().regex(/^\d+$/).transform(Number).optional(),
 
+  // Retention job (releases expired payment id reservations)
+  RETENTION_INTERVAL_SECONDS: z.string().regex(/^\d+$/).transform(Number).optional(),
+
   // Logging
   LOG_LEVEL: z.enum(['debug', 'info', 'warn', 'error']).default('info'),
   LOG_FORMAT: z.enum(['json', 'pretty']).default('json'),
@@ -210,6 +217,7 @@ export function printEnvConfig(): void {
     MPC_MODE: env.MPC_MODE,
     SOLANA_NETWORK: env.SOLANA_NETWORK,
     DATABASE_URL: env.DATABASE_URL.replace(/\/\/.*@/, '//***:***@'),
+    DATABASE_READ_URL: env.DATABASE_READ_URL?.replace(/\/\/.*@/, '//***:***@') || 'not set',
     REDIS_URL: env.REDIS_URL?.replace(/\/\/.*@/, '//***:***@') || 'not set',
     JWT_SECRET: '***',
     ENCRYPTION_MASTER_KEY: '***',
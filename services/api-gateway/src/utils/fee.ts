@@ -0,0 +1,13 @@
+/**
+ * Protocol fee math, mirroring `ninjapay_types::fee` and `process_payment`
+ * in programs/ninjapay-vault: floor(amount * fee_basis_points / 10_000) in
+ * base units. Both sides are checked against
+ * packages/ninjapay-types/tests/fixtures/fee_vectors.json.
+ */
+
+export const BASIS_POINTS_DENOMINATOR = 10_000n;
+
+/** Fee charged on `amount` base units */
+export function protocolFee(amount: bigint, feeBasisPoints: number): bigint {
+  return (amount * BigInt(feeBasisPoints)) / BASIS_POINTS_DENOMINATOR;
+}
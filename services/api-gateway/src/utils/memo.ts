@@ -0,0 +1,42 @@
+import { PublicKey, TransactionInstruction } from '@solana/web3.js';
+import { z } from 'zod';
+
+// SPL Memo program v2
+export const MEMO_PROGRAM_ID = new PublicKey('MemoSq4gqABAXKb96qnH8TysNcWxMyWCqXgDLGmfcHr');
+
+export const MAX_MEMO_BYTES = 256;
+
+// C0/C1 control characters, including newlines and tabs
+const CONTROL_CHARACTERS = /[\u0000-\u001f\u007f-\u009f]/;
+
+// Unpaired UTF-16 surrogates (e.g. a JSON "\ud800" escape) have no UTF-8 encoding
+const LONE_SURROGATE = /[\uD800-\uDBFF](?![\uDC00-\uDFFF])|(?<![\uD800-\uDBFF])[\uDC00-\uDFFF]/;
+
+/**
+ * Memo attached to a payment transaction, e.g. an exchange deposit tag.
+ * Validated as UTF-8 of at most 256 bytes with no control characters.
+ */
+export const memoSchema = z
+  .string()
+  .min(1)
+  .refine((memo) => !LONE_SURROGATE.test(memo), {
+    message: 'memo must be valid UTF-8',
+  })
+  .refine((memo) => Buffer.byteLength(memo, 'utf8') <= MAX_MEMO_BYTES, {
+    message: `memo must be at most ${MAX_MEMO_BYTES} bytes`,
+  })
+  .refine((memo) => !CONTROL_CHARACTERS.test(memo), {
+    message: 'memo must not contain control characters',
+  });
+
+/**
+ * SPL Memo instruction, appended after the payment instruction so the memo
+ * lands in the same transaction. The payer signs it.
+ */
+export function createMemoInstruction(memo: string, signer: PublicKey): TransactionInstruction {
+  return new TransactionInstruction({
+    programId: MEMO_PROGRAM_ID,
+    keys: [{ pubkey: signer, isSigner: true, isWritable: false }],
+    data: Buffer.from(memo, 'utf8'),
+  });
+}
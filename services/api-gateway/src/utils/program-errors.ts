@@ -0,0 +1,152 @@
+/**
+ * Decoding of `custom program error: 0x...` from failed transactions.
+ *
+ * Mirrors arcium-service's program_errors module (GET /api/v1/errors/program/{code}):
+ * 6000 and up are the vault's VaultError variants, 100-5999 Anchor framework
+ * errors, and lower codes the SPL Token program. VAULT_ERRORS is checked
+ * against the program's `#[error_code]` enum in tests/program-errors.test.ts.
+ */
+
+export type ProgramErrorSource = 'vault' | 'anchor' | 'token';
+
+export type DecodedProgramError = {
+  code: number;
+  hex: string;
+  program: ProgramErrorSource;
+  name: string;
+  message: string;
+  remediation: string;
+};
+
+type Entry = [name: string, message: string, remediation: string];
+
+const VAULT_ERROR_OFFSET = 6000;
+const ANCHOR_ERROR_MIN = 100;
+
+/** In code order from 6000, as declared in programs/ninjapay-vault */
+export const VAULT_ERRORS: Entry[] = [
+  ['FeeTooHigh', 'Fee exceeds maximum allowed (10%)', 'Fees are capped at 1000 basis points (10%); submit a lower fee.'],
+  ['InvalidAmount', 'Invalid payment amount', "The payment amount must be greater than zero and within the vault's payment bounds; check min_payment and max_payment on the VaultConfig."],
+  ['Unauthorized', 'Unauthorized access', "Sign with the key this action needs: the vault authority, the program's upgrade authority for initialize, the proposed authority for accept_authority, the recovery authority for emergency recovery, or for a refund the payment's merchant and the fee collector."],
+  ['AlreadyRefunded', 'Payment has already been refunded', 'This payment was already refunded; nothing more is owed to the payer.'],
+  ['InvalidFeeAccount', "Fee token account is not the fee collector's account for this mint", "Pass the fee collector's token account for the payment mint as the fee account."],
+  ['VaultPaused', 'Vault is paused', 'Payments, payroll and refunds are halted by the vault authority; retry once the vault is unpaused.'],
+  ['MerchantAccountMismatch', 'Merchant token account is not owned by the merchant', "Pass a token account owned by the merchant wallet, normally the merchant's associated token account."],
+  ['MintMismatch', 'Token accounts are for different mints', 'Use token accounts for the payment mint: the payer, merchant and fee accounts must all hold the same token, and a refund goes back in the token paid.'],
+  ['InvalidMerchantConfig', "Merchant config is not this merchant's fee override account", "Pass the paying merchant's MerchantConfig (seeds merchant_cfg and the merchant wallet) as the first remaining account, or omit it to pay the vault-wide fee."],
+  ['MathOverflow', 'Arithmetic overflow', 'A vault total would overflow; contact support with the transaction signature.'],
+  ['AmountTooSmall', 'Payment amount is smaller than its fee', 'The fee exceeds the payment amount; pay a larger amount or check the vault and merchant fee settings.'],
+  ['PaymentExpired', 'Payment request has expired', "The payment request's expires_at has passed; ask the merchant for a new payment request."],
+  ['InvalidSplit', 'Split recipients or basis points are invalid', 'Pass 1 to 8 splits with non-zero basis points adding up to 10000 less the vault fee, and their token accounts as remaining accounts in the same order.'],
+  ['InsufficientFunds', 'Token balance is too low to fund the batch', "The company's token account holds less than the batch total; fund it before submitting the batch."],
+  ['InvalidDisbursement', "Disbursements don't match the batch total, count or accounts", 'Pass one amount per payment adding up to the batch total, and for each an employee token account followed by its disbursement record at [disbursement, batch_id, index] as remaining accounts.'],
+  ['RateLimitExceeded', "Payer has reached the vault's hourly payment limit", 'The payer has made max_payments_per_hour payments, or paid max_volume_per_hour base units, within the last hour; retry once their hourly window resets.'],
+  ['MintNotWhitelisted', "Mint is not on the vault's whitelist", "The vault only accepts whitelisted mints: pass the mint's AcceptedMint account, or ask the vault authority to add the mint with add_accepted_mint."],
+  ['NoPendingAuthority', 'No authority transfer is pending', 'There is no proposed authority to cancel; propose_authority first, or the proposal was already accepted or cancelled.'],
+  ['InvalidFeeSchedule', 'Fee tiers must number 1 to 5 with ascending volume thresholds', 'Pass 1 to 5 fee tiers sorted by strictly ascending volume_threshold; to charge the vault-wide fee again, use clear_fee_schedule.'],
+  ['FeeScheduleRequired', 'Fee schedule account is required while the vault has one', 'The vault charges fees by volume tier: pass its FeeSchedule account (seeds ["fee_schedule"]) with the payment.'],
+  ['InvalidFeeCollectors', 'Fee collectors must be 1 to 3 distinct accounts with shares adding up to 10000', 'Pass 1 to 3 distinct fee collectors whose basis points are each above 0 and add up to exactly 10000.'],
+  ['MissingFeeAccount', "A fee collector's token account is missing", "The vault divides fees among several collectors: pass each collector's token account for the mint as fee_token_account_1 and fee_token_account_2."],
+  ['RetentionPeriodNotElapsed', "Payment record is still within the vault's retention period", "The payment record is younger than the vault's retention period (at least 30 days); close it once that has passed, or ask the vault authority for a compliance close."],
+  ['AuthorityTransferExpired', 'Authority transfer offer has expired', 'The authority offer lapsed 48 hours after it was proposed; ask the current authority to call propose_authority again.'],
+  ['InvalidPaymentBatch', 'Payment batch must have 1 to 5 payments, each with its amount, id, commitment and two accounts', 'Send one to five payments, with as many amounts, payment ids and commitments, and a merchant token account and payment record per payment as remaining accounts.'],
+  ['BatchNotFinal', 'Batch is not final yet', 'The batch ran less than 30 days ago or tokens remain in its escrow; close the record once the retention period has passed and the escrow is empty.'],
+  ['DailyCapExceeded', "Payment exceeds the vault's daily volume cap", "Today's payments through the vault have reached its daily volume cap; retry after midnight UTC or ask the vault authority to raise it with set_daily_volume_cap."],
+  ['RefundExceedsPayment', "Refund exceeds the payment's unrefunded net amount", "Refunds can return at most the payment's net amount, less what was already refunded; refund the remainder or less."],
+  ['CommitmentMismatch', 'Commitment does not match the amount and blinding factor', 'The vault verifies commitments on-chain: send the blinding factor the commitment was made with, so that sha256(amount as u64 little-endian || blinding_factor) equals it.'],
+  ['RetentionTooShort', 'Retention period is shorter than the minimum of 30 days', 'Retention periods are at least 30 days; pass 30 or more to set_retention_days.'],
+  ['PaymentUnderDispute', 'Payment is under dispute', "The payer has disputed this payment; it can't be refunded or closed until the vault authority resolves the dispute with resolve_dispute."],
+  ['NotDisputed', 'Payment is not under dispute', "Only a disputed payment can be resolved; check the PaymentRecord's status."],
+  ['EscrowNotHeld', 'Escrowed payment was already captured or cancelled', "The escrowed payment has already been captured or cancelled; check the EscrowRecord's status."],
+  ['EscrowNotExpired', "Escrowed payment can't be cancelled before it expires", 'The payer can cancel an escrowed payment only once its expires_at has passed; until then the merchant may still capture it.'],
+  ['ScheduleNotDue', 'Payroll schedule is not due yet', "The schedule's next_due_at hasn't arrived; run the payroll once it has, or move the date with update_payroll_schedule."],
+  ['ScheduleNotActive', 'Payroll schedule is paused', 'The company paused this payroll schedule; set it Active again with update_payroll_schedule before running it.'],
+  ['InvalidSchedule', 'Payroll schedule needs a positive frequency and authorized amount', 'Pass a frequency_seconds and authorized_amount greater than zero.'],
+  ['ScheduleAmountExceeded', "Payroll exceeds the schedule's authorized amount", "The batch's total_amount is over the schedule's authorized_amount; pay less or raise the limit with update_payroll_schedule."],
+  ['MerchantNotRegistered', 'Merchant is not registered with the vault', 'Have the vault authority call register_merchant for this merchant, or pay a registered merchant.'],
+  ['InvalidPayrollApprover', 'Payroll approver must be a key other than the company', 'Name a second key as approver; the company already signs every payroll batch.'],
+  ['PayerBlacklisted', 'Payer is blacklisted', 'The vault authority has blacklisted this payer; payments resume once remove_from_blacklist is called.'],
+  ['NoPendingRecovery', 'No emergency recovery has been proposed', 'Call propose_emergency_recovery as the recovery authority first, then wait out the 24-hour delay.'],
+  ['RecoveryTimelockActive', "Emergency recovery can't be executed until 24 hours after its proposal", 'Retry emergency_recover once 24 hours have passed since propose_emergency_recovery.'],
+  ['InvalidUnlockTime', 'Unlock time must be in the future', 'Pass an unlock_at later than the current cluster time to create_timelock_payment.'],
+  ['TimelockNotUnlocked', "Timelocked payment hasn't unlocked yet", "Retry claim_timelock_payment at or after the payment's unlock_at."],
+  ['TimelockAlreadyClaimed', 'Timelocked payment already claimed', 'The beneficiary has already been paid; fund a new timelock payment under a fresh payment_id.'],
+  ['InvalidStream', 'Stream must start now or later, end after it starts and have a positive rate', 'Pass a rate_per_second above zero and a start_time no earlier than now, with end_time after it.'],
+  ['NothingStreamed', 'Nothing has streamed since the last withdrawal', 'Wait for the stream to run past its start_time or last withdrawal before calling withdraw_from_stream again.'],
+  ['StreamEnded', 'Stream has ended', 'Streams can only be topped up before their end_time; open a new stream instead.'],
+];
+
+const ANCHOR_ERRORS: Record<number, Entry> = {
+  101: ['InstructionFallbackNotFound', 'Fallback functions are not supported', 'The client is calling an instruction the deployed program does not have; update the SDK or IDL.'],
+  102: ['InstructionDidNotDeserialize', 'The program could not deserialize the given instruction', "Check the instruction arguments match the program's IDL."],
+  2000: ['ConstraintMut', 'A mut constraint was violated', 'Mark the account writable in the transaction.'],
+  2001: ['ConstraintHasOne', 'A has one constraint was violated', 'An account does not belong to the expected owner, e.g. the wrong authority for the vault.'],
+  2002: ['ConstraintSigner', 'A signer constraint was violated', 'The payer or authority must sign the transaction.'],
+  2003: ['ConstraintRaw', 'A raw constraint was violated', 'An account relationship check failed; rebuild the transaction from fresh account data.'],
+  2006: ['ConstraintSeeds', 'A seeds constraint was violated', 'A PDA does not match its seeds; re-derive the vault config or payment record address.'],
+  2012: ['ConstraintAddress', 'An address constraint was violated', 'An account is not at the expected address, e.g. the wrong fee collector.'],
+  2014: ['ConstraintTokenMint', 'A token mint constraint was violated', 'A token account holds a different mint than the payment currency.'],
+  2015: ['ConstraintTokenOwner', 'A token owner constraint was violated', 'A token account is not owned by the payer or merchant it was passed for.'],
+  3001: ['AccountDiscriminatorNotFound', 'No 8 byte discriminator was found on the account', 'The account is empty; initialize the vault before taking payments.'],
+  3002: ['AccountDiscriminatorMismatch', '8 byte discriminator did not match what was expected', 'An account of the wrong type was passed.'],
+  3003: ['AccountDidNotDeserialize', 'Failed to deserialize the account', 'A VaultConfig written by an older program version is shorter than the current layout; the vault authority runs migrate_vault_config.'],
+  3007: ['AccountOwnedByWrongProgram', 'The given account is owned by a different program than expected', 'Check the program id and cluster (devnet vs mainnet).'],
+  3012: ['AccountNotInitialized', 'The program expected this account to be already initialized', "Create the account, e.g. the merchant's token account, before paying."],
+};
+
+const TOKEN_ERRORS: Record<number, Entry> = {
+  0: ['NotRentExempt', 'Lamport balance below rent-exempt threshold', 'Fund the account to the rent-exempt minimum.'],
+  1: ['InsufficientFunds', 'Insufficient funds', "The payer's token balance is below the payment amount plus fee."],
+  2: ['InvalidMint', 'Invalid Mint', 'A token account was created for a different mint.'],
+  3: ['MintMismatch', 'Account not associated with this Mint', "Use the payer's and merchant's token accounts for the payment currency."],
+  4: ['OwnerMismatch', 'Owner does not match', 'The signer does not own the source token account.'],
+  9: ['UninitializedState', 'State is uninitialized', "Create the recipient's associated token account first."],
+  17: ['AccountFrozen', 'Account is frozen', "The token account is frozen by the mint's freeze authority; the payment cannot proceed."],
+  18: ['MintDecimalsMismatch', 'The provided decimals value different from the Mint decimals', "Pass the mint's decimals (6 for USDC)."],
+};
+
+/**
+ * Look up a custom program error code
+ */
+export function lookupProgramError(code: number): DecodedProgramError | null {
+  let program: ProgramErrorSource;
+  let entry: Entry | undefined;
+  if (code >= VAULT_ERROR_OFFSET) {
+    [program, entry] = ['vault', VAULT_ERRORS[code - VAULT_ERROR_OFFSET]];
+  } else if (code >= ANCHOR_ERROR_MIN) {
+    [program, entry] = ['anchor', ANCHOR_ERRORS[code]];
+  } else {
+    [program, entry] = ['token', TOKEN_ERRORS[code]];
+  }
+  if (!entry) return null;
+
+  const [name, message, remediation] = entry;
+  return { code, hex: `0x${code.toString(16)}`, program, name, message, remediation };
+}
+
+/**
+ * Decode the first custom program error in an RPC error message or logs
+ */
+export function decodeProgramError(text: string | string[] | null | undefined): DecodedProgramError | null {
+  const joined = Array.isArray(text) ? text.join('\n') : text ?? '';
+  const match = /custom program error: (0x[0-9a-fA-F]+|\d+)/.exec(joined);
+  return match ? lookupProgramError(Number(match[1])) : null;
+}
+
+/**
+ * Decode a simulation's `err`, e.g. `{ InstructionError: [1, { Custom: 1 }] }`
+ */
+export function decodeSimulationError(err: unknown): DecodedProgramError | null {
+  const custom = (err as any)?.InstructionError?.[1]?.Custom;
+  return typeof custom === 'number' ? lookupProgramError(custom) : null;
+}
+
+/**
+ * Failure message for a submitted transaction, naming the program error and
+ * its remediation when there is one
+ */
+export function describeTransactionError(error: any): string {
+  const decoded = decodeProgramError(error?.message) ?? decodeProgramError(error?.logs);
+  if (!decoded) return error?.message ?? String(error);
+  return `${decoded.name} (custom program error ${decoded.hex}): ${decoded.message}. ${decoded.remediation}`;
+}
@@ -0,0 +1,221 @@
+import { describe, it, expect, vi } from 'vitest';
+import type { Request } from 'express';
+import { mockPrisma, createMockMerchant, createMockApiKey, createMockPaymentIntent } from './setup';
+import {
+  apiKeyScopeDetails,
+  authenticateMerchant,
+  assertLivemode,
+  assertMerchantAllowed,
+  generateToken,
+  parseApiKeyMode,
+} from '../src/middleware/authenticate.js';
+
+vi.mock('bcrypt', () => ({
+  default: { compare: vi.fn(), hash: vi.fn() },
+}));
+
+import bcrypt from 'bcrypt';
+
+function mockRequest(headers: Record<string, string>): Request {
+  return { headers } as unknown as Request;
+}
+
+function thrown(fn: () => void): any {
+  try {
+    fn();
+  } catch (error) {
+    return error;
+  }
+  return undefined;
+}
+
+async function run(req: Request) {
+  const next = vi.fn();
+  await authenticateMerchant(req, {} as any, next);
+  return next.mock.calls[0]?.[0];
+}
+
+describe('Live and test mode authentication', () => {
+  const merchant = createMockMerchant();
+
+  describe('parseApiKeyMode', () => {
+    it('should read the mode from the key prefix', () => {
+      expect(parseApiKeyMode('sk_live_abc123')).toBe('live');
+      expect(parseApiKeyMode('sk_test_abc123')).toBe('test');
+    });
+
+    it('should reject unknown prefixes', () => {
+      expect(parseApiKeyMode('pk_live_abc123')).toBeNull();
+      expect(parseApiKeyMode('sk_prod_abc123')).toBeNull();
+      expect(parseApiKeyMode('sk_live')).toBeNull();
+      expect(parseApiKeyMode('')).toBeNull();
+    });
+  });
+
+  describe('API keys', () => {
+    it('should authenticate a test key into sandbox mode', async () => {
+      const apiKey = createMockApiKey({ keyPrefix: 'sk_test_', livemode: false, merchant });
+      mockPrisma.apiKey.findMany.mockResolvedValue([apiKey]);
+      mockPrisma.apiKey.update.mockResolvedValue(apiKey);
+      vi.mocked(bcrypt.compare).mockResolvedValue(true as never);
+
+      const req = mockRequest({ 'x-api-key': 'sk_test_abc123' });
+      expect(await run(req)).toBeUndefined();
+
+      expect(req.merchantId).toBe(merchant.id);
+      expect(req.livemode).toBe(false);
+      expect(mockPrisma.apiKey.findMany).toHaveBeenCalledWith(
+        expect.objectContaining({
+          where: expect.objectContaining({ keyPrefix: 'sk_test_', livemode: false }),
+        })
+      );
+    });
+
+    it('should authenticate a live key into live mode', async () => {
+      const apiKey = createMockApiKey({ merchant });
+      mockPrisma.apiKey.findMany.mockResolvedValue([apiKey]);
+      mockPrisma.apiKey.update.mockResolvedValue(apiKey);
+      vi.mocked(bcrypt.compare).mockResolvedValue(true as never);
+
+      const req = mockRequest({ 'x-api-key': 'sk_live_abc123' });
+      expect(await run(req)).toBeUndefined();
+      expect(req.livemode).toBe(true);
+    });
+
+    it('should reject keys without a mode prefix before any lookup', async () => {
+      const error = await run(mockRequest({ 'x-api-key': 'abc123' }));
+
+      expect(error.code).toBe('INVALID_API_KEY');
+      expect(error.statusCode).toBe(401);
+      expect(mockPrisma.apiKey.findMany).not.toHaveBeenCalled();
+    });
+
+    it('should reject keys that do not match a stored hash', async () => {
+      mockPrisma.apiKey.findMany.mockResolvedValue([createMockApiKey({ merchant })]);
+      vi.mocked(bcrypt.compare).mockResolvedValue(false as never);
+
+      const error = await run(mockRequest({ 'x-api-key': 'sk_live_wrong' }));
+      expect(error.code).toBe('INVALID_API_KEY');
+    });
+
+    it('should keep merchants pending approval out of live mode', async () => {
+      const pending = { ...merchant, status: 'PENDING' };
+      mockPrisma.apiKey.findMany.mockResolvedValue([createMockApiKey({ merchant: pending })]);
+      vi.mocked(bcrypt.compare).mockResolvedValue(true as never);
+
+      const error = await run(mockRequest({ 'x-api-key': 'sk_live_abc123' }));
+      expect(error.code).toBe('MERCHANT_PENDING_APPROVAL');
+      expect(error.statusCode).toBe(403);
+    });
+  });
+
+  describe('Dashboard sessions', () => {
+    const token = generateToken({
+      id: merchant.id,
+      walletAddress: merchant.walletAddress,
+      type: 'merchant',
+    });
+
+    it('should default to live mode', async () => {
+      mockPrisma.merchant.findUnique.mockResolvedValue(merchant);
+
+      const req = mockRequest({ authorization: `Bearer ${token}` });
+      expect(await run(req)).toBeUndefined();
+      expect(req.livemode).toBe(true);
+    });
+
+    it('should switch to sandbox with the mode header', async () => {
+      mockPrisma.merchant.findUnique.mockResolvedValue(merchant);
+
+      const req = mockRequest({ authorization: `Bearer ${token}`, 'x-ninjapay-mode': 'test' });
+      expect(await run(req)).toBeUndefined();
+      expect(req.livemode).toBe(false);
+    });
+
+    it('should reject unknown modes', async () => {
+      mockPrisma.merchant.findUnique.mockResolvedValue(merchant);
+
+      const req = mockRequest({ authorization: `Bearer ${token}`, 'x-ninjapay-mode': 'staging' });
+      expect((await run(req)).code).toBe('INVALID_MODE');
+    });
+  });
+
+  describe('assertLivemode', () => {
+    it('should allow records in the request mode', () => {
+      const req = { livemode: false } as Request;
+      expect(() =>
+        assertLivemode(req, createMockPaymentIntent({ livemode: false }), 'payment intent')
+      ).not.toThrow();
+    });
+
+    it('should reject live records for test requests with LIVEMODE_MISMATCH', () => {
+      const req = { livemode: false } as Request;
+
+      const error = thrown(() => assertLivemode(req, createMockPaymentIntent(), 'payment intent'));
+
+      expect(error.code).toBe('LIVEMODE_MISMATCH');
+      expect(error.statusCode).toBe(403);
+      expect(error.message).toMatch(/live mode.*test mode/);
+    });
+
+    it('should reject sandbox records for live requests', () => {
+      const req = { livemode: true } as Request;
+
+      const error = thrown(() =>
+        assertLivemode(req, createMockPaymentIntent({ livemode: false }), 'payment intent')
+      );
+      expect(error.code).toBe('LIVEMODE_MISMATCH');
+    });
+  });
+
+  describe('Merchant allowlists', () => {
+    const subMerchantWallet = 'So1anaSubMerchantWa11et1111111111111111111';
+    const scoped = (allowedMerchants: string[]) =>
+      ({ apiKeyScope: { apiKeyId: 'apikey_test123', allowedMerchants } }) as Request;
+
+    it('should carry the key scope onto the request', async () => {
+      const apiKey = createMockApiKey({ merchant, allowedMerchants: [subMerchantWallet] });
+      mockPrisma.apiKey.findMany.mockResolvedValue([apiKey]);
+      mockPrisma.apiKey.update.mockResolvedValue(apiKey);
+      vi.mocked(bcrypt.compare).mockResolvedValue(true as never);
+
+      const req = mockRequest({ 'x-api-key': 'sk_live_abc123' });
+      expect(await run(req)).toBeUndefined();
+      expect(req.apiKeyScope).toEqual({ apiKeyId: 'apikey_test123', allowedMerchants: [subMerchantWallet] });
+    });
+
+    it('should let unrestricted keys and dashboard sessions pay anyone', async () => {
+      await expect(assertMerchantAllowed(scoped([]), subMerchantWallet)).resolves.toBeUndefined();
+      await expect(assertMerchantAllowed({} as Request, subMerchantWallet)).resolves.toBeUndefined();
+      expect(mockPrisma.merchant.count).not.toHaveBeenCalled();
+    });
+
+    it('should accept listed wallets and wallets of listed merchants', async () => {
+      await expect(assertMerchantAllowed(scoped([subMerchantWallet]), subMerchantWallet)).resolves.toBeUndefined();
+
+      mockPrisma.merchant.count.mockResolvedValue(1);
+      await expect(assertMerchantAllowed(scoped(['merchant_sub1']), subMerchantWallet)).resolves.toBeUndefined();
+      expect(mockPrisma.merchant.count).toHaveBeenCalledWith({
+        where: { id: { in: ['merchant_sub1'] }, walletAddress: subMerchantWallet },
+      });
+    });
+
+    it('should reject other recipients with MERCHANT_NOT_ALLOWED', async () => {
+      mockPrisma.merchant.count.mockResolvedValue(0);
+
+      const error = await assertMerchantAllowed(scoped(['merchant_sub1']), merchant.walletAddress).catch((e) => e);
+      expect(error.code).toBe('MERCHANT_NOT_ALLOWED');
+      expect(error.statusCode).toBe(403);
+    });
+
+    it('should describe the scope for audit events', () => {
+      expect(apiKeyScopeDetails(scoped(['merchant_sub1']))).toEqual({
+        api_key_id: 'apikey_test123',
+        key_scope: 'restricted',
+        allowed_merchants: ['merchant_sub1'],
+      });
+      expect(apiKeyScopeDetails(scoped([])).key_scope).toBe('unrestricted');
+      expect(apiKeyScopeDetails({} as Request).key_scope).toBe('dashboard');
+    });
+  });
+});
@@ -0,0 +1,154 @@
+import { describe, it, expect, vi, beforeEach } from 'vitest';
+import { Keypair, PublicKey } from '@solana/web3.js';
+import { ACCOUNT_SIZE, getAssociatedTokenAddressSync } from '@solana/spl-token';
+import { CostQuoteService, PAYMENT_RECORD_SPACE } from '../src/services/cost-quote.js';
+import { MEMO_PROGRAM_ID } from '../src/utils/memo.js';
+
+const VAULT_PROGRAM_ID = 'NJPvau1tPBHrRxUqrLvhLq3zDpNZRGpNPdTpP1Dvq6C';
+const DEVNET_USDC = new PublicKey('4zMMC9srt5Ri5X14GAgXhaHii3GnPAEERYPJgZJDncDU');
+
+const [vaultConfig] = PublicKey.findProgramAddressSync(
+  [Buffer.from('vault_config')],
+  new PublicKey(VAULT_PROGRAM_ID)
+);
+
+function vaultConfigData(feeBasisPoints: number): Buffer {
+  const data = Buffer.alloc(8 + 32 + 32 + 2 + 8 + 8 + 1);
+  data.writeUInt16LE(feeBasisPoints, 8 + 32 + 32);
+  return data;
+}
+
+// Rent-exempt minimum as computed by the runtime: (128 + size) * 3480 * 2
+function rent(size: number): number {
+  return (128 + size) * 6960;
+}
+
+describe('Payment cost quotes', () => {
+  const payer = Keypair.generate().publicKey;
+  const recipient = Keypair.generate().publicKey;
+  const recipientAta = getAssociatedTokenAddressSync(DEVNET_USDC, recipient, true);
+
+  let accounts: Map<string, { data: Buffer }>;
+  let connection: any;
+  let price: number | null;
+  let service: CostQuoteService;
+
+  beforeEach(() => {
+    accounts = new Map([[vaultConfig.toBase58(), { data: vaultConfigData(50) }]]);
+    price = 150;
+    connection = {
+      getAccountInfo: vi.fn(async (key: PublicKey) => accounts.get(key.toBase58()) ?? null),
+      getMinimumBalanceForRentExemption: vi.fn(async (size: number) => rent(size)),
+      getLatestBlockhash: vi.fn(async () => ({
+        blockhash: PublicKey.default.toBase58(),
+        lastValidBlockHeight: 1,
+      })),
+      getFeeForMessage: vi.fn(async () => ({ value: 5000 })),
+      simulateTransaction: vi.fn(async () => ({ value: { err: null, unitsConsumed: 30_000 } })),
+    };
+    service = new CostQuoteService(
+      connection,
+      { solPrice: async () => price },
+      { vaultProgramId: VAULT_PROGRAM_ID }
+    );
+  });
+
+  const request = () => ({
+    payer: payer.toBase58(),
+    recipient: recipient.toBase58(),
+    amount: 100,
+    currency: 'USDC',
+  });
+
+  it('should itemize fees and rent in lamports and the payment currency', async () => {
+    const quote = await service.quote(request());
+
+    expect(quote.currency).toBe('USDC');
+    expect(quote.fee_basis_points).toBe(50);
+    expect(quote.protocol_fee.amount).toBe(0.5);
+    expect(quote.protocol_fee.lamports).toBe(Math.ceil((0.5 / 150) * 1e9));
+
+    // 5000 base fee + 1000 microlamports * 30k CU
+    expect(quote.network_fee.lamports).toBe(5030);
+    expect(quote.payment_record_rent.lamports).toBe(rent(PAYMENT_RECORD_SPACE));
+    expect(quote.payment_record_rent.amount).toBeCloseTo((rent(PAYMENT_RECORD_SPACE) / 1e9) * 150, 6);
+    expect(quote.ata_creation_rent?.lamports).toBe(rent(ACCOUNT_SIZE));
+
+    expect(quote.total.lamports).toBe(
+      quote.protocol_fee.lamports! + 5030 + rent(PAYMENT_RECORD_SPACE) + rent(ACCOUNT_SIZE)
+    );
+    expect(quote.total.amount).toBeCloseTo(
+      0.5 + quote.network_fee.amount! + quote.payment_record_rent.amount! + quote.ata_creation_rent!.amount!,
+      6
+    );
+    expect(Date.parse(quote.quoted_at)).not.toBeNaN();
+  });
+
+  it('should omit ATA rent when the recipient token account exists', async () => {
+    accounts.set(recipientAta.toBase58(), { data: Buffer.alloc(ACCOUNT_SIZE) });
+
+    const quote = await service.quote(request());
+    expect(quote.ata_creation_rent).toBeNull();
+  });
+
+  it('should reflect a vault fee change when re-quoted', async () => {
+    const before = await service.quote(request());
+    accounts.set(vaultConfig.toBase58(), { data: vaultConfigData(100) });
+    const after = await service.quote(request());
+
+    expect(before.protocol_fee.amount).toBe(0.5);
+    expect(after.fee_basis_points).toBe(100);
+    expect(after.protocol_fee.amount).toBe(1);
+    expect(after.total.amount!).toBeGreaterThan(before.total.amount!);
+  });
+
+  it('should round the protocol fee down in base units as the program does', async () => {
+    // 50 bps of 1.999999 USDC is 9999.995 base units
+    const quote = await service.quote({ ...request(), amount: 1.999999 });
+    expect(quote.protocol_fee.amount).toBe(0.009999);
+  });
+
+  it('should fall back to FEE_BASIS_POINTS before the vault is initialized', async () => {
+    accounts.delete(vaultConfig.toBase58());
+    vi.stubEnv('FEE_BASIS_POINTS', '25');
+
+    const quote = await service.quote(request());
+    expect(quote.fee_basis_points).toBe(25);
+    vi.unstubAllEnvs();
+  });
+
+  it('should disclose lamports only when no price is available', async () => {
+    price = null;
+
+    const quote = await service.quote(request());
+    expect(quote.sol_price).toBeNull();
+    expect(quote.network_fee).toEqual({ lamports: 5030, amount: null });
+    expect(quote.protocol_fee).toEqual({ lamports: null, amount: 0.5 });
+    expect(quote.total).toEqual({ lamports: null, amount: null });
+  });
+
+  it('should not charge ATA rent for SOL payments', async () => {
+    price = 1;
+
+    const quote = await service.quote({ ...request(), amount: 2, currency: 'SOL' });
+    expect(quote.ata_creation_rent).toBeNull();
+    expect(quote.protocol_fee).toEqual({ lamports: 10_000_000, amount: 0.01 });
+  });
+
+  it('should simulate the memo instruction after the transfer', async () => {
+    await service.quote({ ...request(), memo: 'deposit-tag 104729' });
+
+    const [tx] = connection.simulateTransaction.mock.calls[0];
+    const programs = tx.message.compiledInstructions.map(
+      (ix: { programIdIndex: number }) => tx.message.staticAccountKeys[ix.programIdIndex]
+    );
+    expect(programs[programs.length - 1].equals(MEMO_PROGRAM_ID)).toBe(true);
+  });
+
+  it('should fall back to the default compute budget if simulation fails', async () => {
+    connection.simulateTransaction.mockRejectedValue(new Error('rpc down'));
+
+    const quote = await service.quote(request());
+    expect(quote.network_fee.lamports).toBe(5000 + 200);
+  });
+});
@@ -0,0 +1,14 @@
+import { describe, it, expect } from 'vitest';
+import { readFileSync } from 'fs';
+import { protocolFee } from '../src/utils/fee.js';
+
+// Shared with ninjapay-types, so the quoted fee can't drift from the audited one
+const { vectors }: { vectors: { amount: string; fee_basis_points: number; fee: string }[] } = JSON.parse(
+  readFileSync(new URL('../../../packages/ninjapay-types/tests/fixtures/fee_vectors.json', import.meta.url), 'utf8')
+);
+
+describe('Protocol fee', () => {
+  it.each(vectors)('should charge $fee on $amount at $fee_basis_points bps', (v) => {
+    expect(protocolFee(BigInt(v.amount), v.fee_basis_points)).toBe(BigInt(v.fee));
+  });
+});
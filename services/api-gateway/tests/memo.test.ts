@@ -0,0 +1,41 @@
+import { describe, it, expect } from 'vitest';
+import { Keypair } from '@solana/web3.js';
+import { createMemoInstruction, MEMO_PROGRAM_ID, memoSchema } from '../src/utils/memo.js';
+
+describe('Payment memos', () => {
+  it('should accept printable UTF-8 up to 256 bytes', () => {
+    expect(memoSchema.safeParse('deposit-tag 104729').success).toBe(true);
+    expect(memoSchema.safeParse('a'.repeat(256)).success).toBe(true);
+    // 64 four-byte characters
+    expect(memoSchema.safeParse('🥷'.repeat(64)).success).toBe(true);
+  });
+
+  it('should measure the limit in bytes, not characters', () => {
+    expect(memoSchema.safeParse('a'.repeat(257)).success).toBe(false);
+    expect(memoSchema.safeParse('é'.repeat(129)).success).toBe(false);
+  });
+
+  it('should reject control characters', () => {
+    for (const memo of ['line\nbreak', 'tab\there', 'nul\u0000', 'del\u007f', 'c1\u0085']) {
+      expect(memoSchema.safeParse(memo).success).toBe(false);
+    }
+  });
+
+  it('should reject strings with no UTF-8 encoding', () => {
+    expect(memoSchema.safeParse('lone \ud800 surrogate').success).toBe(false);
+    expect(memoSchema.safeParse('\udc00').success).toBe(false);
+  });
+
+  it('should reject empty memos', () => {
+    expect(memoSchema.safeParse('').success).toBe(false);
+  });
+
+  it('should build a memo instruction signed by the payer', () => {
+    const payer = Keypair.generate().publicKey;
+    const ix = createMemoInstruction('deposit-tag 104729', payer);
+
+    expect(ix.programId.equals(MEMO_PROGRAM_ID)).toBe(true);
+    expect(ix.keys).toEqual([{ pubkey: payer, isSigner: true, isWritable: false }]);
+    expect(ix.data.toString('utf8')).toBe('deposit-tag 104729');
+  });
+});
@@ -0,0 +1,164 @@
+import { describe, it, expect, vi } from 'vitest';
+import crypto from 'crypto';
+import { PublicKey } from '@solana/web3.js';
+import { mockPrisma } from './setup';
+import {
+  approveMerchant,
+  challengeMessage,
+  issueChallenge,
+  selfRegister,
+  verifyWalletSignature,
+} from '../src/services/merchant-onboarding.js';
+
+vi.mock('bcrypt', () => ({
+  default: { hash: vi.fn().mockResolvedValue('$2b$12$hashed') },
+}));
+
+const ED25519_ORDER = (1n << 252n) + 27742317777372353535851937790883648493n;
+const NOW = new Date('2026-10-16T12:00:00.000Z');
+const CHALLENGE = 'ab'.repeat(32);
+
+function wallet() {
+  const { publicKey, privateKey } = crypto.generateKeyPairSync('ed25519');
+  const raw = Buffer.from(publicKey.export({ format: 'jwk' }).x!, 'base64url');
+  return {
+    address: new PublicKey(raw).toBase58(),
+    sign: (message: string) => crypto.sign(null, Buffer.from(message), privateKey),
+  };
+}
+
+function challengeRecord(address: string, overrides: Record<string, any> = {}) {
+  const expiresAt = new Date(NOW.getTime() + 60_000);
+  return {
+    id: 'challenge_1',
+    wallet: address,
+    challenge: CHALLENGE,
+    message: challengeMessage(address, CHALLENGE, expiresAt),
+    expiresAt,
+    usedAt: null,
+    ...overrides,
+  };
+}
+
+function registration(address: string, signature: Buffer) {
+  return { wallet: address, challenge: CHALLENGE, signature, businessName: 'Acme', email: 'ops@acme.test' };
+}
+
+/** Same signature with S + L: valid to a verifier that skips the S < L check */
+function malleate(signature: Buffer): Buffer {
+  const s = BigInt(`0x${Buffer.from(signature.subarray(32)).reverse().toString('hex')}`) + ED25519_ORDER;
+  const sBytes = Buffer.from(s.toString(16).padStart(64, '0'), 'hex').reverse();
+  return Buffer.concat([signature.subarray(0, 32), sBytes]);
+}
+
+describe('Wallet signatures', () => {
+  it('should verify a signature by the wallet over the message', () => {
+    const w = wallet();
+    expect(verifyWalletSignature(w.address, 'hello', w.sign('hello'))).toBe(true);
+    expect(verifyWalletSignature(w.address, 'hello!', w.sign('hello'))).toBe(false);
+    expect(verifyWalletSignature(wallet().address, 'hello', w.sign('hello'))).toBe(false);
+  });
+
+  it('should reject non-canonical and truncated signatures', () => {
+    const w = wallet();
+    const signature = w.sign('hello');
+
+    expect(verifyWalletSignature(w.address, 'hello', malleate(signature))).toBe(false);
+    expect(verifyWalletSignature(w.address, 'hello', signature.subarray(0, 63))).toBe(false);
+  });
+});
+
+describe('Merchant self-registration', () => {
+  it('should issue a short-lived challenge naming the wallet', async () => {
+    const w = wallet();
+    const issued = await issueChallenge(w.address, NOW);
+
+    expect(issued.message).toContain(`Wallet: ${w.address}`);
+    expect(issued.message).toContain(issued.challenge);
+    expect(issued.expires_at.getTime() - NOW.getTime()).toBe(5 * 60 * 1000);
+    expect(mockPrisma.onboardingChallenge.create.mock.calls[0][0].data.message).toBe(issued.message);
+  });
+
+  it('should create a pending merchant with a test-mode key', async () => {
+    const w = wallet();
+    const record = challengeRecord(w.address);
+    mockPrisma.onboardingChallenge.findUnique.mockResolvedValue(record);
+    mockPrisma.onboardingChallenge.updateMany.mockResolvedValue({ count: 1 });
+    mockPrisma.merchant.findFirst.mockResolvedValue(null);
+    mockPrisma.merchant.create.mockImplementation(({ data }: any) => ({ id: 'merchant_1', ...data }));
+    mockPrisma.apiKey.create.mockImplementation(({ data }: any) => ({ id: 'key_1', ...data }));
+
+    const { merchant, apiKey, rawKey } = await selfRegister(registration(w.address, w.sign(record.message)), NOW);
+
+    expect(merchant.status).toBe('PENDING');
+    expect(apiKey.livemode).toBe(false);
+    expect(rawKey).toMatch(/^sk_test_/);
+    expect(mockPrisma.onboardingChallenge.updateMany).toHaveBeenCalledWith({
+      where: { id: 'challenge_1', usedAt: null, expiresAt: { gt: NOW } },
+      data: { usedAt: NOW },
+    });
+  });
+
+  it('should refuse to replay a used challenge', async () => {
+    const w = wallet();
+    const record = challengeRecord(w.address);
+    const signature = w.sign(record.message);
+
+    mockPrisma.onboardingChallenge.findUnique.mockResolvedValue({ ...record, usedAt: NOW });
+    await expect(selfRegister(registration(w.address, signature), NOW)).rejects.toMatchObject({
+      code: 'INVALID_CHALLENGE',
+    });
+
+    // Two requests racing on the same challenge: only the compare-and-swap winner registers
+    mockPrisma.onboardingChallenge.findUnique.mockResolvedValue(record);
+    mockPrisma.onboardingChallenge.updateMany.mockResolvedValue({ count: 0 });
+    await expect(selfRegister(registration(w.address, signature), NOW)).rejects.toMatchObject({
+      code: 'INVALID_CHALLENGE',
+    });
+    expect(mockPrisma.merchant.create).not.toHaveBeenCalled();
+  });
+
+  it('should refuse expired challenges and challenges issued to another wallet', async () => {
+    const w = wallet();
+    const record = challengeRecord(w.address, { expiresAt: NOW });
+    mockPrisma.onboardingChallenge.findUnique.mockResolvedValue(record);
+    await expect(selfRegister(registration(w.address, w.sign(record.message)), NOW)).rejects.toMatchObject({
+      code: 'INVALID_CHALLENGE',
+    });
+
+    const other = wallet();
+    mockPrisma.onboardingChallenge.findUnique.mockResolvedValue(challengeRecord(w.address));
+    await expect(
+      selfRegister(registration(other.address, other.sign(record.message)), NOW)
+    ).rejects.toMatchObject({ code: 'INVALID_CHALLENGE' });
+  });
+
+  it('should not consume the challenge for a malleated signature', async () => {
+    const w = wallet();
+    const record = challengeRecord(w.address);
+    mockPrisma.onboardingChallenge.findUnique.mockResolvedValue(record);
+
+    await expect(
+      selfRegister(registration(w.address, malleate(w.sign(record.message))), NOW)
+    ).rejects.toMatchObject({ code: 'INVALID_SIGNATURE' });
+    expect(mockPrisma.onboardingChallenge.updateMany).not.toHaveBeenCalled();
+  });
+});
+
+describe('Merchant approval', () => {
+  it('should activate a pending merchant and audit it', async () => {
+    mockPrisma.merchant.updateMany.mockResolvedValue({ count: 1 });
+    mockPrisma.merchant.findUnique.mockResolvedValue({ id: 'merchant_1', status: 'ACTIVE' });
+
+    await approveMerchant('merchant_1', 'ops');
+    expect(mockPrisma.auditEvent.create.mock.calls[0][0].data.action).toBe('merchant.approve');
+  });
+
+  it('should refuse merchants that are not pending', async () => {
+    mockPrisma.merchant.updateMany.mockResolvedValue({ count: 0 });
+    mockPrisma.merchant.findUnique.mockResolvedValue({ id: 'merchant_1', status: 'ACTIVE' });
+
+    await expect(approveMerchant('merchant_1', 'ops')).rejects.toMatchObject({ code: 'MERCHANT_NOT_PENDING' });
+    expect(mockPrisma.auditEvent.create).not.toHaveBeenCalled();
+  });
+});
@@ -0,0 +1,122 @@
+import { describe, it, expect } from 'vitest';
+import { mockPrisma } from './setup';
+import {
+  consumePaymentId,
+  releaseExpiredReservations,
+  reservePaymentId,
+  serializeReservation,
+} from '../src/services/payment-id-reservations.js';
+
+function reservation(overrides = {}) {
+  return {
+    id: 'res_1',
+    paymentId: 'ab'.repeat(32),
+    merchantId: 'merchant_test123',
+    orderRef: 'order-42',
+    livemode: true,
+    status: 'RESERVED',
+    paymentIntentId: null,
+    txSignature: null,
+    expiresAt: new Date(Date.now() + 60_000),
+    consumedAt: null,
+    releasedAt: null,
+    createdAt: new Date(),
+    ...overrides,
+  };
+}
+
+const reserve = () =>
+  reservePaymentId({ merchantId: 'merchant_test123', livemode: true, orderRef: 'order-42', ttlSeconds: 900 });
+
+const consume = (overrides = {}) =>
+  consumePaymentId({ merchantId: 'merchant_test123', livemode: true, paymentId: 'ab'.repeat(32), ...overrides });
+
+describe('Payment id reservations', () => {
+  it('should issue a 32-byte id for a new order', async () => {
+    mockPrisma.paymentIdReservation.findUnique.mockResolvedValue(null);
+    mockPrisma.paymentIdReservation.create.mockImplementation(async ({ data }: any) => reservation(data));
+
+    const { reservation: created, created: isNew } = await reserve();
+    expect(isNew).toBe(true);
+    expect(created.paymentId).toMatch(/^[0-9a-f]{64}$/);
+    expect(mockPrisma.paymentIdReservation.create.mock.calls[0][0].data.expiresAt.getTime()).toBeGreaterThan(
+      Date.now() + 899_000
+    );
+  });
+
+  it('should return the active reservation on repeated calls', async () => {
+    mockPrisma.paymentIdReservation.findUnique.mockResolvedValue(reservation());
+
+    const { reservation: existing, created } = await reserve();
+    expect(created).toBe(false);
+    expect(existing.paymentId).toBe('ab'.repeat(32));
+    expect(mockPrisma.paymentIdReservation.create).not.toHaveBeenCalled();
+  });
+
+  it("should return the winner's id when a concurrent reserve creates it first", async () => {
+    mockPrisma.paymentIdReservation.findUnique
+      .mockResolvedValueOnce(null)
+      .mockResolvedValueOnce(reservation({ paymentId: 'cd'.repeat(32) }));
+    mockPrisma.paymentIdReservation.create.mockRejectedValue(Object.assign(new Error('unique'), { code: 'P2002' }));
+
+    const { reservation: winner, created } = await reserve();
+    expect(created).toBe(false);
+    expect(winner.paymentId).toBe('cd'.repeat(32));
+  });
+
+  it('should issue a new id once the reservation has expired', async () => {
+    const expired = reservation({ expiresAt: new Date(Date.now() - 1000) });
+    mockPrisma.paymentIdReservation.findUnique
+      .mockResolvedValueOnce(expired)
+      .mockResolvedValueOnce(reservation({ paymentId: 'ef'.repeat(32) }));
+    mockPrisma.paymentIdReservation.updateMany.mockResolvedValue({ count: 1 });
+
+    const { created } = await reserve();
+    expect(created).toBe(true);
+
+    // Only replaces the id if nobody else changed the reservation meanwhile
+    const { where, data } = mockPrisma.paymentIdReservation.updateMany.mock.calls[0][0];
+    expect(where).toEqual({ id: 'res_1', paymentId: expired.paymentId, status: 'RESERVED' });
+    expect(data.paymentId).not.toBe(expired.paymentId);
+  });
+
+  it('should consume a reservation once and then be idempotent', async () => {
+    mockPrisma.paymentIdReservation.updateMany.mockResolvedValueOnce({ count: 1 });
+    mockPrisma.paymentIdReservation.findFirst.mockResolvedValue(
+      reservation({ status: 'CONSUMED', paymentIntentId: 'pi_1' })
+    );
+    expect((await consume({ paymentIntentId: 'pi_1' })).status).toBe('CONSUMED');
+
+    mockPrisma.paymentIdReservation.updateMany.mockResolvedValueOnce({ count: 0 });
+    expect((await consume({ paymentIntentId: 'pi_1' })).status).toBe('CONSUMED');
+
+    mockPrisma.paymentIdReservation.updateMany.mockResolvedValueOnce({ count: 0 });
+    await expect(consume({ paymentIntentId: 'pi_2' })).rejects.toMatchObject({
+      code: 'RESERVATION_ALREADY_CONSUMED',
+    });
+  });
+
+  it('should refuse to consume a released reservation', async () => {
+    mockPrisma.paymentIdReservation.updateMany.mockResolvedValue({ count: 0 });
+    mockPrisma.paymentIdReservation.findFirst.mockResolvedValue(reservation({ status: 'RELEASED' }));
+
+    await expect(consume()).rejects.toMatchObject({ statusCode: 410, code: 'RESERVATION_RELEASED' });
+  });
+
+  it('should release only expired unconsumed reservations', async () => {
+    mockPrisma.paymentIdReservation.updateMany.mockResolvedValue({ count: 3 });
+    const now = new Date();
+
+    expect(await releaseExpiredReservations(now)).toBe(3);
+    expect(mockPrisma.paymentIdReservation.updateMany).toHaveBeenCalledWith({
+      where: { status: 'RESERVED', expiresAt: { lte: now } },
+      data: { status: 'RELEASED', releasedAt: now },
+    });
+  });
+
+  it('should report expired reservations that are not yet released', () => {
+    const expired = reservation({ expiresAt: new Date(Date.now() - 1000) });
+    expect(serializeReservation(expired).status).toBe('expired');
+    expect(serializeReservation(reservation()).status).toBe('reserved');
+  });
+});
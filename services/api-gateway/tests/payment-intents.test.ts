@@ -175,22 +175,28 @@ describe('Payment Intents API', () => {
 describe('Payment Intent Status Transitions', () => {
   const validTransitions: Record<string, string[]> = {
     PENDING: ['PROCESSING', 'CANCELLED'],
-    PROCESSING: ['CONFIRMED', 'FAILED'],
-    CONFIRMED: ['FINALIZED', 'FAILED'],
-    FINALIZED: [],
+    PROCESSING: ['CONFIRMED', 'FAILED', 'REVERTED'],
+    CONFIRMED: ['FINALIZED', 'FAILED', 'REVERTED'],
+    // Settlement is reported before the chain finalizes, so a reorg can still roll it back
+    FINALIZED: ['REVERTED'],
     FAILED: [],
     CANCELLED: [],
+    REVERTED: [],
   };
 
   it('should define valid status transitions', () => {
     expect(validTransitions.PENDING).toContain('PROCESSING');
     expect(validTransitions.PENDING).toContain('CANCELLED');
     expect(validTransitions.PROCESSING).toContain('CONFIRMED');
-    expect(validTransitions.FINALIZED).toHaveLength(0);
+    expect(validTransitions.FINALIZED).toEqual(['REVERTED']);
   });
 
-  it('should not allow transition from FINALIZED', () => {
-    expect(validTransitions.FINALIZED).toHaveLength(0);
+  it('should only allow a reorg to revert a FINALIZED intent', () => {
+    expect(validTransitions.FINALIZED).toEqual(['REVERTED']);
+  });
+
+  it('should not allow transition from REVERTED', () => {
+    expect(validTransitions.REVERTED).toHaveLength(0);
   });
 
   it('should not allow transition from CANCELLED', () => {
@@ -0,0 +1,124 @@
+import crypto from 'crypto';
+import { describe, it, expect, vi } from 'vitest';
+import { mockPrisma, createMockPaymentIntent } from './setup';
+
+const { broadcastEvent } = vi.hoisted(() => ({ broadcastEvent: vi.fn() }));
+vi.mock('../src/services/webhook-delivery.js', () => ({
+  getWebhookDeliveryService: () => ({ broadcastEvent }),
+}));
+
+import {
+  revertPayment,
+  verifyReorgSignature,
+  SIGNATURE_TOLERANCE_SECONDS,
+} from '../src/services/payment-reorg.js';
+
+const SECRET = 'a'.repeat(32);
+
+// As delivered by arcium-service's reorg watcher
+const reverted = {
+  record: 'Rec1111111111111111111111111111111111111111',
+  payment_id: 'ab'.repeat(32),
+  merchant: '7xKXtg2CW8ukAp9rXKD2RQU3w5RJKPME6nXbvNfTQAaP',
+  amount: 2_000_000,
+  fee: 10_000,
+  day: '2026-10-15',
+  indexed_slot: 150,
+  detected_at: 1_792_000_000,
+};
+
+function sign(body: string, timestamp: number, secret = SECRET): string {
+  const v1 = crypto.createHmac('sha256', secret).update(`${timestamp}.${body}`).digest('hex');
+  return `t=${timestamp},v1=${v1}`;
+}
+
+function withIntent(status: string) {
+  mockPrisma.paymentIdReservation.findUnique.mockResolvedValue({
+    paymentId: reverted.payment_id,
+    paymentIntentId: 'pi_test123',
+  });
+  mockPrisma.paymentIntent.findUnique.mockResolvedValue(
+    createMockPaymentIntent({ status, txSignature: 'sig123' })
+  );
+}
+
+describe('Reorg callback signature', () => {
+  const body = JSON.stringify({ event: 'payment.reverted', payment: reverted });
+  const now = 1_792_000_010;
+
+  it('should accept the arcium-service signature', () => {
+    expect(verifyReorgSignature(SECRET, sign(body, now), body, now)).toBe(true);
+  });
+
+  it('should reject a tampered body, wrong secret or stale timestamp', () => {
+    expect(verifyReorgSignature(SECRET, sign(body, now), body.replace('2000000', '1'), now)).toBe(false);
+    expect(verifyReorgSignature(SECRET, sign(body, now, 'b'.repeat(32)), body, now)).toBe(false);
+    const stale = now - SIGNATURE_TOLERANCE_SECONDS - 1;
+    expect(verifyReorgSignature(SECRET, sign(body, stale), body, now)).toBe(false);
+    expect(verifyReorgSignature(SECRET, 'v1=deadbeef', body, now)).toBe(false);
+    expect(verifyReorgSignature('', sign(body, now, ''), body, now)).toBe(false);
+  });
+});
+
+describe('Reverting payments', () => {
+  it('should move a confirmed intent to REVERTED and notify subscriptions', async () => {
+    withIntent('CONFIRMED');
+    mockPrisma.paymentIntent.updateMany.mockResolvedValue({ count: 1 });
+
+    const outcome = await revertPayment(reverted);
+
+    expect(outcome).toEqual({ status: 'reverted', paymentIntentId: 'pi_test123' });
+    expect(mockPrisma.paymentIntent.updateMany).toHaveBeenCalledWith(
+      expect.objectContaining({
+        where: { id: 'pi_test123', status: 'CONFIRMED' },
+        data: expect.objectContaining({ status: 'REVERTED' }),
+      })
+    );
+    expect(broadcastEvent).toHaveBeenCalledWith(
+      'merchant_test123',
+      true,
+      'payment.reverted',
+      expect.objectContaining({
+        payment_intent_id: 'pi_test123',
+        status: 'reverted',
+        previous_status: 'confirmed',
+        payment_record: reverted.record,
+      })
+    );
+  });
+
+  it('should revert a FINALIZED intent: settlement is reported before the chain finalizes', async () => {
+    withIntent('FINALIZED');
+    mockPrisma.paymentIntent.updateMany.mockResolvedValue({ count: 1 });
+
+    expect((await revertPayment(reverted)).status).toBe('reverted');
+  });
+
+  it('should be idempotent for redelivered events', async () => {
+    withIntent('REVERTED');
+    expect(await revertPayment(reverted)).toEqual({ status: 'already_reverted', paymentIntentId: 'pi_test123' });
+
+    // Lost the compare-and-swap to a concurrent delivery
+    withIntent('CONFIRMED');
+    mockPrisma.paymentIntent.updateMany.mockResolvedValue({ count: 0 });
+    expect((await revertPayment(reverted)).status).toBe('already_reverted');
+
+    expect(broadcastEvent).not.toHaveBeenCalled();
+  });
+
+  it('should leave intents that never reached the chain alone', async () => {
+    withIntent('CANCELLED');
+    expect(await revertPayment(reverted)).toEqual({
+      status: 'not_revertible',
+      paymentIntentId: 'pi_test123',
+      intentStatus: 'CANCELLED',
+    });
+    expect(mockPrisma.paymentIntent.updateMany).not.toHaveBeenCalled();
+  });
+
+  it('should ignore payment ids without a payment intent', async () => {
+    mockPrisma.paymentIdReservation.findUnique.mockResolvedValue(null);
+    expect(await revertPayment(reverted)).toEqual({ status: 'unknown_payment' });
+    expect(broadcastEvent).not.toHaveBeenCalled();
+  });
+});
@@ -0,0 +1,122 @@
+import { describe, it, expect } from 'vitest';
+import { mockPrisma } from './setup';
+import {
+  budgetViolations,
+  enforcePayrollBudget,
+  hashOverrideToken,
+  issueBudgetOverride,
+  startOfUtcDay,
+} from '../src/services/payroll-budget.js';
+import { renderMetrics, resetMetrics } from '../src/services/metrics.js';
+
+const NOW = new Date('2026-10-16T15:30:00Z');
+
+const batch = (overrides = {}) => ({
+  id: 'batch_1',
+  companyId: 'company_1',
+  employeeCount: 12,
+  totalAmount: 50_000,
+  ...overrides,
+});
+
+const limits = (overrides = {}) => ({
+  maxBatchTotal: 20_000,
+  maxDailyTotal: null,
+  maxBatchEmployees: 50,
+  ...overrides,
+});
+
+describe('Payroll budget', () => {
+  it('should report every limit a batch breaks', () => {
+    expect(budgetViolations(limits(), { totalAmount: 10_000, employeeCount: 12 }, 0)).toEqual([]);
+    expect(
+      budgetViolations(
+        limits({ maxDailyTotal: 60_000, maxBatchEmployees: 10 }),
+        { totalAmount: 50_000, employeeCount: 12 },
+        15_000
+      ).map((v) => [v.limit, v.actual])
+    ).toEqual([
+      ['max_batch_total', 50_000],
+      ['max_daily_total', 65_000],
+      ['max_batch_employees', 12],
+    ]);
+  });
+
+  it('should count the day from midnight UTC', () => {
+    expect(startOfUtcDay(NOW).toISOString()).toBe('2026-10-16T00:00:00.000Z');
+  });
+
+  it('should sum batches queued today for the daily limit', async () => {
+    mockPrisma.company.findUnique.mockResolvedValue(limits({ maxBatchTotal: null, maxDailyTotal: 100_000 }));
+    mockPrisma.payrollBatch.findMany.mockResolvedValue([{ totalAmount: 30_000 }, { totalAmount: 25_000 }]);
+
+    await expect(enforcePayrollBudget(batch(), undefined, NOW)).rejects.toMatchObject({
+      statusCode: 422,
+      code: 'BUDGET_EXCEEDED',
+    });
+    expect(mockPrisma.payrollBatch.findMany.mock.calls[0][0].where.executedDate.gte).toEqual(startOfUtcDay(NOW));
+  });
+
+  it('should refuse an over-limit batch and record it', async () => {
+    resetMetrics();
+    mockPrisma.company.findUnique.mockResolvedValue(limits());
+
+    await expect(enforcePayrollBudget(batch(), undefined, NOW)).rejects.toMatchObject({ code: 'BUDGET_EXCEEDED' });
+    expect(mockPrisma.auditEvent.create.mock.calls[0][0].data.action).toBe('budget.exceeded');
+    expect(mockPrisma.budgetOverride.updateMany).not.toHaveBeenCalled();
+    expect(renderMetrics()).toContain('ninjapay_payroll_budget_exceeded_total{limit="max_batch_total"} 1');
+  });
+
+  it('should let a batch within its limits through without touching the override', async () => {
+    mockPrisma.company.findUnique.mockResolvedValue(limits());
+
+    await enforcePayrollBudget(batch({ totalAmount: 5_000 }), 'bo_unused', NOW);
+    expect(mockPrisma.budgetOverride.updateMany).not.toHaveBeenCalled();
+    expect(mockPrisma.auditEvent.create).not.toHaveBeenCalled();
+  });
+
+  it('should consume a valid override for this batch once', async () => {
+    resetMetrics();
+    mockPrisma.company.findUnique.mockResolvedValue(limits());
+    mockPrisma.budgetOverride.updateMany.mockResolvedValueOnce({ count: 1 }).mockResolvedValueOnce({ count: 0 });
+
+    await enforcePayrollBudget(batch(), 'bo_token', NOW);
+    const where = mockPrisma.budgetOverride.updateMany.mock.calls[0][0].where;
+    expect(where).toMatchObject({
+      tokenHash: hashOverrideToken('bo_token'),
+      companyId: 'company_1',
+      batchId: 'batch_1',
+      usedAt: null,
+      expiresAt: { gt: NOW },
+    });
+    expect(mockPrisma.auditEvent.create.mock.calls[0][0].data.action).toBe('budget.override_used');
+
+    // Replaying the same token fails the compare-and-swap
+    await expect(enforcePayrollBudget(batch(), 'bo_token', NOW)).rejects.toMatchObject({ code: 'BUDGET_EXCEEDED' });
+    expect(renderMetrics()).toContain('ninjapay_payroll_budget_overrides_total{outcome="used"} 1');
+    expect(renderMetrics()).toContain('ninjapay_payroll_budget_overrides_total{outcome="rejected"} 1');
+  });
+
+  it('should only issue overrides for a pending batch of the company', async () => {
+    mockPrisma.payrollBatch.findFirst.mockResolvedValueOnce(null);
+    await expect(
+      issueBudgetOverride({ companyId: 'company_1', batchId: 'batch_x', reason: 'bonus', ttlSeconds: 60, actor: 'ops' })
+    ).rejects.toMatchObject({ code: 'BATCH_NOT_FOUND' });
+
+    mockPrisma.payrollBatch.findFirst.mockResolvedValueOnce({ id: 'batch_1', status: 'PENDING' });
+    mockPrisma.budgetOverride.create.mockImplementation(async ({ data }: any) => ({ id: 'bo_1', ...data }));
+    const { override, token } = await issueBudgetOverride({
+      companyId: 'company_1',
+      batchId: 'batch_1',
+      reason: 'year-end bonus',
+      ttlSeconds: 60,
+      actor: 'ops',
+    });
+    expect(token).toMatch(/^bo_[0-9a-f]{48}$/);
+    expect(override.tokenHash).toBe(hashOverrideToken(token));
+    expect(mockPrisma.auditEvent.create.mock.calls[0][0].data).toMatchObject({
+      action: 'budget.override_issued',
+      actor: 'ops',
+    });
+  });
+});
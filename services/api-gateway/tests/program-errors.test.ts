@@ -0,0 +1,48 @@
+import { describe, it, expect } from 'vitest';
+import { readFileSync } from 'fs';
+import {
+  decodeProgramError,
+  decodeSimulationError,
+  describeTransactionError,
+  VAULT_ERRORS,
+} from '../src/utils/program-errors.js';
+
+// The vault's #[error_code] enum, which arcium-service's build.rs also reads
+const programSource = readFileSync(
+  new URL('../../../programs/ninjapay-vault/src/lib.rs', import.meta.url),
+  'utf8'
+);
+
+function programErrors(): [string, string][] {
+  const body = /pub enum VaultError \{([^}]*)\}/.exec(programSource)![1];
+  return [...body.matchAll(/#\[msg\("([^"]*)"\)\]\s*(\w+)/g)].map((m) => [m[2], m[1]]);
+}
+
+describe('Program errors', () => {
+  it('should match the vault program source', () => {
+    expect(VAULT_ERRORS.map(([name, message]) => [name, message])).toEqual(programErrors());
+  });
+
+  it('should decode vault, Anchor and token errors from RPC messages', () => {
+    expect(decodeProgramError('Transaction simulation failed: custom program error: 0x1771')).toMatchObject({
+      code: 6001,
+      program: 'vault',
+      name: 'InvalidAmount',
+    });
+    expect(decodeProgramError(['Program log: AnchorError', 'failed: custom program error: 0x7d6'])?.name).toBe(
+      'ConstraintSeeds'
+    );
+    expect(decodeSimulationError({ InstructionError: [1, { Custom: 1 }] })?.name).toBe('InsufficientFunds');
+    expect(decodeProgramError('blockhash not found')).toBeNull();
+    expect(decodeProgramError('custom program error: 0x1f40')).toBeNull();
+  });
+
+  it('should describe failed transactions with a remediation', () => {
+    const error = new Error('Error processing Instruction 1: custom program error: 0x1770');
+    expect(describeTransactionError(error)).toBe(
+      'FeeTooHigh (custom program error 0x1770): Fee exceeds maximum allowed (10%). ' +
+        'Fees are capped at 1000 basis points (10%); submit a lower fee.'
+    );
+    expect(describeTransactionError(new Error('blockhash not found'))).toBe('blockhash not found');
+  });
+});
@@ -0,0 +1,80 @@
+import { describe, it, expect, vi } from 'vitest';
+import { ReadRouter } from '../src/services/read-routing.js';
+
+const NOW = 1_700_000_000_000;
+
+function pools() {
+  const primary: any = { name: 'primary' };
+  const replica: any = { name: 'replica', $queryRaw: vi.fn().mockResolvedValue([{ lag: 1.5 }]) };
+  return { primary, replica };
+}
+
+function request(overrides: Record<string, any> = {}): any {
+  return { headers: {}, merchantId: 'merchant_1', ...overrides };
+}
+
+const which = async (db: any) => db.name;
+
+describe('Read routing', () => {
+  it('should send reads to the replica and count queries per pool', async () => {
+    const { primary, replica } = pools();
+    const router = new ReadRouter(primary, replica);
+
+    expect(await router.read(request(), which, NOW)).toBe('replica');
+    expect(await router.read(request({ headers: { 'x-read-consistency': 'strong' } }), which, NOW)).toBe('primary');
+
+    expect(await router.stats(NOW)).toEqual({
+      replica_configured: true,
+      replica_available: true,
+      queries: { primary: 1, replica: 1 },
+      fallbacks: 0,
+      replica_lag_seconds: 1.5,
+    });
+  });
+
+  it('should read from the primary without a replica', async () => {
+    const { primary } = pools();
+    const router = new ReadRouter(primary, null);
+
+    expect(await router.read(request(), which, NOW)).toBe('primary');
+    expect((await router.stats(NOW)).replica_lag_seconds).toBeNull();
+  });
+
+  it('should read from the primary shortly after the same merchant writes', async () => {
+    const { primary, replica } = pools();
+    const router = new ReadRouter(primary, replica, { readAfterWriteSeconds: 5 });
+
+    router.recordWrite(request(), NOW);
+    expect(await router.read(request(), which, NOW + 4_999)).toBe('primary');
+    expect(await router.read(request({ merchantId: 'merchant_2' }), which, NOW + 1)).toBe('replica');
+    expect(await router.read(request(), which, NOW + 5_000)).toBe('replica');
+  });
+
+  it('should fall back to the primary while the replica is unreachable', async () => {
+    const { primary, replica } = pools();
+    const router = new ReadRouter(primary, replica, { replicaRetrySeconds: 30 });
+    const unreachable = Object.assign(new Error("Can't reach database server"), { code: 'P1001' });
+    const query = vi.fn(async (db: any) => {
+      if (db === replica) throw unreachable;
+      return db.name;
+    });
+
+    expect(await router.read(request(), query, NOW)).toBe('primary');
+    expect(await router.read(request(), query, NOW + 29_000)).toBe('primary');
+    expect(query).toHaveBeenCalledTimes(3);
+    expect((await router.stats(NOW + 29_000)).replica_available).toBe(false);
+
+    expect(await router.read(request(), query, NOW + 30_000)).toBe('primary');
+    expect(query).toHaveBeenCalledTimes(5);
+    expect((await router.stats(NOW + 30_000)).fallbacks).toBe(2);
+  });
+
+  it('should not retry query errors on the primary', async () => {
+    const { primary, replica } = pools();
+    const router = new ReadRouter(primary, replica);
+    const query = vi.fn().mockRejectedValue(Object.assign(new Error('bad filter'), { code: 'P2009' }));
+
+    await expect(router.read(request(), query, NOW)).rejects.toThrow('bad filter');
+    expect(query).toHaveBeenCalledTimes(1);
+  });
+});
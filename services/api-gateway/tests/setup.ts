@@ -12,8 +12,17 @@ export const mockPrisma = {
   merchant: {
     findUnique: vi.fn(),
     findFirst: vi.fn(),
+    findMany: vi.fn(),
+    count: vi.fn(),
     create: vi.fn(),
     update: vi.fn(),
+    updateMany: vi.fn(),
+  },
+  onboardingChallenge: {
+    findUnique: vi.fn(),
+    create: vi.fn(),
+    updateMany: vi.fn(),
+    deleteMany: vi.fn(),
   },
   apiKey: {
     findFirst: vi.fn(),
@@ -28,6 +37,7 @@ export const mockPrisma = {
     findMany: vi.fn(),
     create: vi.fn(),
     update: vi.fn(),
+    updateMany: vi.fn(),
     count: vi.fn(),
   },
   paymentLink: {
@@ -44,12 +54,22 @@ export const mockPrisma = {
     findMany: vi.fn(),
     create: vi.fn(),
     update: vi.fn(),
+    updateMany: vi.fn(),
     delete: vi.fn(),
     count: vi.fn(),
   },
   webhookDelivery: {
+    findMany: vi.fn(),
     create: vi.fn(),
     update: vi.fn(),
+    count: vi.fn(),
+  },
+  webhookReplay: {
+    findUnique: vi.fn(),
+    findFirst: vi.fn(),
+    create: vi.fn(),
+    update: vi.fn(),
+    updateMany: vi.fn(),
   },
   company: {
     findUnique: vi.fn(),
@@ -74,12 +94,26 @@ export const mockPrisma = {
     create: vi.fn(),
     updateMany: vi.fn(),
   },
+  paymentIdReservation: {
+    findUnique: vi.fn(),
+    findFirst: vi.fn(),
+    create: vi.fn(),
+    updateMany: vi.fn(),
+  },
+  budgetOverride: {
+    create: vi.fn(),
+    updateMany: vi.fn(),
+  },
+  auditEvent: {
+    create: vi.fn(),
+  },
   $transaction: vi.fn((fn) => fn(mockPrisma)),
 };
 
 // Mock the database module
 vi.mock('@ninjapay/database', () => ({
   prisma: mockPrisma,
+  prismaRead: null,
 }));
 
 // Reset mocks before each test
@@ -94,6 +128,7 @@ export function createMockMerchant(overrides = {}) {
     walletAddress: '7xKXtg2CW8ukAp9rXKD2RQU3w5RJKPME6nXbvNfTQAaP',
     businessName: 'Test Merchant',
     email: 'test@merchant.com',
+    status: 'ACTIVE',
     createdAt: new Date(),
     updatedAt: new Date(),
     ...overrides,
@@ -106,8 +141,10 @@ export function createMockApiKey(overrides = {}) {
     merchantId: 'merchant_test123',
     keyHash: '$2b$12$test.hash.value',
     keyPrefix: 'sk_live_',
+    livemode: true,
     name: 'Test Key',
     permissions: ['read', 'write'],
+    allowedMerchants: [],
     active: true,
     lastUsedAt: null,
     expiresAt: null,
@@ -130,6 +167,7 @@ export function createMockPaymentIntent(overrides = {}) {
     computationId: null,
     computationStatus: 'QUEUED',
     metadata: {},
+    livemode: true,
     createdAt: new Date(),
     updatedAt: new Date(),
     ...overrides,
@@ -149,6 +187,7 @@ export function createMockPaymentLink(overrides = {}) {
     usageCount: 0,
     expiresAt: null,
     metadata: {},
+    livemode: true,
     createdAt: new Date(),
     updatedAt: new Date(),
     ...overrides,
@@ -163,6 +202,7 @@ export function createMockWebhook(overrides = {}) {
     events: ['payment_intent.confirmed'],
     secret: 'whsec_testsecret123',
     enabled: true,
+    livemode: true,
     createdAt: new Date(),
     updatedAt: new Date(),
     ...overrides,
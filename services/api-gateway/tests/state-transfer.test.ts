@@ -0,0 +1,165 @@
+import { describe, it, expect } from 'vitest';
+import { mockPrisma } from './setup';
+import {
+  applyImport,
+  openState,
+  planImport,
+  readState,
+  sealState,
+  StateSnapshot,
+} from '../src/services/state-transfer.js';
+
+const PASSPHRASE = 'correct horse battery staple';
+
+const merchant = {
+  id: 'merchant_1',
+  wallet_address: '7xKXtg2CW8ukAp9rXKD2RQU3w5RJKPME6nXbvNfTQAaP',
+  business_name: 'Acme',
+  email: 'ops@acme.test',
+  kyc_status: 'APPROVED' as const,
+  webhook_url: null,
+  settings: { theme: 'dark' },
+};
+
+const apiKey = {
+  id: 'key_1',
+  merchant_id: 'merchant_1',
+  key_prefix: 'sk_test_',
+  livemode: false,
+  name: 'CI',
+  permissions: ['read'],
+  allowed_merchants: [],
+  active: true,
+  expires_at: null,
+};
+
+const webhook = {
+  id: 'wh_1',
+  merchant_id: 'merchant_1',
+  url: 'https://acme.test/hooks',
+  events: ['payment_intent.confirmed'],
+  enabled: true,
+  format: 'ninjapay',
+  description: null,
+  livemode: false,
+};
+
+function snapshot(overrides: Partial<StateSnapshot> = {}): StateSnapshot {
+  return {
+    version: 1,
+    exported_at: '2026-10-16T12:00:00.000Z',
+    merchants: [merchant],
+    api_keys: [apiKey],
+    webhooks: [webhook],
+    ...overrides,
+  };
+}
+
+const empty = { merchants: [], api_keys: [], webhooks: [] };
+
+function thrownCode(fn: () => unknown): string {
+  try {
+    fn();
+  } catch (error: any) {
+    return error.code;
+  }
+  throw new Error('expected an error');
+}
+
+describe('State export', () => {
+  it('should select no secret columns', async () => {
+    mockPrisma.merchant.findMany.mockResolvedValue([]);
+    mockPrisma.apiKey.findMany.mockResolvedValue([]);
+    mockPrisma.webhook.findMany.mockResolvedValue([]);
+
+    await readState();
+
+    const selected = [mockPrisma.merchant, mockPrisma.apiKey, mockPrisma.webhook].flatMap((model) =>
+      Object.keys(model.findMany.mock.calls[0][0].select)
+    );
+    for (const secret of ['webhookSecret', 'keyHash', 'secret']) {
+      expect(selected).not.toContain(secret);
+    }
+  });
+
+  it('should round-trip through an encrypted archive', () => {
+    const archive = sealState(snapshot(), PASSPHRASE);
+
+    expect(archive.ciphertext).not.toContain('acme');
+    expect(archive.excluded).toContain('webhooks.secret');
+    expect(openState(archive, PASSPHRASE)).toEqual(snapshot());
+  });
+
+  it('should reject a wrong passphrase or a modified archive', () => {
+    const archive = sealState(snapshot(), PASSPHRASE);
+
+    expect(thrownCode(() => openState(archive, 'not the passphrase'))).toBe('INVALID_STATE_ARCHIVE');
+    expect(thrownCode(() => openState({ ...archive, excluded: [] }, PASSPHRASE))).toBe('INVALID_STATE_ARCHIVE');
+  });
+
+  it('should reject archives from another format version', () => {
+    const archive = sealState(snapshot(), PASSPHRASE);
+    expect(thrownCode(() => openState({ ...archive, version: 2 }, PASSPHRASE))).toBe('UNSUPPORTED_STATE_VERSION');
+  });
+});
+
+describe('State import', () => {
+  it('should diff by id and resolve conflicts as requested', () => {
+    const existing = {
+      merchants: [{ ...merchant, business_name: 'Acme Staging' }],
+      api_keys: [apiKey],
+      webhooks: [],
+    };
+
+    const skip = planImport(snapshot(), existing, 'skip');
+    expect(skip.merchants).toEqual({ create: [], overwrite: [], skip: ['merchant_1'], unchanged: [] });
+    expect(skip.api_keys.unchanged).toEqual(['key_1']);
+    expect(skip.webhooks.create).toEqual(['wh_1']);
+    expect(skip.errors).toEqual([]);
+
+    expect(planImport(snapshot(), existing, 'overwrite').merchants.overwrite).toEqual(['merchant_1']);
+  });
+
+  it('should block merchants whose wallet or email belongs to another merchant', () => {
+    const existing = { ...empty, merchants: [{ ...merchant, id: 'merchant_other' }] };
+    expect(planImport(snapshot(), existing, 'overwrite').errors).toHaveLength(1);
+  });
+
+  it('should block records whose merchant is missing', () => {
+    const plan = planImport(snapshot({ merchants: [] }), empty, 'skip');
+    expect(plan.errors).toHaveLength(2);
+  });
+
+  it('should create records with fresh secrets and audit the import', async () => {
+    mockPrisma.merchant.findMany.mockResolvedValue([]);
+    mockPrisma.apiKey.findMany.mockResolvedValue([]);
+    mockPrisma.webhook.findMany.mockResolvedValue([]);
+
+    const plan = await applyImport(snapshot(), 'skip', { actor: 'ops', archiveDigest: 'abc' });
+    expect(plan.merchants.create).toEqual(['merchant_1']);
+
+    const merchantData = mockPrisma.merchant.create.mock.calls[0][0].data;
+    expect(merchantData).not.toHaveProperty('webhookSecret');
+    expect(mockPrisma.apiKey.create.mock.calls[0][0].data.keyHash).toMatch(/^\$2[ab]\$04\$/);
+    expect(mockPrisma.webhook.create.mock.calls[0][0].data.secret).toMatch(/^whsec_/);
+
+    const audit = mockPrisma.auditEvent.create.mock.calls[0][0].data;
+    expect(audit.action).toBe('state.import');
+    expect(audit.details.archive_sha256).toBe('abc');
+    expect(audit.details.plan.webhooks.create).toBe(1);
+  });
+
+  it('should write nothing when the plan has conflicts', async () => {
+    mockPrisma.merchant.findMany.mockResolvedValue([
+      { id: 'merchant_other', walletAddress: merchant.wallet_address, email: 'x@y.test', kycStatus: 'PENDING', settings: {} },
+    ]);
+    mockPrisma.apiKey.findMany.mockResolvedValue([]);
+    mockPrisma.webhook.findMany.mockResolvedValue([]);
+
+    await expect(applyImport(snapshot(), 'skip', { actor: 'ops', archiveDigest: 'abc' })).rejects.toMatchObject({
+      code: 'STATE_IMPORT_CONFLICT',
+    });
+    expect(mockPrisma.merchant.create).not.toHaveBeenCalled();
+    expect(mockPrisma.auditEvent.create).not.toHaveBeenCalled();
+  });
+});
@@ -0,0 +1,131 @@
+import { describe, it, expect } from 'vitest';
+import { mockPrisma } from './setup';
+import {
+  failStaleReplays,
+  MAX_REPLAY_EVENTS,
+  replayWhere,
+  ReplayThrottle,
+  runReplay,
+  STALE_REPLAY_MS,
+  startWebhookReplay,
+} from '../src/services/webhook-replay.js';
+
+const webhook = { id: 'wh_1', url: 'https://merchant.test/hook', format: 'ninjapay', livemode: true, enabled: true };
+
+const replay = (overrides = {}) => ({
+  id: 'replay_1',
+  webhookId: 'wh_1',
+  fromTime: new Date('2026-10-01T00:00:00Z'),
+  toTime: new Date('2026-10-02T00:00:00Z'),
+  eventIds: [],
+  status: 'RUNNING',
+  enqueued: 0,
+  cursorCreatedAt: null,
+  cursorId: null,
+  webhook,
+  ...overrides,
+});
+
+const original = (id: string, createdAt: string) => ({
+  id: `del_${id}`,
+  createdAt: new Date(createdAt),
+  payload: { id: `evt_${id}`, type: 'payment_intent.confirmed', created: 1, livemode: true, data: {} },
+});
+
+describe('Webhook replay', () => {
+  it('should select original deliveries by time range or event id', () => {
+    expect(replayWhere(replay())).toEqual({
+      webhookId: 'wh_1',
+      replayId: null,
+      createdAt: { gte: replay().fromTime, lte: replay().toTime },
+    });
+    expect(replayWhere(replay({ fromTime: null, toTime: null, eventIds: ['evt_a'] }))).toEqual({
+      webhookId: 'wh_1',
+      replayId: null,
+      eventId: { in: ['evt_a'] },
+    });
+  });
+
+  it('should allow one running replay per webhook', async () => {
+    mockPrisma.webhookReplay.findFirst.mockResolvedValue(replay());
+
+    await expect(startWebhookReplay(webhook, { eventIds: ['evt_a'] })).rejects.toMatchObject({
+      statusCode: 409,
+      code: 'REPLAY_IN_PROGRESS',
+    });
+    expect(mockPrisma.webhookReplay.create).not.toHaveBeenCalled();
+  });
+
+  it('should refuse replays over the size cap', async () => {
+    mockPrisma.webhookReplay.findFirst.mockResolvedValue(null);
+    mockPrisma.webhookDelivery.count.mockResolvedValue(MAX_REPLAY_EVENTS + 1);
+
+    await expect(
+      startWebhookReplay(webhook, { from: replay().fromTime, to: replay().toTime })
+    ).rejects.toMatchObject({ statusCode: 422, code: 'REPLAY_TOO_LARGE' });
+  });
+
+  it('should complete a replay with nothing to send straight away', async () => {
+    mockPrisma.webhookReplay.findFirst.mockResolvedValue(null);
+    mockPrisma.webhookDelivery.count.mockResolvedValue(0);
+    mockPrisma.webhookReplay.create.mockImplementation(({ data }: any) => ({ id: 'replay_1', ...data }));
+
+    const created = await startWebhookReplay(webhook, { eventIds: ['evt_missing'] });
+
+    expect(created.status).toBe('COMPLETED');
+    expect(mockPrisma.webhookReplay.findUnique).not.toHaveBeenCalled();
+  });
+
+  it('should enqueue events page by page and resume from the cursor', async () => {
+    const first = original('a', '2026-10-01T10:00:00Z');
+    mockPrisma.webhookReplay.findUnique
+      .mockResolvedValueOnce(replay())
+      .mockResolvedValueOnce(replay({ enqueued: 1, cursorCreatedAt: first.createdAt, cursorId: first.id }));
+    mockPrisma.webhookDelivery.findMany.mockResolvedValueOnce([first]).mockResolvedValueOnce([]);
+    const delivery = { queueReplay: vi.fn().mockResolvedValue(true) };
+
+    await runReplay('replay_1', delivery as any, new ReplayThrottle(1000));
+
+    expect(delivery.queueReplay).toHaveBeenCalledWith(webhook, first.payload, 'replay_1');
+    expect(mockPrisma.webhookReplay.update.mock.calls[0][0].data).toEqual({
+      enqueued: { increment: 1 },
+      cursorCreatedAt: first.createdAt,
+      cursorId: first.id,
+    });
+    expect(mockPrisma.webhookDelivery.findMany.mock.calls[1][0].where.OR).toEqual([
+      { createdAt: { gt: first.createdAt } },
+      { createdAt: first.createdAt, id: { gt: first.id } },
+    ]);
+    expect(mockPrisma.webhookReplay.updateMany.mock.calls[0][0].data.status).toBe('COMPLETED');
+  });
+
+  it('should fail the replay if the webhook is disabled', async () => {
+    mockPrisma.webhookReplay.findUnique.mockResolvedValue(replay({ webhook: { ...webhook, enabled: false } }));
+    mockPrisma.webhookReplay.updateMany.mockResolvedValue({ count: 1 });
+    const delivery = { queueReplay: vi.fn() };
+
+    await runReplay('replay_1', delivery as any, new ReplayThrottle(1000));
+
+    expect(delivery.queueReplay).not.toHaveBeenCalled();
+    expect(mockPrisma.webhookReplay.updateMany.mock.calls[0][0].data.status).toBe('FAILED');
+  });
+
+  it('should space enqueues out to the rate cap', async () => {
+    const throttle = new ReplayThrottle(20);
+    const start = Date.now();
+    for (let i = 0; i < 3; i++) {
+      await throttle.take();
+    }
+    expect(Date.now() - start).toBeGreaterThanOrEqual(90);
+  });
+
+  it('should fail replays that stopped making progress', async () => {
+    mockPrisma.webhookReplay.updateMany.mockResolvedValue({ count: 2 });
+    const now = new Date('2026-10-16T12:00:00Z');
+
+    expect(await failStaleReplays(now)).toBe(2);
+    expect(mockPrisma.webhookReplay.updateMany.mock.calls[0][0].where.updatedAt.lt).toEqual(
+      new Date(now.getTime() - STALE_REPLAY_MS)
+    );
+  });
+});
@@ -0,0 +1,77 @@
+import { describe, it, expect } from 'vitest';
+import { mockPrisma } from './setup';
+import {
+  expirePreviousSecrets,
+  rotateWebhookSecret,
+  serializeSecretRotation,
+  signingSecrets,
+} from '../src/services/webhook-secrets.js';
+
+const NOW = new Date('2026-10-16T12:00:00.000Z');
+const HOUR = 60 * 60 * 1000;
+
+describe('Webhook secret rotation', () => {
+  it('should keep the old secret for the overlap window', async () => {
+    mockPrisma.webhook.updateMany.mockResolvedValue({ count: 1 });
+
+    await rotateWebhookSecret({ id: 'wh_1', secret: 'whsec_old' }, 3600, NOW);
+
+    const { where, data } = mockPrisma.webhook.updateMany.mock.calls[0][0];
+    expect(where).toEqual({ id: 'wh_1', secret: 'whsec_old' });
+    expect(data.secret).toMatch(/^whsec_[0-9a-f]{48}$/);
+    expect(data.previousSecret).toBe('whsec_old');
+    expect(data.previousSecretExpiresAt).toEqual(new Date(NOW.getTime() + HOUR));
+    expect(data.secretRotatedAt).toEqual(NOW);
+  });
+
+  it('should drop the old secret immediately without an overlap', async () => {
+    mockPrisma.webhook.updateMany.mockResolvedValue({ count: 1 });
+
+    await rotateWebhookSecret({ id: 'wh_1', secret: 'whsec_old' }, 0, NOW);
+
+    const { data } = mockPrisma.webhook.updateMany.mock.calls[0][0];
+    expect(data.previousSecret).toBeNull();
+    expect(data.previousSecretExpiresAt).toBeNull();
+  });
+
+  it('should refuse a rotation that raced another', async () => {
+    mockPrisma.webhook.updateMany.mockResolvedValue({ count: 0 });
+
+    await expect(rotateWebhookSecret({ id: 'wh_1', secret: 'whsec_old' }, 3600, NOW)).rejects.toMatchObject({
+      code: 'ROTATION_CONFLICT',
+    });
+  });
+
+  it('should sign with the previous secret until it expires', () => {
+    const webhook = {
+      secret: 'whsec_new',
+      previousSecret: 'whsec_old',
+      previousSecretExpiresAt: new Date(NOW.getTime() + HOUR),
+      secretRotatedAt: NOW,
+    };
+
+    expect(signingSecrets(webhook, NOW)).toEqual(['whsec_new', 'whsec_old']);
+    expect(signingSecrets(webhook, new Date(NOW.getTime() + HOUR))).toEqual(['whsec_new']);
+
+    expect(serializeSecretRotation(webhook, NOW)).toEqual({
+      rotated_at: NOW,
+      previous_secret_active: true,
+      previous_secret_expires_at: webhook.previousSecretExpiresAt,
+    });
+    expect(serializeSecretRotation(webhook, new Date(NOW.getTime() + HOUR))).toMatchObject({
+      previous_secret_active: false,
+      previous_secret_expires_at: null,
+    });
+    expect(serializeSecretRotation({ secret: 'whsec_new' }, NOW)).toBeNull();
+  });
+
+  it('should clear expired previous secrets', async () => {
+    mockPrisma.webhook.updateMany.mockResolvedValue({ count: 2 });
+
+    expect(await expirePreviousSecrets(NOW)).toBe(2);
+    expect(mockPrisma.webhook.updateMany).toHaveBeenCalledWith({
+      where: { previousSecretExpiresAt: { lte: NOW } },
+      data: { previousSecret: null, previousSecretExpiresAt: null },
+    });
+  });
+});
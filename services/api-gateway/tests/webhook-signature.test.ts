@@ -0,0 +1,146 @@
+import { describe, it, expect } from 'vitest';
+import { readFileSync } from 'fs';
+import { signWebhook, signWebhookWithSecrets } from '../src/services/webhook-signature.js';
+// The SDK's verifier is checked here too, so signer and verifier can't drift apart
+import {
+  IdempotencyGuard,
+  WebhookVerificationError,
+  WebhookVerifier,
+} from '../../../packages/sdk/src/webhooks.js';
+
+interface Vector {
+  name: string;
+  timestamp: string;
+  body: string;
+  signature: string;
+}
+
+const vectors: {
+  secret: string;
+  valid: Vector[];
+  invalid: Vector[];
+  rotation: { previous_secret: string; valid: Vector[] };
+} = JSON.parse(
+  readFileSync(new URL('../../../packages/sdk/test-vectors/webhook-signatures.json', import.meta.url), 'utf8')
+);
+
+function thrown(fn: () => unknown): WebhookVerificationError {
+  try {
+    fn();
+  } catch (error) {
+    return error as WebhookVerificationError;
+  }
+  throw new Error('expected an error');
+}
+
+describe('Webhook signatures', () => {
+  const verifier = new WebhookVerifier(vectors.secret);
+  const headers = (v: Vector) => ({
+    'X-Webhook-Timestamp': v.timestamp,
+    'X-Webhook-Signature': v.signature,
+  });
+
+  it.each(vectors.valid)('should sign $name as the vectors do', (v) => {
+    expect(signWebhook(vectors.secret, v.timestamp, v.body)).toBe(v.signature);
+  });
+
+  it.each(vectors.valid)('should verify $name in the SDK', (v) => {
+    const event = verifier.verify(Buffer.from(v.body, 'utf8'), headers(v), Number(v.timestamp));
+    expect(event).toEqual(JSON.parse(v.body));
+  });
+
+  it.each(vectors.invalid)('should reject $name in the SDK', (v) => {
+    expect(thrown(() => verifier.verify(v.body, headers(v), Number(v.timestamp))).code).toBe(
+      'INVALID_SIGNATURE'
+    );
+  });
+
+  it('should sign with both secrets during a rotation', () => {
+    const [v] = vectors.rotation.valid;
+    expect(signWebhookWithSecrets([vectors.secret, vectors.rotation.previous_secret], v.timestamp, v.body)).toBe(
+      v.signature
+    );
+  });
+
+  it.each(vectors.rotation.valid)('should verify $name with either secret in the SDK', (v) => {
+    for (const secret of [vectors.secret, vectors.rotation.previous_secret]) {
+      const event = new WebhookVerifier(secret).verify(v.body, headers(v), Number(v.timestamp));
+      expect(event).toEqual(JSON.parse(v.body));
+    }
+    expect(
+      thrown(() => new WebhookVerifier('whsec_unrelated').verify(v.body, headers(v), Number(v.timestamp))).code
+    ).toBe('INVALID_SIGNATURE');
+  });
+
+  it('should sign the golden fixture as gen-fixtures does', () => {
+    const golden = JSON.parse(readFileSync(new URL('../../../fixtures/webhook_signature.json', import.meta.url), 'utf8'));
+    expect(signWebhook(golden.secret, golden.timestamp, golden.body)).toBe(golden.signature);
+    expect(signWebhookWithSecrets([golden.secret, golden.previous_secret], golden.timestamp, golden.body)).toBe(
+      golden.rotation_signature
+    );
+  });
+
+  it('should reject deliveries outside the timestamp tolerance', () => {
+    const [v] = vectors.valid;
+    const sentAt = Number(v.timestamp);
+
+    expect(() => verifier.verify(v.body, headers(v), sentAt + 5 * 60 * 1000)).not.toThrow();
+    expect(thrown(() => verifier.verify(v.body, headers(v), sentAt + 5 * 60 * 1000 + 1)).code).toBe(
+      'TIMESTAMP_OUT_OF_TOLERANCE'
+    );
+    expect(thrown(() => verifier.verify(v.body, headers(v), sentAt - 6 * 60 * 1000)).code).toBe(
+      'TIMESTAMP_OUT_OF_TOLERANCE'
+    );
+  });
+
+  it('should reject deliveries without signature headers', () => {
+    const [v] = vectors.valid;
+    expect(thrown(() => verifier.verify(v.body, { 'X-Webhook-Timestamp': v.timestamp })).code).toBe(
+      'MISSING_HEADER'
+    );
+  });
+
+  it('should reject signed bodies that are not ninjapay events', () => {
+    const timestamp = '1760616000000';
+    const body = JSON.stringify({ object: 'event', type: 'checkout.session.completed' });
+    const signed = { 'x-webhook-timestamp': timestamp, 'x-webhook-signature': signWebhook(vectors.secret, timestamp, body) };
+
+    expect(thrown(() => verifier.verify(body, signed, Number(timestamp))).code).toBe('INVALID_PAYLOAD');
+  });
+});
+
+describe('Webhook idempotency guard', () => {
+  it('should run the handler once per event id', async () => {
+    const guard = new IdempotencyGuard();
+    let runs = 0;
+
+    expect(await guard.handle({ id: 'evt_1' }, () => { runs++; })).toBe(true);
+    expect(await guard.handle({ id: 'evt_1' }, () => { runs++; })).toBe(false);
+    expect(await guard.handle({ id: 'evt_2' }, () => { runs++; })).toBe(true);
+    expect(runs).toBe(2);
+  });
+
+  it('should let a redelivery retry after the handler fails', async () => {
+    const guard = new IdempotencyGuard();
+
+    await expect(guard.handle({ id: 'evt_1' }, () => { throw new Error('db down'); })).rejects.toThrow(
+      'db down'
+    );
+    expect(await guard.handle({ id: 'evt_1' }, () => {})).toBe(true);
+  });
+
+  it('should defer to a shared store', async () => {
+    const claimed = new Set<string>();
+    const store = {
+      claim: async (id: string) => (claimed.has(id) ? false : (claimed.add(id), true)),
+      release: async (id: string) => { claimed.delete(id); },
+    };
+
+    // Two consumer instances backed by the same store
+    const first = new IdempotencyGuard(store);
+    const second = new IdempotencyGuard(store);
+
+    expect(await first.handle({ id: 'evt_1' }, () => {})).toBe(true);
+    expect(await second.handle({ id: 'evt_1' }, () => {})).toBe(false);
+  });
+});
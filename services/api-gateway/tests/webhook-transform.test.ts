@@ -0,0 +1,87 @@
+import { describe, it, expect } from 'vitest';
+import {
+  compileWebhookFormat,
+  isValidWebhookFormat,
+  transformWebhookBody,
+  MAX_EXPRESSION_LENGTH,
+  MAX_TRANSFORMED_BYTES,
+} from '../src/services/webhook-transform.js';
+
+describe('Webhook payload transformations', () => {
+  const event = {
+    id: 'evt_123',
+    type: 'payment_intent.confirmed',
+    created: 1_700_000_000_000,
+    livemode: false,
+    data: {
+      id: 'pi_123',
+      status: 'processing',
+      items: [{ sku: 'A-1' }, { sku: 'B-2' }],
+    },
+  };
+
+  describe('ninjapay', () => {
+    it('should send the native envelope unchanged', () => {
+      expect(JSON.parse(transformWebhookBody('ninjapay', event))).toEqual(event);
+    });
+  });
+
+  describe('stripe_compatible', () => {
+    it('should map to a Stripe-shaped event', () => {
+      const body = JSON.parse(transformWebhookBody('stripe_compatible', event));
+
+      expect(body).toEqual({
+        id: 'evt_123',
+        object: 'event',
+        created: 1_700_000_000,
+        livemode: false,
+        type: 'payment_intent.succeeded',
+        data: { object: event.data },
+      });
+    });
+  });
+
+  describe('jq expressions', () => {
+    it('should project paths into objects and arrays', () => {
+      const body = transformWebhookBody(
+        'jq:{event: .type, payment: .data.id, first_sku: .data.items[0].sku, tags: ["ninjapay", .id]}',
+        event
+      );
+
+      expect(JSON.parse(body)).toEqual({
+        event: 'payment_intent.confirmed',
+        payment: 'pi_123',
+        first_sku: 'A-1',
+        tags: ['ninjapay', 'evt_123'],
+      });
+    });
+
+    it('should support key shorthand and null for missing paths', () => {
+      const body = transformWebhookBody('jq:{id, missing: .data.nope.deeper}', event);
+      expect(JSON.parse(body)).toEqual({ id: 'evt_123', missing: null });
+    });
+
+    it('should reject invalid or unsupported expressions', () => {
+      expect(isValidWebhookFormat('jq:{a: .b')).toBe(false);
+      expect(isValidWebhookFormat('jq:.data | keys')).toBe(false);
+      expect(isValidWebhookFormat('jq:.items[-1]')).toBe(false);
+      expect(isValidWebhookFormat('jq:env')).toBe(false);
+      expect(isValidWebhookFormat(`jq:${'.a'.repeat(MAX_EXPRESSION_LENGTH)}`)).toBe(false);
+      expect(isValidWebhookFormat('xml')).toBe(false);
+    });
+
+    it('should reject expressions with too many nodes', () => {
+      expect(() => compileWebhookFormat(`jq:[${'1,'.repeat(300)}1]`)).toThrow(/nodes/);
+    });
+
+    it('should not read inherited properties', () => {
+      const body = transformWebhookBody('jq:{c: .constructor, p: .__proto__}', event);
+      expect(JSON.parse(body)).toEqual({ c: null, p: null });
+    });
+
+    it('should enforce the output size limit', () => {
+      const large = { ...event, data: { blob: 'x'.repeat(MAX_TRANSFORMED_BYTES) } };
+      expect(() => transformWebhookBody('jq:.data', large)).toThrow(/exceeds/);
+    });
+  });
+});
@@ -0,0 +1,56 @@
+//! Generates the vault program's error table from its `#[error_code]` enum,
+//! the same source Anchor builds the IDL's error list from, so decoded
+//! program errors can't drift from the deployed program.
+
+use std::env;
+use std::fs;
+use std::path::Path;
+
+const PROGRAM_SOURCE: &str = "../../programs/ninjapay-vault/src/lib.rs";
+const ERROR_ENUM: &str = "pub enum VaultError {";
+
+fn main() {
+    println!("cargo:rerun-if-changed={}", PROGRAM_SOURCE);
+
+    let source = fs::read_to_string(PROGRAM_SOURCE)
+        .unwrap_or_else(|e| panic!("cannot read {}: {}", PROGRAM_SOURCE, e));
+    let errors = parse_errors(&source);
+    assert!(!errors.is_empty(), "no VaultError variants found in {}", PROGRAM_SOURCE);
+
+    let mut out = String::from("/// (name, message) of each VaultError, in code order from 6000\n");
+    out.push_str("pub const VAULT_ERRORS: &[(&str, &str)] = &[\n");
+    for (name, message) in &errors {
+        out.push_str(&format!("    ({:?}, {:?}),\n", name, message));
+    }
+    out.push_str("];\n");
+
+    let path = Path::new(&env::var("OUT_DIR").unwrap()).join("vault_errors.rs");
+    fs::write(path, out).unwrap();
+}
+
+/// Variants and `#[msg]` strings of the `#[error_code]` enum
+fn parse_errors(source: &str) -> Vec<(String, String)> {
+    let start = source
+        .find(ERROR_ENUM)
+        .unwrap_or_else(|| panic!("`{}` not found in {}", ERROR_ENUM, PROGRAM_SOURCE));
+    let body = &source[start + ERROR_ENUM.len()..];
+    let body = &body[..body.find('}').expect("unterminated VaultError enum")];
+
+    let mut errors = Vec::new();
+    let mut message = None;
+    for line in body.lines().map(str::trim).filter(|l| !l.is_empty() && !l.starts_with("//")) {
+        if let Some(msg) = line.strip_prefix("#[msg(\"").and_then(|l| l.strip_suffix("\")]")) {
+            message = Some(msg.replace("\\\"", "\""));
+        } else {
+            let name = line.trim_end_matches(',');
+            // Explicit discriminants would break the 6000 + index numbering
+            assert!(
+                name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_'),
+                "unsupported VaultError variant: {}",
+                line
+            );
+            errors.push((name.to_string(), message.take().unwrap_or_default()));
+        }
+    }
+    errors
+}
@@ -0,0 +1,45 @@
+use actix_web::{dev::Payload, web, FromRequest, HttpRequest};
+use sha2::{Digest, Sha256};
+use std::future::{ready, Ready};
+
+use crate::config::Config;
+use crate::error::ServiceError;
+
+/// Extractor that admits only requests bearing the admin API token.
+///
+/// Admin endpoints are disabled entirely when `ADMIN_API_TOKEN` is unset.
+pub struct AdminAuth;
+
+impl FromRequest for AdminAuth {
+    type Error = ServiceError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        ready(authorize(req))
+    }
+}
+
+fn authorize(req: &HttpRequest) -> Result<AdminAuth, ServiceError> {
+    let config = req
+        .app_data::<web::Data<Config>>()
+        .ok_or_else(|| ServiceError::InternalError("Config not registered".to_string()))?;
+
+    let expected = config
+        .admin_api_token
+        .as_deref()
+        .ok_or_else(|| ServiceError::Forbidden("Admin API is disabled".to_string()))?;
+
+    let provided = req
+        .headers()
+        .get("Authorization")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .ok_or_else(|| ServiceError::Unauthorized("Missing admin token".to_string()))?;
+
+    // Compare digests so the comparison time doesn't depend on the token
+    if Sha256::digest(expected.as_bytes()) != Sha256::digest(provided.as_bytes()) {
+        return Err(ServiceError::Unauthorized("Invalid admin token".to_string()));
+    }
+
+    Ok(AdminAuth)
+}
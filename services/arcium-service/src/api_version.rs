@@ -0,0 +1,34 @@
+use actix_web::{dev::Payload, FromRequest, HttpRequest};
+use std::future::{ready, Ready};
+
+use crate::error::ServiceError;
+
+/// API version a request was routed through.
+///
+/// v1 keeps the original wire format (u64 money fields as JSON numbers).
+/// v2 encodes u64 money fields as decimal strings via
+/// `ninjapay_types::string_u64` so JavaScript clients don't lose precision.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ApiVersion {
+    V1,
+    V2,
+}
+
+impl ApiVersion {
+    pub fn from_path(path: &str) -> Self {
+        if path.starts_with("/api/v2/") {
+            ApiVersion::V2
+        } else {
+            ApiVersion::V1
+        }
+    }
+}
+
+impl FromRequest for ApiVersion {
+    type Error = ServiceError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        ready(Ok(ApiVersion::from_path(req.path())))
+    }
+}
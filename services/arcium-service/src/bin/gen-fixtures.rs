@@ -0,0 +1,57 @@
+//! Write the golden fixtures to `fixtures/` at the repository root.
+//!
+//! ```text
+//! gen-fixtures [--bump] [DIR]
+//! ```
+//!
+//! Without `--bump` each file is regenerated at its current version, which
+//! must reproduce it exactly. `--bump` records version + 1 in every file;
+//! use it when a fixture changes on purpose.
+
+use std::path::PathBuf;
+
+// Shared with the service so the vectors come from the code under test
+#[allow(dead_code)]
+#[path = "../error.rs"]
+mod error;
+#[path = "../fixtures.rs"]
+mod fixtures;
+#[allow(dead_code, deprecated)]
+#[path = "../mpc"]
+mod mpc {
+    pub mod encryption;
+    pub mod envelope;
+}
+
+fn main() -> anyhow::Result<()> {
+    let mut bump = false;
+    let mut dir = PathBuf::from(concat!(env!("CARGO_MANIFEST_DIR"), "/../../fixtures"));
+    for arg in std::env::args().skip(1) {
+        match arg.as_str() {
+            "--bump" => bump = true,
+            flag if flag.starts_with('-') => anyhow::bail!("usage: gen-fixtures [--bump] [DIR]"),
+            path => dir = PathBuf::from(path),
+        }
+    }
+    std::fs::create_dir_all(&dir)?;
+    let dir = dir.canonicalize()?;
+
+    for (name, _) in fixtures::FIXTURES {
+        let path = dir.join(name);
+        let current = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|contents| serde_json::from_str::<serde_json::Value>(&contents).ok())
+            .and_then(|fixture| fixture["version"].as_u64())
+            .map(|version| version as u32);
+        let version = match current {
+            Some(version) if bump => version + 1,
+            Some(version) => version,
+            None => 1,
+        };
+
+        let fixture = fixtures::generate(name, version).map_err(|e| anyhow::anyhow!("{}: {}", name, e))?;
+        std::fs::write(&path, serde_json::to_string_pretty(&fixture)? + "\n")?;
+        println!("{} (version {})", path.display(), version);
+    }
+    Ok(())
+}
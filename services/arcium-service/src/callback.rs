@@ -0,0 +1,575 @@
+use hmac::{Hmac, Mac};
+use reqwest::{Client, Url};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::RwLock;
+use tracing::{info, warn};
+
+use crate::callback_tls::{CallbackTls, ClientIdentities};
+use crate::config::Config;
+use crate::error::ServiceError;
+use crate::store::unix_timestamp;
+
+/// Variables that may appear in a callback URL template
+pub const TEMPLATE_VARIABLES: &[&str] = &["computation_id", "batch_id", "status"];
+
+/// Variables available for payment settlements (no batch)
+pub const PAYMENT_VARIABLES: &[&str] = &["computation_id", "status"];
+
+/// Variables available for payroll settlements
+pub const PAYROLL_VARIABLES: &[&str] = TEMPLATE_VARIABLES;
+
+/// A callback URL that may contain `{variable}` placeholders.
+///
+/// Placeholders are only allowed in the path and query so the destination
+/// host is fixed at queue time and can be checked before expansion.
+#[derive(Debug, Clone)]
+pub struct CallbackTemplate {
+    raw: String,
+}
+
+/// Values substituted into a callback template at delivery time
+#[derive(Debug, Clone, Default)]
+pub struct CallbackVars<'a> {
+    pub computation_id: &'a str,
+    pub batch_id: Option<&'a str>,
+    pub status: &'a str,
+}
+
+impl CallbackTemplate {
+    /// Parse and validate a template, allowing only the given variables
+    pub fn parse(raw: &str, allowed: &[&str]) -> Result<Self, ServiceError> {
+        let mut rest = raw;
+        while let Some(start) = rest.find('{') {
+            let end = rest[start..].find('}').ok_or_else(|| {
+                ServiceError::InvalidInput("callback_url: unterminated '{' in template".to_string())
+            })?;
+            let name = &rest[start + 1..start + end];
+            if !allowed.contains(&name) {
+                return Err(ServiceError::InvalidInput(format!(
+                    "callback_url: unknown template variable '{{{}}}' (allowed: {})",
+                    name,
+                    allowed.join(", ")
+                )));
+            }
+            rest = &rest[start + end + 1..];
+        }
+        if rest.contains('}') {
+            return Err(ServiceError::InvalidInput(
+                "callback_url: unmatched '}' in template".to_string(),
+            ));
+        }
+
+        let template = Self { raw: raw.to_string() };
+        let url = template.base_url()?;
+
+        if url.scheme() != "https" && url.scheme() != "http" {
+            return Err(ServiceError::InvalidInput(
+                "callback_url: scheme must be http or https".to_string(),
+            ));
+        }
+
+        let authority_end = raw
+            .find("://")
+            .map(|i| i + 3)
+            .and_then(|i| raw[i..].find(['/', '?', '#']).map(|j| i + j))
+            .unwrap_or(raw.len());
+        if raw[..authority_end].contains('{') {
+            return Err(ServiceError::InvalidInput(
+                "callback_url: template variables are not allowed in the host".to_string(),
+            ));
+        }
+
+        Ok(template)
+    }
+
+    /// The template with placeholders stripped, used for host checks
+    fn base_url(&self) -> Result<Url, ServiceError> {
+        let mut placeholder = self.raw.clone();
+        for name in TEMPLATE_VARIABLES {
+            placeholder = placeholder.replace(&format!("{{{}}}", name), "x");
+        }
+        let url = Url::parse(&placeholder)
+            .map_err(|e| ServiceError::InvalidInput(format!("callback_url: {}", e)))?;
+        if url.host_str().is_none() {
+            return Err(ServiceError::InvalidInput(
+                "callback_url: missing host".to_string(),
+            ));
+        }
+        Ok(url)
+    }
+
+    /// Substitute variables, percent-encoding their values
+    pub fn expand(&self, vars: &CallbackVars) -> String {
+        self.raw
+            .replace("{computation_id}", &encode_component(vars.computation_id))
+            .replace("{batch_id}", &encode_component(vars.batch_id.unwrap_or_default()))
+            .replace("{status}", &encode_component(vars.status))
+    }
+}
+
+fn encode_component(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+/// Reject destinations that resolve to loopback, private, or link-local addresses
+pub async fn check_destination(url: &Url, allow_private: bool) -> Result<(), ServiceError> {
+    let host = url
+        .host_str()
+        .ok_or_else(|| ServiceError::InvalidInput("callback_url: missing host".to_string()))?;
+    if allow_private {
+        return Ok(());
+    }
+
+    let port = url.port_or_known_default().unwrap_or(443);
+    let addrs = tokio::net::lookup_host((host, port))
+        .await
+        .map_err(|e| ServiceError::InvalidInput(format!("callback_url: cannot resolve host: {}", e)))?;
+
+    for addr in addrs {
+        if is_disallowed(addr.ip()) {
+            return Err(ServiceError::InvalidInput(
+                "callback_url: destination resolves to a disallowed address".to_string(),
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+fn is_disallowed(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => {
+            v4.is_loopback()
+                || v4.is_private()
+                || v4.is_link_local()
+                || v4.is_unspecified()
+                || v4.is_broadcast()
+                || v4.is_multicast()
+                // Carrier-grade NAT (100.64.0.0/10)
+                || (v4.octets()[0] == 100 && (v4.octets()[1] & 0xc0) == 64)
+        }
+        IpAddr::V6(v6) => {
+            if let Some(v4) = v6.to_ipv4_mapped() {
+                return is_disallowed(IpAddr::V4(v4));
+            }
+            v6.is_loopback()
+                || v6.is_unspecified()
+                || v6.is_multicast()
+                // Unique local (fc00::/7) and link-local (fe80::/10)
+                || (v6.segments()[0] & 0xfe00) == 0xfc00
+                || (v6.segments()[0] & 0xffc0) == 0xfe80
+        }
+    }
+}
+
+/// Why a delivery attempt got no HTTP response
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeliveryFailure {
+    /// The destination policy rejected the host
+    DestinationBlocked,
+    /// The callback's TLS settings can't build a client: bad CA bundle,
+    /// or a missing or invalid client identity
+    TlsMisconfigured,
+    SerializationFailed,
+    /// Connection, TLS handshake or timeout
+    RequestFailed,
+}
+
+/// One attempt to deliver a callback, as recorded on the computation
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DeliveryLogEntry {
+    pub url: String,
+    pub status_code: Option<u16>,
+    pub failure_reason: Option<DeliveryFailure>,
+    pub error: Option<String>,
+    pub attempted_at: u64,
+}
+
+impl DeliveryLogEntry {
+    fn failed(url: String, reason: DeliveryFailure, error: String, attempted_at: u64) -> Self {
+        Self {
+            url,
+            status_code: None,
+            failure_reason: Some(reason),
+            error: Some(error),
+            attempted_at,
+        }
+    }
+}
+
+/// Clients for callbacks with TLS settings, keyed by `CallbackTls::cache_key`
+const MAX_CACHED_CLIENTS: usize = 256;
+
+/// Delivers computation results to caller-supplied callback URLs
+pub struct CallbackDispatcher {
+    http_client: Client,
+    tls_clients: RwLock<HashMap<String, (Option<String>, Client)>>,
+    pub identities: ClientIdentities,
+    secret: String,
+    allow_private_hosts: bool,
+}
+
+fn client_builder() -> reqwest::ClientBuilder {
+    Client::builder()
+        .timeout(std::time::Duration::from_secs(10))
+        .redirect(reqwest::redirect::Policy::none())
+}
+
+impl CallbackDispatcher {
+    pub fn new(config: &Config) -> Result<Self, ServiceError> {
+        let http_client = client_builder()
+            .build()
+            .map_err(|e| ServiceError::ConfigError(format!("Failed to create HTTP client: {}", e)))?;
+
+        Ok(Self {
+            http_client,
+            tls_clients: RwLock::new(HashMap::new()),
+            identities: ClientIdentities::new(&config.encryption_master_key)?,
+            secret: config.callback_secret.clone(),
+            allow_private_hosts: config.allow_private_callbacks,
+        })
+    }
+
+    /// Delete a client identity and every cached client presenting it
+    pub fn remove_identity(&self, id: &str) -> bool {
+        let removed = self.identities.remove(id);
+        self.tls_clients
+            .write()
+            .unwrap()
+            .retain(|_, (identity, _)| identity.as_deref() != Some(id));
+        removed
+    }
+
+    /// The client for a callback's TLS settings, built once per distinct
+    /// settings. Errors describe the misconfiguration.
+    fn client_for(&self, tls: Option<&CallbackTls>) -> Result<Client, String> {
+        let Some(tls) = tls else {
+            return Ok(self.http_client.clone());
+        };
+        let key = tls.cache_key();
+        if let Some((_, client)) = self.tls_clients.read().unwrap().get(&key) {
+            return Ok(client.clone());
+        }
+
+        // Client identities are rustls identities
+        let mut builder = client_builder().use_rustls_tls();
+        for certificate in tls.ca_certificates()? {
+            builder = builder.add_root_certificate(certificate);
+        }
+        if let Some(id) = &tls.client_identity_id {
+            builder = builder.identity(self.identities.identity(id)?);
+        }
+        let client = builder
+            .build()
+            .map_err(|e| format!("TLS client could not be built: {}", e))?;
+
+        let mut clients = self.tls_clients.write().unwrap();
+        if clients.len() >= MAX_CACHED_CLIENTS {
+            clients.clear();
+        }
+        clients.insert(key, (tls.client_identity_id.clone(), client.clone()));
+        Ok(client)
+    }
+
+    /// Resolve the template's host and apply the destination policy
+    pub async fn check_template(&self, template: &CallbackTemplate) -> Result<(), ServiceError> {
+        let url = template.base_url()?;
+        check_destination(&url, self.allow_private_hosts).await
+    }
+
+    /// Check the destination host, expand the template, and POST the payload
+    pub async fn deliver<T: Serialize>(
+        &self,
+        template: &CallbackTemplate,
+        tls: Option<&CallbackTls>,
+        vars: &CallbackVars<'_>,
+        payload: &T,
+    ) -> DeliveryLogEntry {
+        let attempted_at = unix_timestamp();
+
+        // The host is fixed by the template, so checking it before expansion
+        // covers the expanded URL as well
+        let checked = self.check_template(template).await;
+        let url = template.expand(vars);
+        if let Err(e) = checked {
+            warn!("Callback blocked for {}: {}", vars.computation_id, e);
+            return DeliveryLogEntry::failed(url, DeliveryFailure::DestinationBlocked, e.to_string(), attempted_at);
+        }
+
+        let client = match self.client_for(tls) {
+            Ok(client) => client,
+            Err(e) => {
+                warn!("Callback TLS misconfigured for {}: {}", vars.computation_id, e);
+                return DeliveryLogEntry::failed(url, DeliveryFailure::TlsMisconfigured, e, attempted_at);
+            }
+        };
+
+        let body = match serde_json::to_vec(payload) {
+            Ok(body) => body,
+            Err(e) => {
+                return DeliveryLogEntry::failed(
+                    url,
+                    DeliveryFailure::SerializationFailed,
+                    format!("Failed to serialize payload: {}", e),
+                    attempted_at,
+                )
+            }
+        };
+
+        let result = client
+            .post(&url)
+            .header("Content-Type", "application/json")
+            .header("X-Ninjapay-Signature", sign_payload(&self.secret, attempted_at, &body))
+            .body(body)
+            .send()
+            .await;
+
+        match result {
+            Ok(response) => {
+                info!(
+                    "Callback delivered for {} ({})",
+                    vars.computation_id,
+                    response.status()
+                );
+                DeliveryLogEntry {
+                    url,
+                    status_code: Some(response.status().as_u16()),
+                    failure_reason: None,
+                    error: None,
+                    attempted_at,
+                }
+            }
+            Err(e) => {
+                warn!("Callback delivery failed for {}: {}", vars.computation_id, e);
+                DeliveryLogEntry::failed(url, DeliveryFailure::RequestFailed, e.to_string(), attempted_at)
+            }
+        }
+    }
+}
+
+/// Signature header value: `t=<unix seconds>,v1=<hex HMAC-SHA256("t.body")>`
+pub fn sign_payload(secret: &str, timestamp: u64, body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())
+        .expect("HMAC accepts keys of any length");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body);
+    format!("t={},v1={}", timestamp, hex::encode(mac.finalize().into_bytes()))
+}
+
+/// Largest `result` accepted in a cluster callback, in serialized bytes
+pub const MAX_CALLBACK_RESULT_BYTES: usize = 64 * 1024;
+
+/// Longest computation id accepted in a cluster callback
+const MAX_COMPUTATION_ID_LEN: usize = 128;
+
+/// Computation states the cluster may report
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ClusterStatus {
+    Queued,
+    Processing,
+    Completed,
+    Failed,
+}
+
+impl ClusterStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ClusterStatus::Queued => "queued",
+            ClusterStatus::Processing => "processing",
+            ClusterStatus::Completed => "completed",
+            ClusterStatus::Failed => "failed",
+        }
+    }
+}
+
+/// Result notification posted by the Arcium cluster
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct ClusterCallback {
+    pub computation_id: String,
+    pub status: ClusterStatus,
+    #[serde(default)]
+    pub result: Option<serde_json::Value>,
+}
+
+/// Parse a cluster callback body, enforcing the schema beyond what serde checks
+pub fn parse_cluster_callback(body: &[u8]) -> Result<ClusterCallback, String> {
+    let callback: ClusterCallback = serde_json::from_slice(body).map_err(|e| e.to_string())?;
+
+    let id = &callback.computation_id;
+    if id.is_empty() || id.len() > MAX_COMPUTATION_ID_LEN {
+        return Err(format!(
+            "computation_id must be 1-{} characters",
+            MAX_COMPUTATION_ID_LEN
+        ));
+    }
+    if !id.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'_' || b == b'-') {
+        return Err("computation_id contains invalid characters".to_string());
+    }
+
+    if let Some(result) = &callback.result {
+        let size = serde_json::to_vec(result).map(|v| v.len()).unwrap_or(usize::MAX);
+        if size > MAX_CALLBACK_RESULT_BYTES {
+            return Err(format!(
+                "result is {} bytes (max {})",
+                size, MAX_CALLBACK_RESULT_BYTES
+            ));
+        }
+    }
+
+    Ok(callback)
+}
+
+/// Verify the cluster's `X-Arcium-Signature`: hex HMAC-SHA256 of the raw body
+pub fn verify_cluster_signature(secret: &str, body: &[u8], signature: &str) -> bool {
+    let Ok(signature) = hex::decode(signature) else {
+        return false;
+    };
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())
+        .expect("HMAC accepts keys of any length");
+    mac.update(body);
+    mac.verify_slice(&signature).is_ok()
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    #[test]
+    fn test_template_validation() {
+        assert!(CallbackTemplate::parse(
+            "https://api.example.com/settlements/{computation_id}/done?s={status}",
+            PAYMENT_VARIABLES
+        )
+        .is_ok());
+
+        // Unknown variable
+        assert!(CallbackTemplate::parse("https://api.example.com/{order}", PAYMENT_VARIABLES).is_err());
+        // batch_id is only known for payroll
+        assert!(CallbackTemplate::parse("https://api.example.com/{batch_id}", PAYMENT_VARIABLES).is_err());
+        assert!(CallbackTemplate::parse("https://api.example.com/{batch_id}", PAYROLL_VARIABLES).is_ok());
+        // Variables must not influence the host
+        assert!(CallbackTemplate::parse("https://{status}.example.com/", PAYMENT_VARIABLES).is_err());
+        // Unbalanced braces
+        assert!(CallbackTemplate::parse("https://api.example.com/{status", PAYMENT_VARIABLES).is_err());
+        assert!(CallbackTemplate::parse("ftp://api.example.com/", PAYMENT_VARIABLES).is_err());
+    }
+
+    #[test]
+    fn test_template_expansion() {
+        let template = CallbackTemplate::parse(
+            "https://api.example.com/batches/{batch_id}/{computation_id}?status={status}",
+            PAYROLL_VARIABLES,
+        )
+        .unwrap();
+
+        let url = template.expand(&CallbackVars {
+            computation_id: "payroll_abc",
+            batch_id: Some("batch 1/2"),
+            status: "completed",
+        });
+        assert_eq!(
+            url,
+            "https://api.example.com/batches/batch%201%2F2/payroll_abc?status=completed"
+        );
+    }
+
+    #[test]
+    fn test_disallowed_addresses() {
+        for ip in ["127.0.0.1", "10.1.2.3", "192.168.0.1", "169.254.169.254", "100.64.0.1", "::1", "fd00::1", "::ffff:10.0.0.1"] {
+            assert!(is_disallowed(ip.parse().unwrap()), "{}", ip);
+        }
+        for ip in ["8.8.8.8", "2606:4700::1111"] {
+            assert!(!is_disallowed(ip.parse().unwrap()), "{}", ip);
+        }
+    }
+
+    #[test]
+    fn test_cluster_callback_schema() {
+        let ok = parse_cluster_callback(br#"{"computation_id":"pay_1","status":"completed","result":{"tx":"abc"}}"#)
+            .unwrap();
+        assert_eq!(ok.status, ClusterStatus::Completed);
+
+        // Missing status, unknown status, truncated body, bad id
+        assert!(parse_cluster_callback(br#"{"computation_id":"pay_1"}"#).is_err());
+        assert!(parse_cluster_callback(br#"{"computation_id":"pay_1","status":"done"}"#).is_err());
+        assert!(parse_cluster_callback(br#"{"computation_id":"pay_1","sta"#).is_err());
+        assert!(parse_cluster_callback(br#"{"computation_id":"../x","status":"failed"}"#).is_err());
+
+        let oversized = format!(
+            r#"{{"computation_id":"pay_1","status":"completed","result":"{}"}}"#,
+            "a".repeat(MAX_CALLBACK_RESULT_BYTES)
+        );
+        assert!(parse_cluster_callback(oversized.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_cluster_signature() {
+        let body = br#"{"computation_id":"pay_1","status":"completed"}"#;
+        let mut mac = Hmac::<Sha256>::new_from_slice(b"secret").unwrap();
+        mac.update(body);
+        let signature = hex::encode(mac.finalize().into_bytes());
+
+        assert!(verify_cluster_signature("secret", body, &signature));
+        assert!(!verify_cluster_signature("other", body, &signature));
+        assert!(!verify_cluster_signature("secret", body, "not-hex"));
+    }
+
+    pub(crate) fn dispatcher() -> CallbackDispatcher {
+        CallbackDispatcher {
+            http_client: client_builder().build().unwrap(),
+            tls_clients: RwLock::new(HashMap::new()),
+            identities: crate::callback_tls::tests::identities(),
+            secret: "secret".to_string(),
+            allow_private_hosts: true,
+        }
+    }
+
+    #[actix_rt::test]
+    async fn test_tls_clients_are_cached_and_misconfiguration_is_reported() {
+        use crate::callback_tls::tests::fixture;
+
+        let dispatcher = dispatcher();
+        let identity = dispatcher
+            .identities
+            .create("acme".to_string(), fixture("client_cert.pem"), &fixture("client_key.pem"))
+            .unwrap();
+        let tls = CallbackTls {
+            ca_bundle: Some(fixture("client_cert.pem")),
+            client_identity_id: Some(identity.id.clone()),
+        };
+
+        assert!(dispatcher.client_for(Some(&tls)).is_ok());
+        assert!(dispatcher.client_for(Some(&tls)).is_ok());
+        assert_eq!(dispatcher.tls_clients.read().unwrap().len(), 1);
+
+        // Deleting the identity evicts its client, so later deliveries fail
+        // with a TLS reason instead of silently presenting it
+        assert!(dispatcher.remove_identity(&identity.id));
+        assert!(dispatcher.tls_clients.read().unwrap().is_empty());
+
+        let template = CallbackTemplate::parse("http://127.0.0.1:9/{status}", PAYMENT_VARIABLES).unwrap();
+        let vars = CallbackVars {
+            computation_id: "pay_1",
+            batch_id: None,
+            status: "completed",
+        };
+        let entry = dispatcher.deliver(&template, Some(&tls), &vars, &"{}").await;
+        assert_eq!(entry.failure_reason, Some(DeliveryFailure::TlsMisconfigured));
+        assert!(entry.error.unwrap().contains("does not exist"));
+    }
+}
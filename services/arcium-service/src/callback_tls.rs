@@ -0,0 +1,271 @@
+//! Per-callback TLS settings: a CA bundle to trust for the caller's
+//! endpoint and a client certificate to present (mTLS).
+//!
+//! Client identities are uploaded through the admin API and referenced by
+//! id; their private keys are kept encrypted under a key derived from the
+//! master key and only decrypted to build an HTTP client.
+
+use chacha20poly1305::{
+    aead::{Aead, KeyInit},
+    ChaCha20Poly1305, Nonce,
+};
+use hkdf::Hkdf;
+use rand::Rng;
+use reqwest::{Certificate, Identity};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+use crate::error::ServiceError;
+use crate::store::unix_timestamp;
+
+const HKDF_INFO: &[u8] = b"ninjapay-client-identity-v1";
+const NONCE_SIZE: usize = 12;
+
+/// Largest CA bundle accepted on a callback
+pub const MAX_CA_BUNDLE_BYTES: usize = 64 * 1024;
+
+/// TLS settings for one callback subscription
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct CallbackTls {
+    /// PEM certificates trusted for the callback host, in addition to the
+    /// public roots
+    #[serde(default)]
+    pub ca_bundle: Option<String>,
+    /// Client identity to present, from `POST /admin/client-identities`
+    #[serde(default)]
+    pub client_identity_id: Option<String>,
+}
+
+impl CallbackTls {
+    /// Key for the HTTP client built from these settings
+    pub fn cache_key(&self) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(self.ca_bundle.as_deref().unwrap_or_default());
+        hasher.update([0]);
+        hasher.update(self.client_identity_id.as_deref().unwrap_or_default());
+        hex::encode(hasher.finalize())
+    }
+
+    pub fn ca_certificates(&self) -> Result<Vec<Certificate>, String> {
+        let Some(bundle) = &self.ca_bundle else {
+            return Ok(Vec::new());
+        };
+        let certificates = Certificate::from_pem_bundle(bundle.as_bytes())
+            .map_err(|e| format!("CA bundle is invalid: {}", e))?;
+        if certificates.is_empty() {
+            return Err("CA bundle contains no certificates".to_string());
+        }
+        Ok(certificates)
+    }
+
+    /// Reject settings that could never produce a working client
+    pub fn validate(&self, identities: &ClientIdentities) -> Result<(), ServiceError> {
+        if self.ca_bundle.as_ref().is_some_and(|b| b.len() > MAX_CA_BUNDLE_BYTES) {
+            return Err(ServiceError::InvalidInput(format!(
+                "callback_tls.ca_bundle must be at most {} bytes",
+                MAX_CA_BUNDLE_BYTES
+            )));
+        }
+        self.ca_certificates()
+            .map_err(|e| ServiceError::InvalidInput(format!("callback_tls: {}", e)))?;
+        if let Some(id) = &self.client_identity_id {
+            if !identities.contains(id) {
+                return Err(ServiceError::InvalidInput(format!(
+                    "callback_tls: unknown client identity {}",
+                    id
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A client certificate and its encrypted private key
+struct ClientIdentity {
+    summary: ClientIdentitySummary,
+    certificate_pem: String,
+    /// Nonce followed by the ChaCha20-Poly1305 ciphertext of the key PEM
+    encrypted_key: Vec<u8>,
+}
+
+/// What the admin API shows of a client identity; never the key
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub struct ClientIdentitySummary {
+    pub id: String,
+    pub name: String,
+    /// SHA-256 of the certificate PEM
+    pub certificate_sha256: String,
+    pub created_at: u64,
+}
+
+/// Client identities available to callback deliveries
+pub struct ClientIdentities {
+    cipher: ChaCha20Poly1305,
+    identities: RwLock<HashMap<String, ClientIdentity>>,
+}
+
+impl ClientIdentities {
+    pub fn new(master_key: &[u8]) -> Result<Self, ServiceError> {
+        let mut key = [0u8; 32];
+        Hkdf::<Sha256>::new(None, master_key)
+            .expand(HKDF_INFO, &mut key)
+            .map_err(|e| ServiceError::EncryptionError(format!("HKDF expansion failed: {}", e)))?;
+        let cipher = ChaCha20Poly1305::new_from_slice(&key)
+            .map_err(|e| ServiceError::EncryptionError(format!("Failed to create cipher: {}", e)))?;
+
+        Ok(Self {
+            cipher,
+            identities: RwLock::new(HashMap::new()),
+        })
+    }
+
+    /// Store a PEM certificate chain and PEM private key (PKCS#8, RSA or SEC1)
+    pub fn create(
+        &self,
+        name: String,
+        certificate_pem: String,
+        private_key_pem: &str,
+    ) -> Result<ClientIdentitySummary, ServiceError> {
+        parse_identity(&certificate_pem, private_key_pem.as_bytes())
+            .map_err(|e| ServiceError::InvalidInput(format!("Invalid client identity: {}", e)))?;
+
+        let mut nonce = [0u8; NONCE_SIZE];
+        rand::thread_rng().fill(&mut nonce);
+        let ciphertext = self
+            .cipher
+            .encrypt(&Nonce::from(nonce), private_key_pem.as_bytes())
+            .map_err(|e| ServiceError::EncryptionError(format!("Encryption failed: {}", e)))?;
+
+        let summary = ClientIdentitySummary {
+            id: format!("cid_{}", hex::encode(rand::random::<[u8; 8]>())),
+            name,
+            certificate_sha256: hex::encode(Sha256::digest(certificate_pem.as_bytes())),
+            created_at: unix_timestamp(),
+        };
+        let identity = ClientIdentity {
+            summary: summary.clone(),
+            certificate_pem,
+            encrypted_key: [nonce.as_slice(), &ciphertext].concat(),
+        };
+        self.identities.write().unwrap().insert(summary.id.clone(), identity);
+        Ok(summary)
+    }
+
+    pub fn list(&self) -> Vec<ClientIdentitySummary> {
+        let mut list: Vec<_> = self
+            .identities
+            .read()
+            .unwrap()
+            .values()
+            .map(|i| i.summary.clone())
+            .collect();
+        list.sort_by_key(|s| s.created_at);
+        list
+    }
+
+    pub fn contains(&self, id: &str) -> bool {
+        self.identities.read().unwrap().contains_key(id)
+    }
+
+    /// Delete an identity, returning whether it existed
+    pub fn remove(&self, id: &str) -> bool {
+        self.identities.write().unwrap().remove(id).is_some()
+    }
+
+    /// Decrypt an identity for a TLS client
+    pub fn identity(&self, id: &str) -> Result<Identity, String> {
+        let identities = self.identities.read().unwrap();
+        let identity = identities
+            .get(id)
+            .ok_or_else(|| format!("client identity {} does not exist", id))?;
+
+        let (nonce, ciphertext) = identity.encrypted_key.split_at(NONCE_SIZE);
+        let nonce: [u8; NONCE_SIZE] = nonce.try_into().expect("nonce is stored first");
+        let key_pem = self
+            .cipher
+            .decrypt(&Nonce::from(nonce), ciphertext)
+            .map_err(|_| format!("client identity {} could not be decrypted", id))?;
+        parse_identity(&identity.certificate_pem, &key_pem)
+            .map_err(|e| format!("client identity {} is invalid: {}", id, e))
+    }
+}
+
+/// Identities are presented over rustls, which takes the key and chain as
+/// one PEM; building a client checks the key is usable
+fn parse_identity(certificate_pem: &str, private_key_pem: &[u8]) -> Result<Identity, reqwest::Error> {
+    let pem = [private_key_pem, b"\n", certificate_pem.as_bytes()].concat();
+    let identity = Identity::from_pem(&pem)?;
+    reqwest::Client::builder()
+        .use_rustls_tls()
+        .identity(identity.clone())
+        .build()?;
+    Ok(identity)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    pub fn fixture(name: &str) -> String {
+        let path = format!("{}/tests/fixtures/tls/{}", env!("CARGO_MANIFEST_DIR"), name);
+        std::fs::read_to_string(path).expect("missing TLS fixture")
+    }
+
+    pub fn identities() -> ClientIdentities {
+        ClientIdentities::new(&[7u8; 32]).unwrap()
+    }
+
+    #[test]
+    fn test_identity_key_is_stored_encrypted() {
+        let identities = identities();
+        let key = fixture("client_key.pem");
+        let summary = identities
+            .create("acme".to_string(), fixture("client_cert.pem"), &key)
+            .unwrap();
+
+        let stored = identities.identities.read().unwrap();
+        let encrypted = &stored[&summary.id].encrypted_key;
+        assert!(!encrypted.windows(16).any(|w| key.as_bytes().windows(16).any(|k| k == w)));
+        drop(stored);
+
+        assert!(identities.identity(&summary.id).is_ok());
+        assert_eq!(identities.list().len(), 1);
+        assert!(identities.remove(&summary.id));
+        assert!(identities.identity(&summary.id).is_err());
+    }
+
+    #[test]
+    fn test_rejects_mismatched_identity() {
+        let result = identities().create("acme".to_string(), fixture("client_cert.pem"), "not a key");
+        assert!(matches!(result, Err(ServiceError::InvalidInput(_))));
+    }
+
+    #[test]
+    fn test_validate_and_cache_key() {
+        let identities = identities();
+        let ca = CallbackTls {
+            ca_bundle: Some(fixture("client_cert.pem")),
+            client_identity_id: None,
+        };
+        assert!(ca.validate(&identities).is_ok());
+
+        let garbage = CallbackTls {
+            ca_bundle: Some("-----BEGIN CERTIFICATE-----\nnope\n-----END CERTIFICATE-----\n".to_string()),
+            client_identity_id: None,
+        };
+        assert!(garbage.validate(&identities).is_err());
+
+        let unknown = CallbackTls {
+            ca_bundle: None,
+            client_identity_id: Some("cid_missing".to_string()),
+        };
+        assert!(unknown.validate(&identities).is_err());
+
+        assert_eq!(ca.cache_key(), ca.clone().cache_key());
+        assert_ne!(ca.cache_key(), unknown.cache_key());
+    }
+}
@@ -1,6 +1,13 @@
 use std::env;
 use thiserror::Error;
 
+use crate::endpoint_switches::Endpoint;
+use crate::payload::PayloadLimits;
+use crate::payroll_ingest::IngestLimits;
+use crate::rpc::{Commitment, RpcLimits};
+use crate::sla::SlaThresholds;
+use crate::strict_mode;
+
 #[derive(Debug, Clone)]
 pub struct Config {
     pub host: String,
@@ -10,7 +17,38 @@ pub struct Config {
     pub arcium_program_id: String,
     pub encryption_master_key: Vec<u8>,
     pub callback_secret: String,
+    pub callback_receiver_url: String,
+    pub allow_private_callbacks: bool,
     pub solana_rpc_url: String,
+    pub solana_default_commitment: Commitment,
+    pub rpc_limits: RpcLimits,
+    pub usdc_mint: String,
+    pub flagged_wallets: Vec<String>,
+    pub admin_api_token: Option<String>,
+    pub status_rate_limit_per_minute: u32,
+    pub instance_id: String,
+    pub leader_lease_ttl_secs: u64,
+    pub leader_max_clock_skew_secs: u64,
+    pub warmup_pubkeys: Vec<String>,
+    pub payroll_ingest: IngestLimits,
+    pub payload_limits: PayloadLimits,
+    pub vault_program_id: String,
+    pub fee_audit_interval_secs: u64,
+    pub vault_stats_index_interval_secs: u64,
+    pub vault_stats_compact_interval_secs: u64,
+    pub fee_history_index_interval_secs: u64,
+    pub reorg_check_interval_secs: u64,
+    pub reorg_callback_url: Option<String>,
+    pub sla_check_interval_secs: u64,
+    pub sla_thresholds: SlaThresholds,
+    pub sla_alert_url: Option<String>,
+    pub sla_auto_escalate: bool,
+    pub computation_retention_secs: u64,
+    pub disabled_endpoints: Vec<Endpoint>,
+    pub strict_mode_default: bool,
+    pub strict_mode_keys: Vec<String>,
+    pub legacy_mode_keys: Vec<String>,
+    pub shutdown_drain_secs: u64,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -68,9 +106,170 @@ impl Config {
         let callback_secret = env::var("ARCIUM_CALLBACK_SECRET")
             .unwrap_or_else(|_| hex::encode(rand::random::<[u8; 32]>()));
 
+        // Where the cluster posts results; this service relays them to callers
+        let callback_receiver_url = env::var("CALLBACK_RECEIVER_URL")
+            .unwrap_or_else(|_| format!("http://localhost:{}/api/callbacks/arcium", port));
+
+        // Only for local development: permits callbacks to private addresses
+        let allow_private_callbacks = env::var("CALLBACK_ALLOW_PRIVATE_HOSTS")
+            .map(|v| v == "true")
+            .unwrap_or(false);
+
         let solana_rpc_url = env::var("SOLANA_RPC_URL")
             .unwrap_or_else(|_| "https://api.devnet.solana.com".to_string());
 
+        let solana_default_commitment = env::var("SOLANA_DEFAULT_COMMITMENT")
+            .unwrap_or_else(|_| "confirmed".to_string())
+            .parse()
+            .map_err(|e| ConfigError::InvalidValue(format!("SOLANA_DEFAULT_COMMITMENT: {}", e)))?;
+
+        // Shared across every chain read; keep under the RPC provider's limit
+        let rpc_limits = RpcLimits {
+            max_requests_per_second: parse_env("SOLANA_RPC_MAX_RPS", 40)? as u32,
+            coalesce_window_ms: parse_env("SOLANA_RPC_COALESCE_MS", 10)? as u64,
+            account_cache_ms: parse_env("SOLANA_ACCOUNT_CACHE_MS", 2000)? as u64,
+        };
+
+        let usdc_mint = env::var("USDC_MINT")
+            .unwrap_or_else(|_| "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v".to_string());
+
+        // Known exchange deposit addresses, one base58 pubkey per line
+        let flagged_wallets = match env::var("FLAGGED_WALLETS_FILE") {
+            Ok(path) => std::fs::read_to_string(&path)
+                .map_err(|_| ConfigError::InvalidValue(format!("Cannot read FLAGGED_WALLETS_FILE: {}", path)))?
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                .map(String::from)
+                .collect(),
+            Err(_) => Vec::new(),
+        };
+
+        // Admin endpoints are disabled unless a token is configured
+        let admin_api_token = env::var("ADMIN_API_TOKEN").ok().filter(|t| !t.is_empty());
+
+        let status_rate_limit_per_minute = env::var("STATUS_RATE_LIMIT_PER_MINUTE")
+            .unwrap_or_else(|_| "60".to_string())
+            .parse()
+            .map_err(|_| {
+                ConfigError::InvalidValue("STATUS_RATE_LIMIT_PER_MINUTE must be a number".to_string())
+            })?;
+
+        // Identifies this instance in the leader lease; must differ per region
+        let instance_id = env::var("INSTANCE_ID")
+            .unwrap_or_else(|_| format!("arcium-{}", hex::encode(rand::random::<[u8; 4]>())));
+
+        let leader_lease_ttl_secs: u64 = env::var("LEADER_LEASE_TTL_SECS")
+            .unwrap_or_else(|_| "15".to_string())
+            .parse()
+            .map_err(|_| ConfigError::InvalidValue("LEADER_LEASE_TTL_SECS must be a number".to_string()))?;
+
+        let leader_max_clock_skew_secs: u64 = env::var("LEADER_MAX_CLOCK_SKEW_SECS")
+            .unwrap_or_else(|_| "2".to_string())
+            .parse()
+            .map_err(|_| {
+                ConfigError::InvalidValue("LEADER_MAX_CLOCK_SKEW_SECS must be a number".to_string())
+            })?;
+
+        if leader_max_clock_skew_secs * 2 >= leader_lease_ttl_secs {
+            return Err(ConfigError::InvalidValue(
+                "LEADER_LEASE_TTL_SECS must be more than twice LEADER_MAX_CLOCK_SKEW_SECS".to_string(),
+            ));
+        }
+
+        // User pubkeys whose ciphers are initialized before serving traffic
+        let warmup_pubkeys = env::var("WARMUP_PUBKEYS")
+            .map(|v| {
+                v.split(',')
+                    .map(str::trim)
+                    .filter(|k| !k.is_empty())
+                    .map(String::from)
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        // Streamed NDJSON payroll uploads
+        let payroll_ingest = IngestLimits {
+            max_rows: parse_env("PAYROLL_NDJSON_MAX_ROWS", 100_000)?,
+            max_bytes: parse_env("PAYROLL_NDJSON_MAX_BYTES", 32 * 1024 * 1024)?,
+            chunk_size: parse_env("PAYROLL_CHUNK_SIZE", 500)?,
+        };
+        if payroll_ingest.chunk_size == 0 {
+            return Err(ConfigError::InvalidValue("PAYROLL_CHUNK_SIZE must be positive".to_string()));
+        }
+
+        // Stored computation params and results
+        let payload_limits = PayloadLimits {
+            compress_threshold_bytes: parse_env("PAYLOAD_COMPRESS_THRESHOLD_BYTES", 4 * 1024)?,
+            max_stored_bytes: parse_env("PAYLOAD_MAX_STORED_BYTES", 1024 * 1024)?,
+        };
+
+        let vault_program_id = env::var("VAULT_PROGRAM_ID")
+            .unwrap_or_else(|_| "NJPvau1tPBHrRxUqrLvhLq3zDpNZRGpNPdTpP1Dvq6C".to_string());
+
+        // How often the leader recomputes on-chain fees; 0 disables the audit
+        let fee_audit_interval_secs = parse_env("FEE_AUDIT_INTERVAL_SECS", 3600)? as u64;
+
+        // Vault stats: how often new PaymentRecords are indexed (0 disables
+        // stats) and how often they are compacted into daily rollups
+        let vault_stats_index_interval_secs = parse_env("VAULT_STATS_INDEX_INTERVAL_SECS", 30)? as u64;
+        let vault_stats_compact_interval_secs = parse_env("VAULT_STATS_COMPACT_INTERVAL_SECS", 300)? as u64;
+        if vault_stats_compact_interval_secs == 0 {
+            return Err(ConfigError::InvalidValue(
+                "VAULT_STATS_COMPACT_INTERVAL_SECS must be positive".to_string(),
+            ));
+        }
+
+        // How often VaultConfig transactions are scanned for fee changes; 0
+        // disables the fee history
+        let fee_history_index_interval_secs = parse_env("FEE_HISTORY_INDEX_INTERVAL_SECS", 60)? as u64;
+
+        // How often payments indexed below `finalized` are re-checked for
+        // reorgs (0 disables), and where `payment.reverted` events are sent
+        let reorg_check_interval_secs = parse_env("REORG_CHECK_INTERVAL_SECS", 30)? as u64;
+        let reorg_callback_url = env::var("REORG_CALLBACK_URL").ok().filter(|url| !url.is_empty());
+
+        // How often open computations are checked against their latency SLA
+        // (0 disables), the per-type thresholds (0 exempts the type), where
+        // breaches are POSTed, and whether the cluster is re-queried for them
+        let sla_check_interval_secs = parse_env("SLA_CHECK_INTERVAL_SECS", 60)? as u64;
+        let sla_thresholds = SlaThresholds {
+            payment_settlement_secs: parse_env("SLA_PAYMENT_SETTLEMENT_SECS", 600)? as u64,
+            payroll_settlement_secs: parse_env("SLA_PAYROLL_SETTLEMENT_SECS", 1800)? as u64,
+        };
+        let sla_alert_url = env::var("SLA_ALERT_URL").ok().filter(|url| !url.is_empty());
+        let sla_auto_escalate = env::var("SLA_AUTO_ESCALATE")
+            .map(|v| v == "true")
+            .unwrap_or(false);
+
+        // How long completed and failed computations, with their timelines,
+        // are kept after their last update (0 keeps them for the process lifetime)
+        let computation_retention_secs = parse_env("COMPUTATION_RETENTION_SECS", 0)? as u64;
+
+        // Endpoints switched off at startup; the admin API toggles them at runtime
+        let disabled_endpoints = env::var("DISABLED_ENDPOINTS")
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .map(|name| {
+                Endpoint::parse(name)
+                    .map_err(|e| ConfigError::InvalidValue(format!("DISABLED_ENDPOINTS: {}", e)))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // Strict mode rejects deprecated request formats; keys are listed by
+        // id (see strict_mode::key_id) to override the default either way
+        let strict_mode_default = env::var("STRICT_MODE_DEFAULT")
+            .map(|v| v == "true")
+            .unwrap_or(false);
+        let strict_mode_keys = parse_key_ids("STRICT_MODE_KEYS")?;
+        let legacy_mode_keys = parse_key_ids("LEGACY_MODE_KEYS")?;
+
+        // After SIGTERM, how long readiness fails while requests are still
+        // served, before the graceful stop
+        let shutdown_drain_secs = parse_env("SHUTDOWN_DRAIN_SECS", 10)? as u64;
+
         Ok(Config {
             host,
             port,
@@ -79,7 +278,59 @@ impl Config {
             arcium_program_id,
             encryption_master_key,
             callback_secret,
+            callback_receiver_url,
+            allow_private_callbacks,
             solana_rpc_url,
+            solana_default_commitment,
+            rpc_limits,
+            usdc_mint,
+            flagged_wallets,
+            admin_api_token,
+            status_rate_limit_per_minute,
+            instance_id,
+            leader_lease_ttl_secs,
+            leader_max_clock_skew_secs,
+            warmup_pubkeys,
+            payroll_ingest,
+            payload_limits,
+            vault_program_id,
+            fee_audit_interval_secs,
+            vault_stats_index_interval_secs,
+            vault_stats_compact_interval_secs,
+            fee_history_index_interval_secs,
+            reorg_check_interval_secs,
+            reorg_callback_url,
+            sla_check_interval_secs,
+            sla_thresholds,
+            sla_alert_url,
+            sla_auto_escalate,
+            computation_retention_secs,
+            disabled_endpoints,
+            strict_mode_default,
+            strict_mode_keys,
+            legacy_mode_keys,
+            shutdown_drain_secs,
         })
     }
 }
+
+fn parse_key_ids(name: &str) -> Result<Vec<String>, ConfigError> {
+    env::var(name)
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|id| !id.is_empty())
+        .map(|id| {
+            strict_mode::parse_key_id(id).map_err(|e| ConfigError::InvalidValue(format!("{}: {}", name, e)))
+        })
+        .collect()
+}
+
+fn parse_env(name: &str, default: usize) -> Result<usize, ConfigError> {
+    match env::var(name) {
+        Ok(value) => value
+            .parse()
+            .map_err(|_| ConfigError::InvalidValue(format!("{} must be a number", name))),
+        Err(_) => Ok(default),
+    }
+}
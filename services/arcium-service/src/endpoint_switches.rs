@@ -0,0 +1,189 @@
+use actix_web::http::Method;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::RwLock;
+
+use crate::error::ServiceError;
+use crate::store::unix_timestamp;
+
+/// Public endpoints an operator can switch off at runtime. Health, status,
+/// callbacks and the admin API are never switchable.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Endpoint {
+    Encrypt,
+    Decrypt,
+    PaymentSettlement,
+    PayrollSettlement,
+    VerifyCommitment,
+    VaultStats,
+}
+
+impl Endpoint {
+    pub const ALL: &'static [Endpoint] = &[
+        Endpoint::Encrypt,
+        Endpoint::Decrypt,
+        Endpoint::PaymentSettlement,
+        Endpoint::PayrollSettlement,
+        Endpoint::VerifyCommitment,
+        Endpoint::VaultStats,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Endpoint::Encrypt => "encrypt",
+            Endpoint::Decrypt => "decrypt",
+            Endpoint::PaymentSettlement => "payment_settlement",
+            Endpoint::PayrollSettlement => "payroll_settlement",
+            Endpoint::VerifyCommitment => "verify_commitment",
+            Endpoint::VaultStats => "vault_stats",
+        }
+    }
+
+    pub fn parse(name: &str) -> Result<Self, ServiceError> {
+        Self::ALL.iter().copied().find(|e| e.as_str() == name).ok_or_else(|| {
+            let names: Vec<_> = Self::ALL.iter().map(|e| e.as_str()).collect();
+            ServiceError::InvalidInput(format!("endpoint must be one of: {}", names.join(", ")))
+        })
+    }
+
+    /// The switchable endpoint serving a request, under either API version
+    pub fn for_request(method: &Method, path: &str) -> Option<Self> {
+        let path = path
+            .strip_prefix("/api/v1")
+            .or_else(|| path.strip_prefix("/api/v2"))?
+            .trim_end_matches('/');
+
+        match (method, path) {
+            (&Method::POST, "/encrypt") => Some(Endpoint::Encrypt),
+            (&Method::POST, "/decrypt") => Some(Endpoint::Decrypt),
+            (&Method::POST, "/computations/payment") => Some(Endpoint::PaymentSettlement),
+            (&Method::POST, "/computations/payroll" | "/computations/payroll/ndjson") => {
+                Some(Endpoint::PayrollSettlement)
+            }
+            (&Method::POST, "/verify-commitment" | "/verify-commitments") => Some(Endpoint::VerifyCommitment),
+            (&Method::GET, p) if p == "/vault/stats" || p.starts_with("/vault/stats/") => {
+                Some(Endpoint::VaultStats)
+            }
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DisabledEndpoint {
+    pub endpoint: Endpoint,
+    /// Shown to callers in the 503 response
+    pub message: Option<String>,
+    pub disabled_at: u64,
+}
+
+/// Kill-switches for public endpoints, seeded from `DISABLED_ENDPOINTS`
+/// and toggled through the admin API
+pub struct EndpointSwitches {
+    disabled: RwLock<BTreeMap<Endpoint, DisabledEndpoint>>,
+}
+
+impl EndpointSwitches {
+    pub fn new(disabled: &[Endpoint]) -> Self {
+        let switches = Self {
+            disabled: RwLock::new(BTreeMap::new()),
+        };
+        for &endpoint in disabled {
+            switches.disable(endpoint, None);
+        }
+        switches
+    }
+
+    pub fn disabled(&self) -> Vec<DisabledEndpoint> {
+        self.disabled.read().unwrap().values().cloned().collect()
+    }
+
+    /// Disable an endpoint, replacing the message if it already was
+    pub fn disable(&self, endpoint: Endpoint, message: Option<String>) -> DisabledEndpoint {
+        let entry = DisabledEndpoint {
+            endpoint,
+            message,
+            disabled_at: unix_timestamp(),
+        };
+        self.disabled.write().unwrap().insert(endpoint, entry.clone());
+        entry
+    }
+
+    /// Re-enable an endpoint, returning whether it was disabled
+    pub fn enable(&self, endpoint: Endpoint) -> bool {
+        self.disabled.write().unwrap().remove(&endpoint).is_some()
+    }
+
+    /// Reject a request to a disabled endpoint with `ENDPOINT_DISABLED`
+    pub fn check(&self, method: &Method, path: &str) -> Result<(), ServiceError> {
+        let Some(endpoint) = Endpoint::for_request(method, path) else {
+            return Ok(());
+        };
+        match self.disabled.read().unwrap().get(&endpoint) {
+            Some(DisabledEndpoint { message: Some(message), .. }) => Err(ServiceError::EndpointDisabled(format!(
+                "The {} endpoint is temporarily disabled: {}",
+                endpoint.as_str(),
+                message
+            ))),
+            Some(_) => Err(ServiceError::EndpointDisabled(format!(
+                "The {} endpoint is temporarily disabled",
+                endpoint.as_str()
+            ))),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_for_request() {
+        assert_eq!(Endpoint::for_request(&Method::POST, "/api/v1/decrypt"), Some(Endpoint::Decrypt));
+        assert_eq!(Endpoint::for_request(&Method::POST, "/api/v2/decrypt/"), Some(Endpoint::Decrypt));
+        assert_eq!(
+            Endpoint::for_request(&Method::POST, "/api/v2/computations/payroll/ndjson"),
+            Some(Endpoint::PayrollSettlement)
+        );
+        assert_eq!(
+            Endpoint::for_request(&Method::GET, "/api/v1/vault/stats/merchants/abc"),
+            Some(Endpoint::VaultStats)
+        );
+        assert_eq!(Endpoint::for_request(&Method::GET, "/api/v1/computations/pay_1"), None);
+        assert_eq!(Endpoint::for_request(&Method::POST, "/api/v1/admin/endpoints/decrypt/enable"), None);
+        assert_eq!(Endpoint::for_request(&Method::GET, "/api/health"), None);
+    }
+
+    #[test]
+    fn test_disable_and_enable() {
+        let switches = EndpointSwitches::new(&[Endpoint::PayrollSettlement]);
+        assert!(matches!(
+            switches.check(&Method::POST, "/api/v1/computations/payroll"),
+            Err(ServiceError::EndpointDisabled(_))
+        ));
+        assert!(switches.check(&Method::POST, "/api/v1/decrypt").is_ok());
+
+        switches.disable(Endpoint::Decrypt, Some("Investigating key rotation".to_string()));
+        match switches.check(&Method::POST, "/api/v2/decrypt") {
+            Err(ServiceError::EndpointDisabled(msg)) => assert!(msg.ends_with("Investigating key rotation")),
+            other => panic!("expected EndpointDisabled, got {:?}", other),
+        }
+        let disabled: Vec<_> = switches.disabled().iter().map(|d| d.endpoint).collect();
+        assert_eq!(disabled, [Endpoint::Decrypt, Endpoint::PayrollSettlement]);
+
+        assert!(switches.enable(Endpoint::Decrypt));
+        assert!(!switches.enable(Endpoint::Decrypt));
+        assert!(switches.check(&Method::POST, "/api/v2/decrypt").is_ok());
+    }
+
+    #[test]
+    fn test_parse() {
+        for &endpoint in Endpoint::ALL {
+            assert_eq!(Endpoint::parse(endpoint.as_str()).unwrap(), endpoint);
+            assert_eq!(serde_json::to_value(endpoint).unwrap(), endpoint.as_str());
+        }
+        assert!(Endpoint::parse("admin").is_err());
+    }
+}
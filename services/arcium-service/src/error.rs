@@ -1,4 +1,6 @@
+use actix_web::http::StatusCode;
 use actix_web::{HttpResponse, ResponseError};
+use ninjapay_types::ErrorCode;
 use serde::Serialize;
 use std::fmt;
 
@@ -7,7 +9,21 @@ pub enum ServiceError {
     EncryptionError(String),
     DecryptionError(String),
     MpcError(String),
+    RpcError(String),
     InvalidInput(String),
+    Unauthorized(String),
+    Forbidden(String),
+    NotFound(String),
+    /// Too many requests; carries seconds until the client may retry
+    RateLimited(u64),
+    /// Streamed upload over its row or byte limit
+    PayloadTooLarge(String),
+    /// Authentic cluster callback that failed schema validation
+    CallbackQuarantined(String),
+    /// Public endpoint switched off by an operator
+    EndpointDisabled(String),
+    /// Strict-mode key sent a deprecated request format
+    DeprecatedFormat(String),
     InternalError(String),
     ConfigError(String),
 }
@@ -18,7 +34,16 @@ impl fmt::Display for ServiceError {
             ServiceError::EncryptionError(msg) => write!(f, "Encryption error: {}", msg),
             ServiceError::DecryptionError(msg) => write!(f, "Decryption error: {}", msg),
             ServiceError::MpcError(msg) => write!(f, "MPC error: {}", msg),
+            ServiceError::RpcError(msg) => write!(f, "RPC error: {}", msg),
             ServiceError::InvalidInput(msg) => write!(f, "Invalid input: {}", msg),
+            ServiceError::Unauthorized(msg) => write!(f, "Unauthorized: {}", msg),
+            ServiceError::Forbidden(msg) => write!(f, "Forbidden: {}", msg),
+            ServiceError::NotFound(msg) => write!(f, "Not found: {}", msg),
+            ServiceError::RateLimited(secs) => write!(f, "Rate limited: retry after {}s", secs),
+            ServiceError::PayloadTooLarge(msg) => write!(f, "Payload too large: {}", msg),
+            ServiceError::CallbackQuarantined(msg) => write!(f, "Callback quarantined: {}", msg),
+            ServiceError::EndpointDisabled(msg) => write!(f, "Endpoint disabled: {}", msg),
+            ServiceError::DeprecatedFormat(msg) => write!(f, "Deprecated format: {}", msg),
             ServiceError::InternalError(msg) => write!(f, "Internal error: {}", msg),
             ServiceError::ConfigError(msg) => write!(f, "Config error: {}", msg),
         }
@@ -35,39 +60,125 @@ struct ErrorResponse {
 
 #[derive(Serialize)]
 struct ErrorDetail {
-    code: String,
+    code: ErrorCode,
     message: String,
 }
 
+impl ServiceError {
+    /// The catalogued code this error is reported under
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            ServiceError::EncryptionError(_) => ErrorCode::EncryptionError,
+            ServiceError::DecryptionError(_) => ErrorCode::DecryptionError,
+            ServiceError::MpcError(_) => ErrorCode::MpcError,
+            ServiceError::RpcError(_) => ErrorCode::RpcError,
+            ServiceError::InvalidInput(_) => ErrorCode::InvalidInput,
+            ServiceError::Unauthorized(_) => ErrorCode::Unauthorized,
+            ServiceError::Forbidden(_) => ErrorCode::Forbidden,
+            ServiceError::NotFound(_) => ErrorCode::NotFound,
+            ServiceError::RateLimited(_) => ErrorCode::RateLimited,
+            ServiceError::PayloadTooLarge(_) => ErrorCode::PayloadTooLarge,
+            ServiceError::CallbackQuarantined(_) => ErrorCode::CallbackQuarantined,
+            ServiceError::EndpointDisabled(_) => ErrorCode::EndpointDisabled,
+            ServiceError::DeprecatedFormat(_) => ErrorCode::DeprecatedFormat,
+            ServiceError::InternalError(_) => ErrorCode::InternalError,
+            ServiceError::ConfigError(_) => ErrorCode::ConfigError,
+        }
+    }
+
+    fn message(&self) -> String {
+        match self {
+            ServiceError::EncryptionError(msg)
+            | ServiceError::DecryptionError(msg)
+            | ServiceError::MpcError(msg)
+            | ServiceError::RpcError(msg)
+            | ServiceError::InvalidInput(msg)
+            | ServiceError::Unauthorized(msg)
+            | ServiceError::Forbidden(msg)
+            | ServiceError::NotFound(msg)
+            | ServiceError::PayloadTooLarge(msg)
+            | ServiceError::CallbackQuarantined(msg)
+            | ServiceError::EndpointDisabled(msg)
+            | ServiceError::DeprecatedFormat(msg)
+            | ServiceError::InternalError(msg)
+            | ServiceError::ConfigError(msg) => msg.clone(),
+            ServiceError::RateLimited(secs) => format!("Too many requests, retry after {}s", secs),
+        }
+    }
+}
+
 impl ResponseError for ServiceError {
+    fn status_code(&self) -> StatusCode {
+        StatusCode::from_u16(self.code().http_status()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR)
+    }
+
     fn error_response(&self) -> HttpResponse {
-        let (status, code, message) = match self {
-            ServiceError::EncryptionError(msg) => {
-                (actix_web::http::StatusCode::INTERNAL_SERVER_ERROR, "ENCRYPTION_ERROR", msg.clone())
-            }
-            ServiceError::DecryptionError(msg) => {
-                (actix_web::http::StatusCode::BAD_REQUEST, "DECRYPTION_ERROR", msg.clone())
-            }
-            ServiceError::MpcError(msg) => {
-                (actix_web::http::StatusCode::SERVICE_UNAVAILABLE, "MPC_ERROR", msg.clone())
-            }
-            ServiceError::InvalidInput(msg) => {
-                (actix_web::http::StatusCode::BAD_REQUEST, "INVALID_INPUT", msg.clone())
-            }
-            ServiceError::InternalError(msg) => {
-                (actix_web::http::StatusCode::INTERNAL_SERVER_ERROR, "INTERNAL_ERROR", msg.clone())
-            }
-            ServiceError::ConfigError(msg) => {
-                (actix_web::http::StatusCode::INTERNAL_SERVER_ERROR, "CONFIG_ERROR", msg.clone())
-            }
-        };
+        let mut response = HttpResponse::build(self.status_code());
+        if let ServiceError::RateLimited(secs) = self {
+            response.insert_header(("Retry-After", secs.to_string()));
+        }
 
-        HttpResponse::build(status).json(ErrorResponse {
+        response.json(ErrorResponse {
             success: false,
             error: ErrorDetail {
-                code: code.to_string(),
-                message,
+                code: self.code(),
+                message: self.message(),
             },
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn every_variant() -> Vec<ServiceError> {
+        let all = vec![
+            ServiceError::EncryptionError(String::new()),
+            ServiceError::DecryptionError(String::new()),
+            ServiceError::MpcError(String::new()),
+            ServiceError::RpcError(String::new()),
+            ServiceError::InvalidInput(String::new()),
+            ServiceError::Unauthorized(String::new()),
+            ServiceError::Forbidden(String::new()),
+            ServiceError::NotFound(String::new()),
+            ServiceError::RateLimited(1),
+            ServiceError::PayloadTooLarge(String::new()),
+            ServiceError::CallbackQuarantined(String::new()),
+            ServiceError::EndpointDisabled(String::new()),
+            ServiceError::DeprecatedFormat(String::new()),
+            ServiceError::InternalError(String::new()),
+            ServiceError::ConfigError(String::new()),
+        ];
+        // Adding a variant without listing it above fails to compile
+        for error in &all {
+            match error {
+                ServiceError::EncryptionError(_)
+                | ServiceError::DecryptionError(_)
+                | ServiceError::MpcError(_)
+                | ServiceError::RpcError(_)
+                | ServiceError::InvalidInput(_)
+                | ServiceError::Unauthorized(_)
+                | ServiceError::Forbidden(_)
+                | ServiceError::NotFound(_)
+                | ServiceError::RateLimited(_)
+                | ServiceError::PayloadTooLarge(_)
+                | ServiceError::CallbackQuarantined(_)
+                | ServiceError::EndpointDisabled(_)
+                | ServiceError::DeprecatedFormat(_)
+                | ServiceError::InternalError(_)
+                | ServiceError::ConfigError(_) => {}
+            }
+        }
+        all
+    }
+
+    #[test]
+    fn test_every_variant_is_catalogued() {
+        for error in every_variant() {
+            let code = error.code();
+            assert!(ErrorCode::ALL.contains(&code), "{} is not catalogued", code);
+            assert_eq!(error.error_response().status().as_u16(), code.http_status(), "{}", code);
+        }
+    }
+}
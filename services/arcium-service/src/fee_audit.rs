@@ -0,0 +1,440 @@
+//! Fee audit: recompute the protocol fee of every on-chain PaymentRecord
+//! with the shared `ninjapay_types::fee` math and report any payment where
+//! the program charged something else.
+//!
+//! PaymentRecord does not store the fee setting in force when it was
+//! written, so each record is checked against the fee the fee history
+//! timeline gives for its timestamp, or the current VaultConfig fee while
+//! the timeline is empty. A mismatch some other fee setting explains is
+//! reported as `fee_changed`; one that no setting explains is a rounding
+//! discrepancy between the program and our fee math, reported as
+//! `unexplained`. Merchants with a `MerchantConfig` fee override or fee
+//! exemption are charged the override or nothing, and mints with an active
+//! `VaultMintConfig` their own rate. The audit reads neither, so those
+//! payments show up as `fee_changed`. An exact-net payment's fee was
+//! charged on what the merchant received rather than on `amount`, which is
+//! gross of the fee on top. The audit assumes fees round down; while the
+//! vault has `round_fee_up` set, any fee that isn't a whole number of base
+//! units is one more and shows up as `unexplained`.
+
+use ninjapay_types::fee;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use solana_sdk::pubkey::Pubkey;
+use std::str::FromStr;
+use std::sync::RwLock;
+use tracing::{info, warn};
+
+use crate::config::Config;
+use crate::error::ServiceError;
+use crate::fee_history::{self, FeeChange, FeeHistory};
+use crate::rpc::{ChainClient, ChainContext, Commitment};
+use crate::store::unix_timestamp;
+
+// Account layouts from programs/ninjapay-vault, after the 8-byte discriminator
+const PAYMENT_RECORD_SIZE: usize = 8 + 32 + 32 + 32 + 8 + 8 + 32 + 8 + 1 + 1 + 32 + 8;
+// Records written before refund_payment have no status byte, those written
+// before the mint was recorded no mint, and those before payment expiry no
+// trailing expires_at. Versioned records add a version byte and 64 reserved
+// bytes, and version 2 each fee collector's key and share of the fee (three
+// of each) after them. Fully refunded records are closed and partial refunds leave the
+// fee charged, so every open record's fee was collected.
+const PAYMENT_RECORD_SIZES: [usize; 6] = [
+    PAYMENT_RECORD_SIZE - 41,
+    PAYMENT_RECORD_SIZE - 40,
+    PAYMENT_RECORD_SIZE - 8,
+    PAYMENT_RECORD_SIZE,
+    PAYMENT_RECORD_SIZE + 1 + 64,
+    PAYMENT_RECORD_SIZE + 1 + 64 + 3 * 32 + 3 * 8,
+];
+const VAULT_CONFIG_FEE_OFFSET: usize = 8 + 32 + 32;
+
+/// Discrepancies kept for the admin endpoint; all of them are logged
+const MAX_RECENT_DISCREPANCIES: usize = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DiscrepancyKind {
+    /// Consistent with a fee setting other than the one the timeline gives,
+    /// e.g. an update_fee not indexed yet
+    FeeChanged,
+    /// No valid fee setting charges this fee on this amount
+    Unexplained,
+}
+
+/// One payment whose on-chain fee differs from the recomputed one
+#[derive(Debug, Clone, Serialize)]
+pub struct FeeDiscrepancy {
+    pub payment_record: String,
+    pub payment_id: String,
+    pub merchant: String,
+    pub amount: u64,
+    /// Fee in force at the payment's timestamp
+    pub fee_basis_points: u16,
+    pub expected_fee: u64,
+    pub onchain_fee: u64,
+    /// Fee settings that reproduce the on-chain fee, as `[low, high]`
+    pub implied_basis_points: Option<[u16; 2]>,
+    pub timestamp: i64,
+    pub kind: DiscrepancyKind,
+}
+
+/// Totals from the latest audit run
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct FeeAuditReport {
+    pub last_run_at: Option<u64>,
+    pub chain_context: Option<ChainContext>,
+    /// Current VaultConfig fee
+    pub fee_basis_points: Option<u16>,
+    /// Fee history changes the records were checked against; 0 means all
+    /// of them were checked against the current fee
+    pub fee_history_changes: usize,
+    pub checked: usize,
+    pub matched: usize,
+    pub fee_changed: usize,
+    pub unexplained: usize,
+    /// Sum of (on-chain fee - expected fee) over all discrepancies
+    pub fee_delta: i128,
+    pub discrepancies: Vec<FeeDiscrepancy>,
+    pub last_error: Option<String>,
+}
+
+/// Fields of a PaymentRecord the audit and vault stats need
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct PaymentRecord {
+    pub payment_id: [u8; 32],
+    pub merchant: Pubkey,
+    pub amount: u64,
+    pub fee: u64,
+    pub timestamp: i64,
+    /// Token paid in; `None` for records that predate it
+    pub mint: Option<Pubkey>,
+    /// Paid in FeeMode::ExactNet, the fee on top of what the merchant
+    /// received
+    pub exact_net: bool,
+}
+
+impl PaymentRecord {
+    /// The amount the fee was charged on
+    fn fee_base(&self) -> u64 {
+        match self.exact_net {
+            true => self.amount.saturating_sub(self.fee),
+            false => self.amount,
+        }
+    }
+}
+
+pub struct FeeAudit {
+    program_id: Pubkey,
+    vault_config: Pubkey,
+    report: RwLock<FeeAuditReport>,
+}
+
+impl FeeAudit {
+    pub fn new(config: &Config) -> Result<Self, ServiceError> {
+        let program_id = Pubkey::from_str(&config.vault_program_id)
+            .map_err(|_| ServiceError::ConfigError("VAULT_PROGRAM_ID is not a valid pubkey".to_string()))?;
+        let (vault_config, _) = Pubkey::find_program_address(&[b"vault_config"], &program_id);
+
+        Ok(Self {
+            program_id,
+            vault_config,
+            report: RwLock::new(FeeAuditReport::default()),
+        })
+    }
+
+    pub fn report(&self) -> FeeAuditReport {
+        self.report.read().unwrap().clone()
+    }
+
+    /// Audit every payment record; failures are kept in `last_error`
+    pub async fn run(&self, chain: &ChainClient, history: &FeeHistory) {
+        match self.audit(chain, history).await {
+            Ok(report) => {
+                info!(
+                    checked = report.checked,
+                    matched = report.matched,
+                    fee_changed = report.fee_changed,
+                    unexplained = report.unexplained,
+                    "Fee audit finished"
+                );
+                *self.report.write().unwrap() = report;
+            }
+            Err(e) => {
+                warn!("Fee audit failed: {}", e);
+                self.report.write().unwrap().last_error = Some(e.to_string());
+            }
+        }
+    }
+
+    async fn audit(&self, chain: &ChainClient, history: &FeeHistory) -> Result<FeeAuditReport, ServiceError> {
+        let mut context = ChainContext::new(chain.default_commitment());
+        let vault_config = chain
+            .get_accounts(&[self.vault_config], &mut context)
+            .await?
+            .pop()
+            .flatten()
+            .ok_or_else(|| ServiceError::NotFound("VaultConfig is not initialized".to_string()))?;
+        let fee_basis_points = vault_config
+            .data
+            .get(VAULT_CONFIG_FEE_OFFSET..VAULT_CONFIG_FEE_OFFSET + 2)
+            .map(|b| u16::from_le_bytes([b[0], b[1]]))
+            .ok_or_else(|| ServiceError::RpcError("VaultConfig account is truncated".to_string()))?;
+
+        let records = fetch_payment_records(chain, &self.program_id, context.commitment).await?;
+        let mut report = audit_records(fee_basis_points, &history.timeline().changes, &records);
+        report.chain_context = Some(context);
+        Ok(report)
+    }
+}
+
+fn audit_records(
+    current_fee_basis_points: u16,
+    fee_changes: &[FeeChange],
+    records: &[(Pubkey, PaymentRecord)],
+) -> FeeAuditReport {
+    let mut report = FeeAuditReport {
+        last_run_at: Some(unix_timestamp()),
+        fee_basis_points: Some(current_fee_basis_points),
+        fee_history_changes: fee_changes.len(),
+        checked: records.len(),
+        ..Default::default()
+    };
+
+    for (key, record) in records {
+        let fee_basis_points = match fee_changes {
+            [] => current_fee_basis_points,
+            changes => match fee_history::fee_at(changes, record.timestamp) {
+                Some(change) => change.fee_basis_points,
+                // Older than the first indexed change: the fee it replaced
+                None => changes[0].previous_fee_basis_points.unwrap_or(changes[0].fee_basis_points),
+            },
+        };
+        let expected_fee = fee::protocol_fee(record.fee_base(), fee_basis_points);
+        if expected_fee == record.fee {
+            report.matched += 1;
+            continue;
+        }
+
+        let implied = fee::implied_basis_points(record.fee_base(), record.fee);
+        let kind = if implied.is_some() {
+            report.fee_changed += 1;
+            DiscrepancyKind::FeeChanged
+        } else {
+            report.unexplained += 1;
+            DiscrepancyKind::Unexplained
+        };
+        report.fee_delta += record.fee as i128 - expected_fee as i128;
+
+        let discrepancy = FeeDiscrepancy {
+            payment_record: key.to_string(),
+            payment_id: hex::encode(record.payment_id),
+            merchant: record.merchant.to_string(),
+            amount: record.amount,
+            fee_basis_points,
+            expected_fee,
+            onchain_fee: record.fee,
+            implied_basis_points: implied.map(|r| [*r.start(), *r.end()]),
+            timestamp: record.timestamp,
+            kind,
+        };
+        warn!(
+            payment_record = %discrepancy.payment_record,
+            payment_id = %discrepancy.payment_id,
+            merchant = %discrepancy.merchant,
+            amount = discrepancy.amount,
+            fee_basis_points = discrepancy.fee_basis_points,
+            expected_fee = discrepancy.expected_fee,
+            onchain_fee = discrepancy.onchain_fee,
+            implied_basis_points = ?discrepancy.implied_basis_points,
+            timestamp = discrepancy.timestamp,
+            kind = ?discrepancy.kind,
+            "Fee discrepancy"
+        );
+        if report.discrepancies.len() < MAX_RECENT_DISCREPANCIES {
+            report.discrepancies.push(discrepancy);
+        }
+    }
+
+    report
+}
+
+/// Every PaymentRecord of the vault program, by account address
+pub(crate) async fn fetch_payment_records(
+    chain: &ChainClient,
+    program_id: &Pubkey,
+    commitment: Commitment,
+) -> Result<Vec<(Pubkey, PaymentRecord)>, ServiceError> {
+    let mut accounts = Vec::new();
+    for size in PAYMENT_RECORD_SIZES {
+        accounts.extend(
+            chain
+                .get_program_accounts(program_id, size, &discriminator("PaymentRecord"), commitment)
+                .await?,
+        );
+    }
+    Ok(accounts
+        .into_iter()
+        .filter_map(|(key, account)| decode_payment_record(&account.data).map(|r| (key, r)))
+        .collect())
+}
+
+/// Anchor account discriminator: first 8 bytes of sha256("account:<Name>")
+fn discriminator(name: &str) -> [u8; 8] {
+    let hash = Sha256::digest(format!("account:{}", name).as_bytes());
+    hash[..8].try_into().unwrap()
+}
+
+fn decode_payment_record(data: &[u8]) -> Option<PaymentRecord> {
+    if !PAYMENT_RECORD_SIZES.contains(&data.len()) || data[..8] != discriminator("PaymentRecord")
+    {
+        return None;
+    }
+    let u64_at = |offset: usize| u64::from_le_bytes(data[offset..offset + 8].try_into().unwrap());
+
+    Some(PaymentRecord {
+        payment_id: data[8..40].try_into().unwrap(),
+        merchant: Pubkey::try_from(&data[72..104]).ok()?,
+        amount: u64_at(104),
+        fee: u64_at(112),
+        timestamp: u64_at(152) as i64,
+        mint: match data.len() {
+            len if len >= 194 => Some(Pubkey::try_from(&data[162..194]).ok()?),
+            _ => None,
+        },
+        // fee_mode, carved out of the reserved tail after refunded_amount
+        // and net_amount
+        exact_net: data.len() >= PAYMENT_RECORD_SIZES[4] && data[PAYMENT_RECORD_SIZE + 1 + 16] == 1,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record_data(payment_id: u8, amount: u64, fee: u64) -> Vec<u8> {
+        let mut data = discriminator("PaymentRecord").to_vec();
+        data.extend_from_slice(&[payment_id; 32]);
+        data.extend_from_slice(&[1; 32]); // payer
+        data.extend_from_slice(&[2; 32]); // merchant
+        data.extend_from_slice(&amount.to_le_bytes());
+        data.extend_from_slice(&fee.to_le_bytes());
+        data.extend_from_slice(&[3; 32]); // commitment
+        data.extend_from_slice(&1_760_000_000i64.to_le_bytes());
+        data.push(255); // bump
+        data.push(0); // status: settled
+        data.extend_from_slice(&[4; 32]); // mint
+        data.extend_from_slice(&0i64.to_le_bytes()); // expires_at
+        data
+    }
+
+    #[test]
+    fn test_decode_payment_record() {
+        let data = record_data(7, 1_999_999, 9_999);
+        assert_eq!(data.len(), PAYMENT_RECORD_SIZE);
+
+        let record = decode_payment_record(&data).unwrap();
+        assert_eq!(record.payment_id, [7; 32]);
+        assert_eq!(record.merchant, Pubkey::new_from_array([2; 32]));
+        assert_eq!((record.amount, record.fee), (1_999_999, 9_999));
+        assert_eq!(record.timestamp, 1_760_000_000);
+        assert_eq!(record.mint, Some(Pubkey::new_from_array([4; 32])));
+
+        let mut other = data.clone();
+        other[0] ^= 1;
+        assert!(decode_payment_record(&other).is_none());
+        assert!(decode_payment_record(&data[..100]).is_none());
+        for size in &PAYMENT_RECORD_SIZES[..2] {
+            let legacy = decode_payment_record(&data[..*size]).unwrap();
+            assert_eq!(legacy, PaymentRecord { mint: None, ..record.clone() });
+        }
+        assert_eq!(decode_payment_record(&data[..PAYMENT_RECORD_SIZES[2]]).unwrap(), record);
+
+        let mut versioned = data.clone();
+        versioned.push(1); // record_version
+        versioned.extend_from_slice(&[0; 64]);
+        assert_eq!(decode_payment_record(&versioned).unwrap(), record);
+
+        versioned[PAYMENT_RECORD_SIZE] = 2;
+        versioned.extend_from_slice(&[5; 3 * 32]); // fee_collectors
+        versioned.extend_from_slice(&[0; 3 * 8]); // fee_shares
+        assert_eq!(decode_payment_record(&versioned).unwrap(), record);
+    }
+
+    #[test]
+    fn test_exact_net_fee_is_checked_against_the_net_amount() {
+        // 2_000_000 to the merchant at 50 bps, the 10_000 fee on top
+        let mut versioned = record_data(1, 2_010_000, 10_000);
+        versioned.push(1); // record_version
+        versioned.extend_from_slice(&0u64.to_le_bytes()); // refunded_amount
+        versioned.extend_from_slice(&2_000_000u64.to_le_bytes()); // net_amount
+        versioned.push(1); // fee_mode: exact net
+        versioned.extend_from_slice(&[0; 64 - 17]);
+        assert!(decode_payment_record(&versioned).unwrap().exact_net);
+        versioned[PAYMENT_RECORD_SIZE] = 2;
+        versioned.extend_from_slice(&[0; 3 * 32 + 3 * 8]); // fee_collectors, fee_shares
+        let exact_net = decode_payment_record(&versioned).unwrap();
+        assert!(exact_net.exact_net);
+
+        // The same amounts with the fee deducted: no setting explains them
+        let deducted = decode_payment_record(&record_data(2, 2_010_000, 10_000)).unwrap();
+        let records = [(Pubkey::new_unique(), exact_net), (Pubkey::new_unique(), deducted)];
+        let report = audit_records(50, &[], &records);
+        assert_eq!((report.matched, report.unexplained), (1, 1));
+        assert_eq!(report.discrepancies[0].payment_id, hex::encode([2; 32]));
+    }
+
+    #[test]
+    fn test_classifies_discrepancies() {
+        let records: Vec<(Pubkey, PaymentRecord)> = [
+            record_data(1, 1_999_999, 9_999),  // 50 bps, rounded down
+            record_data(2, 2_000_000, 20_000), // charged at 100 bps
+            record_data(3, 1_999_999, 10_000), // rounded up: no setting gives this
+        ]
+        .iter()
+        .map(|data| (Pubkey::new_unique(), decode_payment_record(data).unwrap()))
+        .collect();
+
+        let report = audit_records(50, &[], &records);
+        assert_eq!(report.checked, 3);
+        assert_eq!(report.matched, 1);
+        assert_eq!(report.fee_changed, 1);
+        assert_eq!(report.unexplained, 1);
+        assert_eq!(report.fee_delta, (20_000 - 10_000) + (10_000 - 9_999));
+
+        let unexplained = &report.discrepancies[1];
+        assert_eq!(unexplained.kind, DiscrepancyKind::Unexplained);
+        assert_eq!(unexplained.expected_fee, 9_999);
+        assert_eq!(unexplained.implied_basis_points, None);
+        assert_eq!(report.discrepancies[0].implied_basis_points, Some([100, 100]));
+    }
+
+    #[test]
+    fn test_uses_the_fee_in_force_at_each_payment() {
+        let change = |fee_basis_points, previous, timestamp| FeeChange {
+            event: fee_history::FeeEvent::FeeUpdated,
+            fee_basis_points,
+            previous_fee_basis_points: previous,
+            slot: 0,
+            timestamp,
+            signature: String::new(),
+        };
+        // Records are stamped 1_760_000_000: 100 bps then, 50 bps now
+        let timeline = [change(100, Some(25), 1_700_000_000), change(50, Some(100), 1_760_000_001)];
+        let records: Vec<(Pubkey, PaymentRecord)> = [record_data(1, 2_000_000, 20_000), record_data(2, 1_999_999, 9_999)]
+            .iter()
+            .map(|data| (Pubkey::new_unique(), decode_payment_record(data).unwrap()))
+            .collect();
+
+        let report = audit_records(50, &timeline, &records);
+        assert_eq!(report.fee_history_changes, 2);
+        assert_eq!((report.matched, report.fee_changed), (1, 1));
+        assert_eq!(report.discrepancies[0].fee_basis_points, 100);
+        assert_eq!(report.discrepancies[0].expected_fee, 19_999);
+
+        // Before the first indexed change, the fee it replaced applies
+        let report = audit_records(50, &timeline[1..], &records);
+        assert_eq!(report.matched, 1);
+        assert_eq!(report.discrepancies[0].fee_basis_points, 100);
+    }
+}
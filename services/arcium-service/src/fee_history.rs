@@ -0,0 +1,378 @@
+//! Fee timeline: every fee_basis_points the vault has charged and when it
+//! took effect.
+//!
+//! VaultConfig only holds the current fee, so the timeline is rebuilt from
+//! the VaultInitialized and FeeUpdated events the program logs. The indexer
+//! scans VaultConfig's transaction signatures newer than the last one seen;
+//! a backfill rescans all of them and replaces the timeline. A scan that
+//! fails part way changes nothing, so the next pass starts from the same
+//! place.
+//!
+//! Changes are looked up by unix timestamp, which is what PaymentRecords
+//! carry. Block times have one-second resolution, so a payment in the same
+//! second as a fee change, but an earlier slot, resolves to the new fee.
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+use std::str::FromStr;
+use std::sync::RwLock;
+use tracing::{info, warn};
+
+use crate::config::Config;
+use crate::error::ServiceError;
+use crate::rpc::{ChainClient, MAX_SIGNATURES_PER_PAGE};
+use crate::store::unix_timestamp;
+
+// Event layouts from programs/ninjapay-vault, after the 8-byte discriminator
+const VAULT_INITIALIZED_SIZE: usize = 8 + 32 + 32 + 2;
+const FEE_UPDATED_SIZE: usize = 8 + 2 + 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FeeEvent {
+    VaultInitialized,
+    FeeUpdated,
+}
+
+/// A fee setting and the transaction that put it in force
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub struct FeeChange {
+    pub event: FeeEvent,
+    pub fee_basis_points: u16,
+    /// None for the initial fee
+    pub previous_fee_basis_points: Option<u16>,
+    pub slot: u64,
+    pub timestamp: i64,
+    pub signature: String,
+}
+
+/// The fee in force at a point in time
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub struct AppliedFee {
+    pub timestamp: i64,
+    pub fee_basis_points: u16,
+    pub effective_slot: u64,
+    pub effective_timestamp: i64,
+    pub signature: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub struct FeeTimeline {
+    /// Oldest first
+    pub changes: Vec<FeeChange>,
+    pub last_indexed_slot: Option<u64>,
+    pub last_indexed_at: Option<u64>,
+}
+
+/// What a backfill changed
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub struct FeeBackfillReport {
+    /// Transactions scanned
+    pub scanned: usize,
+    pub changes: usize,
+    pub added: usize,
+    pub removed: usize,
+}
+
+/// Result of one signature scan
+struct Scan {
+    changes: Vec<FeeChange>,
+    /// Newest transaction seen, where the next incremental scan stops
+    newest: Option<(Signature, u64)>,
+    scanned: usize,
+}
+
+#[derive(Default)]
+struct State {
+    changes: Vec<FeeChange>,
+    newest_signature: Option<Signature>,
+    last_indexed_slot: Option<u64>,
+    last_indexed_at: Option<u64>,
+}
+
+pub struct FeeHistory {
+    program_id: Pubkey,
+    vault_config: Pubkey,
+    state: RwLock<State>,
+    /// Serializes scans, so an index pass can't append to a timeline a
+    /// backfill has just replaced
+    scan_lock: tokio::sync::Mutex<()>,
+}
+
+impl FeeHistory {
+    pub fn new(config: &Config) -> Result<Self, ServiceError> {
+        let program_id = Pubkey::from_str(&config.vault_program_id)
+            .map_err(|_| ServiceError::ConfigError("VAULT_PROGRAM_ID is not a valid pubkey".to_string()))?;
+        Ok(Self::for_program(program_id))
+    }
+
+    fn for_program(program_id: Pubkey) -> Self {
+        let (vault_config, _) = Pubkey::find_program_address(&[b"vault_config"], &program_id);
+        Self {
+            program_id,
+            vault_config,
+            state: RwLock::new(State::default()),
+            scan_lock: tokio::sync::Mutex::new(()),
+        }
+    }
+
+    pub fn timeline(&self) -> FeeTimeline {
+        let state = self.state.read().unwrap();
+        FeeTimeline {
+            changes: state.changes.clone(),
+            last_indexed_slot: state.last_indexed_slot,
+            last_indexed_at: state.last_indexed_at,
+        }
+    }
+
+    /// The fee in force at `timestamp`
+    pub fn at(&self, timestamp: i64) -> Result<AppliedFee, ServiceError> {
+        let state = self.state.read().unwrap();
+        if state.changes.is_empty() {
+            return Err(ServiceError::NotFound("Fee history has not been indexed yet".to_string()));
+        }
+        let change = fee_at(&state.changes, timestamp).ok_or_else(|| {
+            ServiceError::NotFound(format!("The vault was not initialized at {}", timestamp))
+        })?;
+        Ok(AppliedFee {
+            timestamp,
+            fee_basis_points: change.fee_basis_points,
+            effective_slot: change.slot,
+            effective_timestamp: change.timestamp,
+            signature: change.signature.clone(),
+        })
+    }
+
+    /// Append fee changes from transactions since the last pass
+    pub async fn index(&self, chain: &ChainClient) {
+        let _scan = self.scan_lock.lock().await;
+        let until = self.state.read().unwrap().newest_signature;
+        match self.scan(chain, until).await {
+            Ok(scan) => {
+                let mut state = self.state.write().unwrap();
+                if !scan.changes.is_empty() {
+                    info!(changes = scan.changes.len(), "Fee history indexed fee changes");
+                }
+                state.changes.extend(scan.changes);
+                if let Some((signature, slot)) = scan.newest {
+                    state.newest_signature = Some(signature);
+                    state.last_indexed_slot = Some(slot);
+                }
+                state.last_indexed_at = Some(unix_timestamp());
+            }
+            Err(e) => warn!("Fee history indexing failed: {}", e),
+        }
+    }
+
+    /// Rescan every VaultConfig transaction and replace the timeline
+    pub async fn backfill(&self, chain: &ChainClient) -> Result<FeeBackfillReport, ServiceError> {
+        let _scan = self.scan_lock.lock().await;
+        let scan = self.scan(chain, None).await?;
+
+        let mut state = self.state.write().unwrap();
+        let report = FeeBackfillReport {
+            scanned: scan.scanned,
+            changes: scan.changes.len(),
+            added: scan.changes.iter().filter(|c| !state.changes.contains(c)).count(),
+            removed: state.changes.iter().filter(|c| !scan.changes.contains(c)).count(),
+        };
+        state.changes = scan.changes;
+        state.newest_signature = scan.newest.map(|(signature, _)| signature);
+        state.last_indexed_slot = scan.newest.map(|(_, slot)| slot);
+        state.last_indexed_at = Some(unix_timestamp());
+        info!(
+            scanned = report.scanned,
+            changes = report.changes,
+            added = report.added,
+            removed = report.removed,
+            "Fee history backfilled"
+        );
+        Ok(report)
+    }
+
+    /// Fee changes in transactions newer than `until`, oldest first
+    async fn scan(&self, chain: &ChainClient, until: Option<Signature>) -> Result<Scan, ServiceError> {
+        let commitment = chain.default_commitment();
+        let mut signatures = Vec::new();
+        let mut before = None;
+        loop {
+            let page = chain
+                .signatures_for_address(&self.vault_config, before, until, commitment)
+                .await?;
+            let full = page.len() == MAX_SIGNATURES_PER_PAGE;
+            before = page.last().map(|s| s.signature);
+            signatures.extend(page);
+            if !full {
+                break;
+            }
+        }
+
+        let mut changes = Vec::new();
+        for info in signatures.iter().rev().filter(|s| !s.failed) {
+            let (block_time, logs) = chain.transaction_logs(&info.signature, commitment).await?;
+            let events = parse_fee_events(&self.program_id, &logs);
+            if events.is_empty() {
+                continue;
+            }
+            let Some(timestamp) = block_time.or(info.block_time) else {
+                warn!(signature = %info.signature, "Fee change has no block time; skipped");
+                continue;
+            };
+            changes.extend(events.into_iter().map(|(event, fee_basis_points, previous_fee_basis_points)| {
+                FeeChange {
+                    event,
+                    fee_basis_points,
+                    previous_fee_basis_points,
+                    slot: info.slot,
+                    timestamp,
+                    signature: info.signature.to_string(),
+                }
+            }));
+        }
+
+        Ok(Scan {
+            changes,
+            newest: signatures.first().map(|s| (s.signature, s.slot)),
+            scanned: signatures.len(),
+        })
+    }
+}
+
+/// The last change at or before `timestamp`, from changes sorted oldest first
+pub fn fee_at(changes: &[FeeChange], timestamp: i64) -> Option<&FeeChange> {
+    let after = changes.partition_point(|c| c.timestamp <= timestamp);
+    after.checked_sub(1).map(|i| &changes[i])
+}
+
+/// Anchor event discriminator: first 8 bytes of sha256("event:<Name>")
+fn event_discriminator(name: &str) -> [u8; 8] {
+    let hash = Sha256::digest(format!("event:{}", name).as_bytes());
+    hash[..8].try_into().unwrap()
+}
+
+/// Fee events the vault program emitted in a transaction's logs, as
+/// `(event, fee, previous fee)`. `Program data:` lines are only trusted
+/// while the vault program is the one executing, so another program can't
+/// forge a fee change by logging the same bytes.
+fn parse_fee_events(program_id: &Pubkey, logs: &[String]) -> Vec<(FeeEvent, u16, Option<u16>)> {
+    let program_id = program_id.to_string();
+    let mut stack: Vec<&str> = Vec::new();
+    let mut events = Vec::new();
+    for line in logs {
+        let mut parts = line.split_whitespace();
+        if parts.next() != Some("Program") {
+            continue;
+        }
+        match (parts.next(), parts.next()) {
+            (Some("data:"), Some(data)) if stack.last() == Some(&program_id.as_str()) => {
+                if let Some(event) = STANDARD.decode(data).ok().and_then(|d| decode_fee_event(&d)) {
+                    events.push(event);
+                }
+            }
+            (Some(program), Some("invoke")) => stack.push(program),
+            (Some(_), Some(outcome)) if outcome == "success" || outcome.starts_with("failed") => {
+                stack.pop();
+            }
+            _ => {}
+        }
+    }
+    events
+}
+
+fn decode_fee_event(data: &[u8]) -> Option<(FeeEvent, u16, Option<u16>)> {
+    let u16_at = |offset: usize| u16::from_le_bytes([data[offset], data[offset + 1]]);
+    let discriminator = data.get(..8)?;
+    if discriminator == event_discriminator("VaultInitialized") && data.len() >= VAULT_INITIALIZED_SIZE {
+        Some((FeeEvent::VaultInitialized, u16_at(8 + 32 + 32), None))
+    } else if discriminator == event_discriminator("FeeUpdated") && data.len() >= FEE_UPDATED_SIZE {
+        Some((FeeEvent::FeeUpdated, u16_at(10), Some(u16_at(8))))
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fee_updated(old_fee: u16, new_fee: u16) -> String {
+        let mut data = event_discriminator("FeeUpdated").to_vec();
+        data.extend_from_slice(&old_fee.to_le_bytes());
+        data.extend_from_slice(&new_fee.to_le_bytes());
+        format!("Program data: {}", STANDARD.encode(data))
+    }
+
+    fn change(fee_basis_points: u16, timestamp: i64) -> FeeChange {
+        FeeChange {
+            event: FeeEvent::FeeUpdated,
+            fee_basis_points,
+            previous_fee_basis_points: None,
+            slot: timestamp as u64,
+            timestamp,
+            signature: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_parse_fee_events() {
+        let program = Pubkey::new_unique();
+        let mut initialized = event_discriminator("VaultInitialized").to_vec();
+        initialized.extend_from_slice(&[1; 64]);
+        initialized.extend_from_slice(&50u16.to_le_bytes());
+
+        let logs = [
+            format!("Program {} invoke [1]", program),
+            "Program log: Instruction: Initialize".to_string(),
+            format!("Program data: {}", STANDARD.encode(&initialized)),
+            format!("Program {} consumed 5000 of 200000 compute units", program),
+            format!("Program {} success", program),
+            format!("Program {} invoke [1]", program),
+            fee_updated(50, 75),
+            format!("Program {} success", program),
+        ];
+        assert_eq!(
+            parse_fee_events(&program, &logs),
+            [(FeeEvent::VaultInitialized, 50, None), (FeeEvent::FeeUpdated, 75, Some(50))]
+        );
+    }
+
+    #[test]
+    fn test_ignores_events_logged_by_other_programs() {
+        let program = Pubkey::new_unique();
+        let other = Pubkey::new_unique();
+        let logs = [
+            format!("Program {} invoke [1]", other),
+            fee_updated(50, 0),
+            format!("Program {} invoke [2]", program),
+            format!("Program {} success", program),
+            fee_updated(50, 1),
+            format!("Program {} success", other),
+            fee_updated(50, 2),
+        ];
+        assert!(parse_fee_events(&program, &logs).is_empty());
+    }
+
+    #[test]
+    fn test_fee_at() {
+        let changes = [change(50, 1_000), change(75, 2_000), change(60, 3_000)];
+        assert_eq!(fee_at(&changes, 999), None);
+        assert_eq!(fee_at(&changes, 1_000).unwrap().fee_basis_points, 50);
+        assert_eq!(fee_at(&changes, 2_999).unwrap().fee_basis_points, 75);
+        assert_eq!(fee_at(&changes, i64::MAX).unwrap().fee_basis_points, 60);
+
+        let history = FeeHistory::for_program(Pubkey::new_unique());
+        assert!(matches!(history.at(1_500), Err(ServiceError::NotFound(_))));
+        history.state.write().unwrap().changes = changes.to_vec();
+        let applied = history.at(1_500).unwrap();
+        assert_eq!((applied.fee_basis_points, applied.effective_timestamp), (50, 1_000));
+        assert!(matches!(history.at(0), Err(ServiceError::NotFound(_))));
+    }
+}
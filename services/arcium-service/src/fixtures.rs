@@ -0,0 +1,396 @@
+//! Golden vectors for QA, the cluster team and client implementers, written
+//! to `fixtures/` at the repository root by the `gen-fixtures` binary.
+//!
+//! Every value comes from an RNG seeded with the file's `seed` label, so
+//! regenerating a file at the same version reproduces it exactly. The tests
+//! regenerate each file at its recorded version and compare, so a change to
+//! encryption, commitments, envelopes, webhook signing or the process_payment
+//! layout fails loudly. Ship an intended change with `gen-fixtures --bump`.
+//!
+//! `StdRng` is ChaCha12 as of rand 0.8; a rand upgrade that changes it needs
+//! a bump too.
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use ninjapay_vault::{client, FeeMode};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::Serialize;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use solana_sdk::hash::Hash;
+use solana_sdk::message::Message;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signer::keypair::keypair_from_seed;
+use solana_sdk::signer::Signer;
+use solana_sdk::transaction::Transaction;
+use solana_sdk::pubkey;
+use x25519_dalek::{PublicKey, StaticSecret};
+
+use crate::error::ServiceError;
+use crate::mpc::encryption::{self, CommitmentAlgorithm};
+use crate::mpc::envelope;
+
+// Mainnet ids, as configured by default in config.rs
+const USDC_MINT: Pubkey = pubkey!("EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v");
+const TOKEN_PROGRAM_ID: Pubkey = pubkey!("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA");
+const ASSOCIATED_TOKEN_PROGRAM_ID: Pubkey = pubkey!("ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL");
+
+/// Names of the `ProcessPayment` accounts, in the order
+/// `ninjapay_vault::client::process_payment` passes them. An AcceptedMint
+/// left out is passed as the program id.
+const PROCESS_PAYMENT_ACCOUNTS: [&str; 20] = [
+    "vault_config",
+    "payment_record",
+    "payer_rate_limit",
+    "daily_volume",
+    "payer",
+    "blacklist_check",
+    "payer_token_account",
+    "mint",
+    "accepted_mint",
+    "vault_mint_config",
+    "fee_schedule",
+    "merchant",
+    "registered_merchant",
+    "merchant_stats",
+    "merchant_token_account",
+    "fee_token_account",
+    "fee_token_account_1",
+    "fee_token_account_2",
+    "token_program",
+    "system_program",
+];
+
+/// UTC day the fixture payment is made on, 2024-10-04, fixing its
+/// DailyVolumeRecord
+const PAYMENT_DAY: i64 = 20_000;
+
+/// Amounts every vector set covers: zero, one base unit, a typical
+/// payment, the first integer a JS number can't hold, and the maximum
+const AMOUNTS: [u64; 5] = [0, 1, 1_500_000, 9_007_199_254_740_993, u64::MAX];
+
+type Generator = fn(&mut StdRng) -> Result<Value, ServiceError>;
+
+/// Fixture files and the generator behind each
+pub const FIXTURES: &[(&str, Generator)] = &[
+    ("encryption.json", encryption_vectors),
+    ("merchant_envelope.json", envelope_vectors),
+    ("webhook_signature.json", webhook_vectors),
+    ("process_payment.json", process_payment_vectors),
+];
+
+/// The RNG for a fixture file, seeded from its label
+fn seeded_rng(seed: &str) -> StdRng {
+    StdRng::from_seed(Sha256::digest(seed.as_bytes()).into())
+}
+
+fn seed_label(name: &str) -> String {
+    format!("ninjapay-fixtures:{}", name.trim_end_matches(".json"))
+}
+
+/// Generate fixture `name` at `version`
+pub fn generate(name: &str, version: u32) -> Result<Value, ServiceError> {
+    let (_, generator) = FIXTURES
+        .iter()
+        .find(|(file, _)| *file == name)
+        .ok_or_else(|| ServiceError::InvalidInput(format!("unknown fixture {}", name)))?;
+
+    let seed = seed_label(name);
+    let mut fixture = json!({ "version": version, "seed": seed });
+    let body = generator(&mut seeded_rng(&seed))?;
+    fixture.as_object_mut().unwrap().extend(body.as_object().cloned().unwrap_or_default());
+    Ok(fixture)
+}
+
+fn encryption_vectors(rng: &mut StdRng) -> Result<Value, ServiceError> {
+    let master_key: [u8; 32] = rng.gen();
+    let user_pubkey = Pubkey::new_from_array(rng.gen()).to_string();
+    let derived_key = encryption::derive_user_key(&master_key, &user_pubkey)?;
+    let cipher = encryption::user_cipher(&master_key, &user_pubkey)?;
+
+    let mut vectors = Vec::new();
+    for amount in AMOUNTS {
+        for algorithm in [CommitmentAlgorithm::Sha256, CommitmentAlgorithm::Blake3] {
+            let result = encryption::encrypt_with_nonce(&cipher, amount, rng.gen(), algorithm)?;
+            vectors.push(json!({
+                "amount": amount.to_string(),
+                "nonce": hex::encode(&result.nonce),
+                "ciphertext": STANDARD.encode(&result.ciphertext),
+                "commitment_algorithm": algorithm,
+                "commitment": result.commitment,
+            }));
+        }
+    }
+
+    Ok(json!({
+        "scheme": "key = HKDF-SHA256(ikm = master_key, salt = SHA-256(\"ninjapay-v2\"), info = \"user:\" + user_pubkey); ciphertext = ChaCha20-Poly1305(key, nonce, amount as u64 little-endian), base64 with the tag; commitment = hex(H(amount as u64 little-endian || nonce))",
+        "master_key": hex::encode(master_key),
+        "user_pubkey": user_pubkey,
+        "derived_key": hex::encode(derived_key),
+        "vectors": vectors,
+    }))
+}
+
+fn envelope_vectors(rng: &mut StdRng) -> Result<Value, ServiceError> {
+    let merchant_secret = StaticSecret::from(rng.gen::<[u8; 32]>());
+    let merchant_public = PublicKey::from(&merchant_secret);
+
+    let mut vectors = Vec::new();
+    for amount in AMOUNTS {
+        let ephemeral_secret: [u8; 32] = rng.gen();
+        let envelope = envelope::seal_with_ephemeral(
+            amount,
+            &merchant_public,
+            &StaticSecret::from(ephemeral_secret),
+            rng.gen(),
+        )?;
+        vectors.push(json!({
+            "amount": amount.to_string(),
+            "ephemeral_secret_key": hex::encode(ephemeral_secret),
+            "envelope": envelope,
+        }));
+    }
+
+    Ok(json!({
+        "scheme": "see src/mpc/envelope.rs in arcium-service",
+        "merchant_secret_key": hex::encode(merchant_secret.to_bytes()),
+        "merchant_public_key": hex::encode(merchant_public.as_bytes()),
+        "vectors": vectors,
+    }))
+}
+
+/// Event body in the gateway's field order (`JSON.stringify` of its
+/// `WebhookPayload`)
+#[derive(Serialize)]
+struct WebhookEvent {
+    id: String,
+    #[serde(rename = "type")]
+    event_type: &'static str,
+    created: u64,
+    livemode: bool,
+    data: Value,
+}
+
+fn webhook_secret(rng: &mut StdRng) -> String {
+    format!("whsec_{}", hex::encode(rng.gen::<[u8; 24]>()))
+}
+
+/// `sha256=<hex HMAC-SHA256(secret, "<timestamp>.<body>")>`
+fn sign_webhook(secret: &str, timestamp: &str, body: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(format!("{}.{}", timestamp, body).as_bytes());
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+fn webhook_vectors(rng: &mut StdRng) -> Result<Value, ServiceError> {
+    let secret = webhook_secret(rng);
+    let previous_secret = webhook_secret(rng);
+    let created = 1_760_616_000_000 + rng.gen_range(0..1_000);
+    let timestamp = (created + 250).to_string();
+
+    let event = WebhookEvent {
+        id: format!("evt_{}", hex::encode(rng.gen::<[u8; 16]>())),
+        event_type: "payment_intent.confirmed",
+        created,
+        livemode: false,
+        data: json!({
+            "id": format!("pi_{}", hex::encode(rng.gen::<[u8; 12]>())),
+            "amount": "9007199254740993",
+            "currency": "USDC",
+            "status": "CONFIRMED",
+            "recipient": Pubkey::new_from_array(rng.gen()).to_string(),
+        }),
+    };
+    let body = serde_json::to_string(&event)
+        .map_err(|e| ServiceError::InternalError(format!("Event serialization failed: {}", e)))?;
+
+    Ok(json!({
+        "scheme": "X-Webhook-Signature = \"sha256=\" + hex(HMAC-SHA256(secret, timestamp + \".\" + body)); during a rotation one signature per secret, comma separated, newest first",
+        "secret": secret,
+        "previous_secret": previous_secret,
+        "timestamp": timestamp,
+        "body": body,
+        "signature": sign_webhook(&secret, &timestamp, &body),
+        "rotation_signature": format!(
+            "{},{}",
+            sign_webhook(&secret, &timestamp, &body),
+            sign_webhook(&previous_secret, &timestamp, &body)
+        ),
+    }))
+}
+
+fn associated_token_account(owner: &Pubkey, mint: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[owner.as_ref(), TOKEN_PROGRAM_ID.as_ref(), mint.as_ref()],
+        &ASSOCIATED_TOKEN_PROGRAM_ID,
+    )
+    .0
+}
+
+fn process_payment_vectors(rng: &mut StdRng) -> Result<Value, ServiceError> {
+    let payer_seed: [u8; 32] = rng.gen();
+    let payer = keypair_from_seed(&payer_seed)
+        .map_err(|e| ServiceError::InternalError(format!("Keypair derivation failed: {}", e)))?;
+    let merchant = Pubkey::new_from_array(rng.gen());
+    let fee_collector = Pubkey::new_from_array(rng.gen());
+    let recent_blockhash = Hash::new_from_array(rng.gen());
+
+    let amount = 1_500_000u64;
+    let payment_id: [u8; 32] = rng.gen();
+    let nonce: [u8; 12] = rng.gen();
+    let commitment_hex = encryption::generate_commitment(amount, &nonce, CommitmentAlgorithm::Sha256);
+    let commitment: [u8; 32] = hex::decode(&commitment_hex).unwrap().try_into().unwrap();
+
+    let payment = client::PaymentAccounts {
+        payer: payer.pubkey(),
+        payer_token_account: associated_token_account(&payer.pubkey(), &USDC_MINT),
+        mint: USDC_MINT,
+        accepted_mint: None,
+        vault_mint_config: None,
+        fee_schedule: None,
+        merchant,
+        registered_merchant: None,
+        merchant_token_account: associated_token_account(&merchant, &USDC_MINT),
+        fee_token_account: associated_token_account(&fee_collector, &USDC_MINT),
+        fee_token_account_1: None,
+        fee_token_account_2: None,
+        day: PAYMENT_DAY,
+    };
+    // expires_at 0: never; the nonce opens the commitment for vaults that
+    // verify it. The fee comes out of the amount.
+    let fee_mode = FeeMode::Deducted;
+    let instruction = client::process_payment(&payment, amount, payment_id, commitment, 0, &nonce, fee_mode);
+    if instruction.accounts.len() != PROCESS_PAYMENT_ACCOUNTS.len() {
+        return Err(ServiceError::InternalError(
+            "PROCESS_PAYMENT_ACCOUNTS is out of date with the vault's ProcessPayment".to_string(),
+        ));
+    }
+    let accounts: Vec<Value> = PROCESS_PAYMENT_ACCOUNTS
+        .iter()
+        .zip(&instruction.accounts)
+        .map(|(name, meta)| json!({
+            "name": name,
+            "pubkey": meta.pubkey.to_string(),
+            "is_signer": meta.is_signer,
+            "is_writable": meta.is_writable,
+        }))
+        .collect();
+    let data = instruction.data.clone();
+
+    let message = Message::new_with_blockhash(&[instruction], Some(&payer.pubkey()), &recent_blockhash);
+    let transaction = Transaction::new(&[&payer], message, recent_blockhash);
+
+    // Wire format with one signature: compact-u16 count (a single byte),
+    // the signature, then the message
+    let mut wire = vec![1u8];
+    wire.extend_from_slice(transaction.signatures[0].as_ref());
+    wire.extend_from_slice(&transaction.message_data());
+
+    Ok(json!({
+        "program_id": ninjapay_vault::ID.to_string(),
+        "mint": USDC_MINT.to_string(),
+        "payer_seed": hex::encode(payer_seed),
+        "payer": payer.pubkey().to_string(),
+        "merchant": merchant.to_string(),
+        "fee_collector": fee_collector.to_string(),
+        "recent_blockhash": recent_blockhash.to_string(),
+        "day": PAYMENT_DAY,
+        "args": {
+            "amount": amount.to_string(),
+            "payment_id": hex::encode(payment_id),
+            "commitment": commitment_hex,
+            "commitment_nonce": hex::encode(nonce),
+            "fee_mode": "deducted",
+        },
+        "accounts": accounts,
+        "instruction_data": hex::encode(&data),
+        "message": STANDARD.encode(transaction.message_data()),
+        "signature": transaction.signatures[0].to_string(),
+        "transaction": STANDARD.encode(wire),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_sdk::signature::Signature;
+    use std::str::FromStr;
+
+    fn fixture(name: &str) -> Value {
+        let path = format!("{}/../../fixtures/{}", env!("CARGO_MANIFEST_DIR"), name);
+        let contents = std::fs::read_to_string(&path).expect("missing fixture; run gen-fixtures");
+        serde_json::from_str(&contents).expect("invalid fixture JSON")
+    }
+
+    fn version(fixture: &Value) -> u32 {
+        fixture["version"].as_u64().unwrap() as u32
+    }
+
+    #[test]
+    fn test_fixtures_match_generator() {
+        for (name, _) in FIXTURES {
+            let on_disk = fixture(name);
+            assert_eq!(
+                generate(name, version(&on_disk)).unwrap(),
+                on_disk,
+                "{} no longer matches its generator; if the change is intended, run gen-fixtures --bump",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn test_encryption_vectors_decrypt() {
+        let fixture = fixture("encryption.json");
+        let master_key = hex::decode(fixture["master_key"].as_str().unwrap()).unwrap();
+        let cipher = encryption::user_cipher(&master_key, fixture["user_pubkey"].as_str().unwrap()).unwrap();
+
+        for vector in fixture["vectors"].as_array().unwrap() {
+            let amount: u64 = vector["amount"].as_str().unwrap().parse().unwrap();
+            let nonce = hex::decode(vector["nonce"].as_str().unwrap()).unwrap();
+            let ciphertext = STANDARD.decode(vector["ciphertext"].as_str().unwrap()).unwrap();
+            let algorithm: CommitmentAlgorithm =
+                serde_json::from_value(vector["commitment_algorithm"].clone()).unwrap();
+
+            assert_eq!(encryption::decrypt_with_cipher(&cipher, &ciphertext, &nonce).unwrap(), amount);
+            assert!(encryption::verify_commitment(
+                amount,
+                &nonce,
+                vector["commitment"].as_str().unwrap(),
+                algorithm
+            ));
+        }
+    }
+
+    #[test]
+    fn test_webhook_signature_vector() {
+        let fixture = fixture("webhook_signature.json");
+        let field = |name: &str| fixture[name].as_str().unwrap().to_string();
+
+        let mut mac = Hmac::<Sha256>::new_from_slice(field("secret").as_bytes()).unwrap();
+        mac.update(format!("{}.{}", field("timestamp"), field("body")).as_bytes());
+        let expected = format!("sha256={}", hex::encode(mac.finalize().into_bytes()));
+
+        assert_eq!(field("signature"), expected);
+        assert!(field("rotation_signature").starts_with(&format!("{},", expected)));
+    }
+
+    #[test]
+    fn test_process_payment_transaction_is_signed_by_payer() {
+        let fixture = fixture("process_payment.json");
+        let payer = Pubkey::from_str(fixture["payer"].as_str().unwrap()).unwrap();
+        let message = STANDARD.decode(fixture["message"].as_str().unwrap()).unwrap();
+        let signature = Signature::from_str(fixture["signature"].as_str().unwrap()).unwrap();
+        assert!(signature.verify(payer.as_ref(), &message));
+
+        let data = hex::decode(fixture["instruction_data"].as_str().unwrap()).unwrap();
+        // The blinding factor is the 12-byte nonce, after its u32 length,
+        // then the fee mode's variant byte: Deducted
+        assert_eq!(data.len(), 8 + 8 + 32 + 32 + 8 + 4 + 12 + 1);
+        assert_eq!(data[data.len() - 1], 0);
+        // Anchor's discriminator: sha256("global:process_payment")[..8]
+        assert_eq!(data[..8], Sha256::digest(b"global:process_payment")[..8]);
+    }
+}
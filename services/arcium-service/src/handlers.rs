@@ -1,38 +1,92 @@
-use actix_web::{web, HttpResponse};
+use actix_web::{web, HttpRequest, HttpResponse};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use futures_util::StreamExt;
+use ninjapay_types::format::{self, Locale};
+use ninjapay_types::{error_code, string_u64, ErrorCode};
 use serde::{Deserialize, Serialize};
+use solana_sdk::pubkey::Pubkey;
+use std::str::FromStr;
+use tracing::{info, warn};
 
+use crate::admin::AdminAuth;
+use crate::api_version::ApiVersion;
+use crate::callback::{
+    self, CallbackDispatcher, CallbackTemplate, CallbackVars, ClusterCallback, ClusterStatus,
+};
+use crate::callback_tls::{CallbackTls, ClientIdentitySummary};
 use crate::config::Config;
+use crate::endpoint_switches::{DisabledEndpoint, Endpoint, EndpointSwitches};
 use crate::error::ServiceError;
-use crate::mpc::{self, MpcClient};
+use crate::fee_audit::{FeeAudit, FeeAuditReport};
+use crate::fee_history::FeeHistory;
+use crate::leader::{LeaderElector, LeadershipState};
+use crate::metadata::{self, Metadata};
+use crate::metrics::{Metrics, Subsystem};
+use crate::mpc::envelope::{self, MerchantEnvelope};
+use crate::mpc::{self, CommitmentAlgorithm, MpcClient};
+use crate::pagination::{ListQuery, Listable, Paginated};
+use crate::payload::CompressionReport;
+use crate::payroll_ingest::{IngestedPayroll, PayrollIngest, RowError};
+use crate::program_errors::{self, ProgramError};
+use crate::quarantine::{Quarantine, QuarantinedCallback};
+use crate::rpc::{ChainClient, ChainContext, Commitment, RpcStats};
+use crate::shutdown::Shutdown;
+use crate::sla::SlaMonitor;
+use crate::statements::{Statement, Statements};
+use crate::status::{HealthStatus, Incident, StatusBoard};
+use crate::store::{unix_timestamp, ComputationRecord, ComputationStore};
+use crate::timeline::{self, ComputationTimeline, Timeline, TimelineEvent};
+use crate::reorg::ReorgWatcher;
+use crate::strict_mode::{self, Deprecation, LenientU64, StrictMode, StrictModeState};
+use crate::vault_stats::{self, StatsSnapshot, VaultStats};
+use crate::wallet_validation::{self, WalletReport, WalletValidator};
+use crate::warmup::{Warmup, WarmupReport};
 
 #[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
 struct HealthResponse {
     status: String,
     service: String,
     version: String,
     mpc_mode: String,
+    leadership: LeadershipState,
+    /// Endpoints switched off by an operator; the service is degraded while any are
+    disabled_endpoints: Vec<DisabledEndpoint>,
 }
 
 #[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub struct EncryptRequest {
-    amount: u64,
+    amount: LenientU64,
     user_pubkey: String,
+    /// Hex X25519 key; when set, the amount is also sealed to the merchant
+    #[serde(default)]
+    merchant_x25519_pubkey: Option<String>,
+    #[serde(default)]
+    commitment_algorithm: CommitmentAlgorithm,
 }
 
 #[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
 struct EncryptResponse {
     success: bool,
     data: EncryptData,
 }
 
 #[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
 struct EncryptData {
     ciphertext: String,
     nonce: String,
     commitment: String,
+    commitment_algorithm: CommitmentAlgorithm,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    merchant_envelope: Option<MerchantEnvelope>,
 }
 
 #[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub struct DecryptRequest {
     ciphertext: String,
     nonce: String,
@@ -40,95 +94,928 @@ pub struct DecryptRequest {
 }
 
 #[derive(Serialize)]
-struct DecryptResponse {
+#[serde(rename_all = "snake_case")]
+struct DecryptResponse<T> {
     success: bool,
-    data: DecryptData,
+    data: T,
 }
 
+/// v1 decrypt payload: amount as a JSON number
 #[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
 struct DecryptData {
     amount: u64,
 }
 
+/// v2 decrypt payload: amount as a decimal string
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+struct DecryptDataV2 {
+    #[serde(with = "string_u64")]
+    amount: u64,
+}
+
 #[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub struct PaymentSettlementRequest {
     payment_intent_id: String,
     merchant_wallet: String,
-    amount: u64,
+    amount: LenientU64,
     recipient: String,
     currency: String,
     callback_url: String,
+    #[serde(default)]
+    callback_tls: Option<CallbackTls>,
+    #[serde(default)]
+    metadata: Metadata,
+    /// Validate without queueing (also enabled by `X-Dry-Run: true`)
+    #[serde(default)]
+    dry_run: bool,
 }
 
 #[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub struct PayrollSettlementRequest {
     batch_id: String,
     company_wallet: String,
     payments: Vec<PayrollPaymentInput>,
     currency: String,
     callback_url: String,
+    #[serde(default)]
+    callback_tls: Option<CallbackTls>,
+    /// Check employee wallets on-chain before queueing
+    #[serde(default)]
+    validate_wallets: bool,
 }
 
 #[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub struct PayrollPaymentInput {
     employee_id: String,
     employee_wallet: String,
-    amount: u64,
+    amount: LenientU64,
+}
+
+/// Batch fields for an NDJSON payroll upload; the body holds only the payments
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct PayrollNdjsonQuery {
+    batch_id: String,
+    company_wallet: String,
+    currency: String,
+    callback_url: String,
+    /// A CA bundle doesn't fit in a query string; only the identity can be set
+    callback_client_identity_id: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+struct PayrollNdjsonResponse {
+    success: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<ErrorBody>,
+    data: PayrollNdjsonData,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+struct PayrollNdjsonData {
+    batch_id: String,
+    rows: usize,
+    bytes: usize,
+    chunks: Vec<QueuedChunk>,
+    error_count: usize,
+    errors: Vec<RowError>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+struct QueuedChunk {
+    index: usize,
+    computation_id: String,
+    status: String,
+    rows: usize,
 }
 
 #[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
 struct ComputationQueuedResponse {
     success: bool,
     data: ComputationData,
 }
 
 #[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
 struct ComputationData {
     computation_id: String,
     status: String,
+    #[serde(skip_serializing_if = "Metadata::is_empty")]
+    metadata: Metadata,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    wallet_report: Option<Vec<WalletReport>>,
+    /// Commitment and slot of the chain reads behind `wallet_report`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    chain_context: Option<ChainContext>,
+    /// Cluster result, once the computation's callback has arrived
+    #[serde(skip_serializing_if = "Option::is_none")]
+    result: Option<serde_json::Value>,
+}
+
+#[derive(Deserialize)]
+pub struct ComputationFilters {
+    status: Option<String>,
+    computation_type: Option<String>,
+    batch_id: Option<String>,
+}
+
+impl Listable for ComputationRecord {
+    type Filters = ComputationFilters;
+    const SORT_FIELDS: &'static [&'static str] = &["created_at", "updated_at"];
+
+    fn list_id(&self) -> &str {
+        &self.computation_id
+    }
+
+    fn sort_key(&self, field: &str) -> u64 {
+        match field {
+            "updated_at" => self.updated_at,
+            _ => self.created_at,
+        }
+    }
+
+    fn matches(&self, filters: &ComputationFilters) -> bool {
+        filters.status.as_ref().is_none_or(|s| *s == self.status)
+            && filters.computation_type.as_ref().is_none_or(|t| *t == self.computation_type)
+            && filters.batch_id.as_ref().is_none_or(|b| self.batch_id.as_ref() == Some(b))
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+struct DryRunResponse<A> {
+    success: bool,
+    data: DryRunData<A>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+struct DryRunData<A> {
+    dry_run: bool,
+    computation_id: String,
+    status: String,
+    request: DryRunPayment<A>,
+}
+
+/// Settlement as it would have been queued; amount is versioned like decrypt
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+struct DryRunPayment<A> {
+    payment_intent_id: String,
+    merchant_wallet: String,
+    amount: A,
+    recipient: String,
+    currency: String,
+    callback_url: String,
+    #[serde(skip_serializing_if = "Metadata::is_empty")]
+    metadata: Metadata,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+struct WalletValidationFailedResponse {
+    success: bool,
+    error: ErrorBody,
+    data: WalletReportData,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+struct ErrorBody {
+    code: ErrorCode,
+    message: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+struct WalletReportData {
+    wallet_report: Vec<WalletReport>,
+    chain_context: ChainContext,
+}
+
+/// Query parameters accepted by endpoints that read chain state
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct ChainReadQuery {
+    commitment: Option<Commitment>,
+}
+
+/// Payload relayed to the caller's callback URL
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+struct CallbackPayload {
+    computation_id: String,
+    computation_type: String,
+    status: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    batch_id: Option<String>,
+    #[serde(skip_serializing_if = "Metadata::is_empty")]
+    metadata: Metadata,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    result: Option<serde_json::Value>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+struct CallbackAckResponse {
+    success: bool,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+struct TimelineResponse {
+    success: bool,
+    data: ComputationTimeline,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+struct ListResponse<T> {
+    success: bool,
+    data: Paginated<T>,
+}
+
+#[derive(Deserialize)]
+pub struct QuarantineFilters {}
+
+impl Listable for QuarantinedCallback {
+    type Filters = QuarantineFilters;
+    const SORT_FIELDS: &'static [&'static str] = &["received_at"];
+
+    fn list_id(&self) -> &str {
+        &self.id
+    }
+
+    fn sort_key(&self, _field: &str) -> u64 {
+        self.received_at
+    }
+
+    fn matches(&self, _filters: &QuarantineFilters) -> bool {
+        true
+    }
 }
 
 #[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct OpenIncidentRequest {
+    subsystem: Subsystem,
+    status: HealthStatus,
+    message: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+struct IncidentResponse {
+    success: bool,
+    data: Incident,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct CreateClientIdentityRequest {
+    name: String,
+    /// PEM certificate chain, leaf first
+    certificate: String,
+    /// PKCS#8 PEM private key; stored encrypted and never returned
+    private_key: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+struct ClientIdentityResponse {
+    success: bool,
+    data: ClientIdentitySummary,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+struct ClientIdentitiesResponse {
+    success: bool,
+    data: Vec<ClientIdentitySummary>,
+}
+
+#[derive(Deserialize)]
+pub struct DisableEndpointRequest {
+    /// Shown to callers in the 503 response
+    message: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+struct EndpointSwitchResponse {
+    success: bool,
+    data: EndpointSwitchState,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+struct EndpointSwitchState {
+    endpoint: Endpoint,
+    enabled: bool,
+    disabled_endpoints: Vec<DisabledEndpoint>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct SetStrictModeRequest {
+    strict_mode: bool,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+struct StrictModeResponse {
+    success: bool,
+    data: StrictModeState,
+}
+
+#[derive(Deserialize)]
+pub struct IncidentFilters {
+    subsystem: Option<Subsystem>,
+    status: Option<HealthStatus>,
+}
+
+impl Listable for Incident {
+    type Filters = IncidentFilters;
+    const SORT_FIELDS: &'static [&'static str] = &["started_at"];
+
+    fn list_id(&self) -> &str {
+        &self.id
+    }
+
+    fn sort_key(&self, _field: &str) -> u64 {
+        self.started_at
+    }
+
+    fn matches(&self, filters: &IncidentFilters) -> bool {
+        filters.subsystem.is_none_or(|s| s == self.subsystem)
+            && filters.status.is_none_or(|s| s == self.status)
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub struct VerifyCommitmentRequest {
-    amount: u64,
-    nonce: String,
+    amount: LenientU64,
+    /// Hex blinding factor
+    #[serde(default)]
+    blinding_factor: Option<String>,
+    /// Deprecated name for `blinding_factor`
+    #[serde(default)]
+    nonce: Option<String>,
     commitment: String,
+    #[serde(default)]
+    algorithm: CommitmentAlgorithm,
 }
 
 #[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
 struct VerifyCommitmentResponse {
     success: bool,
     data: VerifyData,
 }
 
 #[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
 struct VerifyData {
     valid: bool,
+    algorithm: CommitmentAlgorithm,
+}
+
+/// Commitments per batch verification request
+const MAX_VERIFY_BATCH: usize = 1000;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct VerifyCommitmentsRequest {
+    commitments: Vec<VerifyCommitmentRequest>,
+}
+
+impl VerifyCommitmentRequest {
+    /// Deprecated forms, with field names under `prefix`
+    fn deprecations(&self, prefix: &str, found: &mut Vec<Deprecation>) {
+        if self.amount.numeric {
+            found.push(Deprecation::numeric_amount(format!("{}amount", prefix)));
+        }
+        if self.nonce.is_some() {
+            found.push(Deprecation::nonce_as_blinding(format!("{}nonce", prefix)));
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+struct VerifyCommitmentsResponse {
+    success: bool,
+    data: VerifyBatchData,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+struct VerifyBatchData {
+    all_valid: bool,
+    /// In request order
+    results: Vec<VerifyData>,
 }
 
 /// Health check endpoint
-pub async fn health_check(config: web::Data<Config>) -> HttpResponse {
+pub async fn health_check(
+    config: web::Data<Config>,
+    leader: web::Data<LeaderElector>,
+    switches: web::Data<EndpointSwitches>,
+) -> HttpResponse {
+    let disabled_endpoints = switches.disabled();
+    let status = if disabled_endpoints.is_empty() { "healthy" } else { "degraded" };
+
     HttpResponse::Ok().json(HealthResponse {
-        status: "healthy".to_string(),
+        status: status.to_string(),
         service: "arcium-service".to_string(),
         version: "2.0.0".to_string(),
         mpc_mode: config.mpc_mode.to_string(),
+        leadership: leader.state(),
+        disabled_endpoints,
     })
 }
 
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+struct ReadinessResponse {
+    ready: bool,
+    warmed: bool,
+    /// Shutting down; still serving requests until the drain window ends
+    draining: bool,
+    in_flight: u64,
+    warmup_ms: u64,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+struct WarmupResponse {
+    success: bool,
+    data: WarmupReport,
+}
+
+/// Readiness probe; 503 until warmup has completed and again once shutdown
+/// has begun
+pub async fn readiness(warmup: web::Data<Warmup>, shutdown: web::Data<Shutdown>) -> HttpResponse {
+    let warmed = warmup.is_warmed();
+    let draining = shutdown.is_draining();
+    let body = ReadinessResponse {
+        ready: warmed && !draining,
+        warmed,
+        draining,
+        in_flight: shutdown.in_flight(),
+        warmup_ms: warmup.last_duration_ms(),
+    };
+
+    if body.ready {
+        HttpResponse::Ok().json(body)
+    } else {
+        HttpResponse::ServiceUnavailable().json(body)
+    }
+}
+
+/// Re-run warmup, e.g. from a pre-traffic deployment hook
+pub async fn run_warmup(
+    _admin: AdminAuth,
+    warmup: web::Data<Warmup>,
+    mpc_client: web::Data<MpcClient>,
+) -> Result<HttpResponse, ServiceError> {
+    let report = warmup.run(&mpc_client)?;
+
+    Ok(HttpResponse::Ok().json(WarmupResponse {
+        success: true,
+        data: report,
+    }))
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+struct CompressionResponse {
+    success: bool,
+    data: CompressionReport,
+}
+
+/// One-off migration: compress stored payloads that qualify under the
+/// current threshold and report the space reclaimed
+pub async fn compress_stored_payloads(
+    _admin: AdminAuth,
+    store: web::Data<ComputationStore>,
+) -> HttpResponse {
+    let report = store.compress_payloads();
+    tracing::info!(
+        "Compressed {} of {} stored payloads, reclaiming {} bytes",
+        report.payloads_compressed,
+        report.payloads_scanned,
+        report.bytes_reclaimed
+    );
+
+    HttpResponse::Ok().json(CompressionResponse {
+        success: true,
+        data: report,
+    })
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+struct FeeAuditResponse {
+    success: bool,
+    data: FeeAuditReport,
+}
+
+/// Latest fee audit: on-chain fees that differ from the shared fee math
+pub async fn fee_audit_report(_admin: AdminAuth, fee_audit: web::Data<FeeAudit>) -> HttpResponse {
+    HttpResponse::Ok().json(FeeAuditResponse {
+        success: true,
+        data: fee_audit.report(),
+    })
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+struct RpcStatsResponse {
+    success: bool,
+    data: RpcStats,
+}
+
+/// RPC calls made since startup, and how much batching and caching saved
+pub async fn rpc_stats(_admin: AdminAuth, chain: web::Data<ChainClient>) -> HttpResponse {
+    HttpResponse::Ok().json(RpcStatsResponse {
+        success: true,
+        data: chain.stats(),
+    })
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+struct FeeHistoryResponse<T> {
+    success: bool,
+    data: T,
+}
+
+/// Every fee setting the vault has had, oldest first
+pub async fn fee_history(history: web::Data<FeeHistory>) -> HttpResponse {
+    HttpResponse::Ok().json(FeeHistoryResponse {
+        success: true,
+        data: history.timeline(),
+    })
+}
+
+#[derive(Debug, Deserialize)]
+pub struct FeeAtQuery {
+    /// Unix seconds
+    pub timestamp: i64,
+}
+
+/// The fee in force at a unix timestamp
+pub async fn fee_at(
+    history: web::Data<FeeHistory>,
+    query: web::Query<FeeAtQuery>,
+) -> Result<HttpResponse, ServiceError> {
+    Ok(HttpResponse::Ok().json(FeeHistoryResponse {
+        success: true,
+        data: history.at(query.timestamp)?,
+    }))
+}
+
+/// Rescan every VaultConfig transaction and rebuild the fee timeline
+pub async fn backfill_fee_history(
+    _admin: AdminAuth,
+    history: web::Data<FeeHistory>,
+    chain: web::Data<ChainClient>,
+) -> Result<HttpResponse, ServiceError> {
+    let report = history.backfill(&chain).await?;
+    Ok(HttpResponse::Ok().json(FeeHistoryResponse {
+        success: true,
+        data: report,
+    }))
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+struct ErrorCatalogResponse {
+    success: bool,
+    data: Vec<error_code::ErrorCatalogEntry>,
+}
+
+/// Every error code the API can return, with HTTP status and retry semantics
+pub async fn error_catalog() -> HttpResponse {
+    HttpResponse::Ok().json(ErrorCatalogResponse {
+        success: true,
+        data: error_code::catalog(),
+    })
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+struct ProgramErrorResponse {
+    success: bool,
+    data: ProgramError,
+}
+
+/// Name and remediation for a custom program error code (decimal or 0x hex)
+pub async fn program_error(path: web::Path<String>) -> Result<HttpResponse, ServiceError> {
+    let code = program_errors::parse_code(&path)
+        .ok_or_else(|| ServiceError::InvalidInput(format!("Invalid program error code: {}", path)))?;
+    let error = program_errors::lookup(code)
+        .ok_or_else(|| ServiceError::NotFound(format!("Unknown program error code: {}", path)))?;
+
+    Ok(HttpResponse::Ok().json(ProgramErrorResponse {
+        success: true,
+        data: error,
+    }))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct FormatQuery {
+    /// Base units, as a decimal string
+    pub amount: String,
+    pub currency: String,
+    /// BCP 47 tag, e.g. `de-DE`
+    pub locale: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub struct FormattedAmount {
+    #[serde(with = "string_u64")]
+    pub amount: u64,
+    pub currency: String,
+    pub decimals: u8,
+    pub locale: String,
+    /// Number only, e.g. `1.234,56`
+    pub formatted: String,
+    /// With the currency code, e.g. `1.234,56 USDC`
+    pub display: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+struct FormatResponse {
+    success: bool,
+    data: FormattedAmount,
+}
+
+/// Render a base-unit amount for a locale, for clients without the
+/// ninjapay-types formatter
+pub async fn format_amount(query: web::Query<FormatQuery>) -> Result<HttpResponse, ServiceError> {
+    let amount = query
+        .amount
+        .parse::<u64>()
+        .ok()
+        .filter(|_| query.amount.bytes().all(|b| b.is_ascii_digit()))
+        .ok_or_else(|| ServiceError::InvalidInput("amount must be a whole number of base units".to_string()))?;
+    let currency = query.currency.to_ascii_uppercase();
+    let decimals = format::currency_decimals(&currency)
+        .ok_or_else(|| ServiceError::InvalidInput(format!("Unsupported currency: {}", query.currency)))?;
+    let locale: Locale = query.locale.parse().map_err(|e: format::UnknownLocale| {
+        ServiceError::InvalidInput(format!(
+            "{}; supported: {}",
+            e,
+            Locale::ALL.iter().map(|l| l.tag()).collect::<Vec<_>>().join(", ")
+        ))
+    })?;
+
+    let formatted = format::format_amount(amount, decimals, locale);
+    Ok(HttpResponse::Ok().json(FormatResponse {
+        success: true,
+        data: FormattedAmount {
+            amount,
+            display: format!("{} {}", formatted, currency),
+            currency,
+            decimals,
+            locale: locale.tag().to_string(),
+            formatted,
+        },
+    }))
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+struct VaultStatsResponse<T> {
+    success: bool,
+    data: T,
+}
+
+fn vault_stats_json(version: ApiVersion, snapshot: &StatsSnapshot) -> HttpResponse {
+    match version {
+        ApiVersion::V1 => HttpResponse::Ok().json(VaultStatsResponse {
+            success: true,
+            data: snapshot,
+        }),
+        ApiVersion::V2 => HttpResponse::Ok().json(VaultStatsResponse {
+            success: true,
+            data: snapshot.map(|v| v.to_string()),
+        }),
+    }
+}
+
+/// Vault-wide payment totals per day
+pub async fn vault_stats(version: ApiVersion, stats: web::Data<VaultStats>) -> HttpResponse {
+    vault_stats_json(version, &stats.snapshot(None))
+}
+
+/// One merchant's payment totals per day
+pub async fn merchant_vault_stats(
+    version: ApiVersion,
+    stats: web::Data<VaultStats>,
+    path: web::Path<String>,
+) -> Result<HttpResponse, ServiceError> {
+    let merchant = Pubkey::from_str(&path)
+        .map_err(|_| ServiceError::InvalidInput(format!("Invalid merchant address: {}", path)))?;
+    Ok(vault_stats_json(version, &stats.snapshot(Some(merchant))))
+}
+
+/// Re-read every PaymentRecord, rebuilding rollups of any rewritten day
+pub async fn backfill_vault_stats(
+    _admin: AdminAuth,
+    stats: web::Data<VaultStats>,
+    chain: web::Data<ChainClient>,
+) -> Result<HttpResponse, ServiceError> {
+    let report = stats.backfill(&chain).await?;
+    Ok(HttpResponse::Ok().json(VaultStatsResponse {
+        success: true,
+        data: report,
+    }))
+}
+
+/// Recompute a day (YYYY-MM-DD) from raw events and diff it against the rollups
+pub async fn check_vault_stats(
+    _admin: AdminAuth,
+    stats: web::Data<VaultStats>,
+    path: web::Path<String>,
+) -> Result<HttpResponse, ServiceError> {
+    let day = vault_stats::parse_day(&path)
+        .ok_or_else(|| ServiceError::InvalidInput(format!("Invalid day, expected YYYY-MM-DD: {}", path)))?;
+    let report = stats.check_day(day);
+    Ok(HttpResponse::Ok().json(VaultStatsResponse {
+        success: true,
+        data: report,
+    }))
+}
+
+/// Last reorg check, reverted payments and their event deliveries
+pub async fn reorg_state(
+    _admin: AdminAuth,
+    watcher: web::Data<ReorgWatcher>,
+    stats: web::Data<VaultStats>,
+) -> HttpResponse {
+    HttpResponse::Ok().json(VaultStatsResponse {
+        success: true,
+        data: watcher.state(&stats),
+    })
+}
+
+/// Computations that ran past their latency SLA, with the alert deliveries
+pub async fn sla_breaches(
+    _admin: AdminAuth,
+    monitor: web::Data<SlaMonitor>,
+    store: web::Data<ComputationStore>,
+) -> HttpResponse {
+    HttpResponse::Ok().json(VaultStatsResponse {
+        success: true,
+        data: monitor.state(&store),
+    })
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct IssueStatementRequest {
+    /// `YYYY-MM`
+    period: String,
+}
+
+#[derive(Deserialize)]
+pub struct StatementFormatQuery {
+    #[serde(default)]
+    format: Option<String>,
+}
+
+fn parse_merchant(merchant: &str) -> Result<Pubkey, ServiceError> {
+    Pubkey::from_str(merchant)
+        .map_err(|_| ServiceError::InvalidInput(format!("Invalid merchant address: {}", merchant)))
+}
+
+fn statement_json(version: ApiVersion, statement: &Statement) -> serde_json::Value {
+    match version {
+        ApiVersion::V1 => serde_json::to_value(statement),
+        ApiVersion::V2 => serde_json::to_value(statement.map(|v| v.to_string())),
+    }
+    .expect("statement serializes")
+}
+
+/// Issue and lock a merchant's statement for a calendar month that has ended
+pub async fn issue_statement(
+    _admin: AdminAuth,
+    version: ApiVersion,
+    stats: web::Data<VaultStats>,
+    statements: web::Data<Statements>,
+    path: web::Path<String>,
+    body: web::Json<IssueStatementRequest>,
+) -> Result<HttpResponse, ServiceError> {
+    let merchant = parse_merchant(&path)?;
+    let statement = statements.issue(&stats, merchant, &body.period, unix_timestamp())?;
+    info!(
+        merchant = %statement.merchant,
+        period = %statement.period,
+        adjustments = statement.adjustments.len(),
+        "Statement issued"
+    );
+    Ok(HttpResponse::Created().json(VaultStatsResponse {
+        success: true,
+        data: statement_json(version, &statement),
+    }))
+}
+
+/// A merchant's statements, newest first
+pub async fn list_statements(
+    version: ApiVersion,
+    statements: web::Data<Statements>,
+    path: web::Path<String>,
+) -> Result<HttpResponse, ServiceError> {
+    let merchant = parse_merchant(&path)?;
+    let list: Vec<_> = statements
+        .list(&merchant)
+        .iter()
+        .map(|statement| statement_json(version, statement))
+        .collect();
+    Ok(HttpResponse::Ok().json(VaultStatsResponse {
+        success: true,
+        data: list,
+    }))
+}
+
+/// One statement, as JSON or with `?format=csv` as a CSV download
+pub async fn get_statement(
+    version: ApiVersion,
+    statements: web::Data<Statements>,
+    path: web::Path<(String, String)>,
+    query: web::Query<StatementFormatQuery>,
+) -> Result<HttpResponse, ServiceError> {
+    let (merchant, id) = path.into_inner();
+    let merchant = parse_merchant(&merchant)?;
+    let statement = statements
+        .get(&merchant, &id)
+        .ok_or_else(|| ServiceError::NotFound(format!("Statement {} not found", id)))?;
+
+    match query.format.as_deref() {
+        None | Some("json") => Ok(HttpResponse::Ok().json(VaultStatsResponse {
+            success: true,
+            data: statement_json(version, &statement),
+        })),
+        Some("csv") => Ok(HttpResponse::Ok()
+            .content_type("text/csv")
+            .insert_header((
+                "Content-Disposition",
+                format!("attachment; filename=\"{}-{}.csv\"", statement.period, statement.id),
+            ))
+            .insert_header(("X-Content-Hash", statement.content_hash.clone()))
+            .body(statement.to_csv())),
+        Some(other) => Err(ServiceError::InvalidInput(format!(
+            "format must be json or csv, not {}",
+            other
+        ))),
+    }
+}
+
 /// Encrypt an amount
 pub async fn encrypt_amount(
+    req: HttpRequest,
     mpc_client: web::Data<MpcClient>,
+    strict_mode: web::Data<StrictMode>,
     body: web::Json<EncryptRequest>,
 ) -> Result<HttpResponse, ServiceError> {
-    let result = mpc::encrypt_amount(body.amount, mpc_client.master_key(), &body.user_pubkey)?;
+    if body.amount.numeric {
+        strict_mode.enforce(strict_mode::key_id(&req).as_deref(), &[Deprecation::numeric_amount("amount")])?;
+    }
+
+    let merchant_key = body
+        .merchant_x25519_pubkey
+        .as_deref()
+        .map(envelope::parse_public_key)
+        .transpose()?;
+
+    let result = mpc_client.encrypt(body.amount.value, &body.user_pubkey, body.commitment_algorithm)?;
+    let merchant_envelope = merchant_key
+        .map(|key| envelope::seal(body.amount.value, &key))
+        .transpose()?;
 
     Ok(HttpResponse::Ok().json(EncryptResponse {
         success: true,
         data: EncryptData {
-            ciphertext: base64::encode(&result.ciphertext),
+            ciphertext: STANDARD.encode(&result.ciphertext),
             nonce: hex::encode(&result.nonce),
             commitment: result.commitment,
+            commitment_algorithm: result.commitment_algorithm,
+            merchant_envelope,
         },
     }))
 }
@@ -136,111 +1023,1137 @@ pub async fn encrypt_amount(
 /// Decrypt an amount
 pub async fn decrypt_amount(
     mpc_client: web::Data<MpcClient>,
+    version: ApiVersion,
     body: web::Json<DecryptRequest>,
 ) -> Result<HttpResponse, ServiceError> {
-    let ciphertext = base64::decode(&body.ciphertext)
+    let ciphertext = STANDARD
+        .decode(&body.ciphertext)
         .map_err(|_| ServiceError::InvalidInput("Invalid base64 ciphertext".to_string()))?;
 
     let nonce = hex::decode(&body.nonce)
         .map_err(|_| ServiceError::InvalidInput("Invalid hex nonce".to_string()))?;
 
-    let amount = mpc::decrypt_amount(&ciphertext, &nonce, mpc_client.master_key(), &body.user_pubkey)?;
+    let amount = mpc_client.decrypt(&ciphertext, &nonce, &body.user_pubkey)?;
 
-    Ok(HttpResponse::Ok().json(DecryptResponse {
-        success: true,
-        data: DecryptData { amount },
-    }))
+    let response = match version {
+        ApiVersion::V1 => HttpResponse::Ok().json(DecryptResponse {
+            success: true,
+            data: DecryptData { amount },
+        }),
+        ApiVersion::V2 => HttpResponse::Ok().json(DecryptResponse {
+            success: true,
+            data: DecryptDataV2 { amount },
+        }),
+    };
+
+    Ok(response)
 }
 
 /// Queue a payment settlement
+#[allow(clippy::too_many_arguments)] // actix extractors
 pub async fn queue_payment_settlement(
+    req: HttpRequest,
+    version: ApiVersion,
     mpc_client: web::Data<MpcClient>,
+    store: web::Data<ComputationStore>,
+    metrics: web::Data<Metrics>,
+    dispatcher: web::Data<CallbackDispatcher>,
+    strict_mode: web::Data<StrictMode>,
+    timeline: web::Data<Timeline>,
     body: web::Json<PaymentSettlementRequest>,
 ) -> Result<HttpResponse, ServiceError> {
-    let params = crate::mpc::client::PaymentSettlementParams {
+    let received_ms = timeline::now_ms();
+    if body.amount.numeric {
+        strict_mode.enforce(strict_mode::key_id(&req).as_deref(), &[Deprecation::numeric_amount("amount")])?;
+    }
+    metadata::validate_metadata(&body.metadata)?;
+    let template = CallbackTemplate::parse(&body.callback_url, callback::PAYMENT_VARIABLES)?;
+    if let Some(tls) = &body.callback_tls {
+        tls.validate(&dispatcher.identities)?;
+    }
+
+    if body.dry_run || is_dry_run(&req) {
+        // Delivery re-checks the host; a dry run checks it up front so
+        // integrators see policy failures before going live
+        dispatcher.check_template(&template).await?;
+        return Ok(dry_run_response(version, body.into_inner()));
+    }
+
+    let params = mpc::ComputationParams::PaymentSettlement(mpc::PaymentSettlementParams {
         payment_intent_id: body.payment_intent_id.clone(),
         merchant_wallet: body.merchant_wallet.clone(),
-        amount: body.amount,
+        amount: body.amount.value,
         recipient: body.recipient.clone(),
         currency: body.currency.clone(),
-    };
+    });
+    let computation_type = params.computation_type();
+    let stored_params = store.encode_payload(&params)?;
+
+    let validated_ms = timeline::now_ms();
+    let result = mpc_client.queue_computation(params).await;
+    metrics.record(Subsystem::MpcCluster, result.is_ok());
+    let result = result?;
 
-    let result = mpc_client
-        .queue_payment_settlement(params, &body.callback_url)
-        .await?;
+    let body = body.into_inner();
+    let mut record = ComputationRecord::new(
+        result.computation_id.clone(),
+        computation_type,
+        result.status.clone(),
+        body.callback_url,
+        body.metadata.clone(),
+    );
+    record.callback_tls = body.callback_tls;
+    record.params = Some(stored_params);
+    store.insert(record);
+    record_queued(&timeline, &result, computation_type, received_ms, validated_ms);
 
     Ok(HttpResponse::Ok().json(ComputationQueuedResponse {
         success: true,
         data: ComputationData {
             computation_id: result.computation_id,
             status: result.status,
+            metadata: body.metadata,
+            wallet_report: None,
+            chain_context: None,
+            result: None,
         },
     }))
 }
 
+fn is_dry_run(req: &HttpRequest) -> bool {
+    req.headers()
+        .get("X-Dry-Run")
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.eq_ignore_ascii_case("true") || v == "1")
+}
+
+/// Echo a validated settlement without queueing or storing anything
+fn dry_run_response(version: ApiVersion, body: PaymentSettlementRequest) -> HttpResponse {
+    match version {
+        ApiVersion::V1 => {
+            let amount = body.amount.value;
+            dry_run_json(body, amount)
+        }
+        ApiVersion::V2 => {
+            let amount = body.amount.value.to_string();
+            dry_run_json(body, amount)
+        }
+    }
+}
+
+fn dry_run_json<A: Serialize>(body: PaymentSettlementRequest, amount: A) -> HttpResponse {
+    HttpResponse::Ok().json(DryRunResponse {
+        success: true,
+        data: DryRunData {
+            dry_run: true,
+            computation_id: mpc::payment_computation_id(),
+            status: "validated".to_string(),
+            request: DryRunPayment {
+                payment_intent_id: body.payment_intent_id,
+                merchant_wallet: body.merchant_wallet,
+                amount,
+                recipient: body.recipient,
+                currency: body.currency,
+                callback_url: body.callback_url,
+                metadata: body.metadata,
+            },
+        },
+    })
+}
+
 /// Queue a payroll settlement
+#[allow(clippy::too_many_arguments)] // actix extractors
 pub async fn queue_payroll_settlement(
+    req: HttpRequest,
     mpc_client: web::Data<MpcClient>,
+    store: web::Data<ComputationStore>,
+    metrics: web::Data<Metrics>,
+    chain: web::Data<ChainClient>,
+    validator: web::Data<WalletValidator>,
+    dispatcher: web::Data<CallbackDispatcher>,
+    strict_mode: web::Data<StrictMode>,
+    timeline: web::Data<Timeline>,
+    query: web::Query<ChainReadQuery>,
     body: web::Json<PayrollSettlementRequest>,
 ) -> Result<HttpResponse, ServiceError> {
+    let received_ms = timeline::now_ms();
+    let deprecations: Vec<Deprecation> = body
+        .payments
+        .iter()
+        .enumerate()
+        .filter(|(_, p)| p.amount.numeric)
+        .map(|(i, _)| Deprecation::numeric_amount(format!("payments[{}].amount", i)))
+        .collect();
+    strict_mode.enforce(strict_mode::key_id(&req).as_deref(), &deprecations)?;
+
+    CallbackTemplate::parse(&body.callback_url, callback::PAYROLL_VARIABLES)?;
+    if let Some(tls) = &body.callback_tls {
+        tls.validate(&dispatcher.identities)?;
+    }
+
+    let (wallet_report, chain_context) = if body.validate_wallets {
+        let rows: Vec<(&str, &str)> = body
+            .payments
+            .iter()
+            .map(|p| (p.employee_id.as_str(), p.employee_wallet.as_str()))
+            .collect();
+        let commitment = query.commitment.unwrap_or(chain.default_commitment());
+        let result = validator.validate(&chain, &rows, &body.currency, commitment).await;
+        metrics.record(Subsystem::SolanaRpc, result.is_ok());
+        let (report, context) = result?;
+
+        // Only hard failures block queueing; warnings are returned as advice
+        if wallet_validation::has_errors(&report) {
+            return Ok(HttpResponse::UnprocessableEntity().json(WalletValidationFailedResponse {
+                success: false,
+                error: ErrorBody {
+                    code: ErrorCode::WalletValidationFailed,
+                    message: "One or more employee wallets failed validation".to_string(),
+                },
+                data: WalletReportData {
+                    wallet_report: report,
+                    chain_context: context,
+                },
+            }));
+        }
+        (Some(report), Some(context))
+    } else {
+        (None, None)
+    };
+
     let payments = body
         .payments
         .iter()
-        .map(|p| crate::mpc::client::PayrollPayment {
+        .map(|p| mpc::PayrollPayment {
             employee_id: p.employee_id.clone(),
             employee_wallet: p.employee_wallet.clone(),
-            amount: p.amount,
+            amount: p.amount.value,
         })
         .collect();
 
-    let params = crate::mpc::client::PayrollSettlementParams {
+    let params = mpc::ComputationParams::PayrollSettlement(mpc::PayrollSettlementParams {
         batch_id: body.batch_id.clone(),
         company_wallet: body.company_wallet.clone(),
         payments,
         currency: body.currency.clone(),
-    };
+    });
+    let computation_type = params.computation_type();
+    let stored_params = store.encode_payload(&params)?;
+
+    let validated_ms = timeline::now_ms();
+    let result = mpc_client.queue_computation(params).await;
+    metrics.record(Subsystem::MpcCluster, result.is_ok());
+    let result = result?;
 
-    let result = mpc_client
-        .queue_payroll_settlement(params, &body.callback_url)
-        .await?;
+    let mut record = ComputationRecord::new(
+        result.computation_id.clone(),
+        computation_type,
+        result.status.clone(),
+        body.callback_url.clone(),
+        Metadata::new(),
+    );
+    record.batch_id = Some(body.batch_id.clone());
+    record.callback_tls = body.callback_tls.clone();
+    record.params = Some(stored_params);
+    store.insert(record);
+    record_queued(&timeline, &result, computation_type, received_ms, validated_ms);
 
     Ok(HttpResponse::Ok().json(ComputationQueuedResponse {
         success: true,
         data: ComputationData {
             computation_id: result.computation_id,
             status: result.status,
+            metadata: Metadata::new(),
+            wallet_report,
+            chain_context,
+            result: None,
         },
     }))
 }
 
+/// Queue a payroll streamed as NDJSON, one payment per line.
+///
+/// Rows are validated as they arrive and grouped into chunks of
+/// `PAYROLL_CHUNK_SIZE`; chunks are queued only once the upload is complete
+/// and every row is valid, so an aborted or rejected upload queues nothing.
+#[allow(clippy::too_many_arguments)] // actix extractors
+pub async fn queue_payroll_ndjson(
+    req: HttpRequest,
+    mpc_client: web::Data<MpcClient>,
+    store: web::Data<ComputationStore>,
+    metrics: web::Data<Metrics>,
+    config: web::Data<Config>,
+    dispatcher: web::Data<CallbackDispatcher>,
+    strict_mode: web::Data<StrictMode>,
+    timeline: web::Data<Timeline>,
+    query: web::Query<PayrollNdjsonQuery>,
+    mut payload: web::Payload,
+) -> Result<HttpResponse, ServiceError> {
+    let received_ms = timeline::now_ms();
+    let query = query.into_inner();
+    CallbackTemplate::parse(&query.callback_url, callback::PAYROLL_VARIABLES)?;
+    let callback_tls = query.callback_client_identity_id.clone().map(|id| CallbackTls {
+        ca_bundle: None,
+        client_identity_id: Some(id),
+    });
+    if let Some(tls) = &callback_tls {
+        tls.validate(&dispatcher.identities)?;
+    }
+    for (field, value) in [
+        ("batch_id", &query.batch_id),
+        ("company_wallet", &query.company_wallet),
+        ("currency", &query.currency),
+    ] {
+        if value.trim().is_empty() {
+            return Err(ServiceError::InvalidInput(format!("{} must not be empty", field)));
+        }
+    }
+
+    // Each chunk is validated before the next is read, so a slow validator
+    // slows the upload rather than buffering it
+    let mut ingest = PayrollIngest::new(config.payroll_ingest);
+    while let Some(chunk) = payload.next().await {
+        let chunk =
+            chunk.map_err(|e| ServiceError::InvalidInput(format!("Upload aborted: {}", e)))?;
+        ingest.push(&chunk)?;
+    }
+    let IngestedPayroll {
+        rows,
+        bytes,
+        chunks,
+        error_count,
+        errors,
+        first_numeric_amount_line,
+    } = ingest.finish()?;
+
+    // Counted once per upload, reported at the first offending line
+    if let Some(line) = first_numeric_amount_line {
+        let deprecation = Deprecation::numeric_amount(format!("lines[{}].amount", line));
+        strict_mode.enforce(strict_mode::key_id(&req).as_deref(), &[deprecation])?;
+    }
+
+    let mut data = PayrollNdjsonData {
+        batch_id: query.batch_id.clone(),
+        rows,
+        bytes,
+        chunks: Vec::with_capacity(chunks.len()),
+        error_count,
+        errors,
+    };
+
+    if error_count > 0 {
+        return Ok(HttpResponse::UnprocessableEntity().json(PayrollNdjsonResponse {
+            success: false,
+            error: Some(ErrorBody {
+                code: ErrorCode::PayrollRowsInvalid,
+                message: format!("{} of {} rows failed validation", error_count, rows),
+            }),
+            data,
+        }));
+    }
+    if chunks.is_empty() {
+        return Err(ServiceError::InvalidInput("Upload contains no payments".to_string()));
+    }
+
+    // Every chunk must fit the stored payload limit before any is queued
+    let mut prepared = Vec::with_capacity(chunks.len());
+    for (index, payments) in chunks.into_iter().enumerate() {
+        let chunk_rows = payments.len();
+        let params = mpc::ComputationParams::PayrollSettlement(mpc::PayrollSettlementParams {
+            batch_id: format!("{}-{}", query.batch_id, index + 1),
+            company_wallet: query.company_wallet.clone(),
+            payments,
+            currency: query.currency.clone(),
+        });
+        let stored_params = store.encode_payload(&params).map_err(|e| match e {
+            ServiceError::PayloadTooLarge(msg) => {
+                ServiceError::PayloadTooLarge(format!("chunk {}: {}", index + 1, msg))
+            }
+            e => e,
+        })?;
+        prepared.push((params, stored_params, chunk_rows));
+    }
+
+    let validated_ms = timeline::now_ms();
+    for (index, (params, stored_params, chunk_rows)) in prepared.into_iter().enumerate() {
+        let computation_type = params.computation_type();
+
+        let result = mpc_client.queue_computation(params).await;
+        metrics.record(Subsystem::MpcCluster, result.is_ok());
+        let result = match result {
+            Ok(result) => result,
+            Err(e) if data.chunks.is_empty() => return Err(e),
+            // The cluster has no cancel; report what is already queued so the
+            // caller can reconcile instead of resubmitting everything
+            Err(e) => {
+                let queued: Vec<&str> =
+                    data.chunks.iter().map(|c| c.computation_id.as_str()).collect();
+                return Err(ServiceError::MpcError(format!(
+                    "{} (chunk {} of batch {}; already queued: {})",
+                    e,
+                    index + 1,
+                    query.batch_id,
+                    queued.join(", ")
+                )));
+            }
+        };
+
+        // Callbacks for every chunk carry the parent batch id
+        let mut record = ComputationRecord::new(
+            result.computation_id.clone(),
+            computation_type,
+            result.status.clone(),
+            query.callback_url.clone(),
+            Metadata::new(),
+        );
+        record.batch_id = Some(query.batch_id.clone());
+        record.callback_tls = callback_tls.clone();
+        record.params = Some(stored_params);
+        store.insert(record);
+        record_queued(&timeline, &result, computation_type, received_ms, validated_ms);
+
+        data.chunks.push(QueuedChunk {
+            index: index + 1,
+            computation_id: result.computation_id,
+            status: result.status,
+            rows: chunk_rows,
+        });
+    }
+
+    Ok(HttpResponse::Ok().json(PayrollNdjsonResponse {
+        success: true,
+        error: None,
+        data,
+    }))
+}
+
+/// Computations queued by this service, as tracked locally
+pub async fn list_computations(
+    store: web::Data<ComputationStore>,
+    query: ListQuery<ComputationRecord>,
+) -> HttpResponse {
+    HttpResponse::Ok().json(ListResponse {
+        success: true,
+        data: query.paginate(store.list()),
+    })
+}
+
 /// Get computation status
 pub async fn get_computation_status(
     mpc_client: web::Data<MpcClient>,
+    store: web::Data<ComputationStore>,
+    metrics: web::Data<Metrics>,
+    timeline: web::Data<Timeline>,
     path: web::Path<String>,
 ) -> Result<HttpResponse, ServiceError> {
     let computation_id = path.into_inner();
-    let result = mpc_client.get_computation_status(&computation_id).await?;
+    let result = mpc_client.get_computation_status(&computation_id).await;
+    metrics.record(Subsystem::MpcCluster, result.is_ok());
+    // Only for computations queued here, so unknown ids leave no timeline
+    if store.contains(&computation_id) {
+        let event = match &result {
+            Ok(response) => TimelineEvent::StatusPolled { status: response.status.clone() },
+            Err(e) => TimelineEvent::StatusPollFailed { error: e.to_string() },
+        };
+        timeline.record(&computation_id, event);
+    }
+    let result = result?;
+
+    let record = store.update_status(&result.computation_id, &result.status);
+    let stored_result = match record.as_ref().and_then(|r| r.result.as_ref()) {
+        Some(payload) => Some(payload.to_json()?),
+        None => None,
+    };
 
     Ok(HttpResponse::Ok().json(ComputationQueuedResponse {
         success: true,
         data: ComputationData {
             computation_id: result.computation_id,
             status: result.status,
+            metadata: record.map(|r| r.metadata).unwrap_or_default(),
+            wallet_report: None,
+            chain_context: None,
+            result: stored_result,
+        },
+    }))
+}
+
+/// Every recorded step of a computation, in order, with the time between them
+pub async fn computation_timeline(
+    timeline: web::Data<Timeline>,
+    path: web::Path<String>,
+) -> Result<HttpResponse, ServiceError> {
+    let computation_id = path.into_inner();
+    let data = timeline
+        .get(&computation_id)
+        .ok_or_else(|| ServiceError::NotFound(format!("No timeline for computation: {}", computation_id)))?;
+    Ok(HttpResponse::Ok().json(TimelineResponse { success: true, data }))
+}
+
+/// Timeline entries for a computation the cluster just accepted; the first
+/// two are timed from before it had an id
+fn record_queued(
+    timeline: &Timeline,
+    result: &mpc::ComputationResponse,
+    computation_type: &str,
+    received_ms: u64,
+    validated_ms: u64,
+) {
+    let computation_type = computation_type.to_string();
+    timeline.record_at(&result.computation_id, received_ms, TimelineEvent::Created { computation_type });
+    timeline.record_at(&result.computation_id, validated_ms, TimelineEvent::Validated);
+    timeline.record(
+        &result.computation_id,
+        TimelineEvent::Queued {
+            request_hash: result.request_hash.clone(),
+            status: result.status.clone(),
+        },
+    );
+}
+
+/// Receive a computation result from the cluster and relay it to the caller
+#[allow(clippy::too_many_arguments)] // actix extractors
+pub async fn receive_cluster_callback(
+    req: HttpRequest,
+    config: web::Data<Config>,
+    store: web::Data<ComputationStore>,
+    dispatcher: web::Data<CallbackDispatcher>,
+    metrics: web::Data<Metrics>,
+    quarantine: web::Data<Quarantine>,
+    leader: web::Data<LeaderElector>,
+    timeline: web::Data<Timeline>,
+    body: web::Bytes,
+) -> Result<HttpResponse, ServiceError> {
+    let signature = req
+        .headers()
+        .get("X-Arcium-Signature")
+        .and_then(|v| v.to_str().ok())
+        .ok_or_else(|| ServiceError::Unauthorized("Missing callback signature".to_string()))?;
+
+    if !callback::verify_cluster_signature(&config.callback_secret, &body, signature) {
+        warn!("Invalid cluster callback signature received");
+        return Err(ServiceError::Unauthorized("Invalid callback signature".to_string()));
+    }
+
+    // Authentic but malformed: keep the raw body and leave state untouched so
+    // the cluster retries and an operator can replay after a fix
+    let callback = match callback::parse_cluster_callback(&body) {
+        Ok(callback) => callback,
+        Err(reason) => {
+            let entry = quarantine.add(&body, reason);
+            metrics.record(Subsystem::MpcCluster, false);
+            warn!("Quarantined malformed cluster callback {}: {}", entry.id, entry.reason);
+            return Err(ServiceError::CallbackQuarantined(format!(
+                "{} (quarantined as {})",
+                entry.reason, entry.id
+            )));
+        }
+    };
+
+    apply_cluster_callback(callback, &store, &dispatcher, &metrics, &leader, &timeline)?;
+
+    Ok(HttpResponse::Ok().json(CallbackAckResponse { success: true }))
+}
+
+/// Record a validated cluster callback and relay it to the caller
+fn apply_cluster_callback(
+    callback: ClusterCallback,
+    store: &web::Data<ComputationStore>,
+    dispatcher: &web::Data<CallbackDispatcher>,
+    metrics: &web::Data<Metrics>,
+    leader: &web::Data<LeaderElector>,
+    timeline: &web::Data<Timeline>,
+) -> Result<(), ServiceError> {
+    let record = store
+        .update_status(&callback.computation_id, callback.status.as_str())
+        .ok_or_else(|| {
+            ServiceError::NotFound(format!("Unknown computation: {}", callback.computation_id))
+        })?;
+    timeline.record(
+        &record.computation_id,
+        TimelineEvent::CallbackReceived { status: record.status.clone() },
+    );
+
+    // A failed settlement submission reports the raw program error; decode it
+    // so the caller sees which check failed
+    let mut result = callback.result;
+    if callback.status == ClusterStatus::Failed {
+        program_errors::annotate_failure(&mut result);
+    }
+    if let Some(result) = &result {
+        store.record_result(&record.computation_id, result);
+    }
+
+    let payload = CallbackPayload {
+        computation_id: record.computation_id.clone(),
+        computation_type: record.computation_type.clone(),
+        status: record.status.clone(),
+        batch_id: record.batch_id.clone(),
+        metadata: record.metadata.clone(),
+        result,
+    };
+
+    // Template was validated at queue time; deliver in the background so the
+    // cluster's request isn't held open by the caller's endpoint
+    let template = CallbackTemplate::parse(&record.callback_url, callback::TEMPLATE_VARIABLES)?;
+    let Some(token) = leader.fencing_token() else {
+        tracing::info!(
+            "Not the leader; leaving callback delivery for {} to the active instance",
+            record.computation_id
+        );
+        return Ok(());
+    };
+    let (store, dispatcher, metrics, leader, timeline) =
+        (store.clone(), dispatcher.clone(), metrics.clone(), leader.clone(), timeline.clone());
+    actix_web::rt::spawn(async move {
+        if !leader.check_fence(token) {
+            warn!("Fencing token {} is stale; skipping callback delivery", token);
+            return;
+        }
+        let vars = CallbackVars {
+            computation_id: &record.computation_id,
+            batch_id: record.batch_id.as_deref(),
+            status: &record.status,
+        };
+        let entry = dispatcher
+            .deliver(&template, record.callback_tls.as_ref(), &vars, &payload)
+            .await;
+        let delivered = entry.status_code.is_some_and(|code| (200..300).contains(&code));
+        metrics.record(Subsystem::Webhooks, delivered);
+        timeline.record(&record.computation_id, TimelineEvent::WebhookDelivered(entry.clone()));
+        store.record_delivery(&record.computation_id, entry);
+    });
+
+    Ok(())
+}
+
+/// List quarantined cluster callbacks
+pub async fn list_quarantine(
+    _admin: AdminAuth,
+    quarantine: web::Data<Quarantine>,
+    query: ListQuery<QuarantinedCallback>,
+) -> HttpResponse {
+    HttpResponse::Ok().json(ListResponse {
+        success: true,
+        data: query.paginate(quarantine.list()),
+    })
+}
+
+/// Re-run a quarantined callback through validation and apply it
+#[allow(clippy::too_many_arguments)] // actix extractors
+pub async fn replay_quarantined(
+    _admin: AdminAuth,
+    quarantine: web::Data<Quarantine>,
+    store: web::Data<ComputationStore>,
+    dispatcher: web::Data<CallbackDispatcher>,
+    metrics: web::Data<Metrics>,
+    leader: web::Data<LeaderElector>,
+    timeline: web::Data<Timeline>,
+    path: web::Path<String>,
+) -> Result<HttpResponse, ServiceError> {
+    let id = path.into_inner();
+    let entry = quarantine
+        .get(&id)
+        .ok_or_else(|| ServiceError::NotFound(format!("Unknown quarantined callback: {}", id)))?;
+
+    let callback = callback::parse_cluster_callback(entry.payload.as_bytes())
+        .map_err(|reason| ServiceError::CallbackQuarantined(format!("{} (still quarantined)", reason)))?;
+    apply_cluster_callback(callback, &store, &dispatcher, &metrics, &leader, &timeline)?;
+    quarantine.remove(&id);
+    tracing::info!("Replayed quarantined callback {}", id);
+
+    Ok(HttpResponse::Ok().json(CallbackAckResponse { success: true }))
+}
+
+/// Public, cacheable service status (no authentication)
+pub async fn public_status(
+    req: HttpRequest,
+    board: web::Data<StatusBoard>,
+    metrics: web::Data<Metrics>,
+) -> Result<HttpResponse, ServiceError> {
+    let client = req
+        .connection_info()
+        .realip_remote_addr()
+        .unwrap_or("unknown")
+        .to_string();
+    board.limiter.check(&client)?;
+
+    let status = board.render(&metrics);
+    let cache_control = format!("public, max-age={}", crate::status::STATUS_CACHE_SECS);
+
+    let not_modified = req
+        .headers()
+        .get("If-None-Match")
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|tags| tags.split(',').any(|tag| tag.trim() == status.etag));
+    if not_modified {
+        return Ok(HttpResponse::NotModified()
+            .insert_header(("ETag", status.etag.clone()))
+            .insert_header(("Cache-Control", cache_control))
+            .finish());
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type("application/json")
+        .insert_header(("ETag", status.etag.clone()))
+        .insert_header(("Cache-Control", cache_control))
+        .body(status.body.clone()))
+}
+
+/// List open incidents
+pub async fn list_incidents(
+    _admin: AdminAuth,
+    board: web::Data<StatusBoard>,
+    query: ListQuery<Incident>,
+) -> HttpResponse {
+    HttpResponse::Ok().json(ListResponse {
+        success: true,
+        data: query.paginate(board.incidents()),
+    })
+}
+
+/// Declare an incident on the public status page
+pub async fn open_incident(
+    _admin: AdminAuth,
+    board: web::Data<StatusBoard>,
+    body: web::Json<OpenIncidentRequest>,
+) -> Result<HttpResponse, ServiceError> {
+    let body = body.into_inner();
+    if body.message.trim().is_empty() || body.message.len() > 500 {
+        return Err(ServiceError::InvalidInput(
+            "message must be between 1 and 500 characters".to_string(),
+        ));
+    }
+
+    let incident = board.open_incident(body.subsystem, body.status, body.message);
+    tracing::info!("Incident opened: {} ({:?})", incident.id, incident.subsystem);
+
+    Ok(HttpResponse::Ok().json(IncidentResponse {
+        success: true,
+        data: incident,
+    }))
+}
+
+/// Resolve an incident
+pub async fn resolve_incident(
+    _admin: AdminAuth,
+    board: web::Data<StatusBoard>,
+    path: web::Path<String>,
+) -> Result<HttpResponse, ServiceError> {
+    let id = path.into_inner();
+    if !board.resolve_incident(&id) {
+        return Err(ServiceError::NotFound(format!("Unknown incident: {}", id)));
+    }
+    tracing::info!("Incident resolved: {}", id);
+
+    Ok(HttpResponse::Ok().json(CallbackAckResponse { success: true }))
+}
+
+/// Who toggled a switch, for the audit log
+fn operator_address(req: &HttpRequest) -> String {
+    req.connection_info()
+        .realip_remote_addr()
+        .unwrap_or("unknown")
+        .to_string()
+}
+
+/// Switch off a public endpoint; callers get 503 `ENDPOINT_DISABLED` until
+/// it is re-enabled
+pub async fn disable_endpoint(
+    _admin: AdminAuth,
+    req: HttpRequest,
+    switches: web::Data<EndpointSwitches>,
+    path: web::Path<String>,
+    body: Option<web::Json<DisableEndpointRequest>>,
+) -> Result<HttpResponse, ServiceError> {
+    let endpoint = Endpoint::parse(&path.into_inner())?;
+    let message = body
+        .and_then(|b| b.into_inner().message)
+        .map(|m| m.trim().to_string())
+        .filter(|m| !m.is_empty());
+    if message.as_ref().is_some_and(|m| m.len() > 500) {
+        return Err(ServiceError::InvalidInput(
+            "message must be at most 500 characters".to_string(),
+        ));
+    }
+
+    switches.disable(endpoint, message.clone());
+    warn!(
+        target: "audit",
+        "Endpoint {} disabled by admin from {}: {}",
+        endpoint.as_str(),
+        operator_address(&req),
+        message.as_deref().unwrap_or("(no message)")
+    );
+
+    Ok(HttpResponse::Ok().json(EndpointSwitchResponse {
+        success: true,
+        data: EndpointSwitchState {
+            endpoint,
+            enabled: false,
+            disabled_endpoints: switches.disabled(),
         },
     }))
 }
 
+/// Switch a disabled endpoint back on; a no-op if it is already enabled
+pub async fn enable_endpoint(
+    _admin: AdminAuth,
+    req: HttpRequest,
+    switches: web::Data<EndpointSwitches>,
+    path: web::Path<String>,
+) -> Result<HttpResponse, ServiceError> {
+    let endpoint = Endpoint::parse(&path.into_inner())?;
+    if switches.enable(endpoint) {
+        warn!(
+            target: "audit",
+            "Endpoint {} enabled by admin from {}",
+            endpoint.as_str(),
+            operator_address(&req)
+        );
+    }
+
+    Ok(HttpResponse::Ok().json(EndpointSwitchResponse {
+        success: true,
+        data: EndpointSwitchState {
+            endpoint,
+            enabled: true,
+            disabled_endpoints: switches.disabled(),
+        },
+    }))
+}
+
+/// Strict mode settings and deprecated-format usage per API key
+pub async fn strict_mode_state(_admin: AdminAuth, strict_mode: web::Data<StrictMode>) -> HttpResponse {
+    HttpResponse::Ok().json(StrictModeResponse {
+        success: true,
+        data: strict_mode.state(),
+    })
+}
+
+/// Turn strict mode on or off for one key, overriding the default
+pub async fn set_strict_mode(
+    _admin: AdminAuth,
+    req: HttpRequest,
+    strict_mode: web::Data<StrictMode>,
+    path: web::Path<String>,
+    body: web::Json<SetStrictModeRequest>,
+) -> Result<HttpResponse, ServiceError> {
+    let key_id = strict_mode::parse_key_id(&path.into_inner())?;
+    strict_mode.set(&key_id, body.strict_mode);
+    warn!(
+        target: "audit",
+        "Strict mode {} for key {} by admin from {}",
+        if body.strict_mode { "enabled" } else { "disabled" },
+        key_id,
+        operator_address(&req)
+    );
+
+    Ok(HttpResponse::Ok().json(StrictModeResponse {
+        success: true,
+        data: strict_mode.state(),
+    }))
+}
+
+/// Drop a key's override so it follows the default again
+pub async fn clear_strict_mode(
+    _admin: AdminAuth,
+    req: HttpRequest,
+    strict_mode: web::Data<StrictMode>,
+    path: web::Path<String>,
+) -> Result<HttpResponse, ServiceError> {
+    let key_id = strict_mode::parse_key_id(&path.into_inner())?;
+    if strict_mode.clear(&key_id) {
+        warn!(
+            target: "audit",
+            "Strict mode override for key {} cleared by admin from {}",
+            key_id,
+            operator_address(&req)
+        );
+    }
+
+    Ok(HttpResponse::Ok().json(StrictModeResponse {
+        success: true,
+        data: strict_mode.state(),
+    }))
+}
+
+/// Client identities available for callback mTLS
+pub async fn list_client_identities(
+    _admin: AdminAuth,
+    dispatcher: web::Data<CallbackDispatcher>,
+) -> HttpResponse {
+    HttpResponse::Ok().json(ClientIdentitiesResponse {
+        success: true,
+        data: dispatcher.identities.list(),
+    })
+}
+
+/// Upload a client certificate and key for callbacks to present
+pub async fn create_client_identity(
+    _admin: AdminAuth,
+    req: HttpRequest,
+    dispatcher: web::Data<CallbackDispatcher>,
+    body: web::Json<CreateClientIdentityRequest>,
+) -> Result<HttpResponse, ServiceError> {
+    let body = body.into_inner();
+    if body.name.trim().is_empty() || body.name.len() > 100 {
+        return Err(ServiceError::InvalidInput(
+            "name must be between 1 and 100 characters".to_string(),
+        ));
+    }
+
+    let summary = dispatcher
+        .identities
+        .create(body.name, body.certificate, &body.private_key)?;
+    warn!(
+        target: "audit",
+        "Client identity {} ({}) created by admin from {}",
+        summary.id,
+        summary.name,
+        operator_address(&req)
+    );
+
+    Ok(HttpResponse::Ok().json(ClientIdentityResponse {
+        success: true,
+        data: summary,
+    }))
+}
+
+/// Delete a client identity; callbacks still referencing it fail with
+/// `tls_misconfigured`
+pub async fn delete_client_identity(
+    _admin: AdminAuth,
+    req: HttpRequest,
+    dispatcher: web::Data<CallbackDispatcher>,
+    path: web::Path<String>,
+) -> Result<HttpResponse, ServiceError> {
+    let id = path.into_inner();
+    if !dispatcher.remove_identity(&id) {
+        return Err(ServiceError::NotFound(format!("Unknown client identity: {}", id)));
+    }
+    warn!(
+        target: "audit",
+        "Client identity {} deleted by admin from {}",
+        id,
+        operator_address(&req)
+    );
+
+    Ok(HttpResponse::Ok().json(CallbackAckResponse { success: true }))
+}
+
 /// Verify a commitment
 pub async fn verify_commitment(
+    req: HttpRequest,
+    strict_mode: web::Data<StrictMode>,
     body: web::Json<VerifyCommitmentRequest>,
 ) -> Result<HttpResponse, ServiceError> {
-    let nonce = hex::decode(&body.nonce)
-        .map_err(|_| ServiceError::InvalidInput("Invalid hex nonce".to_string()))?;
-
-    let valid = mpc::generate_commitment(body.amount, &nonce) == body.commitment;
+    let mut deprecations = Vec::new();
+    body.deprecations("", &mut deprecations);
+    strict_mode.enforce(strict_mode::key_id(&req).as_deref(), &deprecations)?;
 
     Ok(HttpResponse::Ok().json(VerifyCommitmentResponse {
         success: true,
-        data: VerifyData { valid },
+        data: check_commitment(&body).map_err(ServiceError::InvalidInput)?,
+    }))
+}
+
+/// Verify a batch of commitments, each with its own algorithm
+pub async fn verify_commitments(
+    req: HttpRequest,
+    strict_mode: web::Data<StrictMode>,
+    body: web::Json<VerifyCommitmentsRequest>,
+) -> Result<HttpResponse, ServiceError> {
+    if body.commitments.len() > MAX_VERIFY_BATCH {
+        return Err(ServiceError::InvalidInput(format!(
+            "At most {} commitments per request",
+            MAX_VERIFY_BATCH
+        )));
+    }
+
+    let mut deprecations = Vec::new();
+    for (i, item) in body.commitments.iter().enumerate() {
+        item.deprecations(&format!("commitments[{}].", i), &mut deprecations);
+    }
+    strict_mode.enforce(strict_mode::key_id(&req).as_deref(), &deprecations)?;
+
+    let results = body
+        .commitments
+        .iter()
+        .enumerate()
+        .map(|(i, item)| {
+            check_commitment(item).map_err(|e| ServiceError::InvalidInput(format!("commitments[{}]: {}", i, e)))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(HttpResponse::Ok().json(VerifyCommitmentsResponse {
+        success: true,
+        data: VerifyBatchData {
+            all_valid: results.iter().all(|r| r.valid),
+            results,
+        },
     }))
 }
+
+fn check_commitment(request: &VerifyCommitmentRequest) -> Result<VerifyData, String> {
+    let blinding_factor = match (&request.blinding_factor, &request.nonce) {
+        (Some(value), None) | (None, Some(value)) => value,
+        (Some(_), Some(_)) => return Err("Send blinding_factor or nonce, not both".to_string()),
+        (None, None) => return Err("blinding_factor is required".to_string()),
+    };
+    let blinding_factor = hex::decode(blinding_factor).map_err(|_| "Invalid hex blinding factor".to_string())?;
+
+    Ok(VerifyData {
+        valid: mpc::verify_commitment(request.amount.value, &blinding_factor, &request.commitment, request.algorithm),
+        algorithm: request.algorithm,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture(name: &str) -> serde_json::Value {
+        let path = format!("{}/tests/fixtures/{}", env!("CARGO_MANIFEST_DIR"), name);
+        let contents = std::fs::read_to_string(&path).expect("missing fixture");
+        serde_json::from_str(&contents).expect("invalid fixture JSON")
+    }
+
+    #[actix_rt::test]
+    async fn test_format_amount() {
+        use actix_web::body::MessageBody;
+
+        let query = |qs: &str| web::Query::<FormatQuery>::from_query(qs).unwrap();
+        let response = format_amount(query("amount=1234560000&currency=usdc&locale=de_DE")).await.unwrap();
+        let body = response.into_body().try_into_bytes().unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["data"]["amount"], "1234560000");
+        assert_eq!(json["data"]["locale"], "de-DE");
+        assert_eq!(json["data"]["display"], "1.234,56 USDC");
+
+        for qs in [
+            "amount=-1&currency=USDC&locale=en-US",
+            "amount=1.5&currency=USDC&locale=en-US",
+            "amount=1&currency=EUR&locale=en-US",
+            "amount=1&currency=USDC&locale=xx-XX",
+        ] {
+            assert!(matches!(format_amount(query(qs)).await, Err(ServiceError::InvalidInput(_))), "{}", qs);
+        }
+    }
+
+    #[test]
+    fn test_decrypt_response_contract() {
+        let v1 = DecryptResponse {
+            success: true,
+            data: DecryptData { amount: u64::MAX },
+        };
+        let v2 = DecryptResponse {
+            success: true,
+            data: DecryptDataV2 { amount: u64::MAX },
+        };
+
+        assert_eq!(serde_json::to_value(v1).unwrap(), fixture("v1_decrypt_response.json"));
+        assert_eq!(serde_json::to_value(v2).unwrap(), fixture("v2_decrypt_response.json"));
+    }
+
+    #[test]
+    fn test_computation_response_contract() {
+        let response = ComputationQueuedResponse {
+            success: true,
+            data: ComputationData {
+                computation_id: "pay_00112233445566778899aabbccddeeff".to_string(),
+                status: "queued".to_string(),
+                metadata: Metadata::from([("order_id".to_string(), "ord_123".to_string())]),
+                wallet_report: None,
+                chain_context: None,
+                result: None,
+            },
+        };
+
+        assert_eq!(
+            serde_json::to_value(response).unwrap(),
+            fixture("computation_queued_response.json")
+        );
+    }
+
+    #[test]
+    fn test_settlement_request_contract() {
+        let v1: PaymentSettlementRequest =
+            serde_json::from_value(fixture("v1_payment_settlement_request.json")).unwrap();
+        let v2: PaymentSettlementRequest =
+            serde_json::from_value(fixture("v2_payment_settlement_request.json")).unwrap();
+
+        // Above 2^53, so a JS number would have lost precision
+        assert_eq!(v1.amount.value, 9_007_199_254_740_993);
+        assert_eq!(v1.amount.value, v2.amount.value);
+        // The v1 number is what strict mode rejects
+        assert!(v1.amount.numeric && !v2.amount.numeric);
+        assert_eq!(v1.metadata, v2.metadata);
+        // Omitted flag means a real submission
+        assert!(!v1.dry_run);
+    }
+
+    #[test]
+    fn test_verifies_mixed_algorithm_batches() {
+        // Requests without an algorithm predate BLAKE3 and mean SHA-256
+        let items: Vec<serde_json::Value> = fixture("commitment_vectors.json")["vectors"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|v| {
+                let mut item = serde_json::json!({
+                    "amount": v["amount"],
+                    "nonce": v["blinding_factor"],
+                    "commitment": v["commitment"],
+                });
+                if v["algorithm"] != "sha256" {
+                    item["algorithm"] = v["algorithm"].clone();
+                }
+                item
+            })
+            .collect();
+        let request: VerifyCommitmentsRequest =
+            serde_json::from_value(serde_json::json!({ "commitments": items })).unwrap();
+
+        let results: Vec<VerifyData> = request.commitments.iter().map(|c| check_commitment(c).unwrap()).collect();
+        assert!(results.iter().all(|r| r.valid));
+        assert!(results.iter().any(|r| r.algorithm == CommitmentAlgorithm::Blake3));
+
+        // A SHA-256 commitment checked as BLAKE3 doesn't verify
+        let mut swapped: VerifyCommitmentRequest = serde_json::from_value(items[0].clone()).unwrap();
+        swapped.algorithm = CommitmentAlgorithm::Blake3;
+        assert!(!check_commitment(&swapped).unwrap().valid);
+    }
+
+    #[test]
+    fn test_blinding_factor_replaces_nonce() {
+        let vector = &fixture("commitment_vectors.json")["vectors"][0];
+        let modern: VerifyCommitmentRequest = serde_json::from_value(serde_json::json!({
+            "amount": vector["amount"],
+            "blinding_factor": vector["blinding_factor"],
+            "commitment": vector["commitment"],
+        }))
+        .unwrap();
+        assert!(check_commitment(&modern).unwrap().valid);
+        let mut deprecations = Vec::new();
+        modern.deprecations("", &mut deprecations);
+        assert!(deprecations.is_empty());
+
+        let legacy: VerifyCommitmentRequest = serde_json::from_value(serde_json::json!({
+            "amount": vector["amount"].as_str().unwrap().parse::<u64>().unwrap(),
+            "nonce": vector["blinding_factor"],
+            "commitment": vector["commitment"],
+        }))
+        .unwrap();
+        assert!(check_commitment(&legacy).unwrap().valid);
+        legacy.deprecations("commitments[2].", &mut deprecations);
+        let fields: Vec<&str> = deprecations.iter().map(|d| d.field.as_str()).collect();
+        assert_eq!(fields, ["commitments[2].amount", "commitments[2].nonce"]);
+
+        let both = VerifyCommitmentRequest {
+            blinding_factor: modern.blinding_factor.clone(),
+            ..legacy
+        };
+        assert_eq!(check_commitment(&both).err().as_deref(), Some("Send blinding_factor or nonce, not both"));
+    }
+}
@@ -0,0 +1,214 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tracing::{info, warn};
+
+use crate::config::Config;
+use crate::store::unix_timestamp;
+
+/// Lease name guarding background side effects (callback delivery)
+pub const LEADER_LEASE: &str = "arcium-service-leader";
+
+#[derive(Debug, Clone)]
+struct Lease {
+    holder: String,
+    token: u64,
+    expires_at: u64,
+}
+
+/// Lease rows keyed by name, with compare-and-set acquisition.
+///
+/// In-process for now: it coordinates a single deployment and defines the
+/// semantics a shared lease table has to provide for multi-region failover.
+#[derive(Default)]
+pub struct LeaseTable {
+    leases: Mutex<HashMap<String, Lease>>,
+}
+
+impl LeaseTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Acquire or renew `name` for `holder`, returning its fencing token.
+    /// A new holder always gets a strictly larger token than the previous one.
+    fn try_acquire(&self, name: &str, holder: &str, ttl_secs: u64, now: u64) -> Option<u64> {
+        let mut leases = self.leases.lock().unwrap();
+        let token = match leases.get(name) {
+            Some(lease) if lease.holder == holder => lease.token,
+            Some(lease) if lease.expires_at > now => return None,
+            Some(lease) => lease.token + 1,
+            None => 1,
+        };
+        leases.insert(
+            name.to_string(),
+            Lease {
+                holder: holder.to_string(),
+                token,
+                expires_at: now + ttl_secs,
+            },
+        );
+        Some(token)
+    }
+
+    /// Token of the unexpired lease on `name`, if any
+    fn current_token(&self, name: &str, now: u64) -> Option<u64> {
+        let leases = self.leases.lock().unwrap();
+        leases
+            .get(name)
+            .filter(|lease| lease.expires_at > now)
+            .map(|lease| lease.token)
+    }
+}
+
+/// Leadership as reported by the health endpoint
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub struct LeadershipState {
+    pub instance_id: String,
+    pub is_leader: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fencing_token: Option<u64>,
+    pub acquisitions: u64,
+    pub handovers: u64,
+}
+
+/// Decides whether this instance runs background side effects.
+///
+/// Every instance serves HTTP; only the lease holder delivers callbacks.
+pub struct LeaderElector {
+    instance_id: String,
+    table: Arc<LeaseTable>,
+    ttl_secs: u64,
+    max_clock_skew_secs: u64,
+    /// Fencing token and local expiry of the lease we hold
+    held: Mutex<Option<(u64, u64)>>,
+    acquisitions: AtomicU64,
+    handovers: AtomicU64,
+}
+
+impl LeaderElector {
+    pub fn new(config: &Config, table: Arc<LeaseTable>) -> Self {
+        Self {
+            instance_id: config.instance_id.clone(),
+            table,
+            ttl_secs: config.leader_lease_ttl_secs,
+            max_clock_skew_secs: config.leader_max_clock_skew_secs,
+            held: Mutex::new(None),
+            acquisitions: AtomicU64::new(0),
+            handovers: AtomicU64::new(0),
+        }
+    }
+
+    /// How often to renew; a third of the TTL leaves room for two missed beats
+    pub fn heartbeat_interval_secs(&self) -> u64 {
+        (self.ttl_secs / 3).max(1)
+    }
+
+    /// Acquire or renew the lease
+    pub fn heartbeat(&self) {
+        self.heartbeat_at(unix_timestamp());
+    }
+
+    fn heartbeat_at(&self, now: u64) {
+        let acquired = self
+            .table
+            .try_acquire(LEADER_LEASE, &self.instance_id, self.ttl_secs, now);
+
+        let mut held = self.held.lock().unwrap();
+        match (held.is_some(), acquired) {
+            (false, Some(token)) => {
+                self.acquisitions.fetch_add(1, Ordering::Relaxed);
+                info!("{} acquired leadership (fencing token {})", self.instance_id, token);
+            }
+            (true, None) => {
+                self.handovers.fetch_add(1, Ordering::Relaxed);
+                warn!("{} lost leadership", self.instance_id);
+            }
+            _ => {}
+        }
+        *held = acquired.map(|token| (token, now + self.ttl_secs));
+    }
+
+    /// Fencing token for side-effecting work, if this instance leads.
+    ///
+    /// Leadership is treated as lost `max_clock_skew_secs` before the lease
+    /// expires, so a successor whose clock runs ahead cannot overlap with us.
+    pub fn fencing_token(&self) -> Option<u64> {
+        self.fencing_token_at(unix_timestamp())
+    }
+
+    fn fencing_token_at(&self, now: u64) -> Option<u64> {
+        let held = self.held.lock().unwrap();
+        held.filter(|(_, expires_at)| now + self.max_clock_skew_secs < *expires_at)
+            .map(|(token, _)| token)
+    }
+
+    /// Whether `token` is still the current lease token. Workers call this
+    /// immediately before each side effect.
+    pub fn check_fence(&self, token: u64) -> bool {
+        self.table.current_token(LEADER_LEASE, unix_timestamp()) == Some(token)
+    }
+
+    pub fn state(&self) -> LeadershipState {
+        let fencing_token = self.fencing_token();
+        LeadershipState {
+            instance_id: self.instance_id.clone(),
+            is_leader: fencing_token.is_some(),
+            fencing_token,
+            acquisitions: self.acquisitions.load(Ordering::Relaxed),
+            handovers: self.handovers.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn elector(id: &str, table: &Arc<LeaseTable>) -> LeaderElector {
+        LeaderElector {
+            instance_id: id.to_string(),
+            table: table.clone(),
+            ttl_secs: 15,
+            max_clock_skew_secs: 2,
+            held: Mutex::new(None),
+            acquisitions: AtomicU64::new(0),
+            handovers: AtomicU64::new(0),
+        }
+    }
+
+    #[test]
+    fn test_single_leader_and_failover() {
+        let table = Arc::new(LeaseTable::new());
+        let active = elector("us-east", &table);
+        let passive = elector("eu-west", &table);
+
+        active.heartbeat_at(1000);
+        passive.heartbeat_at(1000);
+        assert_eq!(active.fencing_token_at(1000), Some(1));
+        assert_eq!(passive.fencing_token_at(1000), None);
+
+        // Active stops heartbeating; passive takes over only after expiry
+        passive.heartbeat_at(1014);
+        assert_eq!(passive.fencing_token_at(1014), None);
+        passive.heartbeat_at(1015);
+        assert_eq!(passive.fencing_token_at(1015), Some(2));
+
+        // The old leader gave up before expiry and learns it lost on its next beat
+        assert_eq!(active.fencing_token_at(1013), None);
+        active.heartbeat_at(1016);
+        assert_eq!(active.state().handovers, 1);
+    }
+
+    #[test]
+    fn test_stale_token_fails_fence() {
+        let table = Arc::new(LeaseTable::new());
+        table.try_acquire(LEADER_LEASE, "a", 15, 1000);
+        let token = table.try_acquire(LEADER_LEASE, "b", 15, 1015).unwrap();
+
+        assert_eq!(table.current_token(LEADER_LEASE, 1016), Some(token));
+        assert_ne!(table.current_token(LEADER_LEASE, 1016), Some(1));
+    }
+}
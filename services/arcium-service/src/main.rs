@@ -1,17 +1,71 @@
 use actix_cors::Cors;
+use actix_web::dev::Service as _;
 use actix_web::{middleware, web, App, HttpServer};
+use futures_util::future::{ready, Either};
 use std::env;
-use tracing::info;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{info, warn};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
+mod admin;
+mod api_version;
+mod callback;
+mod callback_tls;
 mod config;
+mod endpoint_switches;
 mod error;
+mod fee_audit;
+mod fee_history;
+#[cfg(test)]
+mod fixtures;
 mod handlers;
+mod leader;
+mod metadata;
+mod metrics;
 mod mpc;
+mod pagination;
+mod payload;
+mod payroll_ingest;
+mod program_errors;
+mod quarantine;
+mod rate_limit;
+mod reorg;
 mod routes;
+mod rpc;
+mod shutdown;
+mod sla;
+mod statements;
+mod status;
+mod store;
+mod strict_mode;
+mod timeline;
+mod vault_stats;
+mod wallet_validation;
+mod warmup;
 
+use callback::CallbackDispatcher;
 use config::Config;
+use endpoint_switches::EndpointSwitches;
+use error::ServiceError;
+use fee_audit::FeeAudit;
+use fee_history::FeeHistory;
+use leader::{LeaderElector, LeaseTable};
+use metrics::{Metrics, Subsystem};
 use mpc::MpcClient;
+use quarantine::Quarantine;
+use reorg::ReorgWatcher;
+use rpc::ChainClient;
+use shutdown::Shutdown;
+use sla::SlaMonitor;
+use statements::Statements;
+use status::StatusBoard;
+use store::ComputationStore;
+use strict_mode::StrictMode;
+use timeline::Timeline;
+use vault_stats::VaultStats;
+use wallet_validation::WalletValidator;
+use warmup::Warmup;
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
@@ -39,10 +93,179 @@ async fn main() -> std::io::Result<()> {
     // Initialize MPC client
     let mpc_client = MpcClient::new(&config).expect("Failed to initialize MPC client");
     let mpc_client = web::Data::new(mpc_client);
+
+    // Initialize crypto state before accepting traffic
+    let warmup = web::Data::new(Warmup::new(&config));
+    warmup.run(&mpc_client).expect("Warmup failed");
+
+    let store = web::Data::new(ComputationStore::new(config.payload_limits));
+    let dispatcher =
+        CallbackDispatcher::new(&config).expect("Failed to initialize callback dispatcher");
+    let dispatcher = web::Data::new(dispatcher);
+    let chain = web::Data::new(ChainClient::new(&config));
+    let validator =
+        WalletValidator::new(&config).expect("Failed to initialize wallet validator");
+    let validator = web::Data::new(validator);
+    let metrics = web::Data::new(Metrics::new());
+    let quarantine = web::Data::new(Quarantine::new());
+    let status_board = web::Data::new(StatusBoard::new(config.status_rate_limit_per_minute));
+    let leader = web::Data::new(LeaderElector::new(&config, Arc::new(LeaseTable::new())));
+    let fee_audit = web::Data::new(FeeAudit::new(&config).expect("Failed to initialize fee audit"));
+    let vault_stats = web::Data::new(VaultStats::new(&config).expect("Failed to initialize vault stats"));
+    let fee_history = web::Data::new(FeeHistory::new(&config).expect("Failed to initialize fee history"));
+    let reorg_watcher = web::Data::new(ReorgWatcher::new(&config).expect("Failed to initialize reorg watcher"));
+    let sla_monitor = web::Data::new(SlaMonitor::new(&config).expect("Failed to initialize SLA monitor"));
+    let statements = web::Data::new(Statements::new());
+    let timeline = web::Data::new(Timeline::new());
+    let endpoint_switches = web::Data::new(EndpointSwitches::new(&config.disabled_endpoints));
+    let strict_mode = web::Data::new(StrictMode::new(
+        config.strict_mode_default,
+        &config.strict_mode_keys,
+        &config.legacy_mode_keys,
+    ));
+    let shutdown = web::Data::new(Shutdown::new(Duration::from_secs(config.shutdown_drain_secs)));
     let config = web::Data::new(config);
 
+    // Renew the leader lease in the background; only the holder delivers callbacks
+    let heartbeat = leader.clone();
+    actix_web::rt::spawn(async move {
+        let mut interval =
+            actix_web::rt::time::interval(Duration::from_secs(heartbeat.heartbeat_interval_secs()));
+        loop {
+            interval.tick().await;
+            heartbeat.heartbeat();
+        }
+    });
+
+    // Recompute on-chain fees periodically; only the leader runs the audit
+    if config.fee_audit_interval_secs > 0 {
+        let (audit, audit_chain, audit_leader, audit_history) =
+            (fee_audit.clone(), chain.clone(), leader.clone(), fee_history.clone());
+        let period = Duration::from_secs(config.fee_audit_interval_secs);
+        actix_web::rt::spawn(async move {
+            let mut interval = actix_web::rt::time::interval(period);
+            loop {
+                interval.tick().await;
+                if audit_leader.fencing_token().is_some() {
+                    audit.run(&audit_chain, &audit_history).await;
+                }
+            }
+        });
+    }
+
+    // Index new payments and compact them into daily rollups; every instance
+    // keeps its own rollups, so this doesn't wait for leadership
+    if config.vault_stats_index_interval_secs > 0 {
+        let (stats, stats_chain) = (vault_stats.clone(), chain.clone());
+        let period = Duration::from_secs(config.vault_stats_index_interval_secs);
+        actix_web::rt::spawn(async move {
+            let mut interval = actix_web::rt::time::interval(period);
+            loop {
+                interval.tick().await;
+                stats.index(&stats_chain).await;
+            }
+        });
+
+        let stats = vault_stats.clone();
+        let period = Duration::from_secs(config.vault_stats_compact_interval_secs);
+        actix_web::rt::spawn(async move {
+            let mut interval = actix_web::rt::time::interval(period);
+            loop {
+                interval.tick().await;
+                stats.compact();
+            }
+        });
+    }
+
+    // Re-check indexed payments until they finalize, reverting any a fork
+    // rolled back; every instance corrects its own stats, only the leader
+    // sends payment.reverted
+    if config.vault_stats_index_interval_secs > 0 && config.reorg_check_interval_secs > 0 {
+        let (watcher, stats, watcher_chain, watcher_leader, watcher_dispatcher) = (
+            reorg_watcher.clone(),
+            vault_stats.clone(),
+            chain.clone(),
+            leader.clone(),
+            dispatcher.clone(),
+        );
+        let period = Duration::from_secs(config.reorg_check_interval_secs);
+        actix_web::rt::spawn(async move {
+            let mut interval = actix_web::rt::time::interval(period);
+            loop {
+                interval.tick().await;
+                if let Err(e) = watcher.check(&stats, &watcher_chain).await {
+                    warn!("Reorg check failed: {}", e);
+                }
+                if watcher_leader.fencing_token().is_some() {
+                    watcher.notify(&watcher_dispatcher).await;
+                }
+            }
+        });
+    }
+
+    // Scan VaultConfig transactions for fee changes; like vault stats, every
+    // instance keeps its own timeline
+    if config.fee_history_index_interval_secs > 0 {
+        let (history, history_chain) = (fee_history.clone(), chain.clone());
+        let period = Duration::from_secs(config.fee_history_index_interval_secs);
+        actix_web::rt::spawn(async move {
+            let mut interval = actix_web::rt::time::interval(period);
+            loop {
+                interval.tick().await;
+                history.index(&history_chain).await;
+            }
+        });
+    }
+
+    // Apply timeline events recorded by handlers and background jobs
+    let timeline_writer = timeline.clone();
+    actix_web::rt::spawn(async move { timeline_writer.run().await });
+
+    // Flag computations past their latency SLA; each instance tracks the
+    // computations it queued, so this doesn't wait for leadership
+    if config.sla_check_interval_secs > 0 {
+        let (monitor, sla_store, sla_mpc, sla_dispatcher, sla_timeline) = (
+            sla_monitor.clone(),
+            store.clone(),
+            mpc_client.clone(),
+            dispatcher.clone(),
+            timeline.clone(),
+        );
+        let period = Duration::from_secs(config.sla_check_interval_secs);
+        actix_web::rt::spawn(async move {
+            let mut interval = actix_web::rt::time::interval(period);
+            loop {
+                interval.tick().await;
+                monitor.check(&sla_store, &sla_mpc, &sla_dispatcher, &sla_timeline).await;
+            }
+        });
+    }
+
+    // Drop finished computations past retention, and their timelines with
+    // them; an hourly pass is plenty for retention measured in days
+    if config.computation_retention_secs > 0 {
+        let (retained_store, retained_timeline) = (store.clone(), timeline.clone());
+        let retention_secs = config.computation_retention_secs;
+        actix_web::rt::spawn(async move {
+            let mut interval = actix_web::rt::time::interval(Duration::from_secs(3600));
+            loop {
+                interval.tick().await;
+                let cutoff = store::unix_timestamp().saturating_sub(retention_secs);
+                let computations = retained_store.prune_finished(cutoff);
+                let timelines = retained_timeline.prune(|id| retained_store.contains(id));
+                if computations > 0 || timelines > 0 {
+                    info!("Pruned {} computations and {} timelines", computations, timelines);
+                }
+            }
+        });
+    }
+
     // Start HTTP server
-    HttpServer::new(move || {
+    let app_shutdown = shutdown.clone();
+    let server = HttpServer::new(move || {
+        let api_metrics = metrics.clone();
+        let switches = endpoint_switches.clone();
+        let tracker = app_shutdown.clone();
         let cors = Cors::default()
             .allow_any_origin()
             .allow_any_method()
@@ -50,14 +273,80 @@ async fn main() -> std::io::Result<()> {
             .max_age(3600);
 
         App::new()
+            // Operator kill-switches for individual endpoints; innermost, so
+            // the 503 still gets CORS headers, logging and metrics
+            .wrap_fn(move |req, srv| match switches.check(req.method(), req.path()) {
+                Ok(()) => Either::Left(srv.call(req)),
+                Err(e) => Either::Right(ready(Ok(req.error_response(e)))),
+            })
             .wrap(cors)
             .wrap(middleware::Logger::default())
             .wrap(middleware::Compress::default())
+            .wrap_fn(move |req, srv| {
+                let metrics = api_metrics.clone();
+                let fut = srv.call(req);
+                async move {
+                    let res = fut.await?;
+                    // A switched-off endpoint is a deliberate outage, not a failure
+                    let disabled = res
+                        .response()
+                        .error()
+                        .and_then(|e| e.as_error::<ServiceError>())
+                        .is_some_and(|e| matches!(e, ServiceError::EndpointDisabled(_)));
+                    if !disabled {
+                        metrics.record(Subsystem::Api, !res.status().is_server_error());
+                    }
+                    Ok(res)
+                }
+            })
+            // Outermost, so the drain counts every request
+            .wrap_fn(move |req, srv| {
+                let request = tracker.clone().into_inner().track();
+                let fut = srv.call(req);
+                async move {
+                    let res = fut.await;
+                    request.finish();
+                    res
+                }
+            })
             .app_data(config.clone())
             .app_data(mpc_client.clone())
+            .app_data(store.clone())
+            .app_data(dispatcher.clone())
+            .app_data(chain.clone())
+            .app_data(validator.clone())
+            .app_data(metrics.clone())
+            .app_data(status_board.clone())
+            .app_data(quarantine.clone())
+            .app_data(leader.clone())
+            .app_data(warmup.clone())
+            .app_data(fee_audit.clone())
+            .app_data(vault_stats.clone())
+            .app_data(reorg_watcher.clone())
+            .app_data(sla_monitor.clone())
+            .app_data(fee_history.clone())
+            .app_data(statements.clone())
+            .app_data(timeline.clone())
+            .app_data(endpoint_switches.clone())
+            .app_data(strict_mode.clone())
+            .app_data(app_shutdown.clone())
+            .app_data(web::JsonConfig::default().error_handler(|err, _req| {
+                ServiceError::InvalidInput(err.to_string()).into()
+            }))
             .configure(routes::configure)
     })
     .bind(format!("{}:{}", host, port))?
-    .run()
-    .await
+    // SIGTERM starts the drain below rather than an immediate stop
+    .disable_signals()
+    .run();
+
+    let (handle, draining) = (server.handle(), shutdown.clone());
+    actix_web::rt::spawn(async move {
+        shutdown::wait_for_signal().await;
+        draining.drain(handle).await;
+    });
+
+    server.await?;
+    shutdown.log_report();
+    Ok(())
 }
@@ -0,0 +1,73 @@
+use std::collections::HashMap;
+
+use crate::error::ServiceError;
+
+/// Maximum number of metadata keys per settlement
+pub const MAX_METADATA_KEYS: usize = 20;
+/// Maximum length of a metadata key, in characters
+pub const MAX_KEY_LENGTH: usize = 40;
+/// Maximum length of a metadata value, in characters
+pub const MAX_VALUE_LENGTH: usize = 500;
+
+/// Merchant-supplied key/value pairs attached to a settlement.
+///
+/// Metadata is stored alongside the computation record and echoed back in
+/// status responses. It is never forwarded to the MPC cluster.
+pub type Metadata = HashMap<String, String>;
+
+/// Validate metadata limits, naming the offending field on failure
+pub fn validate_metadata(metadata: &Metadata) -> Result<(), ServiceError> {
+    if metadata.len() > MAX_METADATA_KEYS {
+        return Err(ServiceError::InvalidInput(format!(
+            "metadata: at most {} keys are allowed, got {}",
+            MAX_METADATA_KEYS,
+            metadata.len()
+        )));
+    }
+
+    for (key, value) in metadata {
+        if key.is_empty() {
+            return Err(ServiceError::InvalidInput(
+                "metadata: keys must not be empty".to_string(),
+            ));
+        }
+        if key.chars().count() > MAX_KEY_LENGTH {
+            return Err(ServiceError::InvalidInput(format!(
+                "metadata.{}: key exceeds {} characters",
+                key, MAX_KEY_LENGTH
+            )));
+        }
+        if value.chars().count() > MAX_VALUE_LENGTH {
+            return Err(ServiceError::InvalidInput(format!(
+                "metadata.{}: value exceeds {} characters",
+                key, MAX_VALUE_LENGTH
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_metadata_limits() {
+        let mut metadata = Metadata::new();
+        metadata.insert("order_id".to_string(), "ord_123".to_string());
+        assert!(validate_metadata(&metadata).is_ok());
+
+        metadata.insert("k".repeat(MAX_KEY_LENGTH + 1), "v".to_string());
+        assert!(validate_metadata(&metadata).is_err());
+
+        let mut metadata = Metadata::new();
+        metadata.insert("note".to_string(), "v".repeat(MAX_VALUE_LENGTH + 1));
+        assert!(validate_metadata(&metadata).is_err());
+
+        let metadata: Metadata = (0..=MAX_METADATA_KEYS)
+            .map(|i| (format!("key_{}", i), "v".to_string()))
+            .collect();
+        assert!(validate_metadata(&metadata).is_err());
+    }
+}
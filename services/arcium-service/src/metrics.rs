@@ -0,0 +1,136 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+
+use crate::store::unix_timestamp;
+
+const BUCKET_SECS: u64 = 3600;
+const BUCKETS: usize = 24;
+
+/// Externally visible subsystems tracked for availability
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Subsystem {
+    Api,
+    MpcCluster,
+    SolanaRpc,
+    Webhooks,
+}
+
+impl Subsystem {
+    pub const ALL: [Subsystem; 4] = [
+        Subsystem::Api,
+        Subsystem::MpcCluster,
+        Subsystem::SolanaRpc,
+        Subsystem::Webhooks,
+    ];
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Counts {
+    pub success: u64,
+    pub failure: u64,
+}
+
+impl Counts {
+    pub fn total(&self) -> u64 {
+        self.success + self.failure
+    }
+
+    /// Success percentage, or `None` when nothing was recorded
+    pub fn success_rate(&self) -> Option<f64> {
+        if self.total() == 0 {
+            return None;
+        }
+        Some(self.success as f64 * 100.0 / self.total() as f64)
+    }
+}
+
+struct Bucket {
+    start: u64,
+    counts: Counts,
+}
+
+/// Rolling 24h success/failure counters in hourly buckets
+#[derive(Default)]
+pub struct Metrics {
+    series: Mutex<HashMap<Subsystem, VecDeque<Bucket>>>,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&self, subsystem: Subsystem, success: bool) {
+        self.record_at(subsystem, success, unix_timestamp());
+    }
+
+    fn record_at(&self, subsystem: Subsystem, success: bool, now: u64) {
+        let bucket_start = now - now % BUCKET_SECS;
+        let mut series = self.series.lock().unwrap();
+        let buckets = series.entry(subsystem).or_default();
+
+        if buckets.back().map(|b| b.start) != Some(bucket_start) {
+            buckets.push_back(Bucket {
+                start: bucket_start,
+                counts: Counts::default(),
+            });
+        }
+        while buckets.len() > BUCKETS {
+            buckets.pop_front();
+        }
+
+        let counts = &mut buckets.back_mut().unwrap().counts;
+        if success {
+            counts.success += 1;
+        } else {
+            counts.failure += 1;
+        }
+    }
+
+    /// Counts recorded within the last `window_secs`, at hourly granularity
+    pub fn counts(&self, subsystem: Subsystem, window_secs: u64) -> Counts {
+        self.counts_at(subsystem, window_secs, unix_timestamp())
+    }
+
+    fn counts_at(&self, subsystem: Subsystem, window_secs: u64, now: u64) -> Counts {
+        let cutoff = (now - now % BUCKET_SECS + BUCKET_SECS).saturating_sub(window_secs);
+        let series = self.series.lock().unwrap();
+        let mut total = Counts::default();
+        for bucket in series.get(&subsystem).into_iter().flatten() {
+            if bucket.start >= cutoff {
+                total.success += bucket.counts.success;
+                total.failure += bucket.counts.failure;
+            }
+        }
+        total
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rolling_window() {
+        let metrics = Metrics::new();
+        let start = 1_700_000_000 - 1_700_000_000 % BUCKET_SECS;
+
+        metrics.record_at(Subsystem::Api, true, start);
+        metrics.record_at(Subsystem::Api, false, start + 10);
+        metrics.record_at(Subsystem::Api, true, start + BUCKET_SECS);
+
+        let day = metrics.counts_at(Subsystem::Api, 24 * BUCKET_SECS, start + BUCKET_SECS);
+        assert_eq!(day, Counts { success: 2, failure: 1 });
+
+        let hour = metrics.counts_at(Subsystem::Api, BUCKET_SECS, start + BUCKET_SECS);
+        assert_eq!(hour, Counts { success: 1, failure: 0 });
+
+        // A day later the first bucket has aged out
+        let later = metrics.counts_at(Subsystem::Api, 24 * BUCKET_SECS, start + 24 * BUCKET_SECS);
+        assert_eq!(later, Counts { success: 1, failure: 0 });
+
+        assert_eq!(metrics.counts(Subsystem::Webhooks, BUCKET_SECS).success_rate(), None);
+    }
+}
@@ -1,7 +1,12 @@
+use chacha20poly1305::ChaCha20Poly1305;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::RwLock;
 use tracing::{debug, error, info};
 
+use super::encryption::{self, CommitmentAlgorithm, EncryptionResult};
 use crate::config::Config;
 use crate::error::ServiceError;
 
@@ -10,20 +15,28 @@ pub struct MpcClient {
     cluster_address: String,
     program_id: String,
     callback_secret: String,
+    callback_receiver_url: String,
     master_key: Vec<u8>,
+    /// Pre-initialized ciphers for hot user pubkeys, filled by warmup
+    cipher_pool: RwLock<HashMap<String, ChaCha20Poly1305>>,
 }
 
+/// Body of `POST /api/v1/computations`
 #[derive(Debug, Serialize)]
 struct ComputationRequest {
     computation_id: String,
-    computation_type: String,
-    params: serde_json::Value,
+    /// Serialized as `computation_type` and `params`
+    #[serde(flatten)]
+    params: ComputationParams,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct ComputationResponse {
     pub computation_id: String,
     pub status: String,
+    /// Hex SHA-256 of the request body, when this answers a queueing request
+    #[serde(skip)]
+    pub request_hash: Option<String>,
 }
 
 #[derive(Debug, Serialize)]
@@ -50,6 +63,88 @@ pub struct PayrollSettlementParams {
     pub currency: String,
 }
 
+/// Params forwarded to the cluster, one variant per computation.
+///
+/// The variant is the `computation_type` tag and its fields go under
+/// `params`, matching the cluster's request schema; a renamed field breaks
+/// the golden tests instead of the cluster.
+#[derive(Debug, Serialize)]
+#[serde(tag = "computation_type", content = "params", rename_all = "snake_case")]
+pub enum ComputationParams {
+    PaymentSettlement(PaymentSettlementParams),
+    PayrollSettlement(PayrollSettlementParams),
+}
+
+impl ComputationParams {
+    /// The serialized `computation_type` tag
+    pub fn computation_type(&self) -> &'static str {
+        match self {
+            ComputationParams::PaymentSettlement(_) => "payment_settlement",
+            ComputationParams::PayrollSettlement(_) => "payroll_settlement",
+        }
+    }
+
+    fn new_computation_id(&self) -> String {
+        match self {
+            ComputationParams::PaymentSettlement(_) => payment_computation_id(),
+            ComputationParams::PayrollSettlement(_) => {
+                format!("payroll_{}", hex::encode(rand::random::<[u8; 16]>()))
+            }
+        }
+    }
+
+    /// Reject params the cluster would refuse before spending a round trip
+    pub fn validate(&self) -> Result<(), ServiceError> {
+        match self {
+            ComputationParams::PaymentSettlement(p) => {
+                require_non_empty("payment_intent_id", &p.payment_intent_id)?;
+                require_non_empty("merchant_wallet", &p.merchant_wallet)?;
+                require_non_empty("recipient", &p.recipient)?;
+                require_non_empty("currency", &p.currency)?;
+                if p.amount == 0 {
+                    return Err(ServiceError::InvalidInput("amount must be positive".to_string()));
+                }
+            }
+            ComputationParams::PayrollSettlement(p) => {
+                require_non_empty("batch_id", &p.batch_id)?;
+                require_non_empty("company_wallet", &p.company_wallet)?;
+                require_non_empty("currency", &p.currency)?;
+                if p.payments.is_empty() {
+                    return Err(ServiceError::InvalidInput(
+                        "payments must not be empty".to_string(),
+                    ));
+                }
+                for (i, payment) in p.payments.iter().enumerate() {
+                    require_non_empty(&format!("payments[{}].employee_id", i), &payment.employee_id)?;
+                    require_non_empty(
+                        &format!("payments[{}].employee_wallet", i),
+                        &payment.employee_wallet,
+                    )?;
+                    if payment.amount == 0 {
+                        return Err(ServiceError::InvalidInput(format!(
+                            "payments[{}].amount must be positive",
+                            i
+                        )));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn require_non_empty(field: &str, value: &str) -> Result<(), ServiceError> {
+    if value.trim().is_empty() {
+        return Err(ServiceError::InvalidInput(format!("{} must not be empty", field)));
+    }
+    Ok(())
+}
+
+/// Generate a new payment settlement computation id
+pub fn payment_computation_id() -> String {
+    format!("pay_{}", hex::encode(rand::random::<[u8; 16]>()))
+}
+
 impl MpcClient {
     pub fn new(config: &Config) -> Result<Self, ServiceError> {
         let http_client = Client::builder()
@@ -64,66 +159,125 @@ impl MpcClient {
             cluster_address: config.arcium_cluster_address.clone(),
             program_id: config.arcium_program_id.clone(),
             callback_secret: config.callback_secret.clone(),
+            callback_receiver_url: config.callback_receiver_url.clone(),
             master_key: config.encryption_master_key.clone(),
+            cipher_pool: RwLock::new(HashMap::new()),
         })
     }
 
-    pub fn master_key(&self) -> &[u8] {
-        &self.master_key
+    /// A client for a test cluster at `cluster_address`
+    #[cfg(test)]
+    pub(crate) fn for_cluster(cluster_address: String) -> Self {
+        Self {
+            http_client: Client::new(),
+            cluster_address,
+            program_id: "test_program".to_string(),
+            callback_secret: "secret".to_string(),
+            callback_receiver_url: String::new(),
+            master_key: vec![0; 32],
+            cipher_pool: RwLock::new(HashMap::new()),
+        }
     }
 
-    /// Queue a payment settlement computation
-    pub async fn queue_payment_settlement(
+    /// Pooled cipher for a hot pubkey, or a freshly derived one
+    fn cipher_for(&self, user_pubkey: &str) -> Result<ChaCha20Poly1305, ServiceError> {
+        if let Some(cipher) = self.cipher_pool.read().unwrap().get(user_pubkey) {
+            return Ok(cipher.clone());
+        }
+        encryption::user_cipher(&self.master_key, user_pubkey)
+    }
+
+    /// Encrypt an amount for a user
+    pub fn encrypt(
         &self,
-        params: PaymentSettlementParams,
-        callback_url: &str,
-    ) -> Result<ComputationResponse, ServiceError> {
-        let computation_id = format!("pay_{}", hex::encode(rand::random::<[u8; 16]>()));
+        amount: u64,
+        user_pubkey: &str,
+        algorithm: CommitmentAlgorithm,
+    ) -> Result<EncryptionResult, ServiceError> {
+        encryption::encrypt_with_cipher(&self.cipher_for(user_pubkey)?, amount, algorithm)
+    }
 
-        let request = ComputationRequest {
-            computation_id: computation_id.clone(),
-            computation_type: "payment_settlement".to_string(),
-            params: serde_json::to_value(&params).unwrap(),
-        };
+    /// Decrypt an amount for a user
+    pub fn decrypt(&self, ciphertext: &[u8], nonce: &[u8], user_pubkey: &str) -> Result<u64, ServiceError> {
+        encryption::decrypt_with_cipher(&self.cipher_for(user_pubkey)?, ciphertext, nonce)
+    }
 
-        debug!("Queuing payment settlement: {:?}", computation_id);
+    /// Derive keys and initialize ciphers ahead of traffic, returning the
+    /// number of pooled pubkeys
+    pub fn warm(&self, hot_pubkeys: &[String]) -> Result<usize, ServiceError> {
+        let mut pool = HashMap::with_capacity(hot_pubkeys.len());
+        for pubkey in hot_pubkeys {
+            pool.insert(pubkey.clone(), encryption::user_cipher(&self.master_key, pubkey)?);
+        }
+
+        // Exercise the encrypt/decrypt path once so first requests don't pay
+        // for lazy initialization
+        let probe = encryption::user_cipher(&self.master_key, "warmup")?;
+        let sealed = encryption::encrypt_with_cipher(&probe, 0, CommitmentAlgorithm::default())?;
+        encryption::decrypt_with_cipher(&probe, &sealed.ciphertext, &sealed.nonce)?;
 
-        self.send_computation_request(request, callback_url).await
+        let pooled = pool.len();
+        *self.cipher_pool.write().unwrap() = pool;
+        Ok(pooled)
     }
 
-    /// Queue a payroll settlement computation
-    pub async fn queue_payroll_settlement(
+    /// Validate and queue a computation on the cluster
+    pub async fn queue_computation(
         &self,
-        params: PayrollSettlementParams,
-        callback_url: &str,
+        params: ComputationParams,
     ) -> Result<ComputationResponse, ServiceError> {
-        let computation_id = format!("payroll_{}", hex::encode(rand::random::<[u8; 16]>()));
+        params.validate()?;
 
         let request = ComputationRequest {
-            computation_id: computation_id.clone(),
-            computation_type: "payroll_settlement".to_string(),
-            params: serde_json::to_value(&params).unwrap(),
+            computation_id: params.new_computation_id(),
+            params,
         };
 
-        debug!("Queuing payroll settlement: {:?}", computation_id);
+        debug!(
+            "Queuing {}: {:?}",
+            request.params.computation_type(),
+            request.computation_id
+        );
 
-        self.send_computation_request(request, callback_url).await
+        self.send_computation_request(request).await
     }
 
     /// Get computation status
     pub async fn get_computation_status(
         &self,
         computation_id: &str,
+    ) -> Result<ComputationResponse, ServiceError> {
+        self.fetch_status(computation_id, None).await
+    }
+
+    /// Re-query a computation that is past its SLA, flagged so the cluster
+    /// can prioritize or investigate it
+    pub async fn escalate(
+        &self,
+        computation_id: &str,
+        waited_secs: u64,
+    ) -> Result<ComputationResponse, ServiceError> {
+        info!("Escalating {} after {}s", computation_id, waited_secs);
+        self.fetch_status(computation_id, Some(waited_secs)).await
+    }
+
+    async fn fetch_status(
+        &self,
+        computation_id: &str,
+        escalation_waited_secs: Option<u64>,
     ) -> Result<ComputationResponse, ServiceError> {
         let url = format!(
             "{}/api/v1/computations/{}",
             self.cluster_address, computation_id
         );
 
-        let response = self
-            .http_client
-            .get(&url)
-            .header("X-Program-ID", &self.program_id)
+        let mut request = self.http_client.get(&url).header("X-Program-ID", &self.program_id);
+        if let Some(waited_secs) = escalation_waited_secs {
+            request = request
+                .header("X-Escalation", "sla-breach")
+                .header("X-Escalation-Waited-Secs", waited_secs.to_string());
+        }
+        let response = request
             .send()
             .await
             .map_err(|e| ServiceError::MpcError(format!("Failed to get status: {}", e)))?;
@@ -146,18 +300,20 @@ impl MpcClient {
     async fn send_computation_request(
         &self,
         request: ComputationRequest,
-        callback_url: &str,
     ) -> Result<ComputationResponse, ServiceError> {
         let url = format!("{}/api/v1/computations", self.cluster_address);
+        let body = serde_json::to_vec(&request)
+            .map_err(|e| ServiceError::MpcError(format!("Failed to serialize request: {}", e)))?;
+        let request_hash = hex::encode(Sha256::digest(&body));
 
         let response = self
             .http_client
             .post(&url)
             .header("Content-Type", "application/json")
             .header("X-Program-ID", &self.program_id)
-            .header("X-Callback-URL", callback_url)
+            .header("X-Callback-URL", &self.callback_receiver_url)
             .header("X-Callback-Secret", &self.callback_secret)
-            .json(&request)
+            .body(body)
             .send()
             .await
             .map_err(|e| ServiceError::MpcError(format!("Failed to send request: {}", e)))?;
@@ -172,16 +328,111 @@ impl MpcClient {
             )));
         }
 
-        let result: ComputationResponse = response
+        let mut result: ComputationResponse = response
             .json()
             .await
             .map_err(|e| ServiceError::MpcError(format!("Failed to parse response: {}", e)))?;
 
         info!(
-            "Computation queued: {} (status: {})",
-            result.computation_id, result.status
+            "Computation queued: {} (status: {}, request {})",
+            result.computation_id, result.status, request_hash
         );
+        result.request_hash = Some(request_hash);
 
         Ok(result)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture(name: &str) -> serde_json::Value {
+        let path = format!("{}/tests/fixtures/{}", env!("CARGO_MANIFEST_DIR"), name);
+        let contents = std::fs::read_to_string(&path).expect("missing fixture");
+        serde_json::from_str(&contents).expect("invalid fixture JSON")
+    }
+
+    fn payment() -> PaymentSettlementParams {
+        PaymentSettlementParams {
+            payment_intent_id: "pi_123".to_string(),
+            merchant_wallet: "7xKXtg2CW8ukAp9rXKD2RQU3w5RJKPME6nXbvNfTQAaP".to_string(),
+            amount: 9_007_199_254_740_993,
+            recipient: "9WzDXwBbmkg8ZTbNMqUxvQRAyrZzDsGYdLVL9zYtAWWM".to_string(),
+            currency: "USDC".to_string(),
+        }
+    }
+
+    fn payroll() -> PayrollSettlementParams {
+        PayrollSettlementParams {
+            batch_id: "batch_42".to_string(),
+            company_wallet: "7xKXtg2CW8ukAp9rXKD2RQU3w5RJKPME6nXbvNfTQAaP".to_string(),
+            payments: vec![PayrollPayment {
+                employee_id: "emp_1".to_string(),
+                employee_wallet: "9WzDXwBbmkg8ZTbNMqUxvQRAyrZzDsGYdLVL9zYtAWWM".to_string(),
+                amount: 250_000_000,
+            }],
+            currency: "USDC".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_payment_settlement_request_matches_cluster_schema() {
+        let request = ComputationRequest {
+            computation_id: "pay_0123456789abcdef0123456789abcdef".to_string(),
+            params: ComputationParams::PaymentSettlement(payment()),
+        };
+        assert_eq!(
+            serde_json::to_value(&request).unwrap(),
+            fixture("cluster_payment_settlement_request.json")
+        );
+    }
+
+    #[test]
+    fn test_payroll_settlement_request_matches_cluster_schema() {
+        let request = ComputationRequest {
+            computation_id: "payroll_0123456789abcdef0123456789abcdef".to_string(),
+            params: ComputationParams::PayrollSettlement(payroll()),
+        };
+        assert_eq!(
+            serde_json::to_value(&request).unwrap(),
+            fixture("cluster_payroll_settlement_request.json")
+        );
+    }
+
+    #[test]
+    fn test_computation_type_matches_serialized_tag() {
+        for params in [
+            ComputationParams::PaymentSettlement(payment()),
+            ComputationParams::PayrollSettlement(payroll()),
+        ] {
+            let json = serde_json::to_value(&params).unwrap();
+            assert_eq!(json["computation_type"], params.computation_type());
+            assert!(params.new_computation_id().starts_with(match params {
+                ComputationParams::PaymentSettlement(_) => "pay_",
+                ComputationParams::PayrollSettlement(_) => "payroll_",
+            }));
+        }
+    }
+
+    #[test]
+    fn test_validate_rejects_params_the_cluster_refuses() {
+        assert!(ComputationParams::PaymentSettlement(payment()).validate().is_ok());
+        assert!(ComputationParams::PayrollSettlement(payroll()).validate().is_ok());
+
+        let mut zero_amount = payment();
+        zero_amount.amount = 0;
+        assert!(ComputationParams::PaymentSettlement(zero_amount).validate().is_err());
+
+        let mut blank_wallet = payroll();
+        blank_wallet.payments[0].employee_wallet = " ".to_string();
+        let err = ComputationParams::PayrollSettlement(blank_wallet)
+            .validate()
+            .unwrap_err();
+        assert!(err.to_string().contains("payments[0].employee_wallet"));
+
+        let mut empty = payroll();
+        empty.payments.clear();
+        assert!(ComputationParams::PayrollSettlement(empty).validate().is_err());
+    }
+}
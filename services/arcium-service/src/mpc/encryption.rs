@@ -4,27 +4,44 @@ use chacha20poly1305::{
 };
 use hkdf::Hkdf;
 use rand::Rng;
+use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
+use std::sync::OnceLock;
 
 use crate::error::ServiceError;
 
 const NONCE_SIZE: usize = 12;
 const KEY_SIZE: usize = 32;
-const TAG_SIZE: usize = 16;
+
+/// Hash behind an amount commitment. Both hash the same preimage; BLAKE3 is
+/// for MPC circuits that compute it natively.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CommitmentAlgorithm {
+    #[default]
+    Sha256,
+    Blake3,
+}
 
 #[derive(Debug, Clone)]
 pub struct EncryptionResult {
     pub ciphertext: Vec<u8>,
     pub nonce: Vec<u8>,
     pub commitment: String,
+    pub commitment_algorithm: CommitmentAlgorithm,
+}
+
+/// HKDF salt, computed once per process
+fn hkdf_salt() -> &'static [u8] {
+    static SALT: OnceLock<[u8; 32]> = OnceLock::new();
+    SALT.get_or_init(|| Sha256::digest(b"ninjapay-v2").into())
 }
 
 /// Derive a user-specific encryption key using HKDF
 pub fn derive_user_key(master_key: &[u8], user_pubkey: &str) -> Result<Vec<u8>, ServiceError> {
-    let salt = Sha256::digest(b"ninjapay-v2");
     let info = format!("user:{}", user_pubkey);
 
-    let hkdf = Hkdf::<Sha256>::new(Some(&salt), master_key);
+    let hkdf = Hkdf::<Sha256>::new(Some(hkdf_salt()), master_key);
     let mut okm = vec![0u8; KEY_SIZE];
     hkdf.expand(info.as_bytes(), &mut okm)
         .map_err(|e| ServiceError::EncryptionError(format!("HKDF expansion failed: {}", e)))?;
@@ -32,68 +49,72 @@ pub fn derive_user_key(master_key: &[u8], user_pubkey: &str) -> Result<Vec<u8>,
     Ok(okm)
 }
 
-/// Encrypt an amount using ChaCha20-Poly1305
-pub fn encrypt_amount(
-    amount: u64,
-    master_key: &[u8],
-    user_pubkey: &str,
-) -> Result<EncryptionResult, ServiceError> {
-    // Derive user-specific key
+/// Build the cipher for a user's derived key
+pub fn user_cipher(master_key: &[u8], user_pubkey: &str) -> Result<ChaCha20Poly1305, ServiceError> {
     let user_key = derive_user_key(master_key, user_pubkey)?;
+    ChaCha20Poly1305::new_from_slice(&user_key)
+        .map_err(|e| ServiceError::EncryptionError(format!("Failed to create cipher: {}", e)))
+}
 
+/// Encrypt an amount with an already-initialized user cipher
+pub fn encrypt_with_cipher(
+    cipher: &ChaCha20Poly1305,
+    amount: u64,
+    algorithm: CommitmentAlgorithm,
+) -> Result<EncryptionResult, ServiceError> {
     // Generate random nonce
     let mut nonce_bytes = [0u8; NONCE_SIZE];
     rand::thread_rng().fill(&mut nonce_bytes);
 
-    // Create cipher
-    let cipher = ChaCha20Poly1305::new_from_slice(&user_key)
-        .map_err(|e| ServiceError::EncryptionError(format!("Failed to create cipher: {}", e)))?;
+    encrypt_with_nonce(cipher, amount, nonce_bytes, algorithm)
+}
 
+/// Encrypt an amount under a caller-chosen nonce. Only for deterministic
+/// fixtures: reusing a nonce with the same key breaks confidentiality.
+pub fn encrypt_with_nonce(
+    cipher: &ChaCha20Poly1305,
+    amount: u64,
+    nonce_bytes: [u8; NONCE_SIZE],
+    algorithm: CommitmentAlgorithm,
+) -> Result<EncryptionResult, ServiceError> {
     // Convert amount to bytes (little-endian)
     let amount_bytes = amount.to_le_bytes();
 
     // Encrypt
-    let nonce = Nonce::from_slice(&nonce_bytes);
+    let nonce = Nonce::from(nonce_bytes);
     let ciphertext = cipher
-        .encrypt(nonce, amount_bytes.as_ref())
+        .encrypt(&nonce, amount_bytes.as_ref())
         .map_err(|e| ServiceError::EncryptionError(format!("Encryption failed: {}", e)))?;
 
     // Generate commitment
-    let commitment = generate_commitment(amount, &nonce_bytes);
+    let commitment = generate_commitment(amount, &nonce_bytes, algorithm);
 
     Ok(EncryptionResult {
         ciphertext,
         nonce: nonce_bytes.to_vec(),
         commitment,
+        commitment_algorithm: algorithm,
     })
 }
 
-/// Decrypt an amount using ChaCha20-Poly1305
-pub fn decrypt_amount(
+/// Decrypt an amount with an already-initialized user cipher
+pub fn decrypt_with_cipher(
+    cipher: &ChaCha20Poly1305,
     ciphertext: &[u8],
     nonce: &[u8],
-    master_key: &[u8],
-    user_pubkey: &str,
 ) -> Result<u64, ServiceError> {
-    if nonce.len() != NONCE_SIZE {
-        return Err(ServiceError::DecryptionError(format!(
+    let nonce: [u8; NONCE_SIZE] = nonce.try_into().map_err(|_| {
+        ServiceError::DecryptionError(format!(
             "Invalid nonce size: expected {}, got {}",
             NONCE_SIZE,
             nonce.len()
-        )));
-    }
-
-    // Derive user-specific key
-    let user_key = derive_user_key(master_key, user_pubkey)?;
-
-    // Create cipher
-    let cipher = ChaCha20Poly1305::new_from_slice(&user_key)
-        .map_err(|e| ServiceError::DecryptionError(format!("Failed to create cipher: {}", e)))?;
+        ))
+    })?;
 
     // Decrypt
-    let nonce = Nonce::from_slice(nonce);
+    let nonce = Nonce::from(nonce);
     let plaintext = cipher
-        .decrypt(nonce, ciphertext)
+        .decrypt(&nonce, ciphertext)
         .map_err(|e| ServiceError::DecryptionError(format!("Decryption failed: {}", e)))?;
 
     // Convert bytes to amount
@@ -108,17 +129,36 @@ pub fn decrypt_amount(
     Ok(u64::from_le_bytes(amount_bytes))
 }
 
-/// Generate a Pedersen-style commitment: H(amount || blinding_factor)
-pub fn generate_commitment(amount: u64, blinding_factor: &[u8]) -> String {
-    let mut hasher = Sha256::new();
-    hasher.update(amount.to_le_bytes());
-    hasher.update(blinding_factor);
-    hex::encode(hasher.finalize())
+/// Generate a hash commitment: H(amount || blinding_factor), with the
+/// amount as u64 little-endian and H either SHA-256 or BLAKE3
+///
+/// This is binding and hiding but not additively homomorphic, so commitments
+/// cannot be summed to prove a batch total without revealing the openings.
+pub fn generate_commitment(amount: u64, blinding_factor: &[u8], algorithm: CommitmentAlgorithm) -> String {
+    match algorithm {
+        CommitmentAlgorithm::Sha256 => {
+            let mut hasher = Sha256::new();
+            hasher.update(amount.to_le_bytes());
+            hasher.update(blinding_factor);
+            hex::encode(hasher.finalize())
+        }
+        CommitmentAlgorithm::Blake3 => {
+            let mut hasher = blake3::Hasher::new();
+            hasher.update(&amount.to_le_bytes());
+            hasher.update(blinding_factor);
+            hex::encode(hasher.finalize().as_bytes())
+        }
+    }
 }
 
 /// Verify a commitment matches the expected amount
-pub fn verify_commitment(amount: u64, blinding_factor: &[u8], commitment: &str) -> bool {
-    let expected = generate_commitment(amount, blinding_factor);
+pub fn verify_commitment(
+    amount: u64,
+    blinding_factor: &[u8],
+    commitment: &str,
+    algorithm: CommitmentAlgorithm,
+) -> bool {
+    let expected = generate_commitment(amount, blinding_factor, algorithm);
     expected == commitment
 }
 
@@ -133,15 +173,11 @@ mod tests {
         let user_pubkey = "7xKXtg2CW8ukAp9rXKD2RQU3w5RJKPME6nXbvNfTQAaP";
         let amount = 1_000_000u64; // 1 USDC (6 decimals)
 
-        let result = encrypt_amount(amount, &master_key, user_pubkey).unwrap();
+        let cipher = user_cipher(&master_key, user_pubkey).unwrap();
+        let result = encrypt_with_cipher(&cipher, amount, CommitmentAlgorithm::Sha256).unwrap();
 
-        let decrypted = decrypt_amount(
-            &result.ciphertext,
-            &result.nonce,
-            &master_key,
-            user_pubkey,
-        )
-        .unwrap();
+        let cipher = user_cipher(&master_key, user_pubkey).unwrap();
+        let decrypted = decrypt_with_cipher(&cipher, &result.ciphertext, &result.nonce).unwrap();
 
         assert_eq!(amount, decrypted);
     }
@@ -151,8 +187,32 @@ mod tests {
         let amount = 1_000_000u64;
         let blinding_factor = [0u8; 12];
 
-        let commitment = generate_commitment(amount, &blinding_factor);
-        assert!(verify_commitment(amount, &blinding_factor, &commitment));
-        assert!(!verify_commitment(amount + 1, &blinding_factor, &commitment));
+        for algorithm in [CommitmentAlgorithm::Sha256, CommitmentAlgorithm::Blake3] {
+            let commitment = generate_commitment(amount, &blinding_factor, algorithm);
+            assert!(verify_commitment(amount, &blinding_factor, &commitment, algorithm));
+            assert!(!verify_commitment(amount + 1, &blinding_factor, &commitment, algorithm));
+        }
+        let sha256 = generate_commitment(amount, &blinding_factor, CommitmentAlgorithm::Sha256);
+        assert!(!verify_commitment(amount, &blinding_factor, &sha256, CommitmentAlgorithm::Blake3));
+    }
+
+    #[test]
+    fn test_commitment_vectors() {
+        let path = format!("{}/tests/fixtures/commitment_vectors.json", env!("CARGO_MANIFEST_DIR"));
+        let fixture: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap();
+
+        for vector in fixture["vectors"].as_array().unwrap() {
+            let amount: u64 = vector["amount"].as_str().unwrap().parse().unwrap();
+            let blinding_factor = hex::decode(vector["blinding_factor"].as_str().unwrap()).unwrap();
+            let algorithm: CommitmentAlgorithm =
+                serde_json::from_value(vector["algorithm"].clone()).unwrap();
+            assert_eq!(
+                generate_commitment(amount, &blinding_factor, algorithm),
+                vector["commitment"].as_str().unwrap(),
+                "{}",
+                vector["description"]
+            );
+        }
     }
 }
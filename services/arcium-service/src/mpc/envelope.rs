@@ -0,0 +1,213 @@
+//! Sealed envelopes: the amount encrypted to a merchant's X25519 public key,
+//! so the merchant can read it offline without calling our decrypt endpoint.
+//!
+//! Scheme (version 1):
+//!
+//! ```text
+//! shared     = X25519(ephemeral_secret, merchant_public)
+//! key        = HKDF-SHA256(ikm = shared,
+//!                          salt = ephemeral_public || merchant_public,
+//!                          info = "ninjapay-merchant-envelope-v1", len = 32)
+//! ciphertext = ChaCha20-Poly1305(key, nonce, amount as u64 little-endian)
+//! ```
+//!
+//! Every envelope uses a fresh ephemeral key. Test vectors live in
+//! `tests/fixtures/merchant_envelope_vectors.json`.
+
+use base64::Engine;
+use chacha20poly1305::{
+    aead::{Aead, KeyInit},
+    ChaCha20Poly1305, Nonce,
+};
+use hkdf::Hkdf;
+use rand::{rngs::OsRng, Rng};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use x25519_dalek::{EphemeralSecret, PublicKey, SharedSecret, StaticSecret};
+
+use crate::error::ServiceError;
+
+pub const ENVELOPE_VERSION: u8 = 1;
+const HKDF_INFO: &[u8] = b"ninjapay-merchant-envelope-v1";
+const NONCE_SIZE: usize = 12;
+
+/// An amount only the holder of the merchant's X25519 secret key can open
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct MerchantEnvelope {
+    pub version: u8,
+    /// Hex-encoded sender key for this envelope
+    pub ephemeral_public_key: String,
+    /// Hex-encoded
+    pub nonce: String,
+    /// Base64-encoded, including the Poly1305 tag
+    pub ciphertext: String,
+}
+
+/// Parse a hex-encoded 32-byte X25519 public key
+pub fn parse_public_key(hex_key: &str) -> Result<PublicKey, ServiceError> {
+    let bytes: [u8; 32] = hex::decode(hex_key)
+        .ok()
+        .and_then(|b| b.try_into().ok())
+        .ok_or_else(|| {
+            ServiceError::InvalidInput(
+                "merchant_x25519_pubkey must be a 32-byte hex string".to_string(),
+            )
+        })?;
+    Ok(PublicKey::from(bytes))
+}
+
+/// Seal `amount` to the merchant's key
+pub fn seal(amount: u64, merchant_public: &PublicKey) -> Result<MerchantEnvelope, ServiceError> {
+    let ephemeral = EphemeralSecret::random_from_rng(OsRng);
+    let ephemeral_public = PublicKey::from(&ephemeral);
+    let mut nonce = [0u8; NONCE_SIZE];
+    rand::thread_rng().fill(&mut nonce);
+
+    let shared = ephemeral.diffie_hellman(merchant_public);
+    seal_with_shared(amount, merchant_public, &ephemeral_public, &shared, nonce)
+}
+
+/// Seal with a caller-chosen ephemeral key and nonce, for deterministic
+/// fixtures only
+#[allow(dead_code)] // used by gen-fixtures, which builds this module separately
+pub fn seal_with_ephemeral(
+    amount: u64,
+    merchant_public: &PublicKey,
+    ephemeral: &StaticSecret,
+    nonce: [u8; NONCE_SIZE],
+) -> Result<MerchantEnvelope, ServiceError> {
+    let shared = ephemeral.diffie_hellman(merchant_public);
+    seal_with_shared(amount, merchant_public, &PublicKey::from(ephemeral), &shared, nonce)
+}
+
+fn seal_with_shared(
+    amount: u64,
+    merchant_public: &PublicKey,
+    ephemeral_public: &PublicKey,
+    shared: &SharedSecret,
+    nonce: [u8; NONCE_SIZE],
+) -> Result<MerchantEnvelope, ServiceError> {
+    // A low-order merchant key yields an all-zero secret anyone can compute
+    if !shared.was_contributory() {
+        return Err(ServiceError::InvalidInput(
+            "merchant_x25519_pubkey is not a valid X25519 public key".to_string(),
+        ));
+    }
+
+    let cipher = envelope_cipher(shared, ephemeral_public, merchant_public)?;
+    let ciphertext = cipher
+        .encrypt(&Nonce::from(nonce), amount.to_le_bytes().as_ref())
+        .map_err(|e| ServiceError::EncryptionError(format!("Envelope sealing failed: {}", e)))?;
+
+    Ok(MerchantEnvelope {
+        version: ENVELOPE_VERSION,
+        ephemeral_public_key: hex::encode(ephemeral_public.as_bytes()),
+        nonce: hex::encode(nonce),
+        ciphertext: base64::engine::general_purpose::STANDARD.encode(ciphertext),
+    })
+}
+
+fn envelope_cipher(
+    shared: &SharedSecret,
+    ephemeral_public: &PublicKey,
+    merchant_public: &PublicKey,
+) -> Result<ChaCha20Poly1305, ServiceError> {
+    let mut salt = [0u8; 64];
+    salt[..32].copy_from_slice(ephemeral_public.as_bytes());
+    salt[32..].copy_from_slice(merchant_public.as_bytes());
+
+    let mut key = [0u8; 32];
+    Hkdf::<Sha256>::new(Some(&salt), shared.as_bytes())
+        .expand(HKDF_INFO, &mut key)
+        .map_err(|e| ServiceError::EncryptionError(format!("HKDF expansion failed: {}", e)))?;
+
+    ChaCha20Poly1305::new_from_slice(&key)
+        .map_err(|e| ServiceError::EncryptionError(format!("Failed to create cipher: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture() -> serde_json::Value {
+        let path = format!(
+            "{}/tests/fixtures/merchant_envelope_vectors.json",
+            env!("CARGO_MANIFEST_DIR")
+        );
+        serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap()
+    }
+
+    fn secret(hex_key: &str) -> StaticSecret {
+        let bytes: [u8; 32] = hex::decode(hex_key).unwrap().try_into().unwrap();
+        StaticSecret::from(bytes)
+    }
+
+    /// Reference opener, as a merchant backend would implement it
+    fn open(envelope: &MerchantEnvelope, merchant_secret: &StaticSecret) -> Option<u64> {
+        let ephemeral_public = parse_public_key(&envelope.ephemeral_public_key).ok()?;
+        let merchant_public = PublicKey::from(merchant_secret);
+        let shared = merchant_secret.diffie_hellman(&ephemeral_public);
+        let cipher = envelope_cipher(&shared, &ephemeral_public, &merchant_public).ok()?;
+
+        let nonce: [u8; NONCE_SIZE] = hex::decode(&envelope.nonce).ok()?.try_into().ok()?;
+        let ciphertext = base64::engine::general_purpose::STANDARD
+            .decode(&envelope.ciphertext)
+            .ok()?;
+        let plaintext = cipher.decrypt(&Nonce::from(nonce), ciphertext.as_ref()).ok()?;
+        Some(u64::from_le_bytes(plaintext.try_into().ok()?))
+    }
+
+    #[test]
+    fn test_vectors() {
+        for vector in fixture()["vectors"].as_array().unwrap() {
+            let merchant_secret = secret(vector["merchant_secret_key"].as_str().unwrap());
+            let merchant_public = PublicKey::from(&merchant_secret);
+            assert_eq!(
+                hex::encode(merchant_public.as_bytes()),
+                vector["merchant_public_key"].as_str().unwrap()
+            );
+
+            let ephemeral = secret(vector["ephemeral_secret_key"].as_str().unwrap());
+            let nonce: [u8; NONCE_SIZE] = hex::decode(vector["nonce"].as_str().unwrap())
+                .unwrap()
+                .try_into()
+                .unwrap();
+            let amount: u64 = vector["amount"].as_str().unwrap().parse().unwrap();
+
+            let envelope = seal_with_shared(
+                amount,
+                &merchant_public,
+                &PublicKey::from(&ephemeral),
+                &ephemeral.diffie_hellman(&merchant_public),
+                nonce,
+            )
+            .unwrap();
+
+            let expected: MerchantEnvelope =
+                serde_json::from_value(vector["envelope"].clone()).unwrap();
+            assert_eq!(envelope, expected);
+            assert_eq!(open(&expected, &merchant_secret), Some(amount));
+        }
+    }
+
+    #[test]
+    fn test_only_the_merchant_can_open() {
+        let merchant_secret = StaticSecret::random_from_rng(OsRng);
+        let envelope = seal(42, &PublicKey::from(&merchant_secret)).unwrap();
+        assert_eq!(open(&envelope, &merchant_secret), Some(42));
+
+        let other = StaticSecret::random_from_rng(OsRng);
+        assert_eq!(open(&envelope, &other), None);
+    }
+
+    #[test]
+    fn test_rejects_invalid_keys() {
+        assert!(parse_public_key("abcd").is_err());
+        assert!(parse_public_key(&"zz".repeat(32)).is_err());
+
+        // The identity point is low-order
+        let identity = parse_public_key(&hex::encode([0u8; 32])).unwrap();
+        assert!(seal(1, &identity).is_err());
+    }
+}
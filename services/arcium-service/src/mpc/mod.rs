@@ -1,5 +1,9 @@
 mod client;
-mod encryption;
+pub mod encryption;
+pub mod envelope;
 
-pub use client::MpcClient;
-pub use encryption::{encrypt_amount, decrypt_amount, generate_commitment, EncryptionResult};
+pub use client::{
+    payment_computation_id, ComputationParams, ComputationResponse, MpcClient, PaymentSettlementParams, PayrollPayment,
+    PayrollSettlementParams,
+};
+pub use encryption::{verify_commitment, CommitmentAlgorithm};
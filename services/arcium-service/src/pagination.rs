@@ -0,0 +1,321 @@
+use actix_web::{dev::Payload, web, FromRequest, HttpRequest};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::future::{ready, Ready};
+use std::marker::PhantomData;
+
+use crate::error::ServiceError;
+
+pub const DEFAULT_LIMIT: usize = 50;
+pub const MAX_LIMIT: usize = 200;
+
+/// An item served by a paginated list endpoint.
+///
+/// Items are ordered by `sort_key(field)` and then by `list_id`, and the
+/// cursor records the last item returned (keyset pagination), so items
+/// inserted between pages never shift later pages.
+pub trait Listable {
+    /// Endpoint-specific filter parameters, read from the query string
+    type Filters: DeserializeOwned;
+
+    /// Sortable fields; the first, newest first, is the default
+    const SORT_FIELDS: &'static [&'static str];
+
+    fn list_id(&self) -> &str;
+
+    /// Key for one of `SORT_FIELDS`
+    fn sort_key(&self, field: &str) -> u64;
+
+    fn matches(&self, filters: &Self::Filters) -> bool;
+}
+
+/// Position after the last item of a page
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct Cursor {
+    sort: String,
+    key: u64,
+    id: String,
+}
+
+impl Cursor {
+    fn encode(&self) -> String {
+        URL_SAFE_NO_PAD.encode(serde_json::to_vec(self).expect("cursor serializes"))
+    }
+
+    fn decode(cursor: &str) -> Result<Self, ServiceError> {
+        let invalid = || ServiceError::InvalidInput("cursor is malformed".to_string());
+        let bytes = URL_SAFE_NO_PAD.decode(cursor).map_err(|_| invalid())?;
+        serde_json::from_slice(&bytes).map_err(|_| invalid())
+    }
+}
+
+#[derive(Deserialize)]
+struct PageParams {
+    limit: Option<usize>,
+    cursor: Option<String>,
+    sort: Option<String>,
+}
+
+/// `?limit=&cursor=&sort=` plus the item's typed filters.
+///
+/// `sort` names a field from `Listable::SORT_FIELDS`, prefixed with `-`
+/// for descending order. Invalid parameters are rejected with 400.
+pub struct ListQuery<T: Listable> {
+    pub limit: usize,
+    /// Sort spec as given, e.g. `-created_at`
+    sort: String,
+    field: &'static str,
+    descending: bool,
+    cursor: Option<Cursor>,
+    pub filters: T::Filters,
+    _item: PhantomData<T>,
+}
+
+/// One page of a list endpoint
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub struct Paginated<T> {
+    pub items: Vec<T>,
+    pub next_cursor: Option<String>,
+    pub has_more: bool,
+}
+
+impl<T: Listable> ListQuery<T> {
+    pub fn parse(query_string: &str) -> Result<Self, ServiceError> {
+        let params = web::Query::<PageParams>::from_query(query_string)
+            .map_err(|e| ServiceError::InvalidInput(format!("Invalid list parameters: {}", e)))?
+            .into_inner();
+        let filters = web::Query::<T::Filters>::from_query(query_string)
+            .map_err(|e| ServiceError::InvalidInput(format!("Invalid filter: {}", e)))?
+            .into_inner();
+
+        let limit = params.limit.unwrap_or(DEFAULT_LIMIT);
+        if limit == 0 || limit > MAX_LIMIT {
+            return Err(ServiceError::InvalidInput(format!(
+                "limit must be between 1 and {}",
+                MAX_LIMIT
+            )));
+        }
+
+        let sort = params.sort.unwrap_or_else(|| format!("-{}", T::SORT_FIELDS[0]));
+        let (descending, name) = match sort.strip_prefix('-') {
+            Some(name) => (true, name),
+            None => (false, sort.as_str()),
+        };
+        let field = T::SORT_FIELDS
+            .iter()
+            .copied()
+            .find(|f| *f == name)
+            .ok_or_else(|| {
+                ServiceError::InvalidInput(format!("sort must be one of: {}", T::SORT_FIELDS.join(", ")))
+            })?;
+
+        let cursor = params.cursor.as_deref().map(Cursor::decode).transpose()?;
+        if cursor.as_ref().is_some_and(|c| c.sort != sort) {
+            return Err(ServiceError::InvalidInput(
+                "cursor was issued for a different sort".to_string(),
+            ));
+        }
+
+        Ok(Self {
+            limit,
+            field,
+            descending,
+            sort,
+            cursor,
+            filters,
+            _item: PhantomData,
+        })
+    }
+
+    fn compare(&self, key: u64, id: &str, other_key: u64, other_id: &str) -> Ordering {
+        let ordering = key.cmp(&other_key).then_with(|| id.cmp(other_id));
+        if self.descending {
+            ordering.reverse()
+        } else {
+            ordering
+        }
+    }
+
+    /// Filter, sort and cut one page out of `items`
+    pub fn paginate(&self, items: Vec<T>) -> Paginated<T> {
+        let mut items: Vec<T> = items
+            .into_iter()
+            .filter(|item| item.matches(&self.filters))
+            .filter(|item| match &self.cursor {
+                Some(c) => {
+                    self.compare(item.sort_key(self.field), item.list_id(), c.key, &c.id) == Ordering::Greater
+                }
+                None => true,
+            })
+            .collect();
+        items.sort_by(|a, b| {
+            self.compare(a.sort_key(self.field), a.list_id(), b.sort_key(self.field), b.list_id())
+        });
+
+        let has_more = items.len() > self.limit;
+        items.truncate(self.limit);
+        let next_cursor = match items.last() {
+            Some(last) if has_more => Some(
+                Cursor {
+                    sort: self.sort.clone(),
+                    key: last.sort_key(self.field),
+                    id: last.list_id().to_string(),
+                }
+                .encode(),
+            ),
+            _ => None,
+        };
+
+        Paginated {
+            items,
+            next_cursor,
+            has_more,
+        }
+    }
+}
+
+impl<T: Listable> FromRequest for ListQuery<T> {
+    type Error = ServiceError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        ready(Self::parse(req.query_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+    use std::collections::HashSet;
+
+    #[derive(Debug, Clone)]
+    struct Item {
+        id: String,
+        at: u64,
+        kind: u8,
+    }
+
+    #[derive(Deserialize)]
+    struct ItemFilters {
+        kind: Option<u8>,
+    }
+
+    impl Listable for Item {
+        type Filters = ItemFilters;
+        const SORT_FIELDS: &'static [&'static str] = &["at"];
+
+        fn list_id(&self) -> &str {
+            &self.id
+        }
+
+        fn sort_key(&self, _field: &str) -> u64 {
+            self.at
+        }
+
+        fn matches(&self, filters: &ItemFilters) -> bool {
+            filters.kind.is_none_or(|kind| kind == self.kind)
+        }
+    }
+
+    fn item(n: usize, at: u64) -> Item {
+        Item {
+            id: format!("item_{:04}", n),
+            at,
+            kind: (n % 2) as u8,
+        }
+    }
+
+    fn query(qs: &str) -> Result<ListQuery<Item>, ServiceError> {
+        ListQuery::parse(qs)
+    }
+
+    fn is_invalid_input(result: Result<ListQuery<Item>, ServiceError>) -> bool {
+        matches!(result, Err(ServiceError::InvalidInput(_)))
+    }
+
+    #[test]
+    fn test_defaults_and_filters() {
+        let items: Vec<Item> = (0..5).map(|n| item(n, n as u64)).collect();
+
+        let page = query("").unwrap().paginate(items.clone());
+        let ids: Vec<&str> = page.items.iter().map(|i| i.id.as_str()).collect();
+        assert_eq!(ids, ["item_0004", "item_0003", "item_0002", "item_0001", "item_0000"]);
+        assert!(!page.has_more);
+        assert!(page.next_cursor.is_none());
+
+        let page = query("kind=1&sort=at&limit=1").unwrap().paginate(items);
+        assert_eq!(page.items[0].id, "item_0001");
+        assert!(page.has_more);
+    }
+
+    #[test]
+    fn test_invalid_parameters_are_rejected() {
+        assert!(is_invalid_input(query("limit=0")));
+        assert!(is_invalid_input(query(&format!("limit={}", MAX_LIMIT + 1))));
+        assert!(is_invalid_input(query("limit=ten")));
+        assert!(is_invalid_input(query("sort=id")));
+        assert!(is_invalid_input(query("kind=banana")));
+
+        let page = query("limit=1").unwrap().paginate(vec![item(0, 0), item(1, 1)]);
+        let cursor = page.next_cursor.unwrap();
+        assert!(is_invalid_input(query(&format!("sort=at&cursor={}", cursor))));
+    }
+
+    proptest! {
+        #[test]
+        fn prop_malformed_cursors_are_invalid_input(cursor in "[A-Za-z0-9_-]{0,64}") {
+            let result = query(&format!("cursor={}", cursor));
+            prop_assert!(result.is_ok() || is_invalid_input(result));
+        }
+
+        #[test]
+        fn prop_cursors_are_stable_under_inserts(
+            initial in prop::collection::vec(0u64..20, 0..40),
+            inserts in prop::collection::vec(prop::collection::vec(0u64..20, 0..5), 0..20),
+            limit in 1usize..7,
+            descending in any::<bool>(),
+        ) {
+            let mut items: Vec<Item> = initial.iter().enumerate().map(|(n, at)| item(n, *at)).collect();
+            let original: HashSet<String> = items.iter().map(|i| i.id.clone()).collect();
+            let sort = if descending { "-at" } else { "at" };
+
+            let mut seen = Vec::new();
+            let mut cursor: Option<String> = None;
+            for round in 0.. {
+                let qs = match &cursor {
+                    Some(c) => format!("limit={}&sort={}&cursor={}", limit, sort, c),
+                    None => format!("limit={}&sort={}", limit, sort),
+                };
+                let page = query(&qs).unwrap().paginate(items.clone());
+                prop_assert_eq!(page.has_more, page.next_cursor.is_some());
+                seen.extend(page.items.iter().map(|i| (i.at, i.id.clone())));
+
+                // Concurrent writers insert between page requests
+                for at in inserts.get(round).into_iter().flatten() {
+                    items.push(item(items.len(), *at));
+                }
+
+                match page.next_cursor {
+                    Some(next) => cursor = Some(next),
+                    None => break,
+                }
+            }
+
+            let mut expected = seen.clone();
+            expected.sort();
+            if descending {
+                expected.reverse();
+            }
+            prop_assert_eq!(&seen, &expected, "pages are in order");
+
+            let ids: HashSet<String> = seen.iter().map(|(_, id)| id.clone()).collect();
+            prop_assert_eq!(ids.len(), seen.len(), "no item is returned twice");
+            prop_assert!(original.is_subset(&ids), "no pre-existing item is skipped");
+        }
+    }
+}
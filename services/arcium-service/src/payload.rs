@@ -0,0 +1,196 @@
+//! Stored computation payloads.
+//!
+//! Params and results are kept as bytes tagged with the codec that wrote
+//! them. Payloads at or above the compression threshold are zstd
+//! compressed (when that makes them smaller); every payload must fit in
+//! `max_stored_bytes` once stored.
+
+use serde::Serialize;
+
+use crate::error::ServiceError;
+
+const ZSTD_LEVEL: i32 = 3;
+
+#[derive(Debug, Clone, Copy)]
+pub struct PayloadLimits {
+    /// Payloads this size or larger are compressed
+    pub compress_threshold_bytes: usize,
+    /// Largest payload accepted, measured after compression
+    pub max_stored_bytes: usize,
+}
+
+/// How a payload's bytes are encoded. Readers reject tags they don't know.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Codec {
+    Identity,
+    Zstd,
+}
+
+/// A JSON payload as stored; only the codec and sizes are serialized
+#[derive(Debug, Clone, Serialize)]
+pub struct StoredPayload {
+    pub codec: Codec,
+    pub original_size: usize,
+    pub stored_size: usize,
+    #[serde(skip)]
+    data: Vec<u8>,
+}
+
+/// Result of compressing already stored payloads
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub struct CompressionReport {
+    pub payloads_scanned: usize,
+    pub payloads_compressed: usize,
+    pub bytes_before: usize,
+    pub bytes_after: usize,
+    pub bytes_reclaimed: usize,
+}
+
+impl StoredPayload {
+    /// Serialize `value`, compressing it if it is over the threshold.
+    /// Fails with `PayloadTooLarge` if the stored form is over the limit.
+    pub fn encode<T: Serialize>(value: &T, limits: &PayloadLimits) -> Result<Self, ServiceError> {
+        let json = serde_json::to_vec(value)
+            .map_err(|e| ServiceError::InternalError(format!("Payload serialization failed: {}", e)))?;
+        let payload = Self::identity(json).compressed(limits)?;
+
+        if payload.stored_size > limits.max_stored_bytes {
+            return Err(ServiceError::PayloadTooLarge(format!(
+                "computation payload is {} bytes ({} stored with {:?}); the limit is {} stored bytes",
+                payload.original_size, payload.stored_size, payload.codec, limits.max_stored_bytes
+            )));
+        }
+        Ok(payload)
+    }
+
+    fn identity(data: Vec<u8>) -> Self {
+        Self {
+            codec: Codec::Identity,
+            original_size: data.len(),
+            stored_size: data.len(),
+            data,
+        }
+    }
+
+    /// This payload recompressed under `limits`; unchanged if it is already
+    /// compressed, under the threshold, or doesn't shrink
+    fn compressed(self, limits: &PayloadLimits) -> Result<Self, ServiceError> {
+        if self.codec != Codec::Identity || self.original_size < limits.compress_threshold_bytes {
+            return Ok(self);
+        }
+
+        let data = zstd::bulk::compress(&self.data, ZSTD_LEVEL)
+            .map_err(|e| ServiceError::InternalError(format!("Payload compression failed: {}", e)))?;
+        if data.len() >= self.stored_size {
+            return Ok(self);
+        }
+        Ok(Self {
+            codec: Codec::Zstd,
+            original_size: self.original_size,
+            stored_size: data.len(),
+            data,
+        })
+    }
+
+    /// The original bytes
+    pub fn decode(&self) -> Result<Vec<u8>, ServiceError> {
+        match self.codec {
+            Codec::Identity => Ok(self.data.clone()),
+            Codec::Zstd => zstd::bulk::decompress(&self.data, self.original_size).map_err(|e| {
+                ServiceError::InternalError(format!("Stored payload is corrupt: {}", e))
+            }),
+        }
+    }
+
+    pub fn to_json(&self) -> Result<serde_json::Value, ServiceError> {
+        serde_json::from_slice(&self.decode()?)
+            .map_err(|e| ServiceError::InternalError(format!("Stored payload is corrupt: {}", e)))
+    }
+
+    /// Compress in place if the payload qualifies, adding it to `report`
+    pub fn compress_in_place(&mut self, limits: &PayloadLimits, report: &mut CompressionReport) {
+        report.payloads_scanned += 1;
+        report.bytes_before += self.stored_size;
+
+        let current = self.clone();
+        match current.compressed(limits) {
+            Ok(compressed) if compressed.codec != self.codec => {
+                report.payloads_compressed += 1;
+                *self = compressed;
+            }
+            Ok(_) => {}
+            Err(e) => tracing::warn!("Leaving payload uncompressed: {}", e),
+        }
+        report.bytes_after += self.stored_size;
+        report.bytes_reclaimed = report.bytes_before - report.bytes_after;
+    }
+
+    #[cfg(test)]
+    pub fn uncompressed<T: Serialize>(value: &T) -> Self {
+        Self::identity(serde_json::to_vec(value).unwrap())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const LIMITS: PayloadLimits = PayloadLimits {
+        compress_threshold_bytes: 1024,
+        max_stored_bytes: 64 * 1024,
+    };
+
+    fn payroll(rows: usize) -> serde_json::Value {
+        let payments: Vec<_> = (0..rows)
+            .map(|i| json!({ "employee_id": format!("emp_{}", i), "employee_wallet": "11111111111111111111111111111111", "amount": 1_000_000 }))
+            .collect();
+        json!({ "computation_type": "payroll_settlement", "params": { "payments": payments } })
+    }
+
+    #[test]
+    fn test_compresses_above_threshold_and_round_trips() {
+        let small = StoredPayload::encode(&payroll(1), &LIMITS).unwrap();
+        assert_eq!(small.codec, Codec::Identity);
+        assert_eq!(small.to_json().unwrap(), payroll(1));
+
+        let large = StoredPayload::encode(&payroll(500), &LIMITS).unwrap();
+        assert_eq!(large.codec, Codec::Zstd);
+        assert!(large.stored_size * 5 < large.original_size);
+        assert_eq!(large.to_json().unwrap(), payroll(500));
+    }
+
+    #[test]
+    fn test_rejects_payloads_over_the_stored_limit() {
+        let limits = PayloadLimits {
+            compress_threshold_bytes: usize::MAX,
+            ..LIMITS
+        };
+        assert!(matches!(
+            StoredPayload::encode(&payroll(1000), &limits),
+            Err(ServiceError::PayloadTooLarge(_))
+        ));
+
+        // The same payload fits once compressed
+        assert!(StoredPayload::encode(&payroll(1000), &LIMITS).is_ok());
+    }
+
+    #[test]
+    fn test_compress_in_place_reports_space_reclaimed() {
+        let mut report = CompressionReport::default();
+        let mut small = StoredPayload::uncompressed(&payroll(1));
+        let mut large = StoredPayload::uncompressed(&payroll(500));
+        let original = large.original_size;
+
+        small.compress_in_place(&LIMITS, &mut report);
+        large.compress_in_place(&LIMITS, &mut report);
+        large.compress_in_place(&LIMITS, &mut report);
+
+        assert_eq!(report.payloads_scanned, 3);
+        assert_eq!(report.payloads_compressed, 1);
+        assert_eq!(report.bytes_reclaimed, original - large.stored_size);
+        assert_eq!(large.to_json().unwrap(), payroll(500));
+    }
+}
@@ -0,0 +1,340 @@
+//! Streaming NDJSON payroll ingestion.
+//!
+//! Each line is one payment, validated as bytes arrive, so a large payroll
+//! never sits in memory as a single JSON document. Valid rows are grouped
+//! into cluster-sized chunks as they are read; nothing is queued until the
+//! whole upload has arrived, so an aborted upload leaves nothing behind.
+
+use serde::{Deserialize, Serialize};
+use solana_sdk::pubkey::Pubkey;
+use std::str::FromStr;
+
+use crate::error::ServiceError;
+use crate::mpc::PayrollPayment;
+use crate::strict_mode::LenientU64;
+
+/// Longest accepted line; a payment row is well under 1 KiB
+pub const MAX_LINE_BYTES: usize = 4096;
+
+/// Row errors returned in full; the rest are only counted
+pub const MAX_REPORTED_ERRORS: usize = 100;
+
+#[derive(Debug, Clone, Copy)]
+pub struct IngestLimits {
+    pub max_rows: usize,
+    pub max_bytes: usize,
+    /// Payments per cluster computation
+    pub chunk_size: usize,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+struct PayrollRow {
+    employee_id: String,
+    employee_wallet: String,
+    amount: LenientU64,
+}
+
+/// A rejected line, numbered from 1
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RowError {
+    pub line: usize,
+    pub message: String,
+}
+
+/// Everything read from a completed upload
+#[derive(Debug)]
+pub struct IngestedPayroll {
+    pub rows: usize,
+    pub bytes: usize,
+    pub chunks: Vec<Vec<PayrollPayment>>,
+    pub error_count: usize,
+    /// The first `MAX_REPORTED_ERRORS` row errors
+    pub errors: Vec<RowError>,
+    /// First valid row whose amount was a JSON number rather than a string
+    pub first_numeric_amount_line: Option<usize>,
+}
+
+/// Incremental NDJSON reader; feed it body chunks with `push`, then `finish`
+pub struct PayrollIngest {
+    limits: IngestLimits,
+    /// The current, not yet terminated line
+    line: Vec<u8>,
+    line_number: usize,
+    /// Set while discarding the rest of an overlong line
+    overlong: bool,
+    bytes: usize,
+    rows: usize,
+    chunks: Vec<Vec<PayrollPayment>>,
+    current: Vec<PayrollPayment>,
+    error_count: usize,
+    errors: Vec<RowError>,
+    first_numeric_amount_line: Option<usize>,
+}
+
+impl PayrollIngest {
+    pub fn new(limits: IngestLimits) -> Self {
+        Self {
+            limits,
+            line: Vec::new(),
+            line_number: 0,
+            overlong: false,
+            bytes: 0,
+            rows: 0,
+            chunks: Vec::new(),
+            current: Vec::with_capacity(limits.chunk_size),
+            error_count: 0,
+            errors: Vec::new(),
+            first_numeric_amount_line: None,
+        }
+    }
+
+    /// Consume the next body chunk; fails once the upload exceeds a limit
+    pub fn push(&mut self, mut data: &[u8]) -> Result<(), ServiceError> {
+        self.bytes += data.len();
+        if self.bytes > self.limits.max_bytes {
+            return Err(ServiceError::PayloadTooLarge(format!(
+                "Upload exceeds {} bytes",
+                self.limits.max_bytes
+            )));
+        }
+
+        while let Some(end) = data.iter().position(|&b| b == b'\n') {
+            self.buffer(&data[..end]);
+            self.end_line()?;
+            data = &data[end + 1..];
+        }
+        self.buffer(data);
+        Ok(())
+    }
+
+    /// Process a final unterminated line and close the last chunk
+    pub fn finish(mut self) -> Result<IngestedPayroll, ServiceError> {
+        if !self.line.is_empty() || self.overlong {
+            self.end_line()?;
+        }
+        if !self.current.is_empty() {
+            self.chunks.push(std::mem::take(&mut self.current));
+        }
+
+        Ok(IngestedPayroll {
+            rows: self.rows,
+            bytes: self.bytes,
+            chunks: self.chunks,
+            error_count: self.error_count,
+            errors: self.errors,
+            first_numeric_amount_line: self.first_numeric_amount_line,
+        })
+    }
+
+    fn buffer(&mut self, data: &[u8]) {
+        if self.overlong {
+            return;
+        }
+        if self.line.len() + data.len() > MAX_LINE_BYTES {
+            self.overlong = true;
+            self.line.clear();
+        } else {
+            self.line.extend_from_slice(data);
+        }
+    }
+
+    fn end_line(&mut self) -> Result<(), ServiceError> {
+        self.line_number += 1;
+        let line = std::mem::take(&mut self.line);
+        let overlong = std::mem::replace(&mut self.overlong, false);
+
+        let trimmed = line.strip_suffix(b"\r").unwrap_or(&line);
+        if !overlong && trimmed.iter().all(u8::is_ascii_whitespace) {
+            return Ok(());
+        }
+
+        self.rows += 1;
+        if self.rows > self.limits.max_rows {
+            return Err(ServiceError::PayloadTooLarge(format!(
+                "Upload exceeds {} rows",
+                self.limits.max_rows
+            )));
+        }
+
+        let parsed = if overlong {
+            Err(format!("line exceeds {} bytes", MAX_LINE_BYTES))
+        } else {
+            parse_row(trimmed)
+        };
+        match parsed {
+            Ok((payment, numeric_amount)) => {
+                if numeric_amount {
+                    self.first_numeric_amount_line.get_or_insert(self.line_number);
+                }
+                // Once a row has failed the upload will be rejected, so stop
+                // keeping the valid ones
+                if self.error_count == 0 {
+                    self.current.push(payment);
+                    if self.current.len() == self.limits.chunk_size {
+                        let full = std::mem::replace(
+                            &mut self.current,
+                            Vec::with_capacity(self.limits.chunk_size),
+                        );
+                        self.chunks.push(full);
+                    }
+                }
+            }
+            Err(message) => {
+                if self.error_count == 0 {
+                    self.chunks = Vec::new();
+                    self.current = Vec::new();
+                }
+                self.error_count += 1;
+                if self.errors.len() < MAX_REPORTED_ERRORS {
+                    self.errors.push(RowError {
+                        line: self.line_number,
+                        message,
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A valid row, and whether its amount used the deprecated numeric form
+fn parse_row(line: &[u8]) -> Result<(PayrollPayment, bool), String> {
+    let row: PayrollRow = serde_json::from_slice(line).map_err(|e| e.to_string())?;
+
+    if row.employee_id.trim().is_empty() {
+        return Err("employee_id must not be empty".to_string());
+    }
+    if Pubkey::from_str(&row.employee_wallet).is_err() {
+        return Err("employee_wallet is not a valid Solana address".to_string());
+    }
+    if row.amount.value == 0 {
+        return Err("amount must be positive".to_string());
+    }
+
+    let payment = PayrollPayment {
+        employee_id: row.employee_id,
+        employee_wallet: row.employee_wallet,
+        amount: row.amount.value,
+    };
+    Ok((payment, row.amount.numeric))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WALLET: &str = "7xKXtg2CW87d97TXJSDpbD5jBkheTqA83TZRuJosgAsU";
+
+    fn limits() -> IngestLimits {
+        IngestLimits {
+            max_rows: 10,
+            max_bytes: 4096,
+            chunk_size: 2,
+        }
+    }
+
+    fn row(id: usize, amount: &str) -> String {
+        format!(
+            "{{\"employee_id\":\"emp_{}\",\"employee_wallet\":\"{}\",\"amount\":{}}}\n",
+            id, WALLET, amount
+        )
+    }
+
+    fn ingest(body: &str, split: usize) -> Result<IngestedPayroll, ServiceError> {
+        let mut ingest = PayrollIngest::new(limits());
+        for chunk in body.as_bytes().chunks(split) {
+            ingest.push(chunk)?;
+        }
+        ingest.finish()
+    }
+
+    #[test]
+    fn test_chunks_rows_regardless_of_body_splits() {
+        // CRLF on the second row, no terminator on the last
+        let body = format!(
+            "{}{}\r\n{}",
+            row(1, "100"),
+            row(2, "\"200\"").trim_end(),
+            row(3, "300").trim_end()
+        );
+
+        for split in [1, 7, body.len()] {
+            let payroll = ingest(&body, split).unwrap();
+            assert_eq!(payroll.rows, 3);
+            assert_eq!(payroll.bytes, body.len());
+            assert_eq!(payroll.error_count, 0);
+
+            let sizes: Vec<usize> = payroll.chunks.iter().map(Vec::len).collect();
+            assert_eq!(sizes, vec![2, 1]);
+            assert_eq!(payroll.chunks[0][1].amount, 200);
+            assert_eq!(payroll.chunks[1][0].employee_id, "emp_3");
+
+            // Only the second row sends its amount as a string
+            assert_eq!(payroll.first_numeric_amount_line, Some(1));
+        }
+    }
+
+    #[test]
+    fn test_reports_row_errors_by_line() {
+        let body = format!(
+            "{}\n{}not json\n{}{}",
+            row(1, "100"),
+            row(2, "0"),
+            row(3, "300").replace(WALLET, "not-a-wallet"),
+            row(4, "400")
+        );
+
+        let payroll = ingest(&body, 5).unwrap();
+        assert_eq!(payroll.rows, 5);
+        assert_eq!(payroll.error_count, 3);
+        assert!(payroll.chunks.is_empty());
+
+        let lines: Vec<usize> = payroll.errors.iter().map(|e| e.line).collect();
+        assert_eq!(lines, vec![3, 4, 5]);
+        assert_eq!(payroll.errors[0].message, "amount must be positive");
+    }
+
+    #[test]
+    fn test_overlong_line_is_a_row_error() {
+        let body = format!("{}{}\n{}", row(1, "100"), "x".repeat(MAX_LINE_BYTES + 1), row(2, "200"));
+        let mut ingest = PayrollIngest::new(IngestLimits {
+            max_bytes: 1 << 20,
+            ..limits()
+        });
+        ingest.push(body.as_bytes()).unwrap();
+        let payroll = ingest.finish().unwrap();
+
+        assert_eq!(payroll.error_count, 1);
+        assert_eq!(payroll.errors[0].line, 2);
+    }
+
+    #[test]
+    fn test_enforces_row_and_byte_limits() {
+        let rows: String = (0..11).map(|i| row(i, "1")).collect();
+        let mut ingest = PayrollIngest::new(IngestLimits {
+            max_bytes: 1 << 20,
+            ..limits()
+        });
+        assert!(matches!(ingest.push(rows.as_bytes()), Err(ServiceError::PayloadTooLarge(_))));
+
+        let mut ingest = PayrollIngest::new(limits());
+        ingest.push(&[b' '; 4096]).unwrap();
+        assert!(matches!(ingest.push(b" "), Err(ServiceError::PayloadTooLarge(_))));
+    }
+
+    #[test]
+    fn test_caps_reported_errors() {
+        let body = "{}\n".repeat(MAX_REPORTED_ERRORS + 5);
+        let mut ingest = PayrollIngest::new(IngestLimits {
+            max_rows: 1000,
+            max_bytes: 1 << 20,
+            chunk_size: 2,
+        });
+        ingest.push(body.as_bytes()).unwrap();
+        let payroll = ingest.finish().unwrap();
+
+        assert_eq!(payroll.error_count, MAX_REPORTED_ERRORS + 5);
+        assert_eq!(payroll.errors.len(), MAX_REPORTED_ERRORS);
+    }
+}
@@ -0,0 +1,227 @@
+//! Decoding of custom program errors from failed transactions.
+//!
+//! A failed `process_payment` surfaces only as `custom program error: 0x1771`.
+//! The code alone says which program raised it: 6000 and up are the vault's
+//! own `VaultError`s (generated from the program source by build.rs), 100 to
+//! 5999 are Anchor framework errors, and lower codes come from the SPL Token
+//! program the vault calls into.
+
+use serde::Serialize;
+
+include!(concat!(env!("OUT_DIR"), "/vault_errors.rs"));
+
+/// Anchor numbers `#[error_code]` variants from here
+const VAULT_ERROR_OFFSET: u32 = 6000;
+
+/// Lowest Anchor framework error code
+const ANCHOR_ERROR_MIN: u32 = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorSource {
+    Vault,
+    Anchor,
+    Token,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub struct ProgramError {
+    pub code: u32,
+    pub hex: String,
+    pub program: ErrorSource,
+    pub name: &'static str,
+    pub message: &'static str,
+    pub remediation: &'static str,
+}
+
+// (code, name, message, remediation)
+const ANCHOR_ERRORS: &[(u32, &str, &str, &str)] = &[
+    (101, "InstructionFallbackNotFound", "Fallback functions are not supported", "The client is calling an instruction the deployed program does not have; update the SDK or IDL."),
+    (102, "InstructionDidNotDeserialize", "The program could not deserialize the given instruction", "Check the instruction arguments match the program's IDL."),
+    (2000, "ConstraintMut", "A mut constraint was violated", "Mark the account writable in the transaction."),
+    (2001, "ConstraintHasOne", "A has one constraint was violated", "An account does not belong to the expected owner, e.g. the wrong authority for the vault."),
+    (2002, "ConstraintSigner", "A signer constraint was violated", "The payer or authority must sign the transaction."),
+    (2003, "ConstraintRaw", "A raw constraint was violated", "An account relationship check failed; rebuild the transaction from fresh account data."),
+    (2006, "ConstraintSeeds", "A seeds constraint was violated", "A PDA does not match its seeds; re-derive the vault config or payment record address."),
+    (2012, "ConstraintAddress", "An address constraint was violated", "An account is not at the expected address, e.g. the wrong fee collector."),
+    (2014, "ConstraintTokenMint", "A token mint constraint was violated", "A token account holds a different mint than the payment currency."),
+    (2015, "ConstraintTokenOwner", "A token owner constraint was violated", "A token account is not owned by the payer or merchant it was passed for."),
+    (3001, "AccountDiscriminatorNotFound", "No 8 byte discriminator was found on the account", "The account is empty; initialize the vault before taking payments."),
+    (3002, "AccountDiscriminatorMismatch", "8 byte discriminator did not match what was expected", "An account of the wrong type was passed."),
+    (3003, "AccountDidNotDeserialize", "Failed to deserialize the account", "A VaultConfig written by an older program version is shorter than the current layout; the vault authority runs migrate_vault_config."),
+    (3007, "AccountOwnedByWrongProgram", "The given account is owned by a different program than expected", "Check the program id and cluster (devnet vs mainnet)."),
+    (3012, "AccountNotInitialized", "The program expected this account to be already initialized", "Create the account, e.g. the merchant's token account, before paying."),
+];
+
+// (code, name, message, remediation)
+const TOKEN_ERRORS: &[(u32, &str, &str, &str)] = &[
+    (0, "NotRentExempt", "Lamport balance below rent-exempt threshold", "Fund the account to the rent-exempt minimum."),
+    (1, "InsufficientFunds", "Insufficient funds", "The payer's token balance is below the payment amount plus fee."),
+    (2, "InvalidMint", "Invalid Mint", "A token account was created for a different mint."),
+    (3, "MintMismatch", "Account not associated with this Mint", "Use the payer's and merchant's token accounts for the payment currency."),
+    (4, "OwnerMismatch", "Owner does not match", "The signer does not own the source token account."),
+    (9, "UninitializedState", "State is uninitialized", "Create the recipient's associated token account first."),
+    (17, "AccountFrozen", "Account is frozen", "The token account is frozen by the mint's freeze authority; the payment cannot proceed."),
+    (18, "MintDecimalsMismatch", "The provided decimals value different from the Mint decimals", "Pass the mint's decimals (6 for USDC)."),
+];
+
+fn vault_remediation(name: &str) -> Option<&'static str> {
+    match name {
+        "FeeTooHigh" => Some("Fees are capped at 1000 basis points (10%); submit a lower fee."),
+        "InvalidAmount" => Some("The payment amount must be greater than zero and within the vault's payment bounds; check min_payment and max_payment on the VaultConfig."),
+        "Unauthorized" => Some("Sign with the key this action needs: the vault authority, the program's upgrade authority for initialize, the proposed authority for accept_authority, the recovery authority for emergency recovery, or for a refund the payment's merchant and the fee collector."),
+        "AlreadyRefunded" => Some("This payment was already refunded; nothing more is owed to the payer."),
+        "InvalidFeeAccount" => Some("Pass the fee collector's token account for the payment mint as the fee account."),
+        "VaultPaused" => Some("Payments, payroll and refunds are halted by the vault authority; retry once the vault is unpaused."),
+        "MerchantAccountMismatch" => Some("Pass a token account owned by the merchant wallet, normally the merchant's associated token account."),
+        "MintMismatch" => Some("Use token accounts for the payment mint: the payer, merchant and fee accounts must all hold the same token, and a refund goes back in the token paid."),
+        "InvalidMerchantConfig" => Some("Pass the paying merchant's MerchantConfig (seeds merchant_cfg and the merchant wallet) as the first remaining account, or omit it to pay the vault-wide fee."),
+        "MathOverflow" => Some("A vault total would overflow; contact support with the transaction signature."),
+        "AmountTooSmall" => Some("The fee exceeds the payment amount; pay a larger amount or check the vault and merchant fee settings."),
+        "PaymentExpired" => Some("The payment request's expires_at has passed; ask the merchant for a new payment request."),
+        "InvalidSplit" => Some("Pass 1 to 8 splits with non-zero basis points adding up to 10000 less the vault fee, and their token accounts as remaining accounts in the same order."),
+        "InsufficientFunds" => Some("The company's token account holds less than the batch total; fund it before submitting the batch."),
+        "InvalidDisbursement" => Some("Pass one amount per payment adding up to the batch total, and for each an employee token account followed by its disbursement record at [disbursement, batch_id, index] as remaining accounts."),
+        "RateLimitExceeded" => Some("The payer has made max_payments_per_hour payments, or paid max_volume_per_hour base units, within the last hour; retry once their hourly window resets."),
+        "MintNotWhitelisted" => Some("The vault only accepts whitelisted mints: pass the mint's AcceptedMint account, or ask the vault authority to add the mint with add_accepted_mint."),
+        "NoPendingAuthority" => Some("There is no proposed authority to cancel; propose_authority first, or the proposal was already accepted or cancelled."),
+        "InvalidFeeSchedule" => Some("Pass 1 to 5 fee tiers sorted by strictly ascending volume_threshold; to charge the vault-wide fee again, use clear_fee_schedule."),
+        "FeeScheduleRequired" => Some("The vault charges fees by volume tier: pass its FeeSchedule account (seeds [\"fee_schedule\"]) with the payment."),
+        "InvalidFeeCollectors" => Some("Pass 1 to 3 distinct fee collectors whose basis points are each above 0 and add up to exactly 10000."),
+        "MissingFeeAccount" => Some("The vault divides fees among several collectors: pass each collector's token account for the mint as fee_token_account_1 and fee_token_account_2."),
+        "RetentionPeriodNotElapsed" => Some("The payment record is younger than the vault's retention period (at least 30 days); close it once that has passed, or ask the vault authority for a compliance close."),
+        "AuthorityTransferExpired" => Some("The authority offer lapsed 48 hours after it was proposed; ask the current authority to call propose_authority again."),
+        "InvalidPaymentBatch" => Some("Send one to five payments, with as many amounts, payment ids and commitments, and a merchant token account and payment record per payment as remaining accounts."),
+        "BatchNotFinal" => Some("The batch ran less than 30 days ago or tokens remain in its escrow; close the record once the retention period has passed and the escrow is empty."),
+        "DailyCapExceeded" => Some("Today's payments through the vault have reached its daily volume cap; retry after midnight UTC or ask the vault authority to raise it with set_daily_volume_cap."),
+        "RefundExceedsPayment" => Some("Refunds can return at most the payment's net amount, less what was already refunded; refund the remainder or less."),
+        "CommitmentMismatch" => Some("The vault verifies commitments on-chain: send the blinding factor the commitment was made with, so that sha256(amount as u64 little-endian || blinding_factor) equals it."),
+        "RetentionTooShort" => Some("Retention periods are at least 30 days; pass 30 or more to set_retention_days."),
+        "PaymentUnderDispute" => Some("The payer has disputed this payment; it can't be refunded or closed until the vault authority resolves the dispute with resolve_dispute."),
+        "NotDisputed" => Some("Only a disputed payment can be resolved; check the PaymentRecord's status."),
+        "EscrowNotHeld" => Some("The escrowed payment has already been captured or cancelled; check the EscrowRecord's status."),
+        "EscrowNotExpired" => Some("The payer can cancel an escrowed payment only once its expires_at has passed; until then the merchant may still capture it."),
+        "ScheduleNotDue" => Some("The schedule's next_due_at hasn't arrived; run the payroll once it has, or move the date with update_payroll_schedule."),
+        "ScheduleNotActive" => Some("The company paused this payroll schedule; set it Active again with update_payroll_schedule before running it."),
+        "InvalidSchedule" => Some("Pass a frequency_seconds and authorized_amount greater than zero."),
+        "ScheduleAmountExceeded" => Some("The batch's total_amount is over the schedule's authorized_amount; pay less or raise the limit with update_payroll_schedule."),
+        "MerchantNotRegistered" => Some("Have the vault authority call register_merchant for this merchant, or pay a registered merchant."),
+        "InvalidPayrollApprover" => Some("Name a second key as approver; the company already signs every payroll batch."),
+        "PayerBlacklisted" => Some("The vault authority has blacklisted this payer; payments resume once remove_from_blacklist is called."),
+        "NoPendingRecovery" => Some("Call propose_emergency_recovery as the recovery authority first, then wait out the 24-hour delay."),
+        "RecoveryTimelockActive" => Some("Retry emergency_recover once 24 hours have passed since propose_emergency_recovery."),
+        "InvalidUnlockTime" => Some("Pass an unlock_at later than the current cluster time to create_timelock_payment."),
+        "TimelockNotUnlocked" => Some("Retry claim_timelock_payment at or after the payment's unlock_at."),
+        "TimelockAlreadyClaimed" => Some("The beneficiary has already been paid; fund a new timelock payment under a fresh payment_id."),
+        "InvalidStream" => Some("Pass a rate_per_second above zero and a start_time no earlier than now, with end_time after it."),
+        "NothingStreamed" => Some("Wait for the stream to run past its start_time or last withdrawal before calling withdraw_from_stream again."),
+        "StreamEnded" => Some("Streams can only be topped up before their end_time; open a new stream instead."),
+        _ => None,
+    }
+}
+
+/// Look up a custom program error code
+pub fn lookup(code: u32) -> Option<ProgramError> {
+    let entry = |program, name, message, remediation| ProgramError {
+        code,
+        hex: format!("{:#x}", code),
+        program,
+        name,
+        message,
+        remediation,
+    };
+
+    if code >= VAULT_ERROR_OFFSET {
+        let (name, message) = VAULT_ERRORS.get((code - VAULT_ERROR_OFFSET) as usize)?;
+        let remediation = vault_remediation(name).unwrap_or("Contact support with the transaction signature.");
+        return Some(entry(ErrorSource::Vault, name, message, remediation));
+    }
+
+    let (table, program) = if code >= ANCHOR_ERROR_MIN {
+        (ANCHOR_ERRORS, ErrorSource::Anchor)
+    } else {
+        (TOKEN_ERRORS, ErrorSource::Token)
+    };
+    table
+        .iter()
+        .find(|(c, ..)| *c == code)
+        .map(|(_, name, message, remediation)| entry(program, name, message, remediation))
+}
+
+/// Parse a code given as decimal (`6001`) or hex (`0x1771`)
+pub fn parse_code(code: &str) -> Option<u32> {
+    match code.strip_prefix("0x").or_else(|| code.strip_prefix("0X")) {
+        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+        None => code.parse().ok(),
+    }
+}
+
+/// Decode the first `custom program error: 0x...` in an RPC error or log
+pub fn decode_message(message: &str) -> Option<ProgramError> {
+    const MARKER: &str = "custom program error: ";
+    let rest = &message[message.find(MARKER)? + MARKER.len()..];
+    let code: String = rest
+        .chars()
+        .take_while(|c| c.is_ascii_alphanumeric())
+        .collect();
+    lookup(parse_code(&code)?)
+}
+
+/// Attach the decoded error as `program_error` to a failed result whose
+/// `error` string carries a custom program error
+pub fn annotate_failure(result: &mut Option<serde_json::Value>) {
+    let Some(serde_json::Value::Object(fields)) = result else {
+        return;
+    };
+    let decoded = fields.get("error").and_then(|e| e.as_str()).and_then(decode_message);
+    if let Some(error) = decoded {
+        fields.insert("program_error".to_string(), serde_json::to_value(error).unwrap());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decodes_vault_errors_from_the_program_source() {
+        let error = decode_message("Transaction simulation failed: custom program error: 0x1771").unwrap();
+        assert_eq!(error.program, ErrorSource::Vault);
+        assert_eq!(error.name, "InvalidAmount");
+        assert_eq!(error.message, "Invalid payment amount");
+        assert_eq!(error.hex, "0x1771");
+
+        // Every generated variant needs its own remediation
+        for (i, (name, _)) in VAULT_ERRORS.iter().enumerate() {
+            assert!(vault_remediation(name).is_some(), "no remediation for {}", name);
+            assert_eq!(lookup(VAULT_ERROR_OFFSET + i as u32).unwrap().name, *name);
+        }
+        assert!(lookup(VAULT_ERROR_OFFSET + VAULT_ERRORS.len() as u32).is_none());
+    }
+
+    #[test]
+    fn test_decodes_framework_and_token_errors() {
+        assert_eq!(lookup(2006).unwrap().name, "ConstraintSeeds");
+        assert_eq!(lookup(1).unwrap().program, ErrorSource::Token);
+        assert_eq!(parse_code("0x7d6"), Some(2006));
+        assert_eq!(parse_code("2006"), Some(2006));
+        assert_eq!(parse_code("0xzz"), None);
+        assert!(decode_message("blockhash not found").is_none());
+    }
+
+    #[test]
+    fn test_annotates_failed_settlement_results() {
+        let mut result = Some(serde_json::json!({
+            "error": "Error processing Instruction 2: custom program error: 0x1",
+        }));
+        annotate_failure(&mut result);
+        let program_error = &result.unwrap()["program_error"];
+        assert_eq!(program_error["name"], "InsufficientFunds");
+        assert_eq!(program_error["program"], "token");
+
+        let mut result = Some(serde_json::json!({ "error": "blockhash not found" }));
+        annotate_failure(&mut result);
+        assert!(result.unwrap().get("program_error").is_none());
+    }
+}
@@ -0,0 +1,79 @@
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::sync::RwLock;
+
+use crate::store::unix_timestamp;
+
+/// Oldest entries are dropped beyond this many quarantined callbacks
+pub const MAX_QUARANTINED: usize = 1000;
+
+/// An authentic (correctly signed) cluster callback that failed schema checks
+#[derive(Debug, Clone, Serialize)]
+pub struct QuarantinedCallback {
+    pub id: String,
+    pub reason: String,
+    /// Raw request body as received
+    pub payload: String,
+    pub received_at: u64,
+}
+
+/// Holds malformed callbacks for inspection and replay
+#[derive(Default)]
+pub struct Quarantine {
+    entries: RwLock<VecDeque<QuarantinedCallback>>,
+}
+
+impl Quarantine {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&self, payload: &[u8], reason: String) -> QuarantinedCallback {
+        let entry = QuarantinedCallback {
+            id: format!("qcb_{}", hex::encode(rand::random::<[u8; 8]>())),
+            reason,
+            payload: String::from_utf8_lossy(payload).into_owned(),
+            received_at: unix_timestamp(),
+        };
+
+        let mut entries = self.entries.write().unwrap();
+        if entries.len() == MAX_QUARANTINED {
+            entries.pop_front();
+        }
+        entries.push_back(entry.clone());
+        entry
+    }
+
+    pub fn list(&self) -> Vec<QuarantinedCallback> {
+        self.entries.read().unwrap().iter().cloned().collect()
+    }
+
+    pub fn get(&self, id: &str) -> Option<QuarantinedCallback> {
+        self.entries.read().unwrap().iter().find(|e| e.id == id).cloned()
+    }
+
+    /// Remove an entry after a successful replay
+    pub fn remove(&self, id: &str) -> bool {
+        let mut entries = self.entries.write().unwrap();
+        let before = entries.len();
+        entries.retain(|e| e.id != id);
+        entries.len() != before
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_oldest_entries_are_dropped() {
+        let quarantine = Quarantine::new();
+        let first = quarantine.add(b"{}", "first".to_string());
+        for _ in 0..MAX_QUARANTINED {
+            quarantine.add(b"{}", "later".to_string());
+        }
+
+        assert_eq!(quarantine.list().len(), MAX_QUARANTINED);
+        assert!(quarantine.get(&first.id).is_none());
+    }
+}
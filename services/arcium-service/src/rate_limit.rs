@@ -0,0 +1,63 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use crate::error::ServiceError;
+use crate::store::unix_timestamp;
+
+/// Fixed-window request limiter keyed by client address
+pub struct RateLimiter {
+    max_requests: u32,
+    window_secs: u64,
+    windows: Mutex<HashMap<String, (u64, u32)>>,
+}
+
+impl RateLimiter {
+    pub fn new(max_requests: u32, window_secs: u64) -> Self {
+        Self {
+            max_requests,
+            window_secs,
+            windows: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn check(&self, key: &str) -> Result<(), ServiceError> {
+        self.check_at(key, unix_timestamp())
+    }
+
+    fn check_at(&self, key: &str, now: u64) -> Result<(), ServiceError> {
+        let window_start = now - now % self.window_secs;
+        let mut windows = self.windows.lock().unwrap();
+
+        // Drop stale windows so the map doesn't grow without bound
+        if windows.len() > 10_000 {
+            windows.retain(|_, (start, _)| *start == window_start);
+        }
+
+        let entry = windows.entry(key.to_string()).or_insert((window_start, 0));
+        if entry.0 != window_start {
+            *entry = (window_start, 0);
+        }
+        if entry.1 >= self.max_requests {
+            return Err(ServiceError::RateLimited(
+                window_start + self.window_secs - now,
+            ));
+        }
+        entry.1 += 1;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fixed_window() {
+        let limiter = RateLimiter::new(2, 60);
+        assert!(limiter.check_at("a", 120).is_ok());
+        assert!(limiter.check_at("a", 130).is_ok());
+        assert!(limiter.check_at("a", 140).is_err());
+        assert!(limiter.check_at("b", 140).is_ok());
+        assert!(limiter.check_at("a", 180).is_ok());
+    }
+}
@@ -0,0 +1,420 @@
+//! Reorg watcher: re-checking indexed payments until they finalize.
+//!
+//! Vault stats index PaymentRecords at the client's default commitment,
+//! usually `confirmed`, which a fork can still roll back. Each pass reads the
+//! records not yet seen at `finalized`. One that is there is done. One that
+//! is missing although the finalized slot has passed the slot it was
+//! indexed at is read again at `confirmed`: if it is back it re-landed and
+//! stays pending, otherwise the address's history tells a refund (the
+//! record was created and closed) from a reorg (nothing ever landed).
+//!
+//! Reorged payments are dropped from the stats, which rebuilds the affected
+//! rollups and surfaces locked periods as statement adjustments, and a
+//! `payment.reverted` event is POSTed to `REORG_CALLBACK_URL` by the leader.
+
+use serde::Serialize;
+use solana_sdk::pubkey::Pubkey;
+use std::collections::VecDeque;
+use std::sync::{Mutex, RwLock};
+use tracing::{info, warn};
+
+use crate::callback::{CallbackDispatcher, CallbackTemplate, CallbackVars, DeliveryLogEntry};
+use crate::config::Config;
+use crate::error::ServiceError;
+use crate::rpc::{ChainClient, ChainContext, Commitment};
+use crate::store::unix_timestamp;
+use crate::vault_stats::{ReorgedPayment, VaultStats};
+
+/// Reverted payments kept for redelivery; the oldest are dropped beyond this
+const MAX_UNDELIVERED: usize = 1_000;
+
+/// Delivery attempts kept for the admin API
+const MAX_DELIVERY_LOG: usize = 100;
+
+/// What one pass found
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub struct ReorgCheckReport {
+    pub checked: usize,
+    pub finalized: usize,
+    /// Not finalized yet, or re-landed after a fork
+    pub pending: usize,
+    /// Closed by refund_payment before the pass saw them finalize
+    pub refunded: usize,
+    pub reverted: usize,
+    pub finalized_slot: u64,
+    pub checked_at: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub struct ReorgState {
+    pub last_check: Option<ReorgCheckReport>,
+    pub reverted: Vec<ReorgedPayment>,
+    /// Reverted payments whose event has not been delivered yet
+    pub undelivered: usize,
+    pub deliveries: Vec<DeliveryLogEntry>,
+}
+
+/// `payment.reverted` event body
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
+struct RevertedEvent<'a> {
+    event: &'static str,
+    payment: &'a ReorgedPayment,
+}
+
+pub struct ReorgWatcher {
+    callback: Option<CallbackTemplate>,
+    undelivered: Mutex<VecDeque<ReorgedPayment>>,
+    deliveries: Mutex<VecDeque<DeliveryLogEntry>>,
+    last_check: RwLock<Option<ReorgCheckReport>>,
+}
+
+impl ReorgWatcher {
+    pub fn new(config: &Config) -> Result<Self, ServiceError> {
+        let callback = config
+            .reorg_callback_url
+            .as_deref()
+            .map(|url| CallbackTemplate::parse(url, &[]))
+            .transpose()
+            .map_err(|e| ServiceError::ConfigError(format!("REORG_CALLBACK_URL: {}", e)))?;
+        Ok(Self::with_callback(callback))
+    }
+
+    fn with_callback(callback: Option<CallbackTemplate>) -> Self {
+        Self {
+            callback,
+            undelivered: Mutex::new(VecDeque::new()),
+            deliveries: Mutex::new(VecDeque::new()),
+            last_check: RwLock::new(None),
+        }
+    }
+
+    /// Re-check every payment not yet seen at `finalized`, reverting the
+    /// ones a fork rolled back
+    pub async fn check(&self, stats: &VaultStats, chain: &ChainClient) -> Result<ReorgCheckReport, ServiceError> {
+        let pending = stats.unfinalized();
+        let mut report = ReorgCheckReport {
+            checked: pending.len(),
+            checked_at: unix_timestamp(),
+            ..Default::default()
+        };
+        if pending.is_empty() {
+            *self.last_check.write().unwrap() = Some(report.clone());
+            return Ok(report);
+        }
+
+        report.finalized_slot = chain.slot(Commitment::Finalized).await?;
+        let keys: Vec<Pubkey> = pending.iter().map(|(key, _)| *key).collect();
+        let mut context = ChainContext::new(Commitment::Finalized);
+        let accounts = chain.get_accounts(&keys, &mut context).await?;
+
+        let mut finalized = Vec::new();
+        let mut missing = Vec::new();
+        for ((key, slot), account) in pending.iter().zip(accounts) {
+            if account.is_some() {
+                finalized.push(*key);
+            } else if report.finalized_slot >= *slot {
+                missing.push(*key);
+            } else {
+                report.pending += 1;
+            }
+        }
+        stats.mark_finalized(&finalized);
+        report.finalized = finalized.len();
+        if missing.is_empty() {
+            *self.last_check.write().unwrap() = Some(report.clone());
+            return Ok(report);
+        }
+
+        let mut context = ChainContext::new(Commitment::Confirmed);
+        let confirmed = chain.get_accounts(&missing, &mut context).await?;
+        for (key, account) in missing.iter().zip(confirmed) {
+            if account.is_some() {
+                report.pending += 1;
+            } else if chain.has_history(key, Commitment::Confirmed).await? {
+                stats.mark_refunded(key);
+                report.refunded += 1;
+            } else if let Some(reverted) = stats.revert(key, report.checked_at) {
+                report.reverted += 1;
+                let mut undelivered = self.undelivered.lock().unwrap();
+                if self.callback.is_some() {
+                    if undelivered.len() >= MAX_UNDELIVERED {
+                        undelivered.pop_front();
+                    }
+                    undelivered.push_back(reverted);
+                }
+            }
+        }
+
+        if report.reverted > 0 {
+            warn!(
+                reverted = report.reverted,
+                finalized_slot = report.finalized_slot,
+                "Reverted payments that were rolled back before finalizing"
+            );
+        }
+        *self.last_check.write().unwrap() = Some(report.clone());
+        Ok(report)
+    }
+
+    /// POST `payment.reverted` for each undelivered reversal; failures are
+    /// kept for the next pass. Returns how many were delivered.
+    pub async fn notify(&self, dispatcher: &CallbackDispatcher) -> usize {
+        let Some(template) = &self.callback else {
+            return 0;
+        };
+        let queued: Vec<ReorgedPayment> = self.undelivered.lock().unwrap().drain(..).collect();
+        let mut delivered = 0;
+        let mut failed = Vec::new();
+        for payment in queued {
+            let vars = CallbackVars {
+                computation_id: &payment.record,
+                batch_id: None,
+                status: "reverted",
+            };
+            let event = RevertedEvent {
+                event: "payment.reverted",
+                payment: &payment,
+            };
+            let entry = dispatcher.deliver(template, None, &vars, &event).await;
+            if entry.status_code.is_some_and(|code| (200..300).contains(&code)) {
+                delivered += 1;
+            } else {
+                failed.push(payment);
+            }
+
+            let mut log = self.deliveries.lock().unwrap();
+            if log.len() >= MAX_DELIVERY_LOG {
+                log.pop_front();
+            }
+            log.push_back(entry);
+        }
+
+        if delivered > 0 {
+            info!(delivered, "Delivered payment.reverted events");
+        }
+        let mut undelivered = self.undelivered.lock().unwrap();
+        for payment in failed.into_iter().rev() {
+            undelivered.push_front(payment);
+        }
+        undelivered.truncate(MAX_UNDELIVERED);
+        delivered
+    }
+
+    pub fn state(&self, stats: &VaultStats) -> ReorgState {
+        ReorgState {
+            last_check: self.last_check.read().unwrap().clone(),
+            reverted: stats.reorged(),
+            undelivered: self.undelivered.lock().unwrap().len(),
+            deliveries: self.deliveries.lock().unwrap().iter().cloned().collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fee_audit::PaymentRecord;
+    use crate::rpc::RpcLimits;
+    use actix_web::{web, App, HttpResponse, HttpServer};
+    use serde_json::{json, Value};
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    /// Accounts and slot as seen at one commitment level
+    #[derive(Default)]
+    struct View {
+        slot: u64,
+        accounts: Vec<Pubkey>,
+    }
+
+    #[derive(Default)]
+    struct Chain {
+        views: HashMap<&'static str, View>,
+        /// Addresses with transactions on chain
+        history: Vec<Pubkey>,
+    }
+
+    /// A JSON-RPC node serving `Chain`, which the test rewrites to simulate a fork
+    fn mock_rpc(chain: Arc<Mutex<Chain>>) -> String {
+        let server = HttpServer::new(move || {
+            let chain = chain.clone();
+            App::new().route(
+                "/",
+                web::post().to(move |body: web::Json<Value>| {
+                    let chain = chain.clone();
+                    async move {
+                        let chain = chain.lock().unwrap();
+                        let params = &body["params"];
+                        let commitment = |config: &Value| match config["commitment"].as_str() {
+                            Some("finalized") => "finalized",
+                            _ => "confirmed",
+                        };
+                        let result = match body["method"].as_str().unwrap_or_default() {
+                            "getVersion" => json!({ "solana-core": "1.18.26", "feature-set": 0 }),
+                            "getSlot" => json!(chain.views[commitment(&params[0])].slot),
+                            "getMultipleAccounts" => {
+                                let view = &chain.views[commitment(&params[1])];
+                                let accounts: Vec<Value> = params[0]
+                                    .as_array()
+                                    .unwrap()
+                                    .iter()
+                                    .map(|key| {
+                                        let key: Pubkey = key.as_str().unwrap().parse().unwrap();
+                                        if view.accounts.contains(&key) {
+                                            json!({
+                                                "lamports": 1_000_000,
+                                                "data": ["", "base64"],
+                                                "owner": Pubkey::default().to_string(),
+                                                "executable": false,
+                                                "rentEpoch": 0,
+                                                "space": 0,
+                                            })
+                                        } else {
+                                            Value::Null
+                                        }
+                                    })
+                                    .collect();
+                                json!({ "context": { "slot": view.slot }, "value": accounts })
+                            }
+                            "getSignaturesForAddress" => {
+                                let key: Pubkey = params[0].as_str().unwrap().parse().unwrap();
+                                if chain.history.contains(&key) {
+                                    json!([{
+                                        "signature": "1111111111111111111111111111111111111111111111111111111111111111",
+                                        "slot": 90,
+                                        "err": null,
+                                        "memo": null,
+                                        "blockTime": null,
+                                    }])
+                                } else {
+                                    json!([])
+                                }
+                            }
+                            method => panic!("unexpected RPC method {}", method),
+                        };
+                        HttpResponse::Ok().json(json!({ "jsonrpc": "2.0", "id": body["id"], "result": result }))
+                    }
+                }),
+            )
+        })
+        .workers(1)
+        .disable_signals()
+        .bind("127.0.0.1:0")
+        .unwrap();
+        let url = format!("http://{}", server.addrs()[0]);
+        actix_web::rt::spawn(server.run());
+        url
+    }
+
+    /// A receiver recording each delivered body; answers 500 while `failing`
+    fn receiver(received: Arc<Mutex<Vec<Value>>>, failing: Arc<Mutex<bool>>) -> String {
+        let server = HttpServer::new(move || {
+            let (received, failing) = (received.clone(), failing.clone());
+            App::new().route(
+                "/reorgs",
+                web::post().to(move |body: web::Json<Value>| {
+                    let (received, failing) = (received.clone(), failing.clone());
+                    async move {
+                        if *failing.lock().unwrap() {
+                            return HttpResponse::InternalServerError().finish();
+                        }
+                        received.lock().unwrap().push(body.into_inner());
+                        HttpResponse::Ok().finish()
+                    }
+                }),
+            )
+        })
+        .workers(1)
+        .disable_signals()
+        .bind("127.0.0.1:0")
+        .unwrap();
+        let url = format!("http://{}/reorgs", server.addrs()[0]);
+        actix_web::rt::spawn(server.run());
+        url
+    }
+
+    fn record(amount: u64) -> (Pubkey, PaymentRecord) {
+        let record = PaymentRecord {
+            payment_id: [7; 32],
+            merchant: Pubkey::new_from_array([1; 32]),
+            amount,
+            fee: amount / 200,
+            timestamp: 1_759_968_000,
+            mint: None,
+            exact_net: false,
+        };
+        (Pubkey::new_unique(), record)
+    }
+
+    #[actix_rt::test]
+    async fn test_reorged_payment_is_reverted_and_announced() {
+        let (kept, reorged, refunded, relanded) =
+            (record(1_000_000), record(2_000_000), record(3_000_000), record(4_000_000));
+        let stats = VaultStats::for_program(Pubkey::new_unique());
+        stats.ingest(&[kept.clone(), reorged.clone(), refunded.clone(), relanded.clone()], 100);
+        stats.compact();
+
+        // Nothing is finalized yet: the finalized slot is behind the index
+        let chain_state = Arc::new(Mutex::new(Chain::default()));
+        {
+            let mut chain = chain_state.lock().unwrap();
+            chain.views.insert("finalized", View { slot: 90, accounts: vec![] });
+            chain.views.insert("confirmed", View { slot: 110, accounts: vec![kept.0, reorged.0, refunded.0, relanded.0] });
+        }
+        let limits = RpcLimits {
+            max_requests_per_second: 1000,
+            coalesce_window_ms: 0,
+            account_cache_ms: 0,
+        };
+        let chain = ChainClient::with_limits(mock_rpc(chain_state.clone()), Commitment::Confirmed, limits);
+        let (received, failing) = (Arc::new(Mutex::new(Vec::new())), Arc::new(Mutex::new(true)));
+        let template = CallbackTemplate::parse(&receiver(received.clone(), failing.clone()), &[]).unwrap();
+        let watcher = ReorgWatcher::with_callback(Some(template));
+
+        let report = watcher.check(&stats, &chain).await.unwrap();
+        assert_eq!((report.checked, report.finalized, report.pending, report.reverted), (4, 0, 4, 0));
+
+        // A fork drops two payments; one re-lands, the other's refund made it
+        // through; only `kept` finalized
+        {
+            let mut chain = chain_state.lock().unwrap();
+            chain.views.insert("finalized", View { slot: 140, accounts: vec![kept.0] });
+            chain.views.insert("confirmed", View { slot: 150, accounts: vec![kept.0, relanded.0] });
+            chain.history = vec![kept.0, refunded.0, relanded.0];
+        }
+        let report = watcher.check(&stats, &chain).await.unwrap();
+        assert_eq!(
+            (report.finalized, report.pending, report.refunded, report.reverted, report.finalized_slot),
+            (1, 1, 1, 1, 140)
+        );
+        assert_eq!(stats.unfinalized(), [(relanded.0, 100)]);
+
+        let snapshot = stats.snapshot(None);
+        assert_eq!(snapshot.totals.payments, 2);
+        assert_eq!(snapshot.totals.volume, 5_000_000);
+        let state = watcher.state(&stats);
+        assert_eq!(state.reverted.len(), 1);
+        assert_eq!(state.reverted[0].record, reorged.0.to_string());
+        assert_eq!(state.reverted[0].payment_id, hex::encode([7; 32]));
+
+        // Delivery failures are retried on the next pass
+        let dispatcher = crate::callback::tests::dispatcher();
+        assert_eq!(watcher.notify(&dispatcher).await, 0);
+        assert_eq!(watcher.state(&stats).undelivered, 1);
+        *failing.lock().unwrap() = false;
+        assert_eq!(watcher.notify(&dispatcher).await, 1);
+        assert_eq!(watcher.notify(&dispatcher).await, 0);
+
+        let received = received.lock().unwrap();
+        assert_eq!(received.len(), 1);
+        assert_eq!(received[0]["event"], "payment.reverted");
+        assert_eq!(received[0]["payment"]["record"], reorged.0.to_string());
+        assert_eq!(received[0]["payment"]["amount"], 2_000_000);
+        let state = watcher.state(&stats);
+        assert_eq!((state.undelivered, state.deliveries.len()), (0, 2));
+        assert_eq!(state.deliveries[1].status_code, Some(200));
+    }
+}
@@ -7,14 +7,81 @@ pub fn configure(cfg: &mut web::ServiceConfig) {
         web::scope("/api")
             // Health check
             .route("/health", web::get().to(handlers::health_check))
-            // Encryption endpoints
-            .route("/v1/encrypt", web::post().to(handlers::encrypt_amount))
-            .route("/v1/decrypt", web::post().to(handlers::decrypt_amount))
-            // MPC computation endpoints
-            .route("/v1/computations/payment", web::post().to(handlers::queue_payment_settlement))
-            .route("/v1/computations/payroll", web::post().to(handlers::queue_payroll_settlement))
-            .route("/v1/computations/{id}", web::get().to(handlers::get_computation_status))
-            // Commitment verification
-            .route("/v1/verify-commitment", web::post().to(handlers::verify_commitment)),
+            // Readiness (waits for warmup)
+            .route("/ready", web::get().to(handlers::readiness))
+            // Public status page (separately rate limited, cacheable)
+            .route("/status", web::get().to(handlers::public_status))
+            // Cluster result notifications (HMAC-authenticated)
+            .route("/callbacks/arcium", web::post().to(handlers::receive_cluster_callback))
+            // v1 keeps numeric u64 money fields; v2 encodes them as strings
+            .service(
+                web::scope("/v1")
+                    .configure(configure_versioned)
+                    .configure(configure_admin),
+            )
+            .service(web::scope("/v2").configure(configure_versioned)),
+    );
+}
+
+fn configure_versioned(cfg: &mut web::ServiceConfig) {
+    cfg
+        // Encryption endpoints
+        .route("/encrypt", web::post().to(handlers::encrypt_amount))
+        .route("/decrypt", web::post().to(handlers::decrypt_amount))
+        // MPC computation endpoints
+        .route("/computations/payment", web::post().to(handlers::queue_payment_settlement))
+        .route("/computations/payroll", web::post().to(handlers::queue_payroll_settlement))
+        // Streaming NDJSON upload for large payrolls, split into cluster-sized chunks
+        .route("/computations/payroll/ndjson", web::post().to(handlers::queue_payroll_ndjson))
+        .route("/computations", web::get().to(handlers::list_computations))
+        .route("/computations/{id}", web::get().to(handlers::get_computation_status))
+        // Lifecycle events for support, with the time between steps
+        .route("/computations/{id}/timeline", web::get().to(handlers::computation_timeline))
+        // Commitment verification
+        .route("/verify-commitment", web::post().to(handlers::verify_commitment))
+        .route("/verify-commitments", web::post().to(handlers::verify_commitments))
+        // Error code catalog
+        .route("/errors", web::get().to(handlers::error_catalog))
+        // Decode `custom program error: 0x...` from failed transactions
+        .route("/errors/program/{code}", web::get().to(handlers::program_error))
+        // Locale-aware amount display
+        .route("/format", web::get().to(handlers::format_amount))
+        // Daily payment totals from indexed PaymentRecords
+        .route("/vault/stats", web::get().to(handlers::vault_stats))
+        .route("/vault/stats/merchants/{merchant}", web::get().to(handlers::merchant_vault_stats))
+        // Monthly statements, locked once issued (issuing is admin-only)
+        .route("/merchants/{merchant}/statements", web::post().to(handlers::issue_statement))
+        .route("/merchants/{merchant}/statements", web::get().to(handlers::list_statements))
+        .route("/merchants/{merchant}/statements/{id}", web::get().to(handlers::get_statement))
+        // Fee settings over time, from indexed FeeUpdated events
+        .route("/fees/history", web::get().to(handlers::fee_history))
+        .route("/fees/at", web::get().to(handlers::fee_at));
+}
+
+fn configure_admin(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/admin")
+            .route("/incidents", web::get().to(handlers::list_incidents))
+            .route("/incidents", web::post().to(handlers::open_incident))
+            .route("/incidents/{id}", web::delete().to(handlers::resolve_incident))
+            .route("/quarantine", web::get().to(handlers::list_quarantine))
+            .route("/quarantine/{id}/replay", web::post().to(handlers::replay_quarantined))
+            .route("/endpoints/{name}/disable", web::post().to(handlers::disable_endpoint))
+            .route("/endpoints/{name}/enable", web::post().to(handlers::enable_endpoint))
+            .route("/strict-mode", web::get().to(handlers::strict_mode_state))
+            .route("/strict-mode/{key_id}", web::put().to(handlers::set_strict_mode))
+            .route("/strict-mode/{key_id}", web::delete().to(handlers::clear_strict_mode))
+            .route("/client-identities", web::get().to(handlers::list_client_identities))
+            .route("/client-identities", web::post().to(handlers::create_client_identity))
+            .route("/client-identities/{id}", web::delete().to(handlers::delete_client_identity))
+            .route("/warmup", web::post().to(handlers::run_warmup))
+            .route("/computations/compress-payloads", web::post().to(handlers::compress_stored_payloads))
+            .route("/fee-audit", web::get().to(handlers::fee_audit_report))
+            .route("/rpc-stats", web::get().to(handlers::rpc_stats))
+            .route("/fee-history/backfill", web::post().to(handlers::backfill_fee_history))
+            .route("/vault-stats/backfill", web::post().to(handlers::backfill_vault_stats))
+            .route("/vault-stats/check/{day}", web::get().to(handlers::check_vault_stats))
+            .route("/reorgs", web::get().to(handlers::reorg_state))
+            .route("/sla-breaches", web::get().to(handlers::sla_breaches)),
     );
 }
@@ -0,0 +1,650 @@
+//! Chain reads over Solana RPC.
+//!
+//! Account reads are batched into `getMultipleAccounts` calls of up to
+//! `MAX_MULTIPLE_ACCOUNTS` keys. Reads issued within `coalesce_window_ms` of
+//! each other share a call, and a key already queued or in flight is not
+//! fetched twice. Fetched accounts are kept for `account_cache_ms`. Every
+//! RPC call, of any method, draws from one requests-per-second budget so
+//! background jobs and request handlers together stay inside the
+//! provider's limit.
+
+use serde::{Deserialize, Serialize};
+use solana_account_decoder::UiAccountEncoding;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_client::rpc_client::GetConfirmedSignaturesForAddress2Config;
+use solana_client::rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig, RpcTransactionConfig};
+use solana_client::rpc_filter::{Memcmp, RpcFilterType};
+use solana_sdk::signature::Signature;
+use solana_sdk::{account::Account, commitment_config::CommitmentConfig, pubkey::Pubkey};
+use solana_transaction_status::UiTransactionEncoding;
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::future::Future;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::oneshot;
+
+use crate::config::Config;
+use crate::error::ServiceError;
+
+/// Maximum number of keys accepted by a single `getMultipleAccounts` call
+pub const MAX_MULTIPLE_ACCOUNTS: usize = 100;
+
+/// Maximum number of signatures returned by one `getSignaturesForAddress` call
+pub const MAX_SIGNATURES_PER_PAGE: usize = 1000;
+
+/// Accounts kept in the read cache before expired entries are swept
+const MAX_CACHED_ACCOUNTS: usize = 10_000;
+
+/// How chain reads use the RPC provider
+#[derive(Debug, Clone, Copy)]
+pub struct RpcLimits {
+    /// RPC requests per second across every caller in the process
+    pub max_requests_per_second: u32,
+    /// How long an account read waits for others to share its call
+    pub coalesce_window_ms: u64,
+    /// How long fetched accounts are served from memory (0 disables)
+    pub account_cache_ms: u64,
+}
+
+/// Solana commitment level requested for a chain read
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Commitment {
+    Processed,
+    Confirmed,
+    Finalized,
+}
+
+impl Commitment {
+    fn config(self) -> CommitmentConfig {
+        match self {
+            Commitment::Processed => CommitmentConfig::processed(),
+            Commitment::Confirmed => CommitmentConfig::confirmed(),
+            Commitment::Finalized => CommitmentConfig::finalized(),
+        }
+    }
+
+    /// `getTransaction` does not accept `processed`
+    fn at_least_confirmed(self) -> Self {
+        match self {
+            Commitment::Processed => Commitment::Confirmed,
+            other => other,
+        }
+    }
+}
+
+impl FromStr for Commitment {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "processed" => Ok(Commitment::Processed),
+            "confirmed" => Ok(Commitment::Confirmed),
+            "finalized" => Ok(Commitment::Finalized),
+            other => Err(format!(
+                "unknown commitment '{}' (expected processed, confirmed or finalized)",
+                other
+            )),
+        }
+    }
+}
+
+/// What a chain read actually observed, returned alongside its result
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub struct ChainContext {
+    pub commitment: Commitment,
+    /// Lowest slot across the RPC calls that made up the read
+    pub slot: u64,
+}
+
+impl ChainContext {
+    pub fn new(commitment: Commitment) -> Self {
+        Self { commitment, slot: 0 }
+    }
+
+    fn observe(&mut self, slot: u64) {
+        self.slot = if self.slot == 0 { slot } else { self.slot.min(slot) };
+    }
+}
+
+/// A transaction that touched an address, from `getSignaturesForAddress`
+#[derive(Debug, Clone, PartialEq)]
+pub struct SignatureInfo {
+    pub signature: Signature,
+    pub slot: u64,
+    pub block_time: Option<i64>,
+    pub failed: bool,
+}
+
+/// RPC usage since startup
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub struct RpcStats {
+    pub get_multiple_accounts_calls: u64,
+    pub get_program_accounts_calls: u64,
+    pub get_signatures_for_address_calls: u64,
+    pub get_transaction_calls: u64,
+    pub get_slot_calls: u64,
+    pub failed_calls: u64,
+    /// Time spent waiting for the requests-per-second budget
+    pub budget_wait_ms: u64,
+    /// Keys asked for through `get_accounts`
+    pub accounts_requested: u64,
+    pub accounts_from_cache: u64,
+    /// Keys that joined a read already queued or in flight
+    pub accounts_coalesced: u64,
+    pub accounts_fetched: u64,
+    /// Keys per `getMultipleAccounts` call
+    pub average_batch_size: f64,
+    /// Calls a `getAccountInfo` per requested key would have made, less
+    /// the `getMultipleAccounts` calls actually made
+    pub calls_saved: u64,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum RpcMethod {
+    MultipleAccounts,
+    ProgramAccounts,
+    SignaturesForAddress,
+    Transaction,
+    Slot,
+}
+
+/// Spaces RPC calls out to the configured requests per second
+struct RpcBudget {
+    interval: Duration,
+    next_at: Mutex<Instant>,
+}
+
+impl RpcBudget {
+    fn new(max_requests_per_second: u32) -> Self {
+        Self {
+            interval: Duration::from_secs(1) / max_requests_per_second.max(1),
+            next_at: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Wait for the next free slot, returning how long that took
+    async fn acquire(&self) -> Duration {
+        let wait = {
+            let mut next_at = self.next_at.lock().unwrap();
+            let now = Instant::now();
+            let at = (*next_at).max(now);
+            *next_at = at + self.interval;
+            at - now
+        };
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+        }
+        wait
+    }
+}
+
+/// An account and the slot it was read at, or why the read failed
+type AccountRead = Result<(Option<Account>, u64), String>;
+
+#[derive(Default)]
+struct Coalescer {
+    /// Keys waiting for the next batch, per commitment
+    queued: HashMap<Commitment, Vec<Pubkey>>,
+    /// Callers waiting on each key that is queued or being fetched
+    waiters: HashMap<(Pubkey, Commitment), Vec<oneshot::Sender<AccountRead>>>,
+}
+
+struct CachedAccount {
+    account: Option<Account>,
+    slot: u64,
+    expires_at: Instant,
+}
+
+struct Inner {
+    rpc: RpcClient,
+    limits: RpcLimits,
+    budget: RpcBudget,
+    coalescer: Mutex<Coalescer>,
+    cache: Mutex<HashMap<(Pubkey, Commitment), CachedAccount>>,
+    stats: Mutex<RpcStats>,
+}
+
+impl Inner {
+    /// Run one RPC call inside the budget
+    async fn call<T, E>(&self, method: RpcMethod, request: impl Future<Output = Result<T, E>>) -> Result<T, E> {
+        let waited = self.budget.acquire().await;
+        let result = request.await;
+
+        let mut stats = self.stats.lock().unwrap();
+        let calls = match method {
+            RpcMethod::MultipleAccounts => &mut stats.get_multiple_accounts_calls,
+            RpcMethod::ProgramAccounts => &mut stats.get_program_accounts_calls,
+            RpcMethod::SignaturesForAddress => &mut stats.get_signatures_for_address_calls,
+            RpcMethod::Transaction => &mut stats.get_transaction_calls,
+            RpcMethod::Slot => &mut stats.get_slot_calls,
+        };
+        *calls += 1;
+        if result.is_err() {
+            stats.failed_calls += 1;
+        }
+        stats.budget_wait_ms += waited.as_millis() as u64;
+        result
+    }
+
+    /// After the coalescing window, fetch every key queued for `commitment`
+    /// and hand each result to the callers waiting on it
+    async fn flush(self: Arc<Self>, commitment: Commitment) {
+        tokio::time::sleep(Duration::from_millis(self.limits.coalesce_window_ms)).await;
+        let keys = self
+            .coalescer
+            .lock()
+            .unwrap()
+            .queued
+            .remove(&commitment)
+            .unwrap_or_default();
+
+        for chunk in keys.chunks(MAX_MULTIPLE_ACCOUNTS) {
+            let result = self
+                .call(
+                    RpcMethod::MultipleAccounts,
+                    self.rpc.get_multiple_accounts_with_commitment(chunk, commitment.config()),
+                )
+                .await
+                .map(|batch| (batch.context.slot, batch.value))
+                .map_err(|e| format!("getMultipleAccounts failed: {}", e));
+            if let Ok((slot, accounts)) = &result {
+                self.stats.lock().unwrap().accounts_fetched += chunk.len() as u64;
+                self.cache_accounts(chunk, accounts, *slot, commitment);
+            }
+
+            let mut coalescer = self.coalescer.lock().unwrap();
+            for (i, key) in chunk.iter().enumerate() {
+                let read = match &result {
+                    Ok((slot, accounts)) => Ok((accounts.get(i).cloned().flatten(), *slot)),
+                    Err(e) => Err(e.clone()),
+                };
+                for waiter in coalescer.waiters.remove(&(*key, commitment)).unwrap_or_default() {
+                    let _ = waiter.send(read.clone());
+                }
+            }
+        }
+    }
+
+    fn cache_accounts(&self, keys: &[Pubkey], accounts: &[Option<Account>], slot: u64, commitment: Commitment) {
+        if self.limits.account_cache_ms == 0 {
+            return;
+        }
+        let now = Instant::now();
+        let expires_at = now + Duration::from_millis(self.limits.account_cache_ms);
+        let mut cache = self.cache.lock().unwrap();
+        if cache.len() + keys.len() > MAX_CACHED_ACCOUNTS {
+            cache.retain(|_, cached| cached.expires_at > now);
+            if cache.len() + keys.len() > MAX_CACHED_ACCOUNTS {
+                cache.clear();
+            }
+        }
+        for (key, account) in keys.iter().zip(accounts) {
+            cache.insert(
+                (*key, commitment),
+                CachedAccount {
+                    account: account.clone(),
+                    slot,
+                    expires_at,
+                },
+            );
+        }
+    }
+}
+
+/// Wrapper over the Solana RPC client used for chain reads
+pub struct ChainClient {
+    inner: Arc<Inner>,
+    default_commitment: Commitment,
+}
+
+impl ChainClient {
+    pub fn new(config: &Config) -> Self {
+        Self::with_limits(config.solana_rpc_url.clone(), config.solana_default_commitment, config.rpc_limits)
+    }
+
+    pub(crate) fn with_limits(url: String, default_commitment: Commitment, limits: RpcLimits) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                rpc: RpcClient::new(url),
+                limits,
+                budget: RpcBudget::new(limits.max_requests_per_second),
+                coalescer: Mutex::new(Coalescer::default()),
+                cache: Mutex::new(HashMap::new()),
+                stats: Mutex::new(RpcStats::default()),
+            }),
+            default_commitment,
+        }
+    }
+
+    /// Commitment used when the caller does not request one
+    pub fn default_commitment(&self) -> Commitment {
+        self.default_commitment
+    }
+
+    /// RPC usage since startup
+    pub fn stats(&self) -> RpcStats {
+        let mut stats = self.inner.stats.lock().unwrap().clone();
+        if stats.get_multiple_accounts_calls > 0 {
+            stats.average_batch_size = stats.accounts_fetched as f64 / stats.get_multiple_accounts_calls as f64;
+        }
+        stats.calls_saved = stats.accounts_requested.saturating_sub(stats.get_multiple_accounts_calls);
+        stats
+    }
+
+    /// Fetch accounts, preserving order: from the cache where fresh, the
+    /// rest in shared `getMultipleAccounts` batches
+    pub async fn get_accounts(
+        &self,
+        keys: &[Pubkey],
+        context: &mut ChainContext,
+    ) -> Result<Vec<Option<Account>>, ServiceError> {
+        let commitment = context.commitment;
+        let mut accounts = vec![None; keys.len()];
+        let mut pending = Vec::new();
+        let (mut from_cache, mut coalesced) = (0, 0);
+        let mut start_batch = false;
+        {
+            let now = Instant::now();
+            let cache = self.inner.cache.lock().unwrap();
+            let mut guard = self.inner.coalescer.lock().unwrap();
+            let coalescer = &mut *guard;
+            for (i, key) in keys.iter().enumerate() {
+                if let Some(cached) = cache.get(&(*key, commitment)).filter(|c| c.expires_at > now) {
+                    accounts[i] = cached.account.clone();
+                    context.observe(cached.slot);
+                    from_cache += 1;
+                    continue;
+                }
+
+                let (sender, receiver) = oneshot::channel();
+                match coalescer.waiters.entry((*key, commitment)) {
+                    Entry::Occupied(mut waiting) => {
+                        waiting.get_mut().push(sender);
+                        coalesced += 1;
+                    }
+                    Entry::Vacant(slot) => {
+                        slot.insert(vec![sender]);
+                        let queue = coalescer.queued.entry(commitment).or_default();
+                        start_batch |= queue.is_empty();
+                        queue.push(*key);
+                    }
+                }
+                pending.push((i, receiver));
+            }
+        }
+        {
+            let mut stats = self.inner.stats.lock().unwrap();
+            stats.accounts_requested += keys.len() as u64;
+            stats.accounts_from_cache += from_cache;
+            stats.accounts_coalesced += coalesced;
+        }
+
+        // The batch outlives this call, so a caller that gives up doesn't
+        // fail the others waiting on it
+        if start_batch {
+            tokio::spawn(self.inner.clone().flush(commitment));
+        }
+
+        for (i, receiver) in pending {
+            let (account, slot) = receiver
+                .await
+                .map_err(|_| ServiceError::RpcError("Account read was abandoned".to_string()))?
+                .map_err(ServiceError::RpcError)?;
+            accounts[i] = account;
+            context.observe(slot);
+        }
+        Ok(accounts)
+    }
+
+    /// Every account of `program_id` with the given size and discriminator
+    pub async fn get_program_accounts(
+        &self,
+        program_id: &Pubkey,
+        data_size: usize,
+        discriminator: &[u8],
+        commitment: Commitment,
+    ) -> Result<Vec<(Pubkey, Account)>, ServiceError> {
+        let config = RpcProgramAccountsConfig {
+            filters: Some(vec![
+                RpcFilterType::DataSize(data_size as u64),
+                RpcFilterType::Memcmp(Memcmp::new_raw_bytes(0, discriminator.to_vec())),
+            ]),
+            account_config: RpcAccountInfoConfig {
+                encoding: Some(UiAccountEncoding::Base64),
+                commitment: Some(commitment.config()),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        self.inner
+            .call(
+                RpcMethod::ProgramAccounts,
+                self.inner.rpc.get_program_accounts_with_config(program_id, config),
+            )
+            .await
+            .map_err(|e| ServiceError::RpcError(format!("getProgramAccounts failed: {}", e)))
+    }
+
+    /// The node's latest slot at `commitment`
+    pub async fn slot(&self, commitment: Commitment) -> Result<u64, ServiceError> {
+        self.inner
+            .call(RpcMethod::Slot, self.inner.rpc.get_slot_with_commitment(commitment.config()))
+            .await
+            .map_err(|e| ServiceError::RpcError(format!("getSlot failed: {}", e)))
+    }
+
+    /// Whether the address has ever appeared in a transaction
+    pub async fn has_history(&self, key: &Pubkey, commitment: Commitment) -> Result<bool, ServiceError> {
+        let config = GetConfirmedSignaturesForAddress2Config {
+            limit: Some(1),
+            commitment: Some(commitment.config()),
+            ..Default::default()
+        };
+        let signatures = self
+            .inner
+            .call(
+                RpcMethod::SignaturesForAddress,
+                self.inner.rpc.get_signatures_for_address_with_config(key, config),
+            )
+            .await
+            .map_err(|e| ServiceError::RpcError(format!("getSignaturesForAddress failed: {}", e)))?;
+        Ok(!signatures.is_empty())
+    }
+
+    /// One page of transactions that touched `key`, newest first: those
+    /// older than `before` and newer than `until`
+    pub async fn signatures_for_address(
+        &self,
+        key: &Pubkey,
+        before: Option<Signature>,
+        until: Option<Signature>,
+        commitment: Commitment,
+    ) -> Result<Vec<SignatureInfo>, ServiceError> {
+        let config = GetConfirmedSignaturesForAddress2Config {
+            before,
+            until,
+            limit: Some(MAX_SIGNATURES_PER_PAGE),
+            commitment: Some(commitment.at_least_confirmed().config()),
+        };
+        let statuses = self
+            .inner
+            .call(
+                RpcMethod::SignaturesForAddress,
+                self.inner.rpc.get_signatures_for_address_with_config(key, config),
+            )
+            .await
+            .map_err(|e| ServiceError::RpcError(format!("getSignaturesForAddress failed: {}", e)))?;
+        statuses
+            .into_iter()
+            .map(|status| {
+                let signature = Signature::from_str(&status.signature).map_err(|_| {
+                    ServiceError::RpcError(format!("RPC returned an invalid signature: {}", status.signature))
+                })?;
+                Ok(SignatureInfo {
+                    signature,
+                    slot: status.slot,
+                    block_time: status.block_time,
+                    failed: status.err.is_some(),
+                })
+            })
+            .collect()
+    }
+
+    /// Block time and log messages of a transaction, if the node has it
+    pub async fn transaction_logs(
+        &self,
+        signature: &Signature,
+        commitment: Commitment,
+    ) -> Result<(Option<i64>, Vec<String>), ServiceError> {
+        let config = RpcTransactionConfig {
+            encoding: Some(UiTransactionEncoding::Base64),
+            commitment: Some(commitment.at_least_confirmed().config()),
+            max_supported_transaction_version: Some(0),
+        };
+        let transaction = self
+            .inner
+            .call(
+                RpcMethod::Transaction,
+                self.inner.rpc.get_transaction_with_config(signature, config),
+            )
+            .await
+            .map_err(|e| ServiceError::RpcError(format!("getTransaction failed: {}", e)))?;
+        let logs = transaction
+            .transaction
+            .meta
+            .and_then(|meta| Option::from(meta.log_messages))
+            .unwrap_or_default();
+        Ok((transaction.block_time, logs))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_context_keeps_lowest_slot() {
+        let mut context = ChainContext::new(Commitment::Confirmed);
+        context.observe(120);
+        context.observe(118);
+        context.observe(121);
+        assert_eq!(context.slot, 118);
+    }
+
+    #[test]
+    fn test_parse_commitment() {
+        assert_eq!("finalized".parse::<Commitment>(), Ok(Commitment::Finalized));
+        assert!("max".parse::<Commitment>().is_err());
+    }
+
+    /// A JSON-RPC node answering getMultipleAccounts with every account
+    /// missing at slot 100; records the number of keys in each call
+    fn mock_rpc() -> (String, Arc<Mutex<Vec<usize>>>) {
+        use actix_web::{web, App, HttpResponse, HttpServer};
+        use serde_json::{json, Value};
+
+        let batches = Arc::new(Mutex::new(Vec::new()));
+        let recorded = batches.clone();
+        let server = HttpServer::new(move || {
+            let recorded = recorded.clone();
+            App::new().route(
+                "/",
+                web::post().to(move |body: web::Json<Value>| {
+                    let recorded = recorded.clone();
+                    async move {
+                        // The client checks the node version before some calls
+                        if body["method"] == "getVersion" {
+                            return HttpResponse::Ok().json(json!({
+                                "jsonrpc": "2.0",
+                                "id": body["id"],
+                                "result": { "solana-core": "1.18.26", "feature-set": 0 },
+                            }));
+                        }
+                        let keys = body["params"][0].as_array().map_or(0, Vec::len);
+                        recorded.lock().unwrap().push(keys);
+                        HttpResponse::Ok().json(json!({
+                            "jsonrpc": "2.0",
+                            "id": body["id"],
+                            "result": { "context": { "slot": 100 }, "value": vec![Value::Null; keys] },
+                        }))
+                    }
+                }),
+            )
+        })
+        .workers(1)
+        .disable_signals()
+        .bind("127.0.0.1:0")
+        .unwrap();
+        let url = format!("http://{}", server.addrs()[0]);
+        actix_web::rt::spawn(server.run());
+        (url, batches)
+    }
+
+    fn client(url: String, coalesce_window_ms: u64, account_cache_ms: u64) -> ChainClient {
+        let limits = RpcLimits {
+            max_requests_per_second: 1000,
+            coalesce_window_ms,
+            account_cache_ms,
+        };
+        ChainClient::with_limits(url, Commitment::Confirmed, limits)
+    }
+
+    #[actix_rt::test]
+    async fn test_concurrent_reads_share_batches() {
+        let (url, batches) = mock_rpc();
+        let chain = client(url, 20, 0);
+        let keys: Vec<Pubkey> = (0..150).map(|_| Pubkey::new_unique()).collect();
+
+        let mut first = ChainContext::new(Commitment::Confirmed);
+        let mut second = ChainContext::new(Commitment::Confirmed);
+        let (a, b) = futures_util::join!(
+            chain.get_accounts(&keys[..120], &mut first),
+            chain.get_accounts(&keys[30..], &mut second)
+        );
+        assert_eq!(a.unwrap(), vec![None; 120]);
+        assert_eq!(b.unwrap(), vec![None; 120]);
+        assert_eq!(first.slot, 100);
+
+        // 150 distinct keys in two calls, not 240 single reads
+        assert_eq!(*batches.lock().unwrap(), vec![100, 50]);
+        let stats = chain.stats();
+        assert_eq!(stats.accounts_requested, 240);
+        assert_eq!(stats.accounts_coalesced, 90);
+        assert_eq!(stats.accounts_fetched, 150);
+        assert_eq!(stats.average_batch_size, 75.0);
+        assert_eq!(stats.calls_saved, 238);
+    }
+
+    #[actix_rt::test]
+    async fn test_cached_accounts_skip_rpc() {
+        let (url, batches) = mock_rpc();
+        let chain = client(url, 0, 60_000);
+        let keys: Vec<Pubkey> = (0..3).map(|_| Pubkey::new_unique()).collect();
+
+        let mut context = ChainContext::new(Commitment::Confirmed);
+        chain.get_accounts(&keys, &mut context).await.unwrap();
+        chain.get_accounts(&keys, &mut context).await.unwrap();
+        // Cached per commitment
+        let mut finalized = ChainContext::new(Commitment::Finalized);
+        chain.get_accounts(&keys, &mut finalized).await.unwrap();
+
+        assert_eq!(batches.lock().unwrap().len(), 2);
+        assert_eq!(chain.stats().accounts_from_cache, 3);
+        assert_eq!(context.slot, 100);
+    }
+
+    #[actix_rt::test]
+    async fn test_budget_spaces_calls() {
+        let budget = RpcBudget::new(50);
+        let start = Instant::now();
+        for _ in 0..3 {
+            budget.acquire().await;
+        }
+        assert!(start.elapsed() >= Duration::from_millis(40));
+    }
+}
@@ -0,0 +1,175 @@
+//! Deploy-safe shutdown. On SIGTERM the readiness probe flips to 503 at
+//! once, but requests keep being served for a drain window while the load
+//! balancer notices; only then does actix's graceful stop begin.
+//!
+//! Every request is counted while in flight so the drain can be logged:
+//! how many requests were served after the signal, and how many were
+//! dropped unanswered (cut off by the graceful stop timeout).
+
+use actix_web::dev::ServerHandle;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{info, warn};
+
+/// Counts collected since the drain started
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DrainReport {
+    pub served: u64,
+    pub rejected: u64,
+    pub in_flight: u64,
+}
+
+/// Shutdown state shared by the readiness probe and the request counter
+pub struct Shutdown {
+    drain_window: Duration,
+    draining: AtomicBool,
+    in_flight: AtomicU64,
+    served_during_drain: AtomicU64,
+    rejected_during_drain: AtomicU64,
+}
+
+impl Shutdown {
+    pub fn new(drain_window: Duration) -> Self {
+        Self {
+            drain_window,
+            draining: AtomicBool::new(false),
+            in_flight: AtomicU64::new(0),
+            served_during_drain: AtomicU64::new(0),
+            rejected_during_drain: AtomicU64::new(0),
+        }
+    }
+
+    pub fn is_draining(&self) -> bool {
+        self.draining.load(Ordering::Acquire)
+    }
+
+    pub fn in_flight(&self) -> u64 {
+        self.in_flight.load(Ordering::Relaxed)
+    }
+
+    pub fn report(&self) -> DrainReport {
+        DrainReport {
+            served: self.served_during_drain.load(Ordering::Relaxed),
+            rejected: self.rejected_during_drain.load(Ordering::Relaxed),
+            in_flight: self.in_flight(),
+        }
+    }
+
+    /// Count a request until the returned guard is finished or dropped
+    pub fn track(self: &Arc<Self>) -> InFlight {
+        self.in_flight.fetch_add(1, Ordering::Relaxed);
+        InFlight {
+            shutdown: self.clone(),
+            finished: false,
+        }
+    }
+
+    /// Fail readiness, keep serving for the drain window, then stop the
+    /// server gracefully
+    pub async fn drain(&self, server: ServerHandle) {
+        self.draining.store(true, Ordering::Release);
+        info!(
+            "Shutdown requested; draining for {}s with {} requests in flight",
+            self.drain_window.as_secs(),
+            self.in_flight()
+        );
+
+        actix_web::rt::time::sleep(self.drain_window).await;
+
+        info!(
+            "Drain window over; stopping with {} requests in flight ({} served during drain)",
+            self.in_flight(),
+            self.report().served
+        );
+        server.stop(true).await;
+    }
+
+    /// Log the drain outcome once the server has stopped
+    pub fn log_report(&self) {
+        let report = self.report();
+        if report.rejected > 0 {
+            warn!(
+                "Shutdown complete: {} requests served during drain, {} dropped unanswered",
+                report.served, report.rejected
+            );
+        } else {
+            info!("Shutdown complete: {} requests served during drain", report.served);
+        }
+    }
+}
+
+/// A request being served; see [`Shutdown::track`]
+pub struct InFlight {
+    shutdown: Arc<Shutdown>,
+    finished: bool,
+}
+
+impl InFlight {
+    /// The request produced a response
+    pub fn finish(mut self) {
+        self.finished = true;
+    }
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.shutdown.in_flight.fetch_sub(1, Ordering::Relaxed);
+        if self.shutdown.is_draining() {
+            let counter = if self.finished {
+                &self.shutdown.served_during_drain
+            } else {
+                &self.shutdown.rejected_during_drain
+            };
+            counter.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Resolve on SIGTERM or Ctrl-C
+pub async fn wait_for_signal() {
+    #[cfg(unix)]
+    {
+        use actix_web::rt::signal::unix::{signal, SignalKind};
+
+        let mut terminate = signal(SignalKind::terminate()).expect("Failed to install SIGTERM handler");
+        tokio::select! {
+            _ = terminate.recv() => {}
+            _ = actix_web::rt::signal::ctrl_c() => {}
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = actix_web::rt::signal::ctrl_c().await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counts_only_requests_finishing_during_drain() {
+        let shutdown = Arc::new(Shutdown::new(Duration::from_secs(5)));
+
+        shutdown.track().finish();
+        let before = shutdown.track();
+        let dropped = shutdown.track();
+        assert_eq!(shutdown.in_flight(), 2);
+        assert!(!shutdown.is_draining());
+
+        shutdown.draining.store(true, Ordering::Release);
+        before.finish();
+        shutdown.track().finish();
+        drop(dropped);
+
+        assert_eq!(
+            shutdown.report(),
+            DrainReport {
+                served: 2,
+                rejected: 1,
+                in_flight: 0,
+            }
+        );
+    }
+}
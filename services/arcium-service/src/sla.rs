@@ -0,0 +1,423 @@
+//! Latency SLA: flagging computations the cluster is slow to finish.
+//!
+//! Each pass compares the age of every queued or processing computation,
+//! from its stored `created_at`, with the threshold for its type. Nothing
+//! is timed in memory, so a restarted instance flags an overdue computation
+//! on its first pass. A breach is stamped on the record as
+//! `sla_breached_at`, logged at ERROR, POSTed to `SLA_ALERT_URL` as a
+//! `computation.sla_breached` event, and with `SLA_AUTO_ESCALATE` the
+//! cluster is re-queried with an escalation header.
+//!
+//! Every instance checks the computations in its own store.
+
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::sync::{Mutex, RwLock};
+use tracing::{error, warn};
+
+use crate::callback::{CallbackDispatcher, CallbackTemplate, CallbackVars, DeliveryLogEntry};
+use crate::config::Config;
+use crate::error::ServiceError;
+use crate::mpc::MpcClient;
+use crate::store::{unix_timestamp, ComputationRecord, ComputationStore};
+use crate::timeline::{Timeline, TimelineEvent};
+
+/// Alert deliveries kept for the admin API
+const MAX_DELIVERY_LOG: usize = 100;
+
+/// Seconds a computation may stay queued or processing, per type; 0 turns
+/// tracking off for that type
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub struct SlaThresholds {
+    pub payment_settlement_secs: u64,
+    pub payroll_settlement_secs: u64,
+}
+
+impl SlaThresholds {
+    pub fn for_type(&self, computation_type: &str) -> Option<u64> {
+        let secs = match computation_type {
+            "payment_settlement" => self.payment_settlement_secs,
+            "payroll_settlement" => self.payroll_settlement_secs,
+            _ => 0,
+        };
+        (secs > 0).then_some(secs)
+    }
+
+    fn is_overdue(&self, record: &ComputationRecord, now: u64) -> bool {
+        matches!(record.status.as_str(), "queued" | "processing")
+            && self
+                .for_type(&record.computation_type)
+                .is_some_and(|secs| now.saturating_sub(record.created_at) > secs)
+    }
+}
+
+/// A computation that ran past its threshold
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub struct SlaBreach {
+    pub computation_id: String,
+    pub computation_type: String,
+    /// Current status; a breached computation may have finished since
+    pub status: String,
+    pub threshold_secs: u64,
+    pub created_at: u64,
+    pub breached_at: u64,
+    /// Until it finished, or until now while it is still running
+    pub waited_secs: u64,
+}
+
+impl SlaBreach {
+    fn from_record(
+        record: &ComputationRecord,
+        thresholds: &SlaThresholds,
+        now: u64,
+    ) -> Option<Self> {
+        let breached_at = record.sla_breached_at?;
+        let finished = !matches!(record.status.as_str(), "queued" | "processing");
+        let until = if finished { record.updated_at } else { now };
+        Some(Self {
+            computation_id: record.computation_id.clone(),
+            computation_type: record.computation_type.clone(),
+            status: record.status.clone(),
+            threshold_secs: thresholds.for_type(&record.computation_type).unwrap_or_default(),
+            created_at: record.created_at,
+            breached_at,
+            waited_secs: until.saturating_sub(record.created_at),
+        })
+    }
+}
+
+/// What one pass found
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub struct SlaCheckReport {
+    pub breached: usize,
+    pub alerted: usize,
+    pub escalated: usize,
+    /// Escalations the cluster answered with a finished status
+    pub resolved_by_escalation: usize,
+    pub checked_at: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub struct SlaState {
+    pub thresholds: SlaThresholds,
+    pub auto_escalate: bool,
+    pub last_check: Option<SlaCheckReport>,
+    /// Most recent breach first
+    pub breaches: Vec<SlaBreach>,
+    pub alert_deliveries: Vec<DeliveryLogEntry>,
+}
+
+/// `computation.sla_breached` event body
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
+struct BreachEvent<'a> {
+    event: &'static str,
+    computation: &'a SlaBreach,
+}
+
+pub struct SlaMonitor {
+    thresholds: SlaThresholds,
+    alert: Option<CallbackTemplate>,
+    auto_escalate: bool,
+    deliveries: Mutex<VecDeque<DeliveryLogEntry>>,
+    last_check: RwLock<Option<SlaCheckReport>>,
+}
+
+impl SlaMonitor {
+    pub fn new(config: &Config) -> Result<Self, ServiceError> {
+        let alert = config
+            .sla_alert_url
+            .as_deref()
+            .map(|url| CallbackTemplate::parse(url, &[]))
+            .transpose()
+            .map_err(|e| ServiceError::ConfigError(format!("SLA_ALERT_URL: {}", e)))?;
+        Ok(Self::with(config.sla_thresholds, alert, config.sla_auto_escalate))
+    }
+
+    fn with(thresholds: SlaThresholds, alert: Option<CallbackTemplate>, auto_escalate: bool) -> Self {
+        Self {
+            thresholds,
+            alert,
+            auto_escalate,
+            deliveries: Mutex::new(VecDeque::new()),
+            last_check: RwLock::new(None),
+        }
+    }
+
+    /// Flag computations that have newly run past their threshold, alert on
+    /// them and, if enabled, escalate them to the cluster
+    pub async fn check(
+        &self,
+        store: &ComputationStore,
+        mpc: &MpcClient,
+        dispatcher: &CallbackDispatcher,
+        timeline: &Timeline,
+    ) -> SlaCheckReport {
+        let now = unix_timestamp();
+        let breached: Vec<SlaBreach> = store
+            .mark_sla_breached(now, |record| self.thresholds.is_overdue(record, now))
+            .iter()
+            .filter_map(|record| SlaBreach::from_record(record, &self.thresholds, now))
+            .collect();
+        let mut report = SlaCheckReport {
+            breached: breached.len(),
+            checked_at: now,
+            ..Default::default()
+        };
+
+        for breach in &breached {
+            error!(
+                computation_id = %breach.computation_id,
+                computation_type = %breach.computation_type,
+                status = %breach.status,
+                waited_secs = breach.waited_secs,
+                threshold_secs = breach.threshold_secs,
+                "Computation breached its latency SLA"
+            );
+            timeline.record(
+                &breach.computation_id,
+                TimelineEvent::SlaBreached {
+                    threshold_secs: breach.threshold_secs,
+                    waited_secs: breach.waited_secs,
+                },
+            );
+            if self.alert(breach, dispatcher).await {
+                report.alerted += 1;
+            }
+            if self.auto_escalate {
+                let escalated = mpc.escalate(&breach.computation_id, breach.waited_secs).await;
+                timeline.record(
+                    &breach.computation_id,
+                    TimelineEvent::Escalated {
+                        status: escalated.as_ref().ok().map(|response| response.status.clone()),
+                        error: escalated.as_ref().err().map(|e| e.to_string()),
+                    },
+                );
+                match escalated {
+                    Ok(response) => {
+                        report.escalated += 1;
+                        store.update_status(&breach.computation_id, &response.status);
+                        if !matches!(response.status.as_str(), "queued" | "processing") {
+                            report.resolved_by_escalation += 1;
+                        }
+                    }
+                    Err(e) => warn!("Escalating {} failed: {}", breach.computation_id, e),
+                }
+            }
+        }
+
+        *self.last_check.write().unwrap() = Some(report.clone());
+        report
+    }
+
+    /// POST the breach to the ops webhook, returning whether it was accepted
+    async fn alert(&self, breach: &SlaBreach, dispatcher: &CallbackDispatcher) -> bool {
+        let Some(template) = &self.alert else {
+            return false;
+        };
+        let vars = CallbackVars {
+            computation_id: &breach.computation_id,
+            batch_id: None,
+            status: "sla_breached",
+        };
+        let event = BreachEvent {
+            event: "computation.sla_breached",
+            computation: breach,
+        };
+        let entry = dispatcher.deliver(template, None, &vars, &event).await;
+        let delivered = entry.status_code.is_some_and(|code| (200..300).contains(&code));
+
+        let mut log = self.deliveries.lock().unwrap();
+        if log.len() >= MAX_DELIVERY_LOG {
+            log.pop_front();
+        }
+        log.push_back(entry);
+        delivered
+    }
+
+    pub fn state(&self, store: &ComputationStore) -> SlaState {
+        let now = unix_timestamp();
+        let mut breaches: Vec<SlaBreach> = store
+            .list()
+            .iter()
+            .filter_map(|record| SlaBreach::from_record(record, &self.thresholds, now))
+            .collect();
+        breaches.sort_by(|a, b| {
+            b.breached_at
+                .cmp(&a.breached_at)
+                .then_with(|| a.computation_id.cmp(&b.computation_id))
+        });
+
+        SlaState {
+            thresholds: self.thresholds,
+            auto_escalate: self.auto_escalate,
+            last_check: self.last_check.read().unwrap().clone(),
+            breaches,
+            alert_deliveries: self.deliveries.lock().unwrap().iter().cloned().collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metadata::Metadata;
+    use crate::payload::PayloadLimits;
+    use actix_web::{web, App, HttpRequest, HttpResponse, HttpServer};
+    use serde_json::{json, Value};
+    use std::sync::Arc;
+
+    const THRESHOLDS: SlaThresholds = SlaThresholds {
+        payment_settlement_secs: 600,
+        payroll_settlement_secs: 1_800,
+    };
+
+    fn store() -> ComputationStore {
+        ComputationStore::new(PayloadLimits {
+            compress_threshold_bytes: 4 * 1024,
+            max_stored_bytes: 1024 * 1024,
+        })
+    }
+
+    fn insert(store: &ComputationStore, id: &str, computation_type: &str, status: &str, age_secs: u64) {
+        let mut record = ComputationRecord::new(
+            id.to_string(),
+            computation_type,
+            status.to_string(),
+            String::new(),
+            Metadata::new(),
+        );
+        record.created_at -= age_secs;
+        record.updated_at = record.created_at;
+        store.insert(record);
+    }
+
+    /// A cluster answering status queries with `status`, recording the
+    /// escalation headers it sees, and an ops receiver recording alerts
+    fn servers(
+        status: &'static str,
+        escalations: Arc<Mutex<Vec<(String, String)>>>,
+        alerts: Arc<Mutex<Vec<Value>>>,
+    ) -> (String, String) {
+        let server = HttpServer::new(move || {
+            let (escalations, alerts) = (escalations.clone(), alerts.clone());
+            App::new()
+                .route(
+                    "/api/v1/computations/{id}",
+                    web::get().to(move |req: HttpRequest, id: web::Path<String>| {
+                        let escalations = escalations.clone();
+                        async move {
+                            let header = |name| {
+                                let value = req.headers().get(name).and_then(|v| v.to_str().ok());
+                                value.unwrap_or_default().to_string()
+                            };
+                            let seen = (header("X-Escalation"), header("X-Escalation-Waited-Secs"));
+                            escalations.lock().unwrap().push(seen);
+                            HttpResponse::Ok().json(json!({ "computation_id": id.into_inner(), "status": status }))
+                        }
+                    }),
+                )
+                .route(
+                    "/ops",
+                    web::post().to(move |body: web::Json<Value>| {
+                        let alerts = alerts.clone();
+                        async move {
+                            alerts.lock().unwrap().push(body.into_inner());
+                            HttpResponse::Ok().finish()
+                        }
+                    }),
+                )
+        })
+        .workers(1)
+        .disable_signals()
+        .bind("127.0.0.1:0")
+        .unwrap();
+        let url = format!("http://{}", server.addrs()[0]);
+        actix_web::rt::spawn(server.run());
+        (url.clone(), format!("{}/ops", url))
+    }
+
+    #[test]
+    fn test_only_open_computations_past_their_threshold_breach() {
+        let store = store();
+        insert(&store, "pay_late", "payment_settlement", "processing", 601);
+        insert(&store, "pay_on_time", "payment_settlement", "queued", 599);
+        insert(&store, "pay_done", "payment_settlement", "completed", 3_600);
+        insert(&store, "payroll_on_time", "payroll_settlement", "processing", 900);
+        insert(&store, "payroll_late", "payroll_settlement", "queued", 1_801);
+
+        let now = unix_timestamp();
+        let mut breached: Vec<String> = store
+            .mark_sla_breached(now, |r| THRESHOLDS.is_overdue(r, now))
+            .into_iter()
+            .map(|r| r.computation_id)
+            .collect();
+        breached.sort();
+        assert_eq!(breached, ["pay_late", "payroll_late"]);
+
+        // Flagged once, however many passes see it
+        assert!(store.mark_sla_breached(now, |r| THRESHOLDS.is_overdue(r, now)).is_empty());
+
+        let disabled = SlaThresholds { payment_settlement_secs: 0, ..THRESHOLDS };
+        assert_eq!(disabled.for_type("payment_settlement"), None);
+        assert_eq!(disabled.for_type("unknown"), None);
+    }
+
+    #[actix_rt::test]
+    async fn test_breaches_are_alerted_escalated_and_listed() {
+        let (escalations, alerts) = (Arc::new(Mutex::new(Vec::new())), Arc::new(Mutex::new(Vec::new())));
+        let (cluster, ops) = servers("completed", escalations.clone(), alerts.clone());
+        let mpc = MpcClient::for_cluster(cluster);
+        let dispatcher = crate::callback::tests::dispatcher();
+
+        // Queued before a restart: only the stored timestamp says it is late
+        let store = store();
+        insert(&store, "pay_late", "payment_settlement", "processing", 700);
+        insert(&store, "pay_on_time", "payment_settlement", "processing", 10);
+
+        let timeline = Arc::new(Timeline::new());
+        let writer = timeline.clone();
+        actix_web::rt::spawn(async move { writer.run().await });
+
+        let monitor = SlaMonitor::with(THRESHOLDS, Some(CallbackTemplate::parse(&ops, &[]).unwrap()), true);
+        let report = monitor.check(&store, &mpc, &dispatcher, &timeline).await;
+        assert_eq!(
+            (report.breached, report.alerted, report.escalated, report.resolved_by_escalation),
+            (1, 1, 1, 1)
+        );
+        assert_eq!(*escalations.lock().unwrap(), [("sla-breach".to_string(), "700".to_string())]);
+        let alert = alerts.lock().unwrap()[0].clone();
+        assert_eq!(alert["event"], "computation.sla_breached");
+        assert_eq!(alert["computation"]["computation_id"], "pay_late");
+        assert_eq!(alert["computation"]["threshold_secs"], 600);
+
+        // The escalation's answer updated the record; the breach stays listed
+        let record = store.list().into_iter().find(|record| record.computation_id == "pay_late").unwrap();
+        assert_eq!(record.status, "completed");
+        assert!(record.sla_breached_at.is_some());
+        let state = monitor.state(&store);
+        assert_eq!(state.breaches.len(), 1);
+        assert_eq!(state.breaches[0].status, "completed");
+        assert_eq!(state.alert_deliveries[0].status_code, Some(200));
+
+        // Both steps land on the computation's timeline
+        actix_web::rt::time::sleep(std::time::Duration::from_millis(20)).await;
+        let events: Vec<TimelineEvent> =
+            timeline.get("pay_late").unwrap().events.into_iter().map(|e| e.event).collect();
+        assert_eq!(
+            events,
+            [
+                TimelineEvent::SlaBreached { threshold_secs: 600, waited_secs: 700 },
+                TimelineEvent::Escalated { status: Some("completed".to_string()), error: None },
+            ]
+        );
+
+        // Nothing new on the next pass
+        let report = monitor.check(&store, &mpc, &dispatcher, &timeline).await;
+        assert_eq!((report.breached, report.escalated), (0, 0));
+    }
+}
@@ -0,0 +1,401 @@
+//! Monthly merchant statements.
+//!
+//! A statement totals a calendar month (UTC) of the merchant's daily vault
+//! stats and is immutable once issued: its period is locked. A later
+//! backfill can still change a locked month's rollups (a late payment, a
+//! refund, a rewritten record). The statement keeps what it said. The
+//! difference is reported as an adjustment on the merchant's next
+//! statement, so issued totals plus the adjustments flagged since always
+//! equal the current rollups.
+//!
+//! Statements are issued in order, and only for months that have ended.
+
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use solana_sdk::pubkey::Pubkey;
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+use crate::error::ServiceError;
+use crate::vault_stats::{self, Day, DayTotals, Totals, VaultStats};
+
+const SECONDS_PER_DAY: i64 = 86_400;
+
+/// Change to an earlier, locked period's totals since it was issued
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub struct Adjustment<D = i128> {
+    pub period: String,
+    pub payments: i64,
+    pub volume: D,
+    pub fees: D,
+}
+
+/// `M` and `D` are the wire types of money and signed money fields (u64 and
+/// i128 for v1, decimal strings for v2)
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub struct Statement<M = u64, D = i128> {
+    pub id: String,
+    pub merchant: String,
+    /// `YYYY-MM`
+    pub period: String,
+    pub period_start: String,
+    pub period_end: String,
+    /// Sum of `days`
+    pub totals: Totals<M>,
+    /// Payments of this period refunded before it was issued; not in `totals`
+    pub refunds: Totals<M>,
+    pub days: Vec<DayTotals<M>>,
+    pub adjustments: Vec<Adjustment<D>>,
+    pub issued_at: u64,
+    /// SHA-256 of the merchant, period, amounts and issue time
+    pub content_hash: String,
+}
+
+impl Statement {
+    pub fn map(&self, f: impl Fn(u64) -> String) -> Statement<String, String> {
+        Statement {
+            id: self.id.clone(),
+            merchant: self.merchant.clone(),
+            period: self.period.clone(),
+            period_start: self.period_start.clone(),
+            period_end: self.period_end.clone(),
+            totals: self.totals.map(&f),
+            refunds: self.refunds.map(&f),
+            days: self
+                .days
+                .iter()
+                .map(|d| DayTotals { day: d.day.clone(), totals: d.totals.map(&f) })
+                .collect(),
+            adjustments: self
+                .adjustments
+                .iter()
+                .map(|a| Adjustment {
+                    period: a.period.clone(),
+                    payments: a.payments,
+                    volume: a.volume.to_string(),
+                    fees: a.fees.to_string(),
+                })
+                .collect(),
+            issued_at: self.issued_at,
+            content_hash: self.content_hash.clone(),
+        }
+    }
+
+    fn compute_hash(&self) -> String {
+        let content = serde_json::to_vec(&(
+            &self.merchant,
+            &self.period,
+            &self.totals,
+            &self.refunds,
+            &self.days,
+            &self.adjustments,
+            self.issued_at,
+        ))
+        .expect("statement serializes");
+        hex::encode(Sha256::digest(content))
+    }
+
+    /// One row per day, refund total and adjustment
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("statement_id,merchant,period,line,line_period,payments,volume,fees\n");
+        let mut row = |line: &str, line_period: &str, payments: String, volume: String, fees: String| {
+            csv.push_str(&format!(
+                "{},{},{},{},{},{},{},{}\n",
+                self.id, self.merchant, self.period, line, line_period, payments, volume, fees
+            ));
+        };
+        for day in &self.days {
+            let t = &day.totals;
+            row("day", &day.day, t.payments.to_string(), t.volume.to_string(), t.fees.to_string());
+        }
+        let t = &self.totals;
+        row("total", &self.period, t.payments.to_string(), t.volume.to_string(), t.fees.to_string());
+        let r = &self.refunds;
+        row("refunds", &self.period, r.payments.to_string(), r.volume.to_string(), r.fees.to_string());
+        for a in &self.adjustments {
+            row("adjustment", &a.period, a.payments.to_string(), a.volume.to_string(), a.fees.to_string());
+        }
+        csv
+    }
+}
+
+/// First and last day of a `YYYY-MM` period
+pub fn parse_period(period: &str) -> Option<(Day, Day)> {
+    let first = vault_stats::parse_day(&format!("{}-01", period))?;
+    let (year, month) = period.split_once('-')?;
+    let (year, month): (i64, i64) = (year.parse().ok()?, month.parse().ok()?);
+    let (next_year, next_month) = if month == 12 { (year + 1, 1) } else { (year, month + 1) };
+    let next = vault_stats::parse_day(&format!("{:04}-{:02}-01", next_year, next_month))?;
+    Some((first, next - 1))
+}
+
+/// The merchant's daily totals for `first..=last`, as vault stats serves them
+fn period_days(stats: &VaultStats, merchant: &Pubkey, first: Day, last: Day) -> (Totals, Vec<DayTotals>) {
+    let snapshot = stats.snapshot(Some(*merchant));
+    let days: Vec<DayTotals> = snapshot
+        .days
+        .iter()
+        .filter(|d| vault_stats::parse_day(&d.day).is_some_and(|day| (first..=last).contains(&day)))
+        .cloned()
+        .collect();
+    let mut totals = Totals::default();
+    for day in &days {
+        totals.merge(&day.totals);
+    }
+    (totals, days)
+}
+
+/// Issued statements, per merchant in period order
+#[derive(Default)]
+pub struct Statements {
+    merchants: RwLock<HashMap<Pubkey, Vec<Statement>>>,
+}
+
+impl Statements {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Issue the statement for `period`, locking it
+    pub fn issue(
+        &self,
+        stats: &VaultStats,
+        merchant: Pubkey,
+        period: &str,
+        now: u64,
+    ) -> Result<Statement, ServiceError> {
+        let (first, last) = parse_period(period)
+            .ok_or_else(|| ServiceError::InvalidInput(format!("Invalid period, expected YYYY-MM: {}", period)))?;
+        if ((last + 1) * SECONDS_PER_DAY) as u64 > now {
+            return Err(ServiceError::InvalidInput(format!("Period {} has not ended yet", period)));
+        }
+
+        let mut merchants = self.merchants.write().unwrap();
+        let issued = merchants.entry(merchant).or_default();
+        if let Some(latest) = issued.last() {
+            if latest.period.as_str() >= period {
+                return Err(ServiceError::InvalidInput(format!(
+                    "Statements are issued in order; the latest is for {}",
+                    latest.period
+                )));
+            }
+        }
+
+        let adjustments = pending_adjustments(stats, &merchant, issued);
+        let (totals, days) = period_days(stats, &merchant, first, last);
+        let mut statement = Statement {
+            id: format!("stmt_{}", hex::encode(rand::random::<[u8; 8]>())),
+            merchant: merchant.to_string(),
+            period: period.to_string(),
+            period_start: vault_stats::format_day(first),
+            period_end: vault_stats::format_day(last),
+            totals,
+            refunds: stats.refunds(&merchant, first, last),
+            days,
+            adjustments,
+            issued_at: now,
+            content_hash: String::new(),
+        };
+        statement.content_hash = statement.compute_hash();
+        issued.push(statement.clone());
+        Ok(statement)
+    }
+
+    /// Newest first
+    pub fn list(&self, merchant: &Pubkey) -> Vec<Statement> {
+        let merchants = self.merchants.read().unwrap();
+        merchants.get(merchant).map(|s| s.iter().rev().cloned().collect()).unwrap_or_default()
+    }
+
+    pub fn get(&self, merchant: &Pubkey, id: &str) -> Option<Statement> {
+        let merchants = self.merchants.read().unwrap();
+        merchants.get(merchant)?.iter().find(|s| s.id == id).cloned()
+    }
+}
+
+/// Differences between each locked period's current rollups and what its
+/// statement, plus the adjustments flagged since, already reported
+fn pending_adjustments(stats: &VaultStats, merchant: &Pubkey, issued: &[Statement]) -> Vec<Adjustment> {
+    let mut adjustments = Vec::new();
+    for locked in issued {
+        let Some((first, last)) = parse_period(&locked.period) else {
+            continue;
+        };
+        let (current, _) = period_days(stats, merchant, first, last);
+
+        let mut reported = Adjustment {
+            period: locked.period.clone(),
+            payments: locked.totals.payments as i64,
+            volume: locked.totals.volume as i128,
+            fees: locked.totals.fees as i128,
+        };
+        for flagged in issued.iter().flat_map(|s| &s.adjustments).filter(|a| a.period == locked.period) {
+            reported.payments += flagged.payments;
+            reported.volume += flagged.volume;
+            reported.fees += flagged.fees;
+        }
+
+        let delta = Adjustment {
+            period: locked.period.clone(),
+            payments: current.payments as i64 - reported.payments,
+            volume: current.volume as i128 - reported.volume,
+            fees: current.fees as i128 - reported.fees,
+        };
+        if (delta.payments, delta.volume, delta.fees) != (0, 0, 0) {
+            adjustments.push(delta);
+        }
+    }
+    adjustments
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fee_audit::PaymentRecord;
+
+    // 2026-08-01 00:00:00 UTC
+    const AUGUST: i64 = 1_785_542_400;
+    const NOW: u64 = 1_790_000_000; // 2026-09-21
+
+    fn merchant() -> Pubkey {
+        Pubkey::new_from_array([1; 32])
+    }
+
+    fn record(amount: u64, timestamp: i64) -> (Pubkey, PaymentRecord) {
+        let record = PaymentRecord {
+            payment_id: [0; 32],
+            merchant: merchant(),
+            amount,
+            fee: amount / 200,
+            timestamp,
+            mint: None,
+            exact_net: false,
+        };
+        (Pubkey::new_unique(), record)
+    }
+
+    #[test]
+    fn test_periods() {
+        let (first, last) = parse_period("2026-08").unwrap();
+        assert_eq!(first, AUGUST / SECONDS_PER_DAY);
+        assert_eq!(vault_stats::format_day(last), "2026-08-31");
+        assert_eq!(parse_period("2024-02").map(|(f, l)| l - f + 1), Some(29));
+        assert_eq!(parse_period("2026-12").map(|(_, l)| vault_stats::format_day(l)).as_deref(), Some("2026-12-31"));
+        assert_eq!(parse_period("2026-13"), None);
+        assert_eq!(parse_period("August"), None);
+    }
+
+    #[test]
+    fn test_statement_reconciles_with_daily_rollups() {
+        let stats = VaultStats::for_program(Pubkey::new_unique());
+        stats.ingest(&[
+            record(1_000_000, AUGUST - 1), // July 31st
+            record(2_000_000, AUGUST),
+            record(3_000_000, AUGUST + 15 * SECONDS_PER_DAY),
+            record(4_000_000, AUGUST + 31 * SECONDS_PER_DAY - 1), // August 31st
+        ], 100);
+        stats.compact();
+        let statements = Statements::new();
+
+        assert!(statements.issue(&stats, merchant(), "2026-09", NOW).is_err());
+        let statement = statements.issue(&stats, merchant(), "2026-08", NOW).unwrap();
+
+        let august: Vec<_> = stats
+            .snapshot(Some(merchant()))
+            .days
+            .iter()
+            .filter(|d| d.day.starts_with("2026-08"))
+            .cloned()
+            .collect();
+        assert_eq!(statement.days, august);
+        let volume: u64 = august.iter().map(|d| d.totals.volume).sum();
+        let fees: u64 = august.iter().map(|d| d.totals.fees).sum();
+        assert_eq!(statement.totals, Totals { payments: 3, volume, fees });
+        assert_eq!(volume, 9_000_000);
+        assert_eq!(statement.content_hash, statement.compute_hash());
+
+        // Locked: not issued twice, nor out of order
+        assert!(statements.issue(&stats, merchant(), "2026-08", NOW).is_err());
+        assert!(statements.issue(&stats, merchant(), "2026-07", NOW).is_err());
+    }
+
+    #[test]
+    fn test_backfill_flags_locked_periods_as_adjustments() {
+        let stats = VaultStats::for_program(Pubkey::new_unique());
+        let records = vec![record(2_000_000, AUGUST), record(3_000_000, AUGUST + SECONDS_PER_DAY)];
+        stats.ingest(&records, 100);
+        stats.mark_finalized(&records.iter().map(|(key, _)| *key).collect::<Vec<_>>());
+        stats.compact();
+        let statements = Statements::new();
+        let july = statements.issue(&stats, merchant(), "2026-07", NOW).unwrap();
+        let august = statements.issue(&stats, merchant(), "2026-08", NOW).unwrap();
+
+        // A late July payment arrives and an August one is refunded
+        stats.replace(&[records[0].clone(), record(500_000, AUGUST - 60)], 200, AUGUST + 2 * SECONDS_PER_DAY);
+        stats.compact();
+
+        assert_eq!(statements.get(&merchant(), &august.id), Some(august.clone()));
+        let september = statements.issue(&stats, merchant(), "2026-09", 1_793_000_000).unwrap();
+        assert_eq!(
+            september.adjustments,
+            vec![
+                Adjustment { period: "2026-07".to_string(), payments: 1, volume: 500_000, fees: 2_500 },
+                Adjustment { period: "2026-08".to_string(), payments: -1, volume: -3_000_000, fees: -15_000 },
+            ]
+        );
+
+        // Issued totals plus adjustments equal the rollups now
+        for (locked, adjustment) in [(&july, &september.adjustments[0]), (&august, &september.adjustments[1])] {
+            let (first, last) = parse_period(&locked.period).unwrap();
+            let (current, _) = period_days(&stats, &merchant(), first, last);
+            assert_eq!(current.volume as i128, locked.totals.volume as i128 + adjustment.volume);
+        }
+
+        // Flagged once only
+        let october = statements.issue(&stats, merchant(), "2026-10", 1_795_000_000).unwrap();
+        assert!(october.adjustments.is_empty());
+    }
+
+    #[test]
+    fn test_refunds_before_issue_are_reported() {
+        let stats = VaultStats::for_program(Pubkey::new_unique());
+        let records = vec![record(2_000_000, AUGUST), record(3_000_000, AUGUST + SECONDS_PER_DAY)];
+        stats.ingest(&records, 100);
+        stats.mark_finalized(&records.iter().map(|(key, _)| *key).collect::<Vec<_>>());
+        stats.compact();
+        stats.replace(&records[..1], 200, AUGUST + 2 * SECONDS_PER_DAY);
+        stats.compact();
+
+        let statement = Statements::new().issue(&stats, merchant(), "2026-08", NOW).unwrap();
+        assert_eq!(statement.totals.volume, 2_000_000);
+        assert_eq!(statement.refunds, Totals { payments: 1, volume: 3_000_000, fees: 15_000 });
+
+        let csv = statement.to_csv();
+        let lines: Vec<_> = csv.lines().collect();
+        assert_eq!(lines.len(), 1 + 1 + 2);
+        assert!(lines[1].ends_with(",day,2026-08-01,1,2000000,10000"));
+        assert!(lines[3].ends_with(",refunds,2026-08,1,3000000,15000"));
+    }
+
+    #[test]
+    fn test_reorged_payment_in_locked_period_is_adjusted_not_refunded() {
+        let stats = VaultStats::for_program(Pubkey::new_unique());
+        let records = vec![record(2_000_000, AUGUST), record(3_000_000, AUGUST + SECONDS_PER_DAY)];
+        stats.ingest(&records, 100);
+        stats.compact();
+        let statements = Statements::new();
+        let august = statements.issue(&stats, merchant(), "2026-08", NOW).unwrap();
+        assert_eq!(august.totals.volume, 5_000_000);
+
+        stats.revert(&records[1].0, NOW).unwrap();
+        stats.compact();
+        let september = statements.issue(&stats, merchant(), "2026-09", 1_793_000_000).unwrap();
+        assert_eq!(
+            september.adjustments,
+            vec![Adjustment { period: "2026-08".to_string(), payments: -1, volume: -3_000_000, fees: -15_000 }]
+        );
+        assert_eq!(september.refunds, Totals::default());
+    }
+}
@@ -0,0 +1,209 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex, RwLock};
+
+use crate::metrics::{Counts, Metrics, Subsystem};
+use crate::rate_limit::RateLimiter;
+use crate::store::unix_timestamp;
+
+/// How long a rendered status snapshot is reused
+pub const STATUS_CACHE_SECS: u64 = 15;
+
+/// Minimum samples in the last hour before metrics can mark a subsystem unhealthy
+const MIN_SAMPLES: u64 = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HealthStatus {
+    Operational,
+    Degraded,
+    Down,
+}
+
+/// Operator-declared incident shown on the public status page
+#[derive(Debug, Clone, Serialize)]
+pub struct Incident {
+    pub id: String,
+    pub subsystem: Subsystem,
+    pub status: HealthStatus,
+    pub message: String,
+    pub started_at: u64,
+}
+
+#[derive(Debug, Serialize)]
+struct SubsystemStatus {
+    status: HealthStatus,
+    success_rate_24h: Option<f64>,
+}
+
+#[derive(Debug, Serialize)]
+struct StatusSnapshot {
+    status: HealthStatus,
+    subsystems: BTreeMap<Subsystem, SubsystemStatus>,
+    incidents: Vec<Incident>,
+    generated_at: u64,
+}
+
+/// A rendered snapshot with its ETag
+pub struct RenderedStatus {
+    pub body: String,
+    pub etag: String,
+    expires_at: u64,
+}
+
+/// Public status page state: incident flags and the cached snapshot
+pub struct StatusBoard {
+    incidents: RwLock<Vec<Incident>>,
+    cache: Mutex<Option<Arc<RenderedStatus>>>,
+    pub limiter: RateLimiter,
+}
+
+impl StatusBoard {
+    pub fn new(requests_per_minute: u32) -> Self {
+        Self {
+            incidents: RwLock::new(Vec::new()),
+            cache: Mutex::new(None),
+            limiter: RateLimiter::new(requests_per_minute, 60),
+        }
+    }
+
+    pub fn incidents(&self) -> Vec<Incident> {
+        self.incidents.read().unwrap().clone()
+    }
+
+    pub fn open_incident(&self, subsystem: Subsystem, status: HealthStatus, message: String) -> Incident {
+        let incident = Incident {
+            id: format!("inc_{}", hex::encode(rand::random::<[u8; 8]>())),
+            subsystem,
+            status,
+            message,
+            started_at: unix_timestamp(),
+        };
+        self.incidents.write().unwrap().push(incident.clone());
+        self.invalidate();
+        incident
+    }
+
+    /// Resolve an incident, returning whether it existed
+    pub fn resolve_incident(&self, id: &str) -> bool {
+        let mut incidents = self.incidents.write().unwrap();
+        let before = incidents.len();
+        incidents.retain(|i| i.id != id);
+        let removed = incidents.len() != before;
+        drop(incidents);
+        if removed {
+            self.invalidate();
+        }
+        removed
+    }
+
+    fn invalidate(&self) {
+        *self.cache.lock().unwrap() = None;
+    }
+
+    /// Current snapshot, re-rendered at most every `STATUS_CACHE_SECS`
+    pub fn render(&self, metrics: &Metrics) -> Arc<RenderedStatus> {
+        let now = unix_timestamp();
+        let mut cache = self.cache.lock().unwrap();
+        if let Some(cached) = cache.as_ref() {
+            if cached.expires_at > now {
+                return cached.clone();
+            }
+        }
+
+        let incidents = self.incidents();
+        let subsystems: BTreeMap<_, _> = Subsystem::ALL
+            .iter()
+            .map(|&subsystem| {
+                let derived = derive_status(metrics.counts(subsystem, 3600));
+                let declared = incidents
+                    .iter()
+                    .filter(|i| i.subsystem == subsystem)
+                    .map(|i| i.status)
+                    .max()
+                    .unwrap_or(HealthStatus::Operational);
+                let success_rate_24h = metrics
+                    .counts(subsystem, 24 * 3600)
+                    .success_rate()
+                    .map(|rate| (rate * 100.0).round() / 100.0);
+                (
+                    subsystem,
+                    SubsystemStatus {
+                        status: derived.max(declared),
+                        success_rate_24h,
+                    },
+                )
+            })
+            .collect();
+
+        let snapshot = StatusSnapshot {
+            status: subsystems
+                .values()
+                .map(|s| s.status)
+                .max()
+                .unwrap_or(HealthStatus::Operational),
+            subsystems,
+            incidents,
+            generated_at: now,
+        };
+
+        let body = serde_json::to_string(&snapshot).expect("status snapshot serializes");
+        let etag = format!("\"{}\"", hex::encode(&Sha256::digest(body.as_bytes())[..16]));
+        let rendered = Arc::new(RenderedStatus {
+            body,
+            etag,
+            expires_at: now + STATUS_CACHE_SECS,
+        });
+        *cache = Some(rendered.clone());
+        rendered
+    }
+}
+
+/// Coarse health from the last hour's success rate
+fn derive_status(last_hour: Counts) -> HealthStatus {
+    if last_hour.total() < MIN_SAMPLES {
+        return HealthStatus::Operational;
+    }
+    match last_hour.success_rate() {
+        Some(rate) if rate >= 99.0 => HealthStatus::Operational,
+        Some(rate) if rate >= 90.0 => HealthStatus::Degraded,
+        Some(_) => HealthStatus::Down,
+        None => HealthStatus::Operational,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_derive_status() {
+        assert_eq!(derive_status(Counts { success: 0, failure: 3 }), HealthStatus::Operational);
+        assert_eq!(derive_status(Counts { success: 995, failure: 5 }), HealthStatus::Operational);
+        assert_eq!(derive_status(Counts { success: 95, failure: 5 }), HealthStatus::Degraded);
+        assert_eq!(derive_status(Counts { success: 5, failure: 95 }), HealthStatus::Down);
+    }
+
+    #[test]
+    fn test_incidents_override_and_invalidate_cache() {
+        let board = StatusBoard::new(60);
+        let metrics = Metrics::new();
+
+        let first = board.render(&metrics);
+        assert!(first.body.contains(r#""status":"operational""#));
+        assert!(Arc::ptr_eq(&first, &board.render(&metrics)));
+
+        let incident = board.open_incident(
+            Subsystem::MpcCluster,
+            HealthStatus::Degraded,
+            "Elevated settlement latency".to_string(),
+        );
+        let second = board.render(&metrics);
+        assert_ne!(first.etag, second.etag);
+        assert!(second.body.contains(r#""status":"degraded""#));
+
+        assert!(board.resolve_incident(&incident.id));
+        assert!(!board.resolve_incident(&incident.id));
+    }
+}
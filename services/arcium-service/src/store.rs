@@ -0,0 +1,176 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::RwLock;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::callback::DeliveryLogEntry;
+use crate::callback_tls::CallbackTls;
+use crate::error::ServiceError;
+use crate::metadata::Metadata;
+use crate::payload::{CompressionReport, PayloadLimits, StoredPayload};
+
+/// A computation queued by this service, as tracked locally
+#[derive(Debug, Clone, Serialize)]
+pub struct ComputationRecord {
+    pub computation_id: String,
+    pub computation_type: String,
+    pub status: String,
+    pub batch_id: Option<String>,
+    pub metadata: Metadata,
+    /// Callback URL template as submitted by the caller
+    pub callback_url: String,
+    /// CA bundle and client identity for callback deliveries
+    pub callback_tls: Option<CallbackTls>,
+    /// Callback deliveries, with the expanded URL actually called
+    pub deliveries: Vec<DeliveryLogEntry>,
+    /// Params sent to the cluster
+    pub params: Option<StoredPayload>,
+    /// Result reported by the cluster's callback
+    pub result: Option<StoredPayload>,
+    pub created_at: u64,
+    pub updated_at: u64,
+    /// When the computation was found still running past its SLA
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sla_breached_at: Option<u64>,
+}
+
+impl ComputationRecord {
+    pub fn new(
+        computation_id: String,
+        computation_type: &str,
+        status: String,
+        callback_url: String,
+        metadata: Metadata,
+    ) -> Self {
+        let now = unix_timestamp();
+        Self {
+            computation_id,
+            computation_type: computation_type.to_string(),
+            status,
+            batch_id: None,
+            metadata,
+            callback_url,
+            callback_tls: None,
+            deliveries: Vec::new(),
+            params: None,
+            result: None,
+            created_at: now,
+            updated_at: now,
+            sla_breached_at: None,
+        }
+    }
+}
+
+/// In-memory store of computation records keyed by computation id
+pub struct ComputationStore {
+    records: RwLock<HashMap<String, ComputationRecord>>,
+    payload_limits: PayloadLimits,
+}
+
+impl ComputationStore {
+    pub fn new(payload_limits: PayloadLimits) -> Self {
+        Self {
+            records: RwLock::new(HashMap::new()),
+            payload_limits,
+        }
+    }
+
+    /// Encode a payload for storage, failing if it is over the size limit
+    pub fn encode_payload<T: Serialize>(&self, value: &T) -> Result<StoredPayload, ServiceError> {
+        StoredPayload::encode(value, &self.payload_limits)
+    }
+
+    pub fn insert(&self, record: ComputationRecord) {
+        self.records
+            .write()
+            .unwrap()
+            .insert(record.computation_id.clone(), record);
+    }
+
+    pub fn list(&self) -> Vec<ComputationRecord> {
+        self.records.read().unwrap().values().cloned().collect()
+    }
+
+    /// Update the status of a known computation, returning the updated record
+    pub fn update_status(&self, computation_id: &str, status: &str) -> Option<ComputationRecord> {
+        let mut records = self.records.write().unwrap();
+        let record = records.get_mut(computation_id)?;
+        if record.status != status {
+            record.status = status.to_string();
+            record.updated_at = unix_timestamp();
+        }
+        Some(record.clone())
+    }
+
+    /// Keep the cluster's result for a computation. Results over the size
+    /// limit are dropped (the callback still relays them).
+    pub fn record_result(&self, computation_id: &str, result: &serde_json::Value) {
+        let payload = match self.encode_payload(result) {
+            Ok(payload) => payload,
+            Err(e) => {
+                tracing::warn!("Not storing result for {}: {}", computation_id, e);
+                return;
+            }
+        };
+        if let Some(record) = self.records.write().unwrap().get_mut(computation_id) {
+            record.result = Some(payload);
+        }
+    }
+
+    /// Compress stored payloads written before compression applied to them,
+    /// e.g. under a higher threshold
+    pub fn compress_payloads(&self) -> CompressionReport {
+        let mut report = CompressionReport::default();
+        for record in self.records.write().unwrap().values_mut() {
+            for payload in [&mut record.params, &mut record.result].into_iter().flatten() {
+                payload.compress_in_place(&self.payload_limits, &mut report);
+            }
+        }
+        report
+    }
+
+    /// Stamp `sla_breached_at` on every computation `overdue` picks that
+    /// isn't stamped yet, returning those records
+    pub fn mark_sla_breached(
+        &self,
+        now: u64,
+        overdue: impl Fn(&ComputationRecord) -> bool,
+    ) -> Vec<ComputationRecord> {
+        let mut breached = Vec::new();
+        for record in self.records.write().unwrap().values_mut() {
+            if record.sla_breached_at.is_none() && overdue(record) {
+                record.sla_breached_at = Some(now);
+                breached.push(record.clone());
+            }
+        }
+        breached
+    }
+
+    pub fn contains(&self, computation_id: &str) -> bool {
+        self.records.read().unwrap().contains_key(computation_id)
+    }
+
+    /// Drop completed and failed computations not updated since `cutoff`,
+    /// returning how many were removed
+    pub fn prune_finished(&self, cutoff: u64) -> usize {
+        let mut records = self.records.write().unwrap();
+        let before = records.len();
+        records.retain(|_, record| {
+            matches!(record.status.as_str(), "queued" | "processing") || record.updated_at >= cutoff
+        });
+        before - records.len()
+    }
+
+    pub fn record_delivery(&self, computation_id: &str, entry: DeliveryLogEntry) {
+        if let Some(record) = self.records.write().unwrap().get_mut(computation_id) {
+            record.deliveries.push(entry);
+        }
+    }
+}
+
+pub fn unix_timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
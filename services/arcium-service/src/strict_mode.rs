@@ -0,0 +1,342 @@
+//! Strict mode: rejecting deprecated request formats per API key.
+//!
+//! Older integrations still send amounts as JSON numbers and commitment
+//! blinding factors under `nonce`. Both keep working for legacy keys, but a
+//! key in strict mode gets `DEPRECATED_FORMAT` naming the field and its
+//! replacement. Every deprecated use is counted per key, strict or not, so
+//! operators can see who still needs a shim before it is removed.
+//!
+//! Keys are identified by the first 16 hex characters of the SHA-256 of the
+//! `X-API-Key` header; the raw key is never stored or logged. The header only
+//! attributes requests, it does not authenticate them.
+
+use actix_web::HttpRequest;
+use serde::de::{self, Deserializer, Visitor};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::{Mutex, RwLock};
+
+use crate::error::ServiceError;
+
+/// Usage of requests without an `X-API-Key` header is counted under this id
+pub const ANONYMOUS: &str = "anonymous";
+
+/// Keys tracked in the usage counters; further keys are counted as anonymous
+const MAX_TRACKED_KEYS: usize = 10_000;
+
+/// Attribution id for the request's API key, or `None` without one
+pub fn key_id(req: &HttpRequest) -> Option<String> {
+    let key = req.headers().get("X-API-Key")?.to_str().ok()?.trim();
+    if key.is_empty() {
+        return None;
+    }
+    Some(hex::encode(&Sha256::digest(key.as_bytes())[..8]))
+}
+
+/// A deprecated form found in a request
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Deprecation {
+    /// Offending field, e.g. `payments[3].amount`
+    pub field: String,
+    /// What the field should be sent as instead
+    pub use_instead: &'static str,
+}
+
+impl Deprecation {
+    /// A money field sent as a JSON number
+    pub fn numeric_amount(field: impl Into<String>) -> Self {
+        Self {
+            field: field.into(),
+            use_instead: "a decimal string, e.g. \"1000000\"",
+        }
+    }
+
+    /// A commitment blinding factor sent as `nonce`
+    pub fn nonce_as_blinding(field: impl Into<String>) -> Self {
+        Self {
+            field: field.into(),
+            use_instead: "blinding_factor",
+        }
+    }
+
+    /// Counter name, without indices: `payments[3].amount` counts as
+    /// `payments[].amount`
+    fn usage_name(&self) -> String {
+        let mut name = String::with_capacity(self.field.len());
+        let mut in_index = false;
+        for c in self.field.chars() {
+            match c {
+                '[' => {
+                    in_index = true;
+                    name.push('[');
+                }
+                ']' => {
+                    in_index = false;
+                    name.push(']');
+                }
+                _ if in_index => {}
+                _ => name.push(c),
+            }
+        }
+        name
+    }
+}
+
+/// A `u64` money field that also remembers whether it was sent as a JSON
+/// number (deprecated) rather than a decimal string
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LenientU64 {
+    pub value: u64,
+    pub numeric: bool,
+}
+
+impl<'de> Deserialize<'de> for LenientU64 {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(LenientVisitor)
+    }
+}
+
+struct LenientVisitor;
+
+impl<'de> Visitor<'de> for LenientVisitor {
+    type Value = LenientU64;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a decimal string or an unsigned 64-bit integer")
+    }
+
+    fn visit_u64<E: de::Error>(self, value: u64) -> Result<LenientU64, E> {
+        Ok(LenientU64 { value, numeric: true })
+    }
+
+    fn visit_i64<E: de::Error>(self, value: i64) -> Result<LenientU64, E> {
+        let value = u64::try_from(value).map_err(|_| E::invalid_value(de::Unexpected::Signed(value), &self))?;
+        Ok(LenientU64 { value, numeric: true })
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<LenientU64, E> {
+        if value.is_empty() || !value.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(E::invalid_value(de::Unexpected::Str(value), &self));
+        }
+        let value = value
+            .parse()
+            .map_err(|_| E::invalid_value(de::Unexpected::Str(value), &self))?;
+        Ok(LenientU64 { value, numeric: false })
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct KeyUsage {
+    pub key_id: String,
+    /// Effective setting for this key
+    pub strict_mode: bool,
+    /// Deprecated uses by field, including rejected requests
+    pub deprecated_fields: BTreeMap<String, u64>,
+    pub total: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StrictModeState {
+    /// Applies to keys without an override and to requests without a key
+    pub default_strict: bool,
+    pub overrides: BTreeMap<String, bool>,
+    /// Keys with any deprecated usage, most usage first
+    pub usage: Vec<KeyUsage>,
+}
+
+/// Per-key strict mode settings and deprecated-format counters, seeded
+/// from `STRICT_MODE_*` and changed through the admin API
+pub struct StrictMode {
+    default_strict: bool,
+    overrides: RwLock<BTreeMap<String, bool>>,
+    usage: Mutex<BTreeMap<String, BTreeMap<String, u64>>>,
+}
+
+impl StrictMode {
+    pub fn new(default_strict: bool, strict_keys: &[String], legacy_keys: &[String]) -> Self {
+        let mut overrides = BTreeMap::new();
+        for key in strict_keys {
+            overrides.insert(key.clone(), true);
+        }
+        for key in legacy_keys {
+            overrides.insert(key.clone(), false);
+        }
+        Self {
+            default_strict,
+            overrides: RwLock::new(overrides),
+            usage: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    pub fn is_strict(&self, key_id: Option<&str>) -> bool {
+        key_id
+            .and_then(|key| self.overrides.read().unwrap().get(key).copied())
+            .unwrap_or(self.default_strict)
+    }
+
+    /// Override the default for one key
+    pub fn set(&self, key_id: &str, strict: bool) {
+        self.overrides.write().unwrap().insert(key_id.to_string(), strict);
+    }
+
+    /// Drop a key's override, returning whether it had one
+    pub fn clear(&self, key_id: &str) -> bool {
+        self.overrides.write().unwrap().remove(key_id).is_some()
+    }
+
+    /// Count the request's deprecated forms and, for a strict key, reject
+    /// it with the first of them
+    pub fn enforce(&self, key_id: Option<&str>, deprecations: &[Deprecation]) -> Result<(), ServiceError> {
+        let Some(first) = deprecations.first() else {
+            return Ok(());
+        };
+        self.record(key_id, deprecations);
+
+        if !self.is_strict(key_id) {
+            return Ok(());
+        }
+        let mut message = format!(
+            "{} uses a deprecated format; send {} instead",
+            first.field, first.use_instead
+        );
+        if deprecations.len() > 1 {
+            message.push_str(&format!(" ({} more deprecated fields)", deprecations.len() - 1));
+        }
+        Err(ServiceError::DeprecatedFormat(message))
+    }
+
+    fn record(&self, key_id: Option<&str>, deprecations: &[Deprecation]) {
+        let mut usage = self.usage.lock().unwrap();
+        let key = match key_id {
+            Some(key) if usage.contains_key(key) || usage.len() < MAX_TRACKED_KEYS => key,
+            _ => ANONYMOUS,
+        };
+        let fields = usage.entry(key.to_string()).or_default();
+        for deprecation in deprecations {
+            *fields.entry(deprecation.usage_name()).or_default() += 1;
+        }
+    }
+
+    pub fn state(&self) -> StrictModeState {
+        let overrides = self.overrides.read().unwrap().clone();
+        let mut usage: Vec<KeyUsage> = self
+            .usage
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(key, fields)| KeyUsage {
+                key_id: key.clone(),
+                strict_mode: if key == ANONYMOUS {
+                    self.default_strict
+                } else {
+                    overrides.get(key).copied().unwrap_or(self.default_strict)
+                },
+                deprecated_fields: fields.clone(),
+                total: fields.values().sum(),
+            })
+            .collect();
+        usage.sort_by(|a, b| b.total.cmp(&a.total).then_with(|| a.key_id.cmp(&b.key_id)));
+
+        StrictModeState {
+            default_strict: self.default_strict,
+            overrides,
+            usage,
+        }
+    }
+}
+
+/// Check an admin-supplied key id has the shape `key_id` produces
+pub fn parse_key_id(key_id: &str) -> Result<String, ServiceError> {
+    if key_id.len() == 16 && key_id.bytes().all(|b| b.is_ascii_hexdigit()) {
+        Ok(key_id.to_ascii_lowercase())
+    } else {
+        Err(ServiceError::InvalidInput(
+            "key_id must be 16 hex characters: the start of the SHA-256 of the API key".to_string(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::TestRequest;
+
+    #[derive(Deserialize)]
+    struct Amount {
+        amount: LenientU64,
+    }
+
+    #[test]
+    fn test_lenient_amounts_remember_their_form() {
+        let modern: Amount = serde_json::from_str(r#"{"amount":"9007199254740993"}"#).unwrap();
+        assert_eq!(modern.amount, LenientU64 { value: 9_007_199_254_740_993, numeric: false });
+
+        let legacy: Amount = serde_json::from_str(r#"{"amount":1000000}"#).unwrap();
+        assert_eq!(legacy.amount, LenientU64 { value: 1_000_000, numeric: true });
+
+        for json in [r#"{"amount":"-1"}"#, r#"{"amount":"1.5"}"#, r#"{"amount":""}"#, r#"{"amount":-1}"#] {
+            assert!(serde_json::from_str::<Amount>(json).is_err(), "{}", json);
+        }
+    }
+
+    #[test]
+    fn test_key_id() {
+        let req = TestRequest::default().insert_header(("X-API-Key", "sk_live_abc")).to_http_request();
+        let id = key_id(&req).unwrap();
+        assert_eq!(id.len(), 16);
+        assert_eq!(parse_key_id(&id).unwrap(), id);
+        assert_eq!(key_id(&TestRequest::default().to_http_request()), None);
+        assert!(parse_key_id("sk_live_abc").is_err());
+    }
+
+    #[test]
+    fn test_strict_keys_are_rejected_and_legacy_keys_counted() {
+        let (strict, legacy) = ("00000000000000aa", "00000000000000bb");
+        let mode = StrictMode::new(false, &[strict.to_string()], &[legacy.to_string()]);
+        let deprecations = [
+            Deprecation::numeric_amount("payments[0].amount"),
+            Deprecation::numeric_amount("payments[1].amount"),
+        ];
+
+        assert!(mode.enforce(Some(legacy), &deprecations).is_ok());
+        assert!(mode.enforce(None, &deprecations[..1]).is_ok());
+        match mode.enforce(Some(strict), &deprecations) {
+            Err(ServiceError::DeprecatedFormat(msg)) => {
+                assert!(msg.starts_with("payments[0].amount uses a deprecated format; send a decimal string"));
+                assert!(msg.ends_with("(1 more deprecated fields)"));
+            }
+            other => panic!("expected DeprecatedFormat, got {:?}", other),
+        }
+        // Clean requests pass either way
+        assert!(mode.enforce(Some(strict), &[]).is_ok());
+
+        let state = mode.state();
+        let totals: Vec<_> = state.usage.iter().map(|u| (u.key_id.as_str(), u.strict_mode, u.total)).collect();
+        assert_eq!(
+            totals,
+            [(strict, true, 2), (legacy, false, 2), (ANONYMOUS, false, 1)]
+        );
+        assert_eq!(state.usage[0].deprecated_fields["payments[].amount"], 2);
+    }
+
+    #[test]
+    fn test_overrides_fall_back_to_the_default() {
+        let key = "00000000000000cc";
+        let mode = StrictMode::new(true, &[], &[key.to_string()]);
+        assert!(!mode.is_strict(Some(key)));
+        assert!(mode.is_strict(None));
+        assert!(mode.is_strict(Some("00000000000000dd")));
+
+        assert!(mode.clear(key));
+        assert!(!mode.clear(key));
+        assert!(mode.is_strict(Some(key)));
+
+        let deprecation = Deprecation::nonce_as_blinding("nonce");
+        assert!(matches!(
+            mode.enforce(None, &[deprecation]),
+            Err(ServiceError::DeprecatedFormat(msg)) if msg.ends_with("send blinding_factor instead")
+        ));
+    }
+}
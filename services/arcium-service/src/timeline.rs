@@ -0,0 +1,245 @@
+//! Lifecycle timeline per computation, for support: every step from the
+//! request arriving to the caller's webhook, in one ordered list.
+//!
+//! Handlers only push events onto a channel; `run` applies them in the
+//! background, so recording never waits on a lock in the hot path. Like the
+//! computation store, timelines are kept in memory by the instance that saw
+//! the events, and the retention pass drops them with their computation.
+
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::{Mutex, RwLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+
+use crate::callback::DeliveryLogEntry;
+
+/// One step in a computation's lifecycle
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum TimelineEvent {
+    /// The queueing request arrived
+    Created { computation_type: String },
+    /// The request passed validation and is about to go to the cluster
+    Validated,
+    /// The cluster accepted it; `request_hash` is the SHA-256 of the body sent
+    Queued { request_hash: Option<String>, status: String },
+    StatusPolled { status: String },
+    StatusPollFailed { error: String },
+    CallbackReceived { status: String },
+    /// A delivery attempt to the caller's callback URL, successful or not
+    WebhookDelivered(DeliveryLogEntry),
+    SlaBreached { threshold_secs: u64, waited_secs: u64 },
+    Escalated { status: Option<String>, error: Option<String> },
+}
+
+impl TimelineEvent {
+    /// Events that can repeat for the same step, numbered by attempt
+    fn is_attempt(&self) -> bool {
+        matches!(self, Self::CallbackReceived { .. } | Self::WebhookDelivered(_))
+    }
+
+    fn same_step(&self, other: &Self) -> bool {
+        std::mem::discriminant(self) == std::mem::discriminant(other)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Entry {
+    at_ms: u64,
+    event: TimelineEvent,
+}
+
+/// An event as returned by the API, with timing relative to the others
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub struct AnnotatedEvent {
+    #[serde(flatten)]
+    pub event: TimelineEvent,
+    pub at_ms: u64,
+    pub since_previous_ms: u64,
+    pub since_created_ms: u64,
+    /// For callbacks and webhook deliveries; above 1 is a retry
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub attempt: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub struct ComputationTimeline {
+    pub computation_id: String,
+    /// From the first event to the last
+    pub total_ms: u64,
+    pub events: Vec<AnnotatedEvent>,
+}
+
+/// Lifecycle events per computation id
+pub struct Timeline {
+    entries: RwLock<HashMap<String, Vec<Entry>>>,
+    sender: UnboundedSender<(String, Entry)>,
+    receiver: Mutex<Option<UnboundedReceiver<(String, Entry)>>>,
+}
+
+impl Timeline {
+    pub fn new() -> Self {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        Self {
+            entries: RwLock::new(HashMap::new()),
+            sender,
+            receiver: Mutex::new(Some(receiver)),
+        }
+    }
+
+    /// Record an event as happening now
+    pub fn record(&self, computation_id: &str, event: TimelineEvent) {
+        self.record_at(computation_id, now_ms(), event);
+    }
+
+    /// Record an event timed before its computation id was known
+    pub fn record_at(&self, computation_id: &str, at_ms: u64, event: TimelineEvent) {
+        // Only fails once `run` has stopped, i.e. at shutdown
+        let _ = self.sender.send((computation_id.to_string(), Entry { at_ms, event }));
+    }
+
+    /// Apply recorded events until the service stops; spawned once at startup
+    pub async fn run(&self) {
+        let Some(mut receiver) = self.receiver.lock().unwrap().take() else {
+            return;
+        };
+        while let Some((computation_id, entry)) = receiver.recv().await {
+            self.entries.write().unwrap().entry(computation_id).or_default().push(entry);
+        }
+    }
+
+    /// The ordered timeline, or None if nothing was recorded for the id
+    pub fn get(&self, computation_id: &str) -> Option<ComputationTimeline> {
+        let mut entries = self.entries.read().unwrap().get(computation_id)?.clone();
+        // Stable, so events recorded in the same millisecond keep their order
+        entries.sort_by_key(|entry| entry.at_ms);
+
+        let created_ms = entries.first().map(|entry| entry.at_ms).unwrap_or_default();
+        let mut previous_ms = created_ms;
+        let mut events: Vec<AnnotatedEvent> = Vec::with_capacity(entries.len());
+        for Entry { at_ms, event } in entries {
+            let attempt = event.is_attempt().then(|| {
+                1 + events.iter().filter(|earlier| earlier.event.same_step(&event)).count()
+            });
+            events.push(AnnotatedEvent {
+                since_previous_ms: at_ms.saturating_sub(previous_ms),
+                since_created_ms: at_ms.saturating_sub(created_ms),
+                at_ms,
+                attempt,
+                event,
+            });
+            previous_ms = at_ms;
+        }
+        Some(ComputationTimeline {
+            computation_id: computation_id.to_string(),
+            total_ms: previous_ms.saturating_sub(created_ms),
+            events,
+        })
+    }
+
+    /// Drop timelines whose computation `keep` rejects, including events that
+    /// arrived after their computation was pruned; returns how many
+    pub fn prune(&self, keep: impl Fn(&str) -> bool) -> usize {
+        let mut entries = self.entries.write().unwrap();
+        let before = entries.len();
+        entries.retain(|computation_id, _| keep(computation_id));
+        before - entries.len()
+    }
+}
+
+impl Default for Timeline {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    fn delivery(status_code: Option<u16>) -> TimelineEvent {
+        TimelineEvent::WebhookDelivered(DeliveryLogEntry {
+            url: "https://merchant.example/hook".to_string(),
+            status_code,
+            failure_reason: None,
+            error: None,
+            attempted_at: 0,
+        })
+    }
+
+    async fn settle() {
+        actix_rt::time::sleep(Duration::from_millis(20)).await;
+    }
+
+    #[actix_rt::test]
+    async fn test_timeline_orders_events_with_durations_and_attempts() {
+        let timeline = Arc::new(Timeline::new());
+        let writer = timeline.clone();
+        actix_rt::spawn(async move { writer.run().await });
+
+        // The queueing steps are recorded after the id is known, timed earlier
+        let queued = TimelineEvent::Queued { request_hash: Some("ab".repeat(32)), status: "queued".to_string() };
+        timeline.record_at("comp-1", 1_300, queued.clone());
+        timeline.record_at("comp-1", 1_000, TimelineEvent::Created { computation_type: "payment_settlement".to_string() });
+        timeline.record_at("comp-1", 1_040, TimelineEvent::Validated);
+        timeline.record_at("comp-1", 5_000, TimelineEvent::CallbackReceived { status: "completed".to_string() });
+        timeline.record_at("comp-1", 5_250, delivery(Some(503)));
+        timeline.record_at("comp-1", 9_000, TimelineEvent::CallbackReceived { status: "completed".to_string() });
+        timeline.record_at("comp-1", 9_100, delivery(Some(200)));
+        settle().await;
+
+        let got = timeline.get("comp-1").unwrap();
+        assert_eq!(got.total_ms, 8_100);
+        let steps: Vec<(u64, u64, Option<usize>)> = got
+            .events
+            .iter()
+            .map(|e| (e.since_previous_ms, e.since_created_ms, e.attempt))
+            .collect();
+        assert_eq!(
+            steps,
+            [
+                (0, 0, None),
+                (40, 40, None),
+                (260, 300, None),
+                (3_700, 4_000, Some(1)),
+                (250, 4_250, Some(1)),
+                (3_750, 8_000, Some(2)),
+                (100, 8_100, Some(2)),
+            ]
+        );
+        assert_eq!(got.events[2].event, queued);
+
+        let json = serde_json::to_value(&got.events[4]).unwrap();
+        assert_eq!(json["event"], "webhook_delivered");
+        assert_eq!(json["status_code"], 503);
+        assert_eq!(json["attempt"], 1);
+        assert!(timeline.get("comp-2").is_none());
+    }
+
+    #[actix_rt::test]
+    async fn test_prune_drops_timelines_with_their_computation() {
+        let timeline = Arc::new(Timeline::new());
+        let writer = timeline.clone();
+        actix_rt::spawn(async move { writer.run().await });
+
+        timeline.record("kept", TimelineEvent::Validated);
+        timeline.record("pruned", TimelineEvent::Validated);
+        settle().await;
+
+        assert_eq!(timeline.prune(|id| id == "kept"), 1);
+        assert!(timeline.get("kept").is_some());
+        assert!(timeline.get("pruned").is_none());
+    }
+}
@@ -0,0 +1,796 @@
+//! Vault stats: daily payment totals, globally and per merchant.
+//!
+//! The indexer pass ingests new PaymentRecords as raw events into a tail;
+//! compaction folds the tail into per-day rollups. Reads combine rollups and
+//! tail under one lock, so a snapshot never misses or double counts a
+//! payment that is being compacted. Snapshots are cached until the next
+//! ingest. A backfill that rewrites already compacted history drops the
+//! rollups from the first affected day and re-queues that day's events, so
+//! the next compaction rebuilds them from raw events.
+//!
+//! A finalized record a backfill no longer finds was refunded, unless it is
+//! past the vault's minimum retention period: then its payer may have
+//! closed it with close_payment_record to reclaim the rent, and it stays
+//! counted (so does a payment refunded that late). A refunded payment's
+//! event is kept aside as a refund, dated by the original payment. Records
+//! are indexed at the client's default commitment and may still be rolled
+//! back: until the reorg check has seen one at `finalized`, a missing record
+//! stays counted and the check decides whether it was refunded or reorged
+//! out.
+
+use serde::Serialize;
+use solana_sdk::pubkey::Pubkey;
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, HashMap};
+use std::str::FromStr;
+use std::sync::{Arc, RwLock};
+use tracing::{info, warn};
+
+use crate::config::Config;
+use crate::error::ServiceError;
+use crate::fee_audit::{self, PaymentRecord};
+use crate::rpc::ChainClient;
+use crate::store::unix_timestamp;
+
+/// Days since 1970-01-01, UTC
+pub type Day = i64;
+
+const SECONDS_PER_DAY: i64 = 86_400;
+
+/// The vault's MIN_RETENTION_DAYS: no PaymentRecord is closed for its rent
+/// before it is this many days old
+const RETENTION_DAYS: Day = 30;
+
+/// Payment count and amounts in base units; `M` is the wire type of money
+/// fields (u64 for v1, decimal strings for v2)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub struct Totals<M = u64> {
+    pub payments: u64,
+    pub volume: M,
+    pub fees: M,
+}
+
+impl Totals {
+    fn add(&mut self, event: &PaymentEvent) {
+        self.payments += 1;
+        self.volume = self.volume.saturating_add(event.amount);
+        self.fees = self.fees.saturating_add(event.fee);
+    }
+
+    pub(crate) fn merge(&mut self, other: &Totals) {
+        self.payments += other.payments;
+        self.volume = self.volume.saturating_add(other.volume);
+        self.fees = self.fees.saturating_add(other.fees);
+    }
+
+    pub(crate) fn map<N>(&self, f: &impl Fn(u64) -> N) -> Totals<N> {
+        Totals {
+            payments: self.payments,
+            volume: f(self.volume),
+            fees: f(self.fees),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub struct DayTotals<M = u64> {
+    pub day: String,
+    #[serde(flatten)]
+    pub totals: Totals<M>,
+}
+
+/// Totals as of one ingest generation
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub struct StatsSnapshot<M = u64> {
+    /// None for vault-wide totals
+    pub merchant: Option<String>,
+    /// Bumped by every ingest and backfill; equal generations are equal snapshots
+    pub generation: u64,
+    pub last_indexed_at: Option<u64>,
+    pub totals: Totals<M>,
+    pub days: Vec<DayTotals<M>>,
+    /// Payments counted from raw events not yet compacted into rollups
+    pub unaggregated: usize,
+}
+
+impl StatsSnapshot {
+    pub fn map<N>(&self, f: impl Fn(u64) -> N) -> StatsSnapshot<N> {
+        StatsSnapshot {
+            merchant: self.merchant.clone(),
+            generation: self.generation,
+            last_indexed_at: self.last_indexed_at,
+            totals: self.totals.map(&f),
+            days: self
+                .days
+                .iter()
+                .map(|d| DayTotals { day: d.day.clone(), totals: d.totals.map(&f) })
+                .collect(),
+            unaggregated: self.unaggregated,
+        }
+    }
+}
+
+/// A merchant whose rollup for the checked day differs from its raw events
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub struct MerchantMismatch {
+    pub merchant: String,
+    pub rollup: Totals,
+    pub recomputed: Totals,
+}
+
+/// One day's rollups recomputed from raw events
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub struct ConsistencyReport {
+    pub day: String,
+    pub consistent: bool,
+    pub rollup: Totals,
+    pub recomputed: Totals,
+    pub mismatches: Vec<MerchantMismatch>,
+    /// Events of this day still in the tail, excluded from both sides
+    pub unaggregated: usize,
+}
+
+/// What a backfill changed
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub struct BackfillReport {
+    pub added: usize,
+    pub removed: usize,
+    pub changed: usize,
+    /// First day whose rollups were dropped for rebuilding
+    pub invalidated_from: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct PaymentEvent {
+    merchant: Pubkey,
+    amount: u64,
+    fee: u64,
+    day: Day,
+    /// Folded into the rollups rather than waiting in the tail
+    aggregated: bool,
+    payment_id: [u8; 32],
+    /// Slot of the read that first indexed the record
+    slot: u64,
+    /// Seen at `finalized`, so it can no longer be reorged out
+    finalized: bool,
+}
+
+impl PaymentEvent {
+    fn new(record: &PaymentRecord, slot: u64) -> Self {
+        Self {
+            merchant: record.merchant,
+            amount: record.amount,
+            fee: record.fee,
+            day: record.timestamp.div_euclid(SECONDS_PER_DAY),
+            aggregated: false,
+            payment_id: record.payment_id,
+            slot,
+            finalized: false,
+        }
+    }
+}
+
+/// A payment whose record was rolled back before it finalized
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub struct ReorgedPayment {
+    /// PaymentRecord address
+    pub record: String,
+    /// Hex of the 32-byte payment id
+    pub payment_id: String,
+    pub merchant: String,
+    pub amount: u64,
+    pub fee: u64,
+    pub day: String,
+    pub indexed_slot: u64,
+    pub detected_at: u64,
+}
+
+#[derive(Default)]
+struct State {
+    /// Every ingested PaymentRecord, by account address
+    events: HashMap<Pubkey, PaymentEvent>,
+    /// Ingested but not yet compacted
+    tail: Vec<Pubkey>,
+    global: BTreeMap<Day, Totals>,
+    merchants: HashMap<Pubkey, BTreeMap<Day, Totals>>,
+    /// Events whose record disappeared in a backfill
+    refunded: Vec<PaymentEvent>,
+    /// Events whose record was rolled back before finalizing
+    reorged: Vec<ReorgedPayment>,
+    generation: u64,
+    last_indexed_at: Option<u64>,
+}
+
+impl State {
+    fn rollups(&self, merchant: Option<&Pubkey>) -> Option<&BTreeMap<Day, Totals>> {
+        match merchant {
+            Some(merchant) => self.merchants.get(merchant),
+            None => Some(&self.global),
+        }
+    }
+
+    /// Drop rollups from `day` on and move their events back to the tail
+    fn invalidate_from(&mut self, day: Day) {
+        self.global.split_off(&day);
+        for rollups in self.merchants.values_mut() {
+            rollups.split_off(&day);
+        }
+        self.merchants.retain(|_, rollups| !rollups.is_empty());
+        for (key, event) in self.events.iter_mut() {
+            if event.aggregated && event.day >= day {
+                event.aggregated = false;
+                self.tail.push(*key);
+            }
+        }
+    }
+
+    /// Drop an event, rebuilding the rollups it was folded into
+    fn remove(&mut self, key: &Pubkey) -> Option<PaymentEvent> {
+        let event = self.events.get(key)?;
+        if event.aggregated {
+            self.invalidate_from(event.day);
+        }
+        self.tail.retain(|k| k != key);
+        self.generation += 1;
+        self.events.remove(key)
+    }
+}
+
+#[derive(Default)]
+struct SnapshotCache {
+    generation: u64,
+    snapshots: HashMap<Option<Pubkey>, Arc<StatsSnapshot>>,
+}
+
+pub struct VaultStats {
+    program_id: Pubkey,
+    state: RwLock<State>,
+    cache: RwLock<SnapshotCache>,
+}
+
+impl VaultStats {
+    pub fn new(config: &Config) -> Result<Self, ServiceError> {
+        let program_id = Pubkey::from_str(&config.vault_program_id)
+            .map_err(|_| ServiceError::ConfigError("VAULT_PROGRAM_ID is not a valid pubkey".to_string()))?;
+        Ok(Self::for_program(program_id))
+    }
+
+    pub(crate) fn for_program(program_id: Pubkey) -> Self {
+        Self {
+            program_id,
+            state: RwLock::new(State::default()),
+            cache: RwLock::new(SnapshotCache::default()),
+        }
+    }
+
+    /// Ingest PaymentRecords created since the last pass
+    pub async fn index(&self, chain: &ChainClient) {
+        match fetch_records(chain, &self.program_id).await {
+            Ok((records, slot)) => {
+                let ingested = self.ingest(&records, slot);
+                if ingested > 0 {
+                    info!(ingested, "Vault stats indexed payments");
+                }
+            }
+            Err(e) => warn!("Vault stats indexing failed: {}", e),
+        }
+    }
+
+    /// Re-read every PaymentRecord and replace the raw events with them
+    pub async fn backfill(&self, chain: &ChainClient) -> Result<BackfillReport, ServiceError> {
+        let (records, slot) = fetch_records(chain, &self.program_id).await?;
+        let report = self.replace(&records, slot, unix_timestamp() as i64);
+        info!(
+            added = report.added,
+            removed = report.removed,
+            changed = report.changed,
+            invalidated_from = ?report.invalidated_from,
+            "Vault stats backfilled"
+        );
+        Ok(report)
+    }
+
+    /// Add records not seen before to the tail, returning how many were new
+    pub(crate) fn ingest(&self, records: &[(Pubkey, PaymentRecord)], slot: u64) -> usize {
+        let mut state = self.state.write().unwrap();
+        let mut ingested = 0;
+        for (key, record) in records {
+            if !state.events.contains_key(key) {
+                state.events.insert(*key, PaymentEvent::new(record, slot));
+                state.tail.push(*key);
+                ingested += 1;
+            }
+        }
+        state.last_indexed_at = Some(unix_timestamp());
+        if ingested > 0 {
+            state.generation += 1;
+        }
+        ingested
+    }
+
+    /// Replace the raw events with `records`, invalidating rollups of any
+    /// day where an already compacted event changed or disappeared. Missing
+    /// records that never finalized are left to the reorg check; those past
+    /// the retention period on `now` are kept as they are.
+    pub(crate) fn replace(&self, records: &[(Pubkey, PaymentRecord)], slot: u64, now: i64) -> BackfillReport {
+        let incoming: HashMap<Pubkey, PaymentEvent> =
+            records.iter().map(|(key, record)| (*key, PaymentEvent::new(record, slot))).collect();
+        let closable_before = now.div_euclid(SECONDS_PER_DAY) - RETENTION_DAYS;
+        let closed = |key: &Pubkey, event: &PaymentEvent| {
+            event.finalized && event.day < closable_before && !incoming.contains_key(key)
+        };
+
+        let mut state = self.state.write().unwrap();
+        let mut report = BackfillReport::default();
+        let mut rewritten_from: Option<Day> = None;
+        for (key, event) in &state.events {
+            let rewritten = match incoming.get(key) {
+                None if !event.finalized || closed(key, event) => false,
+                None => {
+                    report.removed += 1;
+                    true
+                }
+                Some(new) if (new.merchant, new.amount, new.fee, new.day) != (event.merchant, event.amount, event.fee, event.day) => {
+                    report.changed += 1;
+                    true
+                }
+                Some(_) => false,
+            };
+            if rewritten && event.aggregated {
+                rewritten_from = Some(rewritten_from.map_or(event.day, |d| d.min(event.day)));
+            }
+        }
+        report.added = incoming.keys().filter(|key| !state.events.contains_key(key)).count();
+
+        if let Some(day) = rewritten_from {
+            state.invalidate_from(day);
+            report.invalidated_from = Some(format_day(day));
+        }
+
+        // Everything unaggregated is rebuilt from the incoming records,
+        // keeping what the reorg check already knows about each one
+        let State { events, tail, refunded, .. } = &mut *state;
+        let awaiting_check = |key: &Pubkey, event: &PaymentEvent| !event.finalized && !incoming.contains_key(key);
+        refunded.extend(
+            events
+                .iter()
+                .filter(|(key, event)| {
+                    !incoming.contains_key(key) && !awaiting_check(key, event) && !closed(key, event)
+                })
+                .map(|(_, event)| PaymentEvent { aggregated: false, ..event.clone() }),
+        );
+        let known: HashMap<Pubkey, (u64, bool)> =
+            events.iter().map(|(key, event)| (*key, (event.slot, event.finalized))).collect();
+        events.retain(|key, event| {
+            (event.aggregated && incoming.contains_key(key)) || awaiting_check(key, event) || closed(key, event)
+        });
+        tail.clear();
+        tail.extend(events.iter().filter(|(_, event)| !event.aggregated).map(|(key, _)| *key));
+        for (key, mut event) in incoming {
+            if let Entry::Vacant(vacant) = events.entry(key) {
+                if let Some(&(slot, finalized)) = known.get(&key) {
+                    (event.slot, event.finalized) = (slot, finalized);
+                }
+                vacant.insert(event);
+                tail.push(key);
+            }
+        }
+
+        state.last_indexed_at = Some(unix_timestamp());
+        state.generation += 1;
+        report
+    }
+
+    /// Fold the tail into the rollups. Snapshots don't change, so the cache
+    /// stays valid.
+    pub fn compact(&self) -> usize {
+        let mut state = self.state.write().unwrap();
+        let tail = std::mem::take(&mut state.tail);
+        for key in &tail {
+            let Some(event) = state.events.get_mut(key) else {
+                continue;
+            };
+            event.aggregated = true;
+            let event = event.clone();
+            state.global.entry(event.day).or_default().add(&event);
+            state
+                .merchants
+                .entry(event.merchant)
+                .or_default()
+                .entry(event.day)
+                .or_default()
+                .add(&event);
+        }
+        tail.len()
+    }
+
+    /// Vault-wide totals, or one merchant's
+    pub fn snapshot(&self, merchant: Option<Pubkey>) -> Arc<StatsSnapshot> {
+        let state = self.state.read().unwrap();
+        if let Some(snapshot) = self.cached(state.generation, &merchant) {
+            return snapshot;
+        }
+
+        let mut days = state.rollups(merchant.as_ref()).cloned().unwrap_or_default();
+        let mut unaggregated = 0;
+        for event in state.tail.iter().filter_map(|key| state.events.get(key)) {
+            if merchant.is_none_or(|m| m == event.merchant) {
+                days.entry(event.day).or_default().add(event);
+                unaggregated += 1;
+            }
+        }
+
+        let mut totals = Totals::default();
+        for day in days.values() {
+            totals.merge(day);
+        }
+        let snapshot = Arc::new(StatsSnapshot {
+            merchant: merchant.map(|m| m.to_string()),
+            generation: state.generation,
+            last_indexed_at: state.last_indexed_at,
+            totals,
+            days: days
+                .into_iter()
+                .map(|(day, totals)| DayTotals { day: format_day(day), totals })
+                .collect(),
+            unaggregated,
+        });
+
+        // Only cache merchants with payments, so lookups of arbitrary keys can't grow it
+        if totals.payments > 0 || merchant.is_none() {
+            let mut cache = self.cache.write().unwrap();
+            if cache.generation != state.generation {
+                cache.generation = state.generation;
+                cache.snapshots.clear();
+            }
+            cache.snapshots.insert(merchant, snapshot.clone());
+        }
+        snapshot
+    }
+
+    fn cached(&self, generation: u64, merchant: &Option<Pubkey>) -> Option<Arc<StatsSnapshot>> {
+        let cache = self.cache.read().unwrap();
+        if cache.generation != generation {
+            return None;
+        }
+        cache.snapshots.get(merchant).cloned()
+    }
+
+    /// A merchant's refunded payments made on days `first..=last`
+    pub fn refunds(&self, merchant: &Pubkey, first: Day, last: Day) -> Totals {
+        let state = self.state.read().unwrap();
+        let mut totals = Totals::default();
+        for event in state.refunded.iter().filter(|e| e.merchant == *merchant && (first..=last).contains(&e.day)) {
+            totals.add(event);
+        }
+        totals
+    }
+
+    /// Records not yet seen at `finalized`, with the slot each was indexed at
+    pub fn unfinalized(&self) -> Vec<(Pubkey, u64)> {
+        let state = self.state.read().unwrap();
+        let mut pending: Vec<(Pubkey, u64)> = state
+            .events
+            .iter()
+            .filter(|(_, event)| !event.finalized)
+            .map(|(key, event)| (*key, event.slot))
+            .collect();
+        pending.sort_by_key(|&(key, slot)| (slot, key));
+        pending
+    }
+
+    pub fn mark_finalized(&self, keys: &[Pubkey]) {
+        let mut state = self.state.write().unwrap();
+        for key in keys {
+            if let Some(event) = state.events.get_mut(key) {
+                event.finalized = true;
+            }
+        }
+    }
+
+    /// A record that closed before the check saw it finalized, with
+    /// transactions on chain: it was refunded, not reorged
+    pub fn mark_refunded(&self, key: &Pubkey) -> bool {
+        let mut state = self.state.write().unwrap();
+        let Some(event) = state.remove(key) else {
+            return false;
+        };
+        state.refunded.push(PaymentEvent { aggregated: false, ..event });
+        true
+    }
+
+    /// Drop a payment that was rolled back, rebuilding the rollups of its
+    /// day so totals and statements no longer include it
+    pub fn revert(&self, key: &Pubkey, now: u64) -> Option<ReorgedPayment> {
+        let mut state = self.state.write().unwrap();
+        let event = state.remove(key)?;
+        let reorged = ReorgedPayment {
+            record: key.to_string(),
+            payment_id: hex::encode(event.payment_id),
+            merchant: event.merchant.to_string(),
+            amount: event.amount,
+            fee: event.fee,
+            day: format_day(event.day),
+            indexed_slot: event.slot,
+            detected_at: now,
+        };
+        state.reorged.push(reorged.clone());
+        warn!(record = %key, slot = event.slot, "Vault stats dropped a reorged payment");
+        Some(reorged)
+    }
+
+    /// Payments dropped as reorged, oldest first
+    pub fn reorged(&self) -> Vec<ReorgedPayment> {
+        self.state.read().unwrap().reorged.clone()
+    }
+
+    /// Recompute one day from its compacted raw events and diff the result
+    /// against the rollups
+    pub fn check_day(&self, day: Day) -> ConsistencyReport {
+        let state = self.state.read().unwrap();
+
+        let mut recomputed = Totals::default();
+        let mut by_merchant: HashMap<Pubkey, Totals> = HashMap::new();
+        let mut unaggregated = 0;
+        for event in state.events.values().filter(|e| e.day == day) {
+            if !event.aggregated {
+                unaggregated += 1;
+                continue;
+            }
+            recomputed.add(event);
+            by_merchant.entry(event.merchant).or_default().add(event);
+        }
+
+        let rollup = state.global.get(&day).copied().unwrap_or_default();
+        let mut mismatches = Vec::new();
+        let rolled_up = state
+            .merchants
+            .iter()
+            .filter_map(|(merchant, days)| days.get(&day).map(|totals| (*merchant, *totals)));
+        let mut seen = Vec::new();
+        for (merchant, totals) in rolled_up {
+            let expected = by_merchant.get(&merchant).copied().unwrap_or_default();
+            if totals != expected {
+                mismatches.push(MerchantMismatch { merchant: merchant.to_string(), rollup: totals, recomputed: expected });
+            }
+            seen.push(merchant);
+        }
+        for (merchant, expected) in by_merchant.iter().filter(|(m, _)| !seen.contains(m)) {
+            mismatches.push(MerchantMismatch {
+                merchant: merchant.to_string(),
+                rollup: Totals::default(),
+                recomputed: *expected,
+            });
+        }
+        mismatches.sort_by(|a, b| a.merchant.cmp(&b.merchant));
+
+        let consistent = rollup == recomputed && mismatches.is_empty();
+        if !consistent {
+            warn!(day = %format_day(day), mismatches = mismatches.len(), "Vault stats rollup is inconsistent");
+        }
+        ConsistencyReport {
+            day: format_day(day),
+            consistent,
+            rollup,
+            recomputed,
+            mismatches,
+            unaggregated,
+        }
+    }
+}
+
+/// Every PaymentRecord at the default commitment, and a slot the read is
+/// at least as recent as
+async fn fetch_records(
+    chain: &ChainClient,
+    program_id: &Pubkey,
+) -> Result<(Vec<(Pubkey, PaymentRecord)>, u64), ServiceError> {
+    let commitment = chain.default_commitment();
+    let slot = chain.slot(commitment).await?;
+    let records = fee_audit::fetch_payment_records(chain, program_id, commitment).await?;
+    Ok((records, slot))
+}
+
+/// `YYYY-MM-DD` of a day number (civil_from_days)
+pub fn format_day(day: Day) -> String {
+    let z = day + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = doy - (153 * mp + 2) / 5 + 1;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 };
+    let y = yoe + era * 400 + if m <= 2 { 1 } else { 0 };
+    format!("{:04}-{:02}-{:02}", y, m, d)
+}
+
+/// Day number of a `YYYY-MM-DD` date (days_from_civil)
+pub fn parse_day(date: &str) -> Option<Day> {
+    let mut parts = date.splitn(3, '-').map(|p| p.parse::<i64>().ok());
+    let (y, m, d) = (parts.next()??, parts.next()??, parts.next()??);
+    if !(1..=12).contains(&m) || !(1..=31).contains(&d) {
+        return None;
+    }
+    let y = if m <= 2 { y - 1 } else { y };
+    let era = y.div_euclid(400);
+    let yoe = y.rem_euclid(400);
+    let doy = (153 * ((m + 9) % 12) + 2) / 5 + d - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let day = era * 146_097 + doe - 719_468;
+    // Rejects dates like 2026-02-30 that roll over into the next month
+    (format_day(day) == date).then_some(day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 2025-10-09 00:00:00 UTC
+    const DAY_START: i64 = 1_759_968_000;
+
+    fn record(merchant: u8, amount: u64, day_offset: i64) -> (Pubkey, PaymentRecord) {
+        let record = PaymentRecord {
+            payment_id: [0; 32],
+            merchant: Pubkey::new_from_array([merchant; 32]),
+            amount,
+            fee: amount / 200,
+            timestamp: DAY_START + day_offset * SECONDS_PER_DAY + 3_600,
+            mint: None,
+            exact_net: false,
+        };
+        (Pubkey::new_unique(), record)
+    }
+
+    #[test]
+    fn test_snapshot_is_the_same_before_and_after_compaction() {
+        let stats = VaultStats::for_program(Pubkey::new_unique());
+        let records = [record(1, 1_000_000, 0), record(2, 2_000_000, 0), record(1, 4_000_000, 1)];
+        assert_eq!(stats.ingest(&records, 100), 3);
+        assert_eq!(stats.ingest(&records, 100), 0);
+
+        let before = stats.snapshot(None);
+        assert_eq!(before.unaggregated, 3);
+        assert_eq!(before.totals, Totals { payments: 3, volume: 7_000_000, fees: 35_000 });
+        assert_eq!(before.days[0].day, "2025-10-09");
+
+        assert_eq!(stats.compact(), 3);
+        // Still served from cache: compaction doesn't change the totals
+        assert!(Arc::ptr_eq(&before, &stats.snapshot(None)));
+        stats.cache.write().unwrap().snapshots.clear();
+        let after = stats.snapshot(None);
+        assert_eq!(after.unaggregated, 0);
+        assert_eq!((after.totals, &after.days), (before.totals, &before.days));
+
+        let merchant = stats.snapshot(Some(Pubkey::new_from_array([1; 32])));
+        assert_eq!(merchant.totals.volume, 5_000_000);
+        assert_eq!(merchant.days.len(), 2);
+
+        // A new payment invalidates the cached snapshot
+        stats.ingest(&[record(2, 500_000, 1)], 100);
+        let latest = stats.snapshot(None);
+        assert_eq!(latest.generation, before.generation + 1);
+        assert_eq!((latest.totals.payments, latest.unaggregated), (4, 1));
+    }
+
+    #[test]
+    fn test_backfill_rebuilds_rewritten_days() {
+        let stats = VaultStats::for_program(Pubkey::new_unique());
+        let records = vec![record(1, 1_000_000, 0), record(1, 2_000_000, 1), record(2, 3_000_000, 2)];
+        stats.ingest(&records, 100);
+        stats.mark_finalized(&records.iter().map(|(key, _)| *key).collect::<Vec<_>>());
+        stats.compact();
+
+        // Day 1's payment is gone and a late day 0 payment appeared
+        let mut rewritten = vec![records[0].clone(), records[2].clone(), record(2, 8_000_000, 0)];
+        rewritten[1].1.amount = 3_500_000;
+        let report = stats.replace(&rewritten, 200, DAY_START + 3 * SECONDS_PER_DAY);
+        assert_eq!((report.added, report.removed, report.changed), (1, 1, 1));
+        assert_eq!(report.invalidated_from.as_deref(), Some("2025-10-10"));
+        let day = DAY_START / SECONDS_PER_DAY;
+        let refunded = stats.refunds(&Pubkey::new_from_array([1; 32]), day, day + 2);
+        assert_eq!(refunded, Totals { payments: 1, volume: 2_000_000, fees: 10_000 });
+
+        let snapshot = stats.snapshot(None);
+        assert_eq!(snapshot.totals, Totals { payments: 3, volume: 12_500_000, fees: 60_000 });
+        assert_eq!(snapshot.days.len(), 2);
+        assert_eq!(snapshot.unaggregated, 2);
+
+        stats.compact();
+        assert_eq!(stats.snapshot(None).totals, snapshot.totals);
+        for day in 0..3 {
+            assert!(stats.check_day(DAY_START / SECONDS_PER_DAY + day).consistent);
+        }
+    }
+
+    #[test]
+    fn test_unfinalized_records_are_reverted_not_refunded() {
+        let stats = VaultStats::for_program(Pubkey::new_unique());
+        let records = [record(1, 1_000_000, 0), record(1, 2_000_000, 1)];
+        stats.ingest(&records[..1], 100);
+        stats.ingest(&records[1..], 150);
+        stats.mark_finalized(&[records[0].0]);
+        stats.compact();
+        assert_eq!(stats.unfinalized(), [(records[1].0, 150)]);
+
+        // A backfill that misses the unfinalized record leaves it for the reorg check
+        let report = stats.replace(&records[..1], 200, DAY_START + 2 * SECONDS_PER_DAY);
+        assert_eq!((report.removed, report.invalidated_from), (0, None));
+        assert_eq!(stats.snapshot(None).totals.payments, 2);
+        assert_eq!(stats.unfinalized(), [(records[1].0, 150)]);
+
+        let reverted = stats.revert(&records[1].0, 1_000).unwrap();
+        assert_eq!((reverted.amount, reverted.day.as_str(), reverted.indexed_slot), (2_000_000, "2025-10-10", 150));
+        assert_eq!(stats.revert(&records[1].0, 1_000), None);
+        assert_eq!(stats.reorged(), [reverted]);
+
+        let day = DAY_START / SECONDS_PER_DAY;
+        let snapshot = stats.snapshot(None);
+        assert_eq!(snapshot.totals, Totals { payments: 1, volume: 1_000_000, fees: 5_000 });
+        assert_eq!(stats.refunds(&Pubkey::new_from_array([1; 32]), day, day + 2).payments, 0);
+        stats.compact();
+        assert!(stats.check_day(day + 1).consistent);
+        assert!(stats.unfinalized().is_empty());
+    }
+
+    #[test]
+    fn test_records_closed_after_retention_stay_counted() {
+        let stats = VaultStats::for_program(Pubkey::new_unique());
+        let records = [record(1, 1_000_000, 0), record(1, 2_000_000, 1), record(1, 4_000_000, 40)];
+        stats.ingest(&records, 100);
+        stats.mark_finalized(&records.iter().map(|(key, _)| *key).collect::<Vec<_>>());
+        stats.compact();
+
+        // On day 31 only day 0's payment is past retention; day 1's was refunded
+        let report = stats.replace(&records[2..], 200, DAY_START + 31 * SECONDS_PER_DAY);
+        assert_eq!((report.removed, report.invalidated_from.as_deref()), (1, Some("2025-10-10")));
+        let day = DAY_START / SECONDS_PER_DAY;
+        let refunded = stats.refunds(&Pubkey::new_from_array([1; 32]), day, day + 40);
+        assert_eq!(refunded, Totals { payments: 1, volume: 2_000_000, fees: 10_000 });
+
+        let snapshot = stats.snapshot(None);
+        assert_eq!(snapshot.totals, Totals { payments: 2, volume: 5_000_000, fees: 25_000 });
+        stats.compact();
+        assert!(stats.check_day(day).consistent);
+    }
+
+    #[test]
+    fn test_check_day_reports_drifted_rollups() {
+        let stats = VaultStats::for_program(Pubkey::new_unique());
+        stats.ingest(&[record(1, 1_000_000, 0), record(2, 2_000_000, 0)], 100);
+        stats.compact();
+        stats.ingest(&[record(1, 5_000_000, 0)], 100);
+
+        let day = DAY_START / SECONDS_PER_DAY;
+        let clean = stats.check_day(day);
+        assert!(clean.consistent);
+        assert_eq!(clean.unaggregated, 1);
+
+        let drifted = Pubkey::new_from_array([2; 32]);
+        stats.state.write().unwrap().merchants.get_mut(&drifted).unwrap().get_mut(&day).unwrap().volume += 1;
+        let report = stats.check_day(day);
+        assert!(!report.consistent);
+        assert_eq!(report.rollup, report.recomputed);
+        assert_eq!(report.mismatches.len(), 1);
+        assert_eq!(report.mismatches[0].merchant, drifted.to_string());
+        assert_eq!(report.mismatches[0].rollup.volume, 2_000_001);
+    }
+
+    #[test]
+    fn test_day_dates() {
+        assert_eq!(format_day(0), "1970-01-01");
+        assert_eq!(format_day(DAY_START / SECONDS_PER_DAY), "2025-10-09");
+        assert_eq!(parse_day("2025-10-09"), Some(DAY_START / SECONDS_PER_DAY));
+        assert_eq!(parse_day("2024-02-29").map(format_day).as_deref(), Some("2024-02-29"));
+        assert_eq!(parse_day("2026-02-30"), None);
+        assert_eq!(parse_day("2026-1-5"), None);
+        assert_eq!(parse_day("yesterday"), None);
+    }
+}
@@ -0,0 +1,247 @@
+use serde::Serialize;
+use solana_sdk::{account::Account, pubkey, pubkey::Pubkey, system_program};
+use std::collections::HashSet;
+use std::str::FromStr;
+
+use crate::config::Config;
+use crate::error::ServiceError;
+use crate::rpc::{ChainClient, ChainContext, Commitment};
+
+pub const TOKEN_PROGRAM_ID: Pubkey = pubkey!("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA");
+pub const TOKEN_2022_PROGRAM_ID: Pubkey = pubkey!("TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb");
+pub const ASSOCIATED_TOKEN_PROGRAM_ID: Pubkey =
+    pubkey!("ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL");
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WalletStatus {
+    Ok,
+    Warning,
+    Error,
+}
+
+/// Advisory result for one payroll row
+#[derive(Debug, Clone, Serialize)]
+pub struct WalletReport {
+    pub row: usize,
+    pub employee_id: String,
+    pub wallet: String,
+    pub status: WalletStatus,
+    pub errors: Vec<String>,
+    pub warnings: Vec<String>,
+}
+
+impl WalletReport {
+    fn new(row: usize, employee_id: &str, wallet: &str, errors: Vec<String>, warnings: Vec<String>) -> Self {
+        let status = if !errors.is_empty() {
+            WalletStatus::Error
+        } else if !warnings.is_empty() {
+            WalletStatus::Warning
+        } else {
+            WalletStatus::Ok
+        };
+        Self {
+            row,
+            employee_id: employee_id.to_string(),
+            wallet: wallet.to_string(),
+            status,
+            errors,
+            warnings,
+        }
+    }
+}
+
+/// Whether any row in the report is a hard failure
+pub fn has_errors(report: &[WalletReport]) -> bool {
+    report.iter().any(|r| r.status == WalletStatus::Error)
+}
+
+/// Checks payroll destination wallets against on-chain state
+pub struct WalletValidator {
+    flagged: HashSet<Pubkey>,
+    usdc_mint: Pubkey,
+}
+
+impl WalletValidator {
+    pub fn new(config: &Config) -> Result<Self, ServiceError> {
+        let flagged = config
+            .flagged_wallets
+            .iter()
+            .map(|w| {
+                Pubkey::from_str(w).map_err(|_| {
+                    ServiceError::ConfigError(format!("Invalid flagged wallet: {}", w))
+                })
+            })
+            .collect::<Result<_, _>>()?;
+        let usdc_mint = Pubkey::from_str(&config.usdc_mint)
+            .map_err(|_| ServiceError::ConfigError("USDC_MINT is not a valid pubkey".to_string()))?;
+
+        Ok(Self { flagged, usdc_mint })
+    }
+
+    /// Mint paid out for a currency, if it is an SPL token
+    fn payout_mint(&self, currency: &str) -> Option<Pubkey> {
+        match currency.to_ascii_uppercase().as_str() {
+            "USDC" => Some(self.usdc_mint),
+            _ => None,
+        }
+    }
+
+    /// Validate `(employee_id, wallet)` rows, returning one report per row
+    pub async fn validate(
+        &self,
+        chain: &ChainClient,
+        rows: &[(&str, &str)],
+        currency: &str,
+        commitment: Commitment,
+    ) -> Result<(Vec<WalletReport>, ChainContext), ServiceError> {
+        let mut context = ChainContext::new(commitment);
+        let parsed: Vec<Option<Pubkey>> = rows
+            .iter()
+            .map(|(_, wallet)| Pubkey::from_str(wallet).ok())
+            .collect();
+        let keys: Vec<Pubkey> = parsed.iter().flatten().copied().collect();
+
+        let accounts = chain.get_accounts(&keys, &mut context).await?;
+
+        let mint = self.payout_mint(currency);
+        let holdings = match mint {
+            Some(mint) => {
+                let atas: Vec<Pubkey> = keys
+                    .iter()
+                    .map(|key| associated_token_address(key, &mint))
+                    .collect();
+                Some(chain.get_accounts(&atas, &mut context).await?)
+            }
+            None => None,
+        };
+
+        let mut reports = Vec::with_capacity(rows.len());
+        let mut index = 0;
+        for (row, ((employee_id, wallet), key)) in rows.iter().zip(&parsed).enumerate() {
+            let Some(key) = key else {
+                reports.push(WalletReport::new(
+                    row,
+                    employee_id,
+                    wallet,
+                    vec!["wallet is not a valid base58 public key".to_string()],
+                    Vec::new(),
+                ));
+                continue;
+            };
+
+            let account = accounts[index].as_ref();
+            let has_history = match account {
+                Some(_) => true,
+                None => chain.has_history(key, commitment).await?,
+            };
+            let holds_mint = holdings.as_ref().map(|h| h[index].is_some());
+            index += 1;
+
+            let (errors, warnings) =
+                classify(account, has_history, self.flagged.contains(key), holds_mint, currency);
+            reports.push(WalletReport::new(row, employee_id, wallet, errors, warnings));
+        }
+
+        Ok((reports, context))
+    }
+}
+
+fn associated_token_address(wallet: &Pubkey, mint: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[wallet.as_ref(), TOKEN_PROGRAM_ID.as_ref(), mint.as_ref()],
+        &ASSOCIATED_TOKEN_PROGRAM_ID,
+    )
+    .0
+}
+
+/// Split findings into hard errors and soft warnings
+fn classify(
+    account: Option<&Account>,
+    has_history: bool,
+    flagged: bool,
+    holds_mint: Option<bool>,
+    currency: &str,
+) -> (Vec<String>, Vec<String>) {
+    let mut errors = Vec::new();
+    let mut warnings = Vec::new();
+
+    if flagged {
+        errors.push("wallet is on the exchange deposit flag list".to_string());
+    }
+
+    match account {
+        None if !has_history => {
+            errors.push("account does not exist and has no transaction history".to_string());
+        }
+        None => {}
+        Some(account) if account.owner == TOKEN_PROGRAM_ID || account.owner == TOKEN_2022_PROGRAM_ID => {
+            errors.push("address is a token account, not a wallet".to_string());
+        }
+        Some(account) if account.executable => {
+            errors.push("address is a program, not a wallet".to_string());
+        }
+        Some(account) if account.owner != system_program::id() => {
+            warnings.push(format!("account is owned by program {}", account.owner));
+        }
+        Some(_) => {}
+    }
+
+    if holds_mint == Some(false) && errors.is_empty() {
+        warnings.push(format!("wallet has never held {}", currency.to_ascii_uppercase()));
+    }
+
+    (errors, warnings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn account(owner: Pubkey, executable: bool) -> Account {
+        Account {
+            lamports: 1_000_000,
+            data: Vec::new(),
+            owner,
+            executable,
+            rent_epoch: 0,
+        }
+    }
+
+    #[test]
+    fn test_classify_hard_failures() {
+        let (errors, _) = classify(None, false, false, None, "USDC");
+        assert_eq!(errors.len(), 1);
+
+        let token_account = account(TOKEN_PROGRAM_ID, false);
+        let (errors, _) = classify(Some(&token_account), true, false, Some(false), "USDC");
+        assert_eq!(errors, vec!["address is a token account, not a wallet"]);
+
+        let program = account(Pubkey::new_unique(), true);
+        let (errors, _) = classify(Some(&program), true, false, None, "USDC");
+        assert_eq!(errors.len(), 1);
+
+        let wallet = account(system_program::id(), false);
+        let (errors, _) = classify(Some(&wallet), true, true, Some(true), "USDC");
+        assert_eq!(errors.len(), 1);
+    }
+
+    #[test]
+    fn test_classify_soft_warnings() {
+        // Fresh system account with prior activity passes
+        let (errors, warnings) = classify(None, true, false, Some(true), "USDC");
+        assert!(errors.is_empty() && warnings.is_empty());
+
+        let wallet = account(system_program::id(), false);
+        let (errors, warnings) = classify(Some(&wallet), true, false, Some(false), "usdc");
+        assert!(errors.is_empty());
+        assert_eq!(warnings, vec!["wallet has never held USDC"]);
+    }
+
+    #[test]
+    fn test_report_status() {
+        let report = WalletReport::new(0, "emp_1", "w", Vec::new(), vec!["w".to_string()]);
+        assert_eq!(report.status, WalletStatus::Warning);
+        assert!(!has_errors(&[report]));
+    }
+}
@@ -0,0 +1,56 @@
+use serde::Serialize;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::Instant;
+use tracing::info;
+
+use crate::config::Config;
+use crate::error::ServiceError;
+use crate::mpc::MpcClient;
+
+/// Outcome of a warmup run
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub struct WarmupReport {
+    pub duration_ms: u64,
+    pub pooled_keys: usize,
+}
+
+/// Pre-traffic initialization of crypto state, reported through readiness
+pub struct Warmup {
+    hot_pubkeys: Vec<String>,
+    warmed: AtomicBool,
+    last_duration_ms: AtomicU64,
+}
+
+impl Warmup {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            hot_pubkeys: config.warmup_pubkeys.clone(),
+            warmed: AtomicBool::new(false),
+            last_duration_ms: AtomicU64::new(0),
+        }
+    }
+
+    pub fn run(&self, mpc_client: &MpcClient) -> Result<WarmupReport, ServiceError> {
+        let started = Instant::now();
+        let pooled_keys = mpc_client.warm(&self.hot_pubkeys)?;
+        let duration_ms = started.elapsed().as_millis() as u64;
+
+        self.last_duration_ms.store(duration_ms, Ordering::Relaxed);
+        self.warmed.store(true, Ordering::Release);
+        info!("Warmup finished in {}ms ({} pooled keys)", duration_ms, pooled_keys);
+
+        Ok(WarmupReport {
+            duration_ms,
+            pooled_keys,
+        })
+    }
+
+    pub fn is_warmed(&self) -> bool {
+        self.warmed.load(Ordering::Acquire)
+    }
+
+    pub fn last_duration_ms(&self) -> u64 {
+        self.last_duration_ms.load(Ordering::Relaxed)
+    }
+}